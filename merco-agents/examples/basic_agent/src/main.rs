@@ -1,6 +1,6 @@
 use merco_agents::agent::agent::{Agent, AgentLLMConfig};
 use merco_agents::task::task::Task;
-use merco_llmproxy::{LlmConfig, Provider};
+use merco_llmproxy::LlmConfig;
 use dotenv::dotenv;
 
 #[tokio::main]
@@ -11,18 +11,13 @@ async fn main() -> anyhow::Result<()> {
     println!("🤖 Basic Agent Example");
     println!("====================");
 
-    // Get API key
-    let api_key = std::env::var("OPENROUTER_API_KEY")
-        .expect("Please set OPENROUTER_API_KEY environment variable");
-
-    // Configure LLM
-    let llm_config = LlmConfig::new(Provider::OpenAI)
-        .with_base_url("https://openrouter.ai/api/v1".to_string())
-        .with_api_key(api_key);
+    // Configure LLM from MERCO_PROVIDER, MERCO_MODEL, MERCO_API_KEY and MERCO_BASE_URL
+    let llm_config = LlmConfig::from_env("MERCO")?;
+    let model = llm_config.model.clone().unwrap_or_default();
 
     let agent_llm_config = AgentLLMConfig::new(
         llm_config, 
-        "openai/gpt-4o-mini".to_string(), 
+        model, 
         0.0, 
         1000
     );
@@ -1,5 +1,5 @@
 use merco_agents::agent::agent::{Agent, AgentLLMConfig};
-use merco_agents::task::task::{Task, JsonFieldType, JsonField};
+use merco_agents::task::task::{Task, JsonFieldType, JsonField, JsonSchema, FieldConstraints};
 use merco_llmproxy::{LlmConfig, Provider};
 use dotenv::dotenv;
 
@@ -54,7 +54,7 @@ async fn main() -> anyhow::Result<()> {
         Ok(result) => {
             println!("✅ Result:");
             println!("{}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("\n🔍 Validation: JSON structure is correct!");
                 println!("📦 Pretty printed:");
                 println!("{}", serde_json::to_string_pretty(&parsed)?);
@@ -74,34 +74,57 @@ async fn main() -> anyhow::Result<()> {
                 name: "user_id".to_string(),
                 field_type: JsonFieldType::Number,
                 description: Some("Unique user identifier".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "profile".to_string(),
-                field_type: JsonFieldType::Object,
+                field_type: JsonFieldType::Object(JsonSchema::default()),
                 description: Some("Personal information object with name, age, email".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "skills".to_string(),
                 field_type: JsonFieldType::Array(Box::new(JsonFieldType::String)),
                 description: Some("Array of programming skills".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "active".to_string(),
                 field_type: JsonFieldType::Boolean,
                 description: Some("Whether the user is active".to_string()),
+                constraints: FieldConstraints::default(),
             },
         ],
         vec![
             // Optional fields
             JsonField {
                 name: "projects".to_string(),
-                field_type: JsonFieldType::Array(Box::new(JsonFieldType::Object)),
+                field_type: JsonFieldType::Array(Box::new(JsonFieldType::Object(JsonSchema::new(
+                    vec![
+                        JsonField {
+                            name: "name".to_string(),
+                            field_type: JsonFieldType::String,
+                            description: Some("Project name".to_string()),
+                            constraints: FieldConstraints::default(),
+                        },
+                    ],
+                    vec![
+                        JsonField {
+                            name: "url".to_string(),
+                            field_type: JsonFieldType::String,
+                            description: Some("Project homepage".to_string()),
+                            constraints: FieldConstraints::default(),
+                        },
+                    ],
+                )))),
                 description: Some("Array of project objects".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "experience_years".to_string(),
                 field_type: JsonFieldType::Number,
                 description: Some("Years of programming experience".to_string()),
+                constraints: FieldConstraints::default(),
             },
         ],
         false, // not strict mode - allow extra fields
@@ -111,7 +134,7 @@ async fn main() -> anyhow::Result<()> {
         Ok(result) => {
             println!("✅ Result:");
             println!("{}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("\n🔍 Validation: Complex JSON structure is correct!");
                 
                 // Validate specific fields
@@ -144,7 +167,7 @@ async fn main() -> anyhow::Result<()> {
             ("message".to_string(), JsonFieldType::String),
             ("success".to_string(), JsonFieldType::Boolean),
             ("timestamp".to_string(), JsonFieldType::Number),
-            ("data".to_string(), JsonFieldType::Object),
+            ("data".to_string(), JsonFieldType::Object(JsonSchema::default())),
         ],
         true, // strict mode - no extra fields allowed
     );
@@ -153,7 +176,7 @@ async fn main() -> anyhow::Result<()> {
         Ok(result) => {
             println!("✅ Result:");
             println!("{}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("\n🔍 Strict validation passed!");
                 println!("📦 Pretty printed:");
                 println!("{}", serde_json::to_string_pretty(&parsed)?);
@@ -183,7 +206,7 @@ async fn main() -> anyhow::Result<()> {
         Ok(result) => {
             println!("✅ Result:");
             println!("{}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("\n🔍 Financial data validation passed!");
                 
                 // Validate arrays contain numbers
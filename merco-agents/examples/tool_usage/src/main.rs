@@ -1,5 +1,5 @@
 use merco_agents::agent::agent::{Agent, AgentLLMConfig};
-use merco_agents::task::task::{Task, JsonFieldType};
+use merco_agents::task::task::{Task, JsonFieldType, JsonSchema};
 use merco_llmproxy::{LlmConfig, Provider, get_tools_by_names, merco_tool};
 use dotenv::dotenv;
 use chrono::prelude::*;
@@ -172,7 +172,7 @@ async fn main() -> anyhow::Result<()> {
         Ok(result) => {
             println!("✅ Result:");
             println!("{}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("\n🔍 JSON validation passed!");
                 println!("📦 Pretty printed:");
                 println!("{}", serde_json::to_string_pretty(&parsed)?);
@@ -205,7 +205,7 @@ async fn main() -> anyhow::Result<()> {
             ("timestamp".to_string(), JsonFieldType::String),
             ("random_number".to_string(), JsonFieldType::Number),
             ("division_result".to_string(), JsonFieldType::Number),
-            ("text_analysis".to_string(), JsonFieldType::Object),
+            ("text_analysis".to_string(), JsonFieldType::Object(JsonSchema::default())),
             ("report_generated".to_string(), JsonFieldType::Boolean),
         ],
         true, // strict mode
@@ -215,7 +215,7 @@ async fn main() -> anyhow::Result<()> {
         Ok(result) => {
             println!("✅ Result:");
             println!("{}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("\n🔍 Multi-tool JSON validation passed!");
                 println!("📦 Pretty printed:");
                 println!("{}", serde_json::to_string_pretty(&parsed)?);
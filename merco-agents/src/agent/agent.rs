@@ -1,17 +1,46 @@
-use crate::task::task::Task;
+use crate::agent::budget::{Budget, BudgetGuard};
+use crate::agent::error::AgentError;
+use crate::agent::callbacks::AgentCallbacks;
+use crate::agent::events::{AgentEvent, AgentEventStream, Events};
+use crate::agent::middleware::{EnvironmentPreamble, RequestMiddleware};
+use crate::agent::output::{AgentOutput, RunRecord};
+use crate::agent::sampling::{EffectiveSampling, SamplingParams};
+use crate::agent::translation::{Translation, translate_all};
+use crate::approval::approval::{ToolApproval, ToolApprover};
+use crate::audit::audit::{AuditSink, ToolCallRecord, hash_arguments};
+use crate::knowledge::knowledge::KnowledgeBase;
+use crate::memory::memory::{Memory, MemoryEntry};
+use crate::memory::short_term::ShortTermMemory;
+use crate::memory::vector::salient_facts;
+use crate::session::session::{AgentSession, BranchId};
+use crate::task::fact_check::{DiscrepancyAction, FactCheck, verify_claims};
+use crate::task::task::{FINAL_ANSWER_TOOL, OutputFormat, Task};
+use crate::task::validation::retry_feedback;
+use futures::{FutureExt, StreamExt};
 use merco_llmproxy::{
-    ChatMessage, CompletionKind, CompletionRequest, LlmConfig, LlmProvider, Tool,
-    execute_tool, get_provider, traits::ChatMessageRole,
+    CancellationToken, ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, ContextManager, LlmConfig, LlmContext, LlmProvider,
+    PartialJsonParser, Pricing, ProgressSink, ProviderError, ResponseFormat, StreamCollector, StreamContentDelta, Tool, ToolChoice,
+    ToolCallRequest, ToolContext, ToolError, ToolFilter, ToolOutput, ToolOutputSink, ToolRegistry, context, execute_tool_output,
+    get_provider,
 };
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+// Maximum number of shared memory entries injected into a task prompt
+const SHARED_MEMORY_RESULTS: usize = 5;
+// Maximum number of long-term memories injected into a task prompt
+const LONG_TERM_MEMORY_RESULTS: usize = 5;
+// Default bound on LLM requests in one run of the tool loop
+pub const DEFAULT_MAX_ITERATIONS: usize = 25;
 
 #[derive(Debug, Clone)]
 pub struct AgentLLMConfig {
     base_config: LlmConfig,
     model_name: String,
-    temperature: f32,
-    max_tokens: u32,
+    defaults: SamplingParams, // Agent-level sampling, overridable per task and per call
 }
 
 impl AgentLLMConfig {
@@ -21,31 +50,134 @@ impl AgentLLMConfig {
         temperature: f32,
         max_tokens: u32,
     ) -> Self {
+        Self::with_defaults(
+            base_config,
+            model_name,
+            SamplingParams::new().with_temperature(temperature).with_max_tokens(max_tokens),
+        )
+    }
+
+    pub fn with_defaults(base_config: LlmConfig, model_name: String, defaults: SamplingParams) -> Self {
         Self {
             base_config,
             model_name,
-            temperature,
-            max_tokens,
+            defaults,
         }
     }
+
+    pub fn base_config(&self) -> &LlmConfig {
+        &self.base_config
+    }
+
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    pub fn defaults(&self) -> &SamplingParams {
+        &self.defaults
+    }
+}
+
+// What the tool loop does when a tool call fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorPolicy {
+    // Send the error payload back to the model as the tool result and let it react
+    #[default]
+    Report,
+    // Re-run calls that failed with a retryable error, up to `max_attempts` calls in
+    // total, then report
+    Retry { max_attempts: u32 },
+    // Fail the task with the tool's error
+    Stop,
+}
+
+// What the tool loop does when it reaches `max_iterations` or `max_execution_time`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopLimitAction {
+    // Fail the task without retrying
+    #[default]
+    Fail,
+    // Ask the model once more, without tools, for its best answer from what it has so far
+    Summarize,
 }
 
 pub struct Agent {
+    pub id: Option<String>, // Identifies the agent in audit records
     llm_config: AgentLLMConfig,
     provider: Arc<dyn LlmProvider>,
     pub backstory: String,
     pub goals: Vec<String>,
     pub tools: Vec<Tool>,
+    pub streaming_validation: bool,
+    pub shared_memory: Option<Arc<dyn Memory>>,
+    pub short_term_memory: Option<ShortTermMemory>, // Earlier exchanges replayed on later calls
+    pub long_term_memory: Option<Arc<dyn Memory>>, // Facts from earlier tasks, recalled by relevance
+    pub knowledge: Option<Arc<KnowledgeBase>>, // Reference material retrieved for each task
+    pub final_answer_tool: bool,
+    pub middlewares: Vec<Arc<dyn RequestMiddleware>>,
+    pub callbacks: Vec<Arc<dyn AgentCallbacks>>,
+    pub context_manager: Option<ContextManager>,
+    verifier: Option<(Arc<dyn LlmProvider>, String)>, // Provider and model used for fact checks
+    pub(crate) tool_progress: Option<ProgressSink>,
+    pub(crate) tool_output: Option<ToolOutputSink>,
+    pub cancellation: Option<CancellationToken>,
+    pub translation: Option<Translation>,
+    pub tool_error_policy: ToolErrorPolicy,
+    pub max_iterations: usize, // LLM requests allowed in one run of the tool loop
+    pub max_execution_time: Option<Duration>, // Wall-clock limit for a call, checked between iterations
+    pub loop_limit_action: LoopLimitAction,
+    pub tool_registry: Option<Arc<ToolRegistry>>, // Where tool calls run; the global registry if unset
+    pub tool_filter: Option<ToolFilter>, // Namespaces/tools this agent may see and call
+    pub tool_approver: Option<Arc<dyn ToolApprover>>, // Consulted before every tool call
+    pub audit: Option<Arc<dyn AuditSink>>, // Receives a record of every tool call
+    pub pricing: Option<Pricing>, // Prices token usage for `AgentOutput::cost`
+    pub budgets: Vec<Arc<Budget>>, // Limits checked before every request; all must have room
+}
+
+// Result of a single LLM execution
+enum StreamOutcome {
+    Completed(String),
+    // Generation stopped early because the partial output already violated the schema
+    Aborted { partial: String, violation: anyhow::Error },
+    // The tool loop hit a limit; retrying would only hit it again
+    Stopped(String),
 }
 
 impl fmt::Debug for Agent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Agent")
+         .field("id", &self.id)
          .field("llm_config", &self.llm_config)
          .field("provider", &"<LlmProvider>")
          .field("backstory", &self.backstory)
          .field("goals", &self.goals)
          .field("tools", &self.tools)
+         .field("streaming_validation", &self.streaming_validation)
+         .field("shared_memory", &self.shared_memory.as_ref().map(|_| "<Memory>"))
+         .field("short_term_memory", &self.short_term_memory)
+         .field("long_term_memory", &self.long_term_memory.as_ref().map(|_| "<Memory>"))
+         .field("knowledge", &self.knowledge)
+         .field("final_answer_tool", &self.final_answer_tool)
+         .field("middlewares", &self.middlewares.len())
+         .field("callbacks", &self.callbacks.len())
+         .field("context_manager", &self.context_manager)
+         .field("verifier", &self.verifier.as_ref().map(|(_, model)| model))
+         .field("tool_progress", &self.tool_progress.as_ref().map(|_| "<ProgressSink>"))
+         .field("tool_output", &self.tool_output.as_ref().map(|_| "<ToolOutputSink>"))
+         .field("cancellation", &self.cancellation)
+         .field("translation", &self.translation)
+         .field("tool_error_policy", &self.tool_error_policy)
+         .field("max_iterations", &self.max_iterations)
+         .field("max_execution_time", &self.max_execution_time)
+         .field("loop_limit_action", &self.loop_limit_action)
+         .field("tool_registry", &self.tool_registry)
+         .field("tool_filter", &self.tool_filter)
+         .field("tool_approver", &self.tool_approver.as_ref().map(|_| "<ToolApprover>"))
+         .field("audit", &self.audit.as_ref().map(|_| "<AuditSink>"))
+         .field("pricing", &self.pricing)
+         .field("budgets", &self.budgets)
          .finish()
     }
 }
@@ -59,86 +191,463 @@ impl Agent {
     ) -> Self {
         let provider = get_provider(llm_config.base_config.clone()).unwrap();
         Self {
+            id: None,
             llm_config,
             backstory,
             goals,
             tools,
             provider,
+            streaming_validation: false,
+            shared_memory: None,
+            short_term_memory: None,
+            long_term_memory: None,
+            knowledge: None,
+            final_answer_tool: false,
+            middlewares: Vec::new(),
+            callbacks: Vec::new(),
+            context_manager: None,
+            verifier: None,
+            tool_progress: None,
+            tool_output: None,
+            cancellation: None,
+            translation: None,
+            tool_error_policy: ToolErrorPolicy::default(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            max_execution_time: None,
+            loop_limit_action: LoopLimitAction::default(),
+            tool_registry: None,
+            tool_filter: None,
+            tool_approver: None,
+            audit: None,
+            pricing: None,
+            budgets: Vec::new(),
         }
     }
 
-    pub async fn call(&self, task: Task) -> Result<String, String> {
+    pub fn llm_config(&self) -> &AgentLLMConfig {
+        &self.llm_config
+    }
+
+    // Replace the provider built from the config, e.g. with a `MockProvider` in tests
+    pub fn with_provider(mut self, provider: Arc<dyn LlmProvider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    // Provider/model used to fact-check tasks that request it. Required for such tasks: a
+    // model grading its own answer is no check, so they fail without a verifier
+    pub fn with_verifier(mut self, provider: Arc<dyn LlmProvider>, model: impl Into<String>) -> Self {
+        self.verifier = Some((provider, model.into()));
+        self
+    }
+
+    // Receive progress events from long-running tools while the agent waits on them
+    pub fn with_tool_progress(mut self, sink: ProgressSink) -> Self {
+        self.tool_progress = Some(sink);
+        self
+    }
+
+    // Receive every successful tool result, with its JSON value when the tool returned one
+    pub fn with_tool_output(mut self, sink: ToolOutputSink) -> Self {
+        self.tool_output = Some(sink);
+        self
+    }
+
+    // Abort in-flight LLM calls and streams when `token` is cancelled, e.g. on a timeout or
+    // a user-initiated stop. Without one, the caller's context token (if any) applies
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    // Let backstory, goals and tasks be written in any language: they are translated into
+    // the working language before prompting, and text answers translated back
+    pub fn with_translation(mut self, translation: Translation) -> Self {
+        self.translation = Some(translation);
+        self
+    }
+
+    // Choose whether failed tool calls are reported to the model, retried or end the task
+    pub fn with_tool_error_policy(mut self, policy: ToolErrorPolicy) -> Self {
+        self.tool_error_policy = policy;
+        self
+    }
+
+    // Bound the number of LLM requests the tool loop may make for one attempt
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    // Stop the tool loop once a call has run for `limit`; an in-flight request is not interrupted
+    pub fn with_max_execution_time(mut self, limit: Duration) -> Self {
+        self.max_execution_time = Some(limit);
+        self
+    }
+
+    // Choose between failing and asking for a best-effort answer when a loop limit is hit
+    pub fn with_loop_limit_action(mut self, action: LoopLimitAction) -> Self {
+        self.loop_limit_action = action;
+        self
+    }
+
+    // Run tool calls against `registry` instead of the global one. An agent created
+    // without tools offers every tool in the registry
+    pub fn with_tool_registry(mut self, registry: Arc<ToolRegistry>) -> Self {
+        if self.tools.is_empty() {
+            self.tools = registry.get_tools();
+            self.tools.sort_by(|a, b| a.name.cmp(&b.name));
+            if let Some(filter) = &self.tool_filter {
+                self.tools.retain(|tool| filter.permits(&tool.name));
+            }
+        }
+        self.tool_registry = Some(registry);
+        self
+    }
+
+    // Remember finished tasks and include them in later calls, so the agent can hold a
+    // conversation; the oldest exchanges are dropped beyond `max_tokens`
+    pub fn with_short_term_memory(mut self, max_tokens: usize) -> Self {
+        self.short_term_memory = Some(ShortTermMemory::new(max_tokens));
+        self
+    }
+
+    // Store salient facts of every finished task in `memory` (typically a `VectorMemory`)
+    // and recall the most relevant ones before each new task
+    pub fn with_long_term_memory(mut self, memory: Arc<dyn Memory>) -> Self {
+        self.long_term_memory = Some(memory);
+        self
+    }
+
+    // Like `with_short_term_memory`, with a memory configured by the caller, e.g. one
+    // persisted with `ShortTermMemory::with_store`
+    pub fn with_short_term_memory_from(mut self, memory: ShortTermMemory) -> Self {
+        self.short_term_memory = Some(memory);
+        self
+    }
+
+    // Add the chunks of `knowledge` most relevant to each task to its prompt
+    pub fn with_knowledge(mut self, knowledge: Arc<KnowledgeBase>) -> Self {
+        self.knowledge = Some(knowledge);
+        self
+    }
+
+    // Forget the exchanges kept by the short-term memory, if any
+    pub fn clear_memory(&self) {
+        if let Some(memory) = &self.short_term_memory {
+            memory.clear();
+        }
+    }
+
+    // Identify this agent in tool audit records
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    // Record every tool call (name, arguments hash, duration, outcome) to `sink`
+    pub fn with_audit_sink(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit = Some(sink);
+        self
+    }
+
+    // Ask `approver` before every tool call; it can approve, deny with a message for the
+    // model, or edit the arguments. Agents with file, shell or network tools should have one.
+    pub fn with_tool_approver(mut self, approver: Arc<dyn ToolApprover>) -> Self {
+        self.tool_approver = Some(approver);
+        self
+    }
+
+    // Only offer and run tools that pass `filter`, e.g. allow `fs` but deny `fs.write`.
+    // Calls to other tools are answered with an error instead of running
+    pub fn with_tool_filter(mut self, filter: ToolFilter) -> Self {
+        self.tools.retain(|tool| filter.permits(&tool.name));
+        self.tool_filter = Some(filter);
+        self
+    }
+
+    // Stream JSON task output and validate fields as they arrive, aborting on violations
+    pub fn with_streaming_validation(mut self, enabled: bool) -> Self {
+        self.streaming_validation = enabled;
+        self
+    }
+
+    // Require the final answer to be delivered through a synthetic `final_answer` tool
+    // whose parameters are the task's output schema
+    pub fn with_final_answer_tool(mut self, enabled: bool) -> Self {
+        self.final_answer_tool = enabled;
+        self
+    }
+
+    // Keep the tool loop's conversation within the model's context window; the task
+    // prompt is always preserved
+    pub fn with_context_manager(mut self, manager: ContextManager) -> Self {
+        self.context_manager = Some(manager);
+        self
+    }
+
+    // Add a request middleware; middlewares run in the order they are added
+    pub fn with_middleware(mut self, middleware: Arc<dyn RequestMiddleware>) -> Self {
+        self.middlewares.push(middleware);
+        self
+    }
+
+    // Inject the current date/time, timezone, locale and tool summary into every request
+    pub fn with_environment_preamble(self, preamble: EnvironmentPreamble) -> Self {
+        self.with_middleware(Arc::new(preamble))
+    }
+
+    // Price the model per million prompt/completion tokens, so outputs report their cost
+    pub fn with_pricing(mut self, input_per_million: f64, output_per_million: f64) -> Self {
+        self.pricing = Some(Pricing { input_per_million, output_per_million });
+        self
+    }
+
+    // Limit this agent's spending across all its calls
+    pub fn with_budget(self, budget: Budget) -> Self {
+        self.with_shared_budget(Arc::new(budget))
+    }
+
+    // Limit spending together with the other agents holding `budget`
+    pub fn with_shared_budget(mut self, budget: Arc<Budget>) -> Self {
+        self.budgets.push(budget);
+        self
+    }
+
+    // Observe requests, responses, tool calls, retries and the final result
+    pub fn with_callbacks(mut self, callbacks: Arc<dyn AgentCallbacks>) -> Self {
+        self.callbacks.push(callbacks);
+        self
+    }
+
+    // Share findings with other agents through a common memory (usually set by the Crew)
+    pub fn with_shared_memory(mut self, memory: Arc<dyn Memory>) -> Self {
+        self.shared_memory = Some(memory);
+        self
+    }
+
+    // Open connections and preload the model ahead of the first task
+    pub async fn warmup(&self) -> Result<(), String> {
+        self.provider
+            .warmup(&self.llm_config.model_name)
+            .await
+            .map_err(|e| format!("Warmup failed for model {}: {}", self.llm_config.model_name, e))
+    }
+
+    pub async fn call(&self, task: Task) -> Result<AgentOutput, AgentError> {
+        self.call_with_sampling(task, &SamplingParams::default()).await
+    }
+
+    // Run `task` expecting a value of `T`: the output schema is derived from the type,
+    // enforced and validated like any JSON task, and the answer deserialized into it
+    #[cfg(feature = "schema")]
+    pub async fn call_typed<T>(&self, task: Task) -> Result<T, AgentError>
+    where
+        T: serde::de::DeserializeOwned + schemars::JsonSchema,
+    {
+        let output = self.call(task.with_output_type::<T>()).await?.text;
+        serde_json::from_str(output.trim()).map_err(|e| {
+            AgentError::Failed(format!("Output does not deserialize into {}: {}. Raw output: {}", T::schema_name(), e, output))
+        })
+    }
+
+    // Sampling values a call would use, and which layer each came from
+    pub fn effective_sampling(&self, task: &Task, overrides: &SamplingParams) -> EffectiveSampling {
+        EffectiveSampling::resolve(&self.llm_config.defaults, &task.sampling, overrides)
+    }
+
+    // Run `task` with sampling overrides that take precedence over the task's and the agent's
+    pub async fn call_with_sampling(&self, task: Task, overrides: &SamplingParams) -> Result<AgentOutput, AgentError> {
+        self.run(task, overrides, None).await
+    }
+
+    // Like `call`, but reports tokens, tool calls and retries as they happen. The call only
+    // makes progress while the stream is polled; it ends with `Completed` or `Failed`.
+    pub fn call_stream(&self, task: Task) -> AgentEventStream<'_> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let run = async move {
+            let overrides = SamplingParams::default();
+            let result = self.run(task, &overrides, Some(&sender)).await;
+            let _ = sender.send(match result {
+                Ok(output) => AgentEvent::Completed { output: output.text },
+                Err(error) => AgentEvent::Failed { error: error.to_string() },
+            });
+        };
+        // The channel closes once `run` finishes and drops the sender, ending the stream
+        let events = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|event| (event, receiver))
+        });
+        let run = run.into_stream().filter_map(|()| futures::future::ready(None));
+        futures::stream::select(run, events).boxed()
+    }
+
+    async fn run(
+        &self,
+        task: Task,
+        overrides: &SamplingParams,
+        sender: Option<&mpsc::UnboundedSender<AgentEvent>>,
+    ) -> Result<AgentOutput, AgentError> {
+        let record = RunRecord::default();
+        let budget = BudgetGuard::new(&self.budgets, self.pricing);
+        let started = Instant::now();
+        let json_output = matches!(task.output_format, OutputFormat::Json { .. } | OutputFormat::Schema { .. });
+        let result = self.run_attempts(task, overrides, Events::new(sender, &record, &budget)).await.map(|text| {
+            let parsed_json = json_output.then(|| serde_json::from_str(text.trim()).ok()).flatten();
+            record.finish(text, parsed_json, started.elapsed())
+        });
+        self.notify(|callbacks| callbacks.on_finish(&result));
+        result.map_err(|error| match budget.exceeded() {
+            Some(exceeded) => AgentError::BudgetExceeded(exceeded),
+            None => AgentError::Failed(error),
+        })
+    }
+
+    async fn run_attempts(&self, task: Task, overrides: &SamplingParams, events: Events<'_>) -> Result<String, String> {
         const MAX_RETRIES: usize = 3;
-        
-        for attempt in 1..=MAX_RETRIES {
-            println!("Agent execution attempt {} of {}", attempt, MAX_RETRIES);
-            
-            let mut messages = vec![
-                ChatMessage::new(
-                    ChatMessageRole::System,
-                    Some(self.backstory.clone()),
-                    None,
-                    None,
-                ),
-                ChatMessage::new(
-                    ChatMessageRole::User,
-                    Some(self.goals.clone().join("\n")),
-                    None,
-                    None,
-                ),
-                ChatMessage::new(
-                    ChatMessageRole::User,
-                    Some(format!(
-                        "TASK: {}\n\nEXPECTED OUTPUT: {}\n\nOUTPUT FORMAT:\n{}",
-                        task.description,
-                        task.expected_output.as_ref().unwrap_or(&"None".to_string()),
-                        task.get_format_prompt() // Include format prompt
-                    )),
-                    None,
-                    None,
-                ),
-            ];
+
+        if task.fact_check.is_some() && self.verifier.is_none() {
+            return Err("The task requests a fact check, but the agent has no verifier (see `with_verifier`)".to_string());
+        }
+
+        // The final answer arrives as validated tool arguments, so JSON mode isn't needed then
+        let final_answer = self.final_answer_tool.then(|| task.final_answer_tool());
+        // Prefer provider-enforced JSON; dropped if the provider/model rejects it
+        let mut response_format = if final_answer.is_some() { None } else { task.response_format() };
+        // Early abort needs a streamed, tool-free JSON response to inspect as it arrives
+        let stream_validation = self.streaming_validation && self.tools.is_empty() && response_format.is_some();
+        // Inherit the caller's budget, trace id and depth when running inside a tool
+        let mut llm_context = context::current()
+            .map(|parent| parent.with_provider(self.provider.clone()))
+            .unwrap_or_else(|| LlmContext::new(self.provider.clone()))
+            .with_default_model(self.llm_config.model_name.clone());
+        if let Some(token) = &self.cancellation {
+            llm_context = llm_context.with_cancellation(token.clone());
+        }
+
+        let (task, mut messages) = match &self.translation {
+            Some(translation) => self.translated_task_messages(&llm_context, translation, task, events).await?,
+            None => {
+                let messages = self.task_messages(&task)?;
+                (task, messages)
+            }
+        };
+
+        let sampling = self.effective_sampling(&task, overrides);
+
+        // Earlier exchanges go between the agent's introduction and the new task
+        if let Some(memory) = &self.short_term_memory {
+            let at = messages.len().min(2);
+            messages.splice(at..at, memory.messages());
+        }
+
+        if let Some(knowledge) = self.retrieve_knowledge(&task).await {
+            messages.push(ChatMessage::user(knowledge));
+        }
+        if let Some(recalled) = self.recall_long_term_memory(&task).await {
+            messages.push(ChatMessage::user(recalled));
+        }
+        if let Some(recalled) = self.recall_shared_memory(&task).await {
+            messages.push(ChatMessage::user(recalled));
+        }
+        if final_answer.is_some() {
+            messages.push(ChatMessage::user(format!(
+                "When you are done, deliver your result by calling the `{}` tool instead of replying with text.",
+                FINAL_ANSWER_TOOL
+            )));
+        }
+        // Everything up to here is the task setup, which must survive trimming
+        let context_manager = self.context_manager.clone().map(|m| m.with_pinned(messages.len()));
+        let mut revisions = 0;
+        let deadline = self.max_execution_time.map(|limit| Instant::now() + limit);
+
+        // Each fact-check revision round starts with a fresh set of retries
+        let mut attempt = 0;
+        while attempt < MAX_RETRIES {
+            attempt += 1;
+            if llm_context.is_cancelled() {
+                return Err("Agent call was cancelled".to_string());
+            }
+            eprintln!("Agent execution attempt {} of {}", attempt, MAX_RETRIES);
+            events.record.start_attempt();
 
             // Execute the task with the LLM (existing loop logic)
-            let raw_result = match self.execute_with_llm(&mut messages).await {
-                Ok(result) => result,
+            let execution = if stream_validation {
+                self.execute_streaming_with_validation(&llm_context, &messages, &task, &sampling, &mut response_format, events).await
+            } else {
+                self.execute_with_llm(&llm_context, context_manager.as_ref(), &mut messages, &task, &sampling, final_answer.as_ref(), &mut response_format, deadline, events).await
+            };
+
+            let (raw_result, validation) = match execution {
+                Ok(StreamOutcome::Completed(result)) => match validate_output(&task, &result).await {
+                    Err(error) => match task.repair_output(&result) {
+                        Some(repaired) => {
+                            eprintln!("Repaired malformed output on attempt {}", attempt);
+                            (repaired, Ok(()))
+                        }
+                        None => (result, Err(error)),
+                    },
+                    validation => (result, validation),
+                },
+                Ok(StreamOutcome::Aborted { partial, violation }) => {
+                    eprintln!("Aborted generation early on attempt {}: {}", attempt, violation);
+                    (partial, Err(violation))
+                }
+                Ok(StreamOutcome::Stopped(error)) => return Err(error),
                 Err(e) => {
                     if attempt == MAX_RETRIES {
+                        if let Some(fallback) = &task.degraded
+                            && !llm_context.is_cancelled()
+                        {
+                            eprintln!("LLM execution failed after {} attempts: {}. Using degraded fallback.", MAX_RETRIES, e);
+                            events.record.mark_degraded();
+                            return fallback.resolve(&task, &e);
+                        }
                         return Err(format!("LLM execution failed after {} attempts: {}", MAX_RETRIES, e));
                     }
-                    println!("LLM execution failed on attempt {}: {}. Retrying...", attempt, e);
+                    eprintln!("LLM execution failed on attempt {}: {}. Retrying...", attempt, e);
+                    self.notify(|callbacks| callbacks.on_retry(attempt, &e));
                     continue;
                 }
             };
 
             // Validate the output
-            match task.validate_output(&raw_result) {
+            match validation {
                 Ok(()) => {
-                    println!("Output validation successful on attempt {}", attempt);
-                    return Ok(raw_result);
+                    eprintln!("Output validation successful on attempt {}", attempt);
+                    if let Some(check) = &task.fact_check
+                        && let Some(feedback) = self.fact_check(&llm_context, &task, check, &raw_result, revisions, events).await
+                    {
+                        revisions += 1;
+                        attempt = 0;
+                        messages.push(ChatMessage::assistant(Some(raw_result), None));
+                        messages.push(ChatMessage::user(feedback));
+                        continue;
+                    }
+                    self.remember_shared(&task, &raw_result).await;
+                    self.remember_long_term(&task, &raw_result).await;
+                    if let Some(memory) = &self.short_term_memory {
+                        memory.remember(task.description.clone(), raw_result.clone());
+                    }
+                    return self.localize_output(&llm_context, &task, raw_result, events).await;
                 }
-                Err(validation_error) => {
+                Err(error) => {
+                    let validation_error = error.to_string();
                     if attempt == MAX_RETRIES {
                         return Err(format!(
                             "Output validation failed after {} attempts. Last error: {}. Raw output: {}",
                             MAX_RETRIES, validation_error, raw_result
                         ));
                     }
-                    println!(
+                    eprintln!(
                         "Output validation failed on attempt {}: {}. Retrying...", 
                         attempt, validation_error
                     );
-                    
-                    // Add feedback message for retry
-                    messages.push(ChatMessage::new(
-                        ChatMessageRole::User,
-                        Some(format!(
-                            "Your previous response was invalid: {}. Please provide a corrected response that follows the required format exactly.",
-                            validation_error
-                        )),
-                        None,
-                        None,
-                    ));
+                    self.notify(|callbacks| callbacks.on_retry(attempt, &validation_error));
+                    events.emit(AgentEvent::ValidationRetry { attempt, error: validation_error.clone() });
+
+                    // Add the invalid response and feedback message for retry
+                    messages.push(ChatMessage::assistant(Some(raw_result), None));
+                    messages.push(ChatMessage::user(retry_feedback(&error, task.quote_errors)));
                 }
             }
         }
@@ -146,51 +655,999 @@ impl Agent {
         Err("Maximum retry attempts exceeded".to_string())
     }
 
+    // Verify the output's claims; returns revision feedback when the task asks for another
+    // round, otherwise discrepancies (and verifier failures) are only reported as warnings
+    async fn fact_check(
+        &self,
+        llm_context: &LlmContext,
+        task: &Task,
+        check: &FactCheck,
+        output: &str,
+        revisions: usize,
+        events: Events<'_>,
+    ) -> Option<String> {
+        // Checked before the run starts
+        let (provider, default_model) = self.verifier.as_ref()?;
+        let model = check.model.clone().unwrap_or_else(|| default_model.clone());
+
+        let verifier_context = llm_context.clone().with_provider(provider.clone());
+        let complete = async |request| self.complete_auxiliary(&verifier_context, request, events).await;
+        let discrepancies = match verify_claims(complete, &model, &task.description, output).await {
+            Ok(discrepancies) if discrepancies.is_empty() => return None,
+            Ok(discrepancies) => discrepancies,
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                return None;
+            }
+        };
+        let listed: Vec<String> = discrepancies.iter().map(|d| format!("- {}", d)).collect();
+
+        if check.action == DiscrepancyAction::Revise && revisions < check.max_revisions {
+            eprintln!("Fact check found {} discrepancies; requesting a revision", discrepancies.len());
+            return Some(format!(
+                "A fact check by {} disputed these claims in your answer:\n{}\nVerify them and provide a corrected answer in the same format.",
+                model,
+                listed.join("\n")
+            ));
+        }
+        eprintln!("Warning: fact check by {} disputed these claims:\n{}", model, listed.join("\n"));
+        None
+    }
+
+    // The system prompt, goals and task prompt that open every conversation
+    fn task_messages(&self, task: &Task) -> Result<Vec<ChatMessage>, String> {
+        Self::prompt_messages(&self.backstory, &self.goals, task)
+    }
+
+    fn prompt_messages(backstory: &str, goals: &[String], task: &Task) -> Result<Vec<ChatMessage>, String> {
+        let mut messages = vec![
+            ChatMessage::system(backstory),
+            ChatMessage::user(goals.join("\n")),
+            ChatMessage::user(format!(
+                "TASK: {}\n\nEXPECTED OUTPUT: {}\n\nOUTPUT FORMAT:\n{}",
+                task.description,
+                task.expected_output.as_ref().unwrap_or(&"None".to_string()),
+                task.get_format_prompt() // Include format prompt
+            )),
+        ];
+
+        if let Some(attachments) = task.render_attachments().map_err(|e| e.to_string())? {
+            messages.push(ChatMessage::user(format!("REFERENCE MATERIAL:\n\n{}", attachments)));
+        }
+        Ok(messages)
+    }
+
+    // Translate the backstory, goals and task text into the working language in one request,
+    // returning the translated task (used for validation and fact checks) and its prompt
+    async fn translated_task_messages(
+        &self,
+        llm_context: &LlmContext,
+        translation: &Translation,
+        mut task: Task,
+        events: Events<'_>,
+    ) -> Result<(Task, Vec<ChatMessage>), String> {
+        let model = translation.model.as_deref().unwrap_or(&self.llm_config.model_name);
+        let mut texts = vec![self.backstory.clone(), task.description.clone(), task.expected_output.clone().unwrap_or_default()];
+        texts.extend(self.goals.iter().cloned());
+
+        let complete = async |request| self.complete_auxiliary(llm_context, request, events).await;
+        let mut translated = translate_all(complete, model, &texts, &translation.working_language)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter();
+        let backstory = translated.next().unwrap_or_default();
+        task.description = translated.next().unwrap_or_default();
+        if task.expected_output.is_some() {
+            task.expected_output = translated.next();
+        } else {
+            translated.next();
+        }
+        let goals: Vec<String> = translated.collect();
+
+        let messages = Self::prompt_messages(&backstory, &goals, &task)?;
+        Ok((task, messages))
+    }
+
+    // Translate a free-text answer into the output language; structured formats (JSON,
+    // code, diffs) are returned as-is so they stay valid
+    async fn localize_output(
+        &self,
+        llm_context: &LlmContext,
+        task: &Task,
+        output: String,
+        events: Events<'_>,
+    ) -> Result<String, String> {
+        let Some(translation) = &self.translation else {
+            return Ok(output);
+        };
+        let Some(language) = &translation.output_language else {
+            return Ok(output);
+        };
+        if task.output_format != OutputFormat::Text {
+            return Ok(output);
+        }
+        let model = translation.model.as_deref().unwrap_or(&self.llm_config.model_name);
+        let complete = async |request| self.complete_auxiliary(llm_context, request, events).await;
+        translate_all(complete, model, std::slice::from_ref(&output), language)
+            .await
+            .map(|mut translated| translated.remove(0))
+            .map_err(|e| format!("Failed to translate the answer into {}: {}", language, e))
+    }
+
+    // Start a branchable session seeded with this agent's prompt for `task`
+    pub fn start_session(&self, task: &Task) -> Result<AgentSession, String> {
+        Ok(AgentSession::new(self.task_messages(task)?))
+    }
+
+    // Generate the next reply on `branch` with this agent's model settings
+    pub async fn continue_session(&self, session: &mut AgentSession, branch: BranchId) -> Result<String, String> {
+        let mut template = CompletionRequest::new(Vec::new(), self.llm_config.model_name.clone(), None, None, None);
+        EffectiveSampling::resolve(&self.llm_config.defaults, &SamplingParams::default(), &SamplingParams::default())
+            .apply(&mut template);
+        // Held to the agent's budgets like its calls
+        let budget = BudgetGuard::new(&self.budgets, self.pricing);
+        let complete = async |mut request| {
+            budget.admit(&mut request)?;
+            let response = self.provider.completion(request).await?;
+            if let Some(usage) = &response.usage {
+                budget.record(usage);
+            }
+            Ok(response)
+        };
+        session.continue_branch(branch, complete, template).await.map_err(|e| e.to_string())
+    }
+
+    // Run the request through the agent's middlewares before dispatch
+    fn apply_middlewares(&self, request: &mut CompletionRequest) -> Result<(), String> {
+        for middleware in &self.middlewares {
+            middleware.on_request(request)?;
+        }
+        Ok(())
+    }
+
+    // Look up findings from other agents relevant to this task
+    async fn recall_shared_memory(&self, task: &Task) -> Option<String> {
+        let memory = self.shared_memory.as_ref()?;
+        let entries = match memory.search(&task.description, SHARED_MEMORY_RESULTS).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Shared memory search failed: {}", e);
+                return None;
+            }
+        };
+        if entries.is_empty() {
+            return None;
+        }
+
+        let findings: Vec<String> = entries.iter().map(|entry| format!("- {}", entry.content)).collect();
+        Some(format!(
+            "Relevant findings shared by other agents in your crew:\n{}",
+            findings.join("\n")
+        ))
+    }
+
+    // Knowledge chunks relevant to `task`, formatted for the prompt
+    async fn retrieve_knowledge(&self, task: &Task) -> Option<String> {
+        let knowledge = self.knowledge.as_ref()?;
+        let chunks = match knowledge.retrieve(&task.description).await {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                eprintln!("Knowledge retrieval failed: {}", e);
+                return None;
+            }
+        };
+        if chunks.is_empty() {
+            return None;
+        }
+
+        let sections: Vec<String> = chunks
+            .iter()
+            .map(|chunk| format!("--- {} ---\n{}", chunk.label(), chunk.text))
+            .collect();
+        Some(format!("RELEVANT KNOWLEDGE:\n\n{}", sections.join("\n\n")))
+    }
+
+    // Memories from this agent's earlier tasks that relate to `task`, formatted for the prompt
+    async fn recall_long_term_memory(&self, task: &Task) -> Option<String> {
+        let memory = self.long_term_memory.as_ref()?;
+        let entries = match memory.search(&task.description, LONG_TERM_MEMORY_RESULTS).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Long-term memory search failed: {}", e);
+                return None;
+            }
+        };
+        if entries.is_empty() {
+            return None;
+        }
+
+        let memories: Vec<String> = entries.iter().map(|entry| format!("- {}", entry.content)).collect();
+        Some(format!("Things you remember from earlier tasks that may be relevant:\n{}", memories.join("\n")))
+    }
+
+    // Keep the salient facts of a successful result for later tasks
+    async fn remember_long_term(&self, task: &Task, output: &str) {
+        if let Some(memory) = &self.long_term_memory {
+            let entries = salient_facts(output)
+                .into_iter()
+                .map(|fact| MemoryEntry::new(fact, Some(task.description.clone())))
+                .collect();
+            if let Err(e) = memory.save_all(entries).await {
+                eprintln!("Failed to save to long-term memory: {}", e);
+            }
+        }
+    }
+
+    // Publish a successful result so other agents can retrieve it
+    async fn remember_shared(&self, task: &Task, output: &str) {
+        if let Some(memory) = &self.shared_memory {
+            let entry = MemoryEntry::new(output.to_string(), Some(task.description.clone()));
+            if let Err(e) = memory.save(entry).await {
+                eprintln!("Failed to save to shared memory: {}", e);
+            }
+        }
+    }
+
+    // Streams the response and checks each completed field against the task schema,
+    // dropping the stream (which stops generation) on the first unrecoverable violation.
+    async fn execute_streaming_with_validation(
+        &self,
+        llm_context: &LlmContext,
+        messages: &[ChatMessage],
+        task: &Task,
+        sampling: &EffectiveSampling,
+        response_format: &mut Option<ResponseFormat>,
+        events: Events<'_>,
+    ) -> Result<StreamOutcome, String> {
+        let mut stream = loop {
+            let mut request = CompletionRequest::new(messages.to_vec(), self.llm_config.model_name.clone(), None, None, None);
+            sampling.apply(&mut request);
+            request.response_format = response_format.clone();
+            self.apply_middlewares(&mut request)?;
+            if let Some(budget) = &llm_context.budget {
+                budget.check().map_err(|e| e.to_string())?;
+            }
+            if let Err(e) = events.budget.admit(&mut request) {
+                return Ok(StreamOutcome::Stopped(e.to_string()));
+            }
+            self.notify(|callbacks| callbacks.on_llm_request(&request));
+
+            let opened = match &llm_context.cancellation {
+                Some(token) => self.provider.completion_stream_with_cancellation(request, token).await,
+                None => self.provider.completion_stream(request).await,
+            };
+            match opened {
+                Ok(stream) => break stream,
+                Err(ProviderError::ApiError { status: 400 | 422, message })
+                    if response_format.is_some() && rejects_response_format(&message) =>
+                {
+                    eprintln!("Provider rejected response_format ({}). Falling back to prompt-based validation.", message);
+                    *response_format = None;
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        };
+
+        let mut parser = PartialJsonParser::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| e.to_string())?;
+            // Charged like a non-streamed completion: to the call's budgets and the context's
+            if let Some(usage) = &chunk.usage {
+                events.add_usage(usage);
+                if let Some(budget) = &llm_context.budget {
+                    budget.record(usage);
+                }
+            }
+            if let StreamContentDelta::Text(text) = chunk.delta {
+                events.emit(AgentEvent::Token { text: text.clone() });
+                for event in parser.push(&text) {
+                    if let Err(violation) = task.validate_partial(&event) {
+                        self.notify_streamed_response(parser.buffer());
+                        return Ok(StreamOutcome::Aborted {
+                            partial: parser.buffer().to_string(),
+                            violation,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.notify_streamed_response(parser.buffer());
+        Ok(StreamOutcome::Completed(parser.buffer().to_string()))
+    }
+
     // Extracted LLM execution logic (the original loop from call method)
-    async fn execute_with_llm(&self, messages: &mut Vec<ChatMessage>) -> Result<String, String> {
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_with_llm(
+        &self,
+        llm_context: &LlmContext,
+        context_manager: Option<&ContextManager>,
+        messages: &mut Vec<ChatMessage>,
+        task: &Task,
+        sampling: &EffectiveSampling,
+        final_answer: Option<&Tool>,
+        response_format: &mut Option<ResponseFormat>,
+        deadline: Option<Instant>,
+        events: Events<'_>,
+    ) -> Result<StreamOutcome, String> {
+        let mut tools = self.tools.clone();
+        tools.extend(final_answer.cloned());
+
+        let mut iterations = 0;
         loop {
-            let request = CompletionRequest::new(
-                messages.clone(),
-                self.llm_config.model_name.clone(),
-                Some(self.llm_config.temperature),
-                Some(self.llm_config.max_tokens),
-                Some(self.tools.clone()),
-            );
-
-            match self.provider.completion(request).await {
+            let limit = if iterations >= self.max_iterations {
+                Some(format!("Tool loop reached the limit of {} iterations", self.max_iterations))
+            } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                Some(format!("Tool loop exceeded the time limit of {:?}", self.max_execution_time.unwrap_or_default()))
+            } else {
+                None
+            };
+            if let Some(limit) = limit {
+                return match self.loop_limit_action {
+                    LoopLimitAction::Fail => Ok(StreamOutcome::Stopped(limit)),
+                    LoopLimitAction::Summarize => {
+                        eprintln!("{}. Asking for a best-effort answer.", limit);
+                        self.summarize(llm_context, messages, task, sampling, final_answer, response_format, events)
+                            .await
+                            .map(StreamOutcome::Completed)
+                    }
+                };
+            }
+            iterations += 1;
+
+            // Bound the growing tool-loop conversation before each request
+            if let Some(manager) = context_manager {
+                *messages = manager
+                    .prepare(messages, self.provider.as_ref(), &self.llm_config.model_name)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+
+            let mut request =
+                CompletionRequest::new(messages.clone(), self.llm_config.model_name.clone(), None, None, Some(tools.clone()));
+            sampling.apply(&mut request);
+            request.response_format = response_format.clone();
+            if final_answer.is_some() {
+                // Every turn must either use a real tool or deliver the answer
+                request.tool_choice = Some(ToolChoice::Required);
+            }
+            self.apply_middlewares(&mut request)?;
+            self.notify(|callbacks| callbacks.on_llm_request(&request));
+
+            // Routed through the context so the shared budget is enforced and recorded
+            match self.complete(llm_context, request, events).await {
                 Ok(response) => {
+                    self.notify(|callbacks| callbacks.on_llm_response(&response));
                     match response.kind {
                         CompletionKind::Message { content } => {
-                            return Ok(content);
+                            return Ok(StreamOutcome::Completed(content));
                         }
                         CompletionKind::ToolCall { tool_calls } => {
-                            messages.push(ChatMessage::new(
-                                ChatMessageRole::Assistant,
-                                None,
-                                Some(tool_calls.clone()),
-                                None,
-                            ));
+                            let final_call = final_answer
+                                .and_then(|_| tool_calls.iter().find(|c| c.function.name == FINAL_ANSWER_TOOL));
+                            if let Some(call) = final_call {
+                                return task
+                                    .output_from_final_answer(&call.function.arguments)
+                                    .map(StreamOutcome::Completed)
+                                    .map_err(|e| e.to_string());
+                            }
+
+                            messages.push(ChatMessage::assistant(None, Some(tool_calls.clone())));
                             
-                            for call in tool_calls {
-                                let tool_result_content = match execute_tool(&call.function.name, &call.function.arguments) {
-                                    Ok(result) => result,
-                                    Err(e) => {
-                                        eprintln!("Tool Execution Error: {}", e);
-                                        format!("Error executing tool {}: {}", call.function.name, e)
+                            for mut call in tool_calls {
+                                let started_at = chrono::Utc::now();
+                                let timer = std::time::Instant::now();
+                                if let Some(approver) = &self.tool_approver {
+                                    match approver.approve_tool_call(&call.function.name, &call.function.arguments).await {
+                                        ToolApproval::Approve => {}
+                                        ToolApproval::Edit { arguments } => call.function.arguments = arguments,
+                                        ToolApproval::Deny { message } => {
+                                            let denial = format!("Tool call was not approved: {}", message);
+                                            self.audit_tool_call(task, &call, started_at, timer, Some(&denial));
+                                            self.tool_call_started(events, &call);
+                                            self.tool_call_finished(events, &call, false, &denial);
+                                            messages.push(ChatMessage::tool(call.id, denial));
+                                            continue;
+                                        }
+                                    }
+                                }
+                                self.tool_call_started(events, &call);
+                                let result = self.run_tool(llm_context, &call).await;
+                                let error = result.as_ref().err().map(ToString::to_string);
+                                self.audit_tool_call(task, &call, started_at, timer, error.as_deref());
+                                let success = result.is_ok();
+                                let tool_result_content = match result {
+                                    Ok(output) => {
+                                        if let Some(sink) = &self.tool_output {
+                                            sink(&call.function.name, &output);
+                                        }
+                                        output.content
+                                    }
+                                    Err(error) => {
+                                        eprintln!("Tool Execution Error: {}", error);
+                                        if self.tool_error_policy == ToolErrorPolicy::Stop {
+                                            self.tool_call_finished(events, &call, false, &error.to_payload());
+                                            return Err(format!("Tool {} failed: {}", call.function.name, error));
+                                        }
+                                        error.to_payload()
                                     }
                                 };
-                                messages.push(ChatMessage::new(
-                                    ChatMessageRole::Tool,
-                                    Some(tool_result_content),
-                                    None,
-                                    Some(call.id),
-                                ));
+                                self.tool_call_finished(events, &call, success, &tool_result_content);
+                                messages.push(ChatMessage::tool(call.id, tool_result_content));
                             }
                         }
                     }
                 },
+                Err(ProviderError::ApiError { status: 400 | 422, message })
+                    if response_format.is_some() && rejects_response_format(&message) =>
+                {
+                    // Model/provider doesn't support structured output; fall back to prompt-based JSON
+                    eprintln!("Provider rejected response_format ({}). Falling back to prompt-based validation.", message);
+                    *response_format = None;
+                }
+                // Another request would only find the budget spent as well
+                Err(e @ ProviderError::BudgetExceeded(_)) => return Ok(StreamOutcome::Stopped(e.to_string())),
                 Err(e) => return Err(e.to_string()),
             }
         }
     }
+
+    // One last request after a loop limit: no tools except the final answer, so the model
+    // has to answer from the conversation so far
+    #[allow(clippy::too_many_arguments)]
+    async fn summarize(
+        &self,
+        llm_context: &LlmContext,
+        messages: &mut Vec<ChatMessage>,
+        task: &Task,
+        sampling: &EffectiveSampling,
+        final_answer: Option<&Tool>,
+        response_format: &Option<ResponseFormat>,
+        events: Events<'_>,
+    ) -> Result<String, String> {
+        messages.push(ChatMessage::user(
+            "You have run out of tool calls. Do not call any more tools; give your best final answer \
+             using only the information gathered so far."
+                .to_string(),
+        ));
+        let tools = final_answer.map(|tool| vec![tool.clone()]);
+        let mut request = CompletionRequest::new(messages.clone(), self.llm_config.model_name.clone(), None, None, tools);
+        sampling.apply(&mut request);
+        request.response_format = response_format.clone();
+        if final_answer.is_some() {
+            request.tool_choice = Some(ToolChoice::Required);
+        }
+        self.apply_middlewares(&mut request)?;
+        self.notify(|callbacks| callbacks.on_llm_request(&request));
+        let response = self.complete(llm_context, request, events).await.map_err(|e| e.to_string())?;
+        self.notify(|callbacks| callbacks.on_llm_response(&response));
+        match response.kind {
+            CompletionKind::Message { content } => Ok(content),
+            CompletionKind::ToolCall { tool_calls } => match tool_calls.iter().find(|c| c.function.name == FINAL_ANSWER_TOOL) {
+                Some(call) if final_answer.is_some() => {
+                    task.output_from_final_answer(&call.function.arguments).map_err(|e| e.to_string())
+                }
+                _ => Err("Model kept requesting tools after the loop limit was reached".to_string()),
+            },
+        }
+    }
+
+    fn notify(&self, hook: impl Fn(&dyn AgentCallbacks)) {
+        self.callbacks.iter().for_each(|callbacks| hook(callbacks.as_ref()));
+    }
+
+    fn notify_streamed_response(&self, text: &str) {
+        if self.callbacks.is_empty() {
+            return;
+        }
+        let response = CompletionResponse {
+            kind: CompletionKind::Message { content: text.to_string() },
+            usage: None,
+            finish_reason: None,
+            logprobs: None,
+        };
+        self.notify(|callbacks| callbacks.on_llm_response(&response));
+    }
+
+    fn tool_call_started(&self, events: Events<'_>, call: &ToolCallRequest) {
+        self.notify(|callbacks| callbacks.on_tool_call(call));
+        events.emit(AgentEvent::ToolCallStarted {
+            id: call.id.clone(),
+            tool: call.function.name.clone(),
+            arguments: call.function.arguments.clone(),
+        });
+    }
+
+    fn tool_call_finished(&self, events: Events<'_>, call: &ToolCallRequest, success: bool, output: &str) {
+        self.notify(|callbacks| callbacks.on_tool_result(call, output, success));
+        events.record.add_tool_invocation(call, success, output);
+        events.emit(AgentEvent::ToolCallFinished {
+            id: call.id.clone(),
+            tool: call.function.name.clone(),
+            success,
+            output: output.to_string(),
+        });
+    }
+
+    // `LlmContext::completion`, streamed instead when someone is listening for tokens
+    async fn complete(
+        &self,
+        llm_context: &LlmContext,
+        request: CompletionRequest,
+        events: Events<'_>,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let response = self.complete_unrecorded(llm_context, request, events).await?;
+        if let Some(usage) = &response.usage {
+            events.add_usage(usage);
+        }
+        Ok(response)
+    }
+
+    // A request made for the call rather than its task (translations, fact checks): held
+    // to the same budgets and counted in the output's usage, but never streamed
+    async fn complete_auxiliary(
+        &self,
+        llm_context: &LlmContext,
+        mut request: CompletionRequest,
+        events: Events<'_>,
+    ) -> Result<CompletionResponse, ProviderError> {
+        events.budget.admit(&mut request)?;
+        let response = llm_context.completion(request).await?;
+        if let Some(usage) = &response.usage {
+            events.add_usage(usage);
+        }
+        Ok(response)
+    }
+
+    async fn complete_unrecorded(
+        &self,
+        llm_context: &LlmContext,
+        mut request: CompletionRequest,
+        events: Events<'_>,
+    ) -> Result<CompletionResponse, ProviderError> {
+        events.budget.admit(&mut request)?;
+        if !events.is_enabled() {
+            return llm_context.completion(request).await;
+        }
+        if let Some(budget) = &llm_context.budget {
+            budget.check()?;
+        }
+        let mut stream = match &llm_context.cancellation {
+            Some(token) => self.provider.completion_stream_with_cancellation(request, token).await?,
+            None => self.provider.completion_stream(request).await?,
+        };
+        let mut collector = StreamCollector::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if let StreamContentDelta::Text(text) = &chunk.delta
+                && !text.is_empty()
+            {
+                events.emit(AgentEvent::Token { text: text.clone() });
+            }
+            collector.push(&chunk);
+        }
+        let response = collector.finish();
+        if let (Some(budget), Some(usage)) = (&llm_context.budget, &response.usage) {
+            budget.record(usage);
+        }
+        Ok(response)
+    }
+
+    // Report a finished (or refused) tool call to the audit sink, if any
+    fn audit_tool_call(
+        &self,
+        task: &Task,
+        call: &ToolCallRequest,
+        started_at: chrono::DateTime<chrono::Utc>,
+        timer: std::time::Instant,
+        error: Option<&str>,
+    ) {
+        let Some(sink) = &self.audit else { return };
+        sink.record(&ToolCallRecord {
+            tool: call.function.name.clone(),
+            arguments_hash: hash_arguments(&call.function.arguments),
+            started_at,
+            duration_ms: timer.elapsed().as_millis() as u64,
+            success: error.is_none(),
+            error: error.map(str::to_string),
+            agent_id: self.id.clone(),
+            task_id: task.id.clone(),
+        });
+    }
+
+    // Run one tool call, retrying retryable failures as the policy allows
+    async fn run_tool(&self, llm_context: &LlmContext, call: &ToolCallRequest) -> Result<ToolOutput, ToolError> {
+        let max_attempts = match self.tool_error_policy {
+            ToolErrorPolicy::Retry { max_attempts } => max_attempts.max(1),
+            _ => 1,
+        };
+        if let Some(filter) = &self.tool_filter
+            && !filter.permits(&call.function.name)
+        {
+            return Err(ToolError::new(format!("Tool '{}' is not available to this agent", call.function.name)));
+        }
+        let mut attempt = 1;
+        loop {
+            // Tools run one level deeper so LLM calls they make are depth-limited
+            let tool_context = llm_context.nested().map_err(ToolError::from_error)?;
+            let (name, arguments) = (&call.function.name, &call.function.arguments);
+            let run = tool_context.scope(async {
+                match &self.tool_registry {
+                    Some(registry) => registry.execute_tool_output(name, arguments).await,
+                    None => execute_tool_output(name, arguments).await,
+                }
+            });
+            let result = match &self.tool_progress {
+                Some(sink) => ToolContext::current().with_progress(sink.clone()).scope(run).await,
+                None => run.await,
+            };
+            match result.map_err(|e| ToolError::parse(&e)) {
+                Err(error) if error.retryable && attempt < max_attempts => attempt += 1,
+                result => return result,
+            }
+        }
+    }
+}
+
+// `Task::validate_output`, moved to a blocking thread when it runs a code check so the
+// command doesn't hold up the async runtime
+async fn validate_output(task: &Task, output: &str) -> anyhow::Result<()> {
+    if !task.has_code_check() {
+        return task.validate_output(output);
+    }
+    let (task, output, context) = (task.clone(), output.to_string(), ToolContext::current());
+    tokio::task::spawn_blocking(move || context.scope_sync(|| task.validate_output(&output)))
+        .await
+        .map_err(|e| anyhow::anyhow!("Code check did not finish: {}", e))?
+}
+
+// Phrases providers use when they refuse structured output
+const RESPONSE_FORMAT_PHRASES: [&str; 7] =
+    ["response_format", "response format", "json mode", "json_object", "json_schema", "json schema", "structured output"];
+
+// Whether a bad-request error is about structured output, which not every provider or
+// model supports. Other errors (unknown model, malformed body) must not trigger the
+// fallback, even when they mention JSON.
+fn rejects_response_format(message: &str) -> bool {
+    let message = message.to_lowercase();
+    RESPONSE_FORMAT_PHRASES.iter().any(|phrase| message.contains(phrase))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::audit::InMemoryAudit;
+    use crate::task::degraded::DegradedFallback;
+    use crate::task::guardrail::Guardrail;
+    use crate::task::task::{JsonField, JsonFieldType};
+    use crate::memory::memory::InMemoryMemory;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use merco_llmproxy::{LlmConfig, MockProvider, Provider};
+
+    fn mock_agent(provider: &Arc<MockProvider>) -> Agent {
+        let llm_config = AgentLLMConfig::new(LlmConfig::new(Provider::Ollama), "mock".to_string(), 0.0, 100);
+        Agent::new(llm_config, "You are a geographer.".to_string(), Vec::new(), Vec::new()).with_provider(provider.clone())
+    }
+
+    #[tokio::test]
+    async fn test_fact_check_requires_a_verifier() {
+        let provider = Arc::new(MockProvider::new().with_message("Paris is the capital of France."));
+        let task = Task::new("Name the capital of France".to_string(), None)
+            .with_fact_check(FactCheck::new(DiscrepancyAction::Warn));
+
+        let error = mock_agent(&provider).call(task).await.unwrap_err();
+        assert!(error.to_string().contains("no verifier"), "{}", error);
+        assert_eq!(provider.call_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fact_check_revises_disputed_claims() {
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_message("Paris is the capital of Italy.")
+                .with_message("Rome is the capital of Italy."),
+        );
+        let verifier = Arc::new(
+            MockProvider::new()
+                .with_message(r#"{"discrepancies": [{"claim": "Paris is the capital of Italy", "issue": "It is Rome"}]}"#)
+                .with_message(r#"{"discrepancies": []}"#),
+        );
+        let task = Task::new("Name the capital of Italy".to_string(), None)
+            .with_fact_check(FactCheck::new(DiscrepancyAction::Revise).with_model("checker-large"));
+        let agent = mock_agent(&provider).with_verifier(verifier.clone(), "checker");
+
+        let output = agent.call(task).await.unwrap();
+        assert_eq!(output.text, "Rome is the capital of Italy.");
+        assert_eq!(verifier.requests()[0].model, "checker-large");
+        let feedback = provider.requests()[1].messages.last().unwrap().content.clone().unwrap_or_default();
+        assert!(feedback.contains("It is Rome"), "{}", feedback);
+    }
+
+    // Agent whose only tool, `lookup`, counts its calls and returns `result`
+    fn lookup_agent(provider: &Arc<MockProvider>, result: String) -> Agent {
+        lookup_agent_counting(provider, result).0
+    }
+
+    fn lookup_agent_counting(provider: &Arc<MockProvider>, result: String) -> (Agent, Arc<AtomicUsize>) {
+        let lookup = Tool {
+            name: "lookup".to_string(),
+            description: "Look something up".to_string(),
+            parameters: merco_llmproxy::JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            lookup.clone(),
+            merco_llmproxy::sync_executor(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(result.clone())
+            }),
+        );
+        let llm_config = AgentLLMConfig::new(LlmConfig::new(Provider::Ollama), "mock".to_string(), 0.0, 100);
+        let agent = Agent::new(llm_config, "You are a researcher.".to_string(), Vec::new(), vec![lookup])
+            .with_provider(provider.clone())
+            .with_tool_registry(Arc::new(registry));
+        (agent, calls)
+    }
+
+    // Text of every message sent in the provider's nth request
+    fn sent_text(provider: &MockProvider, request: usize) -> String {
+        provider.requests()[request]
+            .messages
+            .iter()
+            .filter_map(|message| message.content.clone())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn test_shared_memory_passes_findings_between_agents() {
+        let memory: Arc<dyn Memory> = Arc::new(InMemoryMemory::new());
+        let researcher = Arc::new(MockProvider::new().with_message("The Eiffel Tower is in Paris."));
+        let writer = Arc::new(MockProvider::new().with_message("A postcard from Paris."));
+
+        mock_agent(&researcher)
+            .with_shared_memory(memory.clone())
+            .call(Task::new("Find where the Eiffel Tower is".to_string(), None))
+            .await
+            .unwrap();
+        mock_agent(&writer)
+            .with_shared_memory(memory)
+            .call(Task::new("Write a postcard about the Eiffel Tower".to_string(), None))
+            .await
+            .unwrap();
+
+        let sent = sent_text(&writer, 0);
+        assert!(sent.contains("Relevant findings shared by other agents"), "{}", sent);
+        assert!(sent.contains("The Eiffel Tower is in Paris."), "{}", sent);
+        assert!(!sent_text(&researcher, 0).contains("Relevant findings"));
+    }
+
+    #[tokio::test]
+    async fn test_final_answer_tool_delivers_the_result() {
+        let provider = Arc::new(MockProvider::new().with_tool_call(FINAL_ANSWER_TOOL, r#"{"answer": "Paris"}"#));
+        let agent = mock_agent(&provider).with_final_answer_tool(true);
+
+        let output = agent.call(Task::new("Name the capital of France".to_string(), None)).await.unwrap();
+        assert_eq!(output.text, "Paris");
+        let request = &provider.requests()[0];
+        assert_eq!(request.tool_choice, Some(ToolChoice::Required));
+        assert!(request.tools.iter().flatten().any(|tool| tool.name == FINAL_ANSWER_TOOL));
+        assert!(sent_text(&provider, 0).contains("calling the `final_answer` tool"));
+    }
+
+    #[tokio::test]
+    async fn test_middlewares_run_in_order_on_the_outgoing_request() {
+        let provider = Arc::new(MockProvider::new().with_message("Done"));
+        let tag = |suffix: &'static str| {
+            move |request: &mut CompletionRequest| {
+                let last = request.messages.last_mut().ok_or("No messages")?;
+                last.content = Some(format!("{}{}", last.content.clone().unwrap_or_default(), suffix));
+                Ok(())
+            }
+        };
+        let agent = mock_agent(&provider).with_middleware(Arc::new(tag(" [a]"))).with_middleware(Arc::new(tag(" [b]")));
+
+        agent.call(Task::new("Say done".to_string(), None)).await.unwrap();
+        let sent = provider.requests()[0].messages.last().unwrap().content.clone().unwrap_or_default();
+        assert!(sent.ends_with(" [a] [b]"), "{}", sent);
+    }
+
+    #[tokio::test]
+    async fn test_failing_middleware_stops_the_request() {
+        let provider = Arc::new(MockProvider::new().with_message("Done"));
+        let reject = |_: &mut CompletionRequest| Err("Blocked by policy".to_string());
+        let agent = mock_agent(&provider).with_middleware(Arc::new(reject));
+
+        let error = agent.call(Task::new("Say done".to_string(), None)).await.unwrap_err();
+        assert!(error.to_string().contains("Blocked by policy"), "{}", error);
+        assert_eq!(provider.call_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_context_manager_keeps_the_task_setup() {
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_tool_call("lookup", "{}")
+                .with_tool_call("lookup", "{}")
+                .with_tool_call("lookup", "{}")
+                .with_message("Done"),
+        );
+        let agent = lookup_agent(&provider, "x".repeat(2000))
+            .with_context_manager(ContextManager::new(1200).with_reserve_tokens(0).with_keep_recent(2));
+
+        agent.call(Task::new("Research the topic".to_string(), None)).await.unwrap();
+        let first = provider.requests()[0].messages.len();
+        let last = provider.requests().last().unwrap().messages.clone();
+        assert!(last.len() < first + 6, "{} messages were sent", last.len());
+        // The leading setup messages, which include the task, survive trimming
+        assert_eq!(
+            last[..first].iter().map(|m| m.content.clone()).collect::<Vec<_>>(),
+            provider.requests()[0].messages.iter().map(|m| m.content.clone()).collect::<Vec<_>>()
+        );
+        assert!(last[..first].iter().any(|m| m.content.as_deref().is_some_and(|c| c.contains("Research the topic"))));
+    }
+
+    #[tokio::test]
+    async fn test_audited_tool_calls_record_the_agent_and_task() {
+        let provider = Arc::new(MockProvider::new().with_tool_call("lookup", r#"{"q": "tides"}"#).with_message("Done"));
+        let audit = Arc::new(InMemoryAudit::new());
+        let agent = lookup_agent(&provider, "High tide at noon".to_string())
+            .with_id("researcher")
+            .with_audit_sink(audit.clone());
+
+        agent.call(Task::new("Check the tides".to_string(), None).with_id("tides-1")).await.unwrap();
+        let records = audit.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].tool, "lookup");
+        assert_eq!(records[0].arguments_hash, hash_arguments(r#"{"q": "tides"}"#));
+        assert_eq!(records[0].agent_id.as_deref(), Some("researcher"));
+        assert_eq!(records[0].task_id.as_deref(), Some("tides-1"));
+        assert!(records[0].success);
+    }
+
+    #[tokio::test]
+    async fn test_failing_guardrail_retries_with_its_feedback() {
+        let provider = Arc::new(MockProvider::new().with_message("Maybe Paris?").with_message("Paris."));
+        let guardrail = Guardrail::new("no hedging", |output| match output.to_lowercase().contains("maybe") {
+            true => Err("Answer without hedging".to_string()),
+            false => Ok(()),
+        });
+        let task = Task::new("Name the capital of France".to_string(), None).with_guardrail(guardrail);
+
+        let output = mock_agent(&provider).call(task).await.unwrap();
+        assert_eq!(output.text, "Paris.");
+        assert_eq!(provider.call_count(), 2);
+        let feedback = provider.requests()[1].messages.last().unwrap().content.clone().unwrap_or_default();
+        assert!(feedback.contains("Guardrail 'no hedging' rejected the output: Answer without hedging"), "{}", feedback);
+    }
+
+    #[tokio::test]
+    async fn test_denied_tool_call_is_not_run_and_the_model_sees_why() {
+        let provider = Arc::new(MockProvider::new().with_tool_call("lookup", r#"{"q": "payroll"}"#).with_message("Understood"));
+        let audit = Arc::new(InMemoryAudit::new());
+        let (agent, calls) = lookup_agent_counting(&provider, "Salaries: ...".to_string());
+        let agent = agent
+            .with_audit_sink(audit.clone())
+            .with_tool_approver(Arc::new(|_: &str, _: &str| ToolApproval::Deny { message: "No HR lookups".to_string() }));
+
+        let output = agent.call(Task::new("Find the payroll".to_string(), None)).await.unwrap();
+        assert_eq!(output.text, "Understood");
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        let denial = provider.requests()[1].messages.last().unwrap().content.clone().unwrap_or_default();
+        assert_eq!(denial, "Tool call was not approved: No HR lookups");
+        assert!(!audit.records()[0].success);
+    }
+
+    #[tokio::test]
+    async fn test_approved_and_edited_tool_calls_run() {
+        let provider = Arc::new(
+            MockProvider::new()
+                .with_tool_call("lookup", r#"{"q": "tides"}"#)
+                .with_tool_call("lookup", r#"{"q": "everything"}"#)
+                .with_message("Done"),
+        );
+        let audit = Arc::new(InMemoryAudit::new());
+        let (agent, calls) = lookup_agent_counting(&provider, "High tide at noon".to_string());
+        let agent = agent.with_audit_sink(audit.clone()).with_tool_approver(Arc::new(|_: &str, arguments: &str| {
+            match arguments.contains("everything") {
+                true => ToolApproval::Edit { arguments: r#"{"q": "tides"}"#.to_string() },
+                false => ToolApproval::Approve,
+            }
+        }));
+
+        agent.call(Task::new("Check the tides".to_string(), None)).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let hashes: Vec<String> = audit.records().into_iter().map(|record| record.arguments_hash).collect();
+        assert_eq!(hashes, vec![hash_arguments(r#"{"q": "tides"}"#); 2]);
+        assert!(sent_text(&provider, 2).contains("High tide at noon"));
+    }
+
+    fn unavailable() -> ProviderError {
+        ProviderError::ApiError { status: 503, message: "overloaded".to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_degraded_fallback_answers_after_the_retries_run_out() {
+        let provider = Arc::new((0..3).fold(MockProvider::new(), |provider, _| provider.with_error(unavailable())));
+        let task = Task::new("Summarize the news".to_string(), None)
+            .with_degraded_fallback(DegradedFallback::cached("Yesterday's summary"));
+
+        let output = mock_agent(&provider).call(task).await.unwrap();
+        assert_eq!(output.text, "Yesterday's summary");
+        assert!(output.degraded);
+        assert_eq!(output.attempts, 3);
+        assert_eq!(provider.call_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_degraded_fallback_is_not_used_when_an_attempt_succeeds() {
+        let provider = Arc::new(MockProvider::new().with_error(unavailable()).with_message("Today's summary"));
+        let task = Task::new("Summarize the news".to_string(), None)
+            .with_degraded_fallback(DegradedFallback::cached("Yesterday's summary"));
+
+        let output = mock_agent(&provider).call(task).await.unwrap();
+        assert_eq!(output.text, "Today's summary");
+        assert!(!output.degraded);
+    }
+
+    #[tokio::test]
+    async fn test_degraded_fallback_does_not_cover_invalid_output() {
+        let provider = Arc::new(MockProvider::new().with_message("no json").with_message("still none").with_message("nope"));
+        let task = json_task().with_degraded_fallback(DegradedFallback::unavailable());
+
+        let error = mock_agent(&provider).call(task).await.unwrap_err().to_string();
+        assert!(error.contains("Output validation failed after 3 attempts"), "{}", error);
+    }
+
+    #[test]
+    fn test_rejects_response_format() {
+        assert!(rejects_response_format("response_format is not supported with this model"));
+        assert!(rejects_response_format("Invalid schema for response_format 'answer'"));
+        assert!(rejects_response_format("model does not support JSON mode"));
+        assert!(!rejects_response_format("model 'gpt-9' not found"));
+        assert!(!rejects_response_format("max_tokens is too large"));
+        assert!(rejects_response_format("'json_schema' is not supported with this model"));
+        assert!(rejects_response_format("This model does not support Structured Outputs"));
+        assert!(!rejects_response_format("invalid JSON body"));
+        assert!(!rejects_response_format("Unsupported image format: bmp"));
+        assert!(!rejects_response_format("Request body failed schema validation: messages is required"));
+    }
+
+    fn json_task() -> Task {
+        let fields = vec![JsonField::new("capital", JsonFieldType::String)];
+        Task::new_with_json_output("Name the capital of France".to_string(), None, fields, Vec::new(), true)
+    }
+
+    fn bad_request(message: &str) -> ProviderError {
+        ProviderError::ApiError { status: 400, message: message.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_when_response_format_is_rejected() {
+        let provider = Arc::new(
+            MockProvider::new().with_error(bad_request("model does not support JSON mode")).with_message(r#"{"capital": "Paris"}"#),
+        );
+        let output = mock_agent(&provider).call(json_task()).await.unwrap();
+
+        assert_eq!(output.parsed_json.unwrap()["capital"], "Paris");
+        let requests = provider.requests();
+        assert!(requests[0].response_format.is_some());
+        assert!(requests[1].response_format.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_other_bad_requests_keep_response_format() {
+        let provider = Arc::new(
+            MockProvider::new().with_error(bad_request("invalid JSON body")).with_message(r#"{"capital": "Paris"}"#),
+        );
+        // The failed attempt is retried as is, not downgraded to prompt-based validation
+        let output = mock_agent(&provider).call(json_task()).await.unwrap();
+
+        assert_eq!(output.attempts, 2);
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|request| request.response_format.is_some()));
+    }
 }
@@ -0,0 +1,338 @@
+use merco_llmproxy::{CompletionRequest, Pricing, ProviderError, TokenUsage, count_tokens};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// Decides what happens when a request would go over a budget
+pub type BudgetHandler = Arc<dyn Fn(&BudgetExceeded) -> BudgetDecision + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetDecision {
+    // Fail the call with the `BudgetExceeded` error
+    Abort,
+    // Finish the call on another (cheaper) model, priced with `pricing`. The budget still
+    // records what it spends but is not enforced again until the next call.
+    SwitchModel { model: String, pricing: Option<Pricing> },
+}
+
+// The limit a request would have crossed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetLimit {
+    Tokens(u64),
+    Cost(f64),
+}
+
+// Why a call was stopped: what was spent (or reserved by requests still running) before
+// the request, and an estimate of what the request would have added (its prompt plus its
+// `max_tokens`, if set)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetExceeded {
+    pub limit: BudgetLimit,
+    pub used_tokens: u64,
+    pub cost: f64,
+    pub estimated_tokens: u64,
+    pub estimated_cost: f64,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            BudgetLimit::Tokens(max) => write!(
+                f,
+                "{} of {} tokens used and the next request needs about {} more",
+                self.used_tokens, max, self.estimated_tokens
+            ),
+            BudgetLimit::Cost(max) => write!(
+                f,
+                "${:.4} of ${:.4} spent and the next request costs about ${:.4} more",
+                self.cost, max, self.estimated_cost
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+#[derive(Debug, Default)]
+struct Spent {
+    tokens: u64,
+    cost: f64,
+    // Estimates of requests admitted but not yet recorded
+    reserved_tokens: u64,
+    reserved_cost: f64,
+    exceeded: Option<BudgetExceeded>,
+}
+
+// A token and/or dollar limit on LLM usage, checked before every request an agent makes.
+// Share one (`Arc`) between agents, or give it to a crew, to limit them together. Cost
+// is only counted for agents with pricing. Each admitted request reserves its estimate
+// until its usage is recorded, so agents running in parallel can't all pass the check
+// and overshoot together.
+#[derive(Default)]
+pub struct Budget {
+    max_tokens: Option<u64>,
+    max_cost: Option<f64>,
+    handler: Option<BudgetHandler>,
+    spent: Mutex<Spent>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u64) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_max_cost(mut self, max_cost: f64) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    // Consult `handler` instead of failing when a request would go over the budget
+    pub fn with_handler(mut self, handler: impl Fn(&BudgetExceeded) -> BudgetDecision + Send + Sync + 'static) -> Self {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    // Finish calls that would go over the budget on `model` instead of failing them
+    pub fn with_fallback_model(self, model: impl Into<String>, pricing: Option<Pricing>) -> Self {
+        let model = model.into();
+        self.with_handler(move |_| BudgetDecision::SwitchModel { model: model.clone(), pricing })
+    }
+
+    pub fn used_tokens(&self) -> u64 {
+        self.spent.lock().unwrap().tokens
+    }
+
+    pub fn cost(&self) -> f64 {
+        self.spent.lock().unwrap().cost
+    }
+
+    // The error that last stopped a call, telling budget aborts apart from other failures
+    pub fn exceeded(&self) -> Option<BudgetExceeded> {
+        self.spent.lock().unwrap().exceeded.clone()
+    }
+
+    fn record(&self, usage: &TokenUsage, cost: Option<f64>) {
+        let mut spent = self.spent.lock().unwrap();
+        spent.tokens += u64::from(usage.total_tokens);
+        spent.cost += cost.unwrap_or_default();
+    }
+
+    // Hold room for a request's estimate if the budget has it
+    fn reserve(&self, estimated_tokens: u64, estimated_cost: f64) -> Result<(), BudgetExceeded> {
+        let mut spent = self.spent.lock().unwrap();
+        let (used_tokens, cost) = (spent.tokens + spent.reserved_tokens, spent.cost + spent.reserved_cost);
+        let limit = match (self.max_tokens, self.max_cost) {
+            (Some(max), _) if used_tokens + estimated_tokens > max => BudgetLimit::Tokens(max),
+            (_, Some(max)) if cost + estimated_cost > max => BudgetLimit::Cost(max),
+            _ => {
+                spent.reserved_tokens += estimated_tokens;
+                spent.reserved_cost += estimated_cost;
+                return Ok(());
+            }
+        };
+        Err(BudgetExceeded { limit, used_tokens, cost, estimated_tokens, estimated_cost })
+    }
+
+    fn release(&self, estimated_tokens: u64, estimated_cost: f64) {
+        let mut spent = self.spent.lock().unwrap();
+        spent.reserved_tokens = spent.reserved_tokens.saturating_sub(estimated_tokens);
+        spent.reserved_cost = (spent.reserved_cost - estimated_cost).max(0.0);
+    }
+}
+
+impl fmt::Debug for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Budget")
+            .field("max_tokens", &self.max_tokens)
+            .field("max_cost", &self.max_cost)
+            .field("handler", &self.handler.as_ref().map(|_| "<BudgetHandler>"))
+            .field("spent", &self.spent)
+            .finish()
+    }
+}
+
+// The budgets one agent call is subject to, and the model it switched to, if any. The
+// call's requests run one at a time, so at most one reservation is held.
+pub(crate) struct BudgetGuard<'a> {
+    budgets: &'a [Arc<Budget>],
+    pricing: Option<Pricing>, // The agent's own
+    switched: Mutex<Option<(String, Option<Pricing>)>>,
+    reserved: Mutex<Option<(u64, f64)>>, // Estimate of the request in flight, held in every budget
+    exceeded: Mutex<Option<BudgetExceeded>>,
+}
+
+impl<'a> BudgetGuard<'a> {
+    pub(crate) fn new(budgets: &'a [Arc<Budget>], pricing: Option<Pricing>) -> Self {
+        Self {
+            budgets,
+            pricing,
+            switched: Mutex::new(None),
+            reserved: Mutex::new(None),
+            exceeded: Mutex::new(None),
+        }
+    }
+
+    // The error that stopped this call, if a budget did
+    pub(crate) fn exceeded(&self) -> Option<BudgetExceeded> {
+        self.exceeded.lock().unwrap().clone()
+    }
+
+    // Give back the reservation of a request that has finished or failed
+    fn release(&self) {
+        if let Some((tokens, cost)) = self.reserved.lock().unwrap().take() {
+            for budget in self.budgets {
+                budget.release(tokens, cost);
+            }
+        }
+    }
+
+    // Pricing of the model the call is currently using
+    pub(crate) fn pricing(&self) -> Option<Pricing> {
+        match &*self.switched.lock().unwrap() {
+            Some((_, pricing)) => *pricing,
+            None => self.pricing,
+        }
+    }
+
+    // Charge `usage` to every budget in place of the request's reservation; returns its
+    // cost, if the current model is priced
+    pub(crate) fn record(&self, usage: &TokenUsage) -> Option<f64> {
+        self.release();
+        let cost = self.pricing().map(|pricing| pricing.cost(usage));
+        for budget in self.budgets {
+            budget.record(usage, cost);
+        }
+        cost
+    }
+
+    // Let `request` through if every budget has room for it, reserving its estimate until
+    // `record`, and point it at the fallback model once a handler has switched to one
+    pub(crate) fn admit(&self, request: &mut CompletionRequest) -> Result<(), ProviderError> {
+        self.release();
+        if let Some((model, _)) = &*self.switched.lock().unwrap() {
+            request.model = model.clone();
+            return Ok(());
+        }
+        let prompt_tokens = count_tokens(&request.messages) as u32;
+        let completion_tokens = request.max_tokens.unwrap_or_default();
+        let estimate = TokenUsage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens };
+        let estimated_cost = self.pricing.as_ref().map(|pricing| pricing.cost(&estimate)).unwrap_or_default();
+        let estimated_tokens = u64::from(estimate.total_tokens);
+        for (index, budget) in self.budgets.iter().enumerate() {
+            let Err(exceeded) = budget.reserve(estimated_tokens, estimated_cost) else {
+                continue;
+            };
+            for reserved in &self.budgets[..index] {
+                reserved.release(estimated_tokens, estimated_cost);
+            }
+            let decision = budget.handler.as_ref().map_or(BudgetDecision::Abort, |handler| handler(&exceeded));
+            match decision {
+                BudgetDecision::Abort => {
+                    let error = ProviderError::BudgetExceeded(exceeded.to_string());
+                    budget.spent.lock().unwrap().exceeded = Some(exceeded.clone());
+                    *self.exceeded.lock().unwrap() = Some(exceeded);
+                    return Err(error);
+                }
+                BudgetDecision::SwitchModel { model, pricing } => {
+                    eprintln!("Budget exceeded ({}). Switching to model {}.", exceeded, model);
+                    request.model = model.clone();
+                    *self.switched.lock().unwrap() = Some((model, pricing));
+                    return Ok(());
+                }
+            }
+        }
+        *self.reserved.lock().unwrap() = Some((estimated_tokens, estimated_cost));
+        Ok(())
+    }
+}
+
+impl Drop for BudgetGuard<'_> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merco_llmproxy::ChatMessage;
+
+    fn request(max_tokens: u32) -> CompletionRequest {
+        let mut request = CompletionRequest::new(vec![ChatMessage::user("hi".to_string())], "m".to_string(), None, None, None);
+        request.max_tokens = Some(max_tokens);
+        request
+    }
+
+    fn usage(total_tokens: u32) -> TokenUsage {
+        TokenUsage { prompt_tokens: total_tokens / 2, completion_tokens: total_tokens - total_tokens / 2, total_tokens }
+    }
+
+    #[test]
+    fn test_records_usage_and_cost() {
+        let budgets = vec![Arc::new(Budget::new().with_max_tokens(1000))];
+        let pricing = Pricing { input_per_million: 1_000_000.0, output_per_million: 2_000_000.0 };
+        let guard = BudgetGuard::new(&budgets, Some(pricing));
+
+        guard.admit(&mut request(100)).unwrap();
+        assert_eq!(guard.record(&usage(10)), Some(15.0));
+        assert_eq!(budgets[0].used_tokens(), 10);
+        assert_eq!(budgets[0].cost(), 15.0);
+    }
+
+    #[test]
+    fn test_aborts_when_a_request_would_go_over() {
+        let budgets = vec![Arc::new(Budget::new().with_max_tokens(100))];
+        let guard = BudgetGuard::new(&budgets, None);
+        guard.admit(&mut request(50)).unwrap();
+        guard.record(&usage(60));
+
+        let error = guard.admit(&mut request(50)).unwrap_err();
+        assert!(matches!(error, ProviderError::BudgetExceeded(_)));
+        let exceeded = guard.exceeded().unwrap();
+        assert_eq!(exceeded.limit, BudgetLimit::Tokens(100));
+        assert_eq!(exceeded.used_tokens, 60);
+        assert_eq!(budgets[0].exceeded(), Some(exceeded));
+    }
+
+    #[test]
+    fn test_reservations_stop_concurrent_overshoot() {
+        let budgets = vec![Arc::new(Budget::new().with_max_tokens(100))];
+        let (first, second) = (BudgetGuard::new(&budgets, None), BudgetGuard::new(&budgets, None));
+
+        // The first request's estimate is held until it is recorded
+        first.admit(&mut request(60)).unwrap();
+        assert!(second.admit(&mut request(60)).is_err());
+
+        // Recording the (smaller) actual usage frees the rest of the reservation
+        first.record(&usage(20));
+        second.admit(&mut request(60)).unwrap();
+
+        // A request that fails without usage gives its reservation back when the call ends
+        drop(second);
+        assert!(BudgetGuard::new(&budgets, None).admit(&mut request(60)).is_ok());
+        assert_eq!(budgets[0].used_tokens(), 20);
+    }
+
+    #[test]
+    fn test_switches_to_the_fallback_model() {
+        let fallback = Pricing { input_per_million: 0.0, output_per_million: 0.0 };
+        let budgets = vec![Arc::new(Budget::new().with_max_cost(0.0001).with_fallback_model("small", Some(fallback)))];
+        let pricing = Pricing { input_per_million: 10.0, output_per_million: 30.0 };
+        let guard = BudgetGuard::new(&budgets, Some(pricing));
+
+        let mut expensive = request(1000);
+        guard.admit(&mut expensive).unwrap();
+        assert_eq!(expensive.model, "small");
+        assert_eq!(guard.record(&usage(1000)), Some(0.0));
+
+        // Later requests of the same call stay on the fallback without another check
+        let mut next = request(1000);
+        guard.admit(&mut next).unwrap();
+        assert_eq!(next.model, "small");
+        assert_eq!(guard.exceeded(), None);
+    }
+}
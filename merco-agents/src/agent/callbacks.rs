@@ -0,0 +1,24 @@
+use crate::agent::output::AgentOutput;
+use merco_llmproxy::{CompletionRequest, CompletionResponse, ToolCallRequest};
+
+// Observes an agent's lifecycle, e.g. for logging, metrics or tracing. Every hook
+// defaults to doing nothing, so implementations only override what they need.
+// Callbacks run inline in the order they were added and should return quickly.
+pub trait AgentCallbacks: Send + Sync {
+    // A request is about to be sent, after middlewares have been applied
+    fn on_llm_request(&self, _request: &CompletionRequest) {}
+
+    // For streamed validation runs this is the text received before the stream ended
+    fn on_llm_response(&self, _response: &CompletionResponse) {}
+
+    // The model asked for a tool call; `call` carries the arguments as approved
+    fn on_tool_call(&self, _call: &ToolCallRequest) {}
+
+    // `output` is what the model sees: the tool's result, its error payload or the denial
+    fn on_tool_result(&self, _call: &ToolCallRequest, _output: &str, _success: bool) {}
+
+    // `attempt` failed (LLM error or invalid output) and another one follows
+    fn on_retry(&self, _attempt: usize, _error: &str) {}
+
+    fn on_finish(&self, _result: &Result<AgentOutput, String>) {}
+}
@@ -0,0 +1,33 @@
+use crate::agent::budget::BudgetExceeded;
+use std::fmt;
+
+// Why an agent call failed. Converts into a `String` for callers that only report errors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentError {
+    // A budget had no room for a request and its handler chose to abort
+    BudgetExceeded(BudgetExceeded),
+    Failed(String),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::BudgetExceeded(exceeded) => write!(f, "Budget exceeded: {}", exceeded),
+            AgentError::Failed(error) => f.write_str(error),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+impl From<String> for AgentError {
+    fn from(error: String) -> Self {
+        AgentError::Failed(error)
+    }
+}
+
+impl From<AgentError> for String {
+    fn from(error: AgentError) -> Self {
+        error.to_string()
+    }
+}
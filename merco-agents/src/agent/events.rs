@@ -0,0 +1,66 @@
+use crate::agent::budget::BudgetGuard;
+use crate::agent::output::RunRecord;
+use futures::stream::BoxStream;
+use merco_llmproxy::TokenUsage;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+// What an agent is doing while `Agent::call_stream` runs. The stream always ends with
+// exactly one `Completed` or `Failed`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    // A piece of the model's text response, as it is generated
+    Token { text: String },
+    ToolCallStarted { id: String, tool: String, arguments: String },
+    // `output` is what the model sees: the tool's result, its error payload or the denial
+    ToolCallFinished { id: String, tool: String, success: bool, output: String },
+    // The output of `attempt` was rejected and the model is asked to try again
+    ValidationRetry { attempt: usize, error: String },
+    Completed { output: String },
+    Failed { error: String },
+}
+
+impl AgentEvent {
+    // True for the event that ends the stream
+    pub fn is_final(&self) -> bool {
+        matches!(self, AgentEvent::Completed { .. } | AgentEvent::Failed { .. })
+    }
+}
+
+pub type AgentEventStream<'a> = BoxStream<'a, AgentEvent>;
+
+// Where a call reports what happens: the stream of a streaming call (`None` for plain
+// calls), the record its `AgentOutput` is built from and the budgets it spends from
+#[derive(Clone, Copy)]
+pub(crate) struct Events<'a> {
+    sender: Option<&'a mpsc::UnboundedSender<AgentEvent>>,
+    pub(crate) record: &'a RunRecord,
+    pub(crate) budget: &'a BudgetGuard<'a>,
+}
+
+impl<'a> Events<'a> {
+    pub(crate) fn new(
+        sender: Option<&'a mpsc::UnboundedSender<AgentEvent>>,
+        record: &'a RunRecord,
+        budget: &'a BudgetGuard<'a>,
+    ) -> Self {
+        Self { sender, record, budget }
+    }
+
+    pub(crate) fn add_usage(&self, usage: &TokenUsage) {
+        let cost = self.budget.record(usage);
+        self.record.add_usage(usage, cost);
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    pub(crate) fn emit(&self, event: AgentEvent) {
+        // A dropped receiver just means nobody is listening any more
+        if let Some(sender) = self.sender {
+            let _ = sender.send(event);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use merco_llmproxy::{ChatMessage, CompletionRequest};
+
+// Inspects or modifies an agent's outgoing request right before it is dispatched
+// (e.g. inject the current date, strip internal notes, apply a style guide).
+// Middlewares run in the order they were added and only affect the outgoing copy,
+// not the agent's stored conversation.
+pub trait RequestMiddleware: Send + Sync {
+    fn on_request(&self, request: &mut CompletionRequest) -> Result<(), String>;
+}
+
+// Any matching closure can be used as a middleware
+impl<F> RequestMiddleware for F
+where
+    F: Fn(&mut CompletionRequest) -> Result<(), String> + Send + Sync,
+{
+    fn on_request(&self, request: &mut CompletionRequest) -> Result<(), String> {
+        self(request)
+    }
+}
+
+// Built-in middleware adding a system note with the current date/time, timezone,
+// locale and available tools, so the model doesn't reason from its training cutoff
+#[derive(Debug, Clone)]
+pub struct EnvironmentPreamble {
+    pub include_datetime: bool,
+    pub include_timezone: bool,
+    pub include_locale: bool,
+    pub include_tools: bool,
+}
+
+impl Default for EnvironmentPreamble {
+    fn default() -> Self {
+        Self {
+            include_datetime: true,
+            include_timezone: true,
+            include_locale: true,
+            include_tools: true,
+        }
+    }
+}
+
+impl EnvironmentPreamble {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_datetime(mut self, enabled: bool) -> Self {
+        self.include_datetime = enabled;
+        self
+    }
+
+    pub fn with_timezone(mut self, enabled: bool) -> Self {
+        self.include_timezone = enabled;
+        self
+    }
+
+    pub fn with_locale(mut self, enabled: bool) -> Self {
+        self.include_locale = enabled;
+        self
+    }
+
+    pub fn with_tools(mut self, enabled: bool) -> Self {
+        self.include_tools = enabled;
+        self
+    }
+
+    // Build the preamble text for the given request
+    pub fn render(&self, request: &CompletionRequest) -> String {
+        let now = chrono::Local::now();
+        let mut lines = vec!["Environment information (use this instead of assumptions from your training data):".to_string()];
+
+        if self.include_datetime {
+            lines.push(format!("- Current date and time: {}", now.format("%A, %Y-%m-%d %H:%M:%S")));
+        }
+        if self.include_timezone {
+            lines.push(format!("- Timezone: UTC{}", now.format("%:z")));
+        }
+        if self.include_locale {
+            let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .iter()
+                .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+                .unwrap_or_else(|| "unknown".to_string());
+            lines.push(format!("- Locale: {}", locale));
+        }
+        if self.include_tools {
+            match request.tools.as_deref() {
+                Some(tools) if !tools.is_empty() => {
+                    lines.push("- Available tools:".to_string());
+                    for tool in tools {
+                        lines.push(format!("  - {}: {}", tool.name, tool.description));
+                    }
+                }
+                _ => lines.push("- Available tools: none".to_string()),
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+impl RequestMiddleware for EnvironmentPreamble {
+    fn on_request(&self, request: &mut CompletionRequest) -> Result<(), String> {
+        let preamble = ChatMessage::system(self.render(request));
+        // Keep the agent's own system prompt first
+        let position = request
+            .messages
+            .iter()
+            .take_while(|m| m.role.is_instruction())
+            .count();
+        request.messages.insert(position, preamble);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merco_llmproxy::{ChatMessageRole, JsonSchema, Tool};
+
+    fn request(messages: Vec<ChatMessage>, tools: Option<Vec<Tool>>) -> CompletionRequest {
+        CompletionRequest::new(messages, "mock".to_string(), None, None, tools)
+    }
+
+    #[test]
+    fn test_render_includes_only_enabled_sections() {
+        let search = Tool {
+            name: "search".to_string(),
+            description: "Search the web".to_string(),
+            parameters: JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        let preamble = EnvironmentPreamble::new().with_timezone(false).with_locale(false);
+
+        let text = preamble.render(&request(Vec::new(), Some(vec![search])));
+        assert!(text.contains("- Current date and time: "), "{}", text);
+        assert!(text.contains("  - search: Search the web"), "{}", text);
+        assert!(!text.contains("Timezone") && !text.contains("Locale"), "{}", text);
+
+        let text = EnvironmentPreamble::new().with_datetime(false).render(&request(Vec::new(), None));
+        assert!(text.contains("- Available tools: none"), "{}", text);
+        assert!(text.contains("- Timezone: UTC"), "{}", text);
+        assert!(!text.contains("Current date"), "{}", text);
+    }
+
+    #[test]
+    fn test_preamble_follows_the_system_prompt() {
+        let mut request = request(vec![ChatMessage::system("You are terse."), ChatMessage::user("Hi")], None);
+
+        EnvironmentPreamble::new().on_request(&mut request).unwrap();
+        let contents: Vec<String> = request.messages.iter().map(|m| m.content.clone().unwrap_or_default()).collect();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[0], "You are terse.");
+        assert!(contents[1].starts_with("Environment information"), "{}", contents[1]);
+        assert!(matches!(request.messages[1].role, ChatMessageRole::System));
+        assert_eq!(contents[2], "Hi");
+    }
+}
@@ -1 +1,10 @@
+#[allow(clippy::module_inception)]
 pub mod agent;
+pub mod budget;
+pub mod callbacks;
+pub mod error;
+pub mod events;
+pub mod middleware;
+pub mod output;
+pub mod sampling;
+pub mod translation;
@@ -0,0 +1,107 @@
+use merco_llmproxy::{TokenUsage, ToolCallRequest};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Result of a successful `Agent::call`: the answer plus what it took to produce it.
+// Displays as the answer text, so `output.to_string()` gives the plain result.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AgentOutput {
+    pub text: String,
+    pub parsed_json: Option<Value>, // The answer parsed, for tasks with JSON output
+    pub tool_invocations: Vec<ToolInvocation>,
+    pub token_usage: TokenUsage, // Summed over every request of the call, retries included
+    pub cost: Option<f64>, // Priced with the agent's pricing (or a budget fallback model's), if it has one
+    pub attempts: usize, // Attempts made; 1 when the first answer was accepted
+    pub duration: Duration,
+    pub degraded: bool, // The text came from the task's degraded fallback, not the model
+}
+
+impl AgentOutput {
+    // Deserialize the parsed JSON answer, e.g. into the type a task's schema came from
+    pub fn parse<T: serde::de::DeserializeOwned>(&self) -> Result<T, String> {
+        let value = self.parsed_json.clone().ok_or_else(|| "Output is not JSON".to_string())?;
+        serde_json::from_value(value).map_err(|e| format!("Output does not deserialize: {}", e))
+    }
+}
+
+impl fmt::Display for AgentOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl From<AgentOutput> for String {
+    fn from(output: AgentOutput) -> Self {
+        output.text
+    }
+}
+
+// A tool call made during an agent call, including refused and failed ones
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolInvocation {
+    pub id: String,
+    pub tool: String,
+    pub arguments: String, // As run, after any approver edits
+    pub output: String, // What the model saw: the result, the error payload or the denial
+    pub success: bool,
+}
+
+// What a call has used so far, filled in as requests and tool calls complete
+#[derive(Debug, Default)]
+pub(crate) struct RunRecord {
+    stats: Mutex<RunStats>,
+}
+
+#[derive(Debug, Default)]
+struct RunStats {
+    usage: TokenUsage,
+    cost: Option<f64>,
+    tool_invocations: Vec<ToolInvocation>,
+    attempts: usize,
+    degraded: bool,
+}
+
+impl RunRecord {
+    pub(crate) fn add_usage(&self, usage: &TokenUsage, cost: Option<f64>) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.usage += *usage;
+        if let Some(cost) = cost {
+            stats.cost = Some(stats.cost.unwrap_or_default() + cost);
+        }
+    }
+
+    pub(crate) fn add_tool_invocation(&self, call: &ToolCallRequest, success: bool, output: &str) {
+        self.stats.lock().unwrap().tool_invocations.push(ToolInvocation {
+            id: call.id.clone(),
+            tool: call.function.name.clone(),
+            arguments: call.function.arguments.clone(),
+            output: output.to_string(),
+            success,
+        });
+    }
+
+    pub(crate) fn start_attempt(&self) {
+        self.stats.lock().unwrap().attempts += 1;
+    }
+
+    pub(crate) fn mark_degraded(&self) {
+        self.stats.lock().unwrap().degraded = true;
+    }
+
+    pub(crate) fn finish(&self, text: String, parsed_json: Option<Value>, duration: Duration) -> AgentOutput {
+        let stats = std::mem::take(&mut *self.stats.lock().unwrap());
+        AgentOutput {
+            text,
+            parsed_json,
+            cost: stats.cost,
+            tool_invocations: stats.tool_invocations,
+            token_usage: stats.usage,
+            attempts: stats.attempts,
+            duration,
+            degraded: stats.degraded,
+        }
+    }
+}
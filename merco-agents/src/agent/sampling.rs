@@ -0,0 +1,124 @@
+use merco_llmproxy::CompletionRequest;
+use std::fmt;
+
+// Sampling settings for one layer. Unset fields fall through to the layer below:
+// agent defaults < task overrides < call overrides
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SamplingParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl SamplingParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+// The layer a sampling value was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingSource {
+    Agent,
+    Task,
+    Call,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved<T> {
+    pub value: T,
+    pub source: SamplingSource,
+}
+
+// The sampling values actually sent with a request, and where each one came from.
+// Unset values are left to the provider's defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EffectiveSampling {
+    pub temperature: Option<Resolved<f32>>,
+    pub max_tokens: Option<Resolved<u32>>,
+    pub top_p: Option<Resolved<f32>>,
+    pub stop: Option<Resolved<Vec<String>>>,
+    pub seed: Option<Resolved<u64>>,
+}
+
+// Take the value from the highest layer that sets it
+fn pick<T: Clone>(layers: [(&Option<T>, SamplingSource); 3]) -> Option<Resolved<T>> {
+    layers
+        .into_iter()
+        .rev()
+        .find_map(|(value, source)| value.clone().map(|value| Resolved { value, source }))
+}
+
+impl EffectiveSampling {
+    pub fn resolve(agent: &SamplingParams, task: &SamplingParams, call: &SamplingParams) -> Self {
+        use SamplingSource::{Agent, Call, Task};
+        Self {
+            temperature: pick([(&agent.temperature, Agent), (&task.temperature, Task), (&call.temperature, Call)]),
+            max_tokens: pick([(&agent.max_tokens, Agent), (&task.max_tokens, Task), (&call.max_tokens, Call)]),
+            top_p: pick([(&agent.top_p, Agent), (&task.top_p, Task), (&call.top_p, Call)]),
+            stop: pick([(&agent.stop, Agent), (&task.stop, Task), (&call.stop, Call)]),
+            seed: pick([(&agent.seed, Agent), (&task.seed, Task), (&call.seed, Call)]),
+        }
+    }
+
+    pub fn apply(&self, request: &mut CompletionRequest) {
+        request.temperature = self.temperature.as_ref().map(|r| r.value);
+        request.max_tokens = self.max_tokens.as_ref().map(|r| r.value);
+        request.top_p = self.top_p.as_ref().map(|r| r.value);
+        request.stop = self.stop.as_ref().map(|r| r.value.clone());
+        request.seed = self.seed.as_ref().map(|r| r.value);
+    }
+}
+
+// e.g. "temperature=0.2 (task), max_tokens=1000 (agent), top_p=default"
+impl fmt::Display for EffectiveSampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn field<T: fmt::Debug>(f: &mut fmt::Formatter<'_>, name: &str, value: &Option<Resolved<T>>) -> fmt::Result {
+            match value {
+                Some(r) => write!(f, "{}={:?} ({:?})", name, r.value, r.source),
+                None => write!(f, "{}=default", name),
+            }
+        }
+        field(f, "temperature", &self.temperature)?;
+        f.write_str(", ")?;
+        field(f, "max_tokens", &self.max_tokens)?;
+        f.write_str(", ")?;
+        field(f, "top_p", &self.top_p)?;
+        f.write_str(", ")?;
+        field(f, "stop", &self.stop)?;
+        f.write_str(", ")?;
+        field(f, "seed", &self.seed)
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{Result, anyhow};
+use merco_llmproxy::{ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, ProviderError};
+use serde_json::Value;
+
+// Prompts authored in any language are translated into `working_language` before they
+// reach the model; free-text answers are translated into `output_language` afterwards
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Translation {
+    pub working_language: String,        // e.g. "English" for English-centric models
+    pub output_language: Option<String>, // None leaves answers in the working language
+    pub model: Option<String>,           // Model used for translating; defaults to the agent's
+}
+
+impl Translation {
+    pub fn new(working_language: impl Into<String>) -> Self {
+        Self {
+            working_language: working_language.into(),
+            output_language: None,
+            model: None,
+        }
+    }
+
+    pub fn with_output_language(mut self, language: impl Into<String>) -> Self {
+        self.output_language = Some(language.into());
+        self
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+}
+
+// Translate `texts` into `language` in a single request sent through `complete` (e.g. a
+// provider's `completion`), preserving order. Texts already in the target language come
+// back unchanged; empty texts are not sent.
+pub async fn translate_all(
+    complete: impl AsyncFnOnce(CompletionRequest) -> Result<CompletionResponse, ProviderError>,
+    model: &str,
+    texts: &[String],
+    language: &str,
+) -> Result<Vec<String>> {
+    let pending: Vec<&String> = texts.iter().filter(|t| !t.trim().is_empty()).collect();
+    if pending.is_empty() {
+        return Ok(texts.to_vec());
+    }
+
+    let request = CompletionRequest {
+        model: model.to_string(),
+        messages: vec![
+            ChatMessage::system(format!(
+                "You are a translator. Translate each string of the JSON array into {language}. \
+                 Keep code, JSON keys, identifiers, URLs, placeholders and Markdown formatting unchanged, \
+                 and return strings that are already in {language} as they are. \
+                 Reply with a JSON array of the translated strings only, in the same order."
+            )),
+            ChatMessage::user(serde_json::to_string(&pending)?),
+        ],
+        temperature: Some(0.0),
+        ..Default::default()
+    };
+
+    let reply = match complete(request).await.map_err(|e| anyhow!("Translation failed: {}", e))?.kind {
+        CompletionKind::Message { content } => content,
+        CompletionKind::ToolCall { .. } => return Err(anyhow!("Translator replied with a tool call")),
+    };
+    let mut translated = parse_translations(&reply, pending.len())?.into_iter();
+
+    Ok(texts
+        .iter()
+        .map(|t| if t.trim().is_empty() { t.clone() } else { translated.next().unwrap_or_default() })
+        .collect())
+}
+
+fn parse_translations(reply: &str, expected: usize) -> Result<Vec<String>> {
+    // Tolerate prose or code fences around the JSON array
+    let json = match (reply.find('['), reply.rfind(']')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err(anyhow!("Translator reply is not a JSON array: {}", reply)),
+    };
+    let value: Value = serde_json::from_str(json).map_err(|e| anyhow!("Translator reply is not JSON: {}", e))?;
+    let items: Vec<String> = value
+        .as_array()
+        .map(|items| items.iter().filter_map(|i| i.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    if items.len() != expected {
+        return Err(anyhow!("Translator returned {} strings, expected {}", items.len(), expected));
+    }
+    Ok(items)
+}
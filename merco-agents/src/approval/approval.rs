@@ -0,0 +1,383 @@
+use crate::session::session::new_run_id;
+use async_trait::async_trait;
+use merco_llmproxy::{HttpTransport, Transport, TransportRequest};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+// Default time a webhook approval waits for its resume call
+pub const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+// What a human is asked to approve
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ApprovalRequest {
+    pub subject: String, // Short label, e.g. "Output of task 2"
+    pub task: String,    // Description of the task that produced the output
+    pub output: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approve,
+    Reject { reason: Option<String> },
+    Revise { feedback: String }, // Run the task again with this feedback
+}
+
+// How approval requests reach a human and how their decisions come back. The same
+// crew can run behind any transport: a terminal prompt, a web service or an app.
+#[async_trait]
+pub trait ApprovalTransport: Send + Sync {
+    async fn request_approval(&self, request: ApprovalRequest) -> Result<ApprovalDecision, String>;
+}
+
+// Prompt on the terminal and read the decision from stdin
+#[derive(Debug, Default, Clone)]
+pub struct CliApproval;
+
+impl CliApproval {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+// "y"/"yes" approves, "n"/"no" rejects, anything else is revision feedback
+fn parse_cli_answer(answer: &str) -> ApprovalDecision {
+    match answer.trim().to_lowercase().as_str() {
+        "" | "y" | "yes" => ApprovalDecision::Approve,
+        "n" | "no" => ApprovalDecision::Reject { reason: None },
+        _ => ApprovalDecision::Revise { feedback: answer.trim().to_string() },
+    }
+}
+
+#[async_trait]
+impl ApprovalTransport for CliApproval {
+    async fn request_approval(&self, request: ApprovalRequest) -> Result<ApprovalDecision, String> {
+        // Stdin is blocking, keep it off the runtime threads
+        tokio::task::spawn_blocking(move || {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "\n=== Approval needed: {} ===", request.subject).map_err(|e| e.to_string())?;
+            writeln!(stdout, "Task: {}\n\n{}\n", request.task, request.output).map_err(|e| e.to_string())?;
+            write!(stdout, "Approve? [Y]es / [n]o / or type feedback for a revision: ").map_err(|e| e.to_string())?;
+            stdout.flush().map_err(|e| e.to_string())?;
+
+            let mut answer = String::new();
+            let read = std::io::stdin().lock().read_line(&mut answer).map_err(|e| e.to_string())?;
+            if read == 0 {
+                return Err("Stdin closed while waiting for approval".to_string());
+            }
+            Ok(parse_cli_answer(&answer))
+        })
+        .await
+        .map_err(|e| format!("Approval prompt failed: {}", e))?
+    }
+}
+
+// An approval waiting for an answer from the application
+#[derive(Debug)]
+pub struct PendingApproval {
+    pub request: ApprovalRequest,
+    responder: oneshot::Sender<ApprovalDecision>,
+}
+
+impl PendingApproval {
+    // Send the decision back to the waiting crew; false if the run has gone away
+    pub fn respond(self, decision: ApprovalDecision) -> bool {
+        self.responder.send(decision).is_ok()
+    }
+
+    pub fn approve(self) -> bool {
+        self.respond(ApprovalDecision::Approve)
+    }
+
+    pub fn reject(self, reason: Option<String>) -> bool {
+        self.respond(ApprovalDecision::Reject { reason })
+    }
+
+    pub fn revise(self, feedback: impl Into<String>) -> bool {
+        self.respond(ApprovalDecision::Revise { feedback: feedback.into() })
+    }
+}
+
+// Hand approvals to the embedding application over a tokio channel, e.g. for a GUI or
+// a service that already has its own request handling
+#[derive(Debug, Clone)]
+pub struct ChannelApproval {
+    sender: mpsc::Sender<PendingApproval>,
+}
+
+impl ChannelApproval {
+    // The receiver yields one `PendingApproval` per gate; answer each with `respond`
+    pub fn new(buffer: usize) -> (Self, mpsc::Receiver<PendingApproval>) {
+        let (sender, receiver) = mpsc::channel(buffer);
+        (Self { sender }, receiver)
+    }
+}
+
+#[async_trait]
+impl ApprovalTransport for ChannelApproval {
+    async fn request_approval(&self, request: ApprovalRequest) -> Result<ApprovalDecision, String> {
+        let (responder, decision) = oneshot::channel();
+        self.sender
+            .send(PendingApproval { request, responder })
+            .await
+            .map_err(|_| "Approval channel is closed".to_string())?;
+        decision
+            .await
+            .map_err(|_| "Approval was dropped without a decision".to_string())
+    }
+}
+
+// POST each request to a webhook together with a resume token. The run waits until the
+// web service calls `resume` with that token, typically from its own callback endpoint.
+//
+// Webhook body: {"token": "...", "request": {"subject", "task", "output"}}
+pub struct WebhookApproval {
+    url: String,
+    transport: Arc<dyn Transport>,
+    timeout: Duration,
+    pending: Mutex<HashMap<String, oneshot::Sender<ApprovalDecision>>>,
+}
+
+impl std::fmt::Debug for WebhookApproval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookApproval")
+            .field("url", &self.url)
+            .field("timeout", &self.timeout)
+            .field("pending", &self.pending.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl WebhookApproval {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            transport: Arc::new(HttpTransport::new()),
+            timeout: DEFAULT_APPROVAL_TIMEOUT,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Send webhooks through a custom transport (proxies, signing, tests)
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    // Fail the gate if no decision arrives within `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // Deliver the decision for `token`; errors if the token is unknown or already used
+    pub fn resume(&self, token: &str, decision: ApprovalDecision) -> Result<(), String> {
+        let responder = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(token)
+            .ok_or_else(|| format!("No pending approval for token '{}'", token))?;
+        responder
+            .send(decision)
+            .map_err(|_| format!("Approval '{}' is no longer waiting", token))
+    }
+
+    // Tokens of approvals that are still waiting for a decision
+    pub fn pending_tokens(&self) -> Vec<String> {
+        self.pending.lock().unwrap().keys().cloned().collect()
+    }
+
+    async fn post(&self, token: &str, request: &ApprovalRequest) -> Result<(), String> {
+        let body = serde_json::to_vec(&serde_json::json!({ "token": token, "request": request }))
+            .map_err(|e| e.to_string())?;
+        let mut webhook = TransportRequest {
+            method: "POST".to_string(),
+            url: self.url.clone(),
+            headers: Default::default(),
+            body,
+        };
+        webhook
+            .headers
+            .insert("content-type", "application/json".parse().map_err(|_| "Invalid header".to_string())?);
+        let response = self
+            .transport
+            .send(webhook)
+            .await
+            .map_err(|e| format!("Approval webhook failed: {}", e))?;
+        if !(200..300).contains(&response.status) {
+            return Err(format!("Approval webhook returned status {}", response.status));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ApprovalTransport for WebhookApproval {
+    async fn request_approval(&self, request: ApprovalRequest) -> Result<ApprovalDecision, String> {
+        let token = new_run_id();
+        let (responder, decision) = oneshot::channel();
+        self.pending.lock().unwrap().insert(token.clone(), responder);
+
+        let result = match self.post(&token, &request).await {
+            Ok(()) => match tokio::time::timeout(self.timeout, decision).await {
+                Ok(Ok(decision)) => Ok(decision),
+                Ok(Err(_)) => Err("Approval was dropped without a decision".to_string()),
+                Err(_) => Err(format!("No approval decision within {:?}", self.timeout)),
+            },
+            Err(e) => Err(e),
+        };
+        self.pending.lock().unwrap().remove(&token);
+        result
+    }
+}
+
+// What a `ToolApprover` decides about one tool call
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ToolApproval {
+    Approve,
+    Deny { message: String },   // Sent back to the model instead of running the tool
+    Edit { arguments: String }, // Run the tool with these JSON arguments instead
+}
+
+// Consulted before each tool call an agent makes. Closures `Fn(&str, &str) -> ToolApproval`
+// (tool name, JSON arguments) are approvers; wrap async checks with `async_tool_approver`.
+#[async_trait]
+pub trait ToolApprover: Send + Sync {
+    async fn approve_tool_call(&self, tool: &str, arguments: &str) -> ToolApproval;
+}
+
+#[async_trait]
+impl<F> ToolApprover for F
+where
+    F: Fn(&str, &str) -> ToolApproval + Send + Sync,
+{
+    async fn approve_tool_call(&self, tool: &str, arguments: &str) -> ToolApproval {
+        self(tool, arguments)
+    }
+}
+
+type ToolApprovalFuture = std::pin::Pin<Box<dyn std::future::Future<Output = ToolApproval> + Send>>;
+
+struct AsyncToolApprover<F>(F);
+
+#[async_trait]
+impl<F> ToolApprover for AsyncToolApprover<F>
+where
+    F: Fn(String, String) -> ToolApprovalFuture + Send + Sync,
+{
+    async fn approve_tool_call(&self, tool: &str, arguments: &str) -> ToolApproval {
+        (self.0)(tool.to_string(), arguments.to_string()).await
+    }
+}
+
+// Build an approver from an async closure taking the tool name and JSON arguments
+pub fn async_tool_approver<F, Fut>(f: F) -> Arc<dyn ToolApprover>
+where
+    F: Fn(String, String) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = ToolApproval> + Send + 'static,
+{
+    Arc::new(AsyncToolApprover(move |tool, arguments| Box::pin(f(tool, arguments)) as ToolApprovalFuture))
+}
+
+// Ask a human about tool calls through any `ApprovalTransport` (terminal, channel, webhook).
+// The request's `output` holds the JSON arguments. Approve runs the call; reject denies it;
+// revision feedback that is a JSON object replaces the arguments, any other feedback denies
+// the call and is passed to the model. Transport failures deny the call.
+#[derive(Clone)]
+pub struct HumanToolApprover {
+    transport: Arc<dyn ApprovalTransport>,
+}
+
+impl std::fmt::Debug for HumanToolApprover {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HumanToolApprover").field("transport", &"<ApprovalTransport>").finish()
+    }
+}
+
+impl HumanToolApprover {
+    pub fn new(transport: Arc<dyn ApprovalTransport>) -> Self {
+        Self { transport }
+    }
+}
+
+#[async_trait]
+impl ToolApprover for HumanToolApprover {
+    async fn approve_tool_call(&self, tool: &str, arguments: &str) -> ToolApproval {
+        let request = ApprovalRequest {
+            subject: format!("Tool call: {}", tool),
+            task: format!("The agent wants to run the tool '{}' with these arguments", tool),
+            output: arguments.to_string(),
+        };
+        match self.transport.request_approval(request).await {
+            Ok(ApprovalDecision::Approve) => ToolApproval::Approve,
+            Ok(ApprovalDecision::Reject { reason }) => ToolApproval::Deny {
+                message: reason.unwrap_or_else(|| "The user declined this tool call".to_string()),
+            },
+            Ok(ApprovalDecision::Revise { feedback }) => {
+                match serde_json::from_str::<serde_json::Value>(&feedback) {
+                    Ok(serde_json::Value::Object(_)) => ToolApproval::Edit { arguments: feedback },
+                    _ => ToolApproval::Deny { message: feedback },
+                }
+            }
+            Err(e) => ToolApproval::Deny { message: format!("Tool call could not be approved: {}", e) },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Answers every request with the same decision, or fails
+    struct FixedApproval(Result<ApprovalDecision, String>);
+
+    #[async_trait]
+    impl ApprovalTransport for FixedApproval {
+        async fn request_approval(&self, request: ApprovalRequest) -> Result<ApprovalDecision, String> {
+            assert_eq!(request.subject, "Tool call: shell");
+            assert_eq!(request.output, r#"{"cmd": "ls"}"#);
+            self.0.clone()
+        }
+    }
+
+    async fn human_decision(decision: Result<ApprovalDecision, String>) -> ToolApproval {
+        HumanToolApprover::new(Arc::new(FixedApproval(decision))).approve_tool_call("shell", r#"{"cmd": "ls"}"#).await
+    }
+
+    #[tokio::test]
+    async fn test_human_decisions_map_to_tool_approvals() {
+        assert_eq!(human_decision(Ok(ApprovalDecision::Approve)).await, ToolApproval::Approve);
+        assert_eq!(
+            human_decision(Ok(ApprovalDecision::Reject { reason: None })).await,
+            ToolApproval::Deny { message: "The user declined this tool call".to_string() }
+        );
+        assert_eq!(
+            human_decision(Ok(ApprovalDecision::Revise { feedback: r#"{"cmd": "pwd"}"#.to_string() })).await,
+            ToolApproval::Edit { arguments: r#"{"cmd": "pwd"}"#.to_string() }
+        );
+        assert_eq!(
+            human_decision(Ok(ApprovalDecision::Revise { feedback: "Use pwd instead".to_string() })).await,
+            ToolApproval::Deny { message: "Use pwd instead".to_string() }
+        );
+        assert_eq!(
+            human_decision(Err("timed out".to_string())).await,
+            ToolApproval::Deny { message: "Tool call could not be approved: timed out".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_parse_cli_answer() {
+        assert_eq!(parse_cli_answer("\n"), ApprovalDecision::Approve);
+        assert_eq!(parse_cli_answer("Yes"), ApprovalDecision::Approve);
+        assert_eq!(parse_cli_answer("n"), ApprovalDecision::Reject { reason: None });
+        assert_eq!(parse_cli_answer(" shorter please \n"), ApprovalDecision::Revise { feedback: "shorter please".to_string() });
+    }
+}
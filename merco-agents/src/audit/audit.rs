@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+// One tool invocation made by an agent. Arguments are only kept as a hash, so the
+// log can be shared without leaking what the model passed to tools.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ToolCallRecord {
+    pub tool: String,
+    pub arguments_hash: String, // See `hash_arguments`
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub error: Option<String>,
+    pub agent_id: Option<String>,
+    pub task_id: Option<String>,
+}
+
+// Stable 64-bit FNV-1a hash of a call's JSON arguments, as 16 hex digits. Equal
+// arguments always hash the same, across processes and releases.
+pub fn hash_arguments(arguments: &str) -> String {
+    let hash = arguments.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+// Where agents report their tool calls. Closures `Fn(&ToolCallRecord)` are sinks too.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: &ToolCallRecord);
+}
+
+impl<F> AuditSink for F
+where
+    F: Fn(&ToolCallRecord) + Send + Sync,
+{
+    fn record(&self, record: &ToolCallRecord) {
+        self(record)
+    }
+}
+
+// Aggregate numbers for one tool
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ToolStats {
+    pub calls: u64,
+    pub failures: u64,
+    pub total_duration_ms: u64,
+    pub max_duration_ms: u64,
+}
+
+impl ToolStats {
+    pub fn mean_duration_ms(&self) -> f64 {
+        if self.calls == 0 { 0.0 } else { self.total_duration_ms as f64 / self.calls as f64 }
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.calls == 0 { 0.0 } else { self.failures as f64 / self.calls as f64 }
+    }
+
+    fn add(&mut self, record: &ToolCallRecord) {
+        self.calls += 1;
+        self.failures += u64::from(!record.success);
+        self.total_duration_ms += record.duration_ms;
+        self.max_duration_ms = self.max_duration_ms.max(record.duration_ms);
+    }
+}
+
+// Per-tool stats over `records`, keyed by tool name
+pub fn tool_stats<'a>(records: impl IntoIterator<Item = &'a ToolCallRecord>) -> BTreeMap<String, ToolStats> {
+    let mut stats: BTreeMap<String, ToolStats> = BTreeMap::new();
+    for record in records {
+        stats.entry(record.tool.clone()).or_default().add(record);
+    }
+    stats
+}
+
+// Keeps every record in memory, e.g. for tests or to inspect a single run
+#[derive(Debug, Default)]
+pub struct InMemoryAudit {
+    records: Mutex<Vec<ToolCallRecord>>,
+}
+
+impl InMemoryAudit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn records(&self) -> Vec<ToolCallRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, ToolStats> {
+        tool_stats(self.records.lock().unwrap().iter())
+    }
+}
+
+impl AuditSink for InMemoryAudit {
+    fn record(&self, record: &ToolCallRecord) {
+        self.records.lock().unwrap().push(record.clone());
+    }
+}
+
+// Keeps only the aggregate stats, for long-running processes
+#[derive(Debug, Default)]
+pub struct StatsAudit {
+    stats: Mutex<BTreeMap<String, ToolStats>>,
+}
+
+impl StatsAudit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> BTreeMap<String, ToolStats> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+impl AuditSink for StatsAudit {
+    fn record(&self, record: &ToolCallRecord) {
+        self.stats.lock().unwrap().entry(record.tool.clone()).or_default().add(record);
+    }
+}
+
+// Appends one JSON object per record to a file (JSON Lines)
+#[derive(Debug)]
+pub struct JsonlAudit {
+    file: Mutex<File>,
+}
+
+impl JsonlAudit {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open audit log {}: {}", path.display(), e))?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+
+impl AuditSink for JsonlAudit {
+    fn record(&self, record: &ToolCallRecord) {
+        let Ok(mut line) = serde_json::to_string(record) else { return };
+        line.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            eprintln!("Failed to write audit record: {}", e);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use merco_agents::protocol::protocol::serve_stdio;
+
+const USAGE: &str = "Usage:
+  merco run --stdio   Serve the NDJSON event protocol on stdin/stdout
+  merco schema        Print the JSON Schemas of protocol requests and events";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // API keys are referenced by environment variable name in definitions
+    dotenv::dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["run", "--stdio"] => serve_stdio().await,
+        ["schema"] => print_schema(),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+fn print_schema() -> anyhow::Result<()> {
+    let schema = merco_agents::protocol::protocol::protocol_schema();
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+#[cfg(not(feature = "schema"))]
+fn print_schema() -> anyhow::Result<()> {
+    anyhow::bail!("merco was built without the `schema` feature")
+}
@@ -0,0 +1,897 @@
+use crate::agent::agent::{Agent, AgentLLMConfig};
+use crate::agent::budget::Budget;
+use crate::agent::callbacks::AgentCallbacks;
+use crate::agent::output::AgentOutput;
+use crate::agent::translation::Translation;
+use crate::approval::approval::{ApprovalDecision, ApprovalRequest, ApprovalTransport, ToolApprover};
+use crate::audit::audit::AuditSink;
+use crate::crew::manager::{Assignment, Delegation, Manager};
+use crate::crew::workspace::{Workspace, WorkspaceConfig};
+use crate::knowledge::knowledge::KnowledgeBase;
+use crate::memory::memory::Memory;
+use crate::task::degraded::DegradedFallback;
+use crate::task::task::{Task, TaskId};
+use futures::stream::FuturesUnordered;
+use futures::{StreamExt, TryStreamExt};
+use merco_llmproxy::{CancellationToken, Pricing, ProgressSink, TokenUsage, ToolOutputSink, ToolRegistry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+// A task assigned to one of the crew's agents (by index into `Crew::agents`)
+#[derive(Debug, Clone)]
+pub struct CrewTask {
+    pub task: Task,
+    pub agent_index: usize,
+    pub parallel: bool, // In the mixed workflow, runs alongside neighbouring parallel tasks
+}
+
+// How a crew gets through its tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Workflow {
+    // Each task runs on its assigned agent, in order, with the previous task's output as context
+    #[default]
+    Sequential,
+    // All tasks run at once, without context from each other
+    Parallel,
+    // Consecutive parallel tasks run at once, other tasks one at a time. Each task gets
+    // the outputs of the task or parallel group before it as context.
+    Mixed,
+    // A manager agent assigns the tasks, reviews the results and writes the final answer
+    Hierarchical,
+    // Each task runs once the tasks in its `context` are done and receives only their
+    // outputs; independent tasks run at once
+    Graph,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrewOutput {
+    pub task_outputs: Vec<String>,
+    pub final_output: String,
+    pub workspace: Option<PathBuf>, // Where the run's files were kept or archived, if any
+    pub task_usage: Vec<TaskUsage>, // One entry per task (per assignment when hierarchical)
+    pub usage: TokenUsage, // Total over all tasks
+    pub cost: Option<f64>, // Total of the priced tasks; `None` if no agent has pricing
+}
+
+impl CrewOutput {
+    // Output of a run that made no (recorded) LLM calls, e.g. a single remote task
+    pub fn new(task_outputs: Vec<String>, final_output: String) -> Self {
+        Self { task_outputs, final_output, workspace: None, task_usage: Vec::new(), usage: TokenUsage::default(), cost: None }
+    }
+
+    fn with_task_usage(mut self, task_usage: Vec<TaskUsage>) -> Self {
+        self.usage = task_usage.iter().fold(TokenUsage::default(), |total, task| total + task.usage);
+        self.cost = task_usage.iter().map(|task| task.cost).fold(None, add_costs);
+        self.task_usage = task_usage;
+        self
+    }
+}
+
+// Token usage and cost of one task, including approval revisions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TaskUsage {
+    pub task_id: String,
+    pub agent_id: Option<String>,
+    pub usage: TokenUsage,
+    pub cost: Option<f64>,
+}
+
+fn add_costs(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or_default() + b.unwrap_or_default()),
+    }
+}
+
+pub struct Crew {
+    pub agents: Vec<Agent>,
+    pub tasks: Vec<CrewTask>,
+    pub workspace: Option<WorkspaceConfig>,
+    pub cancellation: Option<CancellationToken>,
+    pub approval: Option<Arc<dyn ApprovalTransport>>, // Gate for tasks that require approval
+    pub degraded: Option<DegradedFallback>, // Used by tasks without their own fallback
+    pub budget: Option<Arc<Budget>>, // Shared by all agents, on top of their own budgets
+    pub workflow: Workflow,
+    pub max_concurrency: Option<usize>, // Tasks running at once in parallel workflows; unlimited if unset
+    pub manager: Option<Manager>, // Runs the hierarchical workflow
+    // Crew-wide settings, kept to apply to a manager set later
+    callbacks: Vec<Arc<dyn AgentCallbacks>>,
+    tool_progress: Option<ProgressSink>,
+    tool_output: Option<ToolOutputSink>,
+}
+
+impl std::fmt::Debug for Crew {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Crew")
+            .field("agents", &self.agents)
+            .field("tasks", &self.tasks)
+            .field("workspace", &self.workspace)
+            .field("cancellation", &self.cancellation)
+            .field("approval", &self.approval.as_ref().map(|_| "<ApprovalTransport>"))
+            .field("degraded", &self.degraded)
+            .field("budget", &self.budget)
+            .field("workflow", &self.workflow)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("manager", &self.manager)
+            .field("callbacks", &self.callbacks.len())
+            .field("tool_progress", &self.tool_progress.as_ref().map(|_| "<ProgressSink>"))
+            .field("tool_output", &self.tool_output.as_ref().map(|_| "<ToolOutputSink>"))
+            .finish()
+    }
+}
+
+impl Crew {
+    pub fn new(agents: Vec<Agent>) -> Self {
+        // Agents without an id are audited by their position in the crew
+        let agents = agents
+            .into_iter()
+            .enumerate()
+            .map(|(i, agent)| if agent.id.is_some() { agent } else { agent.with_id(format!("agent-{}", i)) })
+            .collect();
+        Self {
+            agents,
+            tasks: Vec::new(),
+            workspace: None,
+            cancellation: None,
+            approval: None,
+            degraded: None,
+            budget: None,
+            workflow: Workflow::default(),
+            max_concurrency: None,
+            manager: None,
+            callbacks: Vec::new(),
+            tool_progress: None,
+            tool_output: None,
+        }
+    }
+
+    // Assign a task to the agent at `agent_index` (builder style)
+    pub fn with_task(mut self, agent_index: usize, task: Task) -> Self {
+        self.tasks.push(CrewTask { task, agent_index, parallel: false });
+        self
+    }
+
+    // Like `with_task`, for a task the mixed workflow may run alongside its neighbours
+    pub fn with_parallel_task(mut self, agent_index: usize, task: Task) -> Self {
+        self.tasks.push(CrewTask { task, agent_index, parallel: true });
+        self
+    }
+
+    // Opt in to a memory shared by every agent, so findings from one agent are
+    // retrievable by the others during the run
+    pub fn with_shared_memory(mut self, memory: Arc<dyn Memory>) -> Self {
+        for agent in &mut self.agents {
+            agent.shared_memory = Some(memory.clone());
+        }
+        self
+    }
+
+    // Author the crew in any language; every agent translates its prompts into the
+    // working language and its text answers back
+    pub fn with_translation(mut self, translation: Translation) -> Self {
+        for agent in &mut self.agents {
+            agent.translation = Some(translation.clone());
+        }
+        self
+    }
+
+    // Give each run its own temporary directory, exposed to tools via `ToolContext`
+    pub fn with_workspace(mut self, config: WorkspaceConfig) -> Self {
+        self.workspace = Some(config);
+        self
+    }
+
+    // Stop the run when `token` is cancelled: in-flight LLM calls are aborted and no
+    // further tasks start
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        for agent in self.all_agents_mut() {
+            agent.cancellation = Some(token.clone());
+        }
+        self.cancellation = Some(token);
+        self
+    }
+
+    // Report progress of long-running tools from every agent (and the manager) to `sink`
+    pub fn with_tool_progress(mut self, sink: ProgressSink) -> Self {
+        for agent in self.all_agents_mut() {
+            agent.tool_progress = Some(sink.clone());
+        }
+        self.tool_progress = Some(sink);
+        self
+    }
+
+    // Report every successful tool result from every agent (and the manager) to `sink`
+    pub fn with_tool_output(mut self, sink: ToolOutputSink) -> Self {
+        for agent in self.all_agents_mut() {
+            agent.tool_output = Some(sink.clone());
+        }
+        self.tool_output = Some(sink);
+        self
+    }
+
+    // Attach `callbacks` to every agent (and the manager), after any callbacks they
+    // already have
+    pub fn with_callbacks(mut self, callbacks: Arc<dyn AgentCallbacks>) -> Self {
+        for agent in self.all_agents_mut() {
+            agent.callbacks.push(callbacks.clone());
+        }
+        self.callbacks.push(callbacks);
+        self
+    }
+
+    // Run tool calls of agents without their own registry against `registry`
+    pub fn with_tool_registry(mut self, registry: Arc<ToolRegistry>) -> Self {
+        self.agents = self
+            .agents
+            .into_iter()
+            .map(|agent| if agent.tool_registry.is_some() { agent } else { agent.with_tool_registry(registry.clone()) })
+            .collect();
+        self
+    }
+
+    // Ask `approver` before tool calls of agents that have no approver of their own
+    pub fn with_tool_approver(mut self, approver: Arc<dyn ToolApprover>) -> Self {
+        self.agents = self
+            .agents
+            .into_iter()
+            .map(|agent| if agent.tool_approver.is_some() { agent } else { agent.with_tool_approver(approver.clone()) })
+            .collect();
+        self
+    }
+
+    // Give agents without knowledge of their own access to `knowledge`
+    pub fn with_knowledge(mut self, knowledge: Arc<KnowledgeBase>) -> Self {
+        self.agents = self
+            .agents
+            .into_iter()
+            .map(|agent| if agent.knowledge.is_some() { agent } else { agent.with_knowledge(knowledge.clone()) })
+            .collect();
+        self
+    }
+
+    // Record the tool calls of agents without their own audit sink to `sink`
+    pub fn with_audit_sink(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.agents = self
+            .agents
+            .into_iter()
+            .map(|agent| if agent.audit.is_some() { agent } else { agent.with_audit_sink(sink.clone()) })
+            .collect();
+        self
+    }
+
+    // Price calls to `model` for agents without pricing of their own, so the run's
+    // output reports its cost
+    pub fn with_model_pricing(mut self, model: &str, input_per_million: f64, output_per_million: f64) -> Self {
+        for agent in self.all_agents_mut() {
+            if agent.pricing.is_none() && agent.llm_config().model_name() == model {
+                agent.pricing = Some(Pricing { input_per_million, output_per_million });
+            }
+        }
+        self
+    }
+
+    // Limit the spending of all agents together; a request that would go over it fails
+    // its task (or switches model, as the budget's handler decides). `budget.exceeded()`
+    // tells a budget abort apart from other failures.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        let budget = Arc::new(budget);
+        for agent in self.all_agents_mut() {
+            agent.budgets.push(budget.clone());
+        }
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn with_workflow(mut self, workflow: Workflow) -> Self {
+        self.workflow = workflow;
+        self
+    }
+
+    // Run at most `limit` tasks at once in the parallel and mixed workflows
+    pub fn with_max_concurrency(mut self, limit: usize) -> Self {
+        self.max_concurrency = Some(limit.max(1));
+        self
+    }
+
+    // Switch to the hierarchical workflow, managed by an agent on `llm_config`
+    pub fn with_manager(self, llm_config: AgentLLMConfig) -> Self {
+        self.with_manager_agent(Manager::default_agent(llm_config))
+    }
+
+    // Switch to the hierarchical workflow, managed by `agent`. It is given a tool to
+    // delegate work to the crew's agents; other tools it uses must be in its own registry.
+    pub fn with_manager_agent(mut self, mut agent: Agent) -> Self {
+        agent.id.get_or_insert_with(|| "manager".to_string());
+        if let Some(token) = &self.cancellation {
+            agent.cancellation = Some(token.clone());
+        }
+        agent.budgets.extend(self.budget.clone());
+        agent.callbacks.extend(self.callbacks.iter().cloned());
+        if let Some(sink) = &self.tool_progress {
+            agent.tool_progress = Some(sink.clone());
+        }
+        if let Some(sink) = &self.tool_output {
+            agent.tool_output = Some(sink.clone());
+        }
+        let workers = self.agents.iter().filter_map(|agent| agent.id.clone()).collect();
+        self.manager = Some(Manager::new(agent, workers));
+        self.workflow = Workflow::Hierarchical;
+        self
+    }
+
+    fn all_agents_mut(&mut self) -> impl Iterator<Item = &mut Agent> {
+        self.agents.iter_mut().chain(self.manager.as_mut().map(|manager| &mut manager.agent))
+    }
+
+    // Route approvals for tasks marked `requires_approval` through `transport`
+    pub fn with_approval_transport(mut self, transport: Arc<dyn ApprovalTransport>) -> Self {
+        self.approval = Some(transport);
+        self
+    }
+
+    // Degraded mode: when the provider is unavailable, tasks without their own fallback
+    // resolve with `fallback` instead of failing the run
+    pub fn with_degraded_mode(mut self, fallback: DegradedFallback) -> Self {
+        self.degraded = Some(fallback);
+        self
+    }
+
+    // Warm up every agent's provider concurrently, e.g. when an interactive service starts
+    pub async fn warmup(&self) -> Result<(), String> {
+        futures::future::join_all(self.agents.iter().map(|agent| agent.warmup()))
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    pub async fn run(&self) -> Result<CrewOutput, String> {
+        let Some(config) = &self.workspace else {
+            return self.run_workflow(None).await;
+        };
+
+        let workspace = Workspace::create(config.clone())?;
+        let result = workspace
+            .tool_context()
+            .scope(self.run_workflow(Some(&workspace)))
+            .await;
+        // Clean up even when the run failed
+        let kept_at = workspace.finish();
+        let mut output = result?;
+        output.workspace = kept_at?;
+        Ok(output)
+    }
+
+    async fn run_workflow(&self, workspace: Option<&Workspace>) -> Result<CrewOutput, String> {
+        match self.workflow {
+            Workflow::Hierarchical => self.run_hierarchical(workspace).await,
+            Workflow::Graph => self.run_graph(workspace).await,
+            _ => self.run_stages(workspace).await,
+        }
+    }
+
+    // Indices of the tasks that run together, stage by stage
+    fn stages(&self) -> Vec<Vec<usize>> {
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        for (i, crew_task) in self.tasks.iter().enumerate() {
+            let joins_previous = match self.workflow {
+                Workflow::Parallel => true,
+                Workflow::Mixed => crew_task.parallel && i > 0 && self.tasks[i - 1].parallel,
+                _ => false,
+            };
+            match stages.last_mut() {
+                Some(stage) if joins_previous => stage.push(i),
+                _ => stages.push(vec![i]),
+            }
+        }
+        stages
+    }
+
+    // Run the tasks stage by stage; the tasks of a stage run concurrently and each
+    // receives the outputs of the previous stage as context
+    async fn run_stages(&self, workspace: Option<&Workspace>) -> Result<CrewOutput, String> {
+        let mut task_outputs: Vec<String> = Vec::new();
+        let mut task_usage = Vec::new();
+        let mut context: Vec<String> = Vec::new();
+
+        for stage in self.stages() {
+            let limit = self.max_concurrency.unwrap_or(stage.len()).max(1);
+            let runs = stage.into_iter().map(|i| self.run_task(i, stage_context(&context)));
+            // Results come back in task order; the first failure stops the stage
+            let results: Vec<(String, TaskUsage)> = futures::stream::iter(runs)
+                .buffered(limit)
+                .try_collect()
+                .await?;
+            context.clear();
+            for (output, usage) in results {
+                context.push(output.clone());
+                task_outputs.push(output);
+                task_usage.push(usage);
+            }
+
+            if let Some(workspace) = workspace {
+                workspace.check_size()?;
+            }
+        }
+
+        let final_output = context.join("\n\n");
+        Ok(CrewOutput::new(task_outputs, final_output).with_task_usage(task_usage))
+    }
+
+    // Run the tasks as a dependency graph: a task starts once every task in its `context`
+    // has finished, and its prompt gets exactly those outputs
+    async fn run_graph(&self, workspace: Option<&Workspace>) -> Result<CrewOutput, String> {
+        let ids: Vec<TaskId> = (0..self.tasks.len()).map(|i| self.task_id(i)).collect();
+        let dependencies = self.dependencies(&ids)?;
+        let limit = self.max_concurrency.unwrap_or(usize::MAX).max(1);
+        let mut results: Vec<Option<(String, TaskUsage)>> = vec![None; self.tasks.len()];
+        let mut started = vec![false; self.tasks.len()];
+        let mut running = FuturesUnordered::new();
+
+        loop {
+            for i in 0..self.tasks.len() {
+                if running.len() >= limit {
+                    break;
+                }
+                if started[i] || dependencies[i].iter().any(|&d| results[d].is_none()) {
+                    continue;
+                }
+                started[i] = true;
+                let context = dependencies[i]
+                    .iter()
+                    .filter_map(|&d| results[d].as_ref().map(|(output, _)| format!("Output of task '{}':\n{}", ids[d], output)))
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                running.push(async move { (i, self.run_task(i, context).await) });
+            }
+            // Dependencies were checked for cycles, so this only ends once every task ran
+            let Some((i, result)) = running.next().await else {
+                break;
+            };
+            results[i] = Some(result?);
+            if let Some(workspace) = workspace {
+                workspace.check_size()?;
+            }
+        }
+
+        // Tasks no other task depends on make up the final output
+        let mut final_outputs = Vec::new();
+        let mut task_outputs = Vec::new();
+        let mut task_usage = Vec::new();
+        for (i, result) in results.into_iter().enumerate() {
+            let Some((output, usage)) = result else { continue };
+            if !dependencies.iter().any(|upstream| upstream.contains(&i)) {
+                final_outputs.push(output.clone());
+            }
+            task_outputs.push(output);
+            task_usage.push(usage);
+        }
+        Ok(CrewOutput::new(task_outputs, final_outputs.join("\n\n")).with_task_usage(task_usage))
+    }
+
+    fn task_id(&self, i: usize) -> TaskId {
+        self.tasks[i].task.id.clone().unwrap_or_else(|| format!("task-{}", i))
+    }
+
+    // Indices of the tasks each task depends on, after checking that every referenced id
+    // exists and that the dependencies are acyclic (Kahn's algorithm)
+    fn dependencies(&self, ids: &[TaskId]) -> Result<Vec<Vec<usize>>, String> {
+        let mut index = HashMap::new();
+        for (i, id) in ids.iter().enumerate() {
+            if index.insert(id.as_str(), i).is_some() {
+                return Err(format!("Duplicate task id '{}'", id));
+            }
+        }
+        let dependencies = self
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(i, crew_task)| {
+                crew_task
+                    .task
+                    .context
+                    .iter()
+                    .map(|upstream| {
+                        index
+                            .get(upstream.as_str())
+                            .copied()
+                            .ok_or_else(|| format!("Task '{}' depends on unknown task '{}'", ids[i], upstream))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut waiting: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+        let mut ready: Vec<usize> = (0..ids.len()).filter(|&i| waiting[i] == 0).collect();
+        let mut sorted = 0;
+        while let Some(done) = ready.pop() {
+            sorted += 1;
+            for (i, upstream) in dependencies.iter().enumerate() {
+                for _ in upstream.iter().filter(|&&d| d == done) {
+                    waiting[i] -= 1;
+                    if waiting[i] == 0 {
+                        ready.push(i);
+                    }
+                }
+            }
+        }
+        if sorted < ids.len() {
+            let cycle: Vec<&str> = (0..ids.len()).filter(|&i| waiting[i] > 0).map(|i| ids[i].as_str()).collect();
+            return Err(format!("Task dependencies form a cycle among: {}", cycle.join(", ")));
+        }
+        Ok(dependencies)
+    }
+
+    async fn run_task(&self, i: usize, context: String) -> Result<(String, TaskUsage), String> {
+        if self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err(format!("Crew run was cancelled before task {}", i));
+        }
+        let crew_task = &self.tasks[i];
+        let agent = self.agents.get(crew_task.agent_index).ok_or_else(|| {
+            format!("Task {} is assigned to unknown agent index {}", i, crew_task.agent_index)
+        })?;
+
+        let mut task = crew_task.task.clone();
+        task.id.get_or_insert_with(|| format!("task-{}", i));
+        if task.degraded.is_none() {
+            task.degraded = self.degraded.clone();
+        }
+        if !context.is_empty() {
+            task.description = format!("{}\n\n{}", task.description, context);
+        }
+
+        let output = agent
+            .call(task.clone())
+            .await
+            .map_err(|e| format!("Task {} failed: {}", i, e))?;
+        let task_id = task.id.clone().unwrap_or_default();
+        let output = self.approve(i, agent, task, output).await?;
+        let usage = TaskUsage { task_id, agent_id: agent.id.clone(), usage: output.token_usage, cost: output.cost };
+        Ok((output.text, usage))
+    }
+
+    // Let the manager work through the tasks: every `delegate_task` call it makes runs on
+    // the chosen agent while the manager waits for the result, and its final reply is the
+    // crew's final output
+    async fn run_hierarchical(&self, workspace: Option<&Workspace>) -> Result<CrewOutput, String> {
+        let manager = self
+            .manager
+            .as_ref()
+            .ok_or_else(|| "The hierarchical workflow needs a manager agent (see `Crew::with_manager`)".to_string())?;
+        let (sender, mut delegations) = mpsc::unbounded_channel::<Delegation>();
+        let run = manager.start_run(sender)?;
+
+        let managing = async {
+            let result = manager.agent.call(self.manager_task()).await;
+            drop(run);
+            result
+        };
+        let mut task_outputs = Vec::new();
+        let mut task_usage = Vec::new();
+        let mut stopped = None;
+        let delegating = async {
+            while let Some(Delegation { assignment, reply }) = delegations.recv().await {
+                if let Some(reason) = &stopped {
+                    let _ = reply.send(Err(format!("The crew run has stopped: {}", reason)));
+                    continue;
+                }
+                let result = self.delegate(task_usage.len(), &assignment).await.map(|(task_id, agent_id, output)| {
+                    task_usage.push(TaskUsage { task_id, agent_id, usage: output.token_usage, cost: output.cost });
+                    task_outputs.push(output.text.clone());
+                    output.text
+                });
+                if let Some(Err(e)) = workspace.map(Workspace::check_size) {
+                    stopped = Some(e);
+                }
+                // A failed assignment goes back to the manager, which may hand it to someone else
+                let _ = reply.send(result);
+            }
+        };
+        let (result, ()) = futures::join!(managing, delegating);
+        if let Some(reason) = stopped {
+            return Err(reason);
+        }
+        let output = result.map_err(|e| format!("Manager failed: {}", e))?;
+        task_usage.push(TaskUsage {
+            task_id: "manager".to_string(),
+            agent_id: manager.agent.id.clone(),
+            usage: output.token_usage,
+            cost: output.cost,
+        });
+        Ok(CrewOutput::new(task_outputs, output.text).with_task_usage(task_usage))
+    }
+
+    // The manager's brief: who is in the crew and what needs doing
+    fn manager_task(&self) -> Task {
+        let mut description = "Get the following tasks done by delegating them to the agents of your crew with the \
+            `delegate_task` tool. Review every result: delegate again with feedback when it needs changes, or to \
+            another agent when it failed. Once all the work is done, reply with the final answer, combining the \
+            results.\n\nAgents:"
+            .to_string();
+        for agent in &self.agents {
+            description.push_str(&format!("\n- {}: {}", agent.id.as_deref().unwrap_or_default(), agent.backstory));
+            if !agent.goals.is_empty() {
+                description.push_str(&format!(" Goals: {}", agent.goals.join("; ")));
+            }
+        }
+        description.push_str("\n\nTasks:");
+        for (i, crew_task) in self.tasks.iter().enumerate() {
+            description.push_str(&format!("\n{}. {}", i, crew_task.task.description));
+            if let Some(agent) = self.agents.get(crew_task.agent_index).and_then(|agent| agent.id.as_deref()) {
+                description.push_str(&format!(" (suggested agent: {})", agent));
+            }
+        }
+        let mut task = Task::new(description, None);
+        task.id = Some("manager".to_string());
+        task
+    }
+
+    // Carry out one assignment of the manager; returns the task id, the agent id and the
+    // (approved) output
+    async fn delegate(&self, number: usize, assignment: &Assignment) -> Result<(String, Option<String>, AgentOutput), String> {
+        if self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err("Crew run was cancelled".to_string());
+        }
+        let agent = self
+            .agents
+            .iter()
+            .find(|agent| agent.id.as_deref() == Some(assignment.agent.as_str()))
+            .ok_or_else(|| format!("Unknown agent '{}'", assignment.agent))?;
+        let (index, mut task) = match assignment.task {
+            Some(index) => {
+                let crew_task = self.tasks.get(index).ok_or_else(|| format!("Unknown task number {}", index))?;
+                let mut task = crew_task.task.clone();
+                task.id.get_or_insert_with(|| format!("task-{}", index));
+                task.description =
+                    format!("{}\n\nInstructions from your manager:\n{}", task.description, assignment.instructions);
+                (index, task)
+            }
+            None => {
+                let mut task = Task::new(assignment.instructions.clone(), None);
+                task.id = Some(format!("delegation-{}", number));
+                (number, task)
+            }
+        };
+        if task.degraded.is_none() {
+            task.degraded = self.degraded.clone();
+        }
+        let output = agent.call(task.clone()).await.map_err(|e| format!("Task {} failed: {}", index, e))?;
+        let task_id = task.id.clone().unwrap_or_default();
+        let output = self.approve(index, agent, task, output).await?;
+        Ok((task_id, agent.id.clone(), output))
+    }
+
+    // Hold a task's output until a human approves it, re-running the task with their
+    // feedback for as long as they ask for revisions
+    async fn approve(&self, index: usize, agent: &Agent, task: Task, mut output: AgentOutput) -> Result<AgentOutput, String> {
+        if !task.requires_approval {
+            return Ok(output);
+        }
+        let transport = self
+            .approval
+            .as_ref()
+            .ok_or_else(|| format!("Task {} requires approval, but the crew has no approval transport", index))?;
+
+        loop {
+            let request = ApprovalRequest {
+                subject: format!("Output of task {}", index),
+                task: task.description.clone(),
+                output: output.text.clone(),
+            };
+            match transport.request_approval(request).await? {
+                ApprovalDecision::Approve => return Ok(output),
+                ApprovalDecision::Reject { reason } => {
+                    return Err(format!(
+                        "Task {} output was rejected{}",
+                        index,
+                        reason.map(|r| format!(": {}", r)).unwrap_or_default()
+                    ));
+                }
+                ApprovalDecision::Revise { feedback } => {
+                    let mut revision = task.clone();
+                    revision.description = format!(
+                        "{}\n\nYour previous answer:\n{}\n\nA reviewer asked for changes:\n{}",
+                        task.description, output.text, feedback
+                    );
+                    let revised = agent
+                        .call(revision)
+                        .await
+                        .map_err(|e| format!("Task {} revision failed: {}", index, e))?;
+                    // The task's usage covers every revision
+                    output = AgentOutput {
+                        token_usage: output.token_usage + revised.token_usage,
+                        cost: add_costs(output.cost, revised.cost),
+                        ..revised
+                    };
+                }
+            }
+        }
+    }
+}
+
+// Context handed to the tasks of a stage: the previous stage's outputs
+fn stage_context(previous: &[String]) -> String {
+    match previous {
+        [] => String::new(),
+        [previous] => format!("Context from the previous task:\n{}", previous),
+        previous => format!("Context from the previous tasks:\n{}", previous.join("\n\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crew::manager::DELEGATE_TOOL;
+    use merco_llmproxy::{CompletionRequest, LlmConfig, MockProvider, Provider, Tool};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn mock_agent(id: &str, provider: MockProvider) -> Agent {
+        let llm_config = AgentLLMConfig::new(LlmConfig::new(Provider::Ollama), "mock".to_string(), 0.0, 100);
+        Agent::new(llm_config, format!("You are {}.", id), Vec::new(), Vec::new())
+            .with_id(id)
+            .with_provider(Arc::new(provider))
+    }
+
+    fn task(id: &str, context: &[&str]) -> Task {
+        context.iter().fold(Task::new(format!("Do {}", id), None).with_id(id), |task, upstream| task.with_context(*upstream))
+    }
+
+    #[derive(Default)]
+    struct FinishCounter(AtomicUsize);
+
+    impl AgentCallbacks for FinishCounter {
+        fn on_finish(&self, _result: &Result<AgentOutput, String>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Tracks how many agent calls are running at once
+    #[derive(Default)]
+    struct InFlight {
+        current: AtomicUsize,
+        max: AtomicUsize,
+    }
+
+    impl AgentCallbacks for InFlight {
+        fn on_llm_request(&self, _request: &CompletionRequest) {
+            let current = self.current.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max.fetch_max(current, Ordering::SeqCst);
+        }
+
+        fn on_finish(&self, _result: &Result<AgentOutput, String>) {
+            self.current.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    fn graph(tasks: Vec<Task>) -> Crew {
+        tasks.into_iter().fold(Crew::new(vec![mock_agent("worker", MockProvider::new())]), |crew, task| crew.with_task(0, task))
+    }
+
+    fn graph_ids(crew: &Crew) -> Vec<TaskId> {
+        (0..crew.tasks.len()).map(|i| crew.task_id(i)).collect()
+    }
+
+    #[test]
+    fn test_dependencies_of_a_diamond() {
+        let crew = graph(vec![task("join", &["left", "right"]), task("left", &["root"]), task("right", &["root"]), task("root", &[])]);
+        assert_eq!(crew.dependencies(&graph_ids(&crew)).unwrap(), vec![vec![1, 2], vec![3], vec![3], vec![]]);
+    }
+
+    #[test]
+    fn test_dependency_errors() {
+        let crew = graph(vec![task("a", &["c"]), task("b", &["a"]), task("c", &["b"]), task("d", &[])]);
+        assert_eq!(crew.dependencies(&graph_ids(&crew)).unwrap_err(), "Task dependencies form a cycle among: a, b, c");
+
+        let crew = graph(vec![task("a", &[]), task("b", &["missing"])]);
+        assert_eq!(crew.dependencies(&graph_ids(&crew)).unwrap_err(), "Task 'b' depends on unknown task 'missing'");
+
+        let crew = graph(vec![task("a", &[]), task("a", &[])]);
+        assert_eq!(crew.dependencies(&graph_ids(&crew)).unwrap_err(), "Duplicate task id 'a'");
+
+        // Tasks without an id are referred to by position
+        let crew = graph(vec![Task::new("First".to_string(), None), task("b", &["task-0"])]);
+        assert_eq!(crew.dependencies(&graph_ids(&crew)).unwrap(), vec![vec![], vec![0]]);
+    }
+
+    #[tokio::test]
+    async fn test_graph_runs_a_diamond_in_dependency_order() {
+        let provider = Arc::new(
+            MockProvider::new().with_message("root done").with_message("left done").with_message("right done").with_message("joined"),
+        );
+        let agent = mock_agent("worker", MockProvider::new()).with_provider(provider.clone());
+        let crew = Crew::new(vec![agent])
+            .with_task(0, task("join", &["left", "right"]))
+            .with_task(0, task("left", &["root"]))
+            .with_task(0, task("right", &["root"]))
+            .with_task(0, task("root", &[]))
+            .with_workflow(Workflow::Graph)
+            .with_max_concurrency(1);
+
+        let output = crew.run().await.unwrap();
+        assert_eq!(output.final_output, "joined");
+        assert_eq!(output.task_outputs, vec!["joined", "left done", "right done", "root done"]);
+
+        let prompts: Vec<String> = provider.requests().iter().map(|r| format!("{:?}", r.messages)).collect();
+        assert!(prompts[0].contains("Do root"));
+        assert!(prompts[1].contains("Output of task 'root':\\nroot done"), "{}", prompts[1]);
+        assert!(prompts[3].contains("Output of task 'left':\\nleft done") && prompts[3].contains("right done"));
+        assert!(!prompts[3].contains("root done"));
+    }
+
+    #[tokio::test]
+    async fn test_graph_and_stages_respect_the_concurrency_limit() {
+        for workflow in [Workflow::Graph, Workflow::Parallel] {
+            let in_flight = Arc::new(InFlight::default());
+            let provider = (0..5).fold(MockProvider::new().with_latency(Duration::from_millis(30)), |p, i| p.with_message(format!("out {}", i)));
+            let crew = (0..5)
+                .fold(Crew::new(vec![mock_agent("worker", provider)]), |crew, i| crew.with_task(0, task(&format!("t{}", i), &[])))
+                .with_workflow(workflow)
+                .with_max_concurrency(2)
+                .with_callbacks(in_flight.clone());
+
+            assert_eq!(crew.run().await.unwrap().task_outputs.len(), 5);
+            assert_eq!(in_flight.max.load(Ordering::SeqCst), 2, "{:?}", workflow);
+        }
+    }
+
+    #[test]
+    fn test_stages() {
+        let crew = Crew::new(vec![mock_agent("worker", MockProvider::new())])
+            .with_task(0, task("a", &[]))
+            .with_parallel_task(0, task("b", &[]))
+            .with_parallel_task(0, task("c", &[]))
+            .with_task(0, task("d", &[]))
+            .with_parallel_task(0, task("e", &[]));
+
+        assert_eq!(crew.stages(), vec![vec![0], vec![1], vec![2], vec![3], vec![4]]);
+        let crew = crew.with_workflow(Workflow::Mixed);
+        assert_eq!(crew.stages(), vec![vec![0], vec![1, 2], vec![3], vec![4]]);
+        let crew = crew.with_workflow(Workflow::Parallel);
+        assert_eq!(crew.stages(), vec![vec![0, 1, 2, 3, 4]]);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_hierarchical_run_frees_the_manager() {
+        let manager = mock_agent("manager", MockProvider::new().with_latency(Duration::from_millis(200)).with_message("done"));
+        let crew = Crew::new(vec![mock_agent("writer", MockProvider::new())])
+            .with_task(0, Task::new("Write a haiku".to_string(), None))
+            .with_manager_agent(manager);
+
+        assert!(tokio::time::timeout(Duration::from_millis(20), crew.run()).await.is_err());
+        assert_eq!(crew.run().await.unwrap().final_output, "done");
+    }
+
+    #[tokio::test]
+    async fn test_crew_callbacks_reach_the_manager() {
+        let counter = Arc::new(FinishCounter::default());
+        let manager = mock_agent("manager", MockProvider::new().with_message("done"));
+        let crew = Crew::new(vec![mock_agent("writer", MockProvider::new())])
+            .with_task(0, Task::new("Write a haiku".to_string(), None))
+            .with_callbacks(counter.clone())
+            .with_manager_agent(manager);
+
+        crew.run().await.unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_manager_has_one_delegate_tool() {
+        let search = Tool {
+            name: "search".to_string(),
+            description: "Search the web".to_string(),
+            parameters: merco_llmproxy::JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        let mut with_tools = mock_agent("lead", MockProvider::new());
+        with_tools.tools.push(search);
+
+        for manager in [mock_agent("manager", MockProvider::new()), with_tools] {
+            let crew = Crew::new(vec![mock_agent("writer", MockProvider::new())]).with_manager_agent(manager);
+            let tools = &crew.manager.as_ref().unwrap().agent.tools;
+            assert_eq!(tools.iter().filter(|t| t.name == DELEGATE_TOOL).count(), 1, "{:?}", tools);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::agent::agent::{Agent, AgentLLMConfig};
+use merco_llmproxy::{JsonSchema, Tool, ToolRegistry, async_executor};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+pub const DELEGATE_TOOL: &str = "delegate_task";
+
+const MANAGER_BACKSTORY: &str = "You are the manager of a crew of agents. You don't do the work yourself: you \
+    split it into assignments, give each to the agent best suited for it, check what comes back and combine \
+    the results into the final answer.";
+
+// Arguments of a `delegate_task` call
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Assignment {
+    pub agent: String,
+    pub task: Option<usize>, // Index of the crew task being worked on, if any
+    pub instructions: String,
+}
+
+// An assignment waiting for the crew run to carry it out
+pub(crate) struct Delegation {
+    pub assignment: Assignment,
+    pub reply: oneshot::Sender<Result<String, String>>,
+}
+
+// The agent that runs a hierarchical crew. It hands out work through the delegate tool,
+// whose calls are forwarded to the crew run currently in progress.
+pub struct Manager {
+    pub agent: Agent,
+    delegations: Arc<Mutex<Option<mpsc::UnboundedSender<Delegation>>>>,
+}
+
+impl std::fmt::Debug for Manager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Manager").field("agent", &self.agent).finish_non_exhaustive()
+    }
+}
+
+impl Manager {
+    // Give `agent` the delegate tool; `workers` are the ids it may assign work to
+    pub(crate) fn new(agent: Agent, workers: Vec<String>) -> Self {
+        let delegations: Arc<Mutex<Option<mpsc::UnboundedSender<Delegation>>>> = Arc::default();
+        let tool = delegate_tool(workers);
+        let slot = delegations.clone();
+        let executor = async_executor(move |arguments: String| {
+            let sender = slot.lock().unwrap().clone();
+            async move {
+                let assignment: Assignment =
+                    serde_json::from_str(&arguments).map_err(|e| format!("Invalid delegate_task arguments: {}", e))?;
+                let sender = sender.ok_or_else(|| "No crew run is in progress".to_string())?;
+                let (reply, result) = oneshot::channel();
+                sender
+                    .send(Delegation { assignment, reply })
+                    .map_err(|_| "The crew run has finished".to_string())?;
+                result.await.map_err(|_| "The crew run has finished".to_string())?
+            }
+        });
+        // Any other tools of the manager must be in its own registry, which replaces the
+        // global one for it
+        let mut registry = agent.tool_registry.as_deref().cloned().unwrap_or_else(ToolRegistry::new);
+        registry.register(tool.clone(), executor);
+        let mut agent = agent.with_tool_registry(Arc::new(registry));
+        // An agent without tools of its own was just given every registry tool, this one included
+        if !agent.tools.iter().any(|t| t.name == DELEGATE_TOOL) {
+            agent.tools.push(tool);
+        }
+        Self { agent, delegations }
+    }
+
+    // Route delegations to `sender` until the returned guard is dropped
+    pub(crate) fn start_run(&self, sender: mpsc::UnboundedSender<Delegation>) -> Result<RunGuard<'_>, String> {
+        let mut slot = self.delegations.lock().unwrap();
+        if slot.is_some() {
+            return Err("The manager is already running this crew".to_string());
+        }
+        *slot = Some(sender);
+        Ok(RunGuard { delegations: &self.delegations })
+    }
+
+    pub(crate) fn default_agent(llm_config: AgentLLMConfig) -> Agent {
+        Agent::new(llm_config, MANAGER_BACKSTORY.to_string(), Vec::new(), Vec::new())
+    }
+}
+
+// Ends a run when dropped, also when the run is cancelled or times out: the run's sender
+// is dropped, so its receiver ends once the last delegation is answered, and the manager
+// is free for the next run
+pub(crate) struct RunGuard<'a> {
+    delegations: &'a Mutex<Option<mpsc::UnboundedSender<Delegation>>>,
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        self.delegations.lock().unwrap().take();
+    }
+}
+
+fn delegate_tool(workers: Vec<String>) -> Tool {
+    let mut properties = serde_json::Map::new();
+    properties.insert(
+        "agent".to_string(),
+        json!({ "type": "string", "enum": workers, "description": "Id of the agent to assign the work to" }),
+    );
+    properties.insert(
+        "task".to_string(),
+        json!({
+            "type": "integer",
+            "description": "Number of the crew task this assignment completes, so its output requirements apply; omit for other work"
+        }),
+    );
+    properties.insert(
+        "instructions".to_string(),
+        json!({
+            "type": "string",
+            "description": "What to do, with any context from earlier results and, when asking for a revision, what to change"
+        }),
+    );
+    Tool {
+        name: DELEGATE_TOOL.to_string(),
+        description: "Assign work to an agent of the crew and wait for its result. Call it again with feedback to \
+            get a revision, or with another agent to reassign work that failed."
+            .to_string(),
+        parameters: JsonSchema {
+            schema_type: "object".to_string(),
+            properties: Some(properties),
+            required: Some(vec!["agent".to_string(), "instructions".to_string()]),
+        },
+    }
+}
@@ -0,0 +1,4 @@
+#[allow(clippy::module_inception)]
+pub mod crew;
+pub mod manager;
+pub mod workspace;
@@ -0,0 +1,213 @@
+use crate::session::session::new_run_id;
+use merco_llmproxy::paths::{extended_length, resolve_in};
+use merco_llmproxy::ToolContext;
+use std::path::{Path, PathBuf};
+
+// What happens to a run's workspace once the run finishes
+#[derive(Debug, Clone, PartialEq)]
+pub enum CleanupPolicy {
+    Delete,
+    Keep,
+    Archive(PathBuf), // Move the workspace into this directory
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkspaceConfig {
+    pub root: PathBuf, // Parent directory for all run workspaces
+    pub max_bytes: Option<u64>,
+    pub cleanup: CleanupPolicy,
+}
+
+impl Default for WorkspaceConfig {
+    fn default() -> Self {
+        Self {
+            root: std::env::temp_dir().join("merco-runs"),
+            max_bytes: None,
+            cleanup: CleanupPolicy::Delete,
+        }
+    }
+}
+
+impl WorkspaceConfig {
+    pub fn with_root(mut self, root: PathBuf) -> Self {
+        self.root = root;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn with_cleanup(mut self, cleanup: CleanupPolicy) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+}
+
+// An isolated directory created for a single crew run
+#[derive(Debug)]
+pub struct Workspace {
+    pub run_id: String,
+    pub path: PathBuf,
+    config: WorkspaceConfig,
+}
+
+impl Workspace {
+    pub fn create(config: WorkspaceConfig) -> Result<Self, String> {
+        let run_id = new_run_id();
+        // Deep roots can exceed MAX_PATH on Windows
+        let path = extended_length(&config.root.join(&run_id));
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create workspace {}: {}", path.display(), e))?;
+        Ok(Self { run_id, path, config })
+    }
+
+    // Context handed to tools so they write into this run's workspace
+    pub fn tool_context(&self) -> ToolContext {
+        ToolContext {
+            run_id: Some(self.run_id.clone()),
+            workspace: Some(self.path.clone()),
+            max_workspace_bytes: self.config.max_bytes,
+            ..Default::default()
+        }
+    }
+
+    // Resolve a model-generated relative path inside the workspace, rejecting traversal,
+    // absolute paths and names that are invalid on Windows
+    pub fn file_path(&self, relative: &str) -> Result<PathBuf, String> {
+        resolve_in(&self.path, relative).map_err(|e| e.to_string())
+    }
+
+    pub fn size(&self) -> u64 {
+        dir_size(&self.path)
+    }
+
+    // Fails if the workspace has grown past its size cap
+    pub fn check_size(&self) -> Result<(), String> {
+        match self.config.max_bytes {
+            Some(max) if self.size() > max => Err(format!(
+                "Workspace {} exceeded its size cap of {} bytes",
+                self.path.display(),
+                max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Apply the cleanup policy; returns the final location if the files were kept
+    pub fn finish(self) -> Result<Option<PathBuf>, String> {
+        match &self.config.cleanup {
+            CleanupPolicy::Delete => std::fs::remove_dir_all(&self.path)
+                .map(|_| None)
+                .map_err(|e| format!("Failed to delete workspace {}: {}", self.path.display(), e)),
+            CleanupPolicy::Keep => Ok(Some(self.path)),
+            CleanupPolicy::Archive(archive_dir) => {
+                let target = extended_length(&archive_dir.join(&self.run_id));
+                std::fs::create_dir_all(archive_dir)
+                    .and_then(|_| move_dir(&self.path, &target))
+                    .map(|_| Some(target))
+                    .map_err(|e| format!("Failed to archive workspace {}: {}", self.path.display(), e))
+            }
+        }
+    }
+}
+
+// Rename, falling back to copy and delete when the archive is on another volume (a
+// rename cannot cross drives on Windows or filesystems on Unix)
+fn move_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_dir(from, to)?;
+    std::fs::remove_dir_all(from)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(root: &Path, max_bytes: Option<u64>, cleanup: CleanupPolicy) -> Workspace {
+        let config = WorkspaceConfig { root: root.to_path_buf(), max_bytes, cleanup };
+        Workspace::create(config).unwrap()
+    }
+
+    #[test]
+    fn test_file_path_stays_inside_the_workspace() {
+        let root = tempfile::tempdir().unwrap();
+        let workspace = workspace(root.path(), None, CleanupPolicy::Delete);
+
+        let path = workspace.file_path("notes/today.md").unwrap();
+        assert!(path.starts_with(&workspace.path));
+        assert!(workspace.file_path("../escape.txt").is_err());
+        assert!(workspace.file_path("/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_check_size_enforces_the_cap() {
+        let root = tempfile::tempdir().unwrap();
+        let workspace = workspace(root.path(), Some(10), CleanupPolicy::Delete);
+        assert_eq!(workspace.tool_context().max_workspace_bytes, Some(10));
+
+        std::fs::create_dir(workspace.path.join("sub")).unwrap();
+        std::fs::write(workspace.path.join("sub/a.txt"), "12345").unwrap();
+        assert_eq!(workspace.size(), 5);
+        assert!(workspace.check_size().is_ok());
+
+        std::fs::write(workspace.path.join("b.txt"), "123456").unwrap();
+        let error = workspace.check_size().unwrap_err();
+        assert!(error.contains("size cap of 10 bytes"), "{}", error);
+    }
+
+    #[test]
+    fn test_finish_applies_the_cleanup_policy() {
+        let root = tempfile::tempdir().unwrap();
+
+        let deleted = workspace(root.path(), None, CleanupPolicy::Delete);
+        let path = deleted.path.clone();
+        assert_eq!(deleted.finish().unwrap(), None);
+        assert!(!path.exists());
+
+        let kept = workspace(root.path(), None, CleanupPolicy::Keep);
+        let path = kept.path.clone();
+        assert_eq!(kept.finish().unwrap(), Some(path.clone()));
+        assert!(path.exists());
+
+        let archive = root.path().join("archive");
+        let archived = workspace(root.path(), None, CleanupPolicy::Archive(archive.clone()));
+        std::fs::write(archived.path.join("report.md"), "done").unwrap();
+        let (path, run_id) = (archived.path.clone(), archived.run_id.clone());
+        let target = archived.finish().unwrap().unwrap();
+        assert!(target.ends_with(&run_id));
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_to_string(archive.join(&run_id).join("report.md")).unwrap(), "done");
+    }
+}
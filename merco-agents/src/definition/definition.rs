@@ -0,0 +1,440 @@
+use crate::agent::agent::{Agent, AgentLLMConfig, DEFAULT_MAX_ITERATIONS, LoopLimitAction, ToolErrorPolicy};
+use crate::agent::sampling::SamplingParams;
+use crate::agent::translation::Translation;
+use crate::crew::crew::{Crew, Workflow};
+use crate::crew::manager::DELEGATE_TOOL;
+use crate::profiles::profiles::Profiles;
+use crate::task::task::Task;
+use anyhow::{Result, anyhow};
+use merco_llmproxy::{LlmConfig, ToolFilter, get_provider, get_tools_by_names};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+// Version of the stored definition format written by this release
+pub const SCHEMA_VERSION: u32 = 2;
+
+// Upgrades a spec from version `i + 1` to `i + 2`; append one entry per schema bump
+type Migration = fn(DefinitionKind, Value) -> Result<Value>;
+const MIGRATIONS: &[Migration] = &[nested_object_fields];
+
+// v1 -> v2: `JsonFieldType::Object` became `Object(JsonSchema)`. Old free-form objects
+// get an empty schema, which still accepts any object.
+fn nested_object_fields(_kind: DefinitionKind, mut spec: Value) -> Result<Value> {
+    fn upgrade(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map.iter_mut() {
+                    if (key == "field_type" || key == "Array") && child == "Object" {
+                        *child = json!({ "Object": { "required_fields": [], "optional_fields": [] } });
+                    } else {
+                        upgrade(child);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(upgrade),
+            _ => {}
+        }
+    }
+    upgrade(&mut spec);
+    Ok(spec)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefinitionKind {
+    Agent,
+    Task,
+    Crew,
+}
+
+// Envelope written around every stored definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document<T> {
+    pub schema_version: u32,
+    pub kind: DefinitionKind,
+    pub spec: T,
+}
+
+// A serializable definition with a known document kind
+pub trait Definition: Serialize + DeserializeOwned {
+    const KIND: DefinitionKind;
+}
+
+// Provider settings of an agent. API keys are never stored, only the name of the
+// environment variable that holds them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmDefinition {
+    pub provider: String, // "openai", "ollama", "anthropic" or "custom"
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    pub model: String,
+    #[serde(flatten)]
+    pub sampling: SamplingParams, // Stored as top-level `temperature`, `max_tokens`, ... keys
+}
+
+impl LlmDefinition {
+    // Resolve the provider config, reading the API key from the environment and
+    // checking that a provider can be created from it
+    pub fn agent_config(&self) -> Result<AgentLLMConfig> {
+        let provider = self.provider.parse().map_err(|e| anyhow!("{}", e))?;
+        let mut config = LlmConfig::new(provider);
+        if let Some(base_url) = &self.base_url {
+            config = config.with_base_url(base_url.clone());
+        }
+        if let Some(var) = &self.api_key_env {
+            let key = std::env::var(var).map_err(|_| anyhow!("Environment variable {} is not set", var))?;
+            config = config.with_api_key(key);
+        }
+        // Agent::new panics on an unusable config, so check it first
+        get_provider(config.clone()).map_err(|e| anyhow!("Invalid provider config: {}", e))?;
+        Ok(AgentLLMConfig::with_defaults(config, self.model.clone(), self.sampling.clone()))
+    }
+}
+
+// An agent's provider settings: written inline, or the name of a profile from
+// `merco.toml` (e.g. `"llm": "fast"`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LlmSpec {
+    Profile(String),
+    Inline(LlmDefinition),
+}
+
+impl LlmSpec {
+    fn is_profile(&self) -> bool {
+        matches!(self, LlmSpec::Profile(_))
+    }
+
+    // Profile references need `profiles`; inline settings are returned as they are
+    pub fn resolve<'a>(&'a self, profiles: Option<&'a Profiles>) -> Result<&'a LlmDefinition> {
+        match (self, profiles) {
+            (LlmSpec::Inline(definition), _) => Ok(definition),
+            (LlmSpec::Profile(name), Some(profiles)) => profiles.get(name),
+            (LlmSpec::Profile(name), None) => Err(anyhow!("Agent uses profile '{}', but no profiles are loaded", name)),
+        }
+    }
+}
+
+// The declarative part of an agent. Runtime wiring (memory, middlewares, context
+// management, verifiers, cancellation) is attached after loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentDefinition {
+    pub llm: LlmSpec,
+    pub backstory: String,
+    #[serde(default)]
+    pub goals: Vec<String>,
+    #[serde(default)]
+    pub tools: Vec<String>, // Names of tools in the global registry
+    #[serde(default)]
+    pub final_answer_tool: bool,
+    #[serde(default)]
+    pub streaming_validation: bool,
+    #[serde(default)]
+    pub translation: Option<Translation>,
+    #[serde(default)]
+    pub tool_error_policy: ToolErrorPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_filter: Option<ToolFilter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub short_term_memory_tokens: Option<usize>, // Token budget of the agent's short-term memory
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_execution_secs: Option<u64>,
+    #[serde(default)]
+    pub loop_limit_action: LoopLimitAction,
+}
+
+fn default_max_iterations() -> usize {
+    DEFAULT_MAX_ITERATIONS
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrewTaskDefinition {
+    pub agent: usize, // Index into `CrewDefinition::agents`
+    pub task: Task,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub parallel: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrewDefinition {
+    pub agents: Vec<AgentDefinition>,
+    #[serde(default)]
+    pub tasks: Vec<CrewTaskDefinition>,
+    #[serde(default, skip_serializing_if = "is_sequential")]
+    pub workflow: Workflow,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manager: Option<AgentDefinition>, // Runs the crew hierarchically when set
+}
+
+fn is_sequential(workflow: &Workflow) -> bool {
+    *workflow == Workflow::Sequential
+}
+
+impl Definition for AgentDefinition {
+    const KIND: DefinitionKind = DefinitionKind::Agent;
+}
+
+impl Definition for Task {
+    const KIND: DefinitionKind = DefinitionKind::Task;
+}
+
+impl Definition for CrewDefinition {
+    const KIND: DefinitionKind = DefinitionKind::Crew;
+}
+
+impl AgentDefinition {
+    // Capture an agent's definition; set `llm.api_key_env` before storing if it needs a key
+    pub fn from_agent(agent: &Agent) -> Self {
+        let config = agent.llm_config();
+        Self {
+            llm: LlmSpec::Inline(LlmDefinition {
+                provider: format!("{:?}", config.base_config().provider).to_lowercase(),
+                base_url: config.base_config().base_url.clone(),
+                api_key_env: None,
+                model: config.model_name().to_string(),
+                sampling: config.defaults().clone(),
+            }),
+            backstory: agent.backstory.clone(),
+            goals: agent.goals.clone(),
+            tools: agent.tools.iter().map(|tool| tool.name.clone()).collect(),
+            final_answer_tool: agent.final_answer_tool,
+            streaming_validation: agent.streaming_validation,
+            translation: agent.translation.clone(),
+            tool_error_policy: agent.tool_error_policy,
+            tool_filter: agent.tool_filter.clone(),
+            short_term_memory_tokens: agent.short_term_memory.as_ref().map(|memory| memory.max_tokens()),
+            max_iterations: agent.max_iterations,
+            max_execution_secs: agent.max_execution_time.map(|limit| limit.as_secs()),
+            loop_limit_action: agent.loop_limit_action,
+        }
+    }
+
+    // Create the agent, reading the API key from the environment and resolving tools by
+    // name. A profile reference is looked up in the discovered `merco.toml`.
+    pub fn build(&self) -> Result<Agent> {
+        let profiles = if self.llm.is_profile() { Some(Profiles::discover()?) } else { None };
+        self.build_with(profiles.as_ref())
+    }
+
+    // Like `build`, resolving profile references in `profiles`
+    pub fn build_with(&self, profiles: Option<&Profiles>) -> Result<Agent> {
+        let llm_config = self.llm.resolve(profiles)?.agent_config()?;
+
+        let names: Vec<&str> = self.tools.iter().map(String::as_str).collect();
+        let tools = get_tools_by_names(&names);
+        if tools.len() != names.len() {
+            let missing: Vec<&str> = names.into_iter().filter(|n| !tools.iter().any(|t| t.name == *n)).collect();
+            return Err(anyhow!("Unknown tools: {}", missing.join(", ")));
+        }
+
+        let mut agent = Agent::new(llm_config, self.backstory.clone(), self.goals.clone(), tools)
+            .with_final_answer_tool(self.final_answer_tool)
+            .with_streaming_validation(self.streaming_validation)
+            .with_tool_error_policy(self.tool_error_policy)
+            .with_max_iterations(self.max_iterations)
+            .with_loop_limit_action(self.loop_limit_action);
+        agent.translation = self.translation.clone();
+        if let Some(filter) = &self.tool_filter {
+            agent = agent.with_tool_filter(filter.clone());
+        }
+        if let Some(max_tokens) = self.short_term_memory_tokens {
+            agent = agent.with_short_term_memory(max_tokens);
+        }
+        if let Some(secs) = self.max_execution_secs {
+            agent = agent.with_max_execution_time(std::time::Duration::from_secs(secs));
+        }
+        Ok(agent)
+    }
+}
+
+impl CrewDefinition {
+    pub fn from_crew(crew: &Crew) -> Self {
+        Self {
+            agents: crew.agents.iter().map(AgentDefinition::from_agent).collect(),
+            tasks: crew
+                .tasks
+                .iter()
+                .map(|t| CrewTaskDefinition { agent: t.agent_index, task: t.task.clone(), parallel: t.parallel })
+                .collect(),
+            workflow: crew.workflow,
+            max_concurrency: crew.max_concurrency,
+            manager: crew.manager.as_ref().map(|manager| {
+                let mut definition = AgentDefinition::from_agent(&manager.agent);
+                // The crew gives the manager its delegate tool again when it is built
+                definition.tools.retain(|tool| tool != DELEGATE_TOOL);
+                definition
+            }),
+        }
+    }
+
+    // Build every agent; `merco.toml` is only read if some agent references a profile
+    pub fn build(&self) -> Result<Crew> {
+        let uses_profiles = self.agents.iter().chain(&self.manager).any(|agent| agent.llm.is_profile());
+        let profiles = if uses_profiles { Some(Profiles::discover()?) } else { None };
+        self.build_with(profiles.as_ref())
+    }
+
+    pub fn build_with(&self, profiles: Option<&Profiles>) -> Result<Crew> {
+        let agents = self
+            .agents
+            .iter()
+            .map(|agent| agent.build_with(profiles))
+            .collect::<Result<Vec<_>>>()?;
+        let mut crew = self.tasks.iter().fold(Crew::new(agents), |crew, t| match t.parallel {
+            true => crew.with_parallel_task(t.agent, t.task.clone()),
+            false => crew.with_task(t.agent, t.task.clone()),
+        });
+        crew.workflow = self.workflow;
+        crew.max_concurrency = self.max_concurrency;
+        Ok(match &self.manager {
+            Some(manager) => crew.with_manager_agent(manager.build_with(profiles)?),
+            None => crew,
+        })
+    }
+}
+
+// Serialize a definition in canonical form: versioned envelope, sorted keys, pretty
+// printed with a trailing newline, so stored definitions diff cleanly
+pub fn to_canonical_json<T: Definition>(spec: &T) -> Result<String> {
+    let document = Document { schema_version: SCHEMA_VERSION, kind: T::KIND, spec };
+    // Going through `Value` sorts object keys, since serde_json maps are ordered
+    let value = serde_json::to_value(&document)?;
+    let mut json = serde_json::to_string_pretty(&value)?;
+    json.push('\n');
+    Ok(json)
+}
+
+// Load a definition written by this or an older release, migrating it to the current
+// schema. Unknown fields from newer minor additions are ignored.
+pub fn from_json<T: Definition>(json: &str) -> Result<T> {
+    let value: Value = serde_json::from_str(json)?;
+    let spec = migrate(T::KIND, value)?;
+    Ok(serde_json::from_value(spec)?)
+}
+
+// Bring a stored document up to `SCHEMA_VERSION`, returning its spec. A bare spec
+// without an envelope is treated as the current version.
+fn migrate(kind: DefinitionKind, value: Value) -> Result<Value> {
+    let Some(version) = value.get("schema_version") else {
+        return Ok(value);
+    };
+    let version = version
+        .as_u64()
+        .ok_or_else(|| anyhow!("schema_version must be a positive integer"))? as u32;
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "Definition has schema version {}, but this release supports versions 1 to {}",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+
+    let stored_kind: DefinitionKind = serde_json::from_value(value.get("kind").cloned().unwrap_or(json!(null)))
+        .map_err(|_| anyhow!("Definition is missing a valid 'kind'"))?;
+    if stored_kind != kind {
+        return Err(anyhow!("Expected a {:?} definition, found {:?}", kind, stored_kind));
+    }
+
+    let mut spec = value.get("spec").cloned().ok_or_else(|| anyhow!("Definition is missing 'spec'"))?;
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        spec = migration(kind, spec)?;
+    }
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::task::{JsonField, JsonFieldType, JsonSchema};
+    use merco_llmproxy::Provider;
+    use std::time::Duration;
+
+    fn agent() -> Agent {
+        let defaults = SamplingParams { temperature: Some(0.2), seed: Some(7), ..Default::default() };
+        let llm_config = AgentLLMConfig::with_defaults(LlmConfig::new(Provider::Ollama), "llama3".to_string(), defaults);
+        Agent::new(llm_config, "You are a geographer.".to_string(), vec!["Be accurate".to_string()], Vec::new())
+            .with_final_answer_tool(true)
+            .with_tool_error_policy(ToolErrorPolicy::Retry { max_attempts: 3 })
+            .with_short_term_memory(2000)
+            .with_max_iterations(5)
+            .with_max_execution_time(Duration::from_secs(90))
+            .with_loop_limit_action(LoopLimitAction::Summarize)
+    }
+
+    #[test]
+    fn test_agent_definition_round_trips() {
+        let definition = AgentDefinition::from_agent(&agent());
+        let json = to_canonical_json(&definition).unwrap();
+
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+        assert_eq!(value["kind"], "agent");
+        assert_eq!(value["spec"]["llm"]["temperature"], json!(0.2f32));
+        assert_eq!(value["spec"]["llm"]["seed"], 7);
+        assert!(json.ends_with("}\n"));
+        assert_eq!(from_json::<AgentDefinition>(&json).unwrap(), definition);
+
+        let rebuilt = definition.build_with(None).unwrap();
+        assert!(rebuilt.final_answer_tool);
+        assert_eq!(rebuilt.tool_error_policy, ToolErrorPolicy::Retry { max_attempts: 3 });
+        assert_eq!(rebuilt.short_term_memory.as_ref().map(|m| m.max_tokens()), Some(2000));
+        assert_eq!(rebuilt.max_iterations, 5);
+        assert_eq!(rebuilt.max_execution_time, Some(Duration::from_secs(90)));
+        assert_eq!(rebuilt.loop_limit_action, LoopLimitAction::Summarize);
+        assert_eq!(AgentDefinition::from_agent(&rebuilt), definition);
+    }
+
+    #[test]
+    fn test_missing_fields_take_their_defaults() {
+        let json = r#"{"llm": {"provider": "ollama", "model": "llama3"}, "backstory": "You help."}"#;
+        let definition: AgentDefinition = from_json(json).unwrap();
+
+        assert_eq!(definition.max_iterations, DEFAULT_MAX_ITERATIONS);
+        assert_eq!(definition.tool_error_policy, ToolErrorPolicy::Report);
+        assert_eq!(definition.loop_limit_action, LoopLimitAction::Fail);
+        assert!(!definition.final_answer_tool && definition.tools.is_empty());
+        assert_eq!(definition.short_term_memory_tokens, None);
+    }
+
+    #[test]
+    fn test_migrates_v1_object_fields() {
+        let task = Task::new_with_json_output(
+            "Describe France".to_string(),
+            None,
+            vec![JsonField::new("capital", JsonFieldType::Object(JsonSchema::new(Vec::new(), Vec::new())))],
+            Vec::new(),
+            false,
+        );
+        let mut value: Value = serde_json::from_str(&to_canonical_json(&task).unwrap()).unwrap();
+        value["schema_version"] = json!(1);
+        let v1 = serde_json::to_string(&value).unwrap().replace(
+            r#"{"Object":{"optional_fields":[],"required_fields":[]}}"#,
+            r#""Object""#,
+        );
+        assert!(v1.contains(r#""field_type":"Object""#), "{}", v1);
+
+        assert_eq!(from_json::<Task>(&v1).unwrap(), task);
+    }
+
+    #[test]
+    fn test_rejects_unsupported_documents() {
+        let json = to_canonical_json(&AgentDefinition::from_agent(&agent())).unwrap();
+        let error = from_json::<CrewDefinition>(&json).unwrap_err();
+        assert!(error.to_string().contains("Expected a Crew definition"), "{}", error);
+
+        let future = json.replacen(&format!("\"schema_version\": {}", SCHEMA_VERSION), "\"schema_version\": 99", 1);
+        let error = from_json::<AgentDefinition>(&future).unwrap_err();
+        assert!(error.to_string().contains("schema version 99"), "{}", error);
+
+        let profile = AgentDefinition { llm: LlmSpec::Profile("fast".to_string()), ..from_json(&json).unwrap() };
+        let error = profile.build_with(None).unwrap_err();
+        assert!(error.to_string().contains("no profiles are loaded"), "{}", error);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::knowledge::loader::{Document, DocumentFormat};
+
+// Separators `RecursiveChunker` tries in turn: paragraphs, lines, sentences, words
+pub const DEFAULT_SEPARATORS: &[&str] = &["\n\n", "\n", ". ", " "];
+
+// Where a chunk came from
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChunkMetadata {
+    pub source: String,
+    pub format: DocumentFormat,
+    pub index: usize,            // Position among the chunks of its document
+    pub offset: usize,           // Byte offset of the chunk in the document's text
+    pub heading: Option<String>, // Enclosing headings, e.g. "Install > Linux"
+}
+
+// A piece of a document small enough to embed and to quote in a prompt
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Chunk {
+    pub text: String,
+    pub metadata: ChunkMetadata,
+}
+
+impl Chunk {
+    // Short citation, e.g. "docs/guide.md > Install > Linux"
+    pub fn label(&self) -> String {
+        match &self.metadata.heading {
+            Some(heading) => format!("{} > {}", self.metadata.source, heading),
+            None => self.metadata.source.clone(),
+        }
+    }
+}
+
+// Splits documents into chunks
+pub trait Chunker: Send + Sync {
+    fn chunk(&self, document: &Document) -> Vec<Chunk>;
+}
+
+// Chunks of at most `size` characters, consecutive chunks sharing up to `overlap`
+// characters. Chunks end between words; only words longer than `size` are cut.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedSizeChunker {
+    pub size: usize,
+    pub overlap: usize,
+}
+
+impl FixedSizeChunker {
+    pub fn new(size: usize, overlap: usize) -> Self {
+        let size = size.max(1);
+        Self { size, overlap: overlap.min(size - 1) }
+    }
+}
+
+impl Chunker for FixedSizeChunker {
+    fn chunk(&self, document: &Document) -> Vec<Chunk> {
+        let pieces = split_pieces(&document.text, 0, self.size, &[" ", "\n"]);
+        let ranges = merge_pieces(&document.text, &pieces, self.size, self.overlap);
+        build_chunks(document, ranges.into_iter().map(|range| (range, None)))
+    }
+}
+
+// Chunks of at most `size` characters that prefer to break at the first separator that
+// works (by default paragraphs, then lines, sentences and words), sharing up to `overlap`
+// characters between consecutive chunks
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecursiveChunker {
+    pub size: usize,
+    pub overlap: usize,
+    pub separators: Vec<String>,
+}
+
+impl RecursiveChunker {
+    pub fn new(size: usize, overlap: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            size,
+            overlap: overlap.min(size - 1),
+            separators: DEFAULT_SEPARATORS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    // Separators to try, most preferred first
+    pub fn with_separators(mut self, separators: &[&str]) -> Self {
+        self.separators = separators.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    fn ranges(&self, text: &str, base: usize) -> Vec<(usize, usize)> {
+        let separators: Vec<&str> = self.separators.iter().map(String::as_str).collect();
+        let pieces = split_pieces(&text[base..], base, self.size, &separators);
+        merge_pieces(text, &pieces, self.size, self.overlap)
+    }
+}
+
+impl Chunker for RecursiveChunker {
+    fn chunk(&self, document: &Document) -> Vec<Chunk> {
+        build_chunks(document, self.ranges(&document.text, 0).into_iter().map(|range| (range, None)))
+    }
+}
+
+// One chunk per Markdown section, labelled with its heading path; sections longer than
+// `max_size` characters are split further like `RecursiveChunker` does. Documents in
+// other formats are chunked recursively without headings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadingChunker {
+    pub inner: RecursiveChunker,
+}
+
+impl HeadingChunker {
+    pub fn new(max_size: usize, overlap: usize) -> Self {
+        Self { inner: RecursiveChunker::new(max_size, overlap) }
+    }
+}
+
+impl Chunker for HeadingChunker {
+    fn chunk(&self, document: &Document) -> Vec<Chunk> {
+        if document.format != DocumentFormat::Markdown {
+            return self.inner.chunk(document);
+        }
+        let text = &document.text;
+        let mut ranges = Vec::new();
+        for (start, end, heading) in markdown_sections(text) {
+            let section = &text[..end];
+            for range in self.inner.ranges(section, start) {
+                ranges.push((range, heading.clone()));
+            }
+        }
+        build_chunks(document, ranges)
+    }
+}
+
+// (start, end, heading path) of each section of a Markdown text. A section runs from its
+// heading line to the next heading; text before the first heading has no heading.
+fn markdown_sections(text: &str) -> Vec<(usize, usize, Option<String>)> {
+    let mut sections = Vec::new();
+    let mut stack: Vec<(usize, String)> = Vec::new(); // (level, title) of enclosing headings
+    let mut section_start = 0;
+    let mut heading: Option<String> = None;
+    let mut in_fence = false;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        let is_heading = !in_fence && (1..=6).contains(&level) && trimmed[level..].starts_with([' ', '\t']);
+        if is_heading {
+            if offset > section_start {
+                sections.push((section_start, offset, heading.clone()));
+            }
+            let title = trimmed[level..].trim().trim_end_matches('#').trim().to_string();
+            stack.retain(|(enclosing, _)| *enclosing < level);
+            stack.push((level, title));
+            heading = Some(stack.iter().map(|(_, title)| title.as_str()).collect::<Vec<_>>().join(" > "));
+            section_start = offset;
+        }
+        offset += line.len();
+    }
+    if text.len() > section_start {
+        sections.push((section_start, text.len(), heading));
+    }
+    sections
+}
+
+// Byte ranges of `text` (whose first byte is at `base` in the document), each at most
+// `size` characters: split at the first separator that occurs, recursing into pieces
+// that are still too long, and cutting by characters when no separator is left
+fn split_pieces(text: &str, base: usize, size: usize, separators: &[&str]) -> Vec<(usize, usize)> {
+    if text.chars().count() <= size {
+        return vec![(base, base + text.len())];
+    }
+    let Some((separator, rest)) = separators.split_first() else {
+        // Hard cut at character boundaries
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).chain([text.len()]).collect();
+        let mut pieces = Vec::new();
+        let mut index = 0;
+        while index + 1 < boundaries.len() {
+            let next = (index + size).min(boundaries.len() - 1);
+            pieces.push((base + boundaries[index], base + boundaries[next]));
+            index = next;
+        }
+        return pieces;
+    };
+    if !text.contains(separator) {
+        return split_pieces(text, base, size, rest);
+    }
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    // Each piece keeps its trailing separator, so pieces stay contiguous
+    for (index, _) in text.match_indices(separator) {
+        let end = index + separator.len();
+        pieces.extend(split_pieces(&text[start..end], base + start, size, rest));
+        start = end;
+    }
+    if start < text.len() {
+        pieces.extend(split_pieces(&text[start..], base + start, size, rest));
+    }
+    pieces
+}
+
+// Greedily join contiguous pieces into ranges of at most `size` characters, starting
+// each range (after the first) with the trailing pieces of the previous one that fit
+// in `overlap` characters
+fn merge_pieces(text: &str, pieces: &[(usize, usize)], size: usize, overlap: usize) -> Vec<(usize, usize)> {
+    let chars = |piece: &(usize, usize)| text[piece.0..piece.1].chars().count();
+    let mut ranges = Vec::new();
+    let mut first = 0;
+    while first < pieces.len() {
+        let mut next = first;
+        let mut length = 0;
+        while next < pieces.len() && (next == first || length + chars(&pieces[next]) <= size) {
+            length += chars(&pieces[next]);
+            next += 1;
+        }
+        ranges.push((pieces[first].0, pieces[next - 1].1));
+        if next == pieces.len() {
+            break;
+        }
+        let mut carried = 0;
+        let mut start = next;
+        while start > first + 1 && carried + chars(&pieces[start - 1]) <= overlap {
+            carried += chars(&pieces[start - 1]);
+            start -= 1;
+        }
+        first = start;
+    }
+    ranges
+}
+
+// Trim each range and wrap the non-empty ones as chunks of `document`
+fn build_chunks(document: &Document, ranges: impl IntoIterator<Item = ((usize, usize), Option<String>)>) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    for ((start, end), heading) in ranges {
+        let raw = &document.text[start..end];
+        let text = raw.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let offset = start + (raw.len() - raw.trim_start().len());
+        chunks.push(Chunk {
+            text: text.to_string(),
+            metadata: ChunkMetadata {
+                source: document.source.clone(),
+                format: document.format,
+                index: chunks.len(),
+                offset,
+                heading,
+            },
+        });
+    }
+    chunks
+}
+
+// Chunk every document with `chunker`
+pub fn chunk_documents(chunker: &dyn Chunker, documents: &[Document]) -> Vec<Chunk> {
+    documents.iter().flat_map(|document| chunker.chunk(document)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(chunks: &[Chunk]) -> Vec<&str> {
+        chunks.iter().map(|chunk| chunk.text.as_str()).collect()
+    }
+
+    // Every chunk fits the size and its offset points at its text in the document
+    fn assert_well_formed(document: &Document, chunks: &[Chunk], size: usize) {
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.text.chars().count() <= size, "{:?}", chunk.text);
+            assert!(document.text[chunk.metadata.offset..].starts_with(&chunk.text), "{:?}", chunk);
+            assert_eq!(chunk.metadata.index, index);
+        }
+    }
+
+    #[test]
+    fn test_fixed_size_breaks_between_words() {
+        let document = Document::new("notes.txt", DocumentFormat::Text, "alpha beta gamma delta epsilon");
+
+        let chunks = FixedSizeChunker::new(11, 0).chunk(&document);
+        assert_eq!(texts(&chunks), ["alpha beta", "gamma", "delta", "epsilon"]);
+        assert_well_formed(&document, &chunks, 11);
+
+        let chunks = FixedSizeChunker::new(11, 6).chunk(&document);
+        assert_eq!(texts(&chunks)[..2], ["alpha beta", "beta gamma"]);
+        assert_well_formed(&document, &chunks, 11);
+    }
+
+    #[test]
+    fn test_long_words_are_cut_at_character_boundaries() {
+        let document = Document::new("word.txt", DocumentFormat::Text, "abcdefghij");
+        assert_eq!(texts(&FixedSizeChunker::new(4, 0).chunk(&document)), ["abcd", "efgh", "ij"]);
+
+        let document = Document::new("accents.txt", DocumentFormat::Text, "ééééé");
+        let chunks = FixedSizeChunker::new(2, 0).chunk(&document);
+        assert_eq!(texts(&chunks), ["éé", "éé", "é"]);
+        assert_well_formed(&document, &chunks, 2);
+    }
+
+    #[test]
+    fn test_recursive_prefers_paragraphs() {
+        let document = Document::new(
+            "essay.txt",
+            DocumentFormat::Text,
+            "First paragraph here.\n\nSecond one. It has two sentences.",
+        );
+
+        let chunks = RecursiveChunker::new(25, 0).chunk(&document);
+        assert_eq!(texts(&chunks), ["First paragraph here.", "Second one.", "It has two sentences."]);
+        assert_eq!(chunks[1].metadata.offset, 23);
+        assert_well_formed(&document, &chunks, 25);
+
+        let chunks = RecursiveChunker::new(25, 0).with_separators(&[" "]).chunk(&document);
+        assert_eq!(texts(&chunks)[..2], ["First paragraph", "here.\n\nSecond one. It"]);
+    }
+
+    #[test]
+    fn test_heading_chunker_labels_sections() {
+        let text = "Intro text\n# Install\n## Linux\nRun apt.\n```\n# not a heading\n```\n# Usage #\nCall it.\n";
+        let document = Document::new("guide.md", DocumentFormat::Markdown, text);
+
+        let chunks = HeadingChunker::new(200, 0).chunk(&document);
+        let headings: Vec<Option<&str>> = chunks.iter().map(|c| c.metadata.heading.as_deref()).collect();
+        assert_eq!(headings, [None, Some("Install"), Some("Install > Linux"), Some("Usage")]);
+        assert!(chunks[2].text.contains("# not a heading"));
+        assert_eq!(chunks[2].label(), "guide.md > Install > Linux");
+        assert_eq!(chunks[0].label(), "guide.md");
+        assert_well_formed(&document, &chunks, 200);
+    }
+
+    #[test]
+    fn test_heading_chunker_ignores_headings_outside_markdown() {
+        let document = Document::new("notes.txt", DocumentFormat::Text, "# Title\nBody");
+
+        let chunks = HeadingChunker::new(200, 0).chunk(&document);
+        assert_eq!(texts(&chunks), ["# Title\nBody"]);
+        assert_eq!(chunks[0].metadata.heading, None);
+    }
+
+    #[test]
+    fn test_chunk_documents_numbers_chunks_per_document() {
+        let documents = [
+            Document::new("a.txt", DocumentFormat::Text, "one two"),
+            Document::new("b.txt", DocumentFormat::Text, "three"),
+        ];
+
+        let chunks = chunk_documents(&FixedSizeChunker::new(4, 0), &documents);
+        let positions: Vec<(&str, usize)> =
+            chunks.iter().map(|c| (c.metadata.source.as_str(), c.metadata.index)).collect();
+        assert_eq!(positions, [("a.txt", 0), ("a.txt", 1), ("b.txt", 0), ("b.txt", 1)]);
+    }
+}
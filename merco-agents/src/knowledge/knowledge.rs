@@ -0,0 +1,245 @@
+use crate::knowledge::chunker::{Chunk, ChunkMetadata, Chunker, RecursiveChunker, chunk_documents};
+use crate::knowledge::loader::{Document, DocumentFormat, load_directory, load_file, load_url};
+use crate::memory::memory::{Memory, MemoryEntry};
+use crate::memory::vector::{VectorMemory, VectorStore};
+use merco_llmproxy::{EmbeddingProvider, HttpTransport, Transport};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+// Default number of chunks injected into a task prompt
+pub const DEFAULT_TOP_K: usize = 4;
+// Default chunk size and overlap, in characters
+pub const DEFAULT_CHUNK_CHARS: usize = 1200;
+pub const DEFAULT_CHUNK_OVERLAP: usize = 200;
+
+// Where an agent's reference knowledge comes from
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KnowledgeSource {
+    File { path: PathBuf },
+    Directory { path: PathBuf }, // Every readable document below it, hidden entries skipped
+    Url { url: String },
+    Text { label: String, content: String },
+}
+
+impl KnowledgeSource {
+    // Name shown with retrieved chunks
+    pub fn label(&self) -> String {
+        match self {
+            KnowledgeSource::File { path } | KnowledgeSource::Directory { path } => path.display().to_string(),
+            KnowledgeSource::Url { url } => url.clone(),
+            KnowledgeSource::Text { label, .. } => label.clone(),
+        }
+    }
+}
+
+impl From<Chunk> for MemoryEntry {
+    fn from(chunk: Chunk) -> Self {
+        let metadata = serde_json::to_value(&chunk.metadata).unwrap_or_default();
+        MemoryEntry::new(chunk.text, Some(chunk.metadata.source)).with_metadata(metadata)
+    }
+}
+
+impl From<MemoryEntry> for Chunk {
+    // Entries stored without chunk metadata become chunk 0 of their source
+    fn from(entry: MemoryEntry) -> Self {
+        let metadata = entry
+            .metadata
+            .and_then(|metadata| serde_json::from_value(metadata).ok())
+            .unwrap_or_else(|| ChunkMetadata {
+                source: entry.source.unwrap_or_else(|| "knowledge".to_string()),
+                format: DocumentFormat::Text,
+                index: 0,
+                offset: 0,
+                heading: None,
+            });
+        Chunk { text: entry.content, metadata }
+    }
+}
+
+// Reference material an agent consults for every task: sources are loaded, split into
+// chunks, embedded and indexed on first use, and the chunks most relevant to a task are
+// added to its prompt
+pub struct KnowledgeBase {
+    sources: Vec<KnowledgeSource>,
+    memory: VectorMemory,
+    chunker: Arc<dyn Chunker>,
+    transport: Arc<dyn Transport>,
+    top_k: usize,
+    indexed: Mutex<usize>, // Number of sources already ingested
+}
+
+impl fmt::Debug for KnowledgeBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KnowledgeBase")
+            .field("sources", &self.sources)
+            .field("memory", &self.memory)
+            .field("top_k", &self.top_k)
+            .finish()
+    }
+}
+
+impl KnowledgeBase {
+    // Embed chunks with `model` of `embedder`, keeping them in an in-memory vector store
+    pub fn new(embedder: Arc<dyn EmbeddingProvider>, model: impl Into<String>) -> Self {
+        Self {
+            sources: Vec::new(),
+            memory: VectorMemory::new(embedder, model),
+            chunker: Arc::new(RecursiveChunker::new(DEFAULT_CHUNK_CHARS, DEFAULT_CHUNK_OVERLAP)),
+            transport: Arc::new(HttpTransport::new()),
+            top_k: DEFAULT_TOP_K,
+            indexed: Mutex::new(0),
+        }
+    }
+
+    pub fn with_source(mut self, source: KnowledgeSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn with_file(self, path: impl Into<PathBuf>) -> Self {
+        self.with_source(KnowledgeSource::File { path: path.into() })
+    }
+
+    pub fn with_directory(self, path: impl Into<PathBuf>) -> Self {
+        self.with_source(KnowledgeSource::Directory { path: path.into() })
+    }
+
+    pub fn with_url(self, url: impl Into<String>) -> Self {
+        self.with_source(KnowledgeSource::Url { url: url.into() })
+    }
+
+    pub fn with_text(self, label: impl Into<String>, content: impl Into<String>) -> Self {
+        self.with_source(KnowledgeSource::Text { label: label.into(), content: content.into() })
+    }
+
+    // Keep chunk vectors in `store` instead, e.g. a `SqliteVectorStore` so the index
+    // survives restarts (call `index` once per new source rather than on every start)
+    pub fn with_store(mut self, store: Arc<dyn VectorStore>) -> Self {
+        self.memory = self.memory.with_store(store);
+        self
+    }
+
+    // Fetch URLs through a custom transport (proxies, auth, tests)
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    // Split sources with `chunker`, e.g. a `HeadingChunker` for Markdown documentation
+    pub fn with_chunker(mut self, chunker: Arc<dyn Chunker>) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    // Shorthand for a `RecursiveChunker` with this chunk size and overlap, in characters
+    pub fn with_chunk_size(self, chars: usize, overlap: usize) -> Self {
+        self.with_chunker(Arc::new(RecursiveChunker::new(chars, overlap)))
+    }
+
+    // Number of chunks added to each task prompt
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    // Leave out chunks less similar to the task than this (cosine similarity)
+    pub fn with_min_similarity(mut self, min_similarity: f32) -> Self {
+        self.memory = self.memory.with_min_similarity(min_similarity);
+        self
+    }
+
+    pub fn sources(&self) -> &[KnowledgeSource] {
+        &self.sources
+    }
+
+    // Load, chunk and embed the sources not indexed yet; returns the number of new chunks.
+    // Retrieval does this on first use, so calling it is only needed to index up front.
+    pub async fn index(&self) -> Result<usize, String> {
+        let mut indexed = self.indexed.lock().await;
+        let mut added = 0;
+        for source in &self.sources[*indexed..] {
+            let documents = self.load(source).await?;
+            added += self.add_documents(&documents).await?;
+            *indexed += 1;
+        }
+        Ok(added)
+    }
+
+    // Chunk and index documents loaded by the caller; returns the number of chunks
+    pub async fn add_documents(&self, documents: &[Document]) -> Result<usize, String> {
+        self.add_chunks(chunk_documents(self.chunker.as_ref(), documents)).await
+    }
+
+    // Index ready-made chunks; returns their number
+    pub async fn add_chunks(&self, chunks: Vec<Chunk>) -> Result<usize, String> {
+        let count = chunks.len();
+        self.memory.save_all(chunks.into_iter().map(MemoryEntry::from).collect()).await?;
+        Ok(count)
+    }
+
+    // The chunks most relevant to `query`, best first
+    pub async fn retrieve(&self, query: &str) -> Result<Vec<Chunk>, String> {
+        self.index().await?;
+        Ok(self.memory.search(query, self.top_k).await?.into_iter().map(Chunk::from).collect())
+    }
+
+    async fn load(&self, source: &KnowledgeSource) -> Result<Vec<Document>, String> {
+        match source {
+            KnowledgeSource::Text { label, content } => Ok(vec![Document::new(label, DocumentFormat::Text, content)]),
+            KnowledgeSource::File { path } => Ok(vec![load_file(path)?]),
+            KnowledgeSource::Directory { path } => load_directory(path),
+            KnowledgeSource::Url { url } => Ok(vec![load_url(self.transport.as_ref(), url).await?]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merco_llmproxy::MockProvider;
+
+    fn knowledge() -> KnowledgeBase {
+        KnowledgeBase::new(Arc::new(MockProvider::new()), "mock-embed")
+    }
+
+    #[tokio::test]
+    async fn test_retrieves_the_most_relevant_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("refunds.md");
+        std::fs::write(&path, "Refunds are issued within 14 days of a return.").unwrap();
+        let knowledge = knowledge()
+            .with_text("shipping", "Orders ship from Berlin every weekday.")
+            .with_file(&path)
+            .with_top_k(1);
+
+        let chunks = knowledge.retrieve("When are refunds issued?").await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text, "Refunds are issued within 14 days of a return.");
+        assert_eq!(chunks[0].metadata.source, path.display().to_string());
+        assert_eq!(chunks[0].metadata.format, DocumentFormat::Markdown);
+
+        let chunks = knowledge.retrieve("Where do orders ship from?").await.unwrap();
+        assert_eq!(chunks[0].metadata.source, "shipping");
+    }
+
+    #[tokio::test]
+    async fn test_sources_are_indexed_once() {
+        let knowledge = knowledge().with_text("a", "First note.").with_text("b", "Second note.");
+        assert_eq!(knowledge.index().await.unwrap(), 2);
+        assert_eq!(knowledge.index().await.unwrap(), 0);
+
+        knowledge.retrieve("note").await.unwrap();
+        assert_eq!(knowledge.add_documents(&[Document::new("c", DocumentFormat::Text, "Third note.")]).await.unwrap(), 1);
+        assert_eq!(knowledge.retrieve("note").await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_missing_source_fails_retrieval() {
+        let dir = tempfile::tempdir().unwrap();
+        let knowledge = knowledge().with_file(dir.path().join("missing.md"));
+        assert!(knowledge.retrieve("anything").await.unwrap_err().starts_with("Failed to read"));
+    }
+}
@@ -0,0 +1,194 @@
+use merco_llmproxy::{Transport, TransportRequest, html_to_text};
+use std::path::{Path, PathBuf};
+
+// How a document's raw content is turned into text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentFormat {
+    Text,
+    Markdown, // Kept as is, so `HeadingChunker` can split at its headings
+    Html,     // Reduced to its visible text
+    Pdf,      // Needs the `pdf` feature
+}
+
+impl DocumentFormat {
+    // Format implied by a file extension; unknown extensions are read as plain text
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("md") | Some("markdown") | Some("mdx") => DocumentFormat::Markdown,
+            Some("html") | Some("htm") | Some("xhtml") => DocumentFormat::Html,
+            Some("pdf") => DocumentFormat::Pdf,
+            _ => DocumentFormat::Text,
+        }
+    }
+
+    // Format implied by an HTTP content type, if it names one
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "text/html" | "application/xhtml+xml" => Some(DocumentFormat::Html),
+            "text/markdown" | "text/x-markdown" => Some(DocumentFormat::Markdown),
+            "application/pdf" => Some(DocumentFormat::Pdf),
+            mime if mime.starts_with("text/") || mime == "application/json" => Some(DocumentFormat::Text),
+            _ => None,
+        }
+    }
+}
+
+// Text extracted from one source, ready to be chunked
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Document {
+    pub source: String, // Path, URL or label the text came from
+    pub format: DocumentFormat,
+    pub text: String,
+}
+
+impl Document {
+    pub fn new(source: impl Into<String>, format: DocumentFormat, text: impl Into<String>) -> Self {
+        Self { source: source.into(), format, text: text.into() }
+    }
+
+    // Extract the text of `bytes` according to `format`
+    pub fn from_bytes(source: impl Into<String>, format: DocumentFormat, bytes: &[u8]) -> Result<Self, String> {
+        let source = source.into();
+        let text = match format {
+            DocumentFormat::Pdf => pdf_text(&source, bytes)?,
+            _ => {
+                let text = String::from_utf8(bytes.to_vec()).map_err(|_| format!("{} is not UTF-8 text", source))?;
+                if text.contains('\0') {
+                    return Err(format!("{} is not a text file", source));
+                }
+                if format == DocumentFormat::Html { html_to_text(&text) } else { text }
+            }
+        };
+        Ok(Self { source, format, text })
+    }
+}
+
+#[cfg(feature = "pdf")]
+fn pdf_text(source: &str, bytes: &[u8]) -> Result<String, String> {
+    // The extractor panics on some malformed files; one bad PDF shouldn't take the agent down
+    std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(bytes))
+        .map_err(|_| format!("Failed to extract text from {}: malformed PDF", source))?
+        .map_err(|e| format!("Failed to extract text from {}: {}", source, e))
+}
+
+#[cfg(not(feature = "pdf"))]
+fn pdf_text(source: &str, _bytes: &[u8]) -> Result<String, String> {
+    Err(format!("Cannot load {}: PDF support needs the `pdf` feature", source))
+}
+
+// Load a file, picking the format from its extension
+pub fn load_file(path: impl AsRef<Path>) -> Result<Document, String> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Document::from_bytes(path.display().to_string(), DocumentFormat::from_path(path), &bytes)
+}
+
+// Load every document below `dir` in path order. Hidden entries are skipped, and so are
+// files that can't be read as documents (binaries, or PDFs without the `pdf` feature).
+pub fn load_directory(dir: impl AsRef<Path>) -> Result<Vec<Document>, String> {
+    let mut files = Vec::new();
+    collect_files(dir.as_ref(), &mut files)?;
+    files.sort();
+    Ok(files.into_iter().filter_map(|file| load_file(file).ok()).collect())
+}
+
+// Fetch a URL, picking the format from the response's content type or else the URL's
+// extension
+pub async fn load_url(transport: &dyn Transport, url: &str) -> Result<Document, String> {
+    let request = TransportRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: Default::default(),
+        body: Vec::new(),
+    };
+    let response = transport.send(request).await.map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    if !response.is_success() {
+        return Err(format!("Fetching {} returned status {}", url, response.status));
+    }
+    let format = response
+        .headers
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .and_then(DocumentFormat::from_content_type)
+        .unwrap_or_else(|| DocumentFormat::from_path(Path::new(url.split(['?', '#']).next().unwrap_or(url))));
+    let bytes = response.bytes().await.map_err(|e| format!("Failed to read {}: {}", url, e))?;
+    Document::from_bytes(url, format, &bytes)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => collect_files(&path, files)?,
+            Ok(kind) if kind.is_file() => files.push(path),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_formats_from_paths_and_content_types() {
+        assert_eq!(DocumentFormat::from_path(Path::new("docs/Guide.MD")), DocumentFormat::Markdown);
+        assert_eq!(DocumentFormat::from_path(Path::new("index.htm")), DocumentFormat::Html);
+        assert_eq!(DocumentFormat::from_path(Path::new("paper.pdf")), DocumentFormat::Pdf);
+        assert_eq!(DocumentFormat::from_path(Path::new("Makefile")), DocumentFormat::Text);
+
+        assert_eq!(DocumentFormat::from_content_type("text/html; charset=utf-8"), Some(DocumentFormat::Html));
+        assert_eq!(DocumentFormat::from_content_type("application/json"), Some(DocumentFormat::Text));
+        assert_eq!(DocumentFormat::from_content_type("image/png"), None);
+    }
+
+    #[test]
+    fn test_load_directory_reads_documents_in_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("guides")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("guides/setup.md"), "# Setup\n\nRun `cargo build`.").unwrap();
+        fs::write(root.join("guides/faq.html"), "<html><body><h1>FAQ</h1><p>Ask away</p></body></html>").unwrap();
+        fs::write(root.join("changelog.txt"), "v1.0 released").unwrap();
+        // Skipped: hidden entries, binaries and PDFs that don't parse
+        fs::write(root.join(".env"), "API_KEY=secret").unwrap();
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 0]).unwrap();
+        fs::write(root.join("paper.pdf"), "not really a pdf").unwrap();
+
+        let documents = load_directory(root).unwrap();
+        let sources: Vec<String> = documents
+            .iter()
+            .map(|document| Path::new(&document.source).strip_prefix(root).unwrap().display().to_string())
+            .collect();
+        assert_eq!(sources, ["changelog.txt", "guides/faq.html", "guides/setup.md"]);
+
+        assert_eq!(documents[0].format, DocumentFormat::Text);
+        assert_eq!(documents[1].format, DocumentFormat::Html);
+        assert!(documents[1].text.contains("Ask away") && !documents[1].text.contains("<p>"));
+        assert_eq!(documents[2].format, DocumentFormat::Markdown);
+        assert_eq!(documents[2].text, "# Setup\n\nRun `cargo build`.");
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.txt");
+        assert!(load_file(&missing).unwrap_err().starts_with("Failed to read"));
+        assert!(load_directory(&missing).unwrap_err().starts_with("Failed to read"));
+
+        let binary = dir.path().join("data.txt");
+        fs::write(&binary, [b'a', 0, b'b']).unwrap();
+        assert!(load_file(&binary).unwrap_err().ends_with("is not a text file"));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod chunker;
+#[allow(clippy::module_inception)]
+pub mod knowledge;
+pub mod loader;
@@ -1,3 +1,11 @@
 pub mod agent;
 pub mod task;
 pub mod crew;
+pub mod memory;
+pub mod session;
+pub mod definition;
+pub mod approval;
+pub mod audit;
+pub mod knowledge;
+pub mod protocol;
+pub mod profiles;
@@ -1,5 +1,5 @@
 use merco_agents::agent::agent::Agent;
-use merco_agents::task::task::{Task, JsonFieldType, JsonField};
+use merco_agents::task::task::{Task, JsonFieldType, JsonField, JsonSchema, FieldConstraints};
 use merco_llmproxy::{LlmConfig, Provider, get_tools_by_names, merco_tool};
 use merco_agents::agent::agent::AgentLLMConfig;
 
@@ -67,8 +67,8 @@ async fn main() -> anyhow::Result<()> {
     match agent_no_tools.call(json_task).await {
         Ok(result) => {
             println!("JSON Task Result: {}", result);
-            // Parse and pretty-print the JSON
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            // Pretty-print the parsed JSON
+            if let Some(parsed) = &result.parsed_json {
                 println!("Parsed JSON: {}", serde_json::to_string_pretty(&parsed)?);
             }
         },
@@ -86,34 +86,86 @@ async fn main() -> anyhow::Result<()> {
                 name: "user_id".to_string(),
                 field_type: JsonFieldType::Number,
                 description: Some("Unique user identifier".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "personal_info".to_string(),
-                field_type: JsonFieldType::Object,
+                field_type: JsonFieldType::Object(JsonSchema::new(
+                    vec![
+                        JsonField {
+                            name: "name".to_string(),
+                            field_type: JsonFieldType::String,
+                            description: Some("Full name".to_string()),
+                            constraints: FieldConstraints::default(),
+                        },
+                        JsonField {
+                            name: "age".to_string(),
+                            field_type: JsonFieldType::Number,
+                            description: Some("Age in years".to_string()),
+                            constraints: FieldConstraints::new().with_range(0.0, 150.0),
+                        },
+                    ],
+                    vec![
+                        JsonField {
+                            name: "email".to_string(),
+                            field_type: JsonFieldType::String,
+                            description: Some("Email address".to_string()),
+                            constraints: FieldConstraints::new().with_pattern(r"^[^@\s]+@[^@\s]+\.[^@\s]+$")?,
+                        },
+                    ],
+                )),
                 description: Some("Personal information object containing name, age, email".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "address".to_string(),
-                field_type: JsonFieldType::Object,
+                field_type: JsonFieldType::Object(JsonSchema::new(
+                    vec![
+                        JsonField {
+                            name: "city".to_string(),
+                            field_type: JsonFieldType::String,
+                            description: Some("City name".to_string()),
+                            constraints: FieldConstraints::default(),
+                        },
+                        JsonField {
+                            name: "country".to_string(),
+                            field_type: JsonFieldType::String,
+                            description: Some("Country name".to_string()),
+                            constraints: FieldConstraints::default(),
+                        },
+                    ],
+                    vec![
+                        JsonField {
+                            name: "state".to_string(),
+                            field_type: JsonFieldType::String,
+                            description: Some("State or region".to_string()),
+                            constraints: FieldConstraints::default(),
+                        },
+                    ],
+                )),
                 description: Some("Address object containing city, state, country".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "active".to_string(),
                 field_type: JsonFieldType::Boolean,
                 description: Some("Whether the user account is active".to_string()),
+                constraints: FieldConstraints::default(),
             },
         ],
         vec![
             // Optional fields
             JsonField {
                 name: "preferences".to_string(),
-                field_type: JsonFieldType::Object,
+                field_type: JsonFieldType::Object(JsonSchema::default()), // Free-form
                 description: Some("User preferences object".to_string()),
+                constraints: FieldConstraints::default(),
             },
             JsonField {
                 name: "tags".to_string(),
                 field_type: JsonFieldType::Array(Box::new(JsonFieldType::String)),
                 description: Some("Array of string tags associated with the user".to_string()),
+                constraints: FieldConstraints::new().with_items(None, Some(5)),
             },
         ],
         false, // not strict mode - allow extra fields
@@ -122,24 +174,24 @@ async fn main() -> anyhow::Result<()> {
     match agent_no_tools.call(nested_task).await {
         Ok(result) => {
             println!("Nested JSON Task Result: {}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("Parsed JSON: {}", serde_json::to_string_pretty(&parsed)?);
                 
                 // Demonstrate accessing nested fields
-                if let Some(personal_info) = parsed.get("personal_info") {
-                    if personal_info.is_object() {
-                        println!("✅ personal_info is correctly formatted as an object");
-                        if let Some(name) = personal_info.get("name") {
-                            println!("   - Name: {}", name);
-                        }
+                if let Some(personal_info) = parsed.get("personal_info")
+                    && personal_info.is_object()
+                {
+                    println!("✅ personal_info is correctly formatted as an object");
+                    if let Some(name) = personal_info.get("name") {
+                        println!("   - Name: {}", name);
                     }
                 }
-                if let Some(address) = parsed.get("address") {
-                    if address.is_object() {
-                        println!("✅ address is correctly formatted as an object");
-                        if let Some(city) = address.get("city") {
-                            println!("   - City: {}", city);
-                        }
+                if let Some(address) = parsed.get("address")
+                    && address.is_object()
+                {
+                    println!("✅ address is correctly formatted as an object");
+                    if let Some(city) = address.get("city") {
+                        println!("   - City: {}", city);
                     }
                 }
             }
@@ -163,19 +215,19 @@ async fn main() -> anyhow::Result<()> {
     match agent_no_tools.call(array_task).await {
         Ok(result) => {
             println!("Array Task Result: {}", result);
-            if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&result) {
+            if let Some(parsed) = &result.parsed_json {
                 println!("Parsed JSON: {}", serde_json::to_string_pretty(&parsed)?);
                 
                 // Validate arrays
-                if let Some(languages) = parsed.get("languages") {
-                    if let Some(lang_array) = languages.as_array() {
-                        println!("✅ languages array contains {} items", lang_array.len());
-                    }
+                if let Some(languages) = parsed.get("languages")
+                    && let Some(lang_array) = languages.as_array()
+                {
+                    println!("✅ languages array contains {} items", lang_array.len());
                 }
-                if let Some(ratings) = parsed.get("difficulty_ratings") {
-                    if let Some(rating_array) = ratings.as_array() {
-                        println!("✅ difficulty_ratings array contains {} items", rating_array.len());
-                    }
+                if let Some(ratings) = parsed.get("difficulty_ratings")
+                    && let Some(rating_array) = ratings.as_array()
+                {
+                    println!("✅ difficulty_ratings array contains {} items", rating_array.len());
                 }
             }
         },
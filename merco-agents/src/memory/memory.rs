@@ -0,0 +1,164 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+
+// A single remembered finding
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MemoryEntry {
+    pub content: String,
+    pub source: Option<String>, // Where the finding came from (e.g. the task that produced it)
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>, // Structured details, e.g. a knowledge chunk's position
+}
+
+impl MemoryEntry {
+    pub fn new(content: String, source: Option<String>) -> Self {
+        Self {
+            content,
+            source,
+            created_at: Utc::now(),
+            metadata: None,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+// Storage for findings that can be recalled by relevance to a query
+#[async_trait]
+pub trait Memory: Send + Sync {
+    async fn save(&self, entry: MemoryEntry) -> Result<(), String>;
+
+    // Save several entries; stores that can batch the work (e.g. embeddings) override this
+    async fn save_all(&self, entries: Vec<MemoryEntry>) -> Result<(), String> {
+        for entry in entries {
+            self.save(entry).await?;
+        }
+        Ok(())
+    }
+
+    // Returns up to `limit` entries relevant to `query`, most relevant first
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryEntry>, String>;
+
+    async fn clear(&self) -> Result<(), String>;
+}
+
+// Default in-process memory ranking entries by keyword overlap with the query
+#[derive(Debug, Default)]
+pub struct InMemoryMemory {
+    entries: Mutex<Vec<MemoryEntry>>,
+}
+
+impl InMemoryMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn keywords(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 2)
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+// Up to `limit` of `entries` sharing the most keywords with `query`; entries sharing none
+// are left out
+pub(crate) fn rank_by_keywords<'a>(
+    query: &str,
+    entries: impl IntoIterator<Item = &'a MemoryEntry>,
+    limit: usize,
+) -> Vec<MemoryEntry> {
+    let query_words = keywords(query);
+    let mut scored: Vec<(usize, &MemoryEntry)> = entries
+        .into_iter()
+        .map(|entry| {
+            let entry_words = keywords(&entry.content);
+            let score = query_words.iter().filter(|w| entry_words.contains(w)).count();
+            (score, entry)
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    // Highest score first; newer entries win ties
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.created_at.cmp(&a.1.created_at)));
+
+    scored.into_iter().take(limit).map(|(_, entry)| entry.clone()).collect()
+}
+
+#[async_trait]
+impl Memory for InMemoryMemory {
+    async fn save(&self, entry: MemoryEntry) -> Result<(), String> {
+        self.entries
+            .lock()
+            .map_err(|e| format!("Failed to lock memory: {}", e))?
+            .push(entry);
+        Ok(())
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryEntry>, String> {
+        let entries = self.entries.lock().map_err(|e| format!("Failed to lock memory: {}", e))?;
+        Ok(rank_by_keywords(query, entries.iter(), limit))
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.entries
+            .lock()
+            .map_err(|e| format!("Failed to lock memory: {}", e))?
+            .clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn entry(content: &str, age_secs: i64) -> MemoryEntry {
+        let mut entry = MemoryEntry::new(content.to_string(), None);
+        entry.created_at -= Duration::seconds(age_secs);
+        entry
+    }
+
+    #[test]
+    fn test_ranks_by_keyword_overlap() {
+        let entries = vec![
+            entry("The rust compiler is fast", 0),
+            entry("Rust borrow checker and compiler errors", 0),
+            entry("Python is dynamic", 0),
+        ];
+
+        let ranked = rank_by_keywords("rust compiler errors", &entries, 5);
+        let contents: Vec<&str> = ranked.iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(contents, ["Rust borrow checker and compiler errors", "The rust compiler is fast"]);
+    }
+
+    #[test]
+    fn test_newer_entries_win_ties_and_limit_applies() {
+        let entries = vec![entry("old rust note", 60), entry("new rust note", 0), entry("mid rust note", 30)];
+
+        let ranked = rank_by_keywords("rust", &entries, 2);
+        let contents: Vec<&str> = ranked.iter().map(|e| e.content.as_str()).collect();
+        assert_eq!(contents, ["new rust note", "mid rust note"]);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_save_search_and_clear() {
+        let memory = InMemoryMemory::new();
+        memory
+            .save_all(vec![entry("Paris is the capital of France", 0), entry("Tokyo is in Japan", 0)])
+            .await
+            .unwrap();
+
+        let found = memory.search("capital of France", 5).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].content, "Paris is the capital of France");
+
+        memory.clear().await.unwrap();
+        assert!(memory.search("capital of France", 5).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,6 @@
+#[allow(clippy::module_inception)]
+pub mod memory;
+pub mod short_term;
+pub mod vector;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
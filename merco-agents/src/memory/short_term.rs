@@ -0,0 +1,215 @@
+use merco_llmproxy::tokenizer::count_message_tokens;
+use merco_llmproxy::{ChatMessage, HeuristicTokenizer, Tokenizer};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// One finished task: the request the agent was given and the answer it gave
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Exchange {
+    pub task: String,
+    pub answer: String,
+}
+
+impl Exchange {
+    fn messages(&self) -> [ChatMessage; 2] {
+        [ChatMessage::user(format!("TASK: {}", self.task)), ChatMessage::assistant(Some(self.answer.clone()), None)]
+    }
+}
+
+// Durable storage behind a `ShortTermMemory`, so its exchanges survive restarts
+pub trait ExchangeStore: Send + Sync {
+    // Every stored exchange, oldest first
+    fn load(&self) -> Result<Vec<Exchange>, String>;
+
+    fn append(&self, exchange: &Exchange) -> Result<(), String>;
+
+    fn clear(&self) -> Result<(), String>;
+}
+
+// An agent's recent task exchanges, replayed ahead of each new task so follow-up tasks
+// can refer to earlier ones. The oldest exchanges are dropped once the history exceeds
+// the token budget; tool calls made along the way are not kept.
+pub struct ShortTermMemory {
+    exchanges: Mutex<VecDeque<Exchange>>,
+    max_tokens: usize,
+    tokenizer: Arc<dyn Tokenizer>,
+    store: Option<Arc<dyn ExchangeStore>>,
+}
+
+impl fmt::Debug for ShortTermMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShortTermMemory")
+            .field("exchanges", &self.len())
+            .field("max_tokens", &self.max_tokens)
+            .field("store", &self.store.as_ref().map(|_| "<ExchangeStore>"))
+            .finish()
+    }
+}
+
+impl ShortTermMemory {
+    pub fn new(max_tokens: usize) -> Self {
+        Self {
+            exchanges: Mutex::new(VecDeque::new()),
+            max_tokens,
+            tokenizer: Arc::new(HeuristicTokenizer::default()),
+            store: None,
+        }
+    }
+
+    // Persist exchanges in `store`, starting from the ones it already holds (as many of
+    // the most recent as fit the budget)
+    pub fn with_store(mut self, store: Arc<dyn ExchangeStore>) -> Result<Self, String> {
+        let mut kept = VecDeque::new();
+        for exchange in store.load()?.into_iter().rev() {
+            kept.push_front(exchange);
+            if self.count(kept.iter()) > self.max_tokens {
+                kept.pop_front();
+                break;
+            }
+        }
+        self.exchanges = Mutex::new(kept);
+        self.store = Some(store);
+        Ok(self)
+    }
+
+    // Count tokens with the model's own tokenizer instead of the character heuristic
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    // Add a finished exchange, then drop the oldest ones until the history fits the budget.
+    // An exchange larger than the whole budget is not kept at all.
+    pub fn remember(&self, task: impl Into<String>, answer: impl Into<String>) {
+        let exchange = Exchange { task: task.into(), answer: answer.into() };
+        if let Some(store) = &self.store
+            && let Err(e) = store.append(&exchange)
+        {
+            eprintln!("Failed to persist exchange: {}", e);
+        }
+        let mut exchanges = self.exchanges.lock().unwrap();
+        exchanges.push_back(exchange);
+        while !exchanges.is_empty() && self.count(exchanges.iter()) > self.max_tokens {
+            exchanges.pop_front();
+        }
+    }
+
+    // The history as alternating user/assistant messages, oldest first
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        self.exchanges.lock().unwrap().iter().flat_map(Exchange::messages).collect()
+    }
+
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.lock().unwrap().iter().cloned().collect()
+    }
+
+    // Estimated prompt tokens the history adds to a request
+    pub fn token_count(&self) -> usize {
+        self.count(self.exchanges.lock().unwrap().iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.exchanges.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.exchanges.lock().unwrap().clear();
+        if let Some(store) = &self.store
+            && let Err(e) = store.clear()
+        {
+            eprintln!("Failed to clear persisted exchanges: {}", e);
+        }
+    }
+
+    fn count<'a>(&self, exchanges: impl Iterator<Item = &'a Exchange>) -> usize {
+        let messages: Vec<ChatMessage> = exchanges.flat_map(Exchange::messages).collect();
+        count_message_tokens(self.tokenizer.as_ref(), &messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Budget that fits exactly two exchanges of the size `remember_n` uses
+    fn two_exchange_budget() -> usize {
+        let probe = ShortTermMemory::new(usize::MAX);
+        remember_n(&probe, 2);
+        probe.token_count()
+    }
+
+    fn remember_n(memory: &ShortTermMemory, count: usize) {
+        for i in 1..=count {
+            memory.remember(format!("Task {}", i), format!("Answer {}", i));
+        }
+    }
+
+    fn tasks(exchanges: &[Exchange]) -> Vec<&str> {
+        exchanges.iter().map(|exchange| exchange.task.as_str()).collect()
+    }
+
+    #[test]
+    fn test_drops_the_oldest_exchanges_at_capacity() {
+        let memory = ShortTermMemory::new(two_exchange_budget());
+        remember_n(&memory, 4);
+
+        assert_eq!(tasks(&memory.exchanges()), ["Task 3", "Task 4"]);
+        assert!(memory.token_count() <= memory.max_tokens());
+        let messages = memory.messages();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].content.as_deref(), Some("TASK: Task 3"));
+        assert_eq!(messages[3].content.as_deref(), Some("Answer 4"));
+    }
+
+    #[test]
+    fn test_oversized_exchange_is_not_kept() {
+        let memory = ShortTermMemory::new(two_exchange_budget());
+        remember_n(&memory, 1);
+        memory.remember("Summarize the book", "word ".repeat(500));
+
+        assert!(memory.is_empty());
+        assert_eq!(memory.token_count(), ShortTermMemory::new(0).token_count());
+    }
+
+    #[derive(Default)]
+    struct VecStore(Mutex<Vec<Exchange>>);
+
+    impl ExchangeStore for VecStore {
+        fn load(&self) -> Result<Vec<Exchange>, String> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn append(&self, exchange: &Exchange) -> Result<(), String> {
+            self.0.lock().unwrap().push(exchange.clone());
+            Ok(())
+        }
+
+        fn clear(&self) -> Result<(), String> {
+            self.0.lock().unwrap().clear();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_store_keeps_history_and_reloads_what_fits() {
+        let store = Arc::new(VecStore::default());
+        let memory = ShortTermMemory::new(usize::MAX).with_store(store.clone()).unwrap();
+        remember_n(&memory, 3);
+        assert_eq!(store.load().unwrap().len(), 3);
+
+        let reloaded = ShortTermMemory::new(two_exchange_budget()).with_store(store.clone()).unwrap();
+        assert_eq!(tasks(&reloaded.exchanges()), ["Task 2", "Task 3"]);
+
+        reloaded.clear();
+        assert!(store.load().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,447 @@
+use crate::memory::memory::{Memory, MemoryEntry, rank_by_keywords};
+use crate::memory::short_term::{Exchange, ExchangeStore};
+use crate::memory::vector::{VectorRecord, VectorStore};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use merco_llmproxy::cosine_similarity;
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS memories (
+    id INTEGER PRIMARY KEY,
+    namespace TEXT NOT NULL,
+    content TEXT NOT NULL,
+    source TEXT,
+    created_at TEXT NOT NULL,
+    vector BLOB,
+    metadata TEXT
+);
+CREATE INDEX IF NOT EXISTS memories_namespace ON memories (namespace);
+CREATE TABLE IF NOT EXISTS exchanges (
+    id INTEGER PRIMARY KEY,
+    namespace TEXT NOT NULL,
+    task TEXT NOT NULL,
+    answer TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS exchanges_namespace ON exchanges (namespace);
+CREATE TABLE IF NOT EXISTS state (
+    namespace TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (namespace, key)
+);
+";
+
+// A stored memory and, for vector stores, its embedding
+type StoredEntry = (MemoryEntry, Option<Vec<f32>>);
+
+// One SQLite file holding an agent's or crew's memories, embedded vectors, conversation
+// exchanges and key-value state, so a restarted process picks up where it left off. Each
+// handle works in a namespace (e.g. an agent id), so one file can serve a whole crew.
+#[derive(Clone)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl fmt::Debug for SqliteStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SqliteStore").finish_non_exhaustive()
+    }
+}
+
+impl SqliteStore {
+    // Open (or create) the database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let connection = Connection::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Self::init(connection)
+    }
+
+    // A database that lives only as long as this store, e.g. for tests
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(connection: Connection) -> Result<Self, String> {
+        connection.execute_batch(SCHEMA).map_err(|e| format!("Failed to create tables: {}", e))?;
+        // Databases created before entries had metadata lack the column
+        let has_metadata = connection.prepare("SELECT metadata FROM memories LIMIT 0").is_ok();
+        if !has_metadata {
+            connection
+                .execute_batch("ALTER TABLE memories ADD COLUMN metadata TEXT")
+                .map_err(|e| format!("Failed to upgrade tables: {}", e))?;
+        }
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.connection.lock().map_err(|e| format!("Failed to lock database: {}", e))
+    }
+
+    // Findings recalled by keyword overlap, like `InMemoryMemory`
+    pub fn memory(&self, namespace: impl Into<String>) -> SqliteMemory {
+        SqliteMemory { store: self.clone(), namespace: namespace.into() }
+    }
+
+    // Embedded entries for a `VectorMemory`, searched by a scan over the namespace
+    pub fn vectors(&self, namespace: impl Into<String>) -> SqliteVectorStore {
+        SqliteVectorStore { store: self.clone(), namespace: namespace.into() }
+    }
+
+    // Exchanges behind a `ShortTermMemory`
+    pub fn exchanges(&self, namespace: impl Into<String>) -> SqliteExchangeStore {
+        SqliteExchangeStore { store: self.clone(), namespace: namespace.into() }
+    }
+
+    // JSON values by key
+    pub fn state(&self, namespace: impl Into<String>) -> SqliteState {
+        SqliteState { store: self.clone(), namespace: namespace.into() }
+    }
+
+    fn insert_entries(&self, namespace: &str, entries: Vec<StoredEntry>) -> Result<(), String> {
+        let mut connection = self.lock()?;
+        let transaction = connection.transaction().map_err(|e| e.to_string())?;
+        {
+            let mut insert = transaction
+                .prepare(
+                    "INSERT INTO memories (namespace, content, source, created_at, vector, metadata)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )
+                .map_err(|e| e.to_string())?;
+            for (entry, vector) in entries {
+                let created_at = entry.created_at.to_rfc3339();
+                let vector = vector.as_deref().map(vector_to_blob);
+                let metadata = entry.metadata.as_ref().map(|metadata| metadata.to_string());
+                insert
+                    .execute(params![namespace, entry.content, entry.source, created_at, vector, metadata])
+                    .map_err(|e| format!("Failed to save memory: {}", e))?;
+            }
+        }
+        transaction.commit().map_err(|e| e.to_string())
+    }
+
+    // Entries of `namespace` (with their vectors, where present), oldest first
+    fn load_entries(&self, namespace: &str, with_vectors: bool) -> Result<Vec<StoredEntry>, String> {
+        let connection = self.lock()?;
+        let sql = if with_vectors {
+            "SELECT content, source, created_at, metadata, vector FROM memories
+             WHERE namespace = ?1 AND vector IS NOT NULL ORDER BY id"
+        } else {
+            "SELECT content, source, created_at, metadata, NULL FROM memories
+             WHERE namespace = ?1 AND vector IS NULL ORDER BY id"
+        };
+        let mut select = connection.prepare(sql).map_err(|e| e.to_string())?;
+        let rows = select
+            .query_map(params![namespace], |row| {
+                let created_at: String = row.get(2)?;
+                let metadata: Option<String> = row.get(3)?;
+                let vector: Option<Vec<u8>> = row.get(4)?;
+                let entry = MemoryEntry {
+                    content: row.get(0)?,
+                    source: row.get(1)?,
+                    created_at: DateTime::parse_from_rfc3339(&created_at)
+                        .map(|time| time.with_timezone(&Utc))
+                        .unwrap_or_default(),
+                    metadata: metadata.and_then(|metadata| serde_json::from_str(&metadata).ok()),
+                };
+                Ok((entry, vector.map(|blob| blob_to_vector(&blob))))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| format!("Failed to load memories: {}", e))
+    }
+
+    fn delete_entries(&self, namespace: &str, with_vectors: bool) -> Result<(), String> {
+        let sql = if with_vectors {
+            "DELETE FROM memories WHERE namespace = ?1 AND vector IS NOT NULL"
+        } else {
+            "DELETE FROM memories WHERE namespace = ?1 AND vector IS NULL"
+        };
+        self.lock()?.execute(sql, params![namespace]).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn vector_to_blob(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn blob_to_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteMemory {
+    store: SqliteStore,
+    namespace: String,
+}
+
+#[async_trait]
+impl Memory for SqliteMemory {
+    async fn save(&self, entry: MemoryEntry) -> Result<(), String> {
+        self.store.insert_entries(&self.namespace, vec![(entry, None)])
+    }
+
+    async fn save_all(&self, entries: Vec<MemoryEntry>) -> Result<(), String> {
+        self.store.insert_entries(&self.namespace, entries.into_iter().map(|entry| (entry, None)).collect())
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryEntry>, String> {
+        let entries = self.store.load_entries(&self.namespace, false)?;
+        Ok(rank_by_keywords(query, entries.iter().map(|(entry, _)| entry), limit))
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.store.delete_entries(&self.namespace, false)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteVectorStore {
+    store: SqliteStore,
+    namespace: String,
+}
+
+#[async_trait]
+impl VectorStore for SqliteVectorStore {
+    async fn insert(&self, records: Vec<VectorRecord>) -> Result<(), String> {
+        let entries = records.into_iter().map(|record| (record.entry, Some(record.vector))).collect();
+        self.store.insert_entries(&self.namespace, entries)
+    }
+
+    async fn search(&self, vector: &[f32], limit: usize) -> Result<Vec<(f32, VectorRecord)>, String> {
+        let mut scored: Vec<(f32, VectorRecord)> = self
+            .store
+            .load_entries(&self.namespace, true)?
+            .into_iter()
+            .map(|(entry, stored)| {
+                let stored = stored.unwrap_or_default();
+                (cosine_similarity(vector, &stored), VectorRecord { vector: stored, entry })
+            })
+            .collect();
+        // Most similar first; newer entries win ties
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.entry.created_at.cmp(&a.1.entry.created_at)));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.store.delete_entries(&self.namespace, true)
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        let count: i64 = self
+            .store
+            .lock()?
+            .query_row(
+                "SELECT COUNT(*) FROM memories WHERE namespace = ?1 AND vector IS NOT NULL",
+                params![self.namespace],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(count as usize)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteExchangeStore {
+    store: SqliteStore,
+    namespace: String,
+}
+
+impl ExchangeStore for SqliteExchangeStore {
+    fn load(&self) -> Result<Vec<Exchange>, String> {
+        let connection = self.store.lock()?;
+        let mut select = connection
+            .prepare("SELECT task, answer FROM exchanges WHERE namespace = ?1 ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let rows = select
+            .query_map(params![self.namespace], |row| Ok(Exchange { task: row.get(0)?, answer: row.get(1)? }))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| format!("Failed to load exchanges: {}", e))
+    }
+
+    fn append(&self, exchange: &Exchange) -> Result<(), String> {
+        self.store
+            .lock()?
+            .execute(
+                "INSERT INTO exchanges (namespace, task, answer) VALUES (?1, ?2, ?3)",
+                params![self.namespace, exchange.task, exchange.answer],
+            )
+            .map_err(|e| format!("Failed to save exchange: {}", e))?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        self.store
+            .lock()?
+            .execute("DELETE FROM exchanges WHERE namespace = ?1", params![self.namespace])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+// Key-value agent state, stored as JSON
+#[derive(Debug, Clone)]
+pub struct SqliteState {
+    store: SqliteStore,
+    namespace: String,
+}
+
+impl SqliteState {
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, String> {
+        let value: Option<String> = self
+            .store
+            .lock()?
+            .query_row(
+                "SELECT value FROM state WHERE namespace = ?1 AND key = ?2",
+                params![self.namespace, key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        value
+            .map(|value| serde_json::from_str(&value).map_err(|e| format!("Invalid state for '{}': {}", key, e)))
+            .transpose()
+    }
+
+    pub fn set<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> Result<(), String> {
+        let value = serde_json::to_string(value).map_err(|e| e.to_string())?;
+        self.store
+            .lock()?
+            .execute(
+                "INSERT INTO state (namespace, key, value) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (namespace, key) DO UPDATE SET value = excluded.value",
+                params![self.namespace, key, value],
+            )
+            .map_err(|e| format!("Failed to save state '{}': {}", key, e))?;
+        Ok(())
+    }
+
+    // Whether the key existed
+    pub fn remove(&self, key: &str) -> Result<bool, String> {
+        let removed = self
+            .store
+            .lock()?
+            .execute("DELETE FROM state WHERE namespace = ?1 AND key = ?2", params![self.namespace, key])
+            .map_err(|e| e.to_string())?;
+        Ok(removed > 0)
+    }
+
+    pub fn keys(&self) -> Result<Vec<String>, String> {
+        let connection = self.store.lock()?;
+        let mut select = connection
+            .prepare("SELECT key FROM state WHERE namespace = ?1 ORDER BY key")
+            .map_err(|e| e.to_string())?;
+        let rows = select.query_map(params![self.namespace], |row| row.get(0)).map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(content: &str) -> MemoryEntry {
+        MemoryEntry::new(content.to_string(), Some("research".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_memory_round_trip() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let memory = store.memory("researcher");
+        memory.save(entry("Rust 1.85 stabilized async closures")).await.unwrap();
+        memory
+            .save_all(vec![
+                entry("Paris is the capital of France").with_metadata(json!({"chunk": 3})),
+                entry("Rust editions ship every three years"),
+            ])
+            .await
+            .unwrap();
+
+        let found = memory.search("rust editions", 5).await.unwrap();
+        let contents: Vec<&str> = found.iter().map(|entry| entry.content.as_str()).collect();
+        assert_eq!(contents, ["Rust editions ship every three years", "Rust 1.85 stabilized async closures"]);
+        assert_eq!(found[0].source.as_deref(), Some("research"));
+
+        let paris = memory.search("capital of France", 1).await.unwrap();
+        assert_eq!(paris[0].metadata, Some(json!({"chunk": 3})));
+
+        // Namespaces share the file but not the entries
+        assert!(store.memory("writer").search("rust", 5).await.unwrap().is_empty());
+        memory.clear().await.unwrap();
+        assert!(memory.search("rust", 5).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_vector_round_trip() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let vectors = store.vectors("researcher");
+        let record = |content: &str, vector: Vec<f32>| VectorRecord { vector, entry: entry(content) };
+        vectors
+            .insert(vec![record("north", vec![0.0, 1.0, 0.0]), record("east", vec![1.0, 0.0, 0.0])])
+            .await
+            .unwrap();
+        // Plain memories in the namespace are not counted as vectors
+        store.memory("researcher").save(entry("unembedded")).await.unwrap();
+        assert_eq!(vectors.count().await.unwrap(), 2);
+
+        let found = vectors.search(&[0.9, 0.1, 0.0], 1).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1.entry.content, "east");
+        assert_eq!(found[0].1.vector, vec![1.0, 0.0, 0.0]);
+        assert!(found[0].0 > 0.9);
+
+        vectors.clear().await.unwrap();
+        assert_eq!(vectors.count().await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_exchanges_round_trip() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let exchanges = store.exchanges("assistant");
+        let first = Exchange { task: "Name a prime".to_string(), answer: "7".to_string() };
+        let second = Exchange { task: "And another".to_string(), answer: "11".to_string() };
+        exchanges.append(&first).unwrap();
+        exchanges.append(&second).unwrap();
+
+        assert_eq!(exchanges.load().unwrap(), [first, second]);
+        assert!(store.exchanges("other").load().unwrap().is_empty());
+        exchanges.clear().unwrap();
+        assert!(exchanges.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let state = store.state("crew");
+        state.set("step", &2).unwrap();
+        state.set("done", &["plan", "research"]).unwrap();
+        state.set("step", &3).unwrap();
+
+        assert_eq!(state.get::<u32>("step").unwrap(), Some(3));
+        assert_eq!(state.get::<Vec<String>>("done").unwrap(), Some(vec!["plan".to_string(), "research".to_string()]));
+        assert_eq!(state.get::<u32>("missing").unwrap(), None);
+        assert!(state.get::<u32>("done").unwrap_err().contains("Invalid state for 'done'"));
+        assert_eq!(state.keys().unwrap(), ["done", "step"]);
+
+        assert!(state.remove("step").unwrap());
+        assert!(!state.remove("step").unwrap());
+        assert_eq!(state.keys().unwrap(), ["done"]);
+    }
+
+    #[test]
+    fn test_file_store_survives_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.db");
+        SqliteStore::open(&path).unwrap().state("crew").set("step", &4).unwrap();
+
+        let reopened = SqliteStore::open(&path).unwrap();
+        assert_eq!(reopened.state("crew").get::<u32>("step").unwrap(), Some(4));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::memory::memory::{Memory, MemoryEntry};
+use async_trait::async_trait;
+use merco_llmproxy::{EmbeddingProvider, EmbeddingRequest, cosine_similarity};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+// Most facts kept from a single task output
+const MAX_FACTS_PER_OUTPUT: usize = 16;
+// Lines longer than this are split into sentences before they are stored
+const MAX_FACT_CHARS: usize = 300;
+
+// An embedded memory entry
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VectorRecord {
+    pub vector: Vec<f32>,
+    pub entry: MemoryEntry,
+}
+
+// Storage and nearest-neighbour search for embedded entries
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    async fn insert(&self, records: Vec<VectorRecord>) -> Result<(), String>;
+
+    // Up to `limit` records most similar to `vector` with their cosine similarity, best first
+    async fn search(&self, vector: &[f32], limit: usize) -> Result<Vec<(f32, VectorRecord)>, String>;
+
+    async fn clear(&self) -> Result<(), String>;
+
+    async fn count(&self) -> Result<usize, String>;
+}
+
+// Default store: a brute-force scan over vectors kept in process
+#[derive(Debug, Default)]
+pub struct InMemoryVectorStore {
+    records: RwLock<Vec<VectorRecord>>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn insert(&self, records: Vec<VectorRecord>) -> Result<(), String> {
+        self.records.write().map_err(|e| format!("Failed to lock vector store: {}", e))?.extend(records);
+        Ok(())
+    }
+
+    async fn search(&self, vector: &[f32], limit: usize) -> Result<Vec<(f32, VectorRecord)>, String> {
+        let records = self.records.read().map_err(|e| format!("Failed to lock vector store: {}", e))?;
+        let mut scored: Vec<(f32, &VectorRecord)> =
+            records.iter().map(|record| (cosine_similarity(vector, &record.vector), record)).collect();
+        // Most similar first; newer entries win ties
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.entry.created_at.cmp(&a.1.entry.created_at)));
+        Ok(scored.into_iter().take(limit).map(|(score, record)| (score, record.clone())).collect())
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.records.write().map_err(|e| format!("Failed to lock vector store: {}", e))?.clear();
+        Ok(())
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        Ok(self.records.read().map_err(|e| format!("Failed to lock vector store: {}", e))?.len())
+    }
+}
+
+// Memory that recalls entries by meaning rather than shared keywords: entries are
+// embedded when saved, and searches return the nearest ones above `min_similarity`
+pub struct VectorMemory {
+    embedder: Arc<dyn EmbeddingProvider>,
+    model: String,
+    store: Arc<dyn VectorStore>,
+    min_similarity: f32,
+}
+
+impl fmt::Debug for VectorMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VectorMemory")
+            .field("model", &self.model)
+            .field("min_similarity", &self.min_similarity)
+            .finish()
+    }
+}
+
+impl VectorMemory {
+    // Embed with `model` of `embedder`, storing vectors in an `InMemoryVectorStore`
+    pub fn new(embedder: Arc<dyn EmbeddingProvider>, model: impl Into<String>) -> Self {
+        Self {
+            embedder,
+            model: model.into(),
+            store: Arc::new(InMemoryVectorStore::new()),
+            min_similarity: 0.0,
+        }
+    }
+
+    // Keep vectors in `store` instead, e.g. a persistent or external database
+    pub fn with_store(mut self, store: Arc<dyn VectorStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    // Leave out search results less similar than this (cosine similarity, -1.0 to 1.0)
+    pub fn with_min_similarity(mut self, min_similarity: f32) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn VectorStore> {
+        &self.store
+    }
+
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, String> {
+        let count = texts.len();
+        let embeddings = self
+            .embedder
+            .embed(EmbeddingRequest::new(self.model.clone(), texts))
+            .await
+            .map_err(|e| format!("Embedding failed: {}", e))?
+            .embeddings;
+        if embeddings.len() != count {
+            return Err(format!("Expected {} embeddings, got {}", count, embeddings.len()));
+        }
+        Ok(embeddings)
+    }
+}
+
+#[async_trait]
+impl Memory for VectorMemory {
+    async fn save(&self, entry: MemoryEntry) -> Result<(), String> {
+        self.save_all(vec![entry]).await
+    }
+
+    async fn save_all(&self, entries: Vec<MemoryEntry>) -> Result<(), String> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let vectors = self.embed(entries.iter().map(|entry| entry.content.clone()).collect()).await?;
+        let records = vectors.into_iter().zip(entries).map(|(vector, entry)| VectorRecord { vector, entry }).collect();
+        self.store.insert(records).await
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> Result<Vec<MemoryEntry>, String> {
+        let vector = self.embed(vec![query.to_string()]).await?.pop().unwrap_or_default();
+        Ok(self
+            .store
+            .search(&vector, limit)
+            .await?
+            .into_iter()
+            .filter(|(score, _)| *score >= self.min_similarity)
+            .map(|(_, record)| record.entry)
+            .collect())
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.store.clear().await
+    }
+}
+
+// Drop Markdown list, heading and quote markers ("- ", "## ", "> - ") from the start of a
+// line. A marker must be followed by whitespace, so "-5°C" and "**bold**" are kept.
+fn strip_markers(line: &str) -> &str {
+    let marker_len = match line.chars().next() {
+        Some('#') => line.len() - line.trim_start_matches('#').len(),
+        Some('-' | '*' | '+' | '>') => 1,
+        _ => return line,
+    };
+    match line[marker_len..].strip_prefix([' ', '\t']) {
+        Some(rest) => strip_markers(rest.trim_start()),
+        None => line,
+    }
+}
+
+// Split a task output into short standalone statements worth remembering: one per line
+// (bullets stripped), long lines split into sentences. JSON outputs are kept whole.
+pub fn salient_facts(output: &str) -> Vec<String> {
+    let output = output.trim();
+    if output.starts_with('{') || output.starts_with('[') {
+        return vec![output.to_string()];
+    }
+    let mut facts = Vec::new();
+    for line in output.lines() {
+        let line = strip_markers(line.trim()).trim();
+        if line.chars().count() <= MAX_FACT_CHARS {
+            facts.push(line.to_string());
+        } else {
+            facts.extend(line.split_inclusive(['.', '!', '?']).map(|sentence| sentence.trim().to_string()));
+        }
+    }
+    // Headings, labels and fragments carry little on their own
+    facts.retain(|fact| fact.split_whitespace().count() >= 3);
+    facts.truncate(MAX_FACTS_PER_OUTPUT);
+    facts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merco_llmproxy::MockProvider;
+
+    #[test]
+    fn test_salient_facts_strip_markers_only() {
+        let output = "## Forecast\n- Rain expected in the afternoon\n> * Winds from the north\n-5°C overnight in the valleys\n**Frost** is likely tonight\n1.5 mm of rain fell";
+        assert_eq!(
+            salient_facts(output),
+            [
+                "Rain expected in the afternoon",
+                "Winds from the north",
+                "-5°C overnight in the valleys",
+                "**Frost** is likely tonight",
+                "1.5 mm of rain fell",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_salient_facts_split_long_lines_and_keep_json() {
+        let long = format!("{}. Second sentence is here.", ["word"; 70].join(" "));
+        let facts = salient_facts(&long);
+        assert_eq!(facts.len(), 2);
+        assert_eq!(facts[1], "Second sentence is here.");
+
+        assert_eq!(salient_facts(" {\"a\": 1}\n"), [r#"{"a": 1}"#]);
+    }
+
+    #[tokio::test]
+    async fn test_recalls_the_most_similar_entries_first() {
+        let memory = VectorMemory::new(Arc::new(MockProvider::new()), "mock-embed").with_min_similarity(0.1);
+        let entries = ["The Eiffel Tower is in Paris", "Paris is the capital of France", "Tokyo has great sushi"];
+        memory
+            .save_all(entries.iter().map(|text| MemoryEntry::new(text.to_string(), None)).collect())
+            .await
+            .unwrap();
+        assert_eq!(memory.store().count().await.unwrap(), 3);
+
+        let found = memory.search("capital of France Paris", 5).await.unwrap();
+        let contents: Vec<&str> = found.iter().map(|entry| entry.content.as_str()).collect();
+        assert_eq!(contents, ["Paris is the capital of France", "The Eiffel Tower is in Paris"]);
+
+        memory.clear().await.unwrap();
+        assert!(memory.search("Paris", 5).await.unwrap().is_empty());
+    }
+}
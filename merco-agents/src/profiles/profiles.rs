@@ -0,0 +1,179 @@
+use crate::agent::agent::AgentLLMConfig;
+use crate::definition::definition::LlmDefinition;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// File looked up in the working directory by `Profiles::discover`
+pub const CONFIG_FILE: &str = "merco.toml";
+// Environment variable pointing at a profile file in another place
+pub const CONFIG_ENV: &str = "MERCO_CONFIG";
+
+// Named LLM profiles, so agents can say "fast" or "local" instead of spelling out
+// provider settings, and a whole crew can be moved to another provider by editing one
+// file:
+//
+//     default = "smart"
+//
+//     [profiles.fast]
+//     provider = "openai"
+//     base_url = "https://openrouter.ai/api/v1"
+//     api_key_env = "OPENROUTER_API_KEY"
+//     model = "openai/gpt-4o-mini"
+//     temperature = 0.2
+//
+//     [profiles.local]
+//     provider = "ollama"
+//     model = "llama3.2"
+//
+// Each profile has the fields of an inline `llm` definition.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, LlmDefinition>,
+}
+
+impl Profiles {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str::<Self>(toml)?.validated()
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        serde_yaml::from_str::<Self>(yaml)?.validated()
+    }
+
+    // Load a `.toml` file, or a `.yaml`/`.yml` file with the `yaml` feature
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let profiles = match extension {
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Self::from_yaml(&text),
+            "toml" => Self::from_toml(&text),
+            other => Err(anyhow!("Unsupported profile file type '{}'", other)),
+        };
+        profiles.with_context(|| format!("Invalid profile file {}", path.display()))
+    }
+
+    // Load the file named by `MERCO_CONFIG`, or `merco.toml` in the working directory
+    pub fn discover() -> Result<Self> {
+        let path = std::env::var_os(CONFIG_ENV).map(PathBuf::from).unwrap_or_else(|| PathBuf::from(CONFIG_FILE));
+        if !path.exists() {
+            return Err(anyhow!("No profile file at {} (set {} to use another file)", path.display(), CONFIG_ENV));
+        }
+        Self::load(path)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&LlmDefinition> {
+        self.profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow!("Unknown profile '{}' (defined: {})", name, known.join(", "))
+        })
+    }
+
+    pub fn default_profile(&self) -> Result<&LlmDefinition> {
+        let name = self.default.as_deref().ok_or_else(|| anyhow!("No default profile is set"))?;
+        self.get(name)
+    }
+
+    // Provider config of a profile, for agents created in code
+    pub fn agent_config(&self, name: &str) -> Result<AgentLLMConfig> {
+        self.get(name)?.agent_config()
+    }
+
+    fn validated(self) -> Result<Self> {
+        if let Some(name) = &self.default {
+            self.get(name).context("Invalid default profile")?;
+        }
+        for (name, profile) in &self.profiles {
+            profile
+                .provider
+                .parse::<merco_llmproxy::Provider>()
+                .map_err(|e| anyhow!("Profile '{}': {}", name, e))?;
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definition::definition::{AgentDefinition, from_json};
+    use merco_llmproxy::Provider;
+
+    const PROFILES: &str = r#"
+default = "local"
+
+[profiles.local]
+provider = "ollama"
+model = "llama3.2"
+
+[profiles.fast]
+provider = "ollama"
+base_url = "http://gpu-box:11434"
+model = "qwen2.5:0.5b"
+temperature = 0.2
+max_tokens = 512
+"#;
+
+    #[test]
+    fn test_loads_profiles_from_toml() {
+        let profiles = Profiles::from_toml(PROFILES).unwrap();
+        assert_eq!(profiles.default_profile().unwrap().model, "llama3.2");
+
+        let fast = profiles.get("fast").unwrap();
+        assert_eq!(fast.base_url.as_deref(), Some("http://gpu-box:11434"));
+        assert_eq!(fast.sampling.temperature, Some(0.2));
+        assert_eq!(fast.sampling.max_tokens, Some(512));
+
+        let config = profiles.agent_config("local").unwrap();
+        assert_eq!(config.base_config().provider, Provider::Ollama);
+        assert_eq!(config.model_name(), "llama3.2");
+
+        let error = profiles.get("smart").unwrap_err().to_string();
+        assert_eq!(error, "Unknown profile 'smart' (defined: fast, local)");
+    }
+
+    #[test]
+    fn test_rejects_invalid_profiles() {
+        let error = Profiles::from_toml(&PROFILES.replace(r#"default = "local""#, r#"default = "smart""#)).unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid default profile"), "{:#}", error);
+
+        let error = Profiles::from_toml(&PROFILES.replacen(r#"provider = "ollama""#, r#"provider = "acme""#, 1)).unwrap_err();
+        assert!(error.to_string().starts_with("Profile 'local':"), "{}", error);
+
+        let error = Profiles::from_toml("").unwrap().default_profile().unwrap_err();
+        assert_eq!(error.to_string(), "No default profile is set");
+    }
+
+    #[test]
+    fn test_load_picks_the_format_from_the_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("merco.toml");
+        std::fs::write(&path, PROFILES).unwrap();
+        assert_eq!(Profiles::load(&path).unwrap(), Profiles::from_toml(PROFILES).unwrap());
+
+        let json = dir.path().join("merco.json");
+        std::fs::write(&json, "{}").unwrap();
+        let error = Profiles::load(&json).unwrap_err();
+        assert!(format!("{:#}", error).contains("Unsupported profile file type 'json'"), "{:#}", error);
+
+        let error = Profiles::load(dir.path().join("missing.toml")).unwrap_err();
+        assert!(error.to_string().starts_with("Failed to read"), "{}", error);
+    }
+
+    #[test]
+    fn test_agents_resolve_profile_references() {
+        let profiles = Profiles::from_toml(PROFILES).unwrap();
+        let definition: AgentDefinition = from_json(r#"{"llm": "fast", "backstory": "You help."}"#).unwrap();
+
+        let agent = definition.build_with(Some(&profiles)).unwrap();
+        assert_eq!(agent.llm_config().model_name(), "qwen2.5:0.5b");
+        assert_eq!(agent.llm_config().defaults().temperature, Some(0.2));
+    }
+}
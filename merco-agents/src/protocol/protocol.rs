@@ -0,0 +1,325 @@
+use crate::agent::agent::Agent;
+use crate::approval::approval::{ApprovalDecision, ApprovalRequest, ChannelApproval, PendingApproval};
+use crate::crew::crew::CrewOutput;
+use crate::definition::definition::{AgentDefinition, CrewDefinition, from_json};
+use crate::session::session::new_run_id;
+use crate::task::task::Task;
+use anyhow::Result;
+use merco_llmproxy::{CancellationToken, ProgressSink, ToolProgress};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+// Bumped on incompatible changes to `Request` or `Event`. Adding variants or optional
+// fields is compatible; clients should ignore events they don't know.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// One line of NDJSON read from the client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    // Run a crew. `crew` is a crew definition document (see `definition`), with or
+    // without its versioned envelope
+    RunCrew { id: String, crew: Value },
+    // Run a single task with one agent; both are definition documents
+    RunTask { id: String, agent: Value, task: Value },
+    // Answer an `approval_requested` event
+    Approval { token: String, decision: ApprovalDecision },
+    Cancel { id: String },
+    // Cancel running work, wait for it to stop and exit. Closing stdin does the same.
+    Shutdown,
+}
+
+// One line of NDJSON written to the client. `id` is the id of the request that
+// started the run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    // First event of every session
+    Ready { protocol_version: u32, version: String },
+    Started { id: String },
+    ToolProgress { id: String, tool: String, fraction: Option<f32>, message: Option<String> },
+    ApprovalRequested { id: String, token: String, request: ApprovalRequest },
+    Completed { id: String, task_outputs: Vec<String>, final_output: String },
+    Failed { id: String, error: String },
+    Cancelled { id: String },
+    // A request could not be handled (malformed line, unknown id, ...)
+    Error { message: String },
+}
+
+// JSON Schemas of both message types, for generating client bindings
+#[cfg(feature = "schema")]
+pub fn protocol_schema() -> Value {
+    serde_json::json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "request": schemars::schema_for!(Request),
+        "event": schemars::schema_for!(Event),
+    })
+}
+
+type EventSender = mpsc::UnboundedSender<Event>;
+
+// State shared by the request loop and the runs it started
+#[derive(Clone)]
+struct Server {
+    events: EventSender,
+    runs: Arc<Mutex<HashMap<String, CancellationToken>>>,
+    approvals: Arc<Mutex<HashMap<String, (String, PendingApproval)>>>, // token -> (run id, approval)
+}
+
+impl Server {
+    fn emit(&self, event: Event) {
+        // Only fails once the writer is gone, and then nobody is listening anyway
+        let _ = self.events.send(event);
+    }
+
+    fn error(&self, message: impl Into<String>) {
+        self.emit(Event::Error { message: message.into() });
+    }
+
+    fn handle(&self, request: Request, runs: &mut JoinSet<()>) {
+        match request {
+            Request::RunCrew { id, crew } => self.start(id, runs, move |server, id, token| async move {
+                let definition: CrewDefinition = from_json(&crew.to_string()).map_err(|e| e.to_string())?;
+                let crew = definition.build().map_err(|e| e.to_string())?;
+                let (approval, pending) = ChannelApproval::new(16);
+                server.forward_approvals(id.clone(), pending);
+                crew.with_cancellation(token)
+                    .with_approval_transport(Arc::new(approval))
+                    .with_tool_progress(server.progress_sink(id))
+                    .run()
+                    .await
+            }),
+            Request::RunTask { id, agent, task } => self.start(id, runs, move |server, id, token| async move {
+                let agent: Agent = from_json::<AgentDefinition>(&agent.to_string())
+                    .and_then(|definition| definition.build())
+                    .map_err(|e| e.to_string())?;
+                let task: Task = from_json(&task.to_string()).map_err(|e| e.to_string())?;
+                let output = agent
+                    .with_cancellation(token)
+                    .with_tool_progress(server.progress_sink(id))
+                    .call(task)
+                    .await?
+                    .text;
+                Ok(CrewOutput::new(vec![output.clone()], output))
+            }),
+            Request::Approval { token, decision } => match self.approvals.lock().unwrap().remove(&token) {
+                Some((_, pending)) => {
+                    pending.respond(decision);
+                }
+                None => self.error(format!("No pending approval with token '{}'", token)),
+            },
+            Request::Cancel { id } => {
+                let Some(token) = self.runs.lock().unwrap().get(&id).cloned() else {
+                    return self.error(format!("No running request with id '{}'", id));
+                };
+                token.cancel();
+                // A run waiting for approval stops once its approvals are dropped
+                self.approvals.lock().unwrap().retain(|_, (run, _)| *run != id);
+            }
+            Request::Shutdown => {}
+        }
+    }
+
+    fn start<F, Fut>(&self, id: String, runs: &mut JoinSet<()>, run: F)
+    where
+        F: FnOnce(Server, String, CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<CrewOutput, String>> + Send + 'static,
+    {
+        let token = CancellationToken::new();
+        {
+            let mut active = self.runs.lock().unwrap();
+            if active.contains_key(&id) {
+                drop(active);
+                self.error(format!("A request with id '{}' is already running", id));
+                return;
+            }
+            active.insert(id.clone(), token.clone());
+        }
+
+        let server = self.clone();
+        runs.spawn(async move {
+            server.emit(Event::Started { id: id.clone() });
+            let result = run(server.clone(), id.clone(), token.clone()).await;
+            server.runs.lock().unwrap().remove(&id);
+            server.emit(match result {
+                Ok(output) => Event::Completed { id, task_outputs: output.task_outputs, final_output: output.final_output },
+                Err(_) if token.is_cancelled() => Event::Cancelled { id },
+                Err(error) => Event::Failed { id, error },
+            });
+        });
+    }
+
+    fn progress_sink(&self, id: String) -> ProgressSink {
+        let events = self.events.clone();
+        Arc::new(move |progress: &ToolProgress| {
+            let _ = events.send(Event::ToolProgress {
+                id: id.clone(),
+                tool: progress.tool.clone(),
+                fraction: progress.fraction,
+                message: progress.message.clone(),
+            });
+        })
+    }
+
+    // Publish each approval the run waits for, and keep it until the client answers
+    fn forward_approvals(&self, id: String, mut pending: mpsc::Receiver<PendingApproval>) {
+        let server = self.clone();
+        tokio::spawn(async move {
+            while let Some(approval) = pending.recv().await {
+                let token = new_run_id().replacen("run-", "approval-", 1);
+                let request = approval.request.clone();
+                server.approvals.lock().unwrap().insert(token.clone(), (id.clone(), approval));
+                server.emit(Event::ApprovalRequested { id: id.clone(), token, request });
+            }
+        });
+    }
+}
+
+// Drive crews over a line-delimited JSON protocol: one `Request` per input line, one
+// `Event` per output line. Runs execute concurrently; the session ends on `shutdown`
+// or when the input closes, after cancelling and awaiting all runs.
+pub async fn serve<R, W>(input: R, mut output: W) -> Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (events, mut receiver) = mpsc::unbounded_channel::<Event>();
+    let writer = tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            let mut line = serde_json::to_string(&event)?;
+            line.push('\n');
+            output.write_all(line.as_bytes()).await?;
+            output.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let server = Server {
+        events,
+        runs: Arc::new(Mutex::new(HashMap::new())),
+        approvals: Arc::new(Mutex::new(HashMap::new())),
+    };
+    server.emit(Event::Ready { protocol_version: PROTOCOL_VERSION, version: env!("CARGO_PKG_VERSION").to_string() });
+
+    let mut runs = JoinSet::new();
+    let mut lines = input.lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Shutdown) => break,
+            Ok(request) => server.handle(request, &mut runs),
+            Err(e) => server.error(format!("Invalid request: {}", e)),
+        }
+    }
+
+    server.runs.lock().unwrap().values().for_each(CancellationToken::cancel);
+    // Unanswered approvals are dropped, which fails the runs waiting on them
+    server.approvals.lock().unwrap().clear();
+    while runs.join_next().await.is_some() {}
+
+    drop(server);
+    writer.await?
+}
+
+// `serve` on the process's stdin and stdout
+pub async fn serve_stdio() -> Result<()> {
+    serve(tokio::io::BufReader::new(tokio::io::stdin()), tokio::io::stdout()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::AsyncReadExt;
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug>(value: T, wire: Value) {
+        assert_eq!(serde_json::to_value(&value).unwrap(), wire);
+        assert_eq!(serde_json::from_value::<T>(wire).unwrap(), value);
+    }
+
+    #[test]
+    fn test_requests_round_trip() {
+        round_trip(
+            Request::RunCrew { id: "r1".to_string(), crew: json!({"agents": []}) },
+            json!({"type": "run_crew", "id": "r1", "crew": {"agents": []}}),
+        );
+        round_trip(
+            Request::RunTask { id: "r2".to_string(), agent: json!({"name": "a"}), task: json!({"description": "t"}) },
+            json!({"type": "run_task", "id": "r2", "agent": {"name": "a"}, "task": {"description": "t"}}),
+        );
+        round_trip(
+            Request::Approval {
+                token: "approval-1".to_string(),
+                decision: ApprovalDecision::Revise { feedback: "Shorter".to_string() },
+            },
+            json!({"type": "approval", "token": "approval-1", "decision": {"decision": "revise", "feedback": "Shorter"}}),
+        );
+        round_trip(Request::Cancel { id: "r1".to_string() }, json!({"type": "cancel", "id": "r1"}));
+        round_trip(Request::Shutdown, json!({"type": "shutdown"}));
+    }
+
+    #[test]
+    fn test_events_round_trip() {
+        round_trip(
+            Event::Ready { protocol_version: 1, version: "0.1.0".to_string() },
+            json!({"type": "ready", "protocol_version": 1, "version": "0.1.0"}),
+        );
+        round_trip(Event::Started { id: "r1".to_string() }, json!({"type": "started", "id": "r1"}));
+        round_trip(
+            Event::ToolProgress { id: "r1".to_string(), tool: "fetch".to_string(), fraction: Some(0.5), message: None },
+            json!({"type": "tool_progress", "id": "r1", "tool": "fetch", "fraction": 0.5, "message": null}),
+        );
+        round_trip(
+            Event::ApprovalRequested {
+                id: "r1".to_string(),
+                token: "approval-1".to_string(),
+                request: ApprovalRequest {
+                    subject: "Output of task 1".to_string(),
+                    task: "Write a tweet".to_string(),
+                    output: "Hello".to_string(),
+                },
+            },
+            json!({
+                "type": "approval_requested",
+                "id": "r1",
+                "token": "approval-1",
+                "request": {"subject": "Output of task 1", "task": "Write a tweet", "output": "Hello"},
+            }),
+        );
+        round_trip(
+            Event::Completed { id: "r1".to_string(), task_outputs: vec!["a".to_string()], final_output: "a".to_string() },
+            json!({"type": "completed", "id": "r1", "task_outputs": ["a"], "final_output": "a"}),
+        );
+        round_trip(
+            Event::Failed { id: "r1".to_string(), error: "boom".to_string() },
+            json!({"type": "failed", "id": "r1", "error": "boom"}),
+        );
+        round_trip(Event::Cancelled { id: "r1".to_string() }, json!({"type": "cancelled", "id": "r1"}));
+        round_trip(Event::Error { message: "bad".to_string() }, json!({"type": "error", "message": "bad"}));
+    }
+
+    #[tokio::test]
+    async fn test_serve_reports_bad_requests() {
+        let input = "\nnot json\n{\"type\": \"cancel\", \"id\": \"r9\"}\n{\"type\": \"approval\", \"token\": \"t\", \"decision\": {\"decision\": \"approve\"}}\n{\"type\": \"shutdown\"}\n";
+        let (writer, mut reader) = tokio::io::duplex(64 * 1024);
+        serve(input.as_bytes(), writer).await.unwrap();
+
+        let mut output = String::new();
+        reader.read_to_string(&mut output).await.unwrap();
+        let events: Vec<Event> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(&events[0], Event::Ready { protocol_version: PROTOCOL_VERSION, .. }));
+        assert!(matches!(&events[1], Event::Error { message } if message.starts_with("Invalid request")));
+        assert_eq!(events[2], Event::Error { message: "No running request with id 'r9'".to_string() });
+        assert_eq!(events[3], Event::Error { message: "No pending approval with token 't'".to_string() });
+    }
+}
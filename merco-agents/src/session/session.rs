@@ -0,0 +1,207 @@
+use merco_llmproxy::{ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, ProviderError};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static RUN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Unique id for a run, used to group branches, workspaces and traces
+pub fn new_run_id() -> String {
+    format!(
+        "run-{}-{}-{}",
+        chrono::Utc::now().format("%Y%m%d%H%M%S%3f"),
+        std::process::id(),
+        RUN_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+pub type BranchId = usize;
+
+// One line of exploration. Holds the full transcript, including the prefix inherited
+// from its parent at the fork point.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Branch {
+    pub id: BranchId,
+    pub parent: Option<BranchId>,
+    pub fork_index: usize, // Number of parent messages inherited when forked
+    pub label: Option<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+// A tree of conversation branches under a single run id, for exploring alternative
+// continuations of the same transcript
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AgentSession {
+    pub run_id: String,
+    branches: Vec<Branch>,
+}
+
+impl AgentSession {
+    // Create a session whose root branch (id 0) starts with `messages`
+    pub fn new(messages: Vec<ChatMessage>) -> Self {
+        Self {
+            run_id: new_run_id(),
+            branches: vec![Branch {
+                id: 0,
+                parent: None,
+                fork_index: 0,
+                label: Some("root".to_string()),
+                messages,
+            }],
+        }
+    }
+
+    pub fn with_run_id(mut self, run_id: String) -> Self {
+        self.run_id = run_id;
+        self
+    }
+
+    pub fn root(&self) -> BranchId {
+        0
+    }
+
+    pub fn branches(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    pub fn branch(&self, id: BranchId) -> Result<&Branch, String> {
+        self.branches.get(id).ok_or_else(|| format!("Unknown branch {}", id))
+    }
+
+    pub fn transcript(&self, id: BranchId) -> Result<&[ChatMessage], String> {
+        Ok(&self.branch(id)?.messages)
+    }
+
+    // Branches forked directly from `id`
+    pub fn children(&self, id: BranchId) -> Vec<BranchId> {
+        self.branches.iter().filter(|b| b.parent == Some(id)).map(|b| b.id).collect()
+    }
+
+    pub fn push(&mut self, id: BranchId, message: ChatMessage) -> Result<(), String> {
+        self.branches
+            .get_mut(id)
+            .ok_or_else(|| format!("Unknown branch {}", id))?
+            .messages
+            .push(message);
+        Ok(())
+    }
+
+    // Fork `id` keeping its first `at` messages; later messages are left on the parent
+    pub fn fork(&mut self, id: BranchId, at: usize, label: Option<String>) -> Result<BranchId, String> {
+        let parent = self.branch(id)?;
+        if at > parent.messages.len() {
+            return Err(format!(
+                "Cannot fork branch {} at message {}: it only has {} messages",
+                id,
+                at,
+                parent.messages.len()
+            ));
+        }
+
+        let new_id = self.branches.len();
+        let messages = parent.messages[..at].to_vec();
+        self.branches.push(Branch {
+            id: new_id,
+            parent: Some(id),
+            fork_index: at,
+            label,
+            messages,
+        });
+        Ok(new_id)
+    }
+
+    // Send the branch's transcript through `complete` (e.g. a provider's `completion`)
+    // using `template` for model and sampling parameters, append the assistant reply to
+    // the branch and return its text
+    pub async fn continue_branch(
+        &mut self,
+        id: BranchId,
+        complete: impl AsyncFnOnce(CompletionRequest) -> Result<CompletionResponse, ProviderError>,
+        template: CompletionRequest,
+    ) -> Result<String, ProviderError> {
+        let mut request = template;
+        request.messages = self
+            .transcript(id)
+            .map_err(ProviderError::ConfigError)?
+            .to_vec();
+
+        let response = complete(request).await?;
+        let reply = match response.kind {
+            CompletionKind::Message { content } => {
+                self.push(id, ChatMessage::assistant(Some(content.clone()), None))
+                    .map_err(ProviderError::ConfigError)?;
+                content
+            }
+            CompletionKind::ToolCall { tool_calls } => {
+                self.push(id, ChatMessage::assistant(None, Some(tool_calls)))
+                    .map_err(ProviderError::ConfigError)?;
+                String::new()
+            }
+        };
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merco_llmproxy::{LlmProvider, MockProvider};
+
+    fn contents(session: &AgentSession, id: BranchId) -> Vec<String> {
+        session.transcript(id).unwrap().iter().map(|m| m.content.clone().unwrap_or_default()).collect()
+    }
+
+    #[test]
+    fn test_new_run_ids_are_unique() {
+        assert_ne!(new_run_id(), new_run_id());
+    }
+
+    #[test]
+    fn test_fork_keeps_the_prefix_and_leaves_the_parent() {
+        let mut session = AgentSession::new(vec![ChatMessage::system("Be brief"), ChatMessage::user("Hi")]);
+        session.push(session.root(), ChatMessage::assistant(Some("Hello".to_string()), None)).unwrap();
+
+        let fork = session.fork(session.root(), 2, Some("retry".to_string())).unwrap();
+        session.push(fork, ChatMessage::assistant(Some("Hey".to_string()), None)).unwrap();
+
+        assert_eq!(contents(&session, 0), ["Be brief", "Hi", "Hello"]);
+        assert_eq!(contents(&session, fork), ["Be brief", "Hi", "Hey"]);
+        let branch = session.branch(fork).unwrap();
+        assert_eq!((branch.parent, branch.fork_index), (Some(0), 2));
+        assert_eq!(session.children(0), [fork]);
+        assert!(session.children(fork).is_empty());
+    }
+
+    #[test]
+    fn test_fork_rejects_bad_branches_and_positions() {
+        let mut session = AgentSession::new(vec![ChatMessage::user("Hi")]);
+
+        let error = session.fork(0, 2, None).unwrap_err();
+        assert!(error.contains("only has 1 messages"), "{}", error);
+        assert_eq!(session.fork(3, 0, None).unwrap_err(), "Unknown branch 3");
+        assert!(session.push(3, ChatMessage::user("Hi")).is_err());
+        assert_eq!(session.branches().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_continue_branch_sends_its_transcript() {
+        let provider = MockProvider::new().with_message("Bonjour").with_message("Hola");
+        let mut session = AgentSession::new(vec![ChatMessage::user("Greet me")]).with_run_id("run-1".to_string());
+        let fork = session.fork(0, 1, None).unwrap();
+        let template = CompletionRequest::new(Vec::new(), "mock".to_string(), None, None, None);
+
+        let first = session
+            .continue_branch(0, async |request| provider.completion(request).await, template.clone())
+            .await
+            .unwrap();
+        let second = session
+            .continue_branch(fork, async |request| provider.completion(request).await, template)
+            .await
+            .unwrap();
+
+        assert_eq!((first.as_str(), second.as_str()), ("Bonjour", "Hola"));
+        assert_eq!(contents(&session, 0), ["Greet me", "Bonjour"]);
+        assert_eq!(contents(&session, fork), ["Greet me", "Hola"]);
+        // The fork was sent without the reply added to the root
+        assert_eq!(provider.requests()[1].messages.len(), 1);
+        assert_eq!(session.run_id, "run-1");
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{Result, anyhow};
+use merco_llmproxy::ToolContext;
+use merco_llmproxy::paths::resolve_in;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+// Placeholder in check arguments replaced by the path of the file under test
+pub const FILE_PLACEHOLDER: &str = "{file}";
+
+// A user-configured command that checks generated code (compiler, linter, ...).
+// A non-zero exit status fails validation and its output is fed back to the agent.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CodeCheck {
+    pub program: String,
+    pub args: Vec<String>, // `{file}` is replaced by the file path; appended if absent
+    pub file_name: String, // Name the code is written to, e.g. `main.rs`
+    pub timeout_secs: u64,
+}
+
+impl CodeCheck {
+    pub fn new(program: impl Into<String>, args: Vec<String>, file_name: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+            file_name: file_name.into(),
+            timeout_secs: 60,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+
+    // `rustc --edition 2021 --emit=metadata` on a library crate
+    pub fn rust() -> Self {
+        Self::new(
+            "rustc",
+            ["--edition", "2021", "--crate-type", "lib", "--emit=metadata", "--out-dir", ".", FILE_PLACEHOLDER]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            "main.rs",
+        )
+    }
+
+    // `python -m py_compile`
+    pub fn python() -> Self {
+        Self::new("python3", vec!["-m".to_string(), "py_compile".to_string()], "main.py")
+    }
+
+    // Write `code` into a scratch directory (inside the run workspace when available)
+    // and run the check command against it
+    pub fn run(&self, code: &str) -> Result<()> {
+        let dir = scratch_dir()?;
+        let result = self.run_in(&dir, code);
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    fn run_in(&self, dir: &std::path::Path, code: &str) -> Result<()> {
+        // Definitions may come from untrusted sources, so keep the file inside the scratch dir
+        let file = resolve_in(dir, &self.file_name).map_err(|e| anyhow!("Invalid check file name: {}", e))?;
+        std::fs::write(&file, code).map_err(|e| anyhow!("Failed to write code to {}: {}", file.display(), e))?;
+        let file_arg = file.to_string_lossy().to_string();
+
+        let mut args: Vec<String> = self.args.iter().map(|a| a.replace(FILE_PLACEHOLDER, &file_arg)).collect();
+        if !self.args.iter().any(|a| a.contains(FILE_PLACEHOLDER)) {
+            args.push(file_arg);
+        }
+
+        let mut child = Command::new(&self.program)
+            .args(&args)
+            .current_dir(dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run code check '{}': {}", self.program, e))?;
+
+        // Drain the pipes while waiting so verbose diagnostics can't block the child
+        let stderr = child.stderr.take().map(read_in_background);
+        let stdout = child.stdout.take().map(read_in_background);
+
+        let deadline = Instant::now() + Duration::from_secs(self.timeout_secs);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow!("Code check '{}' timed out after {}s", self.program, self.timeout_secs));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        if status.success() {
+            return Ok(());
+        }
+
+        let diagnostics: String = [stderr, stdout]
+            .into_iter()
+            .flatten()
+            .map(|reader| reader.join().unwrap_or_default())
+            .collect();
+        Err(anyhow!(
+            "Code check '{}' failed ({}):\n{}",
+            self.program,
+            status,
+            diagnostics.trim()
+        ))
+    }
+}
+
+// Pull the code out of a fenced block if the model wrapped it in one
+pub fn extract_code(output: &str) -> String {
+    let trimmed = output.trim();
+    let Some(start) = trimmed.find("```") else {
+        return trimmed.to_string();
+    };
+    let after_fence = &trimmed[start + 3..];
+    // Skip the language tag line
+    let body = after_fence.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+    match body.find("```") {
+        Some(end) => body[..end].trim_end().to_string(),
+        None => body.trim_end().to_string(),
+    }
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut output = String::new();
+        let _ = pipe.read_to_string(&mut output);
+        output
+    })
+}
+
+fn scratch_dir() -> Result<PathBuf> {
+    let parent = ToolContext::current().workspace.unwrap_or_else(std::env::temp_dir);
+    let dir = parent.join(format!(
+        "code-check-{}-{}",
+        std::process::id(),
+        chrono::Utc::now().format("%Y%m%d%H%M%S%f")
+    ));
+    std::fs::create_dir_all(&dir).map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    // `sh -n` only parses the script, so it works as a syntax checker
+    fn shell_check() -> CodeCheck {
+        CodeCheck::new("sh", vec!["-n".to_string()], "main.sh")
+    }
+
+    #[test]
+    fn test_accepts_code_that_passes() {
+        assert!(shell_check().run("echo hello").is_ok());
+    }
+
+    #[test]
+    fn test_reports_diagnostics_of_failing_code() {
+        let error = shell_check().run("if then fi").unwrap_err().to_string();
+        assert!(error.starts_with("Code check 'sh' failed"), "{}", error);
+        assert!(error.contains("main.sh"), "{}", error);
+    }
+
+    #[test]
+    fn test_times_out() {
+        let check = CodeCheck::new("sh", vec!["-c".to_string(), "sleep 5".to_string(), FILE_PLACEHOLDER.to_string()], "main.sh")
+            .with_timeout(1);
+        let started = Instant::now();
+        let error = check.run("echo hello").unwrap_err().to_string();
+        assert_eq!(error, "Code check 'sh' timed out after 1s");
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_extract_code() {
+        assert_eq!(extract_code("```rust\nfn main() {}\n```\nDone."), "fn main() {}");
+        assert_eq!(extract_code("  fn main() {}  "), "fn main() {}");
+    }
+}
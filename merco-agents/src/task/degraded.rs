@@ -0,0 +1,103 @@
+use crate::task::task::{OutputFormat, Task};
+use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
+
+// Produces an answer from the task and the last provider error
+pub type DegradedHandler = Arc<dyn Fn(&Task, &str) -> Result<String, String> + Send + Sync>;
+
+// Answer used when every LLM attempt for a task failed (provider down, network gone), so
+// the task resolves gracefully instead of failing the crew. Not used for output that
+// merely failed validation.
+#[derive(Clone)]
+pub struct DegradedFallback {
+    handler: DegradedHandler,
+}
+
+impl DegradedFallback {
+    // Rule-based or otherwise computed answer
+    pub fn new(handler: impl Fn(&Task, &str) -> Result<String, String> + Send + Sync + 'static) -> Self {
+        Self {
+            handler: Arc::new(handler),
+        }
+    }
+
+    // A fixed answer, e.g. the last known good output
+    pub fn cached(answer: impl Into<String>) -> Self {
+        let answer = answer.into();
+        Self::new(move |_, _| Ok(answer.clone()))
+    }
+
+    // An explicit "unavailable" response: a `{"status": "unavailable", ...}` object for
+    // JSON tasks (the same mapping for YAML tasks), a short notice otherwise
+    pub fn unavailable() -> Self {
+        Self::new(|task, error| {
+            Ok(match task.output_format {
+                OutputFormat::Json { .. } | OutputFormat::Schema { .. } => json!({ "status": "unavailable", "reason": error }).to_string(),
+                OutputFormat::Yaml { .. } => format!("status: unavailable\nreason: {}", json!(error)),
+                _ => "The service is temporarily unavailable. Please try again later.".to_string(),
+            })
+        })
+    }
+
+    pub fn resolve(&self, task: &Task, error: &str) -> Result<String, String> {
+        (self.handler)(task, error)
+    }
+}
+
+impl fmt::Debug for DegradedFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DegradedFallback(<handler>)")
+    }
+}
+
+// Handlers can't be compared, so two fallbacks are equal only if they share one
+impl PartialEq for DegradedFallback {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.handler, &other.handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::task::{JsonField, JsonFieldType};
+    use serde_json::Value;
+
+    fn fields() -> Vec<JsonField> {
+        vec![JsonField::new("price", JsonFieldType::Number)]
+    }
+
+    #[test]
+    fn test_unavailable_matches_the_output_format() {
+        let fallback = DegradedFallback::unavailable();
+        let error = "API response error: 503: \"overloaded\"";
+
+        let json_task = Task::new_with_json_output("Quote a price".to_string(), None, fields(), Vec::new(), false);
+        let answer: Value = serde_json::from_str(&fallback.resolve(&json_task, error).unwrap()).unwrap();
+        assert_eq!(answer, json!({"status": "unavailable", "reason": error}));
+
+        let yaml_task = Task::new_with_yaml_output("Quote a price".to_string(), None, fields(), Vec::new(), false);
+        let answer = fallback.resolve(&yaml_task, error).unwrap();
+        assert_eq!(answer, "status: unavailable\nreason: \"API response error: 503: \\\"overloaded\\\"\"");
+
+        let text_task = Task::new("Quote a price".to_string(), None);
+        assert!(fallback.resolve(&text_task, error).unwrap().contains("temporarily unavailable"));
+    }
+
+    #[test]
+    fn test_cached_and_custom_fallbacks() {
+        let task = Task::new("Summarize the news".to_string(), None);
+        assert_eq!(DegradedFallback::cached("Yesterday's summary").resolve(&task, "timeout").unwrap(), "Yesterday's summary");
+
+        let fallback = DegradedFallback::new(|task, error| match error.contains("401") {
+            true => Err("Check the API key".to_string()),
+            false => Ok(format!("Could not finish '{}'", task.description)),
+        });
+        assert_eq!(fallback.resolve(&task, "timeout").unwrap(), "Could not finish 'Summarize the news'");
+        assert_eq!(fallback.resolve(&task, "API response error: 401").unwrap_err(), "Check the API key");
+
+        assert_eq!(fallback, fallback.clone());
+        assert_ne!(fallback, DegradedFallback::cached("x"));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::task::code_check::extract_code;
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+
+// Path used in diff headers for created or deleted files
+const NULL_PATH: &str = "/dev/null";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub old_start: usize, // 1-based line number hint from the `@@` header
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Add(text) => Some(text.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    pub old_path: String,
+    pub new_path: String,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    pub fn is_new_file(&self) -> bool {
+        self.old_path == NULL_PATH
+    }
+
+    pub fn is_deleted_file(&self) -> bool {
+        self.new_path == NULL_PATH
+    }
+
+    // The path the patch applies to
+    pub fn path(&self) -> &str {
+        if self.is_new_file() { &self.new_path } else { &self.old_path }
+    }
+}
+
+// Parse a unified diff (optionally wrapped in a code fence). A `---`/`+++` pair only
+// starts a new file once the line counts of the current hunk's `@@` header are used up,
+// so removed `-- ...` and added `++ ...` lines aren't taken for file headers. Models often
+// get the counts wrong, so lines past them still extend the hunk, and a header pair
+// followed by `@@` ends it early.
+pub fn parse_unified_diff(diff: &str) -> Result<Vec<FilePatch>> {
+    let diff = extract_code(diff);
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut patches: Vec<FilePatch> = Vec::new();
+    let mut pending_old: Option<String> = None;
+    let (mut old_left, mut new_left) = (0, 0); // Lines the current hunk still expects
+
+    for (i, &line) in lines.iter().enumerate() {
+        let next_starts_with = |offset: usize, prefix: &str| lines.get(i + offset).is_some_and(|l| l.starts_with(prefix));
+        let in_hunk = old_left > 0 || new_left > 0;
+        let file_header = next_starts_with(1, "+++ ") && (!in_hunk || next_starts_with(2, "@@"));
+
+        if let Some(path) = line.strip_prefix("--- ")
+            && file_header
+        {
+            pending_old = Some(clean_path(path));
+            (old_left, new_left) = (0, 0);
+        } else if let Some(path) = line.strip_prefix("+++ ")
+            && let Some(old_path) = pending_old.take()
+        {
+            patches.push(FilePatch {
+                old_path,
+                new_path: clean_path(path),
+                hunks: Vec::new(),
+            });
+        } else if line.starts_with("@@") {
+            let patch = patches
+                .last_mut()
+                .ok_or_else(|| anyhow!("Hunk header before any file header: {}", line))?;
+            let range = parse_hunk_range(line)?;
+            (old_left, new_left) = (range.old_count, range.new_count);
+            patch.hunks.push(Hunk {
+                old_start: range.old_start,
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = patches.last_mut().and_then(|p| p.hunks.last_mut()) {
+            let (line, old, new) = match line.chars().next() {
+                Some('+') => (HunkLine::Add(line[1..].to_string()), 0, 1),
+                Some('-') => (HunkLine::Remove(line[1..].to_string()), 1, 0),
+                Some(' ') => (HunkLine::Context(line[1..].to_string()), 1, 1),
+                None => (HunkLine::Context(String::new()), 1, 1), // Stripped blank context
+                Some('\\') => continue, // "\ No newline at end of file"
+                Some(_) => continue,    // `diff --git`, `index` and other metadata lines
+            };
+            hunk.lines.push(line);
+            old_left = old_left.saturating_sub(old);
+            new_left = new_left.saturating_sub(new);
+        } else if line.starts_with("+++ ") {
+            return Err(anyhow!("'+++' header without a preceding '---' header"));
+        }
+    }
+
+    if patches.is_empty() {
+        return Err(anyhow!("No file patches found in diff"));
+    }
+    if let Some(patch) = patches.iter().find(|p| p.hunks.is_empty() && !p.is_deleted_file()) {
+        return Err(anyhow!("Patch for '{}' has no hunks", patch.path()));
+    }
+    Ok(patches)
+}
+
+// Apply a single file patch to `original`, locating each hunk by its context
+pub fn apply_file_patch(original: &str, patch: &FilePatch) -> Result<String> {
+    let had_trailing_newline = original.ends_with('\n') || original.is_empty();
+    let mut lines: Vec<String> = original.lines().map(String::from).collect();
+    let mut cursor = 0; // Hunks must apply in order without overlapping
+    let mut offset: isize = 0; // Shift caused by earlier hunks
+
+    for (i, hunk) in patch.hunks.iter().enumerate() {
+        let old = hunk.old_lines();
+        let new = hunk.new_lines();
+        let hint = (hunk.old_start.saturating_sub(1) as isize + offset).max(cursor as isize) as usize;
+
+        let position = find_block(&lines, &old, cursor, hint).ok_or_else(|| {
+            anyhow!(
+                "Hunk {} for '{}' does not apply: expected lines not found near line {}",
+                i + 1,
+                patch.path(),
+                hunk.old_start
+            )
+        })?;
+
+        lines.splice(position..position + old.len(), new.iter().map(|l| l.to_string()));
+        cursor = position + new.len();
+        offset += new.len() as isize - old.len() as isize;
+    }
+
+    let mut result = lines.join("\n");
+    if had_trailing_newline && !result.is_empty() {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+// Apply a multi-file diff to `files` (path -> content), returning the updated set
+pub fn apply_unified_diff(diff: &str, files: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>> {
+    let mut result = files.clone();
+    for patch in parse_unified_diff(diff)? {
+        if patch.is_new_file() {
+            if result.contains_key(&patch.new_path) {
+                return Err(anyhow!("Diff creates '{}', which already exists", patch.new_path));
+            }
+            result.insert(patch.new_path.clone(), apply_file_patch("", &patch)?);
+            continue;
+        }
+
+        let original = result
+            .get(&patch.old_path)
+            .ok_or_else(|| anyhow!("Diff modifies unknown file '{}'", patch.old_path))?;
+        if patch.is_deleted_file() {
+            result.remove(&patch.old_path);
+            continue;
+        }
+
+        let updated = apply_file_patch(original, &patch)?;
+        if patch.new_path != patch.old_path {
+            result.remove(&patch.old_path);
+        }
+        result.insert(patch.new_path.clone(), updated);
+    }
+    Ok(result)
+}
+
+// Find `block` at or after `min`, preferring the position closest to `hint`
+fn find_block(lines: &[String], block: &[&str], min: usize, hint: usize) -> Option<usize> {
+    if block.is_empty() {
+        return Some(hint.min(lines.len()));
+    }
+    let matches_at = |pos: usize| {
+        pos + block.len() <= lines.len()
+            && block.iter().zip(&lines[pos..]).all(|(expected, actual)| expected.trim_end() == actual.trim_end())
+    };
+
+    let last = lines.len().checked_sub(block.len())?;
+    let hint = hint.clamp(min, last.max(min));
+    (0..=last.saturating_sub(min)).find_map(|distance| {
+        [hint.checked_add(distance), hint.checked_sub(distance)]
+            .into_iter()
+            .flatten()
+            .find(|&pos| pos >= min && pos <= last && matches_at(pos))
+    })
+}
+
+fn clean_path(raw: &str) -> String {
+    // Drop timestamps after a tab and the conventional a/ b/ prefixes
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == NULL_PATH {
+        return path.to_string();
+    }
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+// Line numbers and counts from a hunk header
+struct HunkRange {
+    old_start: usize,
+    old_count: usize,
+    new_count: usize,
+}
+
+fn parse_hunk_range(header: &str) -> Result<HunkRange> {
+    // @@ -12,5 +12,7 @@ optional section heading; a missing count means 1
+    let malformed = || anyhow!("Malformed hunk header: {}", header);
+    let range = |prefix: char| -> Option<(usize, usize)> {
+        let range = header.split_whitespace().find_map(|part| part.strip_prefix(prefix))?;
+        let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+        Some((start.parse().ok()?, count.parse().ok()?))
+    };
+    let (old_start, old_count) = range('-').ok_or_else(malformed)?;
+    let (_, new_count) = range('+').ok_or_else(malformed)?;
+    Ok(HunkRange { old_start, old_count, new_count })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries.iter().map(|(path, content)| (path.to_string(), content.to_string())).collect()
+    }
+
+    #[test]
+    fn test_applies_multi_file_multi_hunk_diffs() {
+        let diff = "\
+```diff
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn one() {}
+-fn two() {}
++fn dos() {}
+ fn three() {}
+@@ -6,2 +6,3 @@
+ fn six() {}
+ fn seven() {}
++fn eight() {}
+--- /dev/null
++++ b/README.md
+@@ -0,0 +1,1 @@
++# Demo
+```";
+        let original = "fn one() {}\nfn two() {}\nfn three() {}\nfn four() {}\nfn five() {}\nfn six() {}\nfn seven() {}\n";
+
+        let patches = parse_unified_diff(diff).unwrap();
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].hunks.len(), 2);
+        assert_eq!(patches[0].hunks[1].old_start, 6);
+        assert!(patches[1].is_new_file());
+
+        let result = apply_unified_diff(diff, &files(&[("src/lib.rs", original)])).unwrap();
+        assert_eq!(
+            result["src/lib.rs"],
+            "fn one() {}\nfn dos() {}\nfn three() {}\nfn four() {}\nfn five() {}\nfn six() {}\nfn seven() {}\nfn eight() {}\n"
+        );
+        assert_eq!(result["README.md"], "# Demo\n");
+    }
+
+    #[test]
+    fn test_comment_lines_are_not_file_headers() {
+        // Removing an SQL comment gives `--- `, adding a line starting `++` gives `+++ `
+        let diff = "\
+--- a/schema.sql
++++ b/schema.sql
+@@ -1,3 +1,3 @@
+ CREATE TABLE users (id INT);
+--- old note
++++ counter
+ CREATE TABLE posts (id INT);
+";
+        let patches = parse_unified_diff(diff).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(
+            patches[0].hunks[0].lines,
+            vec![
+                HunkLine::Context("CREATE TABLE users (id INT);".to_string()),
+                HunkLine::Remove("-- old note".to_string()),
+                HunkLine::Add("++ counter".to_string()),
+                HunkLine::Context("CREATE TABLE posts (id INT);".to_string()),
+            ]
+        );
+
+        let original = "CREATE TABLE users (id INT);\n-- old note\nCREATE TABLE posts (id INT);\n";
+        let result = apply_file_patch(original, &patches[0]).unwrap();
+        assert_eq!(result, "CREATE TABLE users (id INT);\n++ counter\nCREATE TABLE posts (id INT);\n");
+    }
+
+    #[test]
+    fn test_tolerates_wrong_hunk_counts() {
+        // Overstated counts: the next file's headers and `@@` still end the hunk
+        let diff = "\
+--- a/a.txt
++++ b/a.txt
+@@ -1,9 +1,9 @@
+-a
++A
+--- a/b.txt
++++ b/b.txt
+@@ -1 +1 @@
+-b
++B
+";
+        let result = apply_unified_diff(diff, &files(&[("a.txt", "a\n"), ("b.txt", "b\n")])).unwrap();
+        assert_eq!(result, files(&[("a.txt", "A\n"), ("b.txt", "B\n")]));
+
+        // Understated counts: lines past them still belong to the hunk
+        let diff = "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-a\n+A\n-x\n+X\n";
+        let result = apply_unified_diff(diff, &files(&[("a.txt", "a\nx\n")])).unwrap();
+        assert_eq!(result["a.txt"], "A\nX\n");
+    }
+
+    #[test]
+    fn test_rejects_malformed_diffs() {
+        assert!(parse_unified_diff("just some text").is_err());
+        assert!(parse_unified_diff("@@ -1 +1 @@\n-a\n+b\n").is_err());
+        assert!(parse_unified_diff("+++ b/a.txt\n@@ -1 +1 @@\n").is_err());
+        assert!(parse_unified_diff("--- a/a.txt\n+++ b/a.txt\n@@ -x +1 @@\n").is_err());
+        assert!(parse_unified_diff("--- a/a.txt\n+++ b/a.txt\n").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{Result, anyhow};
+use merco_llmproxy::{ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, ProviderError};
+use serde_json::Value;
+
+// What to do when the verifier disputes claims in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DiscrepancyAction {
+    Warn,   // Report the discrepancies and accept the output
+    Revise, // Send the discrepancies back to the agent for a revision round
+}
+
+// Per-task verification of key claims by a second provider/model, the agent's verifier
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FactCheck {
+    pub action: DiscrepancyAction,
+    pub max_revisions: usize,  // Revision rounds before falling back to a warning
+    pub model: Option<String>, // Overrides the verifier's model for this task
+}
+
+impl FactCheck {
+    pub fn new(action: DiscrepancyAction) -> Self {
+        Self {
+            action,
+            max_revisions: 1,
+            model: None,
+        }
+    }
+
+    pub fn with_max_revisions(mut self, max_revisions: usize) -> Self {
+        self.max_revisions = max_revisions;
+        self
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+}
+
+// A claim the verifier believes is wrong or unsupported
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discrepancy {
+    pub claim: String,
+    pub issue: String,
+}
+
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\": {}", self.claim, self.issue)
+    }
+}
+
+// Check the key factual claims of `output` produced for `task_description`, sending the
+// request through `complete` (e.g. a provider's `completion`)
+pub async fn verify_claims(
+    complete: impl AsyncFnOnce(CompletionRequest) -> Result<CompletionResponse, ProviderError>,
+    model: &str,
+    task_description: &str,
+    output: &str,
+) -> Result<Vec<Discrepancy>> {
+    let request = CompletionRequest {
+        model: model.to_string(),
+        messages: vec![
+            ChatMessage::system(
+                "You are a meticulous fact-checker. Identify the key factual claims in the answer and check each one. \
+                 Reply with JSON only: {\"discrepancies\": [{\"claim\": \"...\", \"issue\": \"...\"}]}. \
+                 List only claims that are wrong or unsupported; use an empty list if everything checks out."
+                    .to_string(),
+            ),
+            ChatMessage::user(format!("TASK: {}\n\nANSWER:\n{}", task_description, output)),
+        ],
+        temperature: Some(0.0),
+        ..Default::default()
+    };
+
+    let reply = match complete(request).await.map_err(|e| anyhow!("Fact check failed: {}", e))?.kind {
+        CompletionKind::Message { content } => content,
+        CompletionKind::ToolCall { .. } => return Err(anyhow!("Fact checker replied with a tool call")),
+    };
+    parse_discrepancies(&reply)
+}
+
+fn parse_discrepancies(reply: &str) -> Result<Vec<Discrepancy>> {
+    // Tolerate prose or code fences around the JSON object
+    let json = match (reply.find('{'), reply.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err(anyhow!("Fact checker reply is not JSON: {}", reply)),
+    };
+    let value: Value = serde_json::from_str(json).map_err(|e| anyhow!("Fact checker reply is not JSON: {}", e))?;
+    let items = value
+        .get("discrepancies")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("Fact checker reply has no 'discrepancies' list"))?;
+
+    Ok(items
+        .iter()
+        .map(|item| Discrepancy {
+            claim: item.get("claim").and_then(Value::as_str).unwrap_or_default().to_string(),
+            issue: item.get("issue").and_then(Value::as_str).unwrap_or_default().to_string(),
+        })
+        .filter(|d| !d.claim.is_empty() || !d.issue.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_discrepancies() {
+        let reply = "Here is my review:\n```json\n{\"discrepancies\": [{\"claim\": \"Water boils at 50C\", \"issue\": \"It boils at 100C\"}, {}]}\n```";
+        let discrepancies = parse_discrepancies(reply).unwrap();
+        assert_eq!(
+            discrepancies,
+            vec![Discrepancy { claim: "Water boils at 50C".to_string(), issue: "It boils at 100C".to_string() }]
+        );
+        assert!(parse_discrepancies(r#"{"discrepancies": []}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_malformed_replies() {
+        assert!(parse_discrepancies("Everything checks out.").is_err());
+        assert!(parse_discrepancies(r#"{"claims": []}"#).is_err());
+        assert!(parse_discrepancies("{not json}").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use std::fmt;
+use std::sync::Arc;
+
+// Accepts an output or explains what is wrong with it
+pub type GuardrailCheck = Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+// An extra rule a task's output must follow, for constraints a schema can't express. It
+// runs after the output format is validated, and its error is sent back to the model
+// as retry feedback.
+#[derive(Clone)]
+pub struct Guardrail {
+    name: String,
+    check: GuardrailCheck,
+}
+
+impl Guardrail {
+    pub fn new(name: impl Into<String>, check: impl Fn(&str) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Self {
+            name: name.into(),
+            check: Arc::new(check),
+        }
+    }
+
+    // The output must contain a match of `pattern`; anchor it (`^...$`) to match the
+    // whole output
+    pub fn pattern(pattern: &str) -> Result<Self, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        Ok(Self::new("pattern", move |output| match regex.is_match(output) {
+            true => Ok(()),
+            false => Err(format!("Output must match the pattern {}", regex.as_str())),
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn check(&self, output: &str) -> Result<()> {
+        (self.check)(output).map_err(|message| anyhow!("Guardrail '{}' rejected the output: {}", self.name, message))
+    }
+}
+
+impl fmt::Debug for Guardrail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guardrail").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+// Checks can't be compared, so two guardrails are equal only if they share one
+impl PartialEq for Guardrail {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.check, &other.check)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closure_guardrail_names_itself_in_errors() {
+        let guardrail = Guardrail::new("no hedging", |output| match output.contains("maybe") {
+            true => Err("Answer without hedging".to_string()),
+            false => Ok(()),
+        });
+
+        assert!(guardrail.check("Yes.").is_ok());
+        let error = guardrail.check("maybe").unwrap_err();
+        assert_eq!(error.to_string(), "Guardrail 'no hedging' rejected the output: Answer without hedging");
+        assert_eq!(guardrail, guardrail.clone());
+    }
+
+    #[test]
+    fn test_pattern_guardrail() {
+        let guardrail = Guardrail::pattern(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+
+        assert!(guardrail.check("2024-05-01").is_ok());
+        let error = guardrail.check("May 1st").unwrap_err().to_string();
+        assert!(error.contains("must match the pattern ^\\d{4}"), "{}", error);
+        assert!(Guardrail::pattern("(unclosed").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::{Result, anyhow};
+
+// A heading a Markdown output must contain, with some text under it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MarkdownSection {
+    pub heading: String, // Matched case-insensitively, ignoring surrounding whitespace
+    #[serde(default)]
+    pub level: Option<usize>, // Heading level (1 for `#`); any level if unset
+    #[serde(default)]
+    pub description: Option<String>, // What the section should contain, for the prompt
+}
+
+impl MarkdownSection {
+    pub fn new(heading: impl Into<String>) -> Self {
+        Self { heading: heading.into(), level: None, description: None }
+    }
+
+    pub fn with_level(mut self, level: usize) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    fn matches(&self, heading: &Heading) -> bool {
+        heading.title.eq_ignore_ascii_case(self.heading.trim()) && self.level.is_none_or(|level| level == heading.level)
+    }
+}
+
+struct Heading {
+    level: usize,
+    title: String,
+    has_body: bool, // Any non-blank line before the next heading of the same or a higher level
+}
+
+// Markdown with the given sections; `ordered` also requires them in the listed order
+pub fn validate_markdown(output: &str, sections: &[MarkdownSection], ordered: bool) -> Result<()> {
+    if output.trim().is_empty() {
+        return Err(anyhow!("Output is empty"));
+    }
+    let headings = headings(output);
+    let mut previous: Option<(usize, &MarkdownSection)> = None;
+    for section in sections {
+        let Some(position) = headings.iter().position(|heading| section.matches(heading)) else {
+            let marker = "#".repeat(section.level.unwrap_or(2));
+            return Err(anyhow!("Missing required section: '{} {}'", marker, section.heading));
+        };
+        if !headings[position].has_body {
+            return Err(anyhow!("Section '{}' is empty", section.heading));
+        }
+        if ordered
+            && let Some((before, earlier)) = previous
+            && position < before
+        {
+            return Err(anyhow!("Section '{}' must come after '{}'", section.heading, earlier.heading));
+        }
+        previous = Some((position, section));
+    }
+    Ok(())
+}
+
+// Prompt describing the sections to write
+pub fn markdown_format_prompt(sections: &[MarkdownSection], ordered: bool) -> String {
+    if sections.is_empty() {
+        return "Format your response as Markdown.".to_string();
+    }
+    let mut prompt = "Format your response as Markdown with the following sections, each introduced by its heading:\n\n".to_string();
+    for section in sections {
+        prompt.push_str(&format!("{} {}", "#".repeat(section.level.unwrap_or(2)), section.heading));
+        if let Some(description) = &section.description {
+            prompt.push_str(&format!("\n{}", description));
+        }
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("Use these headings exactly as written");
+    if ordered {
+        prompt.push_str(", in this order");
+    }
+    prompt.push_str(", and put content under every one of them. Do not wrap the response in a code fence.");
+    prompt
+}
+
+// The document inside a code fence wrapping the whole output, keeping the fenced blocks
+// it contains; a missing closing fence (truncated output) is tolerated
+pub fn unwrap_fence(output: &str) -> Option<String> {
+    let trimmed = output.trim();
+    let fence = ["```", "~~~"].into_iter().find(|fence| trimmed.starts_with(fence))?;
+    let body = trimmed.split_once('\n').map(|(_, body)| body.trim_end()).unwrap_or("");
+    Some(body.strip_suffix(fence).unwrap_or(body).trim_end().to_string())
+}
+
+// ATX headings (`## Title`) outside code blocks, in document order
+fn headings(text: &str) -> Vec<Heading> {
+    let mut headings: Vec<Heading> = Vec::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if !in_fence && (1..=6).contains(&level) && trimmed[level..].starts_with([' ', '\t']) {
+            let title = trimmed[level..].trim().trim_end_matches('#').trim().to_string();
+            headings.push(Heading { level, title, has_body: false });
+        } else if !trimmed.is_empty() {
+            // Content counts for the innermost open heading and every heading enclosing it
+            let mut level = usize::MAX;
+            for heading in headings.iter_mut().rev() {
+                if heading.level < level {
+                    heading.has_body = true;
+                    level = heading.level;
+                }
+            }
+        }
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sections() -> Vec<MarkdownSection> {
+        vec![MarkdownSection::new("Summary").with_level(2), MarkdownSection::new("Next steps")]
+    }
+
+    #[test]
+    fn test_validates_required_sections() {
+        let report = "# Report\n## Summary\nAll good.\n### Next Steps\n- Ship it\n";
+        assert!(validate_markdown(report, &sections(), true).is_ok());
+
+        let error = validate_markdown("## Summary\nAll good.\n", &sections(), false).unwrap_err();
+        assert_eq!(error.to_string(), "Missing required section: '## Next steps'");
+        let error = validate_markdown("## Summary\n\n## Next steps\nShip it\n", &sections(), false).unwrap_err();
+        assert_eq!(error.to_string(), "Section 'Summary' is empty");
+        let error = validate_markdown("# Summary\nAll good.\n## Next steps\nShip\n", &sections(), false).unwrap_err();
+        assert!(error.to_string().starts_with("Missing required section: '## Summary'"), "{}", error);
+        assert!(validate_markdown("  \n", &[], false).is_err());
+    }
+
+    #[test]
+    fn test_ordering_and_headings_in_code_blocks() {
+        let swapped = "## Next steps\nShip it\n## Summary\nAll good.\n";
+        assert!(validate_markdown(swapped, &sections(), false).is_ok());
+        let error = validate_markdown(swapped, &sections(), true).unwrap_err();
+        assert_eq!(error.to_string(), "Section 'Next steps' must come after 'Summary'");
+
+        let fenced = "## Summary\n```bash\n## Next steps\n```\n";
+        assert!(validate_markdown(fenced, &sections(), false).is_err());
+    }
+
+    #[test]
+    fn test_unwrap_fence() {
+        assert_eq!(unwrap_fence("## Summary\nAll good."), None);
+        assert_eq!(unwrap_fence("```markdown\n## Summary\nAll good.\n```\n").as_deref(), Some("## Summary\nAll good."));
+        assert_eq!(unwrap_fence("~~~yaml\nname: Ada\n~~~").as_deref(), Some("name: Ada"));
+        assert_eq!(unwrap_fence("```yaml\nname: Ada\nborn: 18").as_deref(), Some("name: Ada\nborn: 18"));
+
+        // Blocks inside the document are kept
+        let nested = "```md\n## Usage\n```bash\nmerco run\n```\n## Notes\nDone.\n```";
+        assert_eq!(unwrap_fence(nested).as_deref(), Some("## Usage\n```bash\nmerco run\n```\n## Notes\nDone."));
+    }
+}
@@ -1 +1,11 @@
+#[allow(clippy::module_inception)]
 pub mod task;
+pub mod code_check;
+pub mod degraded;
+pub mod diff;
+pub mod fact_check;
+pub mod guardrail;
+pub mod markdown;
+pub mod repair;
+pub mod schema;
+pub mod validation;
@@ -0,0 +1,150 @@
+use serde_json::Value;
+
+// Fix the usual defects in JSON written by a model: prose or a markdown fence around it,
+// single-quoted strings, unquoted keys, trailing commas, Python literals (True/False/None) and output
+// cut off before its strings, arrays and objects were closed. Returns the corrected
+// JSON, or `None` if it still doesn't parse.
+pub fn repair_json(text: &str) -> Option<String> {
+    let start = text.find(['{', '['])?;
+    let repaired = rewrite(&text[start..]);
+    serde_json::from_str::<Value>(&repaired).ok()?;
+    Some(repaired)
+}
+
+// Re-emit the first JSON value in `text` with the defects above corrected
+fn rewrite(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut open: Vec<char> = Vec::new(); // Closing brackets still owed, innermost last
+    let mut quote: Option<char> = None; // Delimiter of the string being read, if any
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if let Some(delimiter) = quote {
+            match c {
+                '\\' => match chars.next() {
+                    // `\'` is only needed inside single quotes and isn't valid JSON
+                    Some('\'') => out.push('\''),
+                    Some(escaped) => {
+                        out.push('\\');
+                        out.push(escaped);
+                    }
+                    None => {}
+                },
+                c if c == delimiter => {
+                    out.push('"');
+                    quote = None;
+                }
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c => out.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                out.push('"');
+                quote = Some(c);
+            }
+            '{' => {
+                out.push(c);
+                open.push('}');
+            }
+            '[' => {
+                out.push(c);
+                open.push(']');
+            }
+            '}' | ']' => {
+                drop_trailing_comma(&mut out);
+                out.push(c);
+                open.pop();
+                // Anything after the value (a closing fence, an explanation) is dropped
+                if open.is_empty() {
+                    return out;
+                }
+            }
+            '`' => break, // A closing fence around a truncated value
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut word = String::from(c);
+                while let Some(&next) = chars.peek().filter(|next| next.is_ascii_alphanumeric() || **next == '_') {
+                    word.push(next);
+                    chars.next();
+                }
+                // A bare word followed by a colon is an unquoted key
+                if chars.clone().find(|next| !next.is_whitespace()) == Some(':') {
+                    out.push_str(&format!("\"{}\"", word));
+                    continue;
+                }
+                out.push_str(match word.as_str() {
+                    "True" => "true",
+                    "False" => "false",
+                    "None" => "null",
+                    _ => &word,
+                });
+            }
+            c => out.push(c),
+        }
+    }
+
+    // The text ended early: finish the string, drop a dangling separator and close
+    // whatever is still open
+    if quote.is_some() {
+        out.push('"');
+    }
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    if out.ends_with(':') {
+        out.push_str(" null");
+    }
+    drop_trailing_comma(&mut out);
+    while let Some(close) = open.pop() {
+        out.push(close);
+    }
+    out
+}
+
+fn drop_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end();
+    if trimmed.ends_with(',') {
+        out.truncate(trimmed.len() - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repairs_common_defects() {
+        let cases = [
+            ("trailing commas", r#"{"a": [1, 2,], "b": 3,}"#, json!({"a": [1, 2], "b": 3})),
+            ("single quotes", r#"{'name': 'O\'Brien', 'quote': 'say "hi"'}"#, json!({"name": "O'Brien", "quote": "say \"hi\""})),
+            ("unquoted keys", r#"{name: "Ada", born_in : 1815, tags: [true]}"#, json!({"name": "Ada", "born_in": 1815, "tags": [true]})),
+            ("python literals", r#"{"ok": True, "failed": False, "error": None}"#, json!({"ok": true, "failed": false, "error": null})),
+            ("code fence", "Here you go:\n```json\n{\"a\": 1}\n```\nHope this helps.", json!({"a": 1})),
+            ("truncated string", r#"{"a": [1, 2], "b": "unfinis"#, json!({"a": [1, 2], "b": "unfinis"})),
+            ("truncated after a key", r#"{"a": {"b":"#, json!({"a": {"b": null}})),
+            ("truncated in a fence", "```json\n[1, 2,\n```", json!([1, 2])),
+            ("raw newline in a string", "{\"a\": \"line one\nline two\"}", json!({"a": "line one\nline two"})),
+        ];
+        for (name, input, expected) in cases {
+            let repaired = repair_json(input).unwrap_or_else(|| panic!("{}: not repaired", name));
+            assert_eq!(serde_json::from_str::<Value>(&repaired).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_valid_json_passes_through_unchanged() {
+        for input in [r#"{"a": [1, 2.5, "x"], "b": {"c": null}}"#, r#"[{"k": "v \" quoted"}]"#, "{}"] {
+            assert_eq!(repair_json(input).as_deref(), Some(input));
+        }
+    }
+
+    #[test]
+    fn test_gives_up_on_text_without_json() {
+        assert_eq!(repair_json("No JSON here"), None);
+        assert_eq!(repair_json("{a b c}"), None);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::task::validation::ValidationError;
+use anyhow::{Result, anyhow};
+use serde_json::{Map, Value};
+
+// Checks `value` against a JSON Schema document, e.g. one derived from a Rust type.
+// Covers the keywords derived schemas use: type, enum/const, properties, required,
+// additionalProperties, items, any/one/allOf, local `$ref`s and the numeric, length and
+// size bounds. Other keywords (format, pattern, ...) are accepted without checking.
+pub fn validate_against_schema(value: &Value, schema: &Value) -> Result<()> {
+    Validator { root: schema }.check(value, schema, "")
+}
+
+// Checks a value found at `path` against `schema`, a part of the document `root`
+pub(crate) fn validate_schema_at(value: &Value, schema: &Value, root: &Value, path: &str) -> Result<()> {
+    Validator { root }.check(value, schema, path)
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+impl Validator<'_> {
+    fn check(&self, value: &Value, schema: &Value, path: &str) -> Result<()> {
+        let schema = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => return Err(invalid(path, value, format!("{} is not allowed", describe(path)))),
+            Value::Object(schema) => schema,
+            _ => return Ok(()),
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let target = reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.root.pointer(pointer))
+                .ok_or_else(|| anyhow!("Schema reference '{}' cannot be resolved", reference))?;
+            self.check(value, target, path)?;
+        }
+
+        if let Some(types) = schema.get("type") {
+            let allowed: Vec<&str> = match types {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !allowed.is_empty() && !allowed.iter().any(|name| has_type(value, name)) {
+                let names: Vec<String> = allowed.iter().map(|name| with_article(name)).collect();
+                return Err(invalid(path, value, format!("{} must be {}, got: {}", describe(path), names.join(" or "), value)));
+            }
+        }
+
+        if let Some(options) = schema.get("enum").and_then(Value::as_array)
+            && !options.contains(value)
+        {
+            let listed: Vec<String> = options.iter().map(Value::to_string).collect();
+            return Err(invalid(path, value, format!("{} must be one of {}, got: {}", describe(path), listed.join(", "), value)));
+        }
+        if let Some(expected) = schema.get("const")
+            && expected != value
+        {
+            return Err(invalid(path, value, format!("{} must be {}, got: {}", describe(path), expected, value)));
+        }
+
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for sub in all {
+                self.check(value, sub, path)?;
+            }
+        }
+        if let Some(any) = schema.get("anyOf").and_then(Value::as_array)
+            && !any.iter().any(|sub| self.check(value, sub, path).is_ok())
+        {
+            return Err(invalid(path, value, format!("{} does not match any of the allowed shapes, got: {}", describe(path), value)));
+        }
+        if let Some(one) = schema.get("oneOf").and_then(Value::as_array) {
+            let matches = one.iter().filter(|sub| self.check(value, sub, path).is_ok()).count();
+            if matches != 1 {
+                return Err(invalid(path, value, format!("{} must match exactly one of the allowed shapes, got: {}", describe(path), value)));
+            }
+        }
+
+        match value {
+            Value::Object(object) => {
+                let properties = schema.get("properties").and_then(Value::as_object);
+                if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                    for name in required.iter().filter_map(Value::as_str) {
+                        if !object.contains_key(name) {
+                            return Err(invalid(&child(path, name), value, format!("Missing required field: '{}'", child(path, name))));
+                        }
+                    }
+                }
+                for (name, field) in object {
+                    let field_path = child(path, name);
+                    match properties.and_then(|properties| properties.get(name)) {
+                        Some(field_schema) => self.check(field, field_schema, &field_path)?,
+                        None => match schema.get("additionalProperties") {
+                            Some(Value::Bool(false)) => {
+                                return Err(invalid(&field_path, field, format!("Unexpected field: '{}'", field_path)));
+                            }
+                            Some(extra) => self.check(field, extra, &field_path)?,
+                            None => {}
+                        },
+                    }
+                }
+            }
+            Value::Array(items) => {
+                check_size(path, value, items.len(), schema, "minItems", "maxItems", "items")?;
+                match schema.get("items") {
+                    Some(Value::Array(tuple)) => {
+                        for (i, (item, item_schema)) in items.iter().zip(tuple).enumerate() {
+                            self.check(item, item_schema, &child(path, &i.to_string()))?;
+                        }
+                    }
+                    Some(item_schema) => {
+                        for (i, item) in items.iter().enumerate() {
+                            self.check(item, item_schema, &child(path, &i.to_string()))?;
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Value::String(text) => check_size(path, value, text.chars().count(), schema, "minLength", "maxLength", "characters")?,
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum")
+                    && number < min
+                {
+                    return Err(invalid(path, value, format!("{} must be at least {}, got: {}", describe(path), min, number)));
+                }
+                if let Some(max) = bound("maximum")
+                    && number > max
+                {
+                    return Err(invalid(path, value, format!("{} must be at most {}, got: {}", describe(path), max, number)));
+                }
+                if let Some(min) = bound("exclusiveMinimum")
+                    && number <= min
+                {
+                    return Err(invalid(path, value, format!("{} must be greater than {}, got: {}", describe(path), min, number)));
+                }
+                if let Some(max) = bound("exclusiveMaximum")
+                    && number >= max
+                {
+                    return Err(invalid(path, value, format!("{} must be less than {}, got: {}", describe(path), max, number)));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn with_article(name: &str) -> String {
+    match name {
+        "null" => name.to_string(),
+        "object" | "array" | "integer" => format!("an {}", name),
+        _ => format!("a {}", name),
+    }
+}
+
+fn check_size(path: &str, value: &Value, size: usize, schema: &Map<String, Value>, min: &str, max: &str, unit: &str) -> Result<()> {
+    if let Some(min) = schema.get(min).and_then(Value::as_u64)
+        && (size as u64) < min
+    {
+        return Err(invalid(path, value, format!("{} must have at least {} {}, got {}", describe(path), min, unit, size)));
+    }
+    if let Some(max) = schema.get(max).and_then(Value::as_u64)
+        && (size as u64) > max
+    {
+        return Err(invalid(path, value, format!("{} must have at most {} {}, got {}", describe(path), max, unit, size)));
+    }
+    Ok(())
+}
+
+pub(crate) fn invalid(path: &str, value: &Value, message: String) -> anyhow::Error {
+    ValidationError::new(path, message).with_value(value).into()
+}
+
+// JSON Pointer of a child, escaping `~` and `/` in the key
+pub(crate) fn child(path: &str, key: &str) -> String {
+    format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn describe(path: &str) -> String {
+    if path.is_empty() { "Output".to_string() } else { format!("Field '{}'", path) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error(value: Value, schema: &Value) -> ValidationError {
+        let error = validate_against_schema(&value, schema).unwrap_err();
+        error.downcast_ref::<ValidationError>().cloned().unwrap_or_else(|| panic!("{}", error))
+    }
+
+    fn person() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "age": { "type": "integer", "minimum": 0 },
+                "role": { "enum": ["admin", "member"] },
+                "address": { "$ref": "#/$defs/Address" }
+            },
+            "required": ["name", "address"],
+            "additionalProperties": false,
+            "$defs": {
+                "Address": {
+                    "type": "object",
+                    "properties": { "city": { "type": "string" }, "zip": { "type": ["string", "null"] } },
+                    "required": ["city"]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_accepts_matching_values() {
+        let value = json!({ "name": "Ada", "age": 36, "role": "admin", "address": { "city": "London", "zip": null } });
+        assert!(validate_against_schema(&value, &person()).is_ok());
+    }
+
+    #[test]
+    fn test_required_fields() {
+        let missing = error(json!({ "address": { "city": "London" } }), &person());
+        assert_eq!((missing.path.as_str(), missing.message.as_str()), ("/name", "Missing required field: '/name'"));
+
+        let nested = error(json!({ "name": "Ada", "address": {} }), &person());
+        assert_eq!(nested.message, "Missing required field: '/address/city'");
+    }
+
+    #[test]
+    fn test_type_mismatches() {
+        let schema = person();
+        let age = error(json!({ "name": "Ada", "age": "36", "address": { "city": "London" } }), &schema);
+        assert_eq!(age.message, "Field '/age' must be an integer, got: \"36\"");
+
+        let zip = error(json!({ "name": "Ada", "address": { "city": "London", "zip": 12 } }), &schema);
+        assert_eq!(zip.message, "Field '/address/zip' must be a string or null, got: 12");
+
+        let root = error(json!([1]), &schema);
+        assert_eq!(root.message, "Output must be an object, got: [1]");
+    }
+
+    #[test]
+    fn test_enums_and_bounds() {
+        let role = error(json!({ "name": "Ada", "role": "owner", "address": { "city": "London" } }), &person());
+        assert_eq!(role.message, "Field '/role' must be one of \"admin\", \"member\", got: \"owner\"");
+
+        let name = error(json!({ "name": "", "address": { "city": "London" } }), &person());
+        assert_eq!(name.message, "Field '/name' must have at least 1 characters, got 0");
+    }
+
+    #[test]
+    fn test_unexpected_fields_and_array_items() {
+        let extra = error(json!({ "name": "Ada", "address": { "city": "London" }, "a/b": 1 }), &person());
+        assert_eq!(extra.path, "/a~1b");
+
+        let schema = json!({ "type": "array", "items": { "type": "number" }, "maxItems": 3 });
+        assert_eq!(error(json!([1, "2"]), &schema).path, "/1");
+        assert_eq!(error(json!([1, 2, 3, 4]), &schema).message, "Output must have at most 3 items, got 4");
+    }
+
+    #[test]
+    fn test_unresolved_reference() {
+        let schema = json!({ "$ref": "#/$defs/Missing" });
+        let error = validate_against_schema(&json!(1), &schema).unwrap_err();
+        assert_eq!(error.to_string(), "Schema reference '#/$defs/Missing' cannot be resolved");
+    }
+}
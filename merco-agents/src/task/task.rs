@@ -1,28 +1,323 @@
-use serde_json::Value;
+use crate::agent::sampling::SamplingParams;
+use crate::task::code_check::{CodeCheck, extract_code};
+use crate::task::degraded::DegradedFallback;
+use crate::task::diff::apply_unified_diff;
+use crate::task::fact_check::FactCheck;
+use crate::task::guardrail::Guardrail;
+use crate::task::markdown::{MarkdownSection, markdown_format_prompt, unwrap_fence, validate_markdown};
+use crate::task::repair::repair_json;
+use crate::task::schema::{child, invalid, validate_against_schema, validate_schema_at};
+use std::collections::BTreeMap;
+use serde_json::{Map, Value, json};
 use anyhow::{Result, anyhow};
+use merco_llmproxy::{MercoOutput, PartialJsonEvent, ResponseFormat, Tool, tokenizer::default_tokenizer};
+use regex::Regex;
+use std::path::PathBuf;
+
+// Name of the synthetic tool agents can be required to call with their final answer
+pub const FINAL_ANSWER_TOOL: &str = "final_answer";
+
+// Default token budget shared by all attachments of a task
+pub const DEFAULT_ATTACHMENT_TOKENS: usize = 8000;
 
 // Enum to define different output format types
-#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum OutputFormat {
+    #[default]
     Text, // Free-form text output
     Json {
         schema: JsonSchema,
         strict: bool, // Whether to enforce strict validation (all fields required)
     },
+    Code {
+        language: String,
+        check: Option<CodeCheck>, // Command that must accept the code for it to be valid
+    },
+    Diff {
+        files: BTreeMap<String, String>, // Path -> current content the diff must apply to
+    },
+    // JSON matching a full JSON Schema document, e.g. one derived from a Rust type
+    Schema {
+        name: String,
+        schema: Value,
+    },
+    // Markdown document that must contain the given sections
+    Markdown {
+        sections: Vec<MarkdownSection>,
+        ordered: bool, // Whether the sections must appear in the listed order
+    },
+    // YAML mapping with the given fields; parsing it needs the `yaml` feature
+    Yaml {
+        schema: JsonSchema,
+        strict: bool,
+    },
 }
 
-// JSON Schema definition for validation
-#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+// JSON Schema definition for validation. Also describes nested objects; one without
+// any fields accepts any object.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct JsonSchema {
+    #[serde(default)]
     pub required_fields: Vec<JsonField>,
+    #[serde(default)]
     pub optional_fields: Vec<JsonField>,
 }
 
+impl JsonSchema {
+    pub fn new(required_fields: Vec<JsonField>, optional_fields: Vec<JsonField>) -> Self {
+        Self { required_fields, optional_fields }
+    }
+
+    // True for a free-form object, whose content isn't checked
+    pub fn is_empty(&self) -> bool {
+        self.required_fields.is_empty() && self.optional_fields.is_empty()
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &JsonField> {
+        self.required_fields.iter().chain(self.optional_fields.iter())
+    }
+
+    // Whether providers can enforce this schema strictly: every field at every level is
+    // required and no object is free-form
+    fn supports_strict(&self) -> bool {
+        fn type_supports_strict(field_type: &JsonFieldType) -> bool {
+            match field_type {
+                JsonFieldType::Object(schema) => !schema.is_empty() && schema.supports_strict(),
+                JsonFieldType::Array(element_type) => type_supports_strict(element_type),
+                _ => true,
+            }
+        }
+        self.optional_fields.is_empty() && self.required_fields.iter().all(|f| type_supports_strict(&f.field_type))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct JsonField {
     pub name: String,
     pub field_type: JsonFieldType,
     pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "FieldConstraints::is_empty")]
+    pub constraints: FieldConstraints,
+}
+
+impl JsonField {
+    pub fn new(name: impl Into<String>, field_type: JsonFieldType) -> Self {
+        Self { name: name.into(), field_type, description: None, constraints: FieldConstraints::default() }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_constraints(mut self, constraints: FieldConstraints) -> Self {
+        self.constraints = constraints;
+        self
+    }
+}
+
+// Limits on a field's value beyond its type. On array fields, `min_items`/`max_items`
+// bound the array and the other constraints apply to each element.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FieldConstraints {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_values: Option<Vec<Value>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<Pattern>, // Regex the string must contain a match of; anchor it to match whole
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_items: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
+}
+
+impl FieldConstraints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn with_allowed_values(mut self, values: impl IntoIterator<Item = impl Into<Value>>) -> Self {
+        self.allowed_values = Some(values.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = Some(minimum);
+        self
+    }
+
+    pub fn with_maximum(mut self, maximum: f64) -> Self {
+        self.maximum = Some(maximum);
+        self
+    }
+
+    pub fn with_range(self, minimum: f64, maximum: f64) -> Self {
+        self.with_minimum(minimum).with_maximum(maximum)
+    }
+
+    // Fails on an invalid regex, so a broken pattern is caught before any output is validated
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.pattern = Some(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn with_items(mut self, min_items: Option<usize>, max_items: Option<usize>) -> Self {
+        self.min_items = min_items;
+        self.max_items = max_items;
+        self
+    }
+
+    // Check a field's value; for arrays, the item count and then each element
+    fn check(&self, value: &Value, field_name: &str) -> Result<()> {
+        match value.as_array() {
+            Some(items) => {
+                self.check_items(items, field_name)?;
+                for (i, element) in items.iter().enumerate() {
+                    self.check_value(element, &child(field_name, &i.to_string()))?;
+                }
+                Ok(())
+            }
+            None => self.check_value(value, field_name),
+        }
+    }
+
+    // Check a value, or one element of an array field
+    fn check_value(&self, value: &Value, field_name: &str) -> Result<()> {
+        if let Some(allowed) = &self.allowed_values
+            && !allowed.contains(value)
+        {
+            let listed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            return Err(invalid(field_name, value, format!("Field '{}' must be one of {}, got: {}", field_name, listed.join(", "), value)));
+        }
+        if let Some(number) = value.as_f64() {
+            if let Some(minimum) = self.minimum
+                && number < minimum
+            {
+                return Err(invalid(field_name, value, format!("Field '{}' must be at least {}, got: {}", field_name, minimum, value)));
+            }
+            if let Some(maximum) = self.maximum
+                && number > maximum
+            {
+                return Err(invalid(field_name, value, format!("Field '{}' must be at most {}, got: {}", field_name, maximum, value)));
+            }
+        }
+        if let (Some(pattern), Some(text)) = (&self.pattern, value.as_str())
+            && !pattern.0.is_match(text)
+        {
+            return Err(invalid(field_name, value, format!("Field '{}' must match the pattern {}, got: {}", field_name, pattern.as_str(), value)));
+        }
+        Ok(())
+    }
+
+    fn check_items(&self, items: &[Value], field_name: &str) -> Result<()> {
+        if let Some(min_items) = self.min_items
+            && items.len() < min_items
+        {
+            return Err(invalid(field_name, &Value::from(items), format!("Field '{}' must have at least {} items, got {}", field_name, min_items, items.len())));
+        }
+        if let Some(max_items) = self.max_items
+            && items.len() > max_items
+        {
+            return Err(invalid(field_name, &Value::from(items), format!("Field '{}' must have at most {} items, got {}", field_name, max_items, items.len())));
+        }
+        Ok(())
+    }
+
+    // Short description for the format prompt, e.g. `one of "low", "high"; 1 to 5 items`
+    fn describe(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(allowed) = &self.allowed_values {
+            let listed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            parts.push(format!("one of {}", listed.join(", ")));
+        }
+        match (self.minimum, self.maximum) {
+            (Some(minimum), Some(maximum)) => parts.push(format!("between {} and {}", minimum, maximum)),
+            (Some(minimum), None) => parts.push(format!("at least {}", minimum)),
+            (None, Some(maximum)) => parts.push(format!("at most {}", maximum)),
+            (None, None) => {}
+        }
+        if let Some(pattern) = &self.pattern {
+            parts.push(format!("matching /{}/", pattern.as_str()));
+        }
+        match (self.min_items, self.max_items) {
+            (Some(min_items), Some(max_items)) => parts.push(format!("{} to {} items", min_items, max_items)),
+            (Some(min_items), None) => parts.push(format!("at least {} items", min_items)),
+            (None, Some(max_items)) => parts.push(format!("at most {} items", max_items)),
+            (None, None) => {}
+        }
+        (!parts.is_empty()).then(|| parts.join("; "))
+    }
+
+    // JSON Schema keywords for the value (or array elements) and for the array itself
+    fn apply_to_schema(&self, schema: &mut Value, is_array: bool) {
+        let Some(obj) = schema.as_object_mut() else { return };
+        if is_array {
+            if let Some(min_items) = self.min_items {
+                obj.insert("minItems".to_string(), json!(min_items));
+            }
+            if let Some(max_items) = self.max_items {
+                obj.insert("maxItems".to_string(), json!(max_items));
+            }
+        }
+        let target = match obj.get_mut("items") {
+            Some(items) if is_array => items,
+            _ => schema,
+        };
+        let Some(target) = target.as_object_mut() else { return };
+        if let Some(allowed) = &self.allowed_values {
+            target.insert("enum".to_string(), json!(allowed));
+        }
+        if let Some(minimum) = self.minimum {
+            target.insert("minimum".to_string(), json!(minimum));
+        }
+        if let Some(maximum) = self.maximum {
+            target.insert("maximum".to_string(), json!(maximum));
+        }
+        if let Some(pattern) = &self.pattern {
+            target.insert("pattern".to_string(), json!(pattern.as_str()));
+        }
+    }
+}
+
+// A compiled `FieldConstraints::pattern`, stored as its source text. Loading a definition
+// with an invalid regex fails instead of every validation of the output.
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl serde::Serialize for Pattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Pattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Self::new(&pattern).map_err(serde::de::Error::custom)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -30,23 +325,85 @@ pub enum JsonFieldType {
     String,
     Number,
     Boolean,
-    Array(Box<JsonFieldType>), // Array of specific type
-    Object, // Nested object (simplified for now)
+    Array(Box<JsonFieldType>), // Array of specific type, including objects with their own schema
+    Object(JsonSchema), // Nested object, validated recursively
+}
+
+// Where an attachment's content comes from
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AttachmentSource {
+    File(PathBuf), // Read when the prompt is built
+    Text(String),
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+// Reference material rendered into the task prompt under a label
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Attachment {
+    pub label: String,
+    pub source: AttachmentSource,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
 pub struct Task {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<TaskId>, // Identifies the task in audit records and other tasks' `context`
     pub description: String,
+    #[serde(default)]
     pub expected_output: Option<String>,
+    #[serde(default)]
     pub output_format: OutputFormat, // New field for typed output
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default = "default_attachment_tokens")]
+    pub attachment_token_budget: usize,
+    #[serde(default)]
+    pub fact_check: Option<FactCheck>, // Verify key claims with a second model before accepting
+    #[serde(default)]
+    pub requires_approval: bool, // Hold the output for a human decision before the crew moves on
+    #[serde(default)]
+    pub quote_errors: bool, // Quote the offending part of an invalid output in retry feedback
+    #[serde(default)]
+    pub sampling: SamplingParams, // Overrides the agent's sampling defaults for this task
+    #[serde(skip)]
+    pub degraded: Option<DegradedFallback>, // Answer used when the provider is unavailable
+    #[serde(skip)]
+    pub guardrails: Vec<Guardrail>, // Extra checks on the output, run after format validation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<TaskId>, // Tasks whose outputs this one receives in a crew's graph workflow
+}
+
+pub type TaskId = String;
+
+fn default_attachment_tokens() -> usize {
+    DEFAULT_ATTACHMENT_TOKENS
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml(output: &str) -> Result<Value> {
+    serde_yaml::from_str(output).map_err(|e| anyhow!("Output is not valid YAML: {}", e))
+}
+
+#[cfg(not(feature = "yaml"))]
+fn parse_yaml(_output: &str) -> Result<Value> {
+    Err(anyhow!("YAML output needs merco-agents' `yaml` feature"))
 }
 
 impl Task {
     pub fn new(description: String, expected_output: Option<String>) -> Self {
         Self {
+            id: None,
             description,
             expected_output,
             output_format: OutputFormat::Text, // Default to text
+            attachments: Vec::new(),
+            attachment_token_budget: DEFAULT_ATTACHMENT_TOKENS,
+            fact_check: None,
+            requires_approval: false,
+            quote_errors: false,
+            sampling: SamplingParams::default(),
+            degraded: None,
+            guardrails: Vec::new(),
+            context: Vec::new(),
         }
     }
 
@@ -59,6 +416,7 @@ impl Task {
         strict: bool,
     ) -> Self {
         Self {
+            id: None,
             description,
             expected_output,
             output_format: OutputFormat::Json {
@@ -68,6 +426,109 @@ impl Task {
                 },
                 strict,
             },
+            attachments: Vec::new(),
+            attachment_token_budget: DEFAULT_ATTACHMENT_TOKENS,
+            fact_check: None,
+            requires_approval: false,
+            quote_errors: false,
+            sampling: SamplingParams::default(),
+            degraded: None,
+            guardrails: Vec::new(),
+            context: Vec::new(),
+        }
+    }
+
+    // Constructor for code output, optionally checked by a compile/lint command
+    pub fn new_with_code_output(
+        description: String,
+        expected_output: Option<String>,
+        language: String,
+        check: Option<CodeCheck>,
+    ) -> Self {
+        Self {
+            output_format: OutputFormat::Code { language, check },
+            ..Self::new(description, expected_output)
+        }
+    }
+
+    // Constructor for unified diff output against the given files (path -> content)
+    pub fn new_with_diff_output(
+        description: String,
+        expected_output: Option<String>,
+        files: BTreeMap<String, String>,
+    ) -> Self {
+        Self {
+            output_format: OutputFormat::Diff { files },
+            ..Self::new(description, expected_output)
+        }
+    }
+
+    // Constructor for a Markdown report with the given sections
+    pub fn new_with_markdown_output(
+        description: String,
+        expected_output: Option<String>,
+        sections: Vec<MarkdownSection>,
+        ordered: bool,
+    ) -> Self {
+        Self {
+            output_format: OutputFormat::Markdown { sections, ordered },
+            ..Self::new(description, expected_output)
+        }
+    }
+
+    // Constructor for YAML output, validated like JSON output
+    pub fn new_with_yaml_output(
+        description: String,
+        expected_output: Option<String>,
+        required_fields: Vec<JsonField>,
+        optional_fields: Vec<JsonField>,
+        strict: bool,
+    ) -> Self {
+        Self {
+            output_format: OutputFormat::Yaml { schema: JsonSchema::new(required_fields, optional_fields), strict },
+            ..Self::new(description, expected_output)
+        }
+    }
+
+    // Constructor for JSON output described by a JSON Schema document
+    pub fn new_with_schema_output(
+        description: String,
+        expected_output: Option<String>,
+        name: impl Into<String>,
+        schema: Value,
+    ) -> Self {
+        Self {
+            output_format: OutputFormat::Schema { name: name.into(), schema },
+            ..Self::new(description, expected_output)
+        }
+    }
+
+    // Expect JSON output shaped like `T`, e.g. a `#[derive(MercoOutput)]` struct, with
+    // nested objects, enums and arrays validated in full (builder style)
+    pub fn with_output<T: MercoOutput>(mut self) -> Self {
+        self.output_format = OutputFormat::Schema { name: T::output_name(), schema: T::output_schema() };
+        self
+    }
+
+    // Expect JSON output deserializable into `T`, with the schema derived from the type
+    // (builder style). Usually set by `Agent::call_typed`.
+    #[cfg(feature = "schema")]
+    pub fn with_output_type<T: schemars::JsonSchema>(mut self) -> Self {
+        // Providers only accept `[a-zA-Z0-9_-]` in schema names
+        let name: String = T::schema_name()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .take(64)
+            .collect();
+        self.output_format = OutputFormat::Schema { name, schema: merco_llmproxy::typed::schema_for::<T>() };
+        self
+    }
+
+    // Apply a diff produced for this task, returning the updated files
+    pub fn apply_diff_output(&self, output: &str) -> Result<BTreeMap<String, String>> {
+        match &self.output_format {
+            OutputFormat::Diff { files } => apply_unified_diff(output, files),
+            _ => Err(anyhow!("Task does not have diff output")),
         }
     }
 
@@ -84,14 +545,131 @@ impl Task {
                 name,
                 field_type,
                 description: None,
+                constraints: FieldConstraints::default(),
             })
             .collect();
 
         Self::new_with_json_output(description, expected_output, fields, vec![], strict)
     }
 
-    // Validate agent output against the expected format
+    // Identify the task in tool audit records (builder style)
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    // Attach a file whose contents are included in the prompt (builder style)
+    pub fn with_file(mut self, label: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.attachments.push(Attachment {
+            label: label.into(),
+            source: AttachmentSource::File(path.into()),
+        });
+        self
+    }
+
+    // Attach a text snippet included in the prompt (builder style)
+    pub fn with_snippet(mut self, label: impl Into<String>, text: impl Into<String>) -> Self {
+        self.attachments.push(Attachment {
+            label: label.into(),
+            source: AttachmentSource::Text(text.into()),
+        });
+        self
+    }
+
+    // Set the token budget shared by all attachments (builder style)
+    pub fn with_attachment_token_budget(mut self, budget: usize) -> Self {
+        self.attachment_token_budget = budget;
+        self
+    }
+
+    // Have the agent's verifier check key claims in the output before it is accepted. The
+    // agent must have one (`Agent::with_verifier`), otherwise the task fails
+    pub fn with_fact_check(mut self, fact_check: FactCheck) -> Self {
+        self.fact_check = Some(fact_check);
+        self
+    }
+
+    // Override the agent's sampling defaults, e.g. a lower temperature for extraction tasks
+    pub fn with_sampling(mut self, sampling: SamplingParams) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    // Resolve with `fallback` instead of failing when every LLM attempt errors
+    pub fn with_degraded_fallback(mut self, fallback: DegradedFallback) -> Self {
+        self.degraded = Some(fallback);
+        self
+    }
+
+    pub fn with_guardrail(mut self, guardrail: Guardrail) -> Self {
+        self.guardrails.push(guardrail);
+        self
+    }
+
+    // Depend on the task with id `task`: in a crew's graph workflow this task runs after
+    // it and receives its output
+    pub fn with_context(mut self, task: impl Into<TaskId>) -> Self {
+        self.context.push(task.into());
+        self
+    }
+
+    // Ask a human to approve the output through the crew's `ApprovalTransport`
+    pub fn with_approval(mut self) -> Self {
+        self.requires_approval = true;
+        self
+    }
+
+    // Show the model the invalid part of its output when asking it to retry
+    pub fn with_quoted_errors(mut self) -> Self {
+        self.quote_errors = true;
+        self
+    }
+
+    // Render attachments for the prompt, splitting the token budget evenly and
+    // truncating any attachment that exceeds its share
+    pub fn render_attachments(&self) -> Result<Option<String>> {
+        if self.attachments.is_empty() {
+            return Ok(None);
+        }
+
+        let tokenizer = default_tokenizer();
+        let per_attachment = self.attachment_token_budget / self.attachments.len();
+        let mut sections = Vec::new();
+
+        for attachment in &self.attachments {
+            let content = match &attachment.source {
+                AttachmentSource::Text(text) => text.clone(),
+                AttachmentSource::File(path) => std::fs::read_to_string(path)
+                    .map_err(|e| anyhow!("Failed to read attachment '{}' ({}): {}", attachment.label, path.display(), e))?,
+            };
+
+            let tokens = tokenizer.count(&content);
+            let content = if tokens > per_attachment {
+                // Keep a proportional prefix of the content
+                let keep_chars = content.chars().count() * per_attachment / tokens.max(1);
+                let truncated: String = content.chars().take(keep_chars).collect();
+                format!("{}\n[... truncated {} of ~{} tokens ...]", truncated, tokens - per_attachment, tokens)
+            } else {
+                content
+            };
+            sections.push(format!("--- {} ---\n{}", attachment.label, content));
+        }
+
+        Ok(Some(sections.join("\n\n")))
+    }
+
+    // True when validating output runs an external command, which blocks until it exits
+    pub fn has_code_check(&self) -> bool {
+        matches!(self.output_format, OutputFormat::Code { check: Some(_), .. })
+    }
+
+    // Validate agent output against the expected format, then the guardrails
     pub fn validate_output(&self, output: &str) -> Result<()> {
+        self.validate_format(output)?;
+        self.guardrails.iter().try_for_each(|guardrail| guardrail.check(output))
+    }
+
+    fn validate_format(&self, output: &str) -> Result<()> {
         match &self.output_format {
             OutputFormat::Text => {
                 // For text format, any non-empty string is valid
@@ -103,6 +681,110 @@ impl Task {
             OutputFormat::Json { schema, strict } => {
                 self.validate_json_output(output, schema, *strict)
             }
+            OutputFormat::Code { check, .. } => {
+                let code = extract_code(output);
+                if code.is_empty() {
+                    return Err(anyhow!("Output contains no code"));
+                }
+                match check {
+                    Some(check) => check.run(&code),
+                    None => Ok(()),
+                }
+            }
+            // Valid only if every hunk applies cleanly to the provided files
+            OutputFormat::Diff { files } => apply_unified_diff(output, files).map(|_| ()),
+            OutputFormat::Schema { schema, .. } => {
+                let parsed: Value = serde_json::from_str(output.trim())
+                    .map_err(|e| anyhow!("Output is not valid JSON: {}", e))?;
+                validate_against_schema(&parsed, schema)
+            }
+            OutputFormat::Markdown { sections, ordered } => validate_markdown(output, sections, *ordered),
+            OutputFormat::Yaml { schema, strict } => {
+                let parsed = parse_yaml(output)?;
+                let obj = parsed
+                    .as_object()
+                    .ok_or_else(|| anyhow!("YAML output must be a mapping, got: {}", parsed))?;
+                self.validate_object(obj, schema, *strict, "")
+            }
+        }
+    }
+
+    // A corrected version of malformed output (fences, trailing commas, truncated JSON...)
+    // that passes validation, so a retry isn't spent on it
+    pub fn repair_output(&self, output: &str) -> Option<String> {
+        let repaired = match self.output_format {
+            OutputFormat::Json { .. } | OutputFormat::Schema { .. } => repair_json(output)?,
+            // A Markdown or YAML document wrapped in a code fence as a whole
+            OutputFormat::Markdown { .. } | OutputFormat::Yaml { .. } => unwrap_fence(output)?,
+            _ => return None,
+        };
+        self.validate_output(&repaired).is_ok().then_some(repaired)
+    }
+
+    // Check a field completed mid-stream against the schema. Only errors that the rest
+    // of the output can't fix are reported, so generation can be aborted early.
+    pub fn validate_partial(&self, event: &PartialJsonEvent) -> Result<()> {
+        let PartialJsonEvent::FieldCompleted { path, value } = event else {
+            return Ok(());
+        };
+        let (schema, strict) = match &self.output_format {
+            OutputFormat::Json { schema, strict } => (schema, strict),
+            OutputFormat::Schema { schema, .. } => return Self::validate_partial_schema(schema, path, value),
+            _ => return Ok(()),
+        };
+
+        // Walk the schema along the path; array indexes step into the element type
+        let Some(pointer) = path.strip_prefix('/') else {
+            return Ok(());
+        };
+        let mut segments = pointer.split('/').map(|s| s.replace("~1", "/").replace("~0", "~")).peekable();
+        let mut current = schema;
+        let mut name = String::new();
+        while let Some(segment) = segments.next() {
+            name = child(&name, &segment);
+            let Some(field) = current.fields().find(|f| f.name == segment) else {
+                return match *strict && !current.is_empty() {
+                    true => Err(invalid(&name, value, format!("Unexpected field in strict mode: '{}'", name))),
+                    false => Ok(()),
+                };
+            };
+            let mut field_type = &field.field_type;
+            let mut depth = 0;
+            while let JsonFieldType::Array(element_type) = field_type {
+                let Some(index) = segments.next_if(|s| s.parse::<usize>().is_ok()) else { break };
+                name = child(&name, &index);
+                field_type = element_type;
+                depth += 1;
+            }
+            match (field_type, segments.peek()) {
+                (_, None) => {
+                    self.validate_field_type(value, field_type, &name, *strict)?;
+                    // Element constraints only apply one level into an array field
+                    return match depth {
+                        0 => field.constraints.check(value, &name),
+                        1 => field.constraints.check_value(value, &name),
+                        _ => Ok(()),
+                    };
+                }
+                (JsonFieldType::Object(nested), Some(_)) => current = nested,
+                _ => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    // Top-level fields of a JSON Schema output can be checked on their own
+    fn validate_partial_schema(schema: &Value, path: &str, value: &Value) -> Result<()> {
+        let Some(name) = path.strip_prefix('/').filter(|p| !p.contains('/')) else {
+            return Ok(());
+        };
+        let name = name.replace("~1", "/").replace("~0", "~");
+        match schema["properties"].get(&name) {
+            Some(field_schema) => validate_schema_at(value, field_schema, schema, path),
+            None if schema["additionalProperties"] == Value::Bool(false) => {
+                Err(invalid(path, value, format!("Unexpected field: '{}'", path)))
+            }
+            None => Ok(()),
         }
     }
 
@@ -116,35 +798,37 @@ impl Task {
         let obj = parsed.as_object()
             .ok_or_else(|| anyhow!("JSON output must be an object, got: {}", parsed))?;
 
+        self.validate_object(obj, schema, strict, "")
+    }
+
+    // Check an object against its schema; `prefix` is the JSON Pointer of the object
+    fn validate_object(&self, obj: &Map<String, Value>, schema: &JsonSchema, strict: bool, prefix: &str) -> Result<()> {
+        let qualified = |name: &str| child(prefix, name);
+
         // Validate required fields
         for field in &schema.required_fields {
-            if !obj.contains_key(&field.name) {
-                return Err(anyhow!("Missing required field: '{}'", field.name));
-            }
-
-            let value = &obj[&field.name];
-            self.validate_field_type(value, &field.field_type, &field.name)?;
+            let Some(value) = obj.get(&field.name) else {
+                return Err(invalid(&qualified(&field.name), &Value::Object(obj.clone()), format!("Missing required field: '{}'", qualified(&field.name))));
+            };
+            self.validate_field_type(value, &field.field_type, &qualified(&field.name), strict)?;
+            field.constraints.check(value, &qualified(&field.name))?;
         }
 
         // Validate optional fields (if present)
         for field in &schema.optional_fields {
             if let Some(value) = obj.get(&field.name) {
-                self.validate_field_type(value, &field.field_type, &field.name)?;
+                self.validate_field_type(value, &field.field_type, &qualified(&field.name), strict)?;
+                field.constraints.check(value, &qualified(&field.name))?;
             }
         }
 
-        // In strict mode, ensure no extra fields are present
-        if strict {
-            let expected_fields: std::collections::HashSet<&String> = schema
-                .required_fields
-                .iter()
-                .chain(schema.optional_fields.iter())
-                .map(|f| &f.name)
-                .collect();
+        // In strict mode, ensure no extra fields are present (free-form objects excepted)
+        if strict && !schema.is_empty() {
+            let expected_fields: std::collections::HashSet<&String> = schema.fields().map(|f| &f.name).collect();
 
             for key in obj.keys() {
                 if !expected_fields.contains(key) {
-                    return Err(anyhow!("Unexpected field in strict mode: '{}'", key));
+                    return Err(invalid(&qualified(key), &obj[key], format!("Unexpected field in strict mode: '{}'", qualified(key))));
                 }
             }
         }
@@ -153,37 +837,36 @@ impl Task {
     }
 
     // Validate individual field types
-    fn validate_field_type(&self, value: &Value, expected_type: &JsonFieldType, field_name: &str) -> Result<()> {
+    fn validate_field_type(&self, value: &Value, expected_type: &JsonFieldType, field_name: &str, strict: bool) -> Result<()> {
         match expected_type {
             JsonFieldType::String => {
                 if !value.is_string() {
-                    return Err(anyhow!("Field '{}' must be a string, got: {}", field_name, value));
+                    return Err(invalid(field_name, value, format!("Field '{}' must be a string, got: {}", field_name, value)));
                 }
             }
             JsonFieldType::Number => {
                 if !value.is_number() {
-                    return Err(anyhow!("Field '{}' must be a number, got: {}", field_name, value));
+                    return Err(invalid(field_name, value, format!("Field '{}' must be a number, got: {}", field_name, value)));
                 }
             }
             JsonFieldType::Boolean => {
                 if !value.is_boolean() {
-                    return Err(anyhow!("Field '{}' must be a boolean, got: {}", field_name, value));
+                    return Err(invalid(field_name, value, format!("Field '{}' must be a boolean, got: {}", field_name, value)));
                 }
             }
             JsonFieldType::Array(element_type) => {
                 let arr = value.as_array()
-                    .ok_or_else(|| anyhow!("Field '{}' must be an array, got: {}", field_name, value))?;
+                    .ok_or_else(|| invalid(field_name, value, format!("Field '{}' must be an array, got: {}", field_name, value)))?;
                 
                 // Validate each element in the array
                 for (i, element) in arr.iter().enumerate() {
-                    self.validate_field_type(element, element_type, &format!("{}[{}]", field_name, i))?;
+                    self.validate_field_type(element, element_type, &child(field_name, &i.to_string()), strict)?;
                 }
             }
-            JsonFieldType::Object => {
-                if !value.is_object() {
-                    return Err(anyhow!("Field '{}' must be an object, got: {}", field_name, value));
-                }
-                // For now, we just check it's an object. Could extend to nested schema validation.
+            JsonFieldType::Object(schema) => {
+                let obj = value.as_object()
+                    .ok_or_else(|| invalid(field_name, value, format!("Field '{}' must be an object, got: {}", field_name, value)))?;
+                self.validate_object(obj, schema, strict, field_name)?;
             }
         }
         Ok(())
@@ -199,29 +882,7 @@ impl Task {
                 let mut prompt = "You must respond with valid JSON in the following format:\n\n".to_string();
                 
                 prompt.push_str("{\n");
-                
-                // Add required fields
-                for field in &schema.required_fields {
-                    prompt.push_str(&format!(
-                        "  \"{}\": <{}>{},  // REQUIRED{}\n", 
-                        field.name,
-                        self.type_to_string(&field.field_type),
-                        if schema.required_fields.last() == Some(field) && schema.optional_fields.is_empty() { "" } else { "," },
-                        field.description.as_ref().map(|d| format!(" - {}", d)).unwrap_or_default()
-                    ));
-                }
-                
-                // Add optional fields
-                for field in &schema.optional_fields {
-                    prompt.push_str(&format!(
-                        "  \"{}\": <{}>{},  // OPTIONAL{}\n", 
-                        field.name,
-                        self.type_to_string(&field.field_type),
-                        if schema.optional_fields.last() == Some(field) { "" } else { "," },
-                        field.description.as_ref().map(|d| format!(" - {}", d)).unwrap_or_default()
-                    ));
-                }
-                
+                self.push_format_fields(&mut prompt, schema, 1);
                 prompt.push_str("}\n\n");
                 
                 if *strict {
@@ -231,6 +892,254 @@ impl Task {
                 prompt.push_str("Ensure your response is valid JSON and follows this exact structure.");
                 prompt
             }
+            OutputFormat::Code { language, check } => {
+                let mut prompt = format!(
+                    "Respond with only the complete {} code in a single ```{} fenced block, with no explanation.",
+                    language, language
+                );
+                if let Some(check) = check {
+                    prompt.push_str(&format!(" The code must pass `{}` without errors.", check.program));
+                }
+                prompt
+            }
+            OutputFormat::Diff { files } => {
+                let mut prompt = "Respond with only a unified diff (```diff fenced block) that makes the change. \
+                    Use `--- a/<path>` and `+++ b/<path>` headers and `@@` hunks with exact context lines; \
+                    use /dev/null as the old path for new files. Do not output whole files.\n\nCurrent files:\n"
+                    .to_string();
+                for (path, content) in files {
+                    prompt.push_str(&format!("\n--- {} ---\n{}\n", path, content));
+                }
+                prompt
+            }
+            OutputFormat::Schema { schema, .. } => format!(
+                "You must respond with only a JSON value that matches this JSON Schema:\n\n{}\n\n\
+                 Ensure your response is valid JSON with no surrounding text.",
+                serde_json::to_string_pretty(schema).unwrap_or_else(|_| schema.to_string())
+            ),
+            OutputFormat::Markdown { sections, ordered } => markdown_format_prompt(sections, *ordered),
+            OutputFormat::Yaml { schema, strict } => {
+                let mut prompt = "You must respond with a valid YAML document in the following format:\n\n".to_string();
+                self.push_yaml_fields(&mut prompt, schema, "");
+                prompt.push('\n');
+                if *strict {
+                    prompt.push_str("IMPORTANT: Only include the specified fields. No additional fields are allowed.\n");
+                }
+                prompt.push_str("Respond with only the YAML document, without a code fence or any other text.");
+                prompt
+            }
+        }
+    }
+
+    // One line per field (required first), with nested objects expanded and indented
+    fn push_format_fields(&self, prompt: &mut String, schema: &JsonSchema, depth: usize) {
+        let indent = "  ".repeat(depth);
+        let count = schema.required_fields.len() + schema.optional_fields.len();
+        let fields = schema.required_fields.iter().map(|f| (f, "REQUIRED")).chain(schema.optional_fields.iter().map(|f| (f, "OPTIONAL")));
+        for (i, (field, requirement)) in fields.enumerate() {
+            let comma = if i + 1 == count { "" } else { "," };
+            let note = format!("  // {}", Self::field_note(field, requirement));
+            // Objects with a schema (directly or as array elements) are spelled out
+            let (open, close, nested) = match &field.field_type {
+                JsonFieldType::Object(nested) if !nested.is_empty() => ("{", "}", nested),
+                JsonFieldType::Array(element_type) => match element_type.as_ref() {
+                    JsonFieldType::Object(nested) if !nested.is_empty() => ("[{", "}, ...]", nested),
+                    _ => ("", "", &JsonSchema::default()),
+                },
+                _ => ("", "", &JsonSchema::default()),
+            };
+            if open.is_empty() {
+                prompt.push_str(&format!(
+                    "{}\"{}\": <{}>{}{}\n",
+                    indent,
+                    field.name,
+                    self.type_to_string(&field.field_type),
+                    comma,
+                    note
+                ));
+            } else {
+                prompt.push_str(&format!("{}\"{}\": {}{}\n", indent, field.name, open, note));
+                self.push_format_fields(prompt, nested, depth + 1);
+                prompt.push_str(&format!("{}{}{}\n", indent, close, comma));
+            }
+        }
+    }
+
+    // The YAML counterpart of `push_format_fields`; list items start with `- `
+    fn push_yaml_fields(&self, prompt: &mut String, schema: &JsonSchema, indent: &str) {
+        let fields = schema.required_fields.iter().map(|f| (f, "REQUIRED")).chain(schema.optional_fields.iter().map(|f| (f, "OPTIONAL")));
+        for (field, requirement) in fields {
+            let note = format!("  # {}", Self::field_note(field, requirement));
+            match &field.field_type {
+                JsonFieldType::Object(nested) if !nested.is_empty() => {
+                    prompt.push_str(&format!("{}{}:{}\n", indent, field.name, note));
+                    self.push_yaml_fields(prompt, nested, &format!("{}  ", indent));
+                }
+                JsonFieldType::Array(element_type) if matches!(element_type.as_ref(), JsonFieldType::Object(nested) if !nested.is_empty()) => {
+                    let JsonFieldType::Object(nested) = element_type.as_ref() else { unreachable!() };
+                    prompt.push_str(&format!("{}{}:{}\n", indent, field.name, note));
+                    let item_indent = format!("{}    ", indent);
+                    let mut item = String::new();
+                    self.push_yaml_fields(&mut item, nested, &item_indent);
+                    prompt.push_str(&item.replacen(&item_indent, &format!("{}  - ", indent), 1));
+                    prompt.push_str(&format!("{}  - ...\n", indent));
+                }
+                field_type => {
+                    prompt.push_str(&format!("{}{}: <{}>{}\n", indent, field.name, self.type_to_string(field_type), note));
+                }
+            }
+        }
+    }
+
+    // "REQUIRED - description (constraints)" for a field's line in the format prompt
+    fn field_note(field: &JsonField, requirement: &str) -> String {
+        format!(
+            "{}{}{}",
+            requirement,
+            field.description.as_ref().map(|d| format!(" - {}", d)).unwrap_or_default(),
+            field.constraints.describe().map(|c| format!(" ({})", c)).unwrap_or_default()
+        )
+    }
+
+    // Build a standard JSON Schema document describing the expected output (JSON tasks only)
+    pub fn to_json_schema(&self) -> Option<Value> {
+        match &self.output_format {
+            OutputFormat::Text
+            | OutputFormat::Code { .. }
+            | OutputFormat::Diff { .. }
+            | OutputFormat::Markdown { .. }
+            | OutputFormat::Yaml { .. } => None,
+            OutputFormat::Json { schema, strict } => Some(Self::object_schema(schema, *strict)),
+            OutputFormat::Schema { schema, .. } => Some(schema.clone()),
+        }
+    }
+
+    // Response format asking the provider to enforce the output schema (JSON tasks only).
+    // Strict enforcement is only requested when every field is required, as providers like
+    // OpenAI reject strict schemas with optional properties.
+    pub fn response_format(&self) -> Option<ResponseFormat> {
+        match &self.output_format {
+            OutputFormat::Text
+            | OutputFormat::Code { .. }
+            | OutputFormat::Diff { .. }
+            | OutputFormat::Markdown { .. }
+            | OutputFormat::Yaml { .. } => None,
+            OutputFormat::Json { schema, strict } => Some(ResponseFormat::JsonSchema {
+                name: "task_output".to_string(),
+                schema: self.to_json_schema()?,
+                strict: Some(*strict && schema.supports_strict()),
+            }),
+            // Derived schemas rarely meet strict mode's rules (every property required)
+            OutputFormat::Schema { name, schema } => {
+                Some(ResponseFormat::JsonSchema { name: name.clone(), schema: schema.clone(), strict: None })
+            }
+        }
+    }
+
+    // Synthetic tool whose parameters are the task's output schema. Text tasks take a
+    // single `answer` string, and non-object schemas a single `value`.
+    pub fn final_answer_tool(&self) -> Tool {
+        let (properties, required) = match self.to_json_schema() {
+            Some(schema) if !Self::is_object_schema(&schema) => {
+                let mut properties = Map::new();
+                properties.insert("value".to_string(), schema);
+                (properties, vec!["value".to_string()])
+            }
+            Some(schema) => (
+                schema["properties"].as_object().cloned().unwrap_or_default(),
+                schema["required"]
+                    .as_array()
+                    .map(|fields| fields.iter().filter_map(|f| f.as_str().map(String::from)).collect())
+                    .unwrap_or_default(),
+            ),
+            None => {
+                let mut properties = Map::new();
+                properties.insert(
+                    "answer".to_string(),
+                    json!({ "type": "string", "description": "The complete final answer" }),
+                );
+                (properties, vec!["answer".to_string()])
+            }
+        };
+
+        Tool {
+            name: FINAL_ANSWER_TOOL.to_string(),
+            description: "Deliver your final answer for the task. Call this exactly once, when you are done.".to_string(),
+            parameters: merco_llmproxy::JsonSchema {
+                schema_type: "object".to_string(),
+                properties: Some(properties),
+                required: Some(required),
+            },
+        }
+    }
+
+    // Convert the arguments of a `final_answer` call into the task result
+    pub fn output_from_final_answer(&self, arguments: &str) -> Result<String> {
+        match &self.output_format {
+            OutputFormat::Text
+            | OutputFormat::Code { .. }
+            | OutputFormat::Diff { .. }
+            | OutputFormat::Markdown { .. }
+            | OutputFormat::Yaml { .. } => {
+                let parsed: Value = serde_json::from_str(arguments)
+                    .map_err(|e| anyhow!("final_answer arguments are not valid JSON: {}", e))?;
+                parsed["answer"]
+                    .as_str()
+                    .map(String::from)
+                    .ok_or_else(|| anyhow!("final_answer call is missing the 'answer' string"))
+            }
+            OutputFormat::Json { .. } => Ok(arguments.trim().to_string()),
+            OutputFormat::Schema { schema, .. } if Self::is_object_schema(schema) => Ok(arguments.trim().to_string()),
+            OutputFormat::Schema { .. } => {
+                let parsed: Value = serde_json::from_str(arguments)
+                    .map_err(|e| anyhow!("final_answer arguments are not valid JSON: {}", e))?;
+                parsed
+                    .get("value")
+                    .map(Value::to_string)
+                    .ok_or_else(|| anyhow!("final_answer call is missing the 'value' field"))
+            }
+        }
+    }
+
+    fn is_object_schema(schema: &Value) -> bool {
+        schema.get("type").and_then(Value::as_str) == Some("object")
+    }
+
+    // JSON Schema of an object with the given fields; free-form objects have no properties
+    fn object_schema(schema: &JsonSchema, strict: bool) -> Value {
+        if schema.is_empty() {
+            return json!({ "type": "object" });
+        }
+        let mut properties = Map::new();
+        for field in schema.fields() {
+            let mut field_schema = Self::field_type_schema(&field.field_type, strict);
+            field.constraints.apply_to_schema(&mut field_schema, matches!(field.field_type, JsonFieldType::Array(_)));
+            if let (Some(description), Some(obj)) = (&field.description, field_schema.as_object_mut()) {
+                obj.insert("description".to_string(), json!(description));
+            }
+            properties.insert(field.name.clone(), field_schema);
+        }
+        let required: Vec<&String> = schema.required_fields.iter().map(|f| &f.name).collect();
+
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": !strict,
+        })
+    }
+
+    // Helper to convert JsonFieldType to a JSON Schema fragment
+    fn field_type_schema(field_type: &JsonFieldType, strict: bool) -> Value {
+        match field_type {
+            JsonFieldType::String => json!({ "type": "string" }),
+            JsonFieldType::Number => json!({ "type": "number" }),
+            JsonFieldType::Boolean => json!({ "type": "boolean" }),
+            JsonFieldType::Array(element_type) => json!({
+                "type": "array",
+                "items": Self::field_type_schema(element_type, strict),
+            }),
+            JsonFieldType::Object(schema) => Self::object_schema(schema, strict),
         }
     }
 
@@ -241,7 +1150,155 @@ impl Task {
             JsonFieldType::Number => "number".to_string(),
             JsonFieldType::Boolean => "boolean".to_string(),
             JsonFieldType::Array(element_type) => format!("array of {}", self.type_to_string(element_type)),
-            JsonFieldType::Object => "object".to_string(),
+            JsonFieldType::Object(_) => "object".to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(tool: &Tool) -> Vec<String> {
+        tool.parameters.properties.as_ref().unwrap().keys().cloned().collect()
+    }
+
+    #[test]
+    fn test_final_answer_tool_for_text_output() {
+        let task = Task::new("Name the capital of France".to_string(), None);
+        let tool = task.final_answer_tool();
+
+        assert_eq!(tool.name, FINAL_ANSWER_TOOL);
+        assert_eq!(properties(&tool), ["answer"]);
+        assert_eq!(tool.parameters.required, Some(vec!["answer".to_string()]));
+        assert_eq!(task.output_from_final_answer(r#"{"answer": "Paris"}"#).unwrap(), "Paris");
+        assert!(task.output_from_final_answer(r#"{"value": "Paris"}"#).is_err());
+        assert!(task.output_from_final_answer("Paris").is_err());
+    }
+
+    #[test]
+    fn test_final_answer_tool_for_json_output() {
+        let task = Task::new_with_json_output(
+            "Describe France".to_string(),
+            None,
+            vec![JsonField::new("capital", JsonFieldType::String)],
+            vec![JsonField::new("population", JsonFieldType::Number)],
+            true,
+        );
+        let tool = task.final_answer_tool();
+
+        let mut fields = properties(&tool);
+        fields.sort();
+        assert_eq!(fields, ["capital", "population"]);
+        assert_eq!(tool.parameters.required, Some(vec!["capital".to_string()]));
+        let arguments = r#" {"capital": "Paris"} "#;
+        assert_eq!(task.output_from_final_answer(arguments).unwrap(), arguments.trim());
+    }
+
+    #[test]
+    fn test_final_answer_tool_wraps_non_object_schemas() {
+        let schema = json!({ "type": "array", "items": { "type": "string" } });
+        let task = Task::new_with_schema_output("List capitals".to_string(), None, "capitals", schema);
+        let tool = task.final_answer_tool();
+
+        assert_eq!(properties(&tool), ["value"]);
+        assert_eq!(tool.parameters.required, Some(vec!["value".to_string()]));
+        let output = task.output_from_final_answer(r#"{"value": ["Paris", "Rome"]}"#).unwrap();
+        assert_eq!(output, r#"["Paris","Rome"]"#);
+    }
+
+    fn constrained(field_type: JsonFieldType, constraints: FieldConstraints) -> Task {
+        let field = JsonField::new("value", field_type).with_constraints(constraints);
+        Task::new_with_json_output("Produce a value".to_string(), None, vec![field], Vec::new(), false)
+    }
+
+    #[test]
+    fn test_pattern_constraint() {
+        let task = constrained(JsonFieldType::String, FieldConstraints::new().with_pattern(r"^[A-Z]{3}$").unwrap());
+
+        assert!(task.validate_output(r#"{"value": "EUR"}"#).is_ok());
+        let error = task.validate_output(r#"{"value": "euro"}"#).unwrap_err().to_string();
+        assert!(error.contains("must match the pattern ^[A-Z]{3}$"), "{}", error);
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_rejected_up_front() {
+        assert!(FieldConstraints::new().with_pattern("[unclosed").is_err());
+        let error = serde_json::from_str::<FieldConstraints>(r#"{"pattern": "(a"}"#).unwrap_err();
+        assert!(error.to_string().contains("regex parse error"), "{}", error);
+
+        let constraints = FieldConstraints::new().with_pattern(r"^\d+$").unwrap();
+        let json = serde_json::to_string(&constraints).unwrap();
+        assert_eq!(json, r#"{"pattern":"^\\d+$"}"#);
+        assert_eq!(serde_json::from_str::<FieldConstraints>(&json).unwrap(), constraints);
+    }
+
+    #[test]
+    fn test_range_constraint() {
+        let task = constrained(JsonFieldType::Number, FieldConstraints::new().with_range(1.0, 5.0));
+
+        assert!(task.validate_output(r#"{"value": 1}"#).is_ok());
+        assert!(task.validate_output(r#"{"value": 5}"#).is_ok());
+        let error = task.validate_output(r#"{"value": 0.5}"#).unwrap_err().to_string();
+        assert!(error.contains("must be at least 1"), "{}", error);
+        let error = task.validate_output(r#"{"value": 6}"#).unwrap_err().to_string();
+        assert!(error.contains("must be at most 5"), "{}", error);
+    }
+
+    #[test]
+    fn test_length_constraint_applies_to_the_array_and_its_items() {
+        let constraints = FieldConstraints::new().with_items(Some(1), Some(2)).with_range(0.0, 10.0);
+        let task = constrained(JsonFieldType::Array(Box::new(JsonFieldType::Number)), constraints);
+
+        assert!(task.validate_output(r#"{"value": [1, 2]}"#).is_ok());
+        let error = task.validate_output(r#"{"value": []}"#).unwrap_err().to_string();
+        assert!(error.contains("at least 1 items"), "{}", error);
+        let error = task.validate_output(r#"{"value": [1, 2, 3]}"#).unwrap_err().to_string();
+        assert!(error.contains("at most 2 items"), "{}", error);
+        let error = task.validate_output(r#"{"value": [1, 11]}"#).unwrap_err().to_string();
+        assert!(error.contains("must be at most 10"), "{}", error);
+    }
+
+    #[test]
+    fn test_repairs_fenced_markdown_with_code_blocks() {
+        let sections = vec![MarkdownSection::new("Usage"), MarkdownSection::new("Notes")];
+        let task = Task::new_with_markdown_output("Document the CLI".to_string(), None, sections, true);
+        let document = "## Usage\n```bash\nmerco run\n```\n## Notes\nDone.";
+
+        assert!(task.validate_output(document).is_ok());
+        assert_eq!(task.repair_output(document), None);
+        assert_eq!(task.repair_output(&format!("```markdown\n{}\n```", document)).as_deref(), Some(document));
+        assert_eq!(task.repair_output("```markdown\n## Usage\nmerco run\n```"), None);
+    }
+
+    fn yaml_task() -> Task {
+        Task::new_with_yaml_output(
+            "Describe Ada".to_string(),
+            None,
+            vec![JsonField::new("name", JsonFieldType::String), JsonField::new("born", JsonFieldType::Number)],
+            Vec::new(),
+            false,
+        )
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_output() {
+        let task = yaml_task();
+
+        assert!(task.validate_output("name: Ada\nborn: 1815\n").is_ok());
+        assert_eq!(task.repair_output("```yaml\nname: Ada\nborn: 1815\n```").as_deref(), Some("name: Ada\nborn: 1815"));
+        let error = task.validate_output("name: [Ada\nborn: 1815").unwrap_err().to_string();
+        assert!(error.starts_with("Output is not valid YAML"), "{}", error);
+        let error = task.validate_output("- Ada\n- 1815").unwrap_err().to_string();
+        assert!(error.starts_with("YAML output must be a mapping"), "{}", error);
+        assert!(task.validate_output("name: Ada\nborn: eighteen").is_err());
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn test_yaml_output_needs_the_feature() {
+        let error = yaml_task().validate_output("name: Ada\nborn: 1815\n").unwrap_err();
+        assert!(error.to_string().contains("`yaml` feature"), "{}", error);
+    }
+}
@@ -0,0 +1,131 @@
+use serde_json::Value;
+use std::fmt;
+
+// Longest quote of an invalid value put into retry feedback
+const MAX_EXCERPT_CHARS: usize = 500;
+
+// Why a JSON output was rejected, located by the JSON Pointer of the offending value
+// ("" for the whole output), so the model can repair that part instead of starting over.
+// Validators return it inside `anyhow::Error`; `downcast_ref` recovers it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+    pub value: Option<Value>, // The offending value, or the object missing a required field
+}
+
+impl ValidationError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { path: path.into(), message: message.into(), value: None }
+    }
+
+    pub fn with_value(mut self, value: &Value) -> Self {
+        self.value = Some(value.clone());
+        self
+    }
+
+    // The offending value as compact JSON, shortened if it is long
+    pub fn excerpt(&self) -> Option<String> {
+        let text = self.value.as_ref()?.to_string();
+        if text.chars().count() <= MAX_EXCERPT_CHARS {
+            return Some(text);
+        }
+        Some(format!("{}...", text.chars().take(MAX_EXCERPT_CHARS).collect::<String>()))
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+// Message asking the model to correct an invalid response. Located errors point at the
+// part to fix (quoting it when `quote_value` is set); others just describe the problem.
+pub fn retry_feedback(error: &anyhow::Error, quote_value: bool) -> String {
+    let Some(located) = error.downcast_ref::<ValidationError>().filter(|e| !e.path.is_empty()) else {
+        return format!(
+            "Your previous response was invalid: {}. Please provide a corrected response that follows the required format exactly.",
+            error
+        );
+    };
+    let mut feedback = format!("Your previous response was invalid at {}: {}.", located.path, located.message);
+    if quote_value && let Some(excerpt) = located.excerpt() {
+        feedback.push_str(&format!("\nThe invalid part was: {}", excerpt));
+    }
+    feedback.push_str(
+        "\nFix that part, and any other with the same problem, keeping the rest unchanged. Reply with the complete corrected response in the required format.",
+    );
+    feedback
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::task::{JsonField, JsonFieldType, JsonSchema, Task};
+
+    fn profile_task() -> Task {
+        let address = JsonSchema::new(vec![JsonField::new("city", JsonFieldType::String)], Vec::new());
+        Task::new_with_json_output(
+            "Describe a user".to_string(),
+            None,
+            vec![
+                JsonField::new("address", JsonFieldType::Object(address)),
+                JsonField::new("tags", JsonFieldType::Array(Box::new(JsonFieldType::String))),
+            ],
+            Vec::new(),
+            false,
+        )
+    }
+
+    fn located(error: &anyhow::Error) -> &ValidationError {
+        error.downcast_ref::<ValidationError>().expect("a located validation error")
+    }
+
+    #[test]
+    fn test_errors_point_at_nested_fields_and_array_items() {
+        let task = profile_task();
+
+        let error = task.validate_output(r#"{"address": {"city": 7}, "tags": []}"#).unwrap_err();
+        assert_eq!(located(&error).path, "/address/city");
+        assert_eq!(located(&error).value, Some(serde_json::json!(7)));
+
+        let error = task.validate_output(r#"{"address": {}, "tags": []}"#).unwrap_err();
+        assert_eq!(located(&error).path, "/address/city");
+        assert!(error.to_string().contains("'/address/city'"), "{}", error);
+
+        let error = task.validate_output(r#"{"address": {"city": "Oslo"}, "tags": ["a", 2]}"#).unwrap_err();
+        assert_eq!(located(&error).path, "/tags/1");
+    }
+
+    #[test]
+    fn test_retry_feedback_for_located_errors() {
+        let error = profile_task().validate_output(r#"{"address": {"city": 7}, "tags": []}"#).unwrap_err();
+
+        let feedback = retry_feedback(&error, true);
+        assert!(feedback.starts_with(&format!("Your previous response was invalid at /address/city: {}.", error)), "{}", feedback);
+        assert!(feedback.contains("\nThe invalid part was: 7\n"), "{}", feedback);
+        assert!(feedback.ends_with("Reply with the complete corrected response in the required format."), "{}", feedback);
+        assert!(!retry_feedback(&error, false).contains("The invalid part was"));
+    }
+
+    #[test]
+    fn test_retry_feedback_for_other_errors() {
+        let error = anyhow::anyhow!("Output is empty");
+        assert_eq!(
+            retry_feedback(&error, true),
+            "Your previous response was invalid: Output is empty. Please provide a corrected response that follows the required format exactly."
+        );
+    }
+
+    #[test]
+    fn test_excerpt_is_shortened() {
+        let error = ValidationError::new("/text", "Too long").with_value(&Value::String("x".repeat(1000)));
+        let excerpt = error.excerpt().unwrap();
+        assert_eq!(excerpt.chars().count(), MAX_EXCERPT_CHARS + 3);
+        assert!(excerpt.ends_with("..."));
+        assert_eq!(ValidationError::new("", "Bad").excerpt(), None);
+    }
+}
@@ -0,0 +1,354 @@
+// UniFFI bindings for Swift, Kotlin and other foreign hosts. An `Assistant` is one agent
+// with a fixed provider configuration; runs can be awaited from the host's async code,
+// called blocking from a background thread, or observed through an `EventListener`.
+// Generate bindings with `uniffi-bindgen generate --library <libmerco_ffi> --language swift`.
+
+use futures::StreamExt;
+use merco_agents::agent::agent::{Agent, AgentLLMConfig};
+use merco_agents::agent::sampling::SamplingParams;
+use merco_agents::task::task::Task;
+use merco_llmproxy::{
+    CancellationToken, ChatMessage, CompletionRequest, LlmConfig, LlmProvider, ProgressSink, Provider,
+    StreamContentDelta, ToolProgress, get_provider, get_tools_by_names,
+};
+use std::sync::{Arc, Mutex, OnceLock};
+
+uniffi::setup_scaffolding!();
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum MercoError {
+    #[error("Invalid configuration: {message}")]
+    Config { message: String },
+    #[error("Run failed: {message}")]
+    Failed { message: String },
+    #[error("Run was cancelled")]
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, uniffi::Enum)]
+pub enum ProviderKind {
+    OpenAi,
+    Ollama,
+    Anthropic,
+    // Any OpenAI-compatible server, e.g. a local llama.cpp or vLLM instance
+    Custom,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ProviderConfig {
+    pub provider: ProviderKind,
+    pub model: String,
+    #[uniffi(default = None)]
+    pub api_key: Option<String>,
+    #[uniffi(default = None)]
+    pub base_url: Option<String>,
+    #[uniffi(default = None)]
+    pub temperature: Option<f32>,
+    #[uniffi(default = None)]
+    pub max_tokens: Option<u32>,
+}
+
+impl ProviderConfig {
+    fn llm_config(&self) -> LlmConfig {
+        let provider = match self.provider {
+            ProviderKind::OpenAi => Provider::OpenAI,
+            ProviderKind::Ollama => Provider::Ollama,
+            ProviderKind::Anthropic => Provider::Anthropic,
+            ProviderKind::Custom => Provider::Custom,
+        };
+        let mut config = LlmConfig::new(provider);
+        if let Some(api_key) = &self.api_key {
+            config = config.with_api_key(api_key.clone());
+        }
+        if let Some(base_url) = &self.base_url {
+            config = config.with_base_url(base_url.clone());
+        }
+        config
+    }
+
+    fn sampling(&self) -> SamplingParams {
+        SamplingParams { temperature: self.temperature, max_tokens: self.max_tokens, ..Default::default() }
+    }
+}
+
+// What happens during a run, in order: `Started`, any number of deltas and progress
+// updates, then exactly one of `Completed`, `Failed` or `Cancelled`
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum AssistantEvent {
+    Started,
+    TextDelta { text: String },
+    ToolProgress { tool: String, fraction: Option<f32>, message: Option<String> },
+    Completed { output: String },
+    Failed { message: String },
+    Cancelled,
+}
+
+// Implemented by the host to observe runs. Called from a Rust worker thread, so UI
+// code must hop to the main thread itself.
+#[uniffi::export(with_foreign)]
+pub trait EventListener: Send + Sync {
+    fn on_event(&self, event: AssistantEvent);
+}
+
+// Blocking calls run here; async calls use the runtime of the UniFFI executor
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| tokio::runtime::Runtime::new().expect("failed to start the tokio runtime"))
+}
+
+#[derive(uniffi::Object)]
+pub struct Assistant {
+    config: ProviderConfig,
+    backstory: String,
+    goals: Vec<String>,
+    tools: Vec<String>,
+    // Shared by all runs in flight; replaced with a fresh token after `cancel`
+    cancellation: Mutex<CancellationToken>,
+    // Used instead of a provider built from `config` when set
+    provider: Mutex<Option<Arc<dyn LlmProvider>>>,
+}
+
+impl Assistant {
+    // Answer with `provider` instead of the one `config` describes, e.g. a `MockProvider`
+    // in tests. Rust-only: foreign hosts always go through `ProviderConfig`.
+    pub fn set_provider(&self, provider: Arc<dyn LlmProvider>) {
+        *self.provider.lock().unwrap() = Some(provider);
+    }
+
+    fn token(&self) -> CancellationToken {
+        self.cancellation.lock().unwrap().clone()
+    }
+
+    fn provider(&self) -> Result<Arc<dyn LlmProvider>, MercoError> {
+        match self.provider.lock().unwrap().clone() {
+            Some(provider) => Ok(provider),
+            None => get_provider(self.config.llm_config()).map_err(|e| MercoError::Config { message: e.to_string() }),
+        }
+    }
+
+    fn agent(&self, token: CancellationToken) -> Result<Agent, MercoError> {
+        let names: Vec<&str> = self.tools.iter().map(String::as_str).collect();
+        let llm_config = AgentLLMConfig::with_defaults(self.config.llm_config(), self.config.model.clone(), self.config.sampling());
+        let agent = Agent::new(llm_config, self.backstory.clone(), self.goals.clone(), get_tools_by_names(&names))
+            .with_provider(self.provider()?)
+            .with_cancellation(token);
+        Ok(agent)
+    }
+
+    async fn run_agent(&self, prompt: String, progress: Option<ProgressSink>) -> Result<String, MercoError> {
+        let token = self.token();
+        let mut agent = self.agent(token.clone())?;
+        if let Some(sink) = progress {
+            agent = agent.with_tool_progress(sink);
+        }
+        match agent.call(Task::new(prompt, None)).await {
+            Ok(output) => Ok(output.text),
+            Err(_) if token.is_cancelled() => Err(MercoError::Cancelled),
+            Err(error) => Err(MercoError::Failed { message: error.to_string() }),
+        }
+    }
+
+    async fn stream_reply(&self, prompt: String, listener: &dyn EventListener) -> Result<String, MercoError> {
+        let token = self.token();
+        let provider = self.provider()?;
+        let request = CompletionRequest {
+            model: self.config.model.clone(),
+            messages: vec![ChatMessage::system(self.backstory.clone()), ChatMessage::user(prompt)],
+            temperature: self.config.temperature,
+            max_tokens: self.config.max_tokens,
+            ..Default::default()
+        };
+        let failed = |e: merco_llmproxy::ProviderError| match token.is_cancelled() {
+            true => MercoError::Cancelled,
+            false => MercoError::Failed { message: e.to_string() },
+        };
+        let mut stream = provider.completion_stream_with_cancellation(request, &token).await.map_err(failed)?;
+        let mut output = String::new();
+        while let Some(chunk) = stream.next().await {
+            if let StreamContentDelta::Text(text) = chunk.map_err(failed)?.delta {
+                output.push_str(&text);
+                listener.on_event(AssistantEvent::TextDelta { text });
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn finish(listener: &dyn EventListener, result: &Result<String, MercoError>) {
+    listener.on_event(match result {
+        Ok(output) => AssistantEvent::Completed { output: output.clone() },
+        Err(MercoError::Cancelled) => AssistantEvent::Cancelled,
+        Err(error) => AssistantEvent::Failed { message: error.to_string() },
+    });
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Assistant {
+    // `tools` are names of tools registered in Rust with `#[merco_tool]`
+    #[uniffi::constructor(default(goals = [], tools = []))]
+    pub fn new(
+        config: ProviderConfig,
+        backstory: String,
+        goals: Vec<String>,
+        tools: Vec<String>,
+    ) -> Result<Arc<Self>, MercoError> {
+        // Agent::new panics on an unusable config, so check it here once
+        get_provider(config.llm_config()).map_err(|e| MercoError::Config { message: e.to_string() })?;
+        let names: Vec<&str> = tools.iter().map(String::as_str).collect();
+        if get_tools_by_names(&names).len() != names.len() {
+            return Err(MercoError::Config { message: format!("Unknown tools among {:?}", tools) });
+        }
+        Ok(Arc::new(Self {
+            config,
+            backstory,
+            goals,
+            tools,
+            cancellation: Mutex::new(CancellationToken::new()),
+            provider: Mutex::new(None),
+        }))
+    }
+
+    // Run the agent on `prompt` and return its answer
+    pub async fn run(&self, prompt: String) -> Result<String, MercoError> {
+        self.run_agent(prompt, None).await
+    }
+
+    // Blocking variant of `run`; never call it from the main thread
+    pub fn run_blocking(&self, prompt: String) -> Result<String, MercoError> {
+        runtime().block_on(self.run_agent(prompt, None))
+    }
+
+    // Like `run`, reporting tool progress and the outcome to `listener`
+    pub async fn run_with_events(
+        &self,
+        prompt: String,
+        listener: Arc<dyn EventListener>,
+    ) -> Result<String, MercoError> {
+        listener.on_event(AssistantEvent::Started);
+        let progress_listener = listener.clone();
+        let sink: ProgressSink = Arc::new(move |progress: &ToolProgress| {
+            progress_listener.on_event(AssistantEvent::ToolProgress {
+                tool: progress.tool.clone(),
+                fraction: progress.fraction,
+                message: progress.message.clone(),
+            });
+        });
+        let result = self.run_agent(prompt, Some(sink)).await;
+        finish(listener.as_ref(), &result);
+        result
+    }
+
+    // Single-turn chat that streams the reply to `listener` as `TextDelta`s. Tools and
+    // goals are not used; the backstory is the system prompt.
+    pub async fn chat(&self, prompt: String, listener: Arc<dyn EventListener>) -> Result<String, MercoError> {
+        listener.on_event(AssistantEvent::Started);
+        let result = self.stream_reply(prompt, listener.as_ref()).await;
+        finish(listener.as_ref(), &result);
+        result
+    }
+
+    // Cancel every run in flight; later runs are unaffected
+    pub fn cancel(&self) {
+        let mut token = self.cancellation.lock().unwrap();
+        token.cancel();
+        *token = CancellationToken::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merco_llmproxy::MockProvider;
+
+    fn config() -> ProviderConfig {
+        ProviderConfig {
+            provider: ProviderKind::Ollama,
+            model: "llama3.2".to_string(),
+            api_key: None,
+            base_url: None,
+            temperature: Some(0.3),
+            max_tokens: Some(64),
+        }
+    }
+
+    fn assistant(provider: &Arc<MockProvider>) -> Arc<Assistant> {
+        let assistant = Assistant::new(config(), "You are a geographer.".to_string(), Vec::new(), Vec::new()).unwrap();
+        assistant.set_provider(provider.clone());
+        assistant
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<AssistantEvent>>);
+
+    impl EventListener for Recorder {
+        fn on_event(&self, event: AssistantEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[test]
+    fn test_constructor_rejects_bad_configs() {
+        let anthropic = ProviderConfig { provider: ProviderKind::Anthropic, ..config() };
+        let error = Assistant::new(anthropic, String::new(), Vec::new(), Vec::new()).err().unwrap();
+        assert!(matches!(error, MercoError::Config { .. }), "{:?}", error);
+
+        let tools = vec!["no_such_tool".to_string()];
+        let error = Assistant::new(config(), String::new(), Vec::new(), tools).err().unwrap();
+        assert!(error.to_string().contains("Unknown tools"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_run_uses_the_config() {
+        let provider = Arc::new(MockProvider::new().with_message("Paris"));
+        let output = assistant(&provider).run("Capital of France?".to_string()).await.unwrap();
+
+        assert_eq!(output, "Paris");
+        let request = &provider.requests()[0];
+        assert_eq!(request.model, "llama3.2");
+        assert_eq!(request.temperature, Some(0.3));
+        assert_eq!(request.max_tokens, Some(64));
+    }
+
+    #[test]
+    fn test_run_blocking() {
+        let provider = Arc::new(MockProvider::new().with_message("Rome"));
+        assert_eq!(assistant(&provider).run_blocking("Capital of Italy?".to_string()).unwrap(), "Rome");
+    }
+
+    #[tokio::test]
+    async fn test_events_report_the_outcome() {
+        let failure = merco_llmproxy::ProviderError::ConfigError("bad key".to_string());
+        let provider = Arc::new(MockProvider::new().with_message("Berlin").with_error(failure));
+        let assistant = assistant(&provider);
+
+        let recorder = Arc::new(Recorder::default());
+        assistant.run_with_events("Capital of Germany?".to_string(), recorder.clone()).await.unwrap();
+        let events = recorder.0.lock().unwrap().clone();
+        assert!(matches!(&events[..], [AssistantEvent::Started, AssistantEvent::Completed { output }] if output == "Berlin"), "{:?}", events);
+
+        let recorder = Arc::new(Recorder::default());
+        assert!(assistant.run_with_events("Again?".to_string(), recorder.clone()).await.is_err());
+        let events = recorder.0.lock().unwrap().clone();
+        assert!(matches!(events.last(), Some(AssistantEvent::Failed { .. })), "{:?}", events);
+    }
+
+    #[tokio::test]
+    async fn test_chat_streams_text_deltas() {
+        let provider = Arc::new(MockProvider::new().with_message("Madrid is the capital"));
+        let recorder = Arc::new(Recorder::default());
+        let output = assistant(&provider).chat("Capital of Spain?".to_string(), recorder.clone()).await.unwrap();
+
+        assert_eq!(output, "Madrid is the capital");
+        let events = recorder.0.lock().unwrap().clone();
+        let deltas: Vec<String> = events
+            .iter()
+            .filter_map(|event| match event {
+                AssistantEvent::TextDelta { text } => Some(text.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deltas, ["Madrid ", "is ", "the ", "capital"]);
+        assert!(matches!(events.last(), Some(AssistantEvent::Completed { .. })));
+        assert_eq!(provider.requests()[0].messages[0].content.as_deref(), Some("You are a geographer."));
+    }
+}
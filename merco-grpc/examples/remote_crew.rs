@@ -0,0 +1,30 @@
+// Run a crew definition on a merco-grpc server and follow its events:
+//   cargo run --example remote_crew -- crew.json [http://127.0.0.1:50051]
+use merco_grpc::CrewClient;
+use merco_grpc::pb::run_event::Event;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().ok_or_else(|| anyhow::anyhow!("Usage: remote_crew <crew.json> [endpoint]"))?;
+    let endpoint = args.next().unwrap_or_else(|| "http://127.0.0.1:50051".to_string());
+
+    let mut client = CrewClient::connect(endpoint).await?;
+    let run_id = client.kickoff_json(std::fs::read_to_string(path)?, None).await?;
+    println!("Started {}", run_id);
+
+    let mut events = client.events(&run_id).await?;
+    while let Some(event) = events.message().await? {
+        match event.event {
+            Some(Event::ToolProgress(progress)) => println!("[{}] {:?}", progress.tool, progress.message),
+            Some(Event::Completed(completed)) => println!("Completed: {}", completed.final_output),
+            Some(Event::Failed(failed)) => println!("Failed: {}", failed.error),
+            Some(Event::Cancelled(_)) => println!("Cancelled"),
+            Some(Event::Started(_)) | None => {}
+        }
+    }
+
+    let result = client.result(&run_id, true).await?;
+    println!("Status: {:?}", result.status());
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use merco_grpc::CrewServer;
+use std::net::SocketAddr;
+
+const USAGE: &str = "Usage: merco-grpc [--addr <host:port>]   (default 127.0.0.1:50051)";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // API keys are referenced by environment variable name in definitions
+    dotenv::dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let addr: SocketAddr = match args.as_slice() {
+        [] => "127.0.0.1:50051".parse()?,
+        ["--addr", addr] => addr.parse()?,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    eprintln!("Serving merco.v1.CrewService on {}", addr);
+    CrewServer::new()
+        .serve(addr, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
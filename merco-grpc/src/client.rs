@@ -0,0 +1,53 @@
+use crate::pb::crew_service_client::CrewServiceClient;
+use crate::pb::{CancelRequest, GetResultRequest, KickoffRequest, RunEvent, RunResult, StreamEventsRequest};
+use merco_agents::definition::definition::{CrewDefinition, to_canonical_json};
+use tonic::transport::Channel;
+use tonic::{Status, Streaming};
+
+// Convenience wrapper around the generated client
+#[derive(Debug, Clone)]
+pub struct CrewClient {
+    inner: CrewServiceClient<Channel>,
+}
+
+impl CrewClient {
+    // `endpoint` is a URL such as "http://127.0.0.1:50051"
+    pub async fn connect(endpoint: impl Into<String>) -> Result<Self, tonic::transport::Error> {
+        let inner = CrewServiceClient::connect(endpoint.into()).await?;
+        Ok(Self { inner })
+    }
+
+    // Start `crew` on the server and return its run id
+    pub async fn kickoff(&mut self, crew: &CrewDefinition) -> Result<String, Status> {
+        let crew_json = to_canonical_json(crew).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.kickoff_json(crew_json, None).await
+    }
+
+    // Start a crew from its definition document, optionally under a chosen run id
+    pub async fn kickoff_json(&mut self, crew_json: String, run_id: Option<String>) -> Result<String, Status> {
+        let request = KickoffRequest { crew_json, run_id: run_id.unwrap_or_default() };
+        Ok(self.inner.kickoff(request).await?.into_inner().run_id)
+    }
+
+    // All events of the run so far, then new ones until it finishes
+    pub async fn events(&mut self, run_id: impl Into<String>) -> Result<Streaming<RunEvent>, Status> {
+        let request = StreamEventsRequest { run_id: run_id.into() };
+        Ok(self.inner.stream_events(request).await?.into_inner())
+    }
+
+    pub async fn cancel(&mut self, run_id: impl Into<String>) -> Result<(), Status> {
+        self.inner.cancel(CancelRequest { run_id: run_id.into() }).await?;
+        Ok(())
+    }
+
+    // The run's current status, or its final result if `wait` is set
+    pub async fn result(&mut self, run_id: impl Into<String>, wait: bool) -> Result<RunResult, Status> {
+        let request = GetResultRequest { run_id: run_id.into(), wait };
+        Ok(self.inner.get_result(request).await?.into_inner())
+    }
+
+    // The generated client, for calls the wrapper does not cover
+    pub fn inner(&mut self) -> &mut CrewServiceClient<Channel> {
+        &mut self.inner
+    }
+}
@@ -0,0 +1,13 @@
+// gRPC front end for running crews on a remote host; see `proto/merco.proto`. The
+// server keeps finished runs for a while (see `CrewServer::with_result_ttl`), so
+// clients can reconnect to stream events or fetch results later.
+
+pub mod client;
+pub mod server;
+
+pub mod pb {
+    tonic::include_proto!("merco.v1");
+}
+
+pub use client::CrewClient;
+pub use server::CrewServer;
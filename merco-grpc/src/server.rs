@@ -0,0 +1,319 @@
+use crate::pb::crew_service_server::{CrewService, CrewServiceServer};
+use crate::pb::{
+    CancelRequest, CancelResponse, Cancelled, Completed, Failed, GetResultRequest, KickoffRequest, KickoffResponse,
+    RunEvent, RunResult, RunStatus, Started, StreamEventsRequest, ToolProgress, run_event,
+};
+use merco_agents::crew::crew::CrewOutput;
+use merco_agents::definition::definition::{CrewDefinition, from_json};
+use merco_agents::session::session::new_run_id;
+use merco_llmproxy::{CancellationToken, ProgressSink};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{Request, Response, Status};
+
+// How long a finished run's events and result stay available
+pub const DEFAULT_RESULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+// Everything the server remembers about one run. Finished runs are kept for the
+// server's result TTL, so late clients can still read their events and result.
+struct Run {
+    token: CancellationToken,
+    history: Vec<RunEvent>,
+    // Open `StreamEvents` calls; dropped after the final event, which ends them
+    subscribers: Vec<mpsc::UnboundedSender<Result<RunEvent, Status>>>,
+    result: watch::Sender<RunResult>,
+}
+
+#[derive(Clone)]
+pub struct CrewServer {
+    runs: Arc<Mutex<HashMap<String, Run>>>,
+    result_ttl: Duration,
+}
+
+impl Default for CrewServer {
+    fn default() -> Self {
+        Self { runs: Arc::default(), result_ttl: DEFAULT_RESULT_TTL }
+    }
+}
+
+impl CrewServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Forget finished runs this long after they finish; later calls for them get NOT_FOUND
+    pub fn with_result_ttl(mut self, ttl: Duration) -> Self {
+        self.result_ttl = ttl;
+        self
+    }
+
+    pub fn into_service(self) -> CrewServiceServer<Self> {
+        CrewServiceServer::new(self)
+    }
+
+    // Serve on `addr` until `shutdown` resolves, then cancel the runs still going
+    pub async fn serve(self, addr: SocketAddr, shutdown: impl Future<Output = ()>) -> Result<(), tonic::transport::Error> {
+        let runs = self.runs.clone();
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve_with_shutdown(addr, shutdown)
+            .await?;
+        runs.lock().unwrap().values().for_each(|run| run.token.cancel());
+        Ok(())
+    }
+
+    fn emit(&self, run_id: &str, event: run_event::Event) {
+        let event = RunEvent { run_id: run_id.to_string(), event: Some(event) };
+        let mut runs = self.runs.lock().unwrap();
+        let Some(run) = runs.get_mut(run_id) else { return };
+        run.subscribers.retain(|subscriber| subscriber.send(Ok(event.clone())).is_ok());
+        run.history.push(event);
+    }
+
+    fn finish(&self, run_id: &str, output: Result<CrewOutput, String>) {
+        let cancelled = self.runs.lock().unwrap().get(run_id).is_some_and(|run| run.token.is_cancelled());
+        let mut result = RunResult { run_id: run_id.to_string(), ..Default::default() };
+        let event = match output {
+            Ok(output) => {
+                result.status = RunStatus::Completed.into();
+                result.task_outputs = output.task_outputs.clone();
+                result.final_output = output.final_output.clone();
+                run_event::Event::Completed(Completed {
+                    task_outputs: output.task_outputs,
+                    final_output: output.final_output,
+                })
+            }
+            Err(_) if cancelled => {
+                result.status = RunStatus::Cancelled.into();
+                run_event::Event::Cancelled(Cancelled {})
+            }
+            Err(error) => {
+                result.status = RunStatus::Failed.into();
+                result.error = error.clone();
+                run_event::Event::Failed(Failed { error })
+            }
+        };
+        self.emit(run_id, event);
+
+        let mut runs = self.runs.lock().unwrap();
+        if let Some(run) = runs.get_mut(run_id) {
+            run.subscribers.clear();
+            run.result.send_replace(result);
+        }
+        drop(runs);
+
+        // Kickoff rejects ids that are still known, so the entry removed is this run's
+        let runs = self.runs.clone();
+        let run_id = run_id.to_string();
+        let ttl = self.result_ttl;
+        tokio::spawn(async move {
+            tokio::time::sleep(ttl).await;
+            runs.lock().unwrap().remove(&run_id);
+        });
+    }
+
+    fn progress_sink(&self, run_id: String) -> ProgressSink {
+        let server = self.clone();
+        Arc::new(move |progress: &merco_llmproxy::ToolProgress| {
+            server.emit(
+                &run_id,
+                run_event::Event::ToolProgress(ToolProgress {
+                    tool: progress.tool.clone(),
+                    fraction: progress.fraction,
+                    message: progress.message.clone(),
+                }),
+            );
+        })
+    }
+}
+
+fn not_found(run_id: &str) -> Status {
+    Status::not_found(format!("No run with id '{}'", run_id))
+}
+
+#[tonic::async_trait]
+impl CrewService for CrewServer {
+    async fn kickoff(&self, request: Request<KickoffRequest>) -> Result<Response<KickoffResponse>, Status> {
+        let request = request.into_inner();
+        // Build before registering the run, so bad definitions fail the call itself
+        let crew = from_json::<CrewDefinition>(&request.crew_json)
+            .and_then(|definition| definition.build())
+            .map_err(|e| Status::invalid_argument(format!("Invalid crew definition: {:#}", e)))?;
+
+        let run_id = match request.run_id.trim() {
+            "" => new_run_id(),
+            id => id.to_string(),
+        };
+        let token = CancellationToken::new();
+        {
+            let mut runs = self.runs.lock().unwrap();
+            if runs.contains_key(&run_id) {
+                return Err(Status::already_exists(format!("A run with id '{}' already exists", run_id)));
+            }
+            let running = RunResult { run_id: run_id.clone(), status: RunStatus::Running.into(), ..Default::default() };
+            let run = Run { token: token.clone(), history: Vec::new(), subscribers: Vec::new(), result: watch::Sender::new(running) };
+            runs.insert(run_id.clone(), run);
+        }
+
+        let server = self.clone();
+        let id = run_id.clone();
+        tokio::spawn(async move {
+            server.emit(&id, run_event::Event::Started(Started {}));
+            let output = crew.with_cancellation(token).with_tool_progress(server.progress_sink(id.clone())).run().await;
+            server.finish(&id, output);
+        });
+        Ok(Response::new(KickoffResponse { run_id }))
+    }
+
+    type StreamEventsStream = UnboundedReceiverStream<Result<RunEvent, Status>>;
+
+    async fn stream_events(
+        &self,
+        request: Request<StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let run_id = request.into_inner().run_id;
+        let mut runs = self.runs.lock().unwrap();
+        let run = runs.get_mut(&run_id).ok_or_else(|| not_found(&run_id))?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        for event in &run.history {
+            let _ = sender.send(Ok(event.clone()));
+        }
+        // A finished run has no further events; dropping the sender ends the stream
+        if run.result.borrow().status() == RunStatus::Running {
+            run.subscribers.push(sender);
+        }
+        Ok(Response::new(UnboundedReceiverStream::new(receiver)))
+    }
+
+    async fn cancel(&self, request: Request<CancelRequest>) -> Result<Response<CancelResponse>, Status> {
+        let run_id = request.into_inner().run_id;
+        let runs = self.runs.lock().unwrap();
+        runs.get(&run_id).ok_or_else(|| not_found(&run_id))?.token.cancel();
+        Ok(Response::new(CancelResponse {}))
+    }
+
+    async fn get_result(&self, request: Request<GetResultRequest>) -> Result<Response<RunResult>, Status> {
+        let request = request.into_inner();
+        let mut result = {
+            let runs = self.runs.lock().unwrap();
+            runs.get(&request.run_id).ok_or_else(|| not_found(&request.run_id))?.result.subscribe()
+        };
+        if request.wait {
+            result
+                .wait_for(|result| result.status() != RunStatus::Running)
+                .await
+                .map_err(|_| Status::aborted("Server is shutting down"))?;
+        }
+        let result = result.borrow().clone();
+        Ok(Response::new(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CrewClient;
+    use merco_agents::crew::crew::Workflow;
+    use merco_agents::definition::definition::{AgentDefinition, CrewTaskDefinition, to_canonical_json};
+    use merco_agents::task::task::Task;
+    use tokio::sync::oneshot;
+    use tonic::Code;
+
+    // Serve on a free local port; the server stops when the returned sender is dropped
+    async fn start(server: CrewServer) -> (CrewClient, oneshot::Sender<()>) {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let (stop, stopped) = oneshot::channel::<()>();
+        tokio::spawn(server.serve(addr, async {
+            let _ = stopped.await;
+        }));
+        for _ in 0..100 {
+            if let Ok(client) = CrewClient::connect(format!("http://{}", addr)).await {
+                return (client, stop);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Server did not start on {}", addr);
+    }
+
+    // One agent whose provider is unreachable, with `tasks` tasks for it
+    fn crew(tasks: usize) -> CrewDefinition {
+        let agent = r#"{"llm": {"provider": "ollama", "base_url": "http://127.0.0.1:1", "model": "llama3"}, "backstory": "You help."}"#;
+        let agent: AgentDefinition = from_json(agent).unwrap();
+        let tasks = (0..tasks)
+            .map(|i| CrewTaskDefinition { agent: 0, task: Task::new(format!("Task {}", i), None), parallel: false })
+            .collect();
+        CrewDefinition { agents: vec![agent], tasks, workflow: Workflow::default(), max_concurrency: None, manager: None }
+    }
+
+    fn crew_json(tasks: usize) -> String {
+        to_canonical_json(&crew(tasks)).unwrap()
+    }
+
+    async fn run_events(client: &mut CrewClient, run_id: &str) -> Vec<run_event::Event> {
+        let mut stream = client.events(run_id).await.unwrap();
+        let mut events = Vec::new();
+        while let Some(event) = stream.message().await.unwrap() {
+            assert_eq!(event.run_id, run_id);
+            events.push(event.event.unwrap());
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let (mut client, _stop) = start(CrewServer::new()).await;
+
+        let run_id = client.kickoff(&crew(0)).await.unwrap();
+        assert!(run_id.starts_with("run-"), "{}", run_id);
+        let result = client.result(&run_id, true).await.unwrap();
+        assert_eq!(result.status(), RunStatus::Completed);
+        assert_eq!(result.run_id, run_id);
+        let events = run_events(&mut client, &run_id).await;
+        assert!(matches!(events[..], [run_event::Event::Started(_), run_event::Event::Completed(_)]), "{:?}", events);
+
+        let run_id = client.kickoff_json(crew_json(1), Some("unreachable".to_string())).await.unwrap();
+        assert_eq!(run_id, "unreachable");
+        let result = client.result(&run_id, true).await.unwrap();
+        assert_eq!(result.status(), RunStatus::Failed);
+        assert!(!result.error.is_empty());
+        let events = run_events(&mut client, &run_id).await;
+        assert!(matches!(events.last(), Some(run_event::Event::Failed(failed)) if failed.error == result.error));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_calls() {
+        let (mut client, _stop) = start(CrewServer::new()).await;
+
+        let error = client.kickoff_json("{}".to_string(), None).await.unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+
+        client.kickoff_json(crew_json(0), Some("dup".to_string())).await.unwrap();
+        let error = client.kickoff_json(crew_json(0), Some("dup".to_string())).await.unwrap_err();
+        assert_eq!(error.code(), Code::AlreadyExists);
+
+        assert_eq!(client.result("missing", false).await.unwrap_err().code(), Code::NotFound);
+        assert_eq!(client.cancel("missing").await.unwrap_err().code(), Code::NotFound);
+        assert_eq!(client.events("missing").await.unwrap_err().code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_finished_runs_are_evicted_after_the_ttl() {
+        let server = CrewServer::new().with_result_ttl(Duration::from_millis(50));
+        let runs = server.runs.clone();
+        let (mut client, _stop) = start(server).await;
+
+        let run_id = client.kickoff_json(crew_json(0), Some("short-lived".to_string())).await.unwrap();
+        assert_eq!(client.result(&run_id, true).await.unwrap().status(), RunStatus::Completed);
+        assert_eq!(run_events(&mut client, &run_id).await.len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(runs.lock().unwrap().is_empty());
+        assert_eq!(client.result(&run_id, false).await.unwrap_err().code(), Code::NotFound);
+        // The id can be used again once its run is forgotten
+        client.kickoff_json(crew_json(0), Some(run_id)).await.unwrap();
+    }
+}
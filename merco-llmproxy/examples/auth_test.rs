@@ -1,18 +1,13 @@
-use merco_llmproxy::config::{LlmConfig, Provider};
-use merco_llmproxy::traits::{ChatMessage, CompletionRequest, LlmProvider, ChatMessageRole};
-use std::env;
+use merco_llmproxy::config::LlmConfig;
+use merco_llmproxy::traits::{ChatMessage, CompletionRequest};
 use std::error::Error;
 
 #[tokio::main]
 async fn main() {
-    // Load API key from environment variable
-    let api_key = env::var("OPENROUTER_API_KEY")
-        .expect("API key is required.");
-
-    // Configure for OpenRouter (using OpenAI provider)
-    let config = LlmConfig::new(Provider::OpenAI)
-        .with_api_key(api_key)
-        .with_base_url("https://openrouter.ai/api/v1".to_string()); // Explicitly set OpenRouter URL
+    // Reads MERCO_PROVIDER, MERCO_MODEL, MERCO_API_KEY and MERCO_BASE_URL, e.g. for
+    // OpenRouter: openai, openai/gpt-3.5-turbo, <key>, https://openrouter.ai/api/v1
+    let config = LlmConfig::from_env("MERCO").unwrap_or_else(|e| panic!("{}", e));
+    let model = config.model.clone().unwrap_or_default();
 
     println!("Using config: {:?}", config);
 
@@ -21,16 +16,12 @@ async fn main() {
 
     // Create a simple request
     let request = CompletionRequest {
-        model: "openai/gpt-3.5-turbo".to_string(), // Added model field
-        messages: vec![ChatMessage {
-            role: ChatMessageRole::User, // Use imported ChatMessageRole
-            content: Some("Say hello!".to_string()), // Fixed content type to Option<String>
-            tool_calls: None,
-            tool_call_id: None,
-        }],
+        model,
+        messages: vec![ChatMessage::user("Say hello!")],
         temperature: Some(0.7),
         max_tokens: Some(50),
         tools: None,
+        ..Default::default()
     };
 
     println!("Sending request: {:?}", request);
@@ -0,0 +1,21 @@
+// Compare providers on the built-in benchmark battery. Each argument is an environment
+// prefix read with `LlmConfig::from_env`, e.g. with OPENROUTER_PROVIDER, OPENROUTER_MODEL,
+// ... and LOCAL_PROVIDER=ollama, LOCAL_MODEL=llama3.2 set:
+//
+//   cargo run --example provider_bench -- OPENROUTER LOCAL
+use merco_llmproxy::{get_provider, BenchTarget, LlmConfig, ProviderBench};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut bench = ProviderBench::new().with_repetitions(2);
+    for prefix in std::env::args().skip(1) {
+        let config = LlmConfig::from_env(&prefix)?;
+        let model = config.model.clone().unwrap_or_default();
+        bench = bench.with_target(BenchTarget::new(prefix, get_provider(config)?, model));
+    }
+
+    let report = bench.run().await;
+    println!("{}", report.to_markdown());
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use merco_llmproxy::{execute_tool, get_tools_by_names, merco_tool};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::error::Error;
+
+/// Arguments of `convert_temperature`. Field doc comments become parameter descriptions.
+#[derive(Deserialize, JsonSchema)]
+struct ConvertArgs {
+    /// The temperature to convert
+    value: f64,
+    /// The unit of `value`
+    from: Unit,
+    /// Decimal places to round to (defaults to 1)
+    precision: Option<u32>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Unit {
+    Celsius,
+    Fahrenheit,
+}
+
+// A single struct argument: the parameter schema is derived from `ConvertArgs`
+#[merco_tool(description = "Converts a temperature between Celsius and Fahrenheit")]
+fn convert_temperature(args: ConvertArgs) -> String {
+    let (converted, unit) = match args.from {
+        Unit::Celsius => (args.value * 9.0 / 5.0 + 32.0, "°F"),
+        Unit::Fahrenheit => ((args.value - 32.0) * 5.0 / 9.0, "°C"),
+    };
+    format!("{:.*}{}", args.precision.unwrap_or(1) as usize, converted, unit)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let tool = &get_tools_by_names(&["convert_temperature"])[0];
+    println!("Parameters: {}", serde_json::to_string_pretty(&tool.parameters)?);
+
+    let result = execute_tool("convert_temperature", r#"{"value": 21.5, "from": "celsius"}"#).await?;
+    println!("convert_temperature(21.5 °C) = {}", result);
+    Ok(())
+}
@@ -1,12 +1,14 @@
 use merco_llmproxy::{
-    ChatMessage, CompletionKind, CompletionRequest, LlmConfig, Provider, get_provider,
+    CompletionKind, CompletionRequest, Conversation, LlmConfig, Provider, get_provider,
     merco_tool, get_all_tools, execute_tool,
 };
 use std::error::Error;
 
 // Define a simple tool using the macro
+/// * `a` - The first number to add
 #[merco_tool(description = "Adds two numbers together")]
-fn add_numbers(a: i32, b: i32) -> i32 {
+#[param(name = "a", example = 3)]
+fn add_numbers(a: i32, #[param(description = "The second number to add", example = 4)] b: i32) -> i32 {
     a + b
 }
 
@@ -22,6 +24,22 @@ fn concat_strings(first: String, second: String) -> String {
     format!("{}{}", first, second)
 }
 
+// Errors are returned to the model as {"error": ..., "retryable": false}
+#[merco_tool(description = "Divides a by b")]
+fn divide_numbers(a: f64, b: f64) -> Result<f64, String> {
+    if b == 0.0 {
+        return Err("Cannot divide by zero".to_string());
+    }
+    Ok(a / b)
+}
+
+// Async tools can await IO without blocking the runtime
+#[merco_tool(description = "Waits for the given number of milliseconds, then reports how long it waited")]
+async fn wait_millis(millis: u64) -> String {
+    tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+    format!("Waited {}ms", millis)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // At this point, the tools are registered automatically
@@ -36,70 +54,69 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
     
     // Execute a tool directly
-    let add_result = execute_tool("add_numbers", r#"{"a": 5, "b": 7}"#)?;
+    let add_result = execute_tool("add_numbers", r#"{"a": 5, "b": 7}"#).await?;
     println!("\nDirect execution result of add_numbers(5, 7): {}", add_result);
     
-    let multiply_result = execute_tool("multiply_numbers", r#"{"a": 3.5, "b": 2.0}"#)?;
+    let multiply_result = execute_tool("multiply_numbers", r#"{"a": 3.5, "b": 2.0}"#).await?;
     println!("Direct execution result of multiply_numbers(3.5, 2.0): {}", multiply_result);
     
-    let concat_result = execute_tool("concat_strings", r#"{"first": "Hello, ", "second": "World!"}"#)?;
+    let concat_result = execute_tool("concat_strings", r#"{"first": "Hello, ", "second": "World!"}"#).await?;
     println!("Direct execution result of concat_strings(\"Hello, \", \"World!\"): {}", concat_result);
+
+    let divide_error = execute_tool("divide_numbers", r#"{"a": 1.0, "b": 0.0}"#).await.unwrap_err();
+    println!("Direct execution error of divide_numbers(1.0, 0.0): {}", divide_error);
+
+    let wait_result = execute_tool("wait_millis", r#"{"millis": 50}"#).await?;
+    println!("Direct execution result of wait_millis(50): {}", wait_result);
     
     // Now, use these tools with an LLM (if available)
     if let Ok(api_key) = std::env::var("OPENROUTER_API_KEY") {
         println!("\nTesting LLM tool calling with OpenRouter:");
         
         // Create provider config
-        let config = LlmConfig::new(
-            Provider::OpenAI,
-            "mistralai/mistral-7b-instruct-v0.1".to_string()
-        )
+        let config = LlmConfig::new(Provider::OpenAI)
         .with_base_url("https://openrouter.ai/api/v1".to_string())
         .with_api_key(api_key);
         
         let provider = get_provider(config)?;
         
-        // Create a request with our tools
+        // The conversation records each reply, so tool results can be sent back
+        let mut conversation = Conversation::new();
+        conversation.push_user("What is 42 plus 17? Also, what is 8.5 multiplied by 3? Finally, can you concatenate 'Merco' and 'LLM'?");
         let request = CompletionRequest {
             model: "mistralai/mistral-7b-instruct-v0.1".to_string(),
-            messages: vec![
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: Some("What is 42 plus 17? Also, what is 8.5 multiplied by 3? Finally, can you concatenate 'Merco' and 'LLM'?".to_string()),
-                    tool_calls: None,
-                    tool_call_id: None,
-                },
-            ],
             temperature: Some(0.1),
             max_tokens: Some(300),
             tools: Some(tools), // Use our registered tools
+            ..Default::default()
         };
-        
-        // Make the request
-        match provider.completion(request).await {
-            Ok(response) => {
-                println!("LLM Response:");
-                match response.kind {
-                    CompletionKind::Message { content } => {
-                        println!("Text Response: {}", content);
-                    }
-                    CompletionKind::ToolCall { tool_calls } => {
-                        println!("Tool Calls:");
-                        for call in tool_calls {
-                            println!("  Tool: {}", call.function.name);
-                            println!("  Arguments: {}", call.function.arguments);
-                            
-                            // Execute the tool with the arguments from the LLM
-                            match execute_tool(&call.function.name, &call.function.arguments) {
-                                Ok(result) => println!("  Result: {}", result),
-                                Err(e) => println!("  Execution Error: {}", e),
-                            }
-                        }
-                    }
+
+        // Run requested tools until the model answers with text
+        for _ in 0..5 {
+            let response = match conversation.send(provider.as_ref(), request.clone()).await {
+                Ok(response) => response,
+                Err(e) => {
+                    println!("LLM Request Error: {}", e);
+                    break;
                 }
-            }
-            Err(e) => {
-                println!("LLM Request Error: {}", e);
+            };
+            let CompletionKind::ToolCall { tool_calls } = response.kind else {
+                println!("Text Response: {}", conversation.last_reply().unwrap_or_default());
+                break;
+            };
+
+            println!("Tool Calls:");
+            for call in tool_calls {
+                println!("  Tool: {}", call.function.name);
+                println!("  Arguments: {}", call.function.arguments);
+
+                // Execute the tool with the arguments from the LLM
+                let result = match execute_tool(&call.function.name, &call.function.arguments).await {
+                    Ok(result) => result,
+                    Err(e) => format!("Execution Error: {}", e),
+                };
+                println!("  Result: {}", result);
+                conversation.push_tool_result(call.id, result);
             }
         }
     } else {
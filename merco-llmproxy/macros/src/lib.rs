@@ -8,6 +8,7 @@ use syn::{
     punctuated::Punctuated, Token,
 };
 use syn::parse::Parse;
+use syn::spanned::Spanned;
 
 // Custom parsing for attribute arguments
 struct AttributeArgs {
@@ -36,10 +37,75 @@ impl Parse for AttributeArgs {
 /// ```
 ///
 /// This will automatically register the function as a tool that can be called by LLMs.
+///
+/// A function taking a single argument of a custom type is a struct-argument tool: the
+/// tool's arguments are that struct, and its parameter schema (types, required fields,
+/// descriptions from doc comments) is derived with `schemars`. The struct must derive
+/// `Deserialize` and `schemars::JsonSchema`, and merco-llmproxy's `schema` feature must
+/// be enabled:
+///
+/// ```ignore
+/// #[derive(Deserialize, JsonSchema)]
+/// struct SumArgs {
+///     /// The first addend
+///     a: i64,
+///     /// The second addend
+///     b: i64,
+/// }
+///
+/// #[merco_tool(description = "Calculates the sum of two integers")]
+/// fn sum(args: SumArgs) -> i64 {
+///     args.a + args.b
+/// }
+/// ```
+///
+/// Parameters are described with `#[param(...)]` attributes, placed after
+/// `#[merco_tool]` or on the parameter itself, or with a `` * `name` - description ``
+/// list in the function's doc comment. Descriptions and examples end up in the schema:
+///
+/// ```ignore
+/// /// * `b` - The second addend
+/// #[merco_tool(description = "Calculates the sum of two integers")]
+/// #[param(name = "a", description = "The first addend", example = 3)]
+/// pub fn sum(a: i32, #[param(example = 4)] b: i32) -> i32 {
+///     a + b
+/// }
+/// ```
+///
+/// `namespace = "fs"` registers the tool as `fs.<function name>`, so agents can
+/// expose or hide it with a `ToolFilter`.
+///
+/// Tools returning `Result<T, E>` report `Err` values as a structured payload,
+/// `{"error": "...", "retryable": false}`; return a `merco_llmproxy::ToolError` as `E`
+/// to mark a failure retryable. Any other `E` only needs to implement `Display`.
+///
+/// A parameter of type `ProgressReporter` is not part of the schema; the executor passes a
+/// reporter that forwards progress updates to whoever is running the tool:
+///
+/// ```ignore
+/// #[merco_tool(description = "Crawls a site")]
+/// pub fn crawl(url: String, progress: ProgressReporter) -> usize {
+///     progress.message(format!("crawling {}", url));
+///     42
+/// }
+/// ```
+///
+/// `async fn` tools are supported as well; their futures must be `Send`:
+///
+/// ```ignore
+/// #[merco_tool(description = "Fetches a web page")]
+/// pub async fn fetch(url: String) -> String {
+///     reqwest::get(&url).await.unwrap().text().await.unwrap()
+/// }
+/// ```
 #[proc_macro_attribute]
 pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
     let attr_args = parse_macro_input!(attr as AttributeArgs);
-    let input_fn = parse_macro_input!(item as ItemFn);
+    let mut input_fn = parse_macro_input!(item as ItemFn);
+    let param_docs = match take_param_docs(&mut input_fn) {
+        Ok(docs) => docs,
+        Err(error) => return error.to_compile_error().into(),
+    };
 
     // Extract function name and arguments
     let fn_name = input_fn.sig.ident.to_string();
@@ -48,6 +114,7 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
         .inputs
         .iter()
         .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { ty, .. }) if is_progress_reporter(ty) => None,
             FnArg::Typed(PatType { pat, ty, .. }) => {
                 if let Pat::Ident(pat_ident) = &**pat {
                     let arg_name = pat_ident.ident.to_string();
@@ -61,6 +128,59 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
         })
         .collect();
 
+    // `Ok` values are serialized; `Err` values become a structured tool error
+    let returns_result = matches!(
+        &input_fn.sig.output,
+        syn::ReturnType::Type(_, ty) if matches!(&**ty, syn::Type::Path(path) if path.path.segments.last().is_some_and(|s| s.ident == "Result"))
+    );
+    let to_output = if returns_result {
+        quote! {
+            match result {
+                Ok(value) => ::merco_llmproxy::tools::to_canonical_json(&value)
+                    .map_err(|e| format!("Failed to serialize result for {}: {}", #fn_name, e)),
+                Err(error) => Err(::merco_llmproxy::tools::ToolError::from_error(error).to_payload()),
+            }
+        }
+    } else {
+        quote! {
+            ::merco_llmproxy::tools::to_canonical_json(&result)
+                .map_err(|e| format!("Failed to serialize result for {}: {}", #fn_name, e))
+        }
+    };
+
+    // A lone argument of a non-primitive type is the whole argument object
+    let model_args: Vec<_> = input_fn
+        .sig
+        .inputs
+        .iter()
+        .filter(|arg| !matches!(arg, FnArg::Typed(PatType { ty, .. }) if is_progress_reporter(ty)))
+        .collect();
+    let struct_arg = match model_args.as_slice() {
+        [FnArg::Typed(PatType { ty, .. })] if !is_primitive_type(ty) => Some((**ty).clone()),
+        _ => None,
+    };
+    if struct_arg.is_none() {
+        if let Some(unknown) = param_docs.iter().find(|doc| !fn_args.iter().any(|(name, _)| *name == doc.name)) {
+            let message = format!("`{}` has no parameter named `{}`", fn_name, unknown.name);
+            return syn::Error::new(unknown.span, message).to_compile_error().into();
+        }
+    }
+    let describe_params: Vec<_> = param_docs
+        .iter()
+        .map(|doc| {
+            let name = &doc.name;
+            let description = match &doc.description {
+                Some(description) => quote! { Some(#description) },
+                None => quote! { None },
+            };
+            let example = match &doc.example {
+                Some(example) => quote! { Some(::serde_json::json!(#example)) },
+                None => quote! { None },
+            };
+            quote! { ::merco_llmproxy::tools::__describe_param(props, #name, #description, #example); }
+        })
+        .collect();
+
     // Extract description from attribute
     let mut description = format!("Tool function: {}", fn_name);
     for meta in &attr_args.attrs {
@@ -75,6 +195,21 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     }
 
+    // A namespace, if given, prefixes the tool name: `namespace = "fs"` registers `fs.read_file`
+    let mut tool_name = fn_name.clone();
+    for meta in &attr_args.attrs {
+        if let Meta::NameValue(name_value) = meta {
+            if name_value.path.is_ident("namespace") {
+                match &name_value.value {
+                    Expr::Lit(syn::ExprLit { lit: Lit::Str(lit_str), .. }) => {
+                        tool_name = format!("{}.{}", lit_str.value(), fn_name);
+                    }
+                    other => return syn::Error::new_spanned(other, "expected a string literal").to_compile_error().into(),
+                }
+            }
+        }
+    }
+
     // Generate the tool struct name
     let tool_struct_name = Ident::new(&format!("{}ToolArgs", fn_name), Span::call_site());
 
@@ -102,7 +237,26 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     // Generate function wrapper fields
     let fn_ident = &input_fn.sig.ident;
-    let arg_names: Vec<_> = fn_args.iter().map(|(name, _)| Ident::new(name, Span::call_site())).collect();
+    // Arguments in declaration order; a `ProgressReporter` is supplied by the executor
+    let call_args: Vec<_> = input_fn
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(PatType { ty, .. }) if is_progress_reporter(ty) => {
+                Some(quote! { ::merco_llmproxy::tools::ProgressReporter::current(#tool_name) })
+            }
+            FnArg::Typed(PatType { pat, .. }) => match &**pat {
+                Pat::Ident(_) if struct_arg.is_some() => Some(quote! { args }),
+                Pat::Ident(pat_ident) => {
+                    let name = &pat_ident.ident;
+                    Some(quote! { args.#name })
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
     let arg_structs = fn_args.iter().map(|(name, ty_str)| {
         let name_ident = Ident::new(name, Span::call_site());
         // Parse the type string back into a Type syn object for accurate quoting
@@ -112,9 +266,59 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     });
 
+    // Async tools are awaited inside the executor's future
+    let call = if input_fn.sig.asyncness.is_some() {
+        quote! { #fn_ident(#(#call_args),*).await }
+    } else {
+        quote! { #fn_ident(#(#call_args),*) }
+    };
+
     // Generate automatic registration function name (internal use)
     let registration_fn = Ident::new(&format!("_register_{}_tool", fn_name), Span::call_site());
 
+    if let Some(arg_type) = struct_arg {
+        return TokenStream::from(quote! {
+            #input_fn
+
+            struct #tool_struct_name;
+
+            impl #tool_struct_name {
+                fn __get_tool_definition() -> ::merco_llmproxy::traits::Tool {
+                    let mut parameters = ::merco_llmproxy::typed::parameters_for::<#arg_type>();
+                    if let Some(props) = parameters.properties.as_mut() {
+                        #(#describe_params)*
+                    }
+                    ::merco_llmproxy::traits::Tool {
+                        name: #tool_name.to_string(),
+                        description: #description.to_string(),
+                        parameters,
+                    }
+                }
+
+                fn __execute_impl(args_json: String) -> ::merco_llmproxy::tools::ToolFuture {
+                    ::std::boxed::Box::pin(async move {
+                        match ::serde_json::from_str::<#arg_type>(&args_json) {
+                            Ok(args) => {
+                                let result = #call;
+                                #to_output
+                            }
+                            Err(e) => Err(format!("Failed to parse arguments for {}: {}", #fn_name, e)),
+                        }
+                    })
+                }
+            }
+
+            #[::ctor::ctor]
+            fn #registration_fn() {
+                ::merco_llmproxy::tools::__register_macro_tool(
+                    &#tool_name,
+                    #tool_struct_name::__get_tool_definition(),
+                    #tool_struct_name::__execute_impl,
+                );
+            }
+        });
+    }
+
     // Generate the output code
     let expanded = quote! {
         // Include the original function
@@ -134,9 +338,13 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
 
                 let mut props = Map::new();
                 #(#param_properties)*
+                {
+                    let props = &mut props;
+                    #(#describe_params)*
+                }
 
                 ::merco_llmproxy::traits::Tool {
-                    name: #fn_name.to_string(),
+                    name: #tool_name.to_string(),
                     description: #description.to_string(),
                     parameters: JsonSchema {
                         schema_type: "object".to_string(),
@@ -147,17 +355,18 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
             }
 
             // Execute the function with deserialized arguments
-            fn __execute_impl(args_json: &str) -> ::std::result::Result<String, String> {
-                match ::serde_json::from_str::<#tool_struct_name>(args_json) {
-                    Ok(args) => {
-                        // Call the original function using the deserialized arguments
-                        let result = #fn_ident(#(args.#arg_names),*);
-                        // Convert the function's result back to a JSON string
-                        ::serde_json::to_string(&result)
-                           .map_err(|e| format!("Failed to serialize result for {}: {}", #fn_name, e))
+            fn __execute_impl(args_json: String) -> ::merco_llmproxy::tools::ToolFuture {
+                ::std::boxed::Box::pin(async move {
+                    match ::serde_json::from_str::<#tool_struct_name>(&args_json) {
+                        Ok(args) => {
+                            // Call the original function using the deserialized arguments
+                            let result = #call;
+                            // Convert the function's result back to a JSON string
+                            #to_output
+                        }
+                        Err(e) => Err(format!("Failed to parse arguments for {}: {}", #fn_name, e)),
                     }
-                    Err(e) => Err(format!("Failed to parse arguments for {}: {}", #fn_name, e)),
-                }
+                })
             }
         }
 
@@ -166,7 +375,7 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
         fn #registration_fn() {
             let tool_def = #tool_struct_name::__get_tool_definition();
             ::merco_llmproxy::tools::__register_macro_tool(
-                &#fn_name,
+                &#tool_name,
                 tool_def,
                 #tool_struct_name::__execute_impl,
             );
@@ -175,3 +384,394 @@ pub fn merco_tool(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+/// Whether a parameter is the tool's progress reporter rather than a model-supplied argument.
+fn is_progress_reporter(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "ProgressReporter"))
+}
+
+/// Types mapped to a single JSON value (and so to one named parameter) rather than
+/// treated as a struct holding all of the tool's parameters.
+fn is_primitive_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Reference(reference) => is_primitive_type(&reference.elem),
+        syn::Type::Path(path) => path.path.segments.last().is_some_and(|segment| {
+            matches!(
+                segment.ident.to_string().as_str(),
+                "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize"
+                    | "f32" | "f64" | "bool" | "char" | "str" | "String" | "Vec" | "Option" | "HashMap" | "BTreeMap"
+                    | "Value"
+            )
+        }),
+        _ => true,
+    }
+}
+
+// Description and example of one parameter
+struct ParamDoc {
+    name: String,
+    description: Option<String>,
+    example: Option<Expr>,
+    span: Span,
+}
+
+/// Removes `#[param(...)]` attributes from the function and its parameters and collects
+/// them, together with `` * `name` - description `` lines of the doc comment.
+fn take_param_docs(input_fn: &mut ItemFn) -> syn::Result<Vec<ParamDoc>> {
+    let mut docs: Vec<ParamDoc> = Vec::new();
+    let mut merge = |doc: ParamDoc| match docs.iter_mut().find(|existing| existing.name == doc.name) {
+        Some(existing) => {
+            existing.description = doc.description.or(existing.description.take());
+            existing.example = doc.example.or(existing.example.take());
+        }
+        None => docs.push(doc),
+    };
+
+    for attr in &input_fn.attrs {
+        if let Some(doc) = attr_string(attr, "doc") {
+            let line = doc.trim().trim_start_matches(['*', '-']).trim_start();
+            let Some(rest) = line.strip_prefix('`') else { continue };
+            let Some((name, description)) = rest.split_once('`') else { continue };
+            let description = description.trim_start().trim_start_matches(['-', ':']).trim();
+            if !name.is_empty() && !description.is_empty() {
+                merge(ParamDoc { name: name.to_string(), description: Some(description.to_string()), example: None, span: Span::call_site() });
+            }
+        }
+    }
+
+    let mut attrs = std::mem::take(&mut input_fn.attrs);
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("param")) {
+        merge(parse_param_attr(attr, None)?);
+    }
+    attrs.retain(|attr| !attr.path().is_ident("param"));
+    input_fn.attrs = attrs;
+
+    for arg in input_fn.sig.inputs.iter_mut() {
+        let FnArg::Typed(PatType { attrs, pat, .. }) = arg else { continue };
+        let name = match &**pat {
+            Pat::Ident(pat_ident) => pat_ident.ident.to_string(),
+            _ => continue,
+        };
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("param")) {
+            merge(parse_param_attr(attr, Some(&name))?);
+        }
+        attrs.retain(|attr| !attr.path().is_ident("param"));
+    }
+    Ok(docs)
+}
+
+/// Parses `#[param(name = "...", description = "...", example = ...)]`; `name` is
+/// implied for attributes on a parameter.
+fn parse_param_attr(attr: &syn::Attribute, implied_name: Option<&str>) -> syn::Result<ParamDoc> {
+    let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+    let mut doc = ParamDoc { name: implied_name.unwrap_or_default().to_string(), description: None, example: None, span: Span::call_site() };
+    for meta in metas {
+        let Meta::NameValue(name_value) = meta else {
+            return Err(syn::Error::new_spanned(meta, "expected `key = value`"));
+        };
+        let string_value = || match &name_value.value {
+            Expr::Lit(syn::ExprLit { lit: Lit::Str(lit), .. }) => Ok(lit.value()),
+            other => Err(syn::Error::new_spanned(other, "expected a string literal")),
+        };
+        if name_value.path.is_ident("name") {
+            doc.name = string_value()?;
+            doc.span = name_value.value.span();
+        } else if name_value.path.is_ident("description") {
+            doc.description = Some(string_value()?);
+        } else if name_value.path.is_ident("example") {
+            doc.example = Some(name_value.value.clone());
+        } else {
+            return Err(syn::Error::new_spanned(&name_value.path, "expected `name`, `description` or `example`"));
+        }
+    }
+    if doc.name.is_empty() {
+        return Err(syn::Error::new_spanned(attr, "`#[param]` on the function needs `name = \"...\"`"));
+    }
+    Ok(doc)
+}
+
+/// The string value of a `#[name = "..."]` attribute (e.g. a doc comment line).
+fn attr_string(attr: &syn::Attribute, name: &str) -> Option<String> {
+    match &attr.meta {
+        Meta::NameValue(name_value) if name_value.path.is_ident(name) => match &name_value.value {
+            Expr::Lit(syn::ExprLit { lit: Lit::Str(lit), .. }) => Some(lit.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Derives `merco_llmproxy::output::MercoOutput`, describing the type as a JSON Schema.
+///
+/// Structs with named fields become objects (`Option` fields and fields with
+/// `#[serde(default)]` are optional), newtypes take their inner type's schema and enums
+/// are described the way serde represents them, externally or (with `#[serde(tag)]`)
+/// internally tagged. Doc comments become descriptions, and serde's `rename`,
+/// `rename_all` and `skip` attributes are applied. Field types must implement
+/// `MercoOutput` themselves.
+///
+/// ```ignore
+/// #[derive(Deserialize, MercoOutput)]
+/// struct Person {
+///     /// Full name
+///     name: String,
+///     address: Address,
+///     nickname: Option<String>,
+/// }
+/// ```
+#[proc_macro_derive(MercoOutput, attributes(serde))]
+pub fn derive_merco_output(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as syn::DeriveInput);
+    match expand_merco_output(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// The serde attributes that change the shape of the deserialized value.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    tag: Option<String>,
+    skip: bool,
+    default: bool,
+}
+
+fn serde_attrs(attrs: &[syn::Attribute]) -> syn::Result<SerdeAttrs> {
+    let mut parsed = SerdeAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            let key = meta.path.get_ident().map(ToString::to_string).unwrap_or_default();
+            match key.as_str() {
+                "rename" | "rename_all" => {
+                    // `rename(deserialize = "...")` names the form read back in
+                    let value = if meta.input.peek(syn::token::Paren) {
+                        let mut deserialize = None;
+                        meta.parse_nested_meta(|inner| {
+                            let value: syn::LitStr = inner.value()?.parse()?;
+                            if inner.path.is_ident("deserialize") {
+                                deserialize = Some(value.value());
+                            }
+                            Ok(())
+                        })?;
+                        deserialize
+                    } else {
+                        Some(meta.value()?.parse::<syn::LitStr>()?.value())
+                    };
+                    if key == "rename" { parsed.rename = value } else { parsed.rename_all = value }
+                }
+                "tag" => parsed.tag = Some(meta.value()?.parse::<syn::LitStr>()?.value()),
+                "skip" | "skip_deserializing" => parsed.skip = true,
+                "default" => {
+                    parsed.default = true;
+                    if meta.input.peek(Token![=]) {
+                        meta.value()?.parse::<syn::LitStr>()?;
+                    }
+                }
+                "untagged" | "content" | "flatten" => {
+                    return Err(meta.error(format!("`#[serde({})]` is not supported by MercoOutput", key)));
+                }
+                _ => {
+                    // Attributes that don't affect the schema; consume their value
+                    if meta.input.peek(Token![=]) {
+                        meta.value()?.parse::<Expr>()?;
+                    } else if meta.input.peek(syn::token::Paren) {
+                        let content;
+                        syn::parenthesized!(content in meta.input);
+                        content.parse::<proc_macro2::TokenStream>()?;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(parsed)
+}
+
+/// Doc comment lines joined into one description.
+fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| attr_string(attr, "doc"))
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+fn optional_string(value: Option<String>) -> proc_macro2::TokenStream {
+    match value {
+        Some(value) => quote! { ::std::option::Option::Some(#value) },
+        None => quote! { ::std::option::Option::<&str>::None },
+    }
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(path) if path.qself.is_none() && path.path.segments.last().is_some_and(|s| s.ident == "Option"))
+}
+
+/// Applies a serde `rename_all` rule to a field name (written in snake_case).
+fn rename_field(name: &str, rule: Option<&str>) -> String {
+    let pascal = || name.split('_').map(|part| {
+        let mut chars = part.chars();
+        chars.next().map(|first| first.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+    }).collect::<String>();
+    match rule {
+        Some("UPPERCASE") | Some("SCREAMING_SNAKE_CASE") => name.to_ascii_uppercase(),
+        Some("PascalCase") => pascal(),
+        Some("camelCase") => {
+            let pascal = pascal();
+            let mut chars = pascal.chars();
+            chars.next().map(|first| first.to_ascii_lowercase().to_string() + chars.as_str()).unwrap_or_default()
+        }
+        Some("kebab-case") => name.replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => name.to_ascii_uppercase().replace('_', "-"),
+        _ => name.to_string(),
+    }
+}
+
+/// Applies a serde `rename_all` rule to a variant name (written in PascalCase).
+fn rename_variant(name: &str, rule: Option<&str>) -> String {
+    let snake = || {
+        let mut snake = String::new();
+        for (i, c) in name.char_indices() {
+            if i > 0 && c.is_uppercase() {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        }
+        snake
+    };
+    match rule {
+        Some("lowercase") => name.to_ascii_lowercase(),
+        Some("UPPERCASE") => name.to_ascii_uppercase(),
+        Some("camelCase") => {
+            let mut chars = name.chars();
+            chars.next().map(|first| first.to_ascii_lowercase().to_string() + chars.as_str()).unwrap_or_default()
+        }
+        Some("snake_case") => snake(),
+        Some("SCREAMING_SNAKE_CASE") => snake().to_ascii_uppercase(),
+        Some("kebab-case") => snake().replace('_', "-"),
+        Some("SCREAMING-KEBAB-CASE") => snake().to_ascii_uppercase().replace('_', "-"),
+        _ => name.to_string(),
+    }
+}
+
+fn field_schema(ty: &syn::Type) -> proc_macro2::TokenStream {
+    quote! { <#ty as ::merco_llmproxy::output::MercoOutput>::output_schema() }
+}
+
+/// `OutputField` expressions for named fields, skipping those serde never reads.
+fn output_fields(fields: &syn::FieldsNamed, rename_all: Option<&str>, all_default: bool) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    let mut output = Vec::new();
+    for field in &fields.named {
+        let attrs = serde_attrs(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_default();
+        let ident = ident.trim_start_matches("r#");
+        let name = attrs.rename.unwrap_or_else(|| rename_field(ident, rename_all));
+        let description = optional_string(doc_comment(&field.attrs));
+        let required = !is_option(&field.ty) && !attrs.default && !all_default;
+        let schema = field_schema(&field.ty);
+        output.push(quote! {
+            ::merco_llmproxy::output::OutputField {
+                name: ::std::string::String::from(#name),
+                description: #description.map(::std::string::String::from),
+                required: #required,
+                schema: #schema,
+            }
+        });
+    }
+    Ok(output)
+}
+
+fn expand_merco_output(mut input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let container = serde_attrs(&input.attrs)?;
+    let description = optional_string(doc_comment(&input.attrs));
+    let rename_all = container.rename_all.as_deref();
+
+    let body = match &input.data {
+        syn::Data::Struct(data) => match &data.fields {
+            syn::Fields::Named(fields) => {
+                let fields = output_fields(fields, rename_all, container.default)?;
+                quote! { ::merco_llmproxy::output::struct_schema(#description, ::std::vec![#(#fields),*]) }
+            }
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let schema = field_schema(&fields.unnamed[0].ty);
+                quote! { ::merco_llmproxy::output::describe_schema(#schema, #description) }
+            }
+            syn::Fields::Unnamed(fields) => {
+                let items = fields.unnamed.iter().map(|field| field_schema(&field.ty));
+                quote! { ::merco_llmproxy::output::tuple_schema(#description, ::std::vec![#(#items),*]) }
+            }
+            syn::Fields::Unit => {
+                quote! { ::merco_llmproxy::output::tuple_schema(#description, ::std::vec::Vec::new()) }
+            }
+        },
+        syn::Data::Enum(data) => {
+            let mut variants = Vec::new();
+            for variant in &data.variants {
+                let attrs = serde_attrs(&variant.attrs)?;
+                if attrs.skip {
+                    continue;
+                }
+                let name = attrs.rename.unwrap_or_else(|| rename_variant(&variant.ident.to_string(), rename_all));
+                let variant_description = optional_string(doc_comment(&variant.attrs));
+                let data = match &variant.fields {
+                    syn::Fields::Unit => quote! { ::merco_llmproxy::output::VariantData::Unit },
+                    syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                        let schema = field_schema(&fields.unnamed[0].ty);
+                        quote! { ::merco_llmproxy::output::VariantData::Newtype(#schema) }
+                    }
+                    syn::Fields::Unnamed(fields) => {
+                        if container.tag.is_some() {
+                            return Err(syn::Error::new_spanned(variant, "serde cannot internally tag tuple variants"));
+                        }
+                        let items = fields.unnamed.iter().map(|field| field_schema(&field.ty));
+                        quote! { ::merco_llmproxy::output::VariantData::Tuple(::std::vec![#(#items),*]) }
+                    }
+                    syn::Fields::Named(fields) => {
+                        let fields = output_fields(fields, attrs.rename_all.as_deref(), attrs.default)?;
+                        quote! { ::merco_llmproxy::output::VariantData::Struct(::std::vec![#(#fields),*]) }
+                    }
+                };
+                variants.push(quote! {
+                    ::merco_llmproxy::output::OutputVariant {
+                        name: ::std::string::String::from(#name),
+                        description: #variant_description.map(::std::string::String::from),
+                        data: #data,
+                    }
+                });
+            }
+            let tag = optional_string(container.tag);
+            quote! { ::merco_llmproxy::output::enum_schema(#description, ::std::vec![#(#variants),*], #tag) }
+        }
+        syn::Data::Union(data) => {
+            return Err(syn::Error::new_spanned(data.union_token, "MercoOutput cannot be derived for unions"));
+        }
+    };
+
+    // Every type parameter has to describe itself for the fields using it
+    let type_params: Vec<Ident> = input.generics.type_params().map(|param| param.ident.clone()).collect();
+    let where_clause = input.generics.make_where_clause();
+    for param in type_params {
+        where_clause.predicates.push(syn::parse_quote! { #param: ::merco_llmproxy::output::MercoOutput });
+    }
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::merco_llmproxy::output::MercoOutput for #ident #ty_generics #where_clause {
+            fn output_schema() -> ::merco_llmproxy::output::Value {
+                #body
+            }
+
+            fn output_name() -> ::std::string::String {
+                ::std::string::String::from(#name)
+            }
+        }
+    })
+}
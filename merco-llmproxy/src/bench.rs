@@ -0,0 +1,451 @@
+//!
+//! Provider Benchmark
+//!
+//! `ProviderBench` runs a fixed battery of tasks against several provider/model pairs
+//! and reports accuracy, latency and cost side by side, to help pick a backend:
+//!
+//! * **Tool call** - the model must call a weather tool with the right city.
+//! * **JSON extraction** - the model must extract fields as a JSON object in JSON mode.
+//! * **Long context** - the model must find a fact buried in a long prompt.
+//! * **Streaming** - the reply must stream; time to first token is recorded.
+//!
+//! Cost is computed from reported token usage and per-target prices, when given.
+
+use crate::clock::{default_clock, Clock};
+use crate::stream::StreamCollector;
+use crate::traits::{
+    ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, JsonSchema, LlmProvider, ProviderError,
+    ResponseFormat, StreamContentDelta, TokenUsage, Tool,
+};
+use futures::stream::StreamExt;
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// One task of the benchmark battery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchCase {
+    /// Call a `get_weather` tool with the city from the prompt.
+    ToolCall,
+    /// Extract a name and an age from a sentence as a JSON object.
+    JsonExtraction,
+    /// Recall a code hidden in the middle of a long prompt.
+    LongContext,
+    /// Stream a short reply.
+    Streaming,
+}
+
+impl BenchCase {
+    /// Every case, in report order.
+    pub const ALL: [BenchCase; 4] =
+        [BenchCase::ToolCall, BenchCase::JsonExtraction, BenchCase::LongContext, BenchCase::Streaming];
+
+    /// Short label used in reports.
+    pub fn label(&self) -> &'static str {
+        match self {
+            BenchCase::ToolCall => "tool_call",
+            BenchCase::JsonExtraction => "json_extraction",
+            BenchCase::LongContext => "long_context",
+            BenchCase::Streaming => "streaming",
+        }
+    }
+
+    fn request(&self, model: &str) -> CompletionRequest {
+        let mut request = CompletionRequest {
+            model: model.to_string(),
+            temperature: Some(0.0),
+            max_tokens: Some(256),
+            ..Default::default()
+        };
+        match self {
+            BenchCase::ToolCall => {
+                request.messages = vec![ChatMessage::user("What is the weather in Paris right now?")];
+                request.tools = Some(vec![weather_tool()]);
+            }
+            BenchCase::JsonExtraction => {
+                request.messages = vec![
+                    ChatMessage::system("Reply with a JSON object with the keys \"name\" (string) and \"age\" (number)."),
+                    ChatMessage::user("Ada Lovelace was 36 years old when she died in 1852."),
+                ];
+                request.response_format = Some(ResponseFormat::JsonObject);
+            }
+            BenchCase::LongContext => {
+                request.messages = vec![ChatMessage::user(long_context_prompt())];
+            }
+            BenchCase::Streaming => {
+                request.messages = vec![ChatMessage::user("Reply with exactly these words: the stream works")];
+            }
+        }
+        request
+    }
+
+    // Whether the response solves the task
+    fn check(&self, response: &CompletionResponse) -> bool {
+        match (self, &response.kind) {
+            (BenchCase::ToolCall, CompletionKind::ToolCall { tool_calls }) => tool_calls.iter().any(|call| {
+                let arguments: JsonValue = serde_json::from_str(&call.function.arguments).unwrap_or_default();
+                call.function.name == "get_weather"
+                    && arguments["city"].as_str().is_some_and(|city| city.to_lowercase().contains("paris"))
+            }),
+            (BenchCase::JsonExtraction, CompletionKind::Message { content }) => {
+                let value: JsonValue = serde_json::from_str(strip_code_fence(content)).unwrap_or_default();
+                value["name"] == "Ada Lovelace" && value["age"].as_f64() == Some(36.0)
+            }
+            (BenchCase::LongContext, CompletionKind::Message { content }) => content.contains(NEEDLE),
+            (BenchCase::Streaming, CompletionKind::Message { content }) => {
+                content.to_lowercase().contains("the stream works")
+            }
+            _ => false,
+        }
+    }
+}
+
+const NEEDLE: &str = "7319";
+
+fn weather_tool() -> Tool {
+    let mut properties = serde_json::Map::new();
+    properties.insert("city".to_string(), json!({ "type": "string", "description": "City name" }));
+    Tool {
+        name: "get_weather".to_string(),
+        description: "Get the current weather for a city".to_string(),
+        parameters: JsonSchema {
+            schema_type: "object".to_string(),
+            properties: Some(properties),
+            required: Some(vec!["city".to_string()]),
+        },
+    }
+}
+
+// About 6k tokens of filler with the code halfway through
+fn long_context_prompt() -> String {
+    let filler = "The archive holds many records about trade routes, weather and harvests. ";
+    let half = filler.repeat(200);
+    format!(
+        "{half}The secret code is {NEEDLE}. {half}\n\nWhat is the secret code? Reply with the code only.",
+        half = half,
+        NEEDLE = NEEDLE
+    )
+}
+
+fn strip_code_fence(text: &str) -> &str {
+    let text = text.trim();
+    text.strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(str::trim)
+        .unwrap_or(text)
+}
+
+/// Prices of a model in currency units per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Pricing {
+    /// Price per million prompt tokens.
+    pub input_per_million: f64,
+    /// Price per million completion tokens.
+    pub output_per_million: f64,
+}
+
+impl Pricing {
+    /// Cost of the given token usage.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// A provider and model to benchmark.
+#[derive(Clone)]
+pub struct BenchTarget {
+    name: String,
+    provider: Arc<dyn LlmProvider>,
+    model: String,
+    pricing: Option<Pricing>,
+}
+
+impl BenchTarget {
+    /// Benchmarks `model` on `provider`, reported under `name`.
+    pub fn new(name: impl Into<String>, provider: Arc<dyn LlmProvider>, model: impl Into<String>) -> Self {
+        Self { name: name.into(), provider, model: model.into(), pricing: None }
+    }
+
+    /// Sets the prices used to compute cost (builder style).
+    pub fn with_pricing(mut self, input_per_million: f64, output_per_million: f64) -> Self {
+        self.pricing = Some(Pricing { input_per_million, output_per_million });
+        self
+    }
+}
+
+/// The outcome of one case run against one target.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    /// The case that ran.
+    pub case: BenchCase,
+    /// Whether the response solved the task.
+    pub passed: bool,
+    /// Time until the full response was received.
+    pub latency: Duration,
+    /// Time until the first streamed text, for streaming cases.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_token: Option<Duration>,
+    /// Token usage, if the provider reported it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    /// Cost, if both usage and pricing are known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    /// The provider error, if the request failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// All results of one target.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetReport {
+    /// The target's name.
+    pub name: String,
+    /// The benchmarked model.
+    pub model: String,
+    /// One entry per case and repetition.
+    pub results: Vec<CaseResult>,
+}
+
+impl TargetReport {
+    /// Fraction of runs that passed, between 0.0 and 1.0.
+    pub fn accuracy(&self) -> f64 {
+        if self.results.is_empty() {
+            return 0.0;
+        }
+        self.results.iter().filter(|r| r.passed).count() as f64 / self.results.len() as f64
+    }
+
+    /// Mean latency of runs that got a response.
+    pub fn mean_latency(&self) -> Option<Duration> {
+        let answered: Vec<Duration> = self.results.iter().filter(|r| r.error.is_none()).map(|r| r.latency).collect();
+        let count = u32::try_from(answered.len()).ok().filter(|&n| n > 0)?;
+        Some(answered.iter().sum::<Duration>() / count)
+    }
+
+    /// Total cost, if every run's cost is known.
+    pub fn total_cost(&self) -> Option<f64> {
+        self.results.iter().map(|r| r.cost).sum()
+    }
+
+    /// Whether every run of `case` passed.
+    pub fn passed(&self, case: BenchCase) -> bool {
+        let mut runs = self.results.iter().filter(|r| r.case == case).peekable();
+        runs.peek().is_some() && runs.all(|r| r.passed)
+    }
+}
+
+/// A comparison of all targets.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    /// One report per target, in the order they were added.
+    pub targets: Vec<TargetReport>,
+}
+
+impl BenchReport {
+    /// Renders a Markdown table with one row per target.
+    pub fn to_markdown(&self) -> String {
+        let cases: Vec<BenchCase> = BenchCase::ALL
+            .into_iter()
+            .filter(|case| self.targets.iter().any(|t| t.results.iter().any(|r| r.case == *case)))
+            .collect();
+        let mut table = String::from("| target | model | accuracy | mean latency | cost |");
+        cases.iter().for_each(|case| table.push_str(&format!(" {} |", case.label())));
+        table.push_str("\n|---|---|---|---|---|");
+        cases.iter().for_each(|_| table.push_str("---|"));
+        for target in &self.targets {
+            let latency = target.mean_latency().map_or("-".to_string(), |d| format!("{} ms", d.as_millis()));
+            let cost = target.total_cost().map_or("-".to_string(), |c| format!("{:.4}", c));
+            let _ = write!(
+                table,
+                "\n| {} | {} | {:.0}% | {} | {} |",
+                target.name,
+                target.model,
+                target.accuracy() * 100.0,
+                latency,
+                cost
+            );
+            for case in &cases {
+                table.push_str(if target.passed(*case) { " pass |" } else { " fail |" });
+            }
+        }
+        table.push('\n');
+        table
+    }
+}
+
+/// Runs the benchmark battery across targets.
+pub struct ProviderBench {
+    targets: Vec<BenchTarget>,
+    cases: Vec<BenchCase>,
+    repetitions: usize,
+    timeout: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ProviderBench {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            cases: BenchCase::ALL.to_vec(),
+            repetitions: 1,
+            timeout: Duration::from_secs(120),
+            clock: default_clock(),
+        }
+    }
+}
+
+impl ProviderBench {
+    /// Creates a bench running every case once, with a 120 second timeout per request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a target (builder style).
+    pub fn with_target(mut self, target: BenchTarget) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    /// Runs only the given cases (builder style).
+    pub fn with_cases(mut self, cases: impl IntoIterator<Item = BenchCase>) -> Self {
+        self.cases = cases.into_iter().collect();
+        self
+    }
+
+    /// Runs every case `repetitions` times to smooth out latency noise (builder style).
+    pub fn with_repetitions(mut self, repetitions: usize) -> Self {
+        self.repetitions = repetitions.max(1);
+        self
+    }
+
+    /// Sets the time limit of each request (builder style).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Measures latency with `clock` instead of tokio's clock (builder style).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Runs all cases against all targets, one request at a time so latencies are not
+    /// skewed by contention. Failed requests count as failed cases.
+    pub async fn run(&self) -> BenchReport {
+        let mut targets = Vec::new();
+        for target in &self.targets {
+            let mut results = Vec::new();
+            for case in &self.cases {
+                for _ in 0..self.repetitions {
+                    results.push(self.run_case(target, *case).await);
+                }
+            }
+            targets.push(TargetReport { name: target.name.clone(), model: target.model.clone(), results });
+        }
+        BenchReport { targets }
+    }
+
+    async fn run_case(&self, target: &BenchTarget, case: BenchCase) -> CaseResult {
+        let request = case.request(&target.model);
+        let start = self.clock.now();
+        let mut first_token = None;
+        let outcome = tokio::time::timeout(self.timeout, async {
+            if case != BenchCase::Streaming {
+                return target.provider.completion(request).await;
+            }
+            let mut stream = target.provider.completion_stream(request).await?;
+            let mut collector = StreamCollector::new();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                if first_token.is_none() && matches!(&chunk.delta, StreamContentDelta::Text(t) if !t.is_empty()) {
+                    first_token = Some(self.clock.now() - start);
+                }
+                collector.push(&chunk);
+            }
+            Ok(collector.finish())
+        })
+        .await
+        .unwrap_or_else(|_| Err(ProviderError::TransportError(format!("Request timed out after {:?}", self.timeout))));
+        let latency = self.clock.now() - start;
+
+        match outcome {
+            Ok(response) => CaseResult {
+                case,
+                passed: case.check(&response),
+                latency,
+                first_token,
+                usage: response.usage,
+                cost: response.usage.zip(target.pricing).map(|(usage, pricing)| pricing.cost(&usage)),
+                error: None,
+            },
+            Err(error) => CaseResult {
+                case,
+                passed: false,
+                latency,
+                first_token,
+                usage: None,
+                cost: None,
+                error: Some(error.to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+
+    fn solving_mock() -> MockProvider {
+        MockProvider::new()
+            .with_tool_call("get_weather", r#"{"city": "Paris"}"#)
+            .with_message("```json\n{\"name\": \"Ada Lovelace\", \"age\": 36}\n```")
+            .with_message("7319")
+            .with_message("the stream works")
+            .with_latency(Duration::from_millis(200))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_compares_targets() {
+        let good = BenchTarget::new("good", Arc::new(solving_mock()), "good-model").with_pricing(1.0, 2.0);
+        let bad = BenchTarget::new("bad", Arc::new(MockProvider::new().with_message("no idea")), "bad-model");
+        let report = ProviderBench::new().with_target(good).with_target(bad).run().await;
+
+        let good = &report.targets[0];
+        assert_eq!(good.accuracy(), 1.0);
+        assert_eq!(good.mean_latency(), Some(Duration::from_millis(200)));
+        assert!(good.total_cost().unwrap() > 0.0);
+        assert!(good.results[3].first_token.is_some());
+
+        let bad = &report.targets[1];
+        assert_eq!(bad.accuracy(), 0.0);
+        assert_eq!(bad.results.iter().filter(|r| r.error.is_some()).count(), 3); // Script ran out
+        assert_eq!(bad.total_cost(), None);
+
+        let table = report.to_markdown();
+        assert!(table.contains("| good | good-model | 100% | 200 ms |"));
+        assert!(table.contains("| bad | bad-model | 0% |"));
+    }
+
+    #[tokio::test]
+    async fn test_runs_selected_cases() {
+        let mock = Arc::new(MockProvider::new().with_message("7319").with_message("7319"));
+        let report = ProviderBench::new()
+            .with_target(BenchTarget::new("local", mock.clone(), "m"))
+            .with_cases([BenchCase::LongContext])
+            .with_repetitions(2)
+            .run()
+            .await;
+
+        assert_eq!(report.targets[0].results.len(), 2);
+        assert!(report.targets[0].passed(BenchCase::LongContext));
+        assert!(mock.requests()[0].messages[0].content.as_ref().unwrap().len() > 10_000);
+    }
+}
@@ -0,0 +1,98 @@
+//! Runs an OpenAI-compatible gateway in front of providers configured from the
+//! environment.
+//!
+//! `MERCO_GATEWAY_ROUTES` lists route names (default `merco`). Each route `name` is
+//! configured like `LlmConfig::from_env("NAME")` (`NAME_PROVIDER`, `NAME_MODEL`,
+//! `NAME_API_KEY`, `NAME_BASE_URL`), optionally priced with `NAME_PRICE_INPUT` and
+//! `NAME_PRICE_OUTPUT` per million tokens. Set `MERCO_GATEWAY_API_KEY` to require clients
+//! to authenticate.
+
+use merco_llmproxy::{
+    get_provider, CacheMiddleware, CostMiddleware, Gateway, LayeredProvider, LlmConfig, Pricing, RetryMiddleware,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: merco-gateway [--addr <host:port>] [--retries <attempts>] [--cache <entries>]";
+
+struct Options {
+    addr: SocketAddr,
+    retries: u32,
+    cache: usize,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options { addr: ([127, 0, 0, 1], 8080).into(), retries: 3, cache: 0 };
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--addr" => options.addr = value.parse().map_err(|e| format!("Invalid --addr: {}", e))?,
+            "--retries" => options.retries = value.parse().map_err(|e| format!("Invalid --retries: {}", e))?,
+            "--cache" => options.cache = value.parse().map_err(|e| format!("Invalid --cache: {}", e))?,
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+    Ok(options)
+}
+
+fn price(name: &str) -> Result<f64, String> {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse().map_err(|e| format!("Invalid {}: {}", name, e)),
+        Err(_) => Ok(0.0),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let options = parse_args().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        std::process::exit(2);
+    });
+
+    let routes = std::env::var("MERCO_GATEWAY_ROUTES").unwrap_or_else(|_| "merco".to_string());
+    let mut gateway = Gateway::new();
+    let mut costs = Vec::new();
+    for name in routes.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let prefix = name.to_uppercase();
+        let config = LlmConfig::from_env(&prefix)?;
+        let model = config.model.clone();
+        let pricing = Pricing {
+            input_per_million: price(&format!("{}_PRICE_INPUT", prefix))?,
+            output_per_million: price(&format!("{}_PRICE_OUTPUT", prefix))?,
+        };
+
+        // Cost sits beneath the cache so cache hits are not billed
+        let cost = Arc::new(CostMiddleware::new(pricing));
+        let mut provider = LayeredProvider::new(get_provider(config)?)
+            .layer(Arc::new(RetryMiddleware::new(options.retries.max(1))))
+            .layer(cost.clone());
+        if options.cache > 0 {
+            provider = LayeredProvider::new(Arc::new(provider)).layer(Arc::new(CacheMiddleware::new(options.cache)));
+        }
+        gateway = match model {
+            Some(model) => gateway.with_route_model(name, Arc::new(provider), model),
+            None => gateway.with_route(name, Arc::new(provider)),
+        };
+        costs.push((name.to_string(), cost));
+    }
+    if let Ok(api_key) = std::env::var("MERCO_GATEWAY_API_KEY") {
+        gateway = gateway.with_api_key(api_key);
+    }
+
+    eprintln!("Serving OpenAI-compatible API on http://{}/v1", options.addr);
+    gateway
+        .serve(options.addr, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    for (name, cost) in costs {
+        let totals = cost.totals();
+        eprintln!(
+            "{}: {} responses, {} prompt + {} completion tokens, cost {:.4}",
+            name, totals.responses, totals.prompt_tokens, totals.completion_tokens, totals.cost
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,210 @@
+//!
+//! Blocking Client
+//!
+//! A synchronous facade over the async providers, in the spirit of `reqwest::blocking`.
+//! `LlmClient` owns a small tokio runtime and drives every call on it, so CLI tools and
+//! build scripts can request completions without running async code themselves.
+//!
+//! Like `reqwest::blocking`, the client must not be used from within an async runtime;
+//! calls made there return `ProviderError::Unsupported` instead of blocking the executor.
+
+use crate::config::LlmConfig;
+use crate::traits::{
+    ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, CompletionStream, CompletionStreamChunk,
+    LlmProvider, ProviderError,
+};
+use futures::stream::StreamExt;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// The client's runtime. It is shut down without waiting on drop, because dropping a
+/// runtime normally panics when that happens inside another runtime.
+struct ClientRuntime(Option<Runtime>);
+
+impl ClientRuntime {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.0.as_ref().expect("runtime is only taken on drop").block_on(future)
+    }
+}
+
+impl Drop for ClientRuntime {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// A synchronous client for one provider.
+///
+/// Cloning is cheap; clones share the provider and the runtime.
+#[derive(Clone)]
+pub struct LlmClient {
+    provider: Arc<dyn LlmProvider>,
+    runtime: Arc<ClientRuntime>,
+    model: Option<String>,
+}
+
+impl fmt::Debug for LlmClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LlmClient").field("model", &self.model).finish_non_exhaustive()
+    }
+}
+
+impl LlmClient {
+    /// Creates a client for the configured provider, defaulting requests to the
+    /// configuration's `model`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from `get_provider`, or `ProviderError::Unexpected` if the
+    /// runtime cannot be started.
+    pub fn new(config: LlmConfig) -> Result<Self, ProviderError> {
+        let model = config.model.clone();
+        let client = Self::from_provider(crate::get_provider(config)?)?;
+        Ok(Self { model, ..client })
+    }
+
+    /// Creates a client for an existing provider.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::Unexpected` if the runtime cannot be started.
+    pub fn from_provider(provider: Arc<dyn LlmProvider>) -> Result<Self, ProviderError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| ProviderError::Unexpected(format!("Failed to build runtime: {}", e)))?;
+        Ok(Self { provider, runtime: Arc::new(ClientRuntime(Some(runtime))), model: None })
+    }
+
+    /// Sets the model used by requests that leave `model` empty (builder style).
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// The underlying async provider.
+    pub fn provider(&self) -> &Arc<dyn LlmProvider> {
+        &self.provider
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> Result<F::Output, ProviderError> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Err(ProviderError::Unsupported(
+                "LlmClient cannot block inside an async runtime; use the async provider instead".to_string(),
+            ));
+        }
+        Ok(self.runtime.block_on(future))
+    }
+
+    fn with_default_model(&self, mut request: CompletionRequest) -> Result<CompletionRequest, ProviderError> {
+        if request.model.is_empty() {
+            request.model = self.model.clone().ok_or_else(|| ProviderError::MissingConfig("model".to_string()))?;
+        }
+        Ok(request)
+    }
+
+    /// Sends a completion request and waits for the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::MissingConfig` if neither the request nor the client names
+    /// a model, `ProviderError::Unsupported` inside an async runtime, or any provider error.
+    pub fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        let request = self.with_default_model(request)?;
+        self.block_on(self.provider.completion(request))?
+    }
+
+    /// Sends `prompt` as a single user message and returns the reply text.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::ToolFormatError` if the model answers with tool calls, or
+    /// any error from `completion`.
+    pub fn chat(&self, prompt: impl Into<String>) -> Result<String, ProviderError> {
+        let request = CompletionRequest { messages: vec![ChatMessage::user(prompt)], ..Default::default() };
+        match self.completion(request)?.kind {
+            CompletionKind::Message { content } => Ok(content),
+            CompletionKind::ToolCall { .. } => {
+                Err(ProviderError::ToolFormatError("Expected a text reply but the model called tools".to_string()))
+            }
+        }
+    }
+
+    /// Opens a streaming completion whose chunks are read by iterating the result.
+    ///
+    /// # Errors
+    ///
+    /// Same as `completion`; errors after the stream is open are yielded by the iterator.
+    pub fn completion_stream(&self, request: CompletionRequest) -> Result<BlockingStream, ProviderError> {
+        let request = self.with_default_model(request)?;
+        let stream = self.block_on(self.provider.completion_stream(request))??;
+        Ok(BlockingStream { stream, runtime: self.runtime.clone() })
+    }
+}
+
+/// A streaming completion read synchronously, one chunk per `next` call.
+pub struct BlockingStream {
+    stream: CompletionStream,
+    runtime: Arc<ClientRuntime>,
+}
+
+impl fmt::Debug for BlockingStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingStream").finish_non_exhaustive()
+    }
+}
+
+impl Iterator for BlockingStream {
+    type Item = Result<CompletionStreamChunk, ProviderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Some(Err(ProviderError::Unsupported(
+                "BlockingStream cannot block inside an async runtime".to_string(),
+            )));
+        }
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use crate::traits::StreamContentDelta;
+
+    #[test]
+    fn test_chat_and_stream_without_a_runtime() {
+        let provider = Arc::new(MockProvider::new().with_message("Hello there").with_message("Streamed reply"));
+        let client = LlmClient::from_provider(provider.clone()).unwrap().with_model("mock");
+
+        assert_eq!(client.chat("hi").unwrap(), "Hello there");
+        assert_eq!(provider.requests()[0].model, "mock");
+
+        let text: String = client
+            .completion_stream(CompletionRequest::default())
+            .unwrap()
+            .map(|chunk| match chunk.unwrap().delta {
+                StreamContentDelta::Text(text) => text,
+                _ => String::new(),
+            })
+            .collect();
+        assert_eq!(text, "Streamed reply");
+    }
+
+    #[test]
+    fn test_requires_a_model() {
+        let client = LlmClient::from_provider(Arc::new(MockProvider::new().with_message("unused"))).unwrap();
+        assert!(matches!(client.chat("hi"), Err(ProviderError::MissingConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn test_refuses_to_block_inside_a_runtime() {
+        let client = LlmClient::from_provider(Arc::new(MockProvider::new())).unwrap().with_model("mock");
+        assert!(matches!(client.chat("hi"), Err(ProviderError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,288 @@
+//!
+//! Request Builder
+//!
+//! A fluent alternative to filling in `CompletionRequest` fields by hand:
+//!
+//! ```
+//! use merco_llmproxy::CompletionRequest;
+//!
+//! let request = CompletionRequest::builder()
+//!     .model("gpt-4o-mini")
+//!     .system("You are terse.")
+//!     .user("Name a prime number.")
+//!     .temperature(0.2)
+//!     .max_tokens(16)
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(request.messages.len(), 2);
+//! ```
+//!
+//! `build` validates the request, so mistakes surface before anything is sent.
+
+use crate::traits::{ChatMessage, CompletionRequest, ReasoningEffort, ResponseFormat, Tool, ToolChoice};
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+/// Why a `CompletionRequest` is invalid.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum InvalidRequest {
+    /// No model was set.
+    #[error("Model is not set")]
+    MissingModel,
+    /// The request has no messages.
+    #[error("Request has no messages")]
+    NoMessages,
+    /// `max_tokens` is zero.
+    #[error("max_tokens must be greater than 0")]
+    ZeroMaxTokens,
+    /// `temperature` is outside `0.0..=2.0`.
+    #[error("temperature must be between 0 and 2, got {0}")]
+    Temperature(f32),
+    /// `top_p` is outside `0.0..=1.0`.
+    #[error("top_p must be between 0 and 1, got {0}")]
+    TopP(f32),
+    /// `top_logprobs` was set without enabling `logprobs`.
+    #[error("top_logprobs requires logprobs to be enabled")]
+    TopLogprobsWithoutLogprobs,
+    /// The tool choice names a tool that is not in the request.
+    #[error("tool_choice names unknown tool '{0}'")]
+    UnknownTool(String),
+}
+
+impl CompletionRequest {
+    /// Starts building a request.
+    pub fn builder() -> CompletionRequestBuilder {
+        CompletionRequestBuilder::default()
+    }
+
+    /// Checks the request for values providers would reject.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first problem found.
+    pub fn validate(&self) -> Result<(), InvalidRequest> {
+        if self.model.trim().is_empty() {
+            return Err(InvalidRequest::MissingModel);
+        }
+        if self.messages.is_empty() {
+            return Err(InvalidRequest::NoMessages);
+        }
+        if self.max_tokens == Some(0) {
+            return Err(InvalidRequest::ZeroMaxTokens);
+        }
+        if let Some(temperature) = self.temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+            return Err(InvalidRequest::Temperature(temperature));
+        }
+        if let Some(top_p) = self.top_p.filter(|p| !(0.0..=1.0).contains(p)) {
+            return Err(InvalidRequest::TopP(top_p));
+        }
+        if self.top_logprobs.is_some() && self.logprobs != Some(true) {
+            return Err(InvalidRequest::TopLogprobsWithoutLogprobs);
+        }
+        if let Some(ToolChoice::Function(name)) = &self.tool_choice {
+            let known = self.tools.iter().flatten().any(|tool| &tool.name == name);
+            if !known {
+                return Err(InvalidRequest::UnknownTool(name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds a `CompletionRequest`; see `CompletionRequest::builder`.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionRequestBuilder {
+    request: CompletionRequest,
+}
+
+impl CompletionRequestBuilder {
+    /// Sets the model identifier.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.request.model = model.into();
+        self
+    }
+
+    /// Appends a message.
+    pub fn message(mut self, message: ChatMessage) -> Self {
+        self.request.messages.push(message);
+        self
+    }
+
+    /// Appends several messages.
+    pub fn messages(mut self, messages: impl IntoIterator<Item = ChatMessage>) -> Self {
+        self.request.messages.extend(messages);
+        self
+    }
+
+    /// Appends a system message.
+    pub fn system(self, content: impl Into<String>) -> Self {
+        self.message(ChatMessage::system(content.into()))
+    }
+
+    /// Appends a user message.
+    pub fn user(self, content: impl Into<String>) -> Self {
+        self.message(ChatMessage::user(content.into()))
+    }
+
+    /// Sets the sampling temperature.
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.request.temperature = Some(temperature);
+        self
+    }
+
+    /// Sets the maximum number of tokens to generate.
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.request.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the nucleus sampling probability mass.
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.request.top_p = Some(top_p);
+        self
+    }
+
+    /// Sets the frequency penalty.
+    pub fn frequency_penalty(mut self, penalty: f32) -> Self {
+        self.request.frequency_penalty = Some(penalty);
+        self
+    }
+
+    /// Sets the presence penalty.
+    pub fn presence_penalty(mut self, penalty: f32) -> Self {
+        self.request.presence_penalty = Some(penalty);
+        self
+    }
+
+    /// Sets the sampling seed.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.request.seed = Some(seed);
+        self
+    }
+
+    /// Adds a stop sequence.
+    pub fn stop(mut self, sequence: impl Into<String>) -> Self {
+        self.request.stop.get_or_insert_with(Vec::new).push(sequence.into());
+        self
+    }
+
+    /// Requests log probabilities, with `top` alternatives per token if given.
+    pub fn logprobs(mut self, top: Option<u8>) -> Self {
+        self.request.logprobs = Some(true);
+        self.request.top_logprobs = top;
+        self
+    }
+
+    /// Adds a provider-specific body parameter.
+    pub fn extra(mut self, key: impl Into<String>, value: JsonValue) -> Self {
+        self.request.extra.insert(key.into(), value);
+        self
+    }
+
+    /// Sets Ollama-specific model options. They are stored in `extra`, which other
+    /// providers send as-is, so only use this for Ollama requests.
+    #[cfg(feature = "ollama")]
+    pub fn ollama_options(mut self, options: crate::providers::OllamaOptions) -> Self {
+        options.apply(&mut self.request);
+        self
+    }
+
+    /// Adds a tool the model may call.
+    pub fn tool(mut self, tool: Tool) -> Self {
+        self.request.tools.get_or_insert_with(Vec::new).push(tool);
+        self
+    }
+
+    /// Adds several tools the model may call.
+    pub fn tools(mut self, tools: impl IntoIterator<Item = Tool>) -> Self {
+        self.request.tools.get_or_insert_with(Vec::new).extend(tools);
+        self
+    }
+
+    /// Sets how the model chooses tools.
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.request.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Sets the required output format.
+    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.request.response_format = Some(response_format);
+        self
+    }
+
+    /// Sets the reasoning effort for reasoning models.
+    pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.request.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Enables extended thinking with the given token budget.
+    pub fn thinking_budget(mut self, budget_tokens: u32) -> Self {
+        self.request.thinking_budget = Some(budget_tokens);
+        self
+    }
+
+    /// Validates and returns the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidRequest` describing the first problem found.
+    pub fn build(self) -> Result<CompletionRequest, InvalidRequest> {
+        self.request.validate()?;
+        Ok(self.request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::JsonSchema;
+
+    fn builder() -> CompletionRequestBuilder {
+        CompletionRequest::builder().model("test-model").user("Hello")
+    }
+
+    #[test]
+    fn test_builds_request() {
+        let request = builder()
+            .system("Be brief.")
+            .temperature(0.5)
+            .max_tokens(100)
+            .stop("\n\n")
+            .stop("END")
+            .logprobs(Some(3))
+            .build()
+            .unwrap();
+
+        assert_eq!(request.model, "test-model");
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.temperature, Some(0.5));
+        assert_eq!(request.stop, Some(vec!["\n\n".to_string(), "END".to_string()]));
+        assert_eq!((request.logprobs, request.top_logprobs), (Some(true), Some(3)));
+    }
+
+    #[test]
+    fn test_rejects_invalid_requests() {
+        assert_eq!(CompletionRequest::builder().user("Hi").build().unwrap_err(), InvalidRequest::MissingModel);
+        assert_eq!(CompletionRequest::builder().model("m").build().unwrap_err(), InvalidRequest::NoMessages);
+        assert_eq!(builder().max_tokens(0).build().unwrap_err(), InvalidRequest::ZeroMaxTokens);
+        assert_eq!(builder().temperature(3.0).build().unwrap_err(), InvalidRequest::Temperature(3.0));
+        assert_eq!(builder().top_p(-0.1).build().unwrap_err(), InvalidRequest::TopP(-0.1));
+        assert_eq!(
+            builder().tool_choice(ToolChoice::Function("search".to_string())).build().unwrap_err(),
+            InvalidRequest::UnknownTool("search".to_string())
+        );
+    }
+
+    #[test]
+    fn test_forced_tool_must_exist() {
+        let tool = Tool {
+            name: "search".to_string(),
+            description: "Search the web".to_string(),
+            parameters: JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        let request = builder().tool(tool).tool_choice(ToolChoice::Function("search".to_string())).build();
+        assert!(request.is_ok());
+    }
+}
@@ -0,0 +1,387 @@
+//!
+//! Code Execution Tool
+//!
+//! `run_code` executes model-generated Python or JavaScript in a child process and
+//! returns its exit code, stdout and stderr. The process is confined by resource limits
+//! rather than a full sandbox:
+//!
+//! * It starts with an empty environment (only `PATH` and `HOME` are set) in the run's
+//!   workspace, or in a fresh temporary directory removed afterwards.
+//! * On Unix, `ulimit` caps its CPU time, address space, file sizes and open files. In a
+//!   workspace with a size cap, no file may grow past what is left of it.
+//! * A wall-clock timeout kills it, and output is read only up to a size limit. On Unix
+//!   it runs in its own process group, which is killed as a whole, so processes it
+//!   starts don't outlive it.
+//!
+//! The process can still use the network and read files its user can read, so run
+//! agents with this tool under an unprivileged user or inside a container.
+
+use crate::tools::{register_tool, ToolContext, ToolError, ToolExecutor, ToolRegistry};
+use crate::traits::{JsonSchema, Tool};
+use super::process::{capture_output, CodeOutput};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Name of the tool as offered to the model.
+pub const CODE_TOOL_NAME: &str = "run_code";
+
+/// A language `run_code` can execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeLanguage {
+    /// Python 3, run as `python3 -I -`.
+    Python,
+    /// JavaScript, run as `node -`.
+    JavaScript,
+}
+
+impl CodeLanguage {
+    fn name(self) -> &'static str {
+        match self {
+            CodeLanguage::Python => "python",
+            CodeLanguage::JavaScript => "javascript",
+        }
+    }
+}
+
+/// How to start the interpreter of one language; the code is written to its stdin.
+#[derive(Debug, Clone)]
+struct Interpreter {
+    language: CodeLanguage,
+    program: String,
+    args: Vec<String>,
+}
+
+/// Configuration of the `run_code` tool.
+#[derive(Debug, Clone)]
+pub struct CodeInterpreter {
+    interpreters: Vec<Interpreter>,
+    timeout: Duration,
+    max_cpu_secs: u64,
+    max_memory_bytes: u64,
+    max_file_bytes: u64,
+    max_output_bytes: usize,
+}
+
+impl Default for CodeInterpreter {
+    fn default() -> Self {
+        Self {
+            interpreters: vec![
+                Interpreter { language: CodeLanguage::Python, program: "python3".to_string(), args: vec!["-I".to_string(), "-".to_string()] },
+                Interpreter { language: CodeLanguage::JavaScript, program: "node".to_string(), args: vec!["-".to_string()] },
+            ],
+            timeout: Duration::from_secs(30),
+            max_cpu_secs: 30,
+            max_memory_bytes: 1024 * 1024 * 1024,
+            max_file_bytes: 64 * 1024 * 1024,
+            max_output_bytes: 64 * 1024,
+        }
+    }
+}
+
+impl CodeInterpreter {
+    /// Creates the tool for Python (`python3`) and JavaScript (`node`) with a 30 second
+    /// timeout, 30 seconds of CPU time, 1 GiB of memory and 64 KiB of output per stream.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `language` with `program` and `args` instead of the default command; the
+    /// arguments must make it read the code from stdin (builder style).
+    pub fn with_interpreter(mut self, language: CodeLanguage, program: impl Into<String>, args: &[&str]) -> Self {
+        self.interpreters.retain(|interpreter| interpreter.language != language);
+        self.interpreters.push(Interpreter {
+            language,
+            program: program.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        });
+        self
+    }
+
+    /// Only offers `languages` (builder style).
+    pub fn with_languages(mut self, languages: &[CodeLanguage]) -> Self {
+        self.interpreters.retain(|interpreter| languages.contains(&interpreter.language));
+        self
+    }
+
+    /// Sets the wall-clock limit of one execution (builder style).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the CPU time limit in seconds (builder style; Unix only).
+    pub fn with_max_cpu_secs(mut self, secs: u64) -> Self {
+        self.max_cpu_secs = secs;
+        self
+    }
+
+    /// Sets the address space limit in bytes (builder style; Unix only).
+    pub fn with_max_memory_bytes(mut self, bytes: u64) -> Self {
+        self.max_memory_bytes = bytes;
+        self
+    }
+
+    /// Sets the largest file the code may write, in bytes (builder style; Unix only).
+    pub fn with_max_file_bytes(mut self, bytes: u64) -> Self {
+        self.max_file_bytes = bytes;
+        self
+    }
+
+    /// Sets how much of stdout and of stderr is kept, in bytes each (builder style).
+    pub fn with_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = bytes;
+        self
+    }
+
+    /// The tool definition offered to the model.
+    pub fn tool(&self) -> Tool {
+        let languages: Vec<&str> = self.interpreters.iter().map(|interpreter| interpreter.language.name()).collect();
+        let mut properties = serde_json::Map::new();
+        properties.insert(
+            "language".to_string(),
+            serde_json::json!({ "type": "string", "enum": languages, "description": "Language of the code" }),
+        );
+        properties.insert(
+            "code".to_string(),
+            serde_json::json!({ "type": "string", "description": "The complete program; print results to stdout" }),
+        );
+        Tool {
+            name: CODE_TOOL_NAME.to_string(),
+            description: format!(
+                "Runs a {} program and returns its exit code, stdout and stderr. Runs time out after {} seconds.",
+                languages.join(" or "),
+                self.timeout.as_secs()
+            ),
+            parameters: JsonSchema {
+                schema_type: "object".to_string(),
+                properties: Some(properties),
+                required: Some(vec!["language".to_string(), "code".to_string()]),
+            },
+        }
+    }
+
+    /// An executor running `run` with the language and code from the tool arguments.
+    pub fn executor(&self) -> ToolExecutor {
+        #[derive(Deserialize)]
+        struct Args {
+            language: CodeLanguage,
+            code: String,
+        }
+
+        let interpreter = Arc::new(self.clone());
+        Arc::new(move |args: String| {
+            let interpreter = interpreter.clone();
+            Box::pin(async move {
+                let args: Args = serde_json::from_str(&args)
+                    .map_err(|e| format!("Failed to parse arguments for {}: {}", CODE_TOOL_NAME, e))?;
+                let output = interpreter.run(args.language, &args.code).await.map_err(|e| e.to_payload())?;
+                Ok(output.to_report())
+            })
+        })
+    }
+
+    /// Registers the tool in `registry`.
+    pub fn register(&self, registry: &mut ToolRegistry) {
+        registry.register(self.tool(), self.executor());
+    }
+
+    /// Registers the tool in the global registry.
+    pub fn register_global(&self) {
+        register_tool(self.tool(), self.executor());
+    }
+
+    /// Runs `code` in the current run's workspace, or in a fresh temporary directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ToolError` if the language is not enabled, the interpreter cannot be
+    /// started, the run exceeds the timeout, or it leaves the workspace over its size
+    /// cap. A program that fails is not an error: its exit code and stderr are in the
+    /// `CodeOutput`.
+    pub async fn run(&self, language: CodeLanguage, code: &str) -> Result<CodeOutput, ToolError> {
+        let interpreter = self
+            .interpreters
+            .iter()
+            .find(|interpreter| interpreter.language == language)
+            .ok_or_else(|| ToolError::new(format!("{} is not enabled", language.name())))?;
+
+        let context = ToolContext::current();
+        match &context.workspace {
+            Some(workspace) => {
+                // No single file may outgrow what is left of the workspace's size cap
+                let max_file_bytes = context.workspace_quota().map_or(self.max_file_bytes, |quota| quota.min(self.max_file_bytes));
+                let output = self.run_in(interpreter, code, workspace, max_file_bytes).await?;
+                context.check_workspace_size().map_err(ToolError::new)?;
+                Ok(output)
+            }
+            None => {
+                // Randomly named and created exclusively, so another user can't prepare it
+                let dir = tempfile::Builder::new()
+                    .prefix("merco-code-")
+                    .tempdir()
+                    .map_err(|e| ToolError::new(format!("Failed to create a temporary directory: {}", e)))?;
+                self.run_in(interpreter, code, dir.path(), self.max_file_bytes).await
+            }
+        }
+    }
+
+    async fn run_in(
+        &self,
+        interpreter: &Interpreter,
+        code: &str,
+        dir: &Path,
+        max_file_bytes: u64,
+    ) -> Result<CodeOutput, ToolError> {
+        let mut command = self.command(interpreter, max_file_bytes);
+        command
+            .current_dir(dir)
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("HOME", dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut child = command
+            .spawn()
+            .map_err(|e| ToolError::new(format!("Failed to start {}: {}", interpreter.program, e)))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let code = code.to_string();
+        // Written concurrently, so a program that prints before reading all input can't deadlock
+        tokio::spawn(async move {
+            let _ = stdin.write_all(code.as_bytes()).await;
+        });
+
+        match capture_output(child, self.timeout, self.max_output_bytes).await {
+            Ok(output) => output.map_err(|e| ToolError::new(format!("Failed to run {}: {}", interpreter.program, e))),
+            Err(_) => Err(ToolError::new(format!("Execution timed out after {} seconds", self.timeout.as_secs_f32()))),
+        }
+    }
+
+    #[cfg(unix)]
+    fn command(&self, interpreter: &Interpreter, max_file_bytes: u64) -> tokio::process::Command {
+        // The limits are set by the shell, which then replaces itself with the interpreter.
+        // POSIX `ulimit -f` counts 512-byte blocks; `-v` is in KiB
+        let limits = format!(
+            "ulimit -t {} && ulimit -v {} && ulimit -f {} && ulimit -n 256 && exec \"$0\" \"$@\"",
+            self.max_cpu_secs.max(1),
+            (self.max_memory_bytes / 1024).max(1),
+            (max_file_bytes / 512).max(1),
+        );
+        let mut command = tokio::process::Command::new("sh");
+        command.arg("-c").arg(limits).arg(&interpreter.program).args(&interpreter.args);
+        command
+    }
+
+    #[cfg(not(unix))]
+    fn command(&self, interpreter: &Interpreter, _max_file_bytes: u64) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&interpreter.program);
+        command.args(&interpreter.args);
+        command
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    // `sh -s` reads its script from stdin, like the real interpreters, so the tests
+    // don't depend on Python or Node being installed
+    fn shell_interpreter() -> CodeInterpreter {
+        CodeInterpreter::new().with_interpreter(CodeLanguage::Python, "sh", &["-s"]).with_languages(&[CodeLanguage::Python])
+    }
+
+    #[tokio::test]
+    async fn test_captures_output_and_exit_code() {
+        let mut registry = ToolRegistry::new();
+        shell_interpreter().register(&mut registry);
+        let args = serde_json::json!({ "language": "python", "code": "echo out; echo err >&2; pwd; exit 3" });
+
+        let report = registry.execute_tool(CODE_TOOL_NAME, &args.to_string()).await.unwrap();
+        assert!(report.starts_with("exit code: 3\n--- stdout ---\nout\n"), "{}", report);
+        assert!(report.contains("merco-code-"), "{}", report);
+        assert!(report.ends_with("--- stderr ---\nerr\n"), "{}", report);
+
+        let args = serde_json::json!({ "language": "javascript", "code": "1" });
+        let error = registry.execute_tool(CODE_TOOL_NAME, &args.to_string()).await.unwrap_err();
+        assert!(error.contains("must be one of"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_runs_in_the_workspace_with_a_clean_environment() {
+        let workspace = std::env::temp_dir().join(format!("merco-code-test-{}", std::process::id()));
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::env::set_var("MERCO_CODE_TEST_SECRET", "hunter2");
+
+        let context = ToolContext { workspace: Some(workspace.clone()), ..Default::default() };
+        let output = context
+            .scope(shell_interpreter().run(CodeLanguage::Python, "echo \"secret=$MERCO_CODE_TEST_SECRET\" > out.txt"))
+            .await
+            .unwrap();
+        assert_eq!(output.exit_code, Some(0));
+        assert_eq!(std::fs::read_to_string(workspace.join("out.txt")).unwrap(), "secret=\n");
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_limits_time_and_output() {
+        let interpreter = shell_interpreter().with_timeout(Duration::from_millis(200)).with_max_output_bytes(10);
+        let error = interpreter.run(CodeLanguage::Python, "while :; do :; done").await.unwrap_err();
+        assert!(error.error.contains("timed out"), "{}", error);
+
+        let output = interpreter.run(CodeLanguage::Python, "echo 0123456789abcdef").await.unwrap();
+        assert_eq!(output.stdout, "0123456789\n[Truncated after 10 bytes]");
+
+        // Endless output is cut off at the limit instead of piling up until the timeout
+        let interpreter = shell_interpreter().with_timeout(Duration::from_secs(10)).with_max_output_bytes(1000);
+        let output = interpreter.run(CodeLanguage::Python, "while :; do echo yyyyyyy; done").await.unwrap();
+        assert_eq!(output.stdout, format!("{}\n[Truncated after 1000 bytes]", "yyyyyyy\n".repeat(125)));
+    }
+
+    #[tokio::test]
+    async fn test_stays_within_the_workspace_size_cap() {
+        let workspace = std::env::temp_dir().join(format!("merco-code-quota-{}", std::process::id()));
+        std::fs::create_dir_all(&workspace).unwrap();
+        let context = ToolContext { workspace: Some(workspace.clone()), max_workspace_bytes: Some(4096), ..Default::default() };
+        let interpreter = shell_interpreter();
+
+        // A file can't outgrow the cap...
+        let output = context.clone().scope(interpreter.run(CodeLanguage::Python, "head -c 100000 /dev/zero > big")).await;
+        assert_ne!(output.unwrap().exit_code, Some(0));
+        assert!(std::fs::metadata(workspace.join("big")).unwrap().len() <= 4096);
+        std::fs::remove_file(workspace.join("big")).unwrap();
+
+        // ...and files that fit on their own but not together fail the run
+        let script = "head -c 3000 /dev/zero > one; head -c 3000 /dev/zero > two";
+        let error = context.scope(interpreter.run(CodeLanguage::Python, script)).await.unwrap_err();
+        assert!(error.error.contains("over its size cap of 4096 bytes"), "{}", error);
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_kills_background_processes() {
+        let workspace = std::env::temp_dir().join(format!("merco-code-group-{}", std::process::id()));
+        std::fs::create_dir_all(&workspace).unwrap();
+        let context = ToolContext { workspace: Some(workspace.clone()), ..Default::default() };
+        let interpreter = shell_interpreter().with_timeout(Duration::from_millis(200));
+
+        // One grandchild outlives a timed out run, the other a finished one
+        let error = context.clone().scope(interpreter.run(CodeLanguage::Python, "(sleep 1; touch late) & sleep 5")).await;
+        assert!(error.unwrap_err().error.contains("timed out"));
+        let output = context.scope(interpreter.run(CodeLanguage::Python, "(sleep 1; touch later) &")).await.unwrap();
+        assert_eq!(output.exit_code, Some(0));
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!workspace.join("late").exists());
+        assert!(!workspace.join("later").exists());
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+}
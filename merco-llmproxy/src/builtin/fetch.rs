@@ -0,0 +1,373 @@
+//!
+//! Fetch Tool
+//!
+//! `fetch_page` downloads a web page and returns its readable text, so agents can read
+//! documentation without custom HTTP code. Only hosts on the allowlist can be fetched
+//! (redirects included), bodies are cut off at a size limit, and every request has a
+//! timeout. HTML is reduced to plain text; other text responses are returned as-is.
+
+use crate::tools::{register_tool, ToolError, ToolExecutor, ToolRegistry};
+use crate::traits::{JsonSchema, Tool};
+use futures::StreamExt;
+use reqwest::Url;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Name of the tool as offered to the model.
+pub const FETCH_TOOL_NAME: &str = "fetch_page";
+
+/// Elements whose content is never shown as text.
+const HIDDEN_ELEMENTS: &[&str] = &["script", "style", "noscript", "template", "svg"];
+
+/// Elements that start a new line in the extracted text.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "br", "li", "ul", "ol", "tr", "table", "section", "article", "header", "footer", "nav", "main", "h1",
+    "h2", "h3", "h4", "h5", "h6", "pre", "blockquote", "hr", "title", "dt", "dd",
+];
+
+/// Configuration of the `fetch_page` tool.
+#[derive(Debug, Clone)]
+pub struct FetchTool {
+    allowed_hosts: Vec<String>,
+    allow_any_host: bool,
+    max_bytes: usize,
+    timeout: Duration,
+    extract_text: bool,
+}
+
+impl Default for FetchTool {
+    fn default() -> Self {
+        Self { allowed_hosts: Vec::new(), allow_any_host: false, max_bytes: 1024 * 1024, timeout: Duration::from_secs(30), extract_text: true }
+    }
+}
+
+impl FetchTool {
+    /// Creates the tool with an empty allowlist, a 1 MiB body limit and a 30 second
+    /// timeout. Add hosts with `with_allowed_host` before use.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows fetching from `host` (builder style). `docs.rs` allows exactly that host;
+    /// `*.example.com` allows its subdomains, but not `example.com` itself.
+    pub fn with_allowed_host(mut self, host: impl Into<String>) -> Self {
+        self.allowed_hosts.push(host.into().to_lowercase());
+        self
+    }
+
+    /// Allows fetching from any host (builder style). Only use this where the agent
+    /// cannot reach internal services.
+    pub fn allow_any_host(mut self) -> Self {
+        self.allow_any_host = true;
+        self
+    }
+
+    /// Sets the number of body bytes read before the page is cut off (builder style).
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets the timeout of the whole request, body included (builder style).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether HTML is reduced to text; disable to get raw markup (builder style).
+    pub fn with_text_extraction(mut self, enabled: bool) -> Self {
+        self.extract_text = enabled;
+        self
+    }
+
+    /// Whether `url` may be fetched: it must be `http` or `https` with an allowed host.
+    pub fn is_allowed(&self, url: &Url) -> bool {
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        let Some(host) = url.host_str().map(str::to_lowercase) else { return false };
+        self.allow_any_host
+            || self.allowed_hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+                None => host == *allowed,
+            })
+    }
+
+    /// The tool definition offered to the model.
+    pub fn tool(&self) -> Tool {
+        let mut properties = serde_json::Map::new();
+        properties.insert(
+            "url".to_string(),
+            serde_json::json!({ "type": "string", "description": "The http(s) URL of the page to read" }),
+        );
+        Tool {
+            name: FETCH_TOOL_NAME.to_string(),
+            description: "Fetches a web page and returns its text content".to_string(),
+            parameters: JsonSchema {
+                schema_type: "object".to_string(),
+                properties: Some(properties),
+                required: Some(vec!["url".to_string()]),
+            },
+        }
+    }
+
+    /// An executor running `fetch` with the URL from the tool arguments.
+    pub fn executor(&self) -> ToolExecutor {
+        let fetch = Arc::new(self.clone());
+        Arc::new(move |args: String| {
+            let fetch = fetch.clone();
+            Box::pin(async move {
+                let args: serde_json::Value = serde_json::from_str(&args)
+                    .map_err(|e| format!("Failed to parse arguments for {}: {}", FETCH_TOOL_NAME, e))?;
+                let url = args.get("url").and_then(|url| url.as_str()).unwrap_or_default();
+                fetch.fetch(url).await.map_err(|e| e.to_payload())
+            })
+        })
+    }
+
+    /// Registers the tool in `registry`.
+    pub fn register(&self, registry: &mut ToolRegistry) {
+        registry.register(self.tool(), self.executor());
+    }
+
+    /// Registers the tool in the global registry.
+    pub fn register_global(&self) {
+        register_tool(self.tool(), self.executor());
+    }
+
+    /// Fetches `url` and returns its text.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ToolError` if the URL or a redirect target is not allowed, the request
+    /// fails or times out (retryable), or the server answers with an error status
+    /// (retryable for 408, 429 and 5xx).
+    pub async fn fetch(&self, url: &str) -> Result<String, ToolError> {
+        let url = Url::parse(url).map_err(|e| ToolError::new(format!("Invalid URL '{}': {}", url, e)))?;
+        if !self.is_allowed(&url) {
+            return Err(ToolError::new(format!("Fetching {} is not allowed", url)));
+        }
+
+        // Redirects are followed only while they stay on allowed hosts
+        let policy = self.clone();
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 10 || !policy.is_allowed(attempt.url()) {
+                    attempt.stop()
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()
+            .map_err(|e| ToolError::new(format!("Failed to build HTTP client: {}", e)))?;
+
+        let response = client.get(url.clone()).send().await.map_err(|e| ToolError::retryable(format!("Request failed: {}", e)))?;
+        let status = response.status();
+        if status.is_redirection() {
+            let target = response.headers().get("location").and_then(|l| l.to_str().ok()).unwrap_or("an unknown location");
+            return Err(ToolError::new(format!("{} redirects to {}, which is not allowed", url, target)));
+        }
+        if !status.is_success() {
+            let message = format!("HTTP {} fetching {}", status.as_u16(), url);
+            let retryable = status.as_u16() == 408 || status.as_u16() == 429 || status.is_server_error();
+            return Err(if retryable { ToolError::retryable(message) } else { ToolError::new(message) });
+        }
+        let is_html = response
+            .headers()
+            .get("content-type")
+            .and_then(|t| t.to_str().ok())
+            .is_some_and(|t| t.contains("html"));
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| ToolError::retryable(format!("Failed to read {}: {}", url, e)))?;
+            let room = self.max_bytes - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let body = String::from_utf8_lossy(&body);
+        let mut text = if is_html && self.extract_text { html_to_text(&body) } else { body.into_owned() };
+        if truncated {
+            text.push_str(&format!("\n\n[Truncated after {} bytes]", self.max_bytes));
+        }
+        Ok(text)
+    }
+}
+
+/// Reduces an HTML document to readable text: scripts, styles and comments are dropped, block elements become line breaks, entities are decoded and
+/// whitespace is collapsed.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut hidden: Option<String> = None;
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        if hidden.is_none() {
+            text.push_str(&rest[..start]);
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        match &hidden {
+            Some(element) if closing && *element == name => hidden = None,
+            Some(_) => {}
+            // The title is the one part of the head worth keeping
+            None if name == "title" && !closing => {
+                if let Some(end) = rest.to_ascii_lowercase().find("</title>") {
+                    text.push_str(&format!("\n{}\n", &rest[..end]));
+                    rest = &rest[end + "</title>".len()..];
+                }
+            }
+            None if !closing && HIDDEN_ELEMENTS.contains(&name.as_str()) && !tag.ends_with('/') => hidden = Some(name),
+            None if BLOCK_ELEMENTS.contains(&name.as_str()) => text.push('\n'),
+            None => {}
+        }
+    }
+    if hidden.is_none() {
+        text.push_str(rest);
+    }
+
+    let text = decode_entities(&text);
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.join("\n")
+}
+
+/// Decodes the named entities common in prose and all numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|end| *end <= 10).map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => match entity.strip_prefix('#') {
+                Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok().and_then(char::from_u32),
+                Some(decimal) => decimal.parse().ok().and_then(char::from_u32),
+                None => None,
+            },
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Serves one canned HTTP response on a free port and returns the base URL
+    async fn serve_once(response: String) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 4096];
+            let _ = socket.read(&mut buffer).await.unwrap();
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    fn html_response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/html; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    }
+
+    #[test]
+    fn test_html_to_text() {
+        let html = r#"<!DOCTYPE html><html><head><title>Guide &amp; Tips</title><style>p { color: red; }</style>
+            <script>var x = "<p>not text</p>";</script></head>
+            <body><!-- nav --><h1>Intro</h1><p>Use   <code>cargo&nbsp;build</code> &lt;here&gt;.</p>
+            <ul><li>One</li><li>Two &#8212; &#x2713;</li></ul></body></html>"#;
+        assert_eq!(html_to_text(html), "Guide & Tips\nIntro\nUse cargo build <here>.\nOne\nTwo — ✓");
+        assert_eq!(decode_entities("AT&T &unknown; &"), "AT&T &unknown; &");
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let fetch = FetchTool::new().with_allowed_host("docs.rs").with_allowed_host("*.example.com");
+        let allowed = |url: &str| fetch.is_allowed(&Url::parse(url).unwrap());
+
+        assert!(allowed("https://docs.rs/serde"));
+        assert!(allowed("http://api.example.com/v1"));
+        assert!(!allowed("https://example.com/"));
+        assert!(!allowed("https://notexample.com/"));
+        assert!(!allowed("https://docs.rs.evil.com/"));
+        assert!(!allowed("file:///etc/passwd"));
+        assert!(!FetchTool::new().is_allowed(&Url::parse("https://docs.rs").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_fetches_text_with_a_size_limit() {
+        let url = serve_once(html_response("<html><body><p>Hello</p><p>world, and a long tail</p></body></html>")).await;
+        let fetch = FetchTool::new().with_allowed_host("127.0.0.1");
+        let mut registry = ToolRegistry::new();
+        fetch.register(&mut registry);
+        let text = registry.execute_tool(FETCH_TOOL_NAME, &serde_json::json!({ "url": url }).to_string()).await.unwrap();
+        assert_eq!(text, "Hello\nworld, and a long tail");
+
+        let url = serve_once(html_response("<p>Hello</p><p>world, and a long tail</p>")).await;
+        let text = fetch.with_max_bytes(20).fetch(&url).await.unwrap();
+        assert_eq!(text, "Hello\nworld\n\n[Truncated after 20 bytes]");
+    }
+
+    #[tokio::test]
+    async fn test_refuses_disallowed_hosts_and_redirects() {
+        let fetch = FetchTool::new().with_allowed_host("127.0.0.1");
+        let error = fetch.fetch("https://example.com/").await.unwrap_err();
+        assert!(error.error.contains("not allowed"));
+
+        let redirect = "HTTP/1.1 302 Found\r\nlocation: http://example.com/\r\ncontent-length: 0\r\n\r\n".to_string();
+        let error = fetch.fetch(&serve_once(redirect).await).await.unwrap_err();
+        assert!(error.error.ends_with("redirects to http://example.com/, which is not allowed"), "{}", error);
+    }
+}
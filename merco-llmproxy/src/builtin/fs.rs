@@ -0,0 +1,419 @@
+//!
+//! File System Tools
+//!
+//! `read_file`, `write_file` and `list_dir` give agents file access confined to
+//! configured root directories. Paths from the model are resolved with `resolve_in`, so
+//! absolute paths, `..` and symlinks leading out of a root are rejected. Without
+//! configured roots, the tools work in the current run's workspace (`ToolContext`).
+//!
+//! With several roots, a path starts with the name of its root (`docs/guide.md`); with
+//! one, paths are relative to it. Reads are capped and binary files are refused; writes
+//! are capped too and can be disabled altogether.
+
+use crate::paths::resolve_in;
+use crate::tools::{register_tool, sync_executor, ToolContext, ToolExecutor, ToolRegistry};
+use crate::traits::{JsonSchema, Tool};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Name of the tool reading a file.
+pub const READ_FILE_TOOL_NAME: &str = "read_file";
+/// Name of the tool writing a file.
+pub const WRITE_FILE_TOOL_NAME: &str = "write_file";
+/// Name of the tool listing a directory.
+pub const LIST_DIR_TOOL_NAME: &str = "list_dir";
+
+/// Number of leading bytes inspected to tell binary files from text.
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// Configuration of the file system tools.
+#[derive(Debug, Clone)]
+pub struct FileSystemTools {
+    roots: Vec<(String, PathBuf)>,
+    read_only: bool,
+    max_read_bytes: u64,
+    max_write_bytes: usize,
+    max_entries: usize,
+}
+
+impl Default for FileSystemTools {
+    fn default() -> Self {
+        Self { roots: Vec::new(), read_only: false, max_read_bytes: 256 * 1024, max_write_bytes: 1024 * 1024, max_entries: 500 }
+    }
+}
+
+impl FileSystemTools {
+    /// Creates the tools for the run's workspace, reading up to 256 KiB per file and
+    /// writing up to 1 MiB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Confines the tools to `dir` instead of the workspace (builder style). `name`
+    /// prefixes its paths when more than one root is configured.
+    pub fn with_root(mut self, name: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        self.roots.push((name.into(), dir.into()));
+        self
+    }
+
+    /// Leaves out `write_file` (builder style).
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Sets how many bytes `read_file` returns before truncating (builder style).
+    pub fn with_max_read_bytes(mut self, bytes: u64) -> Self {
+        self.max_read_bytes = bytes;
+        self
+    }
+
+    /// Sets the largest content `write_file` accepts, in bytes (builder style).
+    pub fn with_max_write_bytes(mut self, bytes: usize) -> Self {
+        self.max_write_bytes = bytes;
+        self
+    }
+
+    /// Sets how many entries `list_dir` returns (builder style).
+    pub fn with_max_entries(mut self, entries: usize) -> Self {
+        self.max_entries = entries;
+        self
+    }
+
+    /// The tool definitions offered to the model.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.tools_and_executors().into_iter().map(|(tool, _)| tool).collect()
+    }
+
+    /// Registers the tools in `registry`.
+    pub fn register(&self, registry: &mut ToolRegistry) {
+        for (tool, executor) in self.tools_and_executors() {
+            registry.register(tool, executor);
+        }
+    }
+
+    /// Registers the tools in the global registry.
+    pub fn register_global(&self) {
+        for (tool, executor) in self.tools_and_executors() {
+            register_tool(tool, executor);
+        }
+    }
+
+    fn tools_and_executors(&self) -> Vec<(Tool, ToolExecutor)> {
+        let path_description = match self.roots.as_slice() {
+            [_, _, ..] => {
+                let names: Vec<&str> = self.roots.iter().map(|(name, _)| name.as_str()).collect();
+                format!("Relative path starting with one of the roots: {}", names.join(", "))
+            }
+            _ => "Relative path, e.g. `notes/todo.md`".to_string(),
+        };
+        let path = serde_json::json!({ "type": "string", "description": path_description });
+
+        let read = self.clone();
+        let mut tools = vec![(
+            tool(READ_FILE_TOOL_NAME, "Reads a text file", &[("path", path.clone())], &["path"]),
+            sync_executor(move |args| read.read_file(&string_arg(args, "path")?)),
+        )];
+        if !self.read_only {
+            let write = self.clone();
+            let parameters = [
+                ("path", path.clone()),
+                ("content", serde_json::json!({ "type": "string", "description": "The text to write" })),
+                ("append", serde_json::json!({ "type": "boolean", "description": "Append instead of replacing the file" })),
+            ];
+            tools.push((
+                tool(WRITE_FILE_TOOL_NAME, "Writes a text file, creating parent directories", &parameters, &["path", "content"]),
+                sync_executor(move |args| {
+                    let append = parse_args(args)?.get("append").and_then(|a| a.as_bool()).unwrap_or(false);
+                    write.write_file(&string_arg(args, "path")?, &string_arg(args, "content")?, append)
+                }),
+            ));
+        }
+        let list = self.clone();
+        tools.push((
+            tool(LIST_DIR_TOOL_NAME, "Lists a directory; an empty path lists the top level", &[("path", path)], &[]),
+            sync_executor(move |args| {
+                let path = parse_args(args)?.get("path").and_then(|p| p.as_str()).unwrap_or_default().to_string();
+                list.list_dir(&path)
+            }),
+        ));
+        tools
+    }
+
+    /// Resolves a model-supplied path to a root and a path inside it.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let (root, relative) = self.split_root(path)?;
+        if relative.trim_matches(['/', '\\', '.']).is_empty() {
+            return Ok(root);
+        }
+        resolve_in(&root, relative).map_err(|e| e.to_string())
+    }
+
+    fn split_root<'a>(&self, path: &'a str) -> Result<(PathBuf, &'a str), String> {
+        match self.roots.as_slice() {
+            [] => {
+                let workspace = ToolContext::current().workspace;
+                workspace.map(|root| (root, path)).ok_or_else(|| "No workspace is available for this run".to_string())
+            }
+            [(_, root)] => Ok((root.clone(), path)),
+            roots => {
+                let (name, rest) = path.split_once(['/', '\\']).unwrap_or((path, ""));
+                roots
+                    .iter()
+                    .find(|(root_name, _)| root_name == name)
+                    .map(|(_, root)| (root.clone(), rest))
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = roots.iter().map(|(name, _)| name.as_str()).collect();
+                        format!("Path '{}' must start with one of: {}", path, names.join(", "))
+                    })
+            }
+        }
+    }
+
+    /// Reads a text file, truncated to the read limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the path is not allowed, the file cannot be read, or it is
+    /// binary.
+    pub fn read_file(&self, path: &str) -> Result<String, String> {
+        let resolved = self.resolve(path)?;
+        let file = std::fs::File::open(&resolved).map_err(|e| format!("Cannot read '{}': {}", path, e))?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        let mut bytes = Vec::new();
+        let limit = self.max_read_bytes.max(BINARY_SNIFF_BYTES as u64);
+        file.take(limit).read_to_end(&mut bytes).map_err(|e| format!("Cannot read '{}': {}", path, e))?;
+
+        if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+            return Err(format!("'{}' is a binary file ({} bytes)", path, size));
+        }
+        bytes.truncate(self.max_read_bytes as usize);
+        let mut text = match String::from_utf8(bytes) {
+            Ok(text) => text,
+            // A cut can split a multi-byte character; anything else invalid means binary
+            Err(e) if size > self.max_read_bytes && e.utf8_error().error_len().is_none() => {
+                let valid = e.utf8_error().valid_up_to();
+                let mut bytes = e.into_bytes();
+                bytes.truncate(valid);
+                String::from_utf8(bytes).unwrap_or_default()
+            }
+            Err(_) => return Err(format!("'{}' is not a UTF-8 text file ({} bytes)", path, size)),
+        };
+        if size > self.max_read_bytes {
+            text.push_str(&format!("\n\n[Truncated: showing {} of {} bytes]", self.max_read_bytes, size));
+        }
+        Ok(text)
+    }
+
+    /// Writes (or appends) `content` to a file, creating missing parent directories.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if writing is disabled, the path is not allowed, the content is
+    /// over the write limit or the workspace's size cap, or the file cannot be written.
+    pub fn write_file(&self, path: &str, content: &str, append: bool) -> Result<String, String> {
+        if self.read_only {
+            return Err("Writing files is disabled".to_string());
+        }
+        if content.len() > self.max_write_bytes {
+            return Err(format!("Content is {} bytes, the limit is {}", content.len(), self.max_write_bytes));
+        }
+        let resolved = self.resolve(path)?;
+        if resolved.is_dir() {
+            return Err(format!("'{}' is a directory", path));
+        }
+        // Files in the run's workspace count against its size cap; an overwrite frees the old content
+        let quota = if self.roots.is_empty() { ToolContext::current().workspace_quota() } else { None };
+        if let Some(quota) = quota {
+            let freed = if append { 0 } else { std::fs::metadata(&resolved).map_or(0, |metadata| metadata.len()) };
+            if content.len() as u64 > quota + freed {
+                return Err(format!(
+                    "Writing {} bytes would exceed the workspace size cap ({} bytes left)",
+                    content.len(),
+                    quota + freed
+                ));
+            }
+        }
+        if let Some(parent) = resolved.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Cannot create the directory of '{}': {}", path, e))?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true).write(true);
+        // A link planted after `resolve` checked the path must not be followed
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NOFOLLOW);
+        if append {
+            options.append(true);
+        } else {
+            options.truncate(true);
+        }
+        let mut file = options.open(&resolved).map_err(|e| format!("Cannot write '{}': {}", path, e))?;
+        std::io::Write::write_all(&mut file, content.as_bytes()).map_err(|e| format!("Cannot write '{}': {}", path, e))?;
+        Ok(format!("Wrote {} bytes to {}", content.len(), path))
+    }
+
+    /// Lists a directory, one entry per line: directories end with `/`, files show their
+    /// size. With several roots, an empty path lists the roots.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the path is not allowed or is not a readable directory.
+    pub fn list_dir(&self, path: &str) -> Result<String, String> {
+        if self.roots.len() > 1 && path.trim_matches(['/', '\\', '.']).is_empty() {
+            return Ok(self.roots.iter().map(|(name, _)| format!("{}/", name)).collect::<Vec<_>>().join("\n"));
+        }
+        let resolved = self.resolve(path)?;
+        let entries = std::fs::read_dir(&resolved).map_err(|e| format!("Cannot list '{}': {}", path, e))?;
+        let mut lines: Vec<String> = entries
+            .filter_map(Result::ok)
+            .map(|entry| describe_entry(&entry.path(), &entry.file_name().to_string_lossy()))
+            .collect();
+        lines.sort();
+        let total = lines.len();
+        lines.truncate(self.max_entries);
+        if total > self.max_entries {
+            lines.push(format!("[{} more entries not shown]", total - self.max_entries));
+        }
+        if lines.is_empty() {
+            return Ok(format!("'{}' is empty", path));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+fn describe_entry(path: &Path, name: &str) -> String {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => format!("{}/", name),
+        Ok(metadata) => format!("{} ({} bytes)", name, metadata.len()),
+        Err(_) => name.to_string(),
+    }
+}
+
+fn tool(name: &str, description: &str, parameters: &[(&str, serde_json::Value)], required: &[&str]) -> Tool {
+    Tool {
+        name: name.to_string(),
+        description: description.to_string(),
+        parameters: JsonSchema {
+            schema_type: "object".to_string(),
+            properties: Some(parameters.iter().map(|(name, schema)| (name.to_string(), schema.clone())).collect()),
+            required: Some(required.iter().map(|name| name.to_string()).collect()),
+        },
+    }
+}
+
+fn parse_args(args: &str) -> Result<serde_json::Value, String> {
+    if args.trim().is_empty() {
+        return Ok(serde_json::json!({}));
+    }
+    serde_json::from_str(args).map_err(|e| format!("Failed to parse arguments: {}", e))
+}
+
+fn string_arg(args: &str, name: &str) -> Result<String, String> {
+    parse_args(args)?
+        .get(name)
+        .and_then(|value| value.as_str())
+        .map(String::from)
+        .ok_or_else(|| format!("Missing string argument `{}`", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("merco-fs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_write_read_and_list_in_the_workspace() {
+        let workspace = temp_dir("workspace");
+        let mut registry = ToolRegistry::new();
+        FileSystemTools::new().register(&mut registry);
+
+        let context = ToolContext { workspace: Some(workspace.clone()), ..Default::default() };
+        context
+            .scope(async {
+                let written = registry
+                    .execute_tool(WRITE_FILE_TOOL_NAME, r#"{"path": "notes/todo.md", "content": "- ship\n"}"#)
+                    .await
+                    .unwrap();
+                assert_eq!(written, "Wrote 7 bytes to notes/todo.md");
+                registry
+                    .execute_tool(WRITE_FILE_TOOL_NAME, r#"{"path": "notes/todo.md", "content": "- test\n", "append": true}"#)
+                    .await
+                    .unwrap();
+
+                let read = registry.execute_tool(READ_FILE_TOOL_NAME, r#"{"path": "notes/todo.md"}"#).await;
+                assert_eq!(read, Ok("- ship\n- test\n".to_string()));
+                assert_eq!(registry.execute_tool(LIST_DIR_TOOL_NAME, "{}").await, Ok("notes/".to_string()));
+                assert_eq!(
+                    registry.execute_tool(LIST_DIR_TOOL_NAME, r#"{"path": "notes"}"#).await,
+                    Ok("todo.md (14 bytes)".to_string())
+                );
+
+                let escape = registry.execute_tool(READ_FILE_TOOL_NAME, r#"{"path": "../etc/passwd"}"#).await;
+                assert!(escape.unwrap_err().contains("escapes"));
+            })
+            .await;
+
+        let error = registry.execute_tool(READ_FILE_TOOL_NAME, r#"{"path": "notes/todo.md"}"#).await.unwrap_err();
+        assert!(error.contains("No workspace"), "{}", error);
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[test]
+    fn test_named_roots_caps_and_binary_files() {
+        let (docs, data) = (temp_dir("docs"), temp_dir("data"));
+        std::fs::write(docs.join("guide.md"), "0123456789").unwrap();
+        std::fs::write(data.join("image.png"), [0x89, b'P', b'N', b'G', 0, 0]).unwrap();
+        let fs = FileSystemTools::new().with_root("docs", &docs).with_root("data", &data).with_max_read_bytes(4).read_only();
+
+        assert_eq!(fs.list_dir("").unwrap(), "docs/\ndata/");
+        assert_eq!(fs.read_file("docs/guide.md").unwrap(), "0123\n\n[Truncated: showing 4 of 10 bytes]");
+        assert!(fs.read_file("data/image.png").unwrap_err().contains("binary"));
+        assert!(fs.read_file("guide.md").unwrap_err().contains("must start with one of: docs, data"));
+        assert!(fs.write_file("docs/new.md", "x", false).unwrap_err().contains("disabled"));
+        assert_eq!(fs.tools().len(), 2);
+
+        let fs = FileSystemTools::new().with_root("docs", &docs).with_max_write_bytes(3);
+        assert!(fs.write_file("big.md", "four", false).unwrap_err().contains("limit"));
+
+        std::fs::remove_dir_all(docs).unwrap();
+        std::fs::remove_dir_all(data).unwrap();
+    }
+
+    #[test]
+    fn test_writes_stay_within_the_workspace_size_cap() {
+        let workspace = temp_dir("quota");
+        let context = ToolContext { workspace: Some(workspace.clone()), max_workspace_bytes: Some(10), ..Default::default() };
+        let fs = FileSystemTools::new();
+
+        context.scope_sync(|| {
+            assert!(fs.write_file("a.txt", "12345678", false).is_ok());
+            // Overwriting frees the old content, appending does not
+            assert!(fs.write_file("a.txt", "0123456789", false).is_ok());
+            let error = fs.write_file("a.txt", "x", true).unwrap_err();
+            assert!(error.contains("workspace size cap (0 bytes left)"), "{}", error);
+            assert!(fs.write_file("b.txt", "x", false).is_err());
+        });
+        assert_eq!(std::fs::read_to_string(workspace.join("a.txt")).unwrap(), "0123456789");
+        std::fs::remove_dir_all(workspace).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_does_not_write_through_dangling_symlinks() {
+        let (root, outside) = (temp_dir("links"), temp_dir("links-outside"));
+        std::os::unix::fs::symlink(outside.join("planted.txt"), root.join("report.md")).unwrap();
+        let fs = FileSystemTools::new().with_root("work", &root);
+
+        assert!(fs.write_file("report.md", "x", false).unwrap_err().contains("escapes"));
+        assert!(!outside.join("planted.txt").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+}
@@ -0,0 +1,30 @@
+//!
+//! Built-in Tools
+//!
+//! Ready-made tools for common agent needs. Each tool is configured through its own
+//! type, which provides the `Tool` definition and an executor, and registers into a
+//! `ToolRegistry` (or the global registry) with safe defaults that can be loosened.
+//! Each pack has its own feature: `builtin-code`, `builtin-fetch`, `builtin-fs` and
+//! `builtin-shell`.
+
+#[cfg(feature = "builtin-code")]
+pub mod code;
+#[cfg(feature = "builtin-fetch")]
+pub mod fetch;
+#[cfg(feature = "builtin-fs")]
+pub mod fs;
+#[cfg(any(feature = "builtin-code", feature = "builtin-shell"))]
+mod process;
+#[cfg(feature = "builtin-shell")]
+pub mod shell;
+
+#[cfg(feature = "builtin-code")]
+pub use code::{CodeInterpreter, CodeLanguage, CODE_TOOL_NAME};
+#[cfg(feature = "builtin-fetch")]
+pub use fetch::{html_to_text, FetchTool, FETCH_TOOL_NAME};
+#[cfg(feature = "builtin-fs")]
+pub use fs::{FileSystemTools, LIST_DIR_TOOL_NAME, READ_FILE_TOOL_NAME, WRITE_FILE_TOOL_NAME};
+#[cfg(any(feature = "builtin-code", feature = "builtin-shell"))]
+pub use process::CodeOutput;
+#[cfg(feature = "builtin-shell")]
+pub use shell::{ShellConfirmation, ShellPolicy, ShellTool, DEFAULT_BLOCKED_PATTERNS, SHELL_TOOL_NAME};
@@ -0,0 +1,99 @@
+//!
+//! Child Processes
+//!
+//! Output capture and clean-up shared by the code and shell tools.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Child;
+use tokio::time::error::Elapsed;
+
+/// The outcome of one execution (of code, or of a shell command).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeOutput {
+    /// Exit code, or `None` if the process was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// Captured standard output, possibly truncated.
+    pub stdout: String,
+    /// Captured standard error, possibly truncated.
+    pub stderr: String,
+}
+
+impl CodeOutput {
+    /// The output as reported to the model.
+    pub fn to_report(&self) -> String {
+        let exit = self.exit_code.map_or_else(|| "killed by a signal".to_string(), |code| code.to_string());
+        format!("exit code: {}\n--- stdout ---\n{}\n--- stderr ---\n{}", exit, self.stdout, self.stderr)
+    }
+}
+
+/// Waits for `child` to exit within `timeout`, reading at most `max_bytes` (plus one, to
+/// detect truncation) of its stdout and stderr. A full pipe is closed rather than
+/// drained, so a program that prints without end is stopped by `SIGPIPE`.
+///
+/// The child's process group (on Unix, spawn it with `process_group(0)`) is killed once
+/// the child exits, when the timeout fires, or when the future is dropped.
+pub(super) async fn capture_output(
+    mut child: Child,
+    timeout: Duration,
+    max_bytes: usize,
+) -> Result<std::io::Result<CodeOutput>, Elapsed> {
+    let group = ProcessGroup(child.id());
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    tokio::time::timeout(timeout, async {
+        let wait = async {
+            let status = child.wait().await;
+            // Background processes would otherwise keep the pipes open
+            group.kill();
+            status
+        };
+        let (stdout, stderr, status) = tokio::join!(read_capped(stdout, max_bytes), read_capped(stderr, max_bytes), wait);
+        Ok(CodeOutput {
+            exit_code: status?.code(),
+            stdout: truncate_output(&stdout?, max_bytes),
+            stderr: truncate_output(&stderr?, max_bytes),
+        })
+    })
+    .await
+}
+
+async fn read_capped(pipe: Option<impl AsyncRead + Unpin>, max_bytes: usize) -> std::io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    if let Some(pipe) = pipe {
+        pipe.take(max_bytes as u64 + 1).read_to_end(&mut output).await?;
+    }
+    Ok(output)
+}
+
+/// The process group led by a child, killed when dropped.
+struct ProcessGroup(Option<u32>);
+
+impl ProcessGroup {
+    fn kill(&self) {
+        #[cfg(unix)]
+        if let Some(id) = self.0 {
+            // SAFETY: `killpg` has no memory-safety preconditions; at worst it fails with
+            // `ESRCH` once the group is gone
+            unsafe {
+                libc::killpg(id as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Decodes captured process output, keeping at most `max_bytes` of it.
+fn truncate_output(output: &[u8], max_bytes: usize) -> String {
+    if output.len() <= max_bytes {
+        return String::from_utf8_lossy(output).into_owned();
+    }
+    let mut text = String::from_utf8_lossy(&output[..max_bytes]).into_owned();
+    text.push_str(&format!("\n[Truncated after {} bytes]", max_bytes));
+    text
+}
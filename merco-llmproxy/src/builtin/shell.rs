@@ -0,0 +1,482 @@
+//!
+//! Shell Tool
+//!
+//! `run_shell` runs a command for ops-style agents, guarded by a `ShellPolicy`. The
+//! command line is split into arguments like a POSIX shell would (quotes and
+//! backslashes), but it is executed directly, not by a shell: pipes, redirects, `;`,
+//! `&&` and substitutions are rejected, so the policy sees exactly what runs.
+//!
+//! A command runs only if its program is on the allowlist, it matches none of the
+//! blocked patterns, and the confirmation callback (if any) approves it. Patterns are
+//! matched against the parsed arguments rather than the raw text, so quoting or extra
+//! spaces don't get a command past them. Every refusal names the rule that caused it, so
+//! decisions can be audited.
+
+use crate::builtin::process::{capture_output, CodeOutput};
+use crate::tools::{register_tool, ToolContext, ToolError, ToolExecutor, ToolRegistry};
+use crate::traits::{JsonSchema, Tool};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Name of the tool as offered to the model.
+pub const SHELL_TOOL_NAME: &str = "run_shell";
+
+/// Patterns blocked by default: privilege escalation, recursive deletion from the root
+/// or home directory, disk formatting and fork bombs.
+pub const DEFAULT_BLOCKED_PATTERNS: &[&str] = &["sudo", "su -", "rm -rf /", "rm -rf ~", "mkfs", "dd if=", ":(){"];
+
+/// Decides whether a command that passed every other rule may run, e.g. by asking a
+/// human; receives the parsed arguments.
+pub type ShellConfirmation = Arc<dyn Fn(&[String]) -> bool + Send + Sync>;
+
+/// The rules a command must pass before `run_shell` executes it.
+#[derive(Clone)]
+pub struct ShellPolicy {
+    allowed_binaries: Vec<String>,
+    allow_any_binary: bool,
+    blocked_patterns: Vec<String>,
+    confirmation: Option<ShellConfirmation>,
+    working_dir: Option<PathBuf>,
+    timeout: Duration,
+    max_output_bytes: usize,
+}
+
+impl fmt::Debug for ShellPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShellPolicy")
+            .field("allowed_binaries", &self.allowed_binaries)
+            .field("allow_any_binary", &self.allow_any_binary)
+            .field("blocked_patterns", &self.blocked_patterns)
+            .field("confirmation", &self.confirmation.as_ref().map(|_| "<ShellConfirmation>"))
+            .field("working_dir", &self.working_dir)
+            .field("timeout", &self.timeout)
+            .field("max_output_bytes", &self.max_output_bytes)
+            .finish()
+    }
+}
+
+impl Default for ShellPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_binaries: Vec::new(),
+            allow_any_binary: false,
+            blocked_patterns: DEFAULT_BLOCKED_PATTERNS.iter().map(|pattern| pattern.to_string()).collect(),
+            confirmation: None,
+            working_dir: None,
+            timeout: Duration::from_secs(60),
+            max_output_bytes: 64 * 1024,
+        }
+    }
+}
+
+impl ShellPolicy {
+    /// Creates a policy that allows no programs yet, blocks `DEFAULT_BLOCKED_PATTERNS`,
+    /// and runs commands in the run's workspace with a 60 second timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows running `binary` (builder style). A bare name (`git`) is looked up on
+    /// `PATH`; commands naming a program by path must match an allowed path exactly.
+    pub fn allow_binary(mut self, binary: impl Into<String>) -> Self {
+        self.allowed_binaries.push(binary.into());
+        self
+    }
+
+    /// Allows every program, leaving only the other rules (builder style).
+    pub fn allow_any_binary(mut self) -> Self {
+        self.allow_any_binary = true;
+        self
+    }
+
+    /// Refuses commands matching `pattern` (builder style). The pattern's first word names
+    /// a program, compared with the file name of the command's program (`mkfs` also
+    /// covers `mkfs.ext4`); a pattern starting with `-` applies to any program. Every other
+    /// word must appear among the arguments: short flags are compared one by one (`-rf`
+    /// matches `-r -f` and `-fr`), a word ending in `=` matches arguments starting with it,
+    /// and paths are compared after normalization (`/` matches `//` and `/.`).
+    pub fn block_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.blocked_patterns.push(pattern.into());
+        self
+    }
+
+    /// Asks `confirm` before running each command that passed the other rules; a `false`
+    /// answer refuses it (builder style).
+    pub fn with_confirmation(mut self, confirm: impl Fn(&[String]) -> bool + Send + Sync + 'static) -> Self {
+        self.confirmation = Some(Arc::new(confirm));
+        self
+    }
+
+    /// Runs commands in `dir` instead of the run's workspace (builder style).
+    pub fn with_working_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the wall-clock limit of one command (builder style).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how much of stdout and of stderr is kept, in bytes each (builder style).
+    pub fn with_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = bytes;
+        self
+    }
+
+    /// Parses `command` and applies every rule except the confirmation, returning the
+    /// arguments that would run.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming the rule the command breaks.
+    pub fn check(&self, command: &str) -> Result<Vec<String>, String> {
+        let args = split_command(command)?;
+        if let Some(pattern) = self.blocked_patterns.iter().find(|pattern| pattern_matches(pattern, &args)) {
+            return Err(format!("Command refused: it matches the blocked pattern '{}'", pattern));
+        }
+        let program = &args[0];
+        // Names and paths are compared verbatim, so `./git` never passes as `git`
+        let allowed = self.allow_any_binary || self.allowed_binaries.iter().any(|allowed| allowed == program);
+        if !allowed {
+            return Err(format!("Command refused: '{}' is not an allowed program", program));
+        }
+        Ok(args)
+    }
+}
+
+/// Configuration of the `run_shell` tool.
+#[derive(Debug, Clone)]
+pub struct ShellTool {
+    policy: ShellPolicy,
+}
+
+impl ShellTool {
+    /// Creates the tool guarded by `policy`.
+    pub fn new(policy: ShellPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// The policy commands are checked against.
+    pub fn policy(&self) -> &ShellPolicy {
+        &self.policy
+    }
+
+    /// The tool definition offered to the model.
+    pub fn tool(&self) -> Tool {
+        let programs = if self.policy.allow_any_binary {
+            String::new()
+        } else {
+            format!(" Allowed programs: {}.", self.policy.allowed_binaries.join(", "))
+        };
+        let mut properties = serde_json::Map::new();
+        properties.insert(
+            "command".to_string(),
+            serde_json::json!({ "type": "string", "description": "One command with its arguments, e.g. `git status --short`" }),
+        );
+        Tool {
+            name: SHELL_TOOL_NAME.to_string(),
+            description: format!(
+                "Runs a single command (no pipes, redirects or chaining) and returns its exit code, stdout and stderr.{}",
+                programs
+            ),
+            parameters: JsonSchema {
+                schema_type: "object".to_string(),
+                properties: Some(properties),
+                required: Some(vec!["command".to_string()]),
+            },
+        }
+    }
+
+    /// An executor running `run` with the command from the tool arguments.
+    pub fn executor(&self) -> ToolExecutor {
+        let shell = Arc::new(self.clone());
+        Arc::new(move |args: String| {
+            let shell = shell.clone();
+            Box::pin(async move {
+                let args: serde_json::Value = serde_json::from_str(&args)
+                    .map_err(|e| format!("Failed to parse arguments for {}: {}", SHELL_TOOL_NAME, e))?;
+                let command = args.get("command").and_then(|command| command.as_str()).unwrap_or_default();
+                let output = shell.run(command).await.map_err(|e| e.to_payload())?;
+                Ok(output.to_report())
+            })
+        })
+    }
+
+    /// Registers the tool in `registry`.
+    pub fn register(&self, registry: &mut ToolRegistry) {
+        registry.register(self.tool(), self.executor());
+    }
+
+    /// Registers the tool in the global registry.
+    pub fn register_global(&self) {
+        register_tool(self.tool(), self.executor());
+    }
+
+    /// Checks `command` against the policy and runs it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ToolError` if the policy or the confirmation refuses the command, no
+    /// working directory is available, the program cannot be started, it times out, or
+    /// it leaves the workspace over its size cap.
+    /// A command that fails is not an error: its exit code and stderr are in the output.
+    pub async fn run(&self, command: &str) -> Result<CodeOutput, ToolError> {
+        let args = self.policy.check(command).map_err(ToolError::new)?;
+        if let Some(confirm) = &self.policy.confirmation {
+            if !confirm(&args) {
+                return Err(ToolError::new("Command refused: it was not confirmed"));
+            }
+        }
+        let context = ToolContext::current();
+        let (dir, quota) = match &self.policy.working_dir {
+            Some(dir) => (dir.clone(), None),
+            None => {
+                let workspace = context.workspace.clone().ok_or_else(|| {
+                    ToolError::new("No working directory: set one on the policy or run with a workspace")
+                })?;
+                (workspace, context.workspace_quota())
+            }
+        };
+
+        let mut command = tokio::process::Command::new(&args[0]);
+        command
+            .args(&args[1..])
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        {
+            command.process_group(0);
+            if let Some(quota) = quota {
+                limit_file_size(&mut command, quota);
+            }
+        }
+        let child = command.spawn().map_err(|e| ToolError::new(format!("Failed to start {}: {}", args[0], e)))?;
+        let output = match capture_output(child, self.policy.timeout, self.policy.max_output_bytes).await {
+            Ok(output) => output.map_err(|e| ToolError::new(format!("Failed to run {}: {}", args[0], e)))?,
+            Err(_) => {
+                return Err(ToolError::new(format!("Command timed out after {} seconds", self.policy.timeout.as_secs_f32())))
+            }
+        };
+        if quota.is_some() {
+            context.check_workspace_size().map_err(ToolError::new)?;
+        }
+        Ok(output)
+    }
+}
+
+/// Keeps the command from growing any file past `max_bytes`: such a write fails and
+/// the process gets `SIGXFSZ`.
+#[cfg(unix)]
+fn limit_file_size(command: &mut tokio::process::Command, max_bytes: u64) {
+    let limit = libc::rlimit { rlim_cur: max_bytes as libc::rlim_t, rlim_max: max_bytes as libc::rlim_t };
+    // SAFETY: the closure runs in the forked child and only calls `setrlimit`, which is
+    // async-signal-safe
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_FSIZE, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Whether the parsed command `args` matches a blocked `pattern` (see
+/// `ShellPolicy::block_pattern`).
+fn pattern_matches(pattern: &str, args: &[String]) -> bool {
+    let words: Vec<&str> = pattern.split_whitespace().collect();
+    let Some(first) = words.first() else {
+        return false;
+    };
+    let (candidates, required) = if first.starts_with('-') {
+        (args, &words[..])
+    } else {
+        let program = Path::new(&args[0]).file_name().and_then(|name| name.to_str()).unwrap_or(&args[0]);
+        if program != *first && !program.starts_with(&format!("{}.", first)) {
+            return false;
+        }
+        (&args[1..], &words[1..])
+    };
+    let short_flags: Vec<char> = candidates
+        .iter()
+        .filter(|arg| arg.len() > 1 && arg.starts_with('-') && !arg.starts_with("--"))
+        .flat_map(|arg| arg.chars().skip(1))
+        .collect();
+    required.iter().all(|word| {
+        if word.starts_with("--") {
+            candidates.iter().any(|arg| arg == word || arg.starts_with(&format!("{}=", word)))
+        } else if word.len() > 1 && word.starts_with('-') {
+            word.chars().skip(1).all(|flag| short_flags.contains(&flag))
+        } else if word.ends_with('=') {
+            candidates.iter().any(|arg| arg.starts_with(word))
+        } else {
+            candidates.iter().any(|arg| arg == word || Path::new(arg).components().eq(Path::new(word).components()))
+        }
+    })
+}
+
+/// Splits a command line into arguments, honouring single quotes, double quotes and
+/// backslash escapes, and rejecting unquoted shell operators.
+fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let operator_error = |operator: &str| {
+        Err(format!("Command refused: shell operator '{}' is not supported; run one command at a time", operator))
+    };
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Command refused: unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"' | '\\' | '$' | '`')) => arg.push(chars.next().unwrap_or('\\')),
+                        Some('$') if chars.peek() == Some(&'(') => return operator_error("$("),
+                        Some('`') => return operator_error("`"),
+                        Some(c) => arg.push(c),
+                        None => return Err("Command refused: unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(escaped) => current.get_or_insert_with(String::new).push(escaped),
+                None => return Err("Command refused: trailing backslash".to_string()),
+            },
+            '|' | ';' | '&' | '>' | '<' | '`' | '(' | ')' => return operator_error(&c.to_string()),
+            '$' if chars.peek() == Some(&'(') => return operator_error("$("),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+    if args.is_empty() {
+        return Err("Command refused: it is empty".to_string());
+    }
+    Ok(args)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(split_command(r#"git commit -m "fix \"it\"" 'a b' c\ d"#).unwrap(), vec!["git", "commit", "-m", r#"fix "it""#, "a b", "c d"]);
+        assert!(split_command("ls | grep x").unwrap_err().contains("'|'"));
+        assert!(split_command("echo $(whoami)").unwrap_err().contains("'$('"));
+        assert!(split_command("echo \"`id`\"").unwrap_err().contains("'`'"));
+        assert!(split_command("cat a > b").unwrap_err().contains("'>'"));
+        // Quoted operators are plain text
+        assert_eq!(split_command("echo 'a|b;c'").unwrap(), vec!["echo", "a|b;c"]);
+        assert!(split_command("echo 'open").unwrap_err().contains("unterminated"));
+        assert!(split_command("   ").unwrap_err().contains("empty"));
+    }
+
+    #[test]
+    fn test_policy_rules() {
+        let policy = ShellPolicy::new().allow_binary("echo").allow_binary("/usr/bin/env").block_pattern("--force");
+
+        assert_eq!(policy.check("echo hi").unwrap(), vec!["echo", "hi"]);
+        assert!(policy.check("/usr/bin/env").is_ok());
+        assert!(policy.check("./echo hi").unwrap_err().contains("not an allowed program"));
+        assert!(policy.check("env").unwrap_err().contains("not an allowed program"));
+        assert!(policy.check("echo --force").unwrap_err().contains("blocked pattern '--force'"));
+        assert!(policy.check("sudo echo").unwrap_err().contains("blocked pattern 'sudo'"));
+        assert!(ShellPolicy::new().allow_any_binary().check("uname -a").is_ok());
+    }
+
+    #[test]
+    fn test_blocked_patterns_survive_quoting_and_spacing() {
+        let policy = ShellPolicy::new().allow_any_binary().block_pattern("--force");
+        let refused = |command: &str| policy.check(command).is_err_and(|e| e.contains("blocked pattern"));
+
+        assert!(refused(r#"su"do" id"#));
+        assert!(refused("'sudo' id"));
+        assert!(refused("/usr/bin/sudo id"));
+        assert!(refused("echo --for''ce"));
+        assert!(refused("echo --force=yes"));
+        assert!(refused("rm  -rf /"));
+        assert!(refused("rm -r -f /"));
+        assert!(refused("rm -fr //"));
+        assert!(refused("/bin/rm -f -r '/'"));
+        assert!(refused("rm -rf ~/"));
+        assert!(refused("su -"));
+        assert!(refused("dd if=/dev/zero of=/dev/sda"));
+        assert!(refused("mkfs.ext4 /dev/sda1"));
+
+        // Near misses of the default patterns are not refused
+        assert!(policy.check("rm -rf build").is_ok());
+        assert!(policy.check("rm -r /tmp/x").is_ok());
+        assert!(policy.check("echo sudo").is_ok());
+        assert!(policy.check("echo force").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_runs_confirmed_commands_in_the_working_dir() {
+        let confirmed = Arc::new(Mutex::new(Vec::new()));
+        let seen = confirmed.clone();
+        let policy = ShellPolicy::new()
+            .allow_binary("pwd")
+            .allow_binary("ls")
+            .with_working_dir(std::env::temp_dir())
+            .with_confirmation(move |args| {
+                seen.lock().unwrap().push(args.join(" "));
+                args[0] != "ls"
+            });
+        let mut registry = ToolRegistry::new();
+        ShellTool::new(policy).register(&mut registry);
+
+        let report = registry.execute_tool(SHELL_TOOL_NAME, r#"{"command": "pwd"}"#).await.unwrap();
+        let temp_dir = std::env::temp_dir().canonicalize().unwrap();
+        assert!(report.starts_with(&format!("exit code: 0\n--- stdout ---\n{}", temp_dir.display())), "{}", report);
+
+        let refused = registry.execute_tool(SHELL_TOOL_NAME, r#"{"command": "ls -la"}"#).await.unwrap_err();
+        assert!(refused.contains("not confirmed"), "{}", refused);
+        assert_eq!(*confirmed.lock().unwrap(), vec!["pwd", "ls -la"]);
+    }
+
+    #[tokio::test]
+    async fn test_stays_within_the_workspace_size_cap() {
+        let workspace = std::env::temp_dir().join(format!("merco-shell-quota-{}", std::process::id()));
+        std::fs::create_dir_all(&workspace).unwrap();
+        let context = ToolContext { workspace: Some(workspace.clone()), max_workspace_bytes: Some(1000), ..Default::default() };
+        let shell = ShellTool::new(ShellPolicy::new().allow_binary("truncate"));
+
+        let output = context.scope(shell.run("truncate -s 5000 big")).await.unwrap();
+        assert_ne!(output.exit_code, Some(0));
+        assert_eq!(std::fs::metadata(workspace.join("big")).unwrap().len(), 0);
+        std::fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_needs_a_working_dir() {
+        let shell = ShellTool::new(ShellPolicy::new().allow_binary("pwd"));
+        assert!(shell.run("pwd").await.unwrap_err().error.contains("No working directory"));
+    }
+}
@@ -0,0 +1,71 @@
+//!
+//! Request Cancellation
+//!
+//! Helpers that tie provider calls to a `CancellationToken`. Cancelling the token drops
+//! the in-flight request future or the response stream, which aborts the underlying HTTP
+//! call or SSE connection instead of leaving it running in the background.
+
+use crate::traits::{CompletionStream, ProviderError};
+use futures::stream::{self, StreamExt};
+use std::future::Future;
+pub use tokio_util::sync::CancellationToken;
+
+/// Runs `future` until it completes or `token` is cancelled, whichever comes first.
+///
+/// # Errors
+///
+/// Returns `ProviderError::Cancelled` if the token is cancelled first, otherwise the
+/// future's own result.
+pub async fn cancellable<T, F>(token: &CancellationToken, future: F) -> Result<T, ProviderError>
+where
+    F: Future<Output = Result<T, ProviderError>>,
+{
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(ProviderError::Cancelled),
+        result = future => result,
+    }
+}
+
+/// Wraps `stream` so it ends with a `ProviderError::Cancelled` item as soon as `token` is
+/// cancelled; the inner stream (and its connection) is dropped at that point.
+pub fn cancellable_stream(stream: CompletionStream, token: CancellationToken) -> CompletionStream {
+    let cancelled = Box::pin(token.cancelled_owned());
+    let stream = stream::unfold(Some((stream, cancelled)), |state| async move {
+        let (mut stream, mut cancelled) = state?;
+        tokio::select! {
+            biased;
+            _ = &mut cancelled => Some((Err(ProviderError::Cancelled), None)),
+            item = stream.next() => item.map(|item| (item, Some((stream, cancelled)))),
+        }
+    });
+    Box::pin(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::{CompletionStreamChunk, StreamContentDelta};
+
+    #[tokio::test]
+    async fn test_cancel_stops_pending_future() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let result: Result<(), _> = cancellable(&token, futures::future::pending()).await;
+        assert!(matches!(result, Err(ProviderError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_ends_stream() {
+        let token = CancellationToken::new();
+        let chunk = CompletionStreamChunk { delta: StreamContentDelta::Text("hi".to_string()), usage: None, finish_reason: None };
+        // One chunk, then the stream hangs like a stalled SSE connection
+        let inner: CompletionStream = Box::pin(stream::iter(vec![Ok(chunk)]).chain(stream::pending()));
+        let mut stream = cancellable_stream(inner, token.clone());
+
+        assert!(stream.next().await.unwrap().is_ok());
+        token.cancel();
+        assert!(matches!(stream.next().await, Some(Err(ProviderError::Cancelled))));
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,133 @@
+//!
+//! Clock Abstraction
+//!
+//! Rate limiters, key cooldowns, job polling and simulated latency read the time and
+//! sleep through a `Clock`. The default `TokioClock` follows tokio's clock, so tests
+//! running with paused time (`#[tokio::test(start_paused = true)]`) advance instantly
+//! and deterministically. `ManualClock` goes further: sleeps return immediately, move
+//! the clock forward and are recorded, so backoff schedules can be asserted exactly.
+
+use async_trait::async_trait;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A source of the current time and of delays.
+#[async_trait]
+pub trait Clock: Send + Sync + Debug {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+
+    /// Waits for `duration` to pass on this clock.
+    async fn sleep(&self, duration: Duration);
+
+    /// Blocks the current thread for `duration` on this clock. Used by synchronous
+    /// pollers such as long-running tool jobs.
+    fn sleep_blocking(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// The clock used when none is injected.
+pub fn default_clock() -> Arc<dyn Clock> {
+    Arc::new(TokioClock)
+}
+
+/// Wall-clock time as seen by tokio. When tokio's time is paused (in tests), `now`
+/// reports the virtual time and `sleep` auto-advances it instead of waiting.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioClock;
+
+#[async_trait]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// A clock that only moves when told to. Every sleep advances it by the requested
+/// duration without waiting and is recorded for inspection.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+    sleeps: Mutex<Vec<Duration>>,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at the current instant.
+    pub fn new() -> Self {
+        Self { now: Mutex::new(Instant::now()), sleeps: Mutex::new(Vec::new()) }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    /// Returns every sleep requested so far, in order.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.sleeps.lock().unwrap().clone()
+    }
+
+    fn record_sleep(&self, duration: Duration) {
+        self.sleeps.lock().unwrap().push(duration);
+        self.advance(duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        self.record_sleep(duration);
+        // Still let other tasks run, as a real sleep would
+        tokio::task::yield_now().await;
+    }
+
+    fn sleep_blocking(&self, duration: Duration) {
+        self.record_sleep(duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock_records_sleeps() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+
+        clock.sleep(Duration::from_secs(2)).await;
+        clock.sleep_blocking(Duration::from_millis(500));
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(clock.sleeps(), vec![Duration::from_secs(2), Duration::from_millis(500)]);
+        assert_eq!(clock.now() - start, Duration::from_millis(3500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tokio_clock_follows_paused_time() {
+        let clock = TokioClock;
+        let start = clock.now();
+        let real_start = std::time::Instant::now();
+
+        clock.sleep(Duration::from_secs(3600)).await;
+
+        assert!(clock.now() - start >= Duration::from_secs(3600));
+        assert!(real_start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,3 +1,10 @@
+use crate::signing::RequestSigner;
+use crate::telemetry::Telemetry;
+use crate::transport::{default_transport, Transport};
+#[cfg(feature = "http-transport")]
+use crate::transport::{HttpClientConfig, HttpTransport};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// APP site URL
@@ -19,16 +26,51 @@ pub enum Provider {
     Custom, 
 }
 
+impl std::str::FromStr for Provider {
+    type Err = ConfigError;
+
+    /// Parses a provider name such as `"openai"` or `"Ollama"` (case-insensitive).
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "openai" => Ok(Provider::OpenAI),
+            "ollama" => Ok(Provider::Ollama),
+            "anthropic" => Ok(Provider::Anthropic),
+            "custom" => Ok(Provider::Custom),
+            _ => Err(ConfigError::UnknownProvider(name.to_string())),
+        }
+    }
+}
+
 /// Configuration for initializing an LLM provider.
 #[derive(Debug, Clone)]
 pub struct LlmConfig {
     /// The specific provider to use.
     pub provider: Provider,
+    /// Default model identifier for requests made with this configuration, if known
+    /// (e.g. read by `from_env`).
+    pub model: Option<String>,
     /// The API key required by the provider (if any).
     pub api_key: Option<String>,
+    /// Additional API keys to rotate through when a key is rate limited or out of quota.
+    pub api_keys: Vec<String>,
     /// The base URL for the provider's API endpoint.
     /// Optional, mainly for `Custom` providers or overriding defaults (e.g., OpenRouter).
     pub base_url: Option<String>,
+    /// Optional hook that signs each outgoing request (e.g. HMAC for internal gateways).
+    pub request_signer: Option<RequestSigner>,
+    /// Optional transport used to dispatch requests. Defaults to HTTP(S) via reqwest.
+    pub transport: Option<Arc<dyn Transport>>,
+    /// Options for the default HTTP transport (proxy, root CAs, pooling, keep-alive).
+    /// Ignored when a custom `transport` is set.
+    #[cfg(feature = "http-transport")]
+    pub http: Option<HttpClientConfig>,
+    /// Optional override of the chat endpoint path appended to the base URL
+    /// (e.g. `/completion` instead of `/chat/completions`).
+    pub endpoint_path: Option<String>,
+    /// Extra headers sent with every request, overriding provider defaults of the same name.
+    pub headers: Vec<(String, String)>,
+    /// Opt-in collector of anonymous aggregate statistics; `None` disables telemetry.
+    pub telemetry: Option<Arc<Telemetry>>,
 }
 
 /// Errors that can occur during configuration validation.
@@ -40,6 +82,18 @@ pub enum ConfigError {
     /// Missing base URL required for the `Custom` provider.
     #[error("Missing base URL for custom provider")]
     MissingBaseUrl,
+    /// A custom header name or value is invalid.
+    #[error("Invalid header '{0}': {1}")]
+    InvalidHeader(String, String),
+    /// An HTTP client option (proxy URL, certificate, ...) is invalid.
+    #[error("Invalid HTTP client option: {0}")]
+    InvalidHttpOption(String),
+    /// A required environment variable is unset or empty.
+    #[error("Environment variable {0} is not set")]
+    MissingEnvVar(String),
+    /// A provider name is not one of `openai`, `ollama`, `anthropic` or `custom`.
+    #[error("Unknown provider '{0}' (expected openai, ollama, anthropic or custom)")]
+    UnknownProvider(String),
 }
 
 impl LlmConfig {
@@ -47,23 +101,168 @@ impl LlmConfig {
     pub fn new(provider: Provider) -> Self {
         LlmConfig {
             provider,
+            model: None,
             api_key: None,
+            api_keys: Vec::new(),
             base_url: None,
+            request_signer: None,
+            transport: None,
+            #[cfg(feature = "http-transport")]
+            http: None,
+            endpoint_path: None,
+            headers: Vec::new(),
+            telemetry: None,
         }
     }
 
+    /// Reads a configuration from the environment variables `{prefix}_PROVIDER`,
+    /// `{prefix}_MODEL`, `{prefix}_API_KEY` and `{prefix}_BASE_URL`, e.g.
+    /// `MERCO_PROVIDER=openai` for the prefix `"MERCO"`.
+    ///
+    /// The provider and model are always required; the API key and base URL only when
+    /// the provider needs them. Empty variables count as unset.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::MissingEnvVar` naming the first missing variable,
+    /// `ConfigError::UnknownProvider` for an unrecognized provider name, or any other
+    /// error from `validate`.
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        let name = |suffix: &str| format!("{}_{}", prefix.trim_end_matches('_'), suffix);
+        let var = |suffix: &str| {
+            std::env::var(name(suffix)).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+        };
+        let required = |suffix: &str| var(suffix).ok_or_else(|| ConfigError::MissingEnvVar(name(suffix)));
+
+        let mut config = LlmConfig::new(required("PROVIDER")?.parse()?).with_model(required("MODEL")?);
+        if let Some(api_key) = var("API_KEY") {
+            config = config.with_api_key(api_key);
+        }
+        if let Some(base_url) = var("BASE_URL") {
+            config = config.with_base_url(base_url);
+        }
+        match config.validate() {
+            Ok(()) => Ok(config),
+            Err(ConfigError::MissingApiKey(_)) => Err(ConfigError::MissingEnvVar(name("API_KEY"))),
+            Err(ConfigError::MissingBaseUrl) => Err(ConfigError::MissingEnvVar(name("BASE_URL"))),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sets the default model identifier (builder style).
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
     /// Sets the API key for the configuration (builder style).
     pub fn with_api_key(mut self, api_key: String) -> Self {
         self.api_key = Some(api_key);
         self
     }
 
+    /// Adds API keys to rotate through alongside the primary key (builder style).
+    ///
+    /// Requests are spread across all keys; a key that returns 401, 429 or an
+    /// `insufficient_quota` error is put on cooldown and the request retried with the next key.
+    pub fn with_api_keys(mut self, api_keys: Vec<String>) -> Self {
+        self.api_keys.extend(api_keys);
+        self
+    }
+
+    /// Returns every configured API key, primary key first.
+    pub fn all_api_keys(&self) -> Vec<String> {
+        self.api_key.iter().chain(self.api_keys.iter()).cloned().collect()
+    }
+
     /// Sets the base URL for the configuration (builder style).
     pub fn with_base_url(mut self, base_url: String) -> Self {
         self.base_url = Some(base_url);
         self
     }
 
+    /// Sets a hook that signs every outgoing request before dispatch (builder style).
+    pub fn with_request_signer(mut self, signer: RequestSigner) -> Self {
+        self.request_signer = Some(signer);
+        self
+    }
+
+    /// Sets a custom transport for dispatching requests (builder style).
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Adds a header sent with every request (builder style), e.g. OpenRouter's
+    /// `HTTP-Referer`/`X-Title` or a gateway's tenant and routing headers. A header set
+    /// here replaces the provider's default header of the same name.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Records anonymous aggregate statistics of every call in `telemetry` (builder style).
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Merges the custom headers into `headers`, replacing existing values.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidHeader` if a name or value is not a valid HTTP header.
+    pub fn apply_headers(&self, headers: &mut HeaderMap) -> Result<(), ConfigError> {
+        for (name, value) in &self.headers {
+            let invalid = |e: &dyn std::fmt::Display| ConfigError::InvalidHeader(name.clone(), e.to_string());
+            let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+            let header_value = HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+            headers.insert(header_name, header_value);
+        }
+        Ok(())
+    }
+
+    /// Configures the default HTTP transport's client (builder style).
+    #[cfg(feature = "http-transport")]
+    pub fn with_http_client_config(mut self, http: HttpClientConfig) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// Sends requests with a pre-built `reqwest::Client` (builder style).
+    #[cfg(feature = "http-transport")]
+    pub fn with_http_client(self, client: reqwest::Client) -> Self {
+        self.with_transport(Arc::new(HttpTransport::with_client(client)))
+    }
+
+    /// Returns the transport providers should use: the custom transport if set, otherwise
+    /// an HTTP transport built from the `http` options.
+    /// Panics if the HTTP client fails to build.
+    pub fn resolve_transport(&self) -> Arc<dyn Transport> {
+        if let Some(transport) = &self.transport {
+            return transport.clone();
+        }
+        #[cfg(feature = "http-transport")]
+        if let Some(http) = &self.http {
+            return Arc::new(HttpTransport::from_config(http).expect("Failed to build HTTP client from config"));
+        }
+        default_transport()
+    }
+
+    /// Routes requests over the unix domain socket at `socket_path` (builder style).
+    ///
+    /// The base URL's path is still used for routing; its host is only sent as the `Host` header.
+    #[cfg(all(unix, feature = "unix-socket"))]
+    pub fn with_unix_socket(self, socket_path: impl Into<std::path::PathBuf>) -> Self {
+        self.with_transport(Arc::new(crate::transport::UnixSocketTransport::new(socket_path)))
+    }
+
+    /// Overrides the chat endpoint path appended to the base URL (builder style).
+    pub fn with_endpoint_path(mut self, endpoint_path: String) -> Self {
+        self.endpoint_path = Some(endpoint_path);
+        self
+    }
+
     /// Validates the configuration based on the selected provider's requirements.
     ///
     /// # Errors
@@ -72,7 +271,7 @@ impl LlmConfig {
     pub fn validate(&self) -> Result<(), ConfigError> {
         match self.provider {
             Provider::OpenAI | Provider::Anthropic => {
-                if self.api_key.is_none() {
+                if self.api_key.is_none() && self.api_keys.is_empty() {
                     return Err(ConfigError::MissingApiKey(self.provider.clone()));
                 }
             }
@@ -88,6 +287,54 @@ impl LlmConfig {
                 // Base URL defaults to localhost if not provided.
             }
         }
+        self.apply_headers(&mut HeaderMap::new())?;
+        #[cfg(feature = "http-transport")]
+        if let (None, Some(http)) = (&self.transport, &self.http) {
+            http.build_client().map_err(|e| ConfigError::InvalidHttpOption(e.to_string()))?;
+        }
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_headers_override_defaults() {
+        let config = LlmConfig::new(Provider::Ollama)
+            .with_header("X-Title", "My App")
+            .with_header("X-Tenant", "acme");
+        let mut headers = HeaderMap::new();
+        headers.insert("x-title", HeaderValue::from_static("Merco LLM"));
+
+        config.apply_headers(&mut headers).unwrap();
+        assert_eq!(headers["x-title"], "My App");
+        assert_eq!(headers["x-tenant"], "acme");
+
+        let invalid = LlmConfig::new(Provider::Ollama).with_header("Bad Header", "x");
+        assert!(matches!(invalid.validate(), Err(ConfigError::InvalidHeader(..))));
+    }
+
+    #[test]
+    fn test_from_env() {
+        std::env::set_var("MERCO_TEST_ENV_PROVIDER", "OpenAI");
+        std::env::set_var("MERCO_TEST_ENV_MODEL", "gpt-4o-mini");
+        std::env::set_var("MERCO_TEST_ENV_API_KEY", " ");
+        assert_eq!(
+            LlmConfig::from_env("MERCO_TEST_ENV").unwrap_err().to_string(),
+            "Environment variable MERCO_TEST_ENV_API_KEY is not set"
+        );
+
+        std::env::set_var("MERCO_TEST_ENV_API_KEY", "sk-test");
+        std::env::set_var("MERCO_TEST_ENV_BASE_URL", "https://openrouter.ai/api/v1");
+        let config = LlmConfig::from_env("MERCO_TEST_ENV_").unwrap();
+        assert_eq!(config.provider, Provider::OpenAI);
+        assert_eq!(config.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(config.api_key.as_deref(), Some("sk-test"));
+        assert_eq!(config.base_url.as_deref(), Some("https://openrouter.ai/api/v1"));
+
+        std::env::set_var("MERCO_TEST_ENV_PROVIDER", "bedrock");
+        assert!(matches!(LlmConfig::from_env("MERCO_TEST_ENV"), Err(ConfigError::UnknownProvider(name)) if name == "bedrock"));
+        assert!(matches!(LlmConfig::from_env("MERCO_TEST_UNSET"), Err(ConfigError::MissingEnvVar(name)) if name == "MERCO_TEST_UNSET_PROVIDER"));
+    }
+}
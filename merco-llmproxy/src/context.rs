@@ -0,0 +1,421 @@
+//!
+//! Ambient LLM Context
+//!
+//! Provides `LlmContext`, an optional ambient context holding a default provider and
+//! model, a shared token budget, and a trace id. A context can be installed for the
+//! duration of an async task (`LlmContext::scope`) or as a process-wide default
+//! (`set_global_default`). Helper code such as tools that call the LLM can then use
+//! `context::completion` without having provider handles threaded through every
+//! signature.
+//!
+//! Each context also tracks a nesting depth. Code that hands control to something that
+//! may call the LLM again (such as an agent executing a tool) should run it under
+//! `LlmContext::nested`, so recursive tool/LLM chains stop at `max_depth` instead of
+//! looping forever, while every nested call is still metered by the shared budget.
+
+use crate::cancellation::CancellationToken;
+use crate::traits::{CompletionRequest, CompletionResponse, LlmProvider, ProviderError, TokenUsage};
+use lazy_static::lazy_static;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Default maximum nesting depth for LLM calls made from within tools.
+pub const DEFAULT_MAX_DEPTH: u32 = 3;
+
+tokio::task_local! {
+    static CURRENT_CONTEXT: LlmContext;
+}
+
+lazy_static! {
+    static ref GLOBAL_CONTEXT: RwLock<Option<LlmContext>> = RwLock::new(None);
+}
+
+/// A thread-safe token budget shared by every call made through a context.
+#[derive(Debug, Default)]
+pub struct BudgetTracker {
+    max_tokens: Option<u64>,
+    used_tokens: AtomicU64,
+    calls: AtomicU64,
+}
+
+impl BudgetTracker {
+    /// Creates a tracker with an optional maximum number of total tokens.
+    pub fn new(max_tokens: Option<u64>) -> Self {
+        Self { max_tokens, used_tokens: AtomicU64::new(0), calls: AtomicU64::new(0) }
+    }
+
+    /// Records the token usage of a completed call.
+    pub fn record(&self, usage: &TokenUsage) {
+        self.used_tokens.fetch_add(u64::from(usage.total_tokens), Ordering::Relaxed);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the total number of tokens recorded so far.
+    pub fn used_tokens(&self) -> u64 {
+        self.used_tokens.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of calls recorded so far.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of tokens left, or `None` if the budget is unlimited.
+    pub fn remaining(&self) -> Option<u64> {
+        self.max_tokens.map(|max| max.saturating_sub(self.used_tokens()))
+    }
+
+    /// Checks that the budget has not been used up.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::BudgetExceeded` if no tokens are left.
+    pub fn check(&self) -> Result<(), ProviderError> {
+        match self.remaining() {
+            Some(0) => Err(ProviderError::BudgetExceeded(format!(
+                "token budget of {} exhausted",
+                self.max_tokens.unwrap_or_default()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Ambient state available to code running inside a context scope.
+#[derive(Clone)]
+pub struct LlmContext {
+    /// The default provider used by ambient helpers.
+    pub provider: Arc<dyn LlmProvider>,
+    /// The model used when a request leaves `model` empty.
+    pub default_model: Option<String>,
+    /// Optional budget shared by all calls made through this context.
+    pub budget: Option<Arc<BudgetTracker>>,
+    /// Optional identifier correlating all calls made within this context.
+    pub trace_id: Option<String>,
+    /// The current nesting depth (0 for a top-level context).
+    pub depth: u32,
+    /// The maximum nesting depth allowed by `nested`.
+    pub max_depth: u32,
+    /// Optional token that aborts every call made through this context (and nested ones).
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl fmt::Debug for LlmContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LlmContext")
+            .field("provider", &"<LlmProvider>")
+            .field("default_model", &self.default_model)
+            .field("budget", &self.budget)
+            .field("trace_id", &self.trace_id)
+            .field("depth", &self.depth)
+            .field("max_depth", &self.max_depth)
+            .field("cancellation", &self.cancellation)
+            .finish()
+    }
+}
+
+impl LlmContext {
+    /// Creates a context around the given provider.
+    pub fn new(provider: Arc<dyn LlmProvider>) -> Self {
+        Self {
+            provider,
+            default_model: None,
+            budget: None,
+            trace_id: None,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            cancellation: None,
+        }
+    }
+
+    /// Replaces the provider, keeping the budget, trace id and depth (builder style).
+    pub fn with_provider(mut self, provider: Arc<dyn LlmProvider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// Sets the model used when a request leaves `model` empty (builder style).
+    pub fn with_default_model(mut self, model: String) -> Self {
+        self.default_model = Some(model);
+        self
+    }
+
+    /// Attaches a shared budget tracker (builder style).
+    pub fn with_budget(mut self, budget: Arc<BudgetTracker>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Sets the trace id (builder style).
+    pub fn with_trace_id(mut self, trace_id: String) -> Self {
+        self.trace_id = Some(trace_id);
+        self
+    }
+
+    /// Sets the maximum nesting depth (builder style).
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the token that cancels calls made through this context (builder style).
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Returns `true` if this context's cancellation token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Returns a child context one level deeper, sharing the same budget and trace id.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::RecursionLimitExceeded` if this context is already at `max_depth`.
+    pub fn nested(&self) -> Result<LlmContext, ProviderError> {
+        if self.depth >= self.max_depth {
+            return Err(ProviderError::RecursionLimitExceeded(format!(
+                "nesting depth {} reached the limit of {}",
+                self.depth + 1,
+                self.max_depth
+            )));
+        }
+        let mut child = self.clone();
+        child.depth += 1;
+        Ok(child)
+    }
+
+    /// Runs `future` with this context installed as the current task's ambient context.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT_CONTEXT.scope(self, future).await
+    }
+
+    /// Runs the synchronous closure `f` (e.g. a tool executor) with this context installed
+    /// as the current ambient context.
+    pub fn scope_sync<R>(self, f: impl FnOnce() -> R) -> R {
+        CURRENT_CONTEXT.sync_scope(self, f)
+    }
+
+    /// Sends a completion through this context's provider, applying the default model
+    /// and enforcing and recording the budget.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::BudgetExceeded` if the budget is used up,
+    /// `ProviderError::Cancelled` if the context's token is cancelled, or any error
+    /// returned by the provider.
+    pub async fn completion(&self, mut request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        if let Some(budget) = &self.budget {
+            budget.check()?;
+        }
+        if request.model.is_empty() {
+            request.model = self.default_model.clone().ok_or_else(|| {
+                ProviderError::MissingConfig("model (request has no model and the context has no default)".to_string())
+            })?;
+        }
+
+        let response = match &self.cancellation {
+            Some(token) => self.provider.completion_with_cancellation(request, token).await?,
+            None => self.provider.completion(request).await?,
+        };
+        if let (Some(budget), Some(usage)) = (&self.budget, &response.usage) {
+            budget.record(usage);
+        }
+        Ok(response)
+    }
+}
+
+/// Returns the current ambient context: the task-scoped one if set, else the global default.
+pub fn current() -> Option<LlmContext> {
+    CURRENT_CONTEXT
+        .try_with(|ctx| ctx.clone())
+        .ok()
+        .or_else(|| GLOBAL_CONTEXT.read().ok().and_then(|ctx| ctx.clone()))
+}
+
+/// Installs a process-wide default context, used when no task-scoped context is set.
+pub fn set_global_default(context: LlmContext) {
+    if let Ok(mut global) = GLOBAL_CONTEXT.write() {
+        *global = Some(context);
+    }
+}
+
+/// Removes the process-wide default context.
+pub fn clear_global_default() {
+    if let Ok(mut global) = GLOBAL_CONTEXT.write() {
+        *global = None;
+    }
+}
+
+/// Sends a completion through the current ambient context.
+///
+/// # Errors
+///
+/// Returns `ProviderError::MissingConfig` if no context is installed, or any error
+/// from `LlmContext::completion`.
+pub async fn completion(request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+    let context = current().ok_or_else(|| ProviderError::MissingConfig("ambient LLM context".to_string()))?;
+    context.completion(request).await
+}
+
+/// Blocking variant of `completion` for synchronous code such as tool executors.
+///
+/// Must be called from a multi-threaded tokio runtime (or outside any runtime); the
+/// current-thread runtime cannot block in place.
+///
+/// # Errors
+///
+/// Returns `ProviderError::Unsupported` when called from a current-thread runtime, or any
+/// error from `completion`.
+pub fn completion_blocking(request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+    let context = current().ok_or_else(|| ProviderError::MissingConfig("ambient LLM context".to_string()))?;
+    let call = context.clone().scope(async move { context.completion(request).await });
+
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| handle.block_on(call))
+        }
+        Ok(_) => Err(ProviderError::Unsupported(
+            "completion_blocking requires a multi-threaded tokio runtime".to_string(),
+        )),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| ProviderError::Unexpected(format!("Failed to build runtime: {}", e)))?
+            .block_on(call),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::{CompletionKind, CompletionStream};
+    use async_trait::async_trait;
+
+    struct EchoProvider;
+
+    #[async_trait]
+    impl LlmProvider for EchoProvider {
+        async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+            Ok(CompletionResponse {
+                kind: CompletionKind::Message { content: request.model },
+                usage: Some(TokenUsage { prompt_tokens: 5, completion_tokens: 5, total_tokens: 10 }),
+                finish_reason: None,
+                logprobs: None,
+            })
+        }
+
+        async fn completion_stream(&self, _request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+            Err(ProviderError::Unsupported("streaming".to_string()))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_nested_calls_are_metered_and_depth_limited() {
+        let budget = Arc::new(BudgetTracker::new(Some(100)));
+        let root = LlmContext::new(Arc::new(EchoProvider))
+            .with_default_model("echo".to_string())
+            .with_budget(budget.clone())
+            .with_max_depth(1);
+
+        // A synchronous "tool" running one level deep can call the ambient provider
+        let tool_ctx = root.nested().unwrap();
+        let result = tool_ctx.clone().scope_sync(|| completion_blocking(CompletionRequest::default()));
+        assert!(matches!(result.unwrap().kind, CompletionKind::Message { content } if content == "echo"));
+        assert_eq!(budget.used_tokens(), 10);
+
+        // Going one level deeper is refused
+        assert!(matches!(tool_ctx.nested(), Err(ProviderError::RecursionLimitExceeded(_))));
+    }
+
+    fn echo_context() -> LlmContext {
+        LlmContext::new(Arc::new(EchoProvider)).with_default_model("echo".to_string())
+    }
+
+    fn echoed(response: CompletionResponse) -> String {
+        match response.kind {
+            CompletionKind::Message { content } => content,
+            other => panic!("expected a message, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_budget_stops_calls_once_used_up() {
+        let budget = Arc::new(BudgetTracker::new(Some(25)));
+        let context = echo_context().with_budget(budget.clone());
+
+        // A call is admitted while any tokens are left, and may overshoot the limit
+        for _ in 0..3 {
+            context.completion(CompletionRequest::default()).await.unwrap();
+        }
+        assert_eq!((budget.used_tokens(), budget.calls(), budget.remaining()), (30, 3, Some(0)));
+
+        let error = context.completion(CompletionRequest::default()).await.unwrap_err();
+        assert!(matches!(&error, ProviderError::BudgetExceeded(message) if message == "token budget of 25 exhausted"));
+        assert_eq!(budget.calls(), 3);
+
+        let unlimited = BudgetTracker::new(None);
+        unlimited.record(&TokenUsage { prompt_tokens: 0, completion_tokens: 0, total_tokens: 1_000_000 });
+        assert_eq!(unlimited.remaining(), None);
+        assert!(unlimited.check().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_default_model_fills_empty_requests() {
+        let context = echo_context();
+        let request = CompletionRequest { model: "explicit".to_string(), ..Default::default() };
+        assert_eq!(echoed(context.completion(request).await.unwrap()), "explicit");
+        assert_eq!(echoed(context.completion(CompletionRequest::default()).await.unwrap()), "echo");
+
+        let error = LlmContext::new(Arc::new(EchoProvider)).completion(CompletionRequest::default()).await.unwrap_err();
+        assert!(matches!(error, ProviderError::MissingConfig(_)));
+    }
+
+    #[tokio::test]
+    async fn test_nesting_keeps_the_shared_state() {
+        let budget = Arc::new(BudgetTracker::new(Some(100)));
+        let token = CancellationToken::new();
+        let root = echo_context()
+            .with_budget(budget.clone())
+            .with_trace_id("trace-1".to_string())
+            .with_cancellation(token.clone());
+
+        let child = root.nested().unwrap().nested().unwrap();
+        assert_eq!(child.depth, 2);
+        assert_eq!(child.trace_id.as_deref(), Some("trace-1"));
+        child.completion(CompletionRequest::default()).await.unwrap();
+        assert_eq!(budget.used_tokens(), 10);
+
+        token.cancel();
+        assert!(child.is_cancelled() && root.is_cancelled());
+        let error = child.completion(CompletionRequest::default()).await.unwrap_err();
+        assert!(matches!(error, ProviderError::Cancelled), "{:?}", error);
+
+        let error = child.nested().unwrap().nested().unwrap_err();
+        assert!(matches!(&error, ProviderError::RecursionLimitExceeded(message) if message == "nesting depth 4 reached the limit of 3"));
+    }
+
+    #[tokio::test]
+    async fn test_scoped_context_wins_over_the_global_default() {
+        assert!(matches!(completion(CompletionRequest::default()).await, Err(ProviderError::MissingConfig(_))));
+
+        set_global_default(LlmContext::new(Arc::new(EchoProvider)).with_default_model("global".to_string()));
+        assert_eq!(echoed(completion(CompletionRequest::default()).await.unwrap()), "global");
+        let scoped = echo_context().with_default_model("scoped".to_string());
+        let response = scoped.scope(completion(CompletionRequest::default())).await.unwrap();
+        assert_eq!(echoed(response), "scoped");
+
+        clear_global_default();
+        assert!(current().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_needs_a_multi_threaded_runtime() {
+        let result = echo_context().scope_sync(|| completion_blocking(CompletionRequest::default()));
+        assert!(matches!(result, Err(ProviderError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,283 @@
+//!
+//! Context Window Management
+//!
+//! Provides `ContextManager`, which keeps a conversation within a model's context
+//! window before it is sent. Leading pinned messages (the system prompt by default)
+//! and the most recent turns are always preserved; older messages are either dropped
+//! or replaced by an LLM-written summary.
+
+use crate::tokenizer::{count_message_tokens, tokenizer_for_model, HeuristicTokenizer, Tokenizer};
+use crate::traits::{ChatMessage, ChatMessageRole, CompletionKind, CompletionRequest, LlmProvider, ProviderError};
+use std::fmt;
+use std::sync::Arc;
+
+/// Default number of tokens reserved for the model's reply.
+pub const DEFAULT_RESERVE_TOKENS: usize = 1024;
+/// Default number of most recent messages that are never trimmed.
+pub const DEFAULT_KEEP_RECENT: usize = 4;
+
+/// How messages that no longer fit are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimStrategy {
+    /// Drop the oldest messages.
+    Drop,
+    /// Replace the oldest messages with a summary generated by the provider.
+    Summarize,
+}
+
+/// Trims or summarizes the oldest messages so a conversation fits a context window.
+#[derive(Clone)]
+pub struct ContextManager {
+    context_length: usize,
+    reserve_tokens: usize,
+    keep_recent: usize,
+    pinned: Option<usize>,
+    strategy: TrimStrategy,
+    tokenizer: Arc<dyn Tokenizer>,
+}
+
+impl fmt::Debug for ContextManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContextManager")
+            .field("context_length", &self.context_length)
+            .field("reserve_tokens", &self.reserve_tokens)
+            .field("keep_recent", &self.keep_recent)
+            .field("pinned", &self.pinned)
+            .field("strategy", &self.strategy)
+            .field("tokenizer", &"<Tokenizer>")
+            .finish()
+    }
+}
+
+impl ContextManager {
+    /// Creates a manager for a model with the given context length in tokens.
+    pub fn new(context_length: usize) -> Self {
+        Self {
+            context_length,
+            reserve_tokens: DEFAULT_RESERVE_TOKENS,
+            keep_recent: DEFAULT_KEEP_RECENT,
+            pinned: None,
+            strategy: TrimStrategy::Drop,
+            tokenizer: Arc::new(HeuristicTokenizer::default()),
+        }
+    }
+
+    /// Creates a manager using the known context window and tokenizer of `model`.
+    /// Returns `None` if the model's context window is unknown.
+    pub fn for_model(model: &str) -> Option<Self> {
+        let context_length = crate::tokenizer::context_window(model)?;
+        Some(Self::new(context_length).with_tokenizer(Arc::from(tokenizer_for_model(model))))
+    }
+
+    /// Sets the number of tokens reserved for the reply (builder style).
+    pub fn with_reserve_tokens(mut self, reserve_tokens: usize) -> Self {
+        self.reserve_tokens = reserve_tokens;
+        self
+    }
+
+    /// Sets how many of the most recent messages are never trimmed (builder style).
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// Pins the first `count` messages so they are never trimmed (builder style).
+    /// By default only the leading system messages are pinned.
+    pub fn with_pinned(mut self, count: usize) -> Self {
+        self.pinned = Some(count);
+        self
+    }
+
+    /// Sets the trimming strategy (builder style).
+    pub fn with_strategy(mut self, strategy: TrimStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets the tokenizer used to measure messages (builder style).
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Returns the token budget available for the prompt.
+    pub fn prompt_budget(&self) -> usize {
+        self.context_length.saturating_sub(self.reserve_tokens)
+    }
+
+    /// Returns `true` if `messages` fit in the prompt budget.
+    pub fn fits(&self, messages: &[ChatMessage]) -> bool {
+        count_message_tokens(self.tokenizer.as_ref(), messages) <= self.prompt_budget()
+    }
+
+    fn pinned_count(&self, messages: &[ChatMessage]) -> usize {
+        let pinned = self
+            .pinned
+            .unwrap_or_else(|| messages.iter().take_while(|m| m.role.is_instruction()).count());
+        pinned.min(messages.len())
+    }
+
+    /// Splits `messages` into the pinned prefix, the trimmable middle, and the kept tail,
+    /// dropping from the middle until the rest fits. Returns `(kept, removed)`.
+    fn split(&self, messages: &[ChatMessage]) -> (Vec<ChatMessage>, Vec<ChatMessage>) {
+        let pinned = self.pinned_count(messages);
+        let protected_from = messages.len().saturating_sub(self.keep_recent).max(pinned);
+
+        let mut cut = pinned;
+        while cut < protected_from {
+            let candidate: Vec<ChatMessage> =
+                messages[..pinned].iter().chain(messages[cut..].iter()).cloned().collect();
+            if self.fits(&candidate) {
+                break;
+            }
+            cut += 1;
+        }
+        // Never leave tool results whose assistant tool call was removed
+        while cut < messages.len() && messages[cut].role == ChatMessageRole::Tool {
+            cut += 1;
+        }
+
+        let kept = messages[..pinned].iter().chain(messages[cut..].iter()).cloned().collect();
+        (kept, messages[pinned..cut].to_vec())
+    }
+
+    /// Drops the oldest unpinned messages until the conversation fits (or only pinned
+    /// and recent messages remain).
+    pub fn trim(&self, messages: &[ChatMessage]) -> Vec<ChatMessage> {
+        if self.fits(messages) {
+            return messages.to_vec();
+        }
+        self.split(messages).0
+    }
+
+    /// Fits the conversation using the configured strategy. With `TrimStrategy::Summarize`,
+    /// removed messages are condensed by `provider` into a single note placed after the
+    /// pinned messages.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the summarization request.
+    pub async fn prepare(
+        &self,
+        messages: &[ChatMessage],
+        provider: &dyn LlmProvider,
+        model: &str,
+    ) -> Result<Vec<ChatMessage>, ProviderError> {
+        if self.fits(messages) {
+            return Ok(messages.to_vec());
+        }
+        let (mut kept, removed) = self.split(messages);
+        if self.strategy == TrimStrategy::Drop || removed.is_empty() {
+            return Ok(kept);
+        }
+
+        let summary = self.summarize(&removed, provider, model).await?;
+        let pinned = self.pinned_count(messages);
+        kept.insert(pinned, ChatMessage::user(format!("Summary of the earlier conversation:\n{}", summary)));
+        Ok(kept)
+    }
+
+    async fn summarize(
+        &self,
+        messages: &[ChatMessage],
+        provider: &dyn LlmProvider,
+        model: &str,
+    ) -> Result<String, ProviderError> {
+        let transcript: Vec<String> = messages
+            .iter()
+            .map(|m| {
+                let mut line = format!("{:?}: {}", m.role, m.content.as_deref().unwrap_or_default());
+                for call in m.tool_calls.iter().flatten() {
+                    line.push_str(&format!(" [called {}({})]", call.function.name, call.function.arguments));
+                }
+                line
+            })
+            .collect();
+
+        let request = CompletionRequest {
+            model: model.to_string(),
+            messages: vec![
+                ChatMessage::system(
+                    "Summarize the following conversation excerpt concisely. Keep facts, decisions, tool results and open questions."
+                        .to_string(),
+                ),
+                ChatMessage::user(transcript.join("\n")),
+            ],
+            ..Default::default()
+        };
+        match provider.completion(request).await?.kind {
+            CompletionKind::Message { content } => Ok(content),
+            CompletionKind::ToolCall { .. } => {
+                Err(ProviderError::Unexpected("Summarization returned a tool call".to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_preserves_pinned_and_recent() {
+        let mut messages = vec![ChatMessage::system("system".to_string())];
+        for i in 0..10 {
+            messages.push(ChatMessage::user(format!("{} {}", i, "x".repeat(400))));
+        }
+
+        // Each filler message is ~105 tokens; the budget fits about three of them
+        let manager = ContextManager::new(400).with_reserve_tokens(0).with_keep_recent(2);
+        let trimmed = manager.trim(&messages);
+
+        assert_eq!(trimmed[0].content.as_deref(), Some("system"));
+        assert!(trimmed.last().unwrap().content.as_deref().unwrap().starts_with("9 "));
+        assert!(manager.fits(&trimmed));
+        assert!(trimmed.len() < messages.len());
+    }
+
+    #[test]
+    fn test_developer_instructions_are_pinned() {
+        let mut messages = vec![ChatMessage::system("system"), ChatMessage::developer("developer")];
+        messages.extend((0..10).map(|i| ChatMessage::user(format!("{} {}", i, "x".repeat(400)))));
+
+        let manager = ContextManager::new(400).with_reserve_tokens(0).with_keep_recent(1);
+        let trimmed = manager.trim(&messages);
+
+        assert_eq!(trimmed[1].role, ChatMessageRole::Developer);
+        assert_eq!(serde_json::to_value(&trimmed[1]).unwrap()["role"], "developer");
+    }
+
+    #[test]
+    fn test_explicit_pinning_keeps_leading_user_messages() {
+        let mut messages = vec![ChatMessage::system("system"), ChatMessage::user("task")];
+        messages.extend((0..10).map(|i| ChatMessage::user(format!("{} {}", i, "x".repeat(400)))));
+
+        let default = ContextManager::new(400).with_reserve_tokens(0).with_keep_recent(1);
+        assert_ne!(default.trim(&messages)[1].content.as_deref(), Some("task"));
+
+        let trimmed = default.with_pinned(2).trim(&messages);
+        assert_eq!(trimmed[0].content.as_deref(), Some("system"));
+        assert_eq!(trimmed[1].content.as_deref(), Some("task"));
+        assert!(trimmed.last().unwrap().content.as_deref().unwrap().starts_with("9 "));
+    }
+
+    #[tokio::test]
+    async fn test_summarize_replaces_removed_messages() {
+        let provider = crate::MockProvider::new().with_message("Earlier turns covered 0 to 7.");
+        let mut messages = vec![ChatMessage::system("system")];
+        messages.extend((0..10).map(|i| ChatMessage::user(format!("{} {}", i, "x".repeat(400)))));
+
+        let manager = ContextManager::new(400)
+            .with_reserve_tokens(0)
+            .with_keep_recent(2)
+            .with_strategy(TrimStrategy::Summarize);
+        let prepared = manager.prepare(&messages, &provider, "mock").await.unwrap();
+
+        assert_eq!(prepared[0].content.as_deref(), Some("system"));
+        let summary = prepared[1].content.as_deref().unwrap();
+        assert!(summary.ends_with("Earlier turns covered 0 to 7."), "{}", summary);
+        assert!(prepared.last().unwrap().content.as_deref().unwrap().starts_with("9 "));
+        let transcript = provider.requests()[0].messages[1].content.clone().unwrap();
+        assert!(transcript.contains("User: 0 "), "{}", transcript);
+    }
+}
@@ -0,0 +1,266 @@
+//!
+//! Conversation History
+//!
+//! `Conversation` owns the message list of a multi-turn exchange. Responses are
+//! recorded as assistant messages (text or tool calls), tool results are matched to the
+//! calls they answer, and the history can be kept to a window of recent messages or
+//! trimmed to a token budget with a `ContextManager`. Leading system and developer
+//! messages are never dropped. Conversations serialize to JSON, so a chat can be stored
+//! and resumed later.
+
+use crate::context_manager::ContextManager;
+use crate::traits::{
+    ChatMessage, ChatMessageRole, CompletionKind, CompletionRequest, CompletionResponse, LlmProvider, ProviderError,
+    ToolCallRequest,
+};
+use serde::{Deserialize, Serialize};
+
+/// The message history of a conversation with a model.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    messages: Vec<ChatMessage>,
+    /// Maximum number of messages kept after the leading instructions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    window: Option<usize>,
+}
+
+impl Conversation {
+    /// Creates an empty conversation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continues from existing messages.
+    pub fn from_messages(messages: Vec<ChatMessage>) -> Self {
+        Self { messages, window: None }
+    }
+
+    /// Starts the conversation with a system prompt (builder style).
+    pub fn with_system(mut self, content: impl Into<String>) -> Self {
+        self.messages.insert(self.instruction_count(), ChatMessage::system(content));
+        self
+    }
+
+    /// Keeps at most `max_messages` messages after the leading instructions, dropping
+    /// the oldest as new ones arrive (builder style).
+    pub fn with_window(mut self, max_messages: usize) -> Self {
+        self.window = Some(max_messages);
+        self.apply_window();
+        self
+    }
+
+    /// The messages, oldest first.
+    pub fn messages(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    /// Returns the messages, e.g. to build a request by hand.
+    pub fn into_messages(self) -> Vec<ChatMessage> {
+        self.messages
+    }
+
+    /// Number of messages in the history.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns `true` if the history has no messages.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Appends a message.
+    pub fn push(&mut self, message: ChatMessage) {
+        self.messages.push(message);
+        self.apply_window();
+    }
+
+    /// Appends a user message.
+    pub fn push_user(&mut self, content: impl Into<String>) {
+        self.push(ChatMessage::user(content));
+    }
+
+    /// Appends the model's reply as an assistant message and returns the tool calls it
+    /// requested, if any.
+    pub fn record(&mut self, response: &CompletionResponse) -> &[ToolCallRequest] {
+        let message = match &response.kind {
+            CompletionKind::Message { content } => ChatMessage::assistant(Some(content.clone()), None),
+            CompletionKind::ToolCall { tool_calls } => ChatMessage::assistant(None, Some(tool_calls.clone())),
+        };
+        self.push(message);
+        self.messages.last().and_then(|m| m.tool_calls.as_deref()).unwrap_or_default()
+    }
+
+    /// Appends the result of the tool call `tool_call_id`.
+    pub fn push_tool_result(&mut self, tool_call_id: impl Into<String>, content: impl Into<String>) {
+        self.push(ChatMessage::tool(tool_call_id, content));
+    }
+
+    /// Tool calls of the last assistant message that have no result yet.
+    pub fn pending_tool_calls(&self) -> Vec<&ToolCallRequest> {
+        let Some(position) = self.messages.iter().rposition(|m| m.role == ChatMessageRole::Assistant) else {
+            return Vec::new();
+        };
+        let answered: Vec<&str> = self.messages[position + 1..]
+            .iter()
+            .filter_map(|m| m.tool_call_id.as_deref())
+            .collect();
+        self.messages[position]
+            .tool_calls
+            .iter()
+            .flatten()
+            .filter(|call| !answered.contains(&call.id.as_str()))
+            .collect()
+    }
+
+    /// The text of the latest assistant reply, if any.
+    pub fn last_reply(&self) -> Option<&str> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == ChatMessageRole::Assistant && m.content.is_some())
+            .and_then(|m| m.content.as_deref())
+    }
+
+    /// Removes everything except the leading instructions.
+    pub fn clear(&mut self) {
+        self.messages.truncate(self.instruction_count());
+    }
+
+    /// Drops the oldest messages until the history fits `manager`'s prompt budget.
+    pub fn trim_to(&mut self, manager: &ContextManager) {
+        self.messages = manager.trim(&self.messages);
+    }
+
+    /// Sends the history with `request`'s model and settings, and records the reply.
+    /// Any messages already in `request` are replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns the provider error; the history is left unchanged in that case.
+    pub async fn send(
+        &mut self,
+        provider: &dyn LlmProvider,
+        mut request: CompletionRequest,
+    ) -> Result<CompletionResponse, ProviderError> {
+        request.messages = self.messages.clone();
+        let response = provider.completion(request).await?;
+        self.record(&response);
+        Ok(response)
+    }
+
+    /// Serializes the conversation to JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Restores a conversation saved with `to_json`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `json` is not a serialized conversation.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    fn instruction_count(&self) -> usize {
+        self.messages.iter().take_while(|m| m.role.is_instruction()).count()
+    }
+
+    fn apply_window(&mut self) {
+        let Some(window) = self.window else { return };
+        let pinned = self.instruction_count();
+        let mut cut = pinned + (self.messages.len() - pinned).saturating_sub(window);
+        // Never start the kept history with results of a dropped tool call
+        while cut < self.messages.len() && self.messages[cut].role == ChatMessageRole::Tool {
+            cut += 1;
+        }
+        self.messages.drain(pinned..cut);
+    }
+}
+
+impl From<Vec<ChatMessage>> for Conversation {
+    fn from(messages: Vec<ChatMessage>) -> Self {
+        Self::from_messages(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use crate::traits::ToolCallFunction;
+
+    fn tool_call_response(id: &str) -> CompletionResponse {
+        let call = ToolCallRequest::new_function_call(
+            id.to_string(),
+            ToolCallFunction { name: "search".to_string(), arguments: "{}".to_string() },
+        );
+        CompletionResponse {
+            kind: CompletionKind::ToolCall { tool_calls: vec![call] },
+            usage: None,
+            finish_reason: None,
+            logprobs: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_records_replies() {
+        let provider = MockProvider::new().with_message("Hi!").with_message("Fine, thanks.");
+        let mut conversation = Conversation::new().with_system("Be friendly.");
+        let request = CompletionRequest { model: "test-model".to_string(), ..Default::default() };
+
+        conversation.push_user("Hello");
+        conversation.send(&provider, request.clone()).await.unwrap();
+        conversation.push_user("How are you?");
+        conversation.send(&provider, request).await.unwrap();
+
+        assert_eq!(conversation.len(), 5);
+        assert_eq!(conversation.last_reply(), Some("Fine, thanks."));
+        assert_eq!(provider.requests()[1].messages.len(), 4);
+    }
+
+    #[test]
+    fn test_tracks_pending_tool_calls() {
+        let mut conversation = Conversation::new();
+        conversation.push_user("Search for rust");
+        let calls = conversation.record(&tool_call_response("call_1"));
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(conversation.pending_tool_calls().len(), 1);
+
+        conversation.push_tool_result("call_1", "results");
+        assert!(conversation.pending_tool_calls().is_empty());
+    }
+
+    #[test]
+    fn test_window_keeps_instructions_and_tool_pairs() {
+        let mut conversation = Conversation::new().with_system("system").with_window(2);
+        conversation.push_user("first");
+        conversation.record(&tool_call_response("call_1"));
+        conversation.push_tool_result("call_1", "result");
+        conversation.push_user("second");
+
+        // The window would start at the tool result, so it is dropped with its call
+        let roles: Vec<ChatMessageRole> = conversation.messages().iter().map(|m| m.role).collect();
+        assert_eq!(roles, vec![ChatMessageRole::System, ChatMessageRole::User]);
+
+        conversation.clear();
+        assert_eq!(conversation.len(), 1);
+    }
+
+    #[test]
+    fn test_round_trips_through_json() {
+        let mut conversation = Conversation::new().with_system("system").with_window(10);
+        conversation.push_user("Hello");
+        conversation.record(&tool_call_response("call_1"));
+
+        let restored = Conversation::from_json(&conversation.to_json().unwrap()).unwrap();
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.pending_tool_calls()[0].id, "call_1");
+        assert_eq!(restored.window, Some(10));
+    }
+}
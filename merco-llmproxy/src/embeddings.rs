@@ -0,0 +1,113 @@
+//!
+//! Embeddings
+//!
+//! `EmbeddingProvider` turns texts into vectors for semantic search and RAG. Requests
+//! take a batch of inputs and return one vector per input, in order; providers split
+//! large batches into several API calls as needed.
+
+use crate::traits::{ProviderError, TokenUsage};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// A request to embed one or more texts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    /// The embedding model identifier (e.g. `nomic-embed-text`).
+    pub model: String,
+    /// The texts to embed.
+    pub input: Vec<String>,
+    /// Output vector size, for models that support shortening their embeddings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>,
+}
+
+impl EmbeddingRequest {
+    /// Creates a request embedding `input` with `model`.
+    pub fn new(model: impl Into<String>, input: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self { model: model.into(), input: input.into_iter().map(Into::into).collect(), dimensions: None }
+    }
+
+    /// Sets the output vector size (builder style).
+    pub fn with_dimensions(mut self, dimensions: u32) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+}
+
+/// The vectors of an `EmbeddingRequest`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    /// One vector per input, in input order.
+    pub embeddings: Vec<Vec<f32>>,
+    /// Token usage, if the provider reported it. `completion_tokens` is always 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+/// A provider that can compute embeddings.
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Embeds every input of `request`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ProviderError` if a call fails or the provider returns a different
+    /// number of vectors than inputs.
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse, ProviderError>;
+}
+
+/// Cosine similarity of two vectors, between -1.0 and 1.0; 0.0 if either is all zeros
+/// or their lengths differ.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot / norm
+    }
+}
+
+/// Adds up the usage of several batches, keeping `None` if no batch reported any.
+#[cfg_attr(not(feature = "ollama"), allow(dead_code))]
+pub(crate) fn sum_usage(total: Option<TokenUsage>, batch: Option<TokenUsage>) -> Option<TokenUsage> {
+    match (total, batch) {
+        (Some(total), Some(batch)) => Some(TokenUsage {
+            prompt_tokens: total.prompt_tokens + batch.prompt_tokens,
+            completion_tokens: total.completion_tokens + batch.completion_tokens,
+            total_tokens: total.total_tokens + batch.total_tokens,
+        }),
+        (total, batch) => total.or(batch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_mock_embeddings_rank_related_texts_higher() {
+        let request = EmbeddingRequest::new(
+            "mock",
+            ["rust borrow checker", "the borrow checker in rust", "baking sourdough bread"],
+        );
+        let embeddings = MockProvider::new().embed(request).await.unwrap().embeddings;
+
+        assert_eq!(embeddings.len(), 3);
+        let related = cosine_similarity(&embeddings[0], &embeddings[1]);
+        let unrelated = cosine_similarity(&embeddings[0], &embeddings[2]);
+        assert!(related > unrelated);
+    }
+}
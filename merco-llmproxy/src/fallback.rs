@@ -0,0 +1,138 @@
+//!
+//! Provider Fallback Chains
+//!
+//! Provides `FallbackProvider`, which tries an ordered list of providers (each with its
+//! own model) and moves on to the next one when a call fails with a retryable error
+//! (rate limit, exhausted quota, 5xx, timeout). Non-retryable errors such as invalid
+//! requests are returned immediately, since another vendor would reject them too.
+
+use crate::config::LlmConfig;
+use crate::traits::{CompletionRequest, CompletionResponse, CompletionStream, LlmProvider, ProviderError};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Tries each provider in order until one succeeds or fails with a non-retryable error.
+pub struct FallbackProvider {
+    chain: Vec<(Arc<dyn LlmProvider>, String)>,
+}
+
+impl FallbackProvider {
+    /// Builds a chain from ordered `(config, model)` pairs.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::ConfigError` if the list is empty or any config is invalid.
+    pub fn new(chain: Vec<(LlmConfig, String)>) -> Result<Self, ProviderError> {
+        let providers = chain
+            .into_iter()
+            .map(|(config, model)| crate::get_provider(config).map(|provider| (provider, model)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_providers(providers)
+    }
+
+    /// Builds a chain from already constructed `(provider, model)` pairs.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::ConfigError` if the list is empty.
+    pub fn from_providers(chain: Vec<(Arc<dyn LlmProvider>, String)>) -> Result<Self, ProviderError> {
+        if chain.is_empty() {
+            return Err(ProviderError::ConfigError("FallbackProvider requires at least one provider".to_string()));
+        }
+        Ok(Self { chain })
+    }
+
+    fn request_for(request: &CompletionRequest, model: &str) -> CompletionRequest {
+        let mut request = request.clone();
+        request.model = model.to_string();
+        request
+    }
+}
+
+#[async_trait]
+impl LlmProvider for FallbackProvider {
+    async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        let mut last_error = None;
+        for (provider, model) in &self.chain {
+            match provider.completion(Self::request_for(&request, model)).await {
+                Ok(response) => return Ok(response),
+                Err(e) if e.is_retryable() => {
+                    eprintln!("[Fallback] Model '{}' failed ({}), trying next provider", model, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| ProviderError::Unexpected("Fallback chain is empty".to_string())))
+    }
+
+    /// Falls back only while opening the stream; errors after streaming has started are
+    /// passed through, as partial output has already been delivered.
+    async fn completion_stream(&self, request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let mut last_error = None;
+        for (provider, model) in &self.chain {
+            match provider.completion_stream(Self::request_for(&request, model)).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) if e.is_retryable() => {
+                    eprintln!("[Fallback] Model '{}' failed ({}), trying next provider", model, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| ProviderError::Unexpected("Fallback chain is empty".to_string())))
+    }
+
+    /// Warms every provider in the chain with its own model; returns the first failure.
+    async fn warmup(&self, _model: &str) -> Result<(), ProviderError> {
+        let results = futures::future::join_all(self.chain.iter().map(|(provider, model)| provider.warmup(model))).await;
+        results.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::CompletionKind;
+
+    // Fails with the given status, or answers with the requested model name
+    struct StubProvider(Option<u16>);
+
+    #[async_trait]
+    impl LlmProvider for StubProvider {
+        async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+            match self.0 {
+                Some(status) => Err(ProviderError::ApiError { status, message: "stub failure".to_string() }),
+                None => Ok(CompletionResponse {
+                    kind: CompletionKind::Message { content: request.model },
+                    usage: None,
+                    finish_reason: None,
+                    logprobs: None,
+                }),
+            }
+        }
+
+        async fn completion_stream(&self, _request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+            Err(ProviderError::Unsupported("streaming".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_on_retryable_errors_only() {
+        let chain = FallbackProvider::from_providers(vec![
+            (Arc::new(StubProvider(Some(503))) as Arc<dyn LlmProvider>, "primary".to_string()),
+            (Arc::new(StubProvider(None)), "secondary".to_string()),
+        ])
+        .unwrap();
+        let response = chain.completion(CompletionRequest::default()).await.unwrap();
+        assert!(matches!(response.kind, CompletionKind::Message { content } if content == "secondary"));
+
+        let chain = FallbackProvider::from_providers(vec![
+            (Arc::new(StubProvider(Some(400))) as Arc<dyn LlmProvider>, "primary".to_string()),
+            (Arc::new(StubProvider(None)), "secondary".to_string()),
+        ])
+        .unwrap();
+        let error = chain.completion(CompletionRequest::default()).await.unwrap_err();
+        assert!(matches!(error, ProviderError::ApiError { status: 400, .. }));
+    }
+}
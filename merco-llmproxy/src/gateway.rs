@@ -0,0 +1,600 @@
+//!
+//! OpenAI-Compatible Gateway
+//!
+//! An axum server exposing `POST /v1/chat/completions` (streaming and non-streaming) and
+//! `GET /v1/models` in the OpenAI wire format, so applications in any language can reach
+//! Merco providers through an ordinary OpenAI client. Each named route wraps a provider,
+//! usually a `LayeredProvider` carrying retry, caching and cost middleware.
+//!
+//! The request's `model` picks the route: `<route>/<model>` sends `<model>` to that route,
+//! the bare route name uses the route's default model, and anything else goes to the
+//! default route unchanged (so `openai/gpt-4o` still reaches an OpenRouter default route
+//! when no route is called `openai`).
+
+use crate::traits::{
+    ChatMessage, ChatMessageRole, CompletionKind, CompletionRequest, CompletionResponse, CompletionStreamChunk,
+    JsonSchema, LlmProvider, ProviderError, ReasoningEffort, ResponseFormat, StreamContentDelta, TokenUsage, Tool,
+    ToolCallRequest, ToolChoice,
+};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::stream::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+struct Route {
+    provider: Arc<dyn LlmProvider>,
+    model: Option<String>,
+}
+
+/// An OpenAI-compatible HTTP front end for one or more providers.
+pub struct Gateway {
+    routes: BTreeMap<String, Route>,
+    default_route: Option<String>,
+    api_key: Option<String>,
+}
+
+impl Gateway {
+    /// Creates a gateway with no routes.
+    pub fn new() -> Self {
+        Self { routes: BTreeMap::new(), default_route: None, api_key: None }
+    }
+
+    /// Adds a route named `name` (builder style). The first route added is the default.
+    pub fn with_route(mut self, name: impl Into<String>, provider: Arc<dyn LlmProvider>) -> Self {
+        self.insert_route(name.into(), Route { provider, model: None });
+        self
+    }
+
+    /// Adds a route that also answers requests for the bare route name with `model`
+    /// (builder style).
+    pub fn with_route_model(
+        mut self,
+        name: impl Into<String>,
+        provider: Arc<dyn LlmProvider>,
+        model: impl Into<String>,
+    ) -> Self {
+        self.insert_route(name.into(), Route { provider, model: Some(model.into()) });
+        self
+    }
+
+    /// Sends requests whose model names no route to `name` (builder style).
+    pub fn with_default_route(mut self, name: impl Into<String>) -> Self {
+        self.default_route = Some(name.into());
+        self
+    }
+
+    /// Requires clients to send `Authorization: Bearer <api_key>` (builder style).
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    fn insert_route(&mut self, name: String, route: Route) {
+        if self.default_route.is_none() {
+            self.default_route = Some(name.clone());
+        }
+        self.routes.insert(name, route);
+    }
+
+    /// Builds the axum router serving the gateway's endpoints.
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .route("/v1/models", get(list_models))
+            .with_state(Arc::new(self))
+    }
+
+    /// Serves the gateway on `addr` until `shutdown` resolves.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be bound.
+    pub async fn serve(self, addr: SocketAddr, shutdown: impl Future<Output = ()> + Send + 'static) -> std::io::Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, self.router()).with_graceful_shutdown(shutdown).await
+    }
+
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), GatewayError> {
+        let Some(api_key) = &self.api_key else { return Ok(()) };
+        let presented = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if presented == Some(api_key.as_str()) {
+            Ok(())
+        } else {
+            Err(GatewayError::new(StatusCode::UNAUTHORIZED, "invalid_api_key", "Invalid or missing API key"))
+        }
+    }
+
+    /// Picks the provider and upstream model name for a requested model.
+    fn resolve(&self, model: &str) -> Result<(Arc<dyn LlmProvider>, String), GatewayError> {
+        if let Some(route) = self.routes.get(model) {
+            let Some(default_model) = &route.model else {
+                return Err(GatewayError::invalid_request(format!(
+                    "Route '{}' has no default model; use '{}/<model>'",
+                    model, model
+                )));
+            };
+            return Ok((route.provider.clone(), default_model.clone()));
+        }
+        if let Some((name, upstream)) = model.split_once('/') {
+            if let Some(route) = self.routes.get(name) {
+                return Ok((route.provider.clone(), upstream.to_string()));
+            }
+        }
+        match self.default_route.as_ref().and_then(|name| self.routes.get(name)) {
+            Some(route) => Ok((route.provider.clone(), model.to_string())),
+            None => Err(GatewayError::new(
+                StatusCode::NOT_FOUND,
+                "model_not_found",
+                format!("No route serves model '{}'", model),
+            )),
+        }
+    }
+}
+
+impl Default for Gateway {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An error returned to the client as an OpenAI-style `{"error": {...}}` body.
+#[derive(Debug)]
+struct GatewayError {
+    status: StatusCode,
+    kind: &'static str,
+    message: String,
+}
+
+impl GatewayError {
+    fn new(status: StatusCode, kind: &'static str, message: impl Into<String>) -> Self {
+        Self { status, kind, message: message.into() }
+    }
+
+    fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid_request_error", message)
+    }
+
+    fn body(&self) -> JsonValue {
+        json!({ "error": { "message": self.message, "type": self.kind, "code": null } })
+    }
+}
+
+impl From<ProviderError> for GatewayError {
+    fn from(error: ProviderError) -> Self {
+        let (status, kind) = match &error {
+            ProviderError::ApiError { status, .. } => {
+                (StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY), "upstream_error")
+            }
+            ProviderError::ConfigError(_)
+            | ProviderError::MissingConfig(_)
+            | ProviderError::ToolFormatError(_)
+            | ProviderError::Unsupported(_) => (StatusCode::BAD_REQUEST, "invalid_request_error"),
+            ProviderError::BudgetExceeded(_) => (StatusCode::TOO_MANY_REQUESTS, "budget_exceeded"),
+            #[cfg(feature = "http-transport")]
+            ProviderError::RequestError(_) => (StatusCode::BAD_GATEWAY, "upstream_error"),
+            ProviderError::ParseError(_)
+            | ProviderError::StreamError(_)
+            | ProviderError::TransportError(_) => (StatusCode::BAD_GATEWAY, "upstream_error"),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "server_error"),
+        };
+        Self::new(status, kind, error.to_string())
+    }
+}
+
+impl IntoResponse for GatewayError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body())).into_response()
+    }
+}
+
+// --- Wire format ---
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionRequest {
+    model: String,
+    messages: Vec<WireMessage>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    max_tokens: Option<u32>,
+    max_completion_tokens: Option<u32>,
+    frequency_penalty: Option<f32>,
+    presence_penalty: Option<f32>,
+    seed: Option<u64>,
+    stop: Option<WireStop>,
+    logprobs: Option<bool>,
+    top_logprobs: Option<u8>,
+    tools: Option<Vec<WireTool>>,
+    tool_choice: Option<JsonValue>,
+    response_format: Option<JsonValue>,
+    reasoning_effort: Option<ReasoningEffort>,
+    #[serde(default)]
+    stream: bool,
+    stream_options: Option<WireStreamOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WireMessage {
+    role: ChatMessageRole,
+    // A string, an array of content parts, or null
+    #[serde(default)]
+    content: Option<JsonValue>,
+    tool_calls: Option<Vec<ToolCallRequest>>,
+    tool_call_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum WireStop {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct WireTool {
+    function: WireFunction,
+}
+
+#[derive(Debug, Deserialize)]
+struct WireFunction {
+    name: String,
+    #[serde(default)]
+    description: String,
+    parameters: Option<JsonValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WireStreamOptions {
+    #[serde(default)]
+    include_usage: bool,
+}
+
+impl WireMessage {
+    fn into_message(self) -> Result<ChatMessage, GatewayError> {
+        let content = match self.content {
+            None | Some(JsonValue::Null) => None,
+            Some(JsonValue::String(text)) => Some(text),
+            Some(JsonValue::Array(parts)) => {
+                let mut text = String::new();
+                for part in parts {
+                    match (part.get("type").and_then(JsonValue::as_str), part.get("text").and_then(JsonValue::as_str)) {
+                        (Some("text"), Some(part_text)) => text.push_str(part_text),
+                        (kind, _) => {
+                            return Err(GatewayError::invalid_request(format!(
+                                "Unsupported content part type '{}'; only text is supported",
+                                kind.unwrap_or("unknown")
+                            )))
+                        }
+                    }
+                }
+                Some(text)
+            }
+            Some(_) => return Err(GatewayError::invalid_request("Message content must be a string or an array")),
+        };
+        Ok(ChatMessage::new(self.role, content, self.tool_calls, self.tool_call_id))
+    }
+}
+
+fn parse_tool_choice(value: JsonValue) -> Result<ToolChoice, GatewayError> {
+    match &value {
+        JsonValue::String(choice) => match choice.as_str() {
+            "auto" => Ok(ToolChoice::Auto),
+            "none" => Ok(ToolChoice::None),
+            "required" => Ok(ToolChoice::Required),
+            other => Err(GatewayError::invalid_request(format!("Unknown tool_choice '{}'", other))),
+        },
+        _ => value
+            .pointer("/function/name")
+            .and_then(JsonValue::as_str)
+            .map(|name| ToolChoice::Function(name.to_string()))
+            .ok_or_else(|| GatewayError::invalid_request("tool_choice object must name a function")),
+    }
+}
+
+fn parse_response_format(value: JsonValue) -> Result<ResponseFormat, GatewayError> {
+    match value.get("type").and_then(JsonValue::as_str) {
+        Some("text") => Ok(ResponseFormat::Text),
+        Some("json_object") => Ok(ResponseFormat::JsonObject),
+        Some("json_schema") => {
+            let spec = value.get("json_schema").cloned().unwrap_or_default();
+            Ok(ResponseFormat::JsonSchema {
+                name: spec.get("name").and_then(JsonValue::as_str).unwrap_or("response").to_string(),
+                schema: spec.get("schema").cloned().unwrap_or_else(|| json!({})),
+                strict: spec.get("strict").and_then(JsonValue::as_bool),
+            })
+        }
+        _ => Err(GatewayError::invalid_request("Unknown response_format type")),
+    }
+}
+
+impl WireTool {
+    fn into_tool(self) -> Tool {
+        let object = JsonSchema { schema_type: "object".to_string(), properties: None, required: None };
+        let parameters = self.function.parameters.and_then(|p| serde_json::from_value(p).ok()).unwrap_or(object);
+        Tool { name: self.function.name, description: self.function.description, parameters }
+    }
+}
+
+impl ChatCompletionRequest {
+    fn into_completion_request(self, model: String) -> Result<CompletionRequest, GatewayError> {
+        let messages = self.messages.into_iter().map(WireMessage::into_message).collect::<Result<Vec<_>, _>>()?;
+        Ok(CompletionRequest {
+            messages,
+            model,
+            temperature: self.temperature,
+            max_tokens: self.max_completion_tokens.or(self.max_tokens),
+            top_p: self.top_p,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+            seed: self.seed,
+            stop: self.stop.map(|stop| match stop {
+                WireStop::One(stop) => vec![stop],
+                WireStop::Many(stops) => stops,
+            }),
+            logprobs: self.logprobs,
+            top_logprobs: self.top_logprobs,
+            tools: self.tools.map(|tools| tools.into_iter().map(WireTool::into_tool).collect()),
+            tool_choice: self.tool_choice.map(parse_tool_choice).transpose()?,
+            response_format: self.response_format.map(parse_response_format).transpose()?,
+            reasoning_effort: self.reasoning_effort,
+            ..Default::default()
+        })
+    }
+}
+
+/// Fields shared by every response object of one completion.
+struct ResponseMeta {
+    id: String,
+    created: u64,
+    model: String,
+}
+
+impl ResponseMeta {
+    fn new(model: String) -> Self {
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(created);
+        Self { id: format!("chatcmpl-{:016x}", hasher.finish()), created, model }
+    }
+
+    fn completion(&self, response: CompletionResponse) -> JsonValue {
+        let (content, tool_calls, default_finish) = match response.kind {
+            CompletionKind::Message { content } => (Some(content), None, "stop"),
+            CompletionKind::ToolCall { tool_calls } => (None, Some(tool_calls), "tool_calls"),
+        };
+        let mut message = json!({ "role": "assistant", "content": content });
+        if let Some(tool_calls) = tool_calls {
+            message["tool_calls"] = json!(tool_calls);
+        }
+        json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "message": message,
+                "finish_reason": response.finish_reason.as_deref().unwrap_or(default_finish),
+                "logprobs": response.logprobs.map(|content| json!({ "content": content })),
+            }],
+            "usage": response.usage.map(usage_json),
+        })
+    }
+
+    fn chunk(&self, delta: JsonValue, finish_reason: Option<&str>) -> JsonValue {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        })
+    }
+
+    fn usage_chunk(&self, usage: TokenUsage) -> JsonValue {
+        json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [],
+            "usage": usage_json(usage),
+        })
+    }
+}
+
+fn usage_json(usage: TokenUsage) -> JsonValue {
+    json!({
+        "prompt_tokens": usage.prompt_tokens,
+        "completion_tokens": usage.completion_tokens,
+        "total_tokens": usage.total_tokens,
+    })
+}
+
+fn delta_json(chunk: &CompletionStreamChunk) -> JsonValue {
+    match &chunk.delta {
+        StreamContentDelta::Text(text) => json!({ "content": text }),
+        StreamContentDelta::Reasoning(text) => json!({ "reasoning_content": text }),
+        StreamContentDelta::ToolCallDelta(deltas) => {
+            let tool_calls: Vec<JsonValue> = deltas
+                .iter()
+                .map(|delta| {
+                    let mut call = json!({ "index": delta.index });
+                    if let Some(id) = &delta.id {
+                        call["id"] = json!(id);
+                        call["type"] = json!("function");
+                    }
+                    if let Some(function) = &delta.function {
+                        call["function"] = json!({ "name": function.name, "arguments": function.arguments });
+                    }
+                    call
+                })
+                .collect();
+            json!({ "tool_calls": tool_calls })
+        }
+    }
+}
+
+// --- Handlers ---
+
+async fn chat_completions(
+    State(gateway): State<Arc<Gateway>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, GatewayError> {
+    gateway.authorize(&headers)?;
+    let request: ChatCompletionRequest = serde_json::from_slice(&body)
+        .map_err(|e| GatewayError::invalid_request(format!("Invalid request body: {}", e)))?;
+    let (provider, model) = gateway.resolve(&request.model)?;
+    let meta = ResponseMeta::new(request.model.clone());
+    let stream = request.stream;
+    let include_usage = request.stream_options.as_ref().is_some_and(|options| options.include_usage);
+    let request = request.into_completion_request(model)?;
+
+    if !stream {
+        let response = provider.completion(request).await?;
+        return Ok(Json(meta.completion(response)).into_response());
+    }
+
+    // Opening the stream can still fail with a proper HTTP error; later failures are
+    // reported in-band as an `error` event.
+    let mut upstream = provider.completion_stream(request).await?;
+    let (sender, receiver) = futures::channel::mpsc::unbounded::<Result<Event, Infallible>>();
+    let send = move |data: String| sender.unbounded_send(Ok(Event::default().data(data))).is_ok();
+    tokio::spawn(async move {
+        let mut first = true;
+        let mut finished = false;
+        let mut tool_calls = false;
+        let mut usage = None;
+        while let Some(chunk) = upstream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    send(GatewayError::from(error).body().to_string());
+                    return;
+                }
+            };
+            let mut delta = delta_json(&chunk);
+            if first {
+                delta["role"] = json!("assistant");
+                first = false;
+            }
+            tool_calls |= matches!(chunk.delta, StreamContentDelta::ToolCallDelta(_));
+            finished |= chunk.finish_reason.is_some();
+            usage = chunk.usage.or(usage);
+            // Stops early when the client has gone away, which drops the upstream stream
+            if !send(meta.chunk(delta, chunk.finish_reason.as_deref()).to_string()) {
+                return;
+            }
+        }
+        if !finished {
+            let finish_reason = if tool_calls { "tool_calls" } else { "stop" };
+            send(meta.chunk(json!({}), Some(finish_reason)).to_string());
+        }
+        if let Some(usage) = usage.filter(|_| include_usage) {
+            send(meta.usage_chunk(usage).to_string());
+        }
+        send("[DONE]".to_string());
+    });
+    Ok(Sse::new(receiver).keep_alive(KeepAlive::default()).into_response())
+}
+
+async fn list_models(State(gateway): State<Arc<Gateway>>, headers: HeaderMap) -> Result<Response, GatewayError> {
+    gateway.authorize(&headers)?;
+    let data: Vec<JsonValue> = gateway
+        .routes
+        .keys()
+        .map(|name| json!({ "id": name, "object": "model", "created": 0, "owned_by": "merco" }))
+        .collect();
+    Ok(Json(json!({ "object": "list", "data": data })).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+
+    // Serves `router` on a free port and posts `body` to its chat completions endpoint
+    async fn post(router: Router, body: JsonValue) -> (StatusCode, String) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{}/v1/chat/completions", addr))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        (StatusCode::from_u16(response.status().as_u16()).unwrap(), response.text().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_model_prefix() {
+        let local = Arc::new(MockProvider::new().with_message("from local"));
+        let remote = Arc::new(MockProvider::new().with_message("from remote"));
+        let router = Gateway::new().with_route("remote", remote.clone()).with_route("local", local.clone()).router();
+
+        let (status, body) =
+            post(router, json!({ "model": "local/llama3.2", "messages": [{ "role": "user", "content": "hi" }] })).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let body: JsonValue = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["choices"][0]["message"]["content"], "from local");
+        assert_eq!(body["model"], "local/llama3.2");
+        assert_eq!(local.requests()[0].model, "llama3.2");
+        assert_eq!(remote.call_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_streams_sse_chunks() {
+        let provider = Arc::new(MockProvider::new().with_message("Hello there"));
+        let router = Gateway::new().with_route_model("local", provider, "llama3.2").router();
+
+        let (status, body) = post(
+            router,
+            json!({
+                "model": "local",
+                "stream": true,
+                "stream_options": { "include_usage": true },
+                "messages": [{ "role": "user", "content": [{ "type": "text", "text": "hi" }] }],
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let events: Vec<&str> = body.lines().filter_map(|line| line.strip_prefix("data: ")).collect();
+        assert_eq!(events.last(), Some(&"[DONE]"));
+        let chunks: Vec<JsonValue> = events[..events.len() - 1].iter().map(|e| serde_json::from_str(e).unwrap()).collect();
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        let text: String = chunks.iter().filter_map(|c| c["choices"][0]["delta"]["content"].as_str()).collect();
+        assert_eq!(text, "Hello there");
+        assert!(chunks.last().unwrap()["usage"]["total_tokens"].as_u64().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_missing_api_key() {
+        let router = Gateway::new().with_route("local", Arc::new(MockProvider::new())).with_api_key("secret").router();
+
+        let (status, body) = post(router, json!({ "model": "local/x", "messages": [] })).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("invalid_api_key"));
+    }
+}
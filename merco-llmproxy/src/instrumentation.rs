@@ -0,0 +1,272 @@
+//!
+//! Tracing Instrumentation
+//!
+//! Provides `TracedProvider`, which wraps a provider and records a `tracing` span per
+//! call using the OpenTelemetry GenAI semantic conventions (`gen_ai.system`,
+//! `gen_ai.request.model`, `gen_ai.usage.input_tokens`, ...) plus the call latency.
+//! Spans can be exported to OpenTelemetry with `tracing-opentelemetry`. Available with
+//! the `tracing` feature; `get_provider` wraps every provider automatically.
+
+use crate::traits::{CompletionRequest, CompletionResponse, CompletionStream, LlmProvider, ProviderError, TokenUsage};
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+/// Wraps a provider and emits a GenAI-convention span for every completion.
+pub struct TracedProvider {
+    inner: Arc<dyn LlmProvider>,
+    system: String,
+}
+
+impl TracedProvider {
+    /// Wraps `inner`; `system` identifies the vendor (e.g. `openai`, `ollama`).
+    pub fn new(inner: Arc<dyn LlmProvider>, system: impl Into<String>) -> Self {
+        Self { inner, system: system.into() }
+    }
+
+    fn span(&self, request: &CompletionRequest, streaming: bool) -> Span {
+        tracing::info_span!(
+            "gen_ai.chat",
+            otel.name = %format!("chat {}", request.model),
+            otel.kind = "client",
+            gen_ai.operation.name = "chat",
+            gen_ai.system = %self.system,
+            gen_ai.request.model = %request.model,
+            gen_ai.request.temperature = request.temperature.map(f64::from),
+            gen_ai.request.max_tokens = request.max_tokens,
+            gen_ai.request.top_p = request.top_p.map(f64::from),
+            gen_ai.request.streaming = streaming,
+            gen_ai.response.finish_reasons = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            duration_ms = Empty,
+            error.type = Empty,
+        )
+    }
+}
+
+fn record_usage(span: &Span, usage: &TokenUsage) {
+    span.record("gen_ai.usage.input_tokens", usage.prompt_tokens);
+    span.record("gen_ai.usage.output_tokens", usage.completion_tokens);
+}
+
+fn record_error(span: &Span, error: &ProviderError) {
+    let error_type = match error {
+        ProviderError::ApiError { status, .. } => status.to_string(),
+        other => format!("{:?}", other).split('(').next().unwrap_or("error").to_string(),
+    };
+    span.record("error.type", error_type.as_str());
+    tracing::warn!(parent: span, error = %error, "LLM call failed");
+}
+
+#[async_trait]
+impl LlmProvider for TracedProvider {
+    async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        let span = self.span(&request, false);
+        let started = Instant::now();
+        let result = self.inner.completion(request).instrument(span.clone()).await;
+
+        span.record("duration_ms", started.elapsed().as_millis() as u64);
+        match &result {
+            Ok(response) => {
+                if let Some(usage) = &response.usage {
+                    record_usage(&span, usage);
+                }
+                if let Some(reason) = &response.finish_reason {
+                    span.record("gen_ai.response.finish_reasons", reason.as_str());
+                }
+            }
+            Err(e) => record_error(&span, e),
+        }
+        result
+    }
+
+    async fn completion_stream(&self, request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let span = self.span(&request, true);
+        let started = Instant::now();
+        let stream = match self.inner.completion_stream(request).instrument(span.clone()).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                span.record("duration_ms", started.elapsed().as_millis() as u64);
+                record_error(&span, &e);
+                return Err(e);
+            }
+        };
+
+        // The span stays open until the stream is dropped; latency covers the full stream
+        let stream = stream.inspect(move |chunk| match chunk {
+            Ok(chunk) => {
+                if let Some(usage) = &chunk.usage {
+                    record_usage(&span, usage);
+                }
+                if let Some(reason) = &chunk.finish_reason {
+                    span.record("gen_ai.response.finish_reasons", reason.as_str());
+                    span.record("duration_ms", started.elapsed().as_millis() as u64);
+                }
+            }
+            Err(e) => record_error(&span, e),
+        });
+        Ok(Box::pin(stream))
+    }
+
+    async fn warmup(&self, model: &str) -> Result<(), ProviderError> {
+        let span = tracing::info_span!("gen_ai.warmup", gen_ai.system = %self.system, gen_ai.request.model = %model);
+        self.inner.warmup(model).instrument(span).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use crate::traits::CompletionKind;
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+
+    /// A span as seen by `Recorder`: its name and every field value recorded on it.
+    #[derive(Debug, Clone)]
+    struct RecordedSpan {
+        name: &'static str,
+        fields: BTreeMap<String, String>,
+    }
+
+    impl RecordedSpan {
+        fn field(&self, name: &str) -> Option<&str> {
+            self.fields.get(name).map(String::as_str)
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    /// Minimal subscriber keeping every span and the names of events' levels.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        next_id: Arc<AtomicU64>,
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+        events: Arc<Mutex<Vec<tracing::Level>>>,
+    }
+
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = BTreeMap::new();
+            span.record(&mut FieldVisitor(&mut fields));
+            self.spans.lock().unwrap().push(RecordedSpan { name: span.metadata().name(), fields });
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            values.record(&mut FieldVisitor(&mut spans[span.into_u64() as usize - 1].fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            self.events.lock().unwrap().push(*event.metadata().level());
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn reply(content: &str) -> CompletionResponse {
+        CompletionResponse {
+            kind: CompletionKind::Message { content: content.to_string() },
+            usage: Some(TokenUsage { prompt_tokens: 12, completion_tokens: 3, total_tokens: 15 }),
+            finish_reason: Some("stop".to_string()),
+            logprobs: None,
+        }
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest { model: "llama3".to_string(), temperature: Some(0.5), max_tokens: Some(64), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_one_span_per_completion() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let mock = Arc::new(MockProvider::new().with_response(reply("Hi")).with_response(reply("Hello")));
+        let provider = TracedProvider::new(mock, "ollama");
+
+        provider.completion(request()).await.unwrap();
+        provider.completion(request()).await.unwrap();
+
+        let spans = recorder.spans.lock().unwrap().clone();
+        assert_eq!(spans.len(), 2);
+        for span in &spans {
+            assert_eq!(span.name, "gen_ai.chat");
+            assert_eq!(span.field("otel.name"), Some("chat llama3"));
+            assert_eq!(span.field("gen_ai.system"), Some("ollama"));
+            assert_eq!(span.field("gen_ai.request.model"), Some("llama3"));
+            assert_eq!(span.field("gen_ai.request.temperature"), Some("0.5"));
+            assert_eq!(span.field("gen_ai.request.max_tokens"), Some("64"));
+            assert_eq!(span.field("gen_ai.request.streaming"), Some("false"));
+            assert_eq!(span.field("gen_ai.usage.input_tokens"), Some("12"));
+            assert_eq!(span.field("gen_ai.usage.output_tokens"), Some("3"));
+            assert_eq!(span.field("gen_ai.response.finish_reasons"), Some("stop"));
+            assert!(span.field("duration_ms").is_some());
+            assert_eq!(span.field("error.type"), None);
+        }
+        assert!(recorder.events.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_usage_is_recorded_on_the_span() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let provider = TracedProvider::new(Arc::new(MockProvider::new().with_response(reply("Hi there"))), "openai");
+
+        let chunks: Vec<_> = provider.completion_stream(request()).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 2);
+
+        let spans = recorder.spans.lock().unwrap().clone();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].field("gen_ai.request.streaming"), Some("true"));
+        assert_eq!(spans[0].field("gen_ai.usage.output_tokens"), Some("3"));
+        assert_eq!(spans[0].field("gen_ai.response.finish_reasons"), Some("stop"));
+        assert!(spans[0].field("duration_ms").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_recorded_and_logged() {
+        let recorder = Recorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let mock = MockProvider::new()
+            .with_error(ProviderError::ApiError { status: 429, message: "slow down".to_string() })
+            .with_error(ProviderError::Cancelled);
+        let provider = TracedProvider::new(Arc::new(mock), "openai");
+
+        assert!(provider.completion(request()).await.is_err());
+        assert!(provider.completion_stream(request()).await.is_err());
+
+        let spans = recorder.spans.lock().unwrap().clone();
+        assert_eq!(spans[0].field("error.type"), Some("429"));
+        assert_eq!(spans[1].field("error.type"), Some("Cancelled"));
+        assert_eq!(spans[0].field("gen_ai.usage.input_tokens"), None);
+        assert_eq!(*recorder.events.lock().unwrap(), [tracing::Level::WARN, tracing::Level::WARN]);
+    }
+}
@@ -0,0 +1,150 @@
+//!
+//! Long-Running Tool Jobs
+//!
+//! Some tools (running a test suite, a build, a crawl) cannot answer immediately. Such a
+//! tool starts a `ToolJob` and returns it as a handle; `job_executor` adapts it to a
+//! regular `ToolExecutor` that polls `status()` without blocking the runtime, forwards
+//! progress to the ambient `ToolContext`, and only returns to the LLM turn once the job
+//! finishes or times out.
+
+use crate::clock::{default_clock, Clock};
+use crate::tools::{register_tool, ToolContext, ToolExecutor, ToolProgress};
+use crate::traits::Tool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The state of a running job, as reported by `ToolJob::status`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus {
+    /// The job is still running.
+    Running {
+        /// Completed fraction between 0.0 and 1.0, if known.
+        fraction: Option<f32>,
+        /// Human-readable status, if any.
+        message: Option<String>,
+    },
+    /// The job finished; the string is the tool result.
+    Completed(String),
+    /// The job failed; the string is the error returned to the model.
+    Failed(String),
+}
+
+/// A handle to work started by a long-running tool.
+pub trait ToolJob: Send {
+    /// Polls the job's current state. Called repeatedly until it is no longer `Running`.
+    fn status(&mut self) -> JobStatus;
+
+    /// Stops the job; called when it exceeds its timeout. The default does nothing.
+    fn cancel(&mut self) {}
+}
+
+/// Starts a job from the tool's JSON arguments.
+pub type JobStarter = Arc<dyn Fn(&str) -> Result<Box<dyn ToolJob>, String> + Send + Sync>;
+
+/// Polling behaviour for a long-running tool.
+#[derive(Debug, Clone)]
+pub struct JobOptions {
+    /// Delay between `status()` calls.
+    pub poll_interval: Duration,
+    /// Maximum time to wait before the job is cancelled.
+    pub timeout: Duration,
+    /// Clock used for the poll delay and the timeout.
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Default for JobOptions {
+    fn default() -> Self {
+        Self { poll_interval: Duration::from_millis(500), timeout: Duration::from_secs(600), clock: default_clock() }
+    }
+}
+
+/// Wraps a job starter in a `ToolExecutor` that waits for the job to finish.
+pub fn job_executor(name: impl Into<String>, starter: JobStarter, options: JobOptions) -> ToolExecutor {
+    let name: Arc<str> = name.into().into();
+    Arc::new(move |args: String| {
+        let (name, starter, options) = (name.clone(), starter.clone(), options.clone());
+        Box::pin(async move {
+            let job = starter(&args)?;
+            wait_for_job(&name, job, &options, &ToolContext::current()).await
+        })
+    })
+}
+
+/// Registers a long-running tool in the global registry.
+pub fn register_job_tool(tool: Tool, starter: JobStarter, options: JobOptions) {
+    let executor = job_executor(tool.name.clone(), starter, options);
+    register_tool(tool, executor);
+}
+
+async fn wait_for_job(name: &str, mut job: Box<dyn ToolJob>, options: &JobOptions, context: &ToolContext) -> Result<String, String> {
+    let started = options.clock.now();
+    let mut last_reported: Option<(Option<f32>, Option<String>)> = None;
+    loop {
+        match job.status() {
+            JobStatus::Completed(result) => return Ok(result),
+            JobStatus::Failed(error) => return Err(error),
+            JobStatus::Running { fraction, message } => {
+                // Only report changes so slow jobs don't flood listeners
+                let current = Some((fraction, message.clone()));
+                if current != last_reported {
+                    context.report_progress(&ToolProgress { tool: name.to_string(), fraction, message });
+                    last_reported = current;
+                }
+            }
+        }
+        if options.clock.now().saturating_duration_since(started) >= options.timeout {
+            job.cancel();
+            return Err(format!(
+                "Tool '{}' timed out after {}s and was cancelled",
+                name,
+                options.timeout.as_secs_f32()
+            ));
+        }
+        options.clock.sleep(options.poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::sync::Mutex;
+
+    // Completes after a fixed number of polls
+    struct CountdownJob(u32);
+
+    impl ToolJob for CountdownJob {
+        fn status(&mut self) -> JobStatus {
+            if self.0 == 0 {
+                return JobStatus::Completed("done".to_string());
+            }
+            self.0 -= 1;
+            JobStatus::Running { fraction: Some(1.0 / (self.0 + 1) as f32), message: None }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_job_reports_progress_until_done() {
+        let options = JobOptions { poll_interval: Duration::from_millis(1), timeout: Duration::from_secs(5), ..Default::default() };
+        let executor = job_executor("countdown", Arc::new(|_: &str| Ok(Box::new(CountdownJob(3)) as Box<dyn ToolJob>)), options);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink_events = events.clone();
+        let context = ToolContext::default().with_progress(Arc::new(move |p: &ToolProgress| {
+            sink_events.lock().unwrap().push(p.fraction);
+        }));
+
+        assert_eq!(context.scope(executor("{}".to_string())).await, Ok("done".to_string()));
+        assert_eq!(events.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_job_times_out() {
+        let clock = Arc::new(ManualClock::new());
+        let options = JobOptions { poll_interval: Duration::from_secs(1), timeout: Duration::from_secs(10), clock: clock.clone() };
+        let executor = job_executor("forever", Arc::new(|_: &str| Ok(Box::new(CountdownJob(u32::MAX)) as Box<dyn ToolJob>)), options);
+        assert!(executor("{}".to_string()).await.unwrap_err().contains("timed out"));
+        // Polled once per simulated second, without really waiting
+        assert_eq!(clock.sleeps().len(), 10);
+    }
+}
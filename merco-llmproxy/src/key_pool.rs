@@ -0,0 +1,193 @@
+//!
+//! API Key Pool
+//!
+//! Provides `ApiKeyPool`, which rotates requests across multiple API keys for a single
+//! provider. Keys that hit rate limits, exhausted quotas, or authentication failures are
+//! put on a cooldown and skipped until it expires, so high-volume users can survive
+//! key-level limits without failing requests.
+
+use crate::clock::{default_clock, Clock};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Cooldown applied to a key after a rate limit (429) without a `Retry-After` hint.
+pub const DEFAULT_RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+/// Cooldown applied to a key after an authentication failure or exhausted quota.
+pub const DEFAULT_EXHAUSTED_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+struct KeyState {
+    key: String,
+    cooldown_until: Option<Instant>,
+    requests: u64,
+    failures: u64,
+}
+
+/// Usage statistics for a single key in an `ApiKeyPool`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyUsage {
+    /// A masked form of the key, safe for logging (e.g. `...a1b2`).
+    pub key_hint: String,
+    /// Number of requests dispatched with this key.
+    pub requests: u64,
+    /// Number of requests that failed with a rotation-triggering error.
+    pub failures: u64,
+    /// Whether the key is currently cooling down.
+    pub cooling_down: bool,
+}
+
+/// A thread-safe, round-robin pool of API keys with per-key cooldowns.
+#[derive(Debug)]
+pub struct ApiKeyPool {
+    keys: Mutex<Vec<KeyState>>,
+    next: Mutex<usize>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ApiKeyPool {
+    /// Creates a pool from the given keys. Duplicate and empty keys are ignored.
+    pub fn new(keys: impl IntoIterator<Item = String>) -> Self {
+        let mut states: Vec<KeyState> = Vec::new();
+        for key in keys {
+            if !key.is_empty() && !states.iter().any(|s| s.key == key) {
+                states.push(KeyState { key, cooldown_until: None, requests: 0, failures: 0 });
+            }
+        }
+        Self { keys: Mutex::new(states), next: Mutex::new(0), clock: default_clock() }
+    }
+
+    /// Measures cooldowns on `clock` instead of tokio's clock (builder style).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the number of keys in the pool.
+    pub fn len(&self) -> usize {
+        self.keys.lock().map(|k| k.len()).unwrap_or(0)
+    }
+
+    /// Returns `true` if the pool has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Selects the next available key (round-robin), skipping keys on cooldown.
+    ///
+    /// Returns the key's index (for reporting results) and the key itself, or `None`
+    /// if every key is currently cooling down.
+    pub fn next_key(&self) -> Option<(usize, String)> {
+        let mut keys = self.keys.lock().ok()?;
+        let mut next = self.next.lock().ok()?;
+        let now = self.clock.now();
+        let count = keys.len();
+
+        for offset in 0..count {
+            let idx = (*next + offset) % count;
+            let state = &mut keys[idx];
+            if state.cooldown_until.is_some_and(|until| until > now) {
+                continue;
+            }
+            state.cooldown_until = None;
+            state.requests += 1;
+            *next = (idx + 1) % count;
+            return Some((idx, state.key.clone()));
+        }
+        None
+    }
+
+    /// Puts the key at `idx` on cooldown for the given duration.
+    pub fn report_failure(&self, idx: usize, cooldown: Duration) {
+        if let Ok(mut keys) = self.keys.lock() {
+            if let Some(state) = keys.get_mut(idx) {
+                state.failures += 1;
+                state.cooldown_until = Some(self.clock.now() + cooldown);
+            }
+        }
+    }
+
+    /// Returns usage statistics for every key in the pool.
+    pub fn usage(&self) -> Vec<KeyUsage> {
+        let now = self.clock.now();
+        self.keys
+            .lock()
+            .map(|keys| {
+                keys.iter()
+                    .map(|state| KeyUsage {
+                        key_hint: mask_key(&state.key),
+                        requests: state.requests,
+                        failures: state.failures,
+                        cooling_down: state.cooldown_until.is_some_and(|until| until > now),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Decides whether an API error should rotate to another key, and for how long the
+/// failing key should cool down. `retry_after` is the parsed `Retry-After` header, if any.
+pub fn rotation_cooldown(status: u16, body: &str, retry_after: Option<Duration>) -> Option<Duration> {
+    if status == 401 || status == 402 || body.contains("insufficient_quota") {
+        return Some(DEFAULT_EXHAUSTED_COOLDOWN);
+    }
+    if status == 429 {
+        return Some(retry_after.unwrap_or(DEFAULT_RATE_LIMIT_COOLDOWN));
+    }
+    None
+}
+
+fn mask_key(key: &str) -> String {
+    let tail: String = key.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("...{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_skips_cooling_keys() {
+        let pool = ApiKeyPool::new(vec!["key-aaaa".to_string(), "key-bbbb".to_string(), "key-aaaa".to_string()]);
+        assert_eq!(pool.len(), 2);
+
+        let (first, _) = pool.next_key().unwrap();
+        pool.report_failure(first, Duration::from_secs(60));
+
+        // Only the healthy key is handed out while the other cools down
+        for _ in 0..3 {
+            let (idx, key) = pool.next_key().unwrap();
+            assert_ne!(idx, first);
+            assert_eq!(key, "key-bbbb");
+        }
+
+        let (second, _) = pool.next_key().unwrap();
+        pool.report_failure(second, Duration::from_secs(60));
+        assert!(pool.next_key().is_none());
+
+        let usage = pool.usage();
+        assert_eq!(usage[0].key_hint, "...aaaa");
+        assert!(usage.iter().all(|u| u.cooling_down && u.failures == 1));
+    }
+
+    #[test]
+    fn test_key_returns_after_cooldown() {
+        let clock = Arc::new(crate::clock::ManualClock::new());
+        let pool = ApiKeyPool::new(vec!["key-aaaa".to_string()]).with_clock(clock.clone());
+
+        let (idx, _) = pool.next_key().unwrap();
+        pool.report_failure(idx, Duration::from_secs(30));
+        assert!(pool.next_key().is_none());
+
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(pool.next_key().unwrap().1, "key-aaaa");
+    }
+
+    #[test]
+    fn test_rotation_cooldown() {
+        assert_eq!(rotation_cooldown(401, "", None), Some(DEFAULT_EXHAUSTED_COOLDOWN));
+        assert_eq!(rotation_cooldown(429, "", Some(Duration::from_secs(5))), Some(Duration::from_secs(5)));
+        assert_eq!(rotation_cooldown(429, "insufficient_quota", None), Some(DEFAULT_EXHAUSTED_COOLDOWN));
+        assert_eq!(rotation_cooldown(500, "", None), None);
+    }
+}
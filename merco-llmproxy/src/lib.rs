@@ -3,21 +3,144 @@
 //! Inspired by LiteLLM, this crate aims to simplify interaction with different LLMs
 //! through a common configuration and trait implementation.
 
+// Lets code generated by `merco-macros`, which names `::merco_llmproxy`, compile in this
+// crate's own tests
+#[cfg(all(test, feature = "macros"))]
+extern crate self as merco_llmproxy;
+
+/// Side-by-side accuracy, latency and cost benchmark of providers.
+pub mod bench;
+/// Synchronous client for code that does not run an async runtime.
+pub mod blocking;
+/// Fluent, validating construction of completion requests.
+pub mod builder;
+/// Ready-made tools for web pages, code execution, files and shell commands.
+pub mod builtin;
+/// Cancellation of in-flight requests and streams.
+pub mod cancellation;
+/// Injectable time source for limiters, cooldowns and pollers.
+pub mod clock;
+/// Provider configuration types.
 pub mod config;
+/// Ambient (task-scoped or global) provider context.
+pub mod context;
+/// Trimming and summarization to keep conversations within a context window.
+pub mod context_manager;
+/// Message history that records replies and keeps within a window or token budget.
+pub mod conversation;
+/// Text embeddings for semantic search and retrieval.
+pub mod embeddings;
+/// Ordered provider chains that fall back on retryable errors.
+pub mod fallback;
+/// OpenAI-compatible HTTP gateway in front of any configured providers.
+#[cfg(feature = "gateway")]
+pub mod gateway;
+/// `tracing` spans for LLM calls following the GenAI semantic conventions.
+#[cfg(feature = "tracing")]
+pub mod instrumentation;
+/// Long-running tools that are polled until they finish.
+pub mod jobs;
+/// Rotation across multiple API keys for a single provider.
+pub mod key_pool;
+/// Composable middleware layers around providers.
+pub mod middleware;
+/// Tools generated from OpenAPI 3 documents.
+#[cfg(feature = "openapi")]
+pub mod openapi;
+/// JSON Schemas of Rust types, for asking models for structured output.
+pub mod output;
+/// Incremental parsing of JSON output from streamed responses.
+pub mod partial_json;
+/// Validation of file paths built from model-generated names.
+pub mod paths;
+/// Concrete provider implementations.
 pub mod providers;
+/// Client-side requests-per-minute and tokens-per-minute limiting.
+pub mod rate_limit;
+/// Pluggable signing of outgoing requests.
+pub mod signing;
+/// Aggregation of streamed chunks into a complete response.
+pub mod stream;
+/// Opt-in anonymous usage statistics.
+pub mod telemetry;
+/// Token counting and context window estimation.
+pub mod tokenizer;
+/// Pluggable transports for dispatching provider requests.
+pub mod transport;
+/// Core traits and request/response types shared by all providers.
 pub mod traits;
+/// Tool registry and execution helpers.
 pub mod tools;
+/// Completions deserialized into Rust types through derived JSON schemas.
+#[cfg(feature = "schema")]
+pub mod typed;
 
+pub use bench::{BenchCase, BenchReport, BenchTarget, CaseResult, Pricing, ProviderBench, TargetReport};
+pub use blocking::{BlockingStream, LlmClient};
+pub use builder::{CompletionRequestBuilder, InvalidRequest};
+#[cfg(feature = "builtin-code")]
+pub use builtin::{CodeInterpreter, CodeLanguage};
+#[cfg(any(feature = "builtin-code", feature = "builtin-shell"))]
+pub use builtin::CodeOutput;
+#[cfg(feature = "builtin-fetch")]
+pub use builtin::{html_to_text, FetchTool};
+#[cfg(feature = "builtin-fs")]
+pub use builtin::FileSystemTools;
+#[cfg(feature = "builtin-shell")]
+pub use builtin::{ShellPolicy, ShellTool};
+pub use cancellation::{cancellable, cancellable_stream, CancellationToken};
+pub use clock::{default_clock, Clock, ManualClock, TokioClock};
 pub use config::{ConfigError, LlmConfig, Provider};
-pub use providers::{OllamaProvider, OpenAIProvider};
+pub use context::{BudgetTracker, LlmContext};
+pub use context_manager::{ContextManager, TrimStrategy};
+pub use conversation::Conversation;
+pub use embeddings::{cosine_similarity, EmbeddingProvider, EmbeddingRequest, EmbeddingResponse};
+pub use fallback::FallbackProvider;
+#[cfg(feature = "gateway")]
+pub use gateway::Gateway;
+#[cfg(feature = "tracing")]
+pub use instrumentation::TracedProvider;
+pub use jobs::{job_executor, register_job_tool, JobOptions, JobStarter, JobStatus, ToolJob};
+pub use key_pool::{ApiKeyPool, KeyUsage};
+pub use middleware::{CacheMiddleware, CostMiddleware, CostTotals, LayeredProvider, ProviderMiddleware, RetryMiddleware};
+#[cfg(feature = "openapi")]
+pub use openapi::{OpenApiError, OpenApiTools};
+pub use output::MercoOutput;
+pub use signing::{RequestSigner, SigningRequest};
+pub use stream::{collect_stream, replay_response, StreamCollector};
+pub use telemetry::{Telemetry, TelemetryProvider, TelemetryReport};
+#[cfg(feature = "schema")]
+pub use typed::{completion_typed, completion_typed_with, parameters_for, TypedOptions, TypedStrategy};
+pub use tokenizer::{count_tokens, fits_in_context, HeuristicTokenizer, Tokenizer};
+#[cfg(feature = "tiktoken")]
+pub use tokenizer::TiktokenTokenizer;
+pub use transport::{Transport, TransportRequest, TransportResponse};
+#[cfg(feature = "http-transport")]
+pub use transport::{HttpClientConfig, HttpTransport};
+#[cfg(all(unix, feature = "unix-socket"))]
+pub use transport::UnixSocketTransport;
+pub use paths::{relative_path, resolve_in, sanitize_file_name, PathError};
+pub use partial_json::{stream_partial_json, PartialJsonEvent, PartialJsonParser, PartialJsonUpdate};
+pub use providers::MockProvider;
+#[cfg(feature = "ollama")]
+pub use providers::{OllamaOptions, OllamaProvider};
+#[cfg(feature = "openai")]
+pub use providers::OpenAIProvider;
+pub use rate_limit::{RateLimitedProvider, TokenBucket};
 pub use traits::{
-    ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, CompletionStream,
-    CompletionStreamChunk, JsonSchema, LlmProvider, ProviderError, StreamContentDelta, Tool,
-    ToolCallFunction, ToolCallRequest, ToolCallStreamDelta, TokenUsage,
+    ChatMessage, ChatMessageRole, CompletionKind, CompletionRequest, CompletionResponse, CompletionStream,
+    CompletionStreamChunk, JsonSchema, LlmProvider, ProviderError, ReasoningEffort, ResponseFormat, StreamContentDelta, Tool,
+    ToolCallFunction, ToolCallRequest, ToolCallStreamDelta, ToolChoice, TokenLogprob, TokenUsage,
+    TopLogprob,
 };
 
 // Re-export tool utilities 
-pub use tools::{execute_tool, get_all_tools, get_tools_by_names, register_tool, ToolExecutor, ToolRegistry};
+pub use tools::{
+    async_executor, execute_tool, execute_tool_output, get_all_tools, get_tools_by_names, register_tool,
+    set_tool_arg_limits, sync_executor, to_canonical_json, tool_namespace, validate_tool_args,
+    ProgressReporter, ProgressSink, ToolArgLimits, ToolContext, ToolError, ToolExecutor, ToolFilter, ToolFuture, ToolOutput,
+    ToolOutputSink, ToolProgress, ToolRegistry,
+};
 
 // Conditionally re-export the macro if the feature is enabled
 #[cfg(feature = "macros")]
@@ -64,13 +187,53 @@ use std::sync::Arc;
 /// // Now use the provider methods with the request...
 /// // let response = provider.completion(request).await;
 /// ```
+#[cfg_attr(not(any(feature = "openai", feature = "ollama")), allow(unreachable_code, unused_variables))]
 pub fn get_provider(config: LlmConfig) -> Result<Arc<dyn LlmProvider>, ProviderError> {
     config.validate().map_err(|e| ProviderError::ConfigError(e.to_string()))?;
 
+    let provider: Arc<dyn LlmProvider> = match config.provider {
+        #[cfg(feature = "openai")]
+        Provider::OpenAI => Arc::new(OpenAIProvider::new(config.clone())),
+        #[cfg(feature = "ollama")]
+        Provider::Ollama => Arc::new(OllamaProvider::new(config.clone())),
+        Provider::Anthropic => return Err(ProviderError::Unsupported("Anthropic provider not yet implemented".to_string())),
+        Provider::Custom => return Err(ProviderError::Unsupported("Custom provider logic not yet implemented".to_string())),
+        #[allow(unreachable_patterns)]
+        disabled => {
+            let feature = format!("{:?}", disabled).to_lowercase();
+            return Err(ProviderError::Unsupported(format!(
+                "{:?} provider is disabled; enable the `{}` feature of merco-llmproxy",
+                disabled, feature
+            )));
+        }
+    };
+
+    #[cfg(feature = "tracing")]
+    let provider: Arc<dyn LlmProvider> =
+        Arc::new(TracedProvider::new(provider, format!("{:?}", config.provider).to_lowercase()));
+
+    let provider: Arc<dyn LlmProvider> = match &config.telemetry {
+        Some(telemetry) => {
+            Arc::new(TelemetryProvider::new(provider, telemetry.clone(), format!("{:?}", config.provider).to_lowercase()))
+        }
+        None => provider,
+    };
+
+    Ok(provider)
+}
+
+/// Creates an embedding provider for the configuration.
+///
+/// # Errors
+///
+/// Returns `ProviderError::ConfigError` if the configuration is invalid, and
+/// `ProviderError::Unsupported` for providers without embedding support (currently
+/// everything except Ollama).
+pub fn get_embedding_provider(config: LlmConfig) -> Result<Arc<dyn EmbeddingProvider>, ProviderError> {
+    config.validate().map_err(|e| ProviderError::ConfigError(e.to_string()))?;
     match config.provider {
-        Provider::OpenAI => Ok(Arc::new(OpenAIProvider::new(config))),
+        #[cfg(feature = "ollama")]
         Provider::Ollama => Ok(Arc::new(OllamaProvider::new(config))),
-        Provider::Anthropic => Err(ProviderError::Unsupported("Anthropic provider not yet implemented".to_string())),
-        Provider::Custom => Err(ProviderError::Unsupported("Custom provider logic not yet implemented".to_string())),
+        other => Err(ProviderError::Unsupported(format!("Embeddings are not available for the {:?} provider", other))),
     }
 }
@@ -0,0 +1,412 @@
+//!
+//! Provider Middleware
+//!
+//! Defines the `ProviderMiddleware` trait, with hooks that run before a request is sent,
+//! after a response arrives, on each streamed chunk, and on errors, plus `LayeredProvider`,
+//! which stacks middlewares around any `LlmProvider`. Cross-cutting concerns such as
+//! retries, logging, cost tracking and caching compose as layers instead of each being
+//! its own bespoke wrapper. `RetryMiddleware`, `CacheMiddleware` and `CostMiddleware`
+//! cover the common ones.
+
+use crate::bench::Pricing;
+use crate::clock::{default_clock, Clock};
+use crate::stream::replay_response;
+use crate::traits::{
+    CompletionRequest, CompletionResponse, CompletionStream, CompletionStreamChunk, LlmProvider, ProviderError,
+    TokenUsage,
+};
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Hooks invoked by `LayeredProvider` around each call. Every hook has a no-op default.
+#[async_trait]
+pub trait ProviderMiddleware: Send + Sync {
+    /// Inspects or modifies the request before it is sent. Returning `Some(response)`
+    /// short-circuits the call (e.g. a cache hit); the provider is not called. Streaming
+    /// calls replay the returned response as a stream.
+    async fn before_request(&self, _request: &mut CompletionRequest) -> Result<Option<CompletionResponse>, ProviderError> {
+        Ok(None)
+    }
+
+    /// Inspects or modifies a completed (non-streaming) response.
+    async fn after_response(
+        &self,
+        _request: &CompletionRequest,
+        _response: &mut CompletionResponse,
+    ) -> Result<(), ProviderError> {
+        Ok(())
+    }
+
+    /// Inspects or modifies each chunk of a streaming response.
+    fn on_stream_chunk(&self, _chunk: &mut CompletionStreamChunk) -> Result<(), ProviderError> {
+        Ok(())
+    }
+
+    /// Called when the provider fails. Return `true` to retry the call; `attempt` starts at 1.
+    async fn on_error(&self, _request: &CompletionRequest, _error: &ProviderError, _attempt: u32) -> bool {
+        false
+    }
+}
+
+/// Wraps a provider with an ordered stack of middlewares.
+///
+/// `before_request` hooks run in the order layers were added; `after_response` and
+/// `on_stream_chunk` hooks run in reverse order, so the first layer is outermost.
+pub struct LayeredProvider {
+    inner: Arc<dyn LlmProvider>,
+    layers: Vec<Arc<dyn ProviderMiddleware>>,
+}
+
+impl LayeredProvider {
+    /// Creates a layered provider with no middlewares.
+    pub fn new(inner: Arc<dyn LlmProvider>) -> Self {
+        Self { inner, layers: Vec::new() }
+    }
+
+    /// Adds a middleware as the next (inner) layer (builder style).
+    pub fn layer(mut self, middleware: Arc<dyn ProviderMiddleware>) -> Self {
+        self.layers.push(middleware);
+        self
+    }
+
+    async fn run_before(&self, request: &mut CompletionRequest) -> Result<Option<CompletionResponse>, ProviderError> {
+        for layer in &self.layers {
+            if let Some(response) = layer.before_request(request).await? {
+                return Ok(Some(response));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn should_retry(&self, request: &CompletionRequest, error: &ProviderError, attempt: u32) -> bool {
+        for layer in &self.layers {
+            if layer.on_error(request, error, attempt).await {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[async_trait]
+impl LlmProvider for LayeredProvider {
+    async fn completion(&self, mut request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        if let Some(mut response) = self.run_before(&mut request).await? {
+            for layer in self.layers.iter().rev() {
+                layer.after_response(&request, &mut response).await?;
+            }
+            return Ok(response);
+        }
+
+        let mut attempt = 1;
+        let mut response = loop {
+            match self.inner.completion(request.clone()).await {
+                Ok(response) => break response,
+                Err(e) if self.should_retry(&request, &e, attempt).await => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        };
+        for layer in self.layers.iter().rev() {
+            layer.after_response(&request, &mut response).await?;
+        }
+        Ok(response)
+    }
+
+    async fn completion_stream(&self, mut request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let stream = match self.run_before(&mut request).await? {
+            Some(response) => replay_response(response),
+            None => {
+                let mut attempt = 1;
+                loop {
+                    match self.inner.completion_stream(request.clone()).await {
+                        Ok(stream) => break stream,
+                        Err(e) if self.should_retry(&request, &e, attempt).await => attempt += 1,
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        };
+
+        let layers = self.layers.clone();
+        let stream = stream.map(move |chunk| {
+            let mut chunk = chunk?;
+            for layer in layers.iter().rev() {
+                layer.on_stream_chunk(&mut chunk)?;
+            }
+            Ok(chunk)
+        });
+        Ok(Box::pin(stream))
+    }
+
+    async fn warmup(&self, model: &str) -> Result<(), ProviderError> {
+        self.inner.warmup(model).await
+    }
+}
+
+/// Retries transient failures (see `ProviderError::is_retryable`) with exponential backoff.
+#[derive(Debug, Clone)]
+pub struct RetryMiddleware {
+    max_attempts: u32,
+    backoff: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl RetryMiddleware {
+    /// Makes up to `max_attempts` calls in total, waiting 500ms before the first retry and
+    /// doubling the wait after each one.
+    pub fn new(max_attempts: u32) -> Self {
+        Self { max_attempts, backoff: Duration::from_millis(500), clock: default_clock() }
+    }
+
+    /// Sets the wait before the first retry (builder style).
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the clock used to wait between attempts (builder style).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+#[async_trait]
+impl ProviderMiddleware for RetryMiddleware {
+    async fn on_error(&self, _request: &CompletionRequest, error: &ProviderError, attempt: u32) -> bool {
+        if attempt >= self.max_attempts || !error.is_retryable() {
+            return false;
+        }
+        self.clock.sleep(self.backoff * 2u32.saturating_pow(attempt - 1)).await;
+        true
+    }
+}
+
+/// Serves repeated identical requests from memory.
+///
+/// Requests are keyed by their full serialized form, so any difference in messages,
+/// model or parameters is a miss. Only successful non-streaming responses are stored;
+/// streaming requests can still be served from the cache. The oldest entry is evicted
+/// once the cache is full.
+#[derive(Debug)]
+pub struct CacheMiddleware {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    responses: HashMap<String, CompletionResponse>,
+    order: VecDeque<String>,
+    hits: u64,
+}
+
+impl CacheMiddleware {
+    /// Creates a cache holding at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: Mutex::new(CacheEntries::default()) }
+    }
+
+    /// Number of cached responses.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().responses.len()
+    }
+
+    /// Returns `true` if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of requests answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.entries.lock().unwrap().hits
+    }
+
+    fn key(request: &CompletionRequest) -> Option<String> {
+        serde_json::to_string(request).ok()
+    }
+}
+
+#[async_trait]
+impl ProviderMiddleware for CacheMiddleware {
+    async fn before_request(&self, request: &mut CompletionRequest) -> Result<Option<CompletionResponse>, ProviderError> {
+        let Some(key) = Self::key(request) else { return Ok(None) };
+        let mut entries = self.entries.lock().unwrap();
+        let response = entries.responses.get(&key).cloned();
+        if response.is_some() {
+            entries.hits += 1;
+        }
+        Ok(response)
+    }
+
+    async fn after_response(&self, request: &CompletionRequest, response: &mut CompletionResponse) -> Result<(), ProviderError> {
+        let Some(key) = Self::key(request) else { return Ok(()) };
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.responses.insert(key.clone(), response.clone()).is_none() {
+            entries.order.push_back(key);
+            while entries.order.len() > self.capacity {
+                if let Some(oldest) = entries.order.pop_front() {
+                    entries.responses.remove(&oldest);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Token usage and cost totals recorded by a `CostMiddleware`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CostTotals {
+    /// Responses that reported usage.
+    pub responses: u64,
+    /// Prompt tokens across all responses.
+    pub prompt_tokens: u64,
+    /// Completion tokens across all responses.
+    pub completion_tokens: u64,
+    /// Cost of all responses under the middleware's pricing.
+    pub cost: f64,
+}
+
+/// Adds up the token usage and cost of every response, streamed or not.
+///
+/// All calls are priced the same, so use one `CostMiddleware` per provider and model
+/// family. Responses short-circuited by another layer (such as cache hits) are counted
+/// too; to leave them out, put the cost layer in an inner `LayeredProvider`.
+#[derive(Debug)]
+pub struct CostMiddleware {
+    pricing: Pricing,
+    totals: Mutex<CostTotals>,
+}
+
+impl CostMiddleware {
+    /// Records usage priced with `pricing`.
+    pub fn new(pricing: Pricing) -> Self {
+        Self { pricing, totals: Mutex::new(CostTotals::default()) }
+    }
+
+    /// The totals so far.
+    pub fn totals(&self) -> CostTotals {
+        *self.totals.lock().unwrap()
+    }
+
+    fn record(&self, usage: &TokenUsage) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.responses += 1;
+        totals.prompt_tokens += u64::from(usage.prompt_tokens);
+        totals.completion_tokens += u64::from(usage.completion_tokens);
+        totals.cost += self.pricing.cost(usage);
+    }
+}
+
+#[async_trait]
+impl ProviderMiddleware for CostMiddleware {
+    async fn after_response(&self, _request: &CompletionRequest, response: &mut CompletionResponse) -> Result<(), ProviderError> {
+        if let Some(usage) = &response.usage {
+            self.record(usage);
+        }
+        Ok(())
+    }
+
+    fn on_stream_chunk(&self, chunk: &mut CompletionStreamChunk) -> Result<(), ProviderError> {
+        if let Some(usage) = &chunk.usage {
+            self.record(usage);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::CompletionKind;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // Fails the first call with a 503, then echoes the request's model
+    struct FlakyProvider(AtomicU32);
+
+    #[async_trait]
+    impl LlmProvider for FlakyProvider {
+        async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+            if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(ProviderError::ApiError { status: 503, message: "busy".to_string() });
+            }
+            Ok(CompletionResponse {
+                kind: CompletionKind::Message { content: request.model },
+                usage: None,
+                finish_reason: None,
+                logprobs: None,
+            })
+        }
+
+        async fn completion_stream(&self, _request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+            Err(ProviderError::Unsupported("streaming".to_string()))
+        }
+    }
+
+    struct RewriteModel;
+
+    #[async_trait]
+    impl ProviderMiddleware for RewriteModel {
+        async fn before_request(&self, request: &mut CompletionRequest) -> Result<Option<CompletionResponse>, ProviderError> {
+            request.model = "rewritten".to_string();
+            Ok(None)
+        }
+    }
+
+    struct RetryOnce;
+
+    #[async_trait]
+    impl ProviderMiddleware for RetryOnce {
+        async fn on_error(&self, _request: &CompletionRequest, error: &ProviderError, attempt: u32) -> bool {
+            error.is_retryable() && attempt < 2
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layers_compose() {
+        let provider = LayeredProvider::new(Arc::new(FlakyProvider(AtomicU32::new(0))))
+            .layer(Arc::new(RewriteModel))
+            .layer(Arc::new(RetryOnce));
+
+        let response = provider.completion(CompletionRequest::default()).await.unwrap();
+        assert!(matches!(response.kind, CompletionKind::Message { content } if content == "rewritten"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_middleware_backs_off() {
+        let clock = Arc::new(crate::clock::ManualClock::new());
+        let retry = RetryMiddleware::new(3).with_backoff(Duration::from_millis(100)).with_clock(clock.clone());
+        let busy = ProviderError::ApiError { status: 503, message: "busy".to_string() };
+        let request = CompletionRequest::default();
+
+        assert!(retry.on_error(&request, &busy, 1).await);
+        assert!(retry.on_error(&request, &busy, 2).await);
+        assert!(!retry.on_error(&request, &busy, 3).await);
+        assert!(!retry.on_error(&request, &ProviderError::ConfigError("bad".to_string()), 1).await);
+        assert_eq!(clock.sleeps(), vec![Duration::from_millis(100), Duration::from_millis(200)]);
+    }
+
+    #[tokio::test]
+    async fn test_cache_serves_repeats_and_streams() {
+        let mock = Arc::new(crate::providers::MockProvider::new().with_message("cached answer"));
+        let cache = Arc::new(CacheMiddleware::new(8));
+        let cost = Arc::new(CostMiddleware::new(Pricing { input_per_million: 1.0, output_per_million: 2.0 }));
+        let provider = LayeredProvider::new(Arc::new(LayeredProvider::new(mock.clone()).layer(cost.clone())))
+            .layer(cache.clone());
+        let request = CompletionRequest::new(vec![crate::traits::ChatMessage::user("hi")], "mock".to_string(), None, None, None);
+
+        provider.completion(request.clone()).await.unwrap();
+        let replayed = crate::stream::collect_stream(provider.completion_stream(request).await.unwrap()).await.unwrap();
+
+        assert!(matches!(replayed.kind, CompletionKind::Message { content } if content == "cached answer"));
+        assert_eq!(mock.call_count(), 1);
+        assert_eq!((cache.len(), cache.hits()), (1, 1));
+        assert_eq!(cost.totals().responses, 1);
+    }
+}
@@ -0,0 +1,514 @@
+//!
+//! OpenAPI Tools
+//!
+//! Turns the operations of an OpenAPI 3 document (in its JSON form) into tools. Each
+//! operation becomes one `Tool` whose parameters are the operation's path, query and
+//! header parameters, plus a `body` argument for a JSON request body. Executing the tool
+//! performs the HTTP call and returns the response text.
+//!
+//! Operations are named after their `operationId`, or `<method>_<path>` without one.
+//! Local `$ref`s (`#/components/...`) are inlined; other references and cookie
+//! parameters are not supported. Non-2xx responses are reported as `ToolError`
+//! payloads, retryable for 408, 429 and 5xx statuses.
+
+use crate::tools::{ToolError, ToolExecutor, ToolFuture, ToolRegistry};
+use crate::traits::{JsonSchema, Tool};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Method;
+use serde_json::{Map, Value as JsonValue};
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// HTTP methods an OpenAPI path item can define operations for.
+const METHODS: &[&str] = &["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+/// Depth at which nested `$ref`s stop being inlined, so recursive schemas terminate.
+const MAX_REF_DEPTH: usize = 8;
+
+/// Longest tool name accepted by the common providers.
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+/// Name of the tool argument that carries the JSON request body.
+pub const BODY_ARGUMENT: &str = "body";
+
+/// Why an OpenAPI document could not be turned into tools.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum OpenApiError {
+    /// The document is not valid JSON.
+    #[error("Invalid OpenAPI document: {0}")]
+    InvalidJson(String),
+    /// The document is not an OpenAPI 3 document.
+    #[error("Unsupported document: {0}")]
+    Unsupported(String),
+    /// A header given for authentication is invalid.
+    #[error("Invalid header '{0}': {1}")]
+    InvalidHeader(String, String),
+}
+
+/// Where an operation parameter goes in the HTTP request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamLocation {
+    Path,
+    Query,
+    Header,
+}
+
+/// One API operation and how to map tool arguments onto its request.
+#[derive(Debug, Clone)]
+struct Operation {
+    tool: Tool,
+    method: Method,
+    path: String,
+    params: Vec<(String, ParamLocation)>,
+}
+
+/// Everything an executor needs to perform calls, shared between all of an API's tools.
+struct Endpoint {
+    client: reqwest::Client,
+    base_url: Option<String>,
+    headers: HeaderMap,
+}
+
+/// Tools generated from an OpenAPI 3 document.
+pub struct OpenApiTools {
+    operations: Vec<Operation>,
+    base_url: Option<String>,
+    headers: HeaderMap,
+    client: reqwest::Client,
+}
+
+impl fmt::Debug for OpenApiTools {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.operations.iter().map(|op| op.tool.name.as_str()).collect();
+        // Header values are credentials, so only their names are shown
+        let headers: Vec<&str> = self.headers.keys().map(HeaderName::as_str).collect();
+        f.debug_struct("OpenApiTools")
+            .field("operations", &names)
+            .field("base_url", &self.base_url)
+            .field("headers", &headers)
+            .finish()
+    }
+}
+
+impl OpenApiTools {
+    /// Parses an OpenAPI 3 document in JSON form.
+    ///
+    /// # Errors
+    ///
+    /// Returns `OpenApiError::InvalidJson` for malformed JSON and `OpenApiError::Unsupported`
+    /// for documents that are not OpenAPI 3 (e.g. Swagger 2.0).
+    pub fn from_json(spec: &str) -> Result<Self, OpenApiError> {
+        let spec: JsonValue = serde_json::from_str(spec).map_err(|e| OpenApiError::InvalidJson(e.to_string()))?;
+        Self::from_value(&spec)
+    }
+
+    /// Like `from_json`, for a document that is already parsed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `OpenApiError::Unsupported` for documents that are not OpenAPI 3.
+    pub fn from_value(spec: &JsonValue) -> Result<Self, OpenApiError> {
+        let version = spec.get("openapi").and_then(JsonValue::as_str).unwrap_or_default();
+        if !version.starts_with("3.") {
+            return Err(OpenApiError::Unsupported("expected an `openapi: 3.x` document".to_string()));
+        }
+        // Relative server URLs (e.g. `/api`) need `with_base_url`
+        let base_url = spec
+            .pointer("/servers/0/url")
+            .and_then(JsonValue::as_str)
+            .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+            .map(String::from);
+
+        let mut operations = Vec::new();
+        for (path, item) in spec.get("paths").and_then(JsonValue::as_object).into_iter().flatten() {
+            let item = resolve(spec, item, 0);
+            let shared_params = item.get("parameters").and_then(JsonValue::as_array).cloned().unwrap_or_default();
+            for method in METHODS {
+                if let Some(operation) = item.get(*method) {
+                    operations.push(parse_operation(spec, path, method, operation, &shared_params));
+                }
+            }
+        }
+        Ok(Self { operations, base_url, headers: HeaderMap::new(), client: reqwest::Client::new() })
+    }
+
+    /// Sends requests to `base_url` instead of the document's first server (builder style).
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Adds a header to every request, e.g. an API key (builder style).
+    ///
+    /// # Errors
+    ///
+    /// Returns `OpenApiError::InvalidHeader` if the name or value is not a valid header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Result<Self, OpenApiError> {
+        let invalid = |e: &dyn fmt::Display| OpenApiError::InvalidHeader(name.to_string(), e.to_string());
+        let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(&e))?;
+        let mut header_value = HeaderValue::from_str(value).map_err(|e| invalid(&e))?;
+        header_value.set_sensitive(true);
+        self.headers.insert(header_name, header_value);
+        Ok(self)
+    }
+
+    /// Sends `Authorization: Bearer <token>` with every request (builder style).
+    ///
+    /// # Errors
+    ///
+    /// Returns `OpenApiError::InvalidHeader` if the token contains invalid characters.
+    pub fn with_bearer_token(self, token: &str) -> Result<Self, OpenApiError> {
+        self.with_header("authorization", &format!("Bearer {}", token))
+    }
+
+    /// Uses `client` for requests, e.g. one with a proxy or timeouts (builder style).
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// The generated tool definitions, ordered by path.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.operations.iter().map(|op| op.tool.clone()).collect()
+    }
+
+    /// Registers every operation in `registry`.
+    pub fn register(&self, registry: &mut ToolRegistry) {
+        for (tool, executor) in self.executors() {
+            registry.register(tool, executor);
+        }
+    }
+
+    /// Registers every operation in `registry` under `namespace` (e.g. `github.get_repo`).
+    pub fn register_in(&self, registry: &mut ToolRegistry, namespace: &str) {
+        for (tool, executor) in self.executors() {
+            registry.register_in(namespace, tool, executor);
+        }
+    }
+
+    fn executors(&self) -> Vec<(Tool, ToolExecutor)> {
+        let endpoint =
+            Arc::new(Endpoint { client: self.client.clone(), base_url: self.base_url.clone(), headers: self.headers.clone() });
+        self.operations
+            .iter()
+            .map(|operation| {
+                let tool = operation.tool.clone();
+                let (endpoint, operation) = (endpoint.clone(), Arc::new(operation.clone()));
+                let executor: ToolExecutor = Arc::new(move |args: String| -> ToolFuture {
+                    let (endpoint, operation) = (endpoint.clone(), operation.clone());
+                    Box::pin(async move { call(&endpoint, &operation, &args).await })
+                });
+                (tool, executor)
+            })
+            .collect()
+    }
+}
+
+fn parse_operation(spec: &JsonValue, path: &str, method: &str, operation: &JsonValue, shared: &[JsonValue]) -> Operation {
+    let name = match operation.get("operationId").and_then(JsonValue::as_str) {
+        Some(id) => tool_name(id),
+        None => tool_name(&format!("{}_{}", method, path)),
+    };
+    let text = |key: &str| operation.get(key).and_then(JsonValue::as_str).filter(|text| !text.is_empty());
+    let description = match (text("summary"), text("description")) {
+        (Some(summary), Some(description)) => format!("{}\n\n{}", summary, description),
+        (summary, description) => summary.or(description).map(String::from).unwrap_or_else(|| format!("{} {}", method.to_uppercase(), path)),
+    };
+
+    let (mut properties, mut required, mut params) = (Map::new(), Vec::new(), Vec::new());
+    // Operation-level parameters override path-level ones with the same name and location
+    let own = operation.get("parameters").and_then(JsonValue::as_array).cloned().unwrap_or_default();
+    for param in shared.iter().chain(own.iter()) {
+        let param = resolve(spec, param, 0);
+        let Some(param_name) = param.get("name").and_then(JsonValue::as_str) else { continue };
+        let location = match param.get("in").and_then(JsonValue::as_str) {
+            Some("path") => ParamLocation::Path,
+            Some("query") => ParamLocation::Query,
+            Some("header") => ParamLocation::Header,
+            _ => continue,
+        };
+        let mut schema = param.get("schema").map(|schema| resolve(spec, schema, 0)).unwrap_or_else(|| serde_json::json!({}));
+        if let (Some(description), JsonValue::Object(schema)) = (param.get("description"), &mut schema) {
+            schema.entry("description").or_insert(description.clone());
+        }
+        properties.insert(param_name.to_string(), schema);
+        params.retain(|(name, _)| name != param_name);
+        params.push((param_name.to_string(), location));
+        required.retain(|name| name != param_name);
+        if location == ParamLocation::Path || param.get("required").and_then(JsonValue::as_bool).unwrap_or(false) {
+            required.push(param_name.to_string());
+        }
+    }
+
+    if let Some(body) = operation.get("requestBody").map(|body| resolve(spec, body, 0)) {
+        if let Some(schema) = body.pointer("/content/application~1json/schema") {
+            let mut schema = resolve(spec, schema, 0);
+            if let (Some(description), JsonValue::Object(schema)) = (body.get("description"), &mut schema) {
+                schema.entry("description").or_insert(description.clone());
+            }
+            properties.insert(BODY_ARGUMENT.to_string(), schema);
+            if body.get("required").and_then(JsonValue::as_bool).unwrap_or(false) {
+                required.push(BODY_ARGUMENT.to_string());
+            }
+        }
+    }
+
+    Operation {
+        tool: Tool {
+            name,
+            description,
+            parameters: JsonSchema {
+                schema_type: "object".to_string(),
+                properties: Some(properties),
+                required: Some(required),
+            },
+        },
+        method: Method::from_bytes(method.to_uppercase().as_bytes()).unwrap_or(Method::GET),
+        path: path.to_string(),
+        params,
+    }
+}
+
+/// A provider-safe tool name: letters, digits, `_` and `-`, at most 64 characters.
+fn tool_name(raw: &str) -> String {
+    let mut name: String = raw.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    while name.contains("__") {
+        name = name.replace("__", "_");
+    }
+    let mut name = name.trim_matches('_').to_string();
+    name.truncate(MAX_TOOL_NAME_LEN);
+    name
+}
+
+/// Inlines local `$ref`s in `value`; references nested deeper than `MAX_REF_DEPTH` become
+/// an unconstrained schema.
+fn resolve(spec: &JsonValue, value: &JsonValue, depth: usize) -> JsonValue {
+    match value {
+        JsonValue::Object(object) => {
+            if let Some(reference) = object.get("$ref").and_then(JsonValue::as_str) {
+                if depth >= MAX_REF_DEPTH {
+                    return serde_json::json!({});
+                }
+                return match reference.strip_prefix('#').and_then(|pointer| spec.pointer(pointer)) {
+                    Some(target) => resolve(spec, target, depth + 1),
+                    None => serde_json::json!({}),
+                };
+            }
+            JsonValue::Object(object.iter().map(|(key, value)| (key.clone(), resolve(spec, value, depth))).collect())
+        }
+        JsonValue::Array(items) => JsonValue::Array(items.iter().map(|item| resolve(spec, item, depth)).collect()),
+        other => other.clone(),
+    }
+}
+
+/// A parameter value as it appears in a URL or header: strings verbatim, anything else
+/// as JSON.
+fn param_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Percent-encodes a path parameter so it stays within one path segment. Empty, `.` and
+/// `..` values are rejected: URL parsing would drop or resolve those segments (encoded
+/// dots too), sending the request to another endpoint.
+fn encode_path_segment(name: &str, value: &str) -> Result<String, String> {
+    if matches!(value, "" | "." | "..") {
+        return Err(format!("Invalid value '{}' for path parameter '{}'", value, name));
+    }
+    Ok(value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect())
+}
+
+async fn call(endpoint: &Endpoint, operation: &Operation, args: &str) -> Result<String, String> {
+    let args: JsonValue = if args.trim().is_empty() {
+        JsonValue::Object(Map::new())
+    } else {
+        serde_json::from_str(args).map_err(|e| format!("Failed to parse arguments for {}: {}", operation.tool.name, e))?
+    };
+    let base_url = endpoint
+        .base_url
+        .as_deref()
+        .ok_or_else(|| format!("No base URL configured for {}; the document has no absolute server URL", operation.tool.name))?;
+
+    let mut path = operation.path.clone();
+    let (mut query, mut headers) = (Vec::new(), endpoint.headers.clone());
+    for (name, location) in &operation.params {
+        let Some(value) = args.get(name).filter(|value| !value.is_null()) else { continue };
+        match location {
+            ParamLocation::Path => {
+                path = path.replace(&format!("{{{}}}", name), &encode_path_segment(name, &param_text(value))?)
+            }
+            // Arrays are sent as repeated keys (`form`, `explode: true`, the OpenAPI default)
+            ParamLocation::Query => match value {
+                JsonValue::Array(items) => query.extend(items.iter().map(|item| (name.clone(), param_text(item)))),
+                value => query.push((name.clone(), param_text(value))),
+            },
+            ParamLocation::Header => {
+                let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?;
+                let header_value = HeaderValue::from_str(&param_text(value)).map_err(|e| e.to_string())?;
+                headers.insert(header_name, header_value);
+            }
+        }
+    }
+
+    let url = format!("{}{}", base_url.trim_end_matches('/'), path);
+    let mut request = endpoint.client.request(operation.method.clone(), url).headers(headers).query(&query);
+    if let Some(body) = args.get(BODY_ARGUMENT) {
+        request = request.json(body);
+    }
+
+    let response = request.send().await.map_err(|e| ToolError::retryable(format!("Request failed: {}", e)).to_payload())?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| ToolError::retryable(format!("Failed to read response: {}", e)).to_payload())?;
+    if status.is_success() {
+        Ok(text)
+    } else {
+        let error = ToolError::new(format!("HTTP {}: {}", status.as_u16(), text));
+        let retryable = status.as_u16() == 408 || status.as_u16() == 429 || status.is_server_error();
+        Err(if retryable { ToolError::retryable(error.error) } else { error }.to_payload())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const SPEC: &str = r##"{
+        "openapi": "3.0.3",
+        "servers": [{ "url": "/v1" }],
+        "paths": {
+            "/pets/{petId}": {
+                "parameters": [{ "name": "petId", "in": "path", "schema": { "type": "string" } }],
+                "get": {
+                    "operationId": "getPet",
+                    "summary": "Get a pet",
+                    "parameters": [
+                        { "name": "fields", "in": "query", "schema": { "type": "array", "items": { "type": "string" } } },
+                        { "$ref": "#/components/parameters/Trace" }
+                    ]
+                }
+            },
+            "/pets": {
+                "post": {
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Pet" } } }
+                    }
+                }
+            }
+        },
+        "components": {
+            "parameters": {
+                "Trace": { "name": "X-Trace", "in": "header", "description": "Trace id", "schema": { "type": "string" } }
+            },
+            "schemas": {
+                "Pet": { "type": "object", "properties": { "name": { "type": "string" } }, "required": ["name"] }
+            }
+        }
+    }"##;
+
+    // Answers one request with a 200 whose body is the raw request it received
+    async fn echo_server() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.to_lowercase().strip_prefix("content-length: ").map(|n| n.parse().unwrap()))
+                        .unwrap_or(0usize);
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+            }
+            let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n", request.len());
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.write_all(&request).await.unwrap();
+        });
+        format!("http://{}/v1", addr)
+    }
+
+    #[test]
+    fn test_generates_tool_definitions() {
+        let api = OpenApiTools::from_json(SPEC).unwrap();
+        let tools = api.tools();
+        assert_eq!(tools.len(), 2);
+
+        let get_pet = tools.iter().find(|tool| tool.name == "getPet").unwrap();
+        assert_eq!(get_pet.description, "Get a pet");
+        let properties = get_pet.parameters.properties.as_ref().unwrap();
+        assert_eq!(properties["X-Trace"]["description"], "Trace id");
+        assert_eq!(properties["fields"]["type"], "array");
+        assert_eq!(get_pet.parameters.required, Some(vec!["petId".to_string()]));
+
+        let create = tools.iter().find(|tool| tool.name == "post_pets").unwrap();
+        assert_eq!(create.description, "POST /pets");
+        assert_eq!(create.parameters.properties.as_ref().unwrap()["body"]["required"], serde_json::json!(["name"]));
+        assert_eq!(create.parameters.required, Some(vec!["body".to_string()]));
+
+        assert!(matches!(OpenApiTools::from_json(r#"{"swagger": "2.0"}"#), Err(OpenApiError::Unsupported(_))));
+    }
+
+    #[tokio::test]
+    async fn test_maps_arguments_onto_the_request() {
+        let api = OpenApiTools::from_json(SPEC).unwrap().with_base_url(echo_server().await).with_bearer_token("secret").unwrap();
+        let mut registry = ToolRegistry::new();
+        api.register_in(&mut registry, "pets");
+
+        let request = registry
+            .execute_tool("pets.getPet", r#"{"petId": "a b/c", "fields": ["name", "age"], "X-Trace": "t-1"}"#)
+            .await
+            .unwrap();
+        assert!(request.starts_with("GET /v1/pets/a%20b%2Fc?fields=name&fields=age HTTP/1.1"), "{}", request);
+        assert!(request.contains("authorization: Bearer secret"), "{}", request);
+        assert!(request.contains("x-trace: t-1"), "{}", request);
+    }
+
+    #[tokio::test]
+    async fn test_sends_the_json_body() {
+        let api = OpenApiTools::from_json(SPEC).unwrap().with_base_url(echo_server().await);
+        let mut registry = ToolRegistry::new();
+        api.register(&mut registry);
+
+        let request = registry.execute_tool("post_pets", r#"{"body": {"name": "Rex"}}"#).await.unwrap();
+        assert!(request.starts_with("POST /v1/pets HTTP/1.1"), "{}", request);
+        assert!(request.ends_with(r#"{"name":"Rex"}"#), "{}", request);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_dot_segments_in_path_parameters() {
+        let mut registry = ToolRegistry::new();
+        OpenApiTools::from_json(SPEC).unwrap().with_base_url("http://127.0.0.1:9/v1").register(&mut registry);
+        for pet_id in ["..", ".", ""] {
+            let args = serde_json::json!({ "petId": pet_id }).to_string();
+            let error = registry.execute_tool("getPet", &args).await.unwrap_err();
+            assert!(error.contains("Invalid value"), "{}", error);
+        }
+        assert_eq!(encode_path_segment("petId", "..a").unwrap(), "..a");
+    }
+
+    #[tokio::test]
+    async fn test_requires_a_base_url() {
+        let mut registry = ToolRegistry::new();
+        OpenApiTools::from_json(SPEC).unwrap().register(&mut registry);
+        let error = registry.execute_tool("getPet", r#"{"petId": "1"}"#).await.unwrap_err();
+        assert!(error.contains("No base URL"), "{}", error);
+    }
+}
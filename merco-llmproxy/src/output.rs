@@ -0,0 +1,414 @@
+//!
+//! Output Schemas
+//!
+//! `MercoOutput` describes a Rust type as a JSON Schema, so a model can be asked for a
+//! value of that type and its answer checked before it is deserialized. Implementations
+//! are usually derived with `#[derive(MercoOutput)]` (`macros` feature), which covers
+//! structs with named fields, newtypes and enums; doc comments become descriptions and
+//! `Option` fields are optional. Serde's `rename`, `rename_all`, `skip`, `default` and
+//! `tag` attributes are taken into account so the schema matches what deserializes.
+//!
+//! Schemas are built by calling `output_schema` of each field type, so recursive types
+//! are not supported.
+
+use serde_json::{json, Map};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// The JSON value schemas are made of, re-exported for derived implementations.
+pub use serde_json::Value;
+
+#[cfg(feature = "macros")]
+pub use merco_macros::MercoOutput;
+
+/// A type with a JSON Schema describing its serialized form.
+pub trait MercoOutput {
+    /// The JSON Schema of the type, with nested types inlined.
+    fn output_schema() -> Value;
+
+    /// A name for the schema, as accepted by providers (`[a-zA-Z0-9_-]`).
+    fn output_name() -> String {
+        "output".to_string()
+    }
+}
+
+/// A field of a struct (or struct variant) schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputField {
+    /// Name of the field in JSON.
+    pub name: String,
+    /// Description shown to the model.
+    pub description: Option<String>,
+    /// Whether the field must be present.
+    pub required: bool,
+    /// Schema of the field's value.
+    pub schema: Value,
+}
+
+impl OutputField {
+    /// A required field.
+    pub fn new(name: impl Into<String>, schema: Value) -> Self {
+        Self { name: name.into(), description: None, required: true, schema }
+    }
+
+    /// Sets the description (builder style).
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Marks the field as optional (builder style).
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+}
+
+/// The data carried by an enum variant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariantData {
+    /// No data; serialized as the variant name.
+    Unit,
+    /// A single unnamed value.
+    Newtype(Value),
+    /// Several unnamed values, serialized as an array.
+    Tuple(Vec<Value>),
+    /// Named fields.
+    Struct(Vec<OutputField>),
+}
+
+/// A variant of an enum schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputVariant {
+    /// Name of the variant in JSON.
+    pub name: String,
+    /// Description shown to the model.
+    pub description: Option<String>,
+    /// The variant's data.
+    pub data: VariantData,
+}
+
+/// Schema of an object with the given fields; unknown fields are not allowed.
+pub fn struct_schema(description: Option<&str>, fields: Vec<OutputField>) -> Value {
+    let mut schema = object_schema(fields);
+    describe(&mut schema, description);
+    schema
+}
+
+/// Schema of a tuple struct with several fields: an array of exactly that shape.
+pub fn tuple_schema(description: Option<&str>, items: Vec<Value>) -> Value {
+    let mut schema = data_schema(VariantData::Tuple(items));
+    describe(&mut schema, description);
+    schema
+}
+
+/// `schema` with a description added, e.g. for a newtype around another type.
+pub fn describe_schema(mut schema: Value, description: Option<&str>) -> Value {
+    describe(&mut schema, description);
+    schema
+}
+
+/// Schema of an enum, externally tagged (`{"Variant": data}`, or `"Variant"` for unit
+/// variants) unless `tag` names the field of an internally tagged representation.
+pub fn enum_schema(description: Option<&str>, variants: Vec<OutputVariant>, tag: Option<&str>) -> Value {
+    let all_unit = variants.iter().all(|variant| variant.data == VariantData::Unit);
+    let mut schema = if all_unit && tag.is_none() {
+        let names: Vec<&str> = variants.iter().map(|variant| variant.name.as_str()).collect();
+        let mut schema = json!({ "type": "string", "enum": names });
+        let described: Vec<String> = variants
+            .iter()
+            .filter_map(|variant| variant.description.as_ref().map(|d| format!("{}: {}", variant.name, d)))
+            .collect();
+        if !described.is_empty() {
+            describe(&mut schema, Some(&described.join("; ")));
+        }
+        schema
+    } else {
+        let options: Vec<Value> = variants.into_iter().map(|variant| variant_schema(variant, tag)).collect();
+        json!({ "oneOf": options })
+    };
+    if let Some(description) = description {
+        let combined = match schema.get("description").and_then(Value::as_str) {
+            Some(variants) => format!("{} ({})", description, variants),
+            None => description.to_string(),
+        };
+        describe(&mut schema, Some(&combined));
+    }
+    schema
+}
+
+fn variant_schema(variant: OutputVariant, tag: Option<&str>) -> Value {
+    let OutputVariant { name, description, data } = variant;
+    let mut schema = match (tag, data) {
+        (None, VariantData::Unit) => json!({ "type": "string", "enum": [name] }),
+        (None, data) => object_schema(vec![OutputField::new(name, data_schema(data))]),
+        (Some(tag), data) => {
+            let mut fields = vec![OutputField::new(tag, json!({ "type": "string", "enum": [name] }))];
+            match data {
+                VariantData::Unit => {}
+                VariantData::Struct(variant_fields) => fields.extend(variant_fields),
+                // Serde only supports internal tagging for unit, struct and newtype variants
+                // (the latter when the inner type is a struct), so merge the inner object
+                VariantData::Newtype(inner) => return merge_tag(inner, fields.remove(0)),
+                VariantData::Tuple(_) => {}
+            }
+            object_schema(fields)
+        }
+    };
+    describe(&mut schema, description.as_deref());
+    schema
+}
+
+fn data_schema(data: VariantData) -> Value {
+    match data {
+        VariantData::Unit => json!({ "type": "null" }),
+        VariantData::Newtype(schema) => schema,
+        VariantData::Tuple(items) => {
+            let len = items.len();
+            json!({ "type": "array", "items": items, "minItems": len, "maxItems": len })
+        }
+        VariantData::Struct(fields) => object_schema(fields),
+    }
+}
+
+fn merge_tag(mut inner: Value, tag: OutputField) -> Value {
+    if let Some(object) = inner.as_object_mut() {
+        if let Some(properties) = object.get_mut("properties").and_then(Value::as_object_mut) {
+            properties.insert(tag.name.clone(), tag.schema);
+        }
+        if let Some(required) = object.get_mut("required").and_then(Value::as_array_mut) {
+            required.push(json!(tag.name));
+        }
+    }
+    inner
+}
+
+fn object_schema(fields: Vec<OutputField>) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for field in fields {
+        let mut schema = field.schema;
+        describe(&mut schema, field.description.as_deref());
+        if field.required {
+            required.push(json!(field.name));
+        }
+        properties.insert(field.name, schema);
+    }
+    json!({ "type": "object", "properties": properties, "required": required, "additionalProperties": false })
+}
+
+fn describe(schema: &mut Value, description: Option<&str>) {
+    if let (Some(description), Some(object)) = (description, schema.as_object_mut()) {
+        object.insert("description".to_string(), json!(description));
+    }
+}
+
+macro_rules! integer_outputs {
+    ($($ty:ty),*) => {
+        $(impl MercoOutput for $ty {
+            fn output_schema() -> Value {
+                json!({ "type": "integer", "minimum": <$ty>::MIN, "maximum": <$ty>::MAX })
+            }
+        })*
+    };
+}
+
+integer_outputs!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize);
+
+impl MercoOutput for f32 {
+    fn output_schema() -> Value {
+        json!({ "type": "number" })
+    }
+}
+
+impl MercoOutput for f64 {
+    fn output_schema() -> Value {
+        json!({ "type": "number" })
+    }
+}
+
+impl MercoOutput for bool {
+    fn output_schema() -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+impl MercoOutput for String {
+    fn output_schema() -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl MercoOutput for char {
+    fn output_schema() -> Value {
+        json!({ "type": "string", "minLength": 1, "maxLength": 1 })
+    }
+}
+
+impl MercoOutput for Value {
+    fn output_schema() -> Value {
+        json!({})
+    }
+}
+
+impl MercoOutput for () {
+    fn output_schema() -> Value {
+        json!({ "type": "null" })
+    }
+}
+
+impl<T: MercoOutput> MercoOutput for Option<T> {
+    fn output_schema() -> Value {
+        json!({ "anyOf": [T::output_schema(), { "type": "null" }] })
+    }
+}
+
+impl<T: MercoOutput> MercoOutput for Box<T> {
+    fn output_schema() -> Value {
+        T::output_schema()
+    }
+
+    fn output_name() -> String {
+        T::output_name()
+    }
+}
+
+impl<T: MercoOutput> MercoOutput for Vec<T> {
+    fn output_schema() -> Value {
+        json!({ "type": "array", "items": T::output_schema() })
+    }
+}
+
+impl<T: MercoOutput, const N: usize> MercoOutput for [T; N] {
+    fn output_schema() -> Value {
+        json!({ "type": "array", "items": T::output_schema(), "minItems": N, "maxItems": N })
+    }
+}
+
+impl<T: MercoOutput> MercoOutput for HashSet<T> {
+    fn output_schema() -> Value {
+        json!({ "type": "array", "items": T::output_schema(), "uniqueItems": true })
+    }
+}
+
+impl<T: MercoOutput> MercoOutput for BTreeSet<T> {
+    fn output_schema() -> Value {
+        json!({ "type": "array", "items": T::output_schema(), "uniqueItems": true })
+    }
+}
+
+impl<V: MercoOutput> MercoOutput for HashMap<String, V> {
+    fn output_schema() -> Value {
+        json!({ "type": "object", "additionalProperties": V::output_schema() })
+    }
+}
+
+impl<V: MercoOutput> MercoOutput for BTreeMap<String, V> {
+    fn output_schema() -> Value {
+        json!({ "type": "object", "additionalProperties": V::output_schema() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_struct_schema_marks_required_fields() {
+        let schema = struct_schema(
+            Some("A person"),
+            vec![
+                OutputField::new("name", String::output_schema()).with_description("Full name"),
+                OutputField::new("age", <Option<u8>>::output_schema()).optional(),
+            ],
+        );
+        assert_eq!(schema["description"], "A person");
+        assert_eq!(schema["required"], json!(["name"]));
+        assert_eq!(schema["properties"]["name"]["description"], "Full name");
+        assert_eq!(schema["properties"]["age"]["anyOf"][0]["maximum"], 255);
+        assert_eq!(schema["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_enum_schema_representations() {
+        let unit = |name: &str| OutputVariant { name: name.to_string(), description: None, data: VariantData::Unit };
+        let plain = enum_schema(None, vec![unit("Low"), unit("High")], None);
+        assert_eq!(plain, json!({ "type": "string", "enum": ["Low", "High"] }));
+
+        let circle = OutputVariant {
+            name: "Circle".to_string(),
+            description: None,
+            data: VariantData::Struct(vec![OutputField::new("radius", f64::output_schema())]),
+        };
+        let external = enum_schema(None, vec![unit("Empty"), circle.clone()], None);
+        assert_eq!(external["oneOf"][0], json!({ "type": "string", "enum": ["Empty"] }));
+        assert_eq!(external["oneOf"][1]["required"], json!(["Circle"]));
+        assert_eq!(external["oneOf"][1]["properties"]["Circle"]["required"], json!(["radius"]));
+
+        let internal = enum_schema(None, vec![circle], Some("kind"));
+        assert_eq!(internal["oneOf"][0]["properties"]["kind"]["enum"], json!(["Circle"]));
+        assert_eq!(internal["oneOf"][0]["required"], json!(["kind", "radius"]));
+    }
+
+    #[cfg(feature = "macros")]
+    mod derived {
+        use super::*;
+        use serde::Deserialize;
+
+        /// A parcel on its way
+        #[allow(dead_code)]
+        #[derive(MercoOutput, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Shipment {
+            /// Carrier tracking number
+            tracking_id: String,
+            #[serde(rename = "eta")]
+            expected_arrival: Option<String>,
+            #[serde(skip_deserializing)]
+            internal_note: String,
+            status: Status,
+        }
+
+        #[allow(dead_code)]
+        #[derive(MercoOutput, Deserialize)]
+        #[serde(rename_all = "snake_case")]
+        enum Status {
+            InTransit,
+            Delivered,
+            /// Lost in transit
+            Lost { last_seen: String },
+        }
+
+        #[allow(dead_code)]
+        #[derive(MercoOutput, Deserialize)]
+        #[serde(tag = "kind")]
+        enum Shape {
+            Circle { radius: f64 },
+            Square { side: f64 },
+        }
+
+        #[test]
+        fn test_derived_struct_schema() {
+            let schema = Shipment::output_schema();
+
+            assert_eq!(schema["description"], "A parcel on its way");
+            let properties: Vec<&String> = schema["properties"].as_object().unwrap().keys().collect();
+            assert_eq!(properties, ["eta", "status", "trackingId"]);
+            assert_eq!(schema["properties"]["trackingId"]["description"], "Carrier tracking number");
+            assert_eq!(schema["required"], json!(["trackingId", "status"]));
+        }
+
+        #[test]
+        fn test_derived_enum_schemas() {
+            let status = Status::output_schema();
+            assert_eq!(status["oneOf"][0], json!({ "type": "string", "enum": ["in_transit"] }));
+            assert_eq!(status["oneOf"][1], json!({ "type": "string", "enum": ["delivered"] }));
+            assert_eq!(status["oneOf"][2]["required"], json!(["lost"]));
+            assert_eq!(status["oneOf"][2]["properties"]["lost"]["required"], json!(["last_seen"]));
+            assert_eq!(status["oneOf"][2]["description"], "Lost in transit");
+
+            let shape = Shape::output_schema();
+            assert_eq!(shape["oneOf"][0]["properties"]["kind"]["enum"], json!(["Circle"]));
+            assert_eq!(shape["oneOf"][1]["required"], json!(["kind", "side"]));
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//!
+//! Incremental (Partial) JSON Parsing
+//!
+//! Provides `PartialJsonParser`, which consumes streamed text deltas and reports
+//! structured JSON output as it is being generated. Object fields and array
+//! elements are reported as soon as they are complete, and a best-effort snapshot
+//! of the whole value (with open strings, objects and arrays closed) can be taken
+//! at any point. This lets UIs render structured output progressively instead of
+//! waiting for the final, validated response.
+
+use crate::traits::{CompletionStream, ProviderError, StreamContentDelta};
+use futures::stream::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::de::Error as DeError;
+use serde_json::Value as JsonValue;
+use std::pin::Pin;
+
+/// An event emitted by the `PartialJsonParser` while consuming text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartialJsonEvent {
+    /// An object field or array element has been fully received.
+    FieldCompleted {
+        /// JSON-pointer-style path of the completed value (e.g. `/address/city` or `/tags/0`).
+        path: String,
+        /// The completed value.
+        value: JsonValue,
+    },
+    /// The root value has been fully received.
+    Completed(JsonValue),
+}
+
+/// An update produced by `stream_partial_json` for each processed stream chunk.
+#[derive(Debug, Clone)]
+pub struct PartialJsonUpdate {
+    /// Events triggered by the text in this chunk.
+    pub events: Vec<PartialJsonEvent>,
+    /// A best-effort snapshot of the value received so far, if any can be built.
+    pub snapshot: Option<JsonValue>,
+}
+
+/// Type alias for the stream returned by `stream_partial_json`.
+pub type PartialJsonStream =
+    Pin<Box<dyn Stream<Item = Result<PartialJsonUpdate, ProviderError>> + Send>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Object,
+    Array,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    kind: Container,
+    /// Key of the field currently being received (objects only).
+    key: Option<String>,
+    /// Index of the element currently being received (arrays only).
+    index: usize,
+    /// Byte offset where the current field/element value starts.
+    value_start: Option<usize>,
+    /// Whether the next string in this object is a key.
+    expect_key: bool,
+}
+
+impl Frame {
+    fn new(kind: Container) -> Self {
+        Self {
+            kind,
+            key: None,
+            index: 0,
+            value_start: None,
+            expect_key: kind == Container::Object,
+        }
+    }
+
+    fn closer(&self) -> char {
+        match self.kind {
+            Container::Object => '}',
+            Container::Array => ']',
+        }
+    }
+}
+
+/// Incrementally parses a JSON object or array from streamed text.
+///
+/// Any text before the first `{` or `[` (such as prose or a markdown code fence) and
+/// after the root value is closed is ignored.
+///
+/// # Examples
+///
+/// ```
+/// use merco_llmproxy::partial_json::{PartialJsonEvent, PartialJsonParser};
+///
+/// let mut parser = PartialJsonParser::new();
+/// parser.push(r#"{"name": "Al"#);
+/// assert_eq!(parser.snapshot().unwrap()["name"], "Al");
+///
+/// let events = parser.push(r#"ex", "age": 25}"#);
+/// assert!(matches!(events.last(), Some(PartialJsonEvent::Completed(_))));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PartialJsonParser {
+    buffer: String,
+    /// Byte offset of the next character to scan.
+    pos: usize,
+    stack: Vec<Frame>,
+    root_start: Option<usize>,
+    in_string: bool,
+    escape: bool,
+    string_start: usize,
+    string_is_key: bool,
+    /// Last position where the buffer can be cut and closed, with the closers to append.
+    safe_cut: Option<(usize, String)>,
+    completed: Option<JsonValue>,
+}
+
+impl PartialJsonParser {
+    /// Creates a new, empty parser.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the raw text received so far.
+    pub fn buffer(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Returns `true` once the root value has been fully received.
+    pub fn is_complete(&self) -> bool {
+        self.completed.is_some()
+    }
+
+    /// Appends a text delta and returns the events it triggered.
+    pub fn push(&mut self, delta: &str) -> Vec<PartialJsonEvent> {
+        let mut events = Vec::new();
+        if self.completed.is_some() {
+            return events;
+        }
+        self.buffer.push_str(delta);
+
+        while let Some(c) = self.buffer[self.pos..].chars().next() {
+            let i = self.pos;
+            self.pos += c.len_utf8();
+
+            if self.root_start.is_none() {
+                if c == '{' || c == '[' {
+                    self.root_start = Some(i);
+                    self.open(c);
+                }
+                continue;
+            }
+
+            if self.in_string {
+                if self.escape {
+                    self.escape = false;
+                } else if c == '\\' {
+                    self.escape = true;
+                } else if c == '"' {
+                    self.in_string = false;
+                    if self.string_is_key {
+                        let key = serde_json::from_str::<String>(&self.buffer[self.string_start..self.pos]).ok();
+                        if let Some(frame) = self.stack.last_mut() {
+                            frame.key = key;
+                        }
+                    } else {
+                        self.safe_cut = Some((self.pos, self.closers()));
+                    }
+                }
+                continue;
+            }
+
+            match c {
+                '"' => {
+                    self.in_string = true;
+                    self.string_start = i;
+                    self.string_is_key = self.stack.last().is_some_and(|f| f.kind == Container::Object && f.expect_key);
+                    if !self.string_is_key {
+                        self.begin_value(i);
+                    }
+                }
+                '{' | '[' => {
+                    self.begin_value(i);
+                    self.open(c);
+                }
+                '}' | ']' => {
+                    self.finish_value(i, &mut events);
+                    self.stack.pop();
+                    if self.stack.is_empty() {
+                        let root = self.root_start.unwrap_or(0);
+                        if let Ok(value) = serde_json::from_str::<JsonValue>(&self.buffer[root..self.pos]) {
+                            events.push(PartialJsonEvent::Completed(value.clone()));
+                            self.completed = Some(value);
+                        }
+                        break;
+                    }
+                    self.safe_cut = Some((self.pos, self.closers()));
+                }
+                ':' => {
+                    if let Some(frame) = self.stack.last_mut() {
+                        frame.expect_key = false;
+                    }
+                }
+                ',' => {
+                    self.safe_cut = Some((i, self.closers()));
+                    self.finish_value(i, &mut events);
+                    if let Some(frame) = self.stack.last_mut() {
+                        match frame.kind {
+                            Container::Object => {
+                                frame.expect_key = true;
+                                frame.key = None;
+                            }
+                            Container::Array => frame.index += 1,
+                        }
+                    }
+                }
+                c if c.is_whitespace() => {}
+                _ => self.begin_value(i),
+            }
+        }
+
+        events
+    }
+
+    /// Builds a best-effort snapshot of the value received so far.
+    ///
+    /// Open strings, objects, and arrays are closed; incomplete keys and literals are dropped.
+    /// Returns `None` if no value has started yet.
+    pub fn snapshot(&self) -> Option<JsonValue> {
+        if let Some(value) = &self.completed {
+            return Some(value.clone());
+        }
+        let root = self.root_start?;
+
+        // First try closing the buffer as-is (this keeps partially received string values).
+        let mut candidate = self.buffer[root..].to_string();
+        if self.in_string {
+            if self.string_is_key {
+                candidate.truncate(self.string_start - root);
+            } else {
+                if self.escape {
+                    candidate.pop();
+                }
+                candidate.push('"');
+            }
+        }
+        candidate.push_str(&self.closers());
+        if let Ok(value) = serde_json::from_str(&candidate) {
+            return Some(value);
+        }
+
+        // Fall back to the last position known to end on a value boundary.
+        let (cut, closers) = self.safe_cut.as_ref()?;
+        let mut candidate = self.buffer[root..*cut].to_string();
+        candidate.push_str(closers);
+        serde_json::from_str(&candidate).ok()
+    }
+
+    /// Deserializes the current snapshot into `T`.
+    ///
+    /// Works best with types whose fields are `Option`s or have `#[serde(default)]`,
+    /// since fields that have not been received yet are missing from the snapshot.
+    pub fn snapshot_as<T: DeserializeOwned>(&self) -> Option<T> {
+        self.snapshot().and_then(|value| serde_json::from_value(value).ok())
+    }
+
+    /// Consumes the parser and returns the completed root value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::ParseError` if the root value was never completed.
+    pub fn finish(self) -> Result<JsonValue, ProviderError> {
+        self.completed.ok_or_else(|| {
+            ProviderError::ParseError(serde_json::Error::custom("Incomplete JSON value in stream"))
+        })
+    }
+
+    fn open(&mut self, c: char) {
+        let kind = if c == '{' { Container::Object } else { Container::Array };
+        self.stack.push(Frame::new(kind));
+        self.safe_cut = Some((self.pos, self.closers()));
+    }
+
+    fn begin_value(&mut self, i: usize) {
+        if let Some(frame) = self.stack.last_mut() {
+            if frame.value_start.is_none() && !frame.expect_key {
+                frame.value_start = Some(i);
+            }
+        }
+    }
+
+    /// Completes the field/element currently open in the innermost container, if any.
+    fn finish_value(&mut self, end: usize, events: &mut Vec<PartialJsonEvent>) {
+        let Some(start) = self.stack.last_mut().and_then(|f| f.value_start.take()) else {
+            return;
+        };
+        if let Ok(value) = serde_json::from_str::<JsonValue>(self.buffer[start..end].trim()) {
+            events.push(PartialJsonEvent::FieldCompleted { path: self.current_path(), value });
+        }
+    }
+
+    /// JSON-pointer path of the value currently being received in the innermost container.
+    fn current_path(&self) -> String {
+        let mut path = String::new();
+        for frame in &self.stack {
+            path.push('/');
+            match frame.kind {
+                Container::Object => {
+                    let key = frame.key.as_deref().unwrap_or_default();
+                    path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                }
+                Container::Array => path.push_str(&frame.index.to_string()),
+            }
+        }
+        path
+    }
+
+    fn closers(&self) -> String {
+        self.stack.iter().rev().map(Frame::closer).collect()
+    }
+}
+
+/// Wraps a completion stream, feeding its text deltas through a `PartialJsonParser`.
+///
+/// Each text chunk yields a `PartialJsonUpdate` with the events it triggered and a
+/// snapshot of the value received so far. Non-text chunks are skipped.
+pub fn stream_partial_json(stream: CompletionStream) -> PartialJsonStream {
+    let mut parser = PartialJsonParser::new();
+    let updates = stream.filter_map(move |chunk| {
+        let update = match chunk {
+            Ok(chunk) => match chunk.delta {
+                StreamContentDelta::Text(text) if !text.is_empty() => {
+                    let events = parser.push(&text);
+                    Some(Ok(PartialJsonUpdate { events, snapshot: parser.snapshot() }))
+                }
+                _ => None,
+            },
+            Err(e) => Some(Err(e)),
+        };
+        futures::future::ready(update)
+    });
+    Box::pin(updates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fields_complete_incrementally() {
+        let mut parser = PartialJsonParser::new();
+        let mut events = Vec::new();
+        for piece in ["```json\n{\"na", "me\": \"Alex\", \"address\": {\"ci", "ty\": \"SF\"}, \"tags\": [\"a\", ", "\"b\"]}\n```"] {
+            events.extend(parser.push(piece));
+        }
+
+        let paths: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                PartialJsonEvent::FieldCompleted { path, .. } => Some(path.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paths, vec!["/name", "/address/city", "/address", "/tags/0", "/tags/1", "/tags"]);
+        assert_eq!(
+            parser.finish().unwrap(),
+            json!({"name": "Alex", "address": {"city": "SF"}, "tags": ["a", "b"]})
+        );
+    }
+
+    #[test]
+    fn test_snapshot_closes_open_structures() {
+        let mut parser = PartialJsonParser::new();
+        assert_eq!(parser.snapshot(), None);
+
+        parser.push(r#"{"title": "Hel"#);
+        assert_eq!(parser.snapshot(), Some(json!({"title": "Hel"})));
+
+        parser.push(r#"lo", "items": [1, 2"#);
+        assert_eq!(parser.snapshot(), Some(json!({"title": "Hello", "items": [1, 2]})));
+
+        parser.push(r#"], "done": tr"#);
+        assert_eq!(parser.snapshot(), Some(json!({"title": "Hello", "items": [1, 2]})));
+
+        parser.push(r#"ue, "ne"#);
+        assert_eq!(parser.snapshot(), Some(json!({"title": "Hello", "items": [1, 2], "done": true})));
+    }
+}
@@ -0,0 +1,242 @@
+//!
+//! Path Safety
+//!
+//! Tools and output writers often create files from model-generated names. This module
+//! validates such names the same way on every platform, so a crew that works on Linux
+//! does not break (or write outside its workspace) on Windows:
+//!
+//! * `/` and `\` are both treated as separators, and paths must stay relative: drive
+//!   letters, UNC prefixes, `..` components and alternate data streams are rejected.
+//! * Windows reserved device names (`CON`, `NUL`, `COM1`, ...), invalid characters and
+//!   trailing dots or spaces are rejected, as are components longer than 255 bytes.
+//! * Resolved paths are checked against symlinks escaping the root, and long absolute
+//!   paths get the `\\?\` prefix on Windows so they work past `MAX_PATH`.
+
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Longest file or directory name accepted by common filesystems (NTFS, ext4, APFS).
+pub const MAX_COMPONENT_LEN: usize = 255;
+/// Length above which Windows needs the extended-length `\\?\` prefix.
+pub const WINDOWS_MAX_PATH: usize = 260;
+
+/// Characters not allowed in file names on Windows (besides control characters).
+const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Device names Windows reserves in every directory, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
+    "COM8", "COM9", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Why a path was rejected.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    /// The path has no components.
+    #[error("Path is empty")]
+    Empty,
+    /// The path is absolute or has a drive or UNC prefix.
+    #[error("Path must be relative: {0}")]
+    Absolute(String),
+    /// The path contains a `..` component or resolves outside its root.
+    #[error("Path escapes its root directory: {0}")]
+    Traversal(String),
+    /// A component is a reserved device name on Windows.
+    #[error("'{0}' is a reserved file name on Windows")]
+    ReservedName(String),
+    /// A component contains a character that is invalid in file names.
+    #[error("'{0}' contains the invalid character {1:?}")]
+    InvalidCharacter(String, char),
+    /// A component ends with a dot or a space, which Windows silently strips.
+    #[error("'{0}' ends with a dot or space")]
+    TrailingDotOrSpace(String),
+    /// A component is longer than `MAX_COMPONENT_LEN` bytes.
+    #[error("'{0}' is longer than {MAX_COMPONENT_LEN} bytes")]
+    TooLong(String),
+}
+
+/// Returns `true` if `name` is a Windows device name such as `CON` or `com1.txt`.
+pub fn is_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+fn check_component(component: &str) -> Result<(), PathError> {
+    if let Some(c) = component.chars().find(|c| INVALID_CHARS.contains(c) || c.is_control()) {
+        return Err(PathError::InvalidCharacter(component.to_string(), c));
+    }
+    if is_reserved_name(component) {
+        return Err(PathError::ReservedName(component.to_string()));
+    }
+    if component.ends_with('.') || component.ends_with(' ') {
+        return Err(PathError::TrailingDotOrSpace(component.to_string()));
+    }
+    if component.len() > MAX_COMPONENT_LEN {
+        return Err(PathError::TooLong(component.to_string()));
+    }
+    Ok(())
+}
+
+/// Validates a relative path given with either separator and returns it as a native path.
+///
+/// # Errors
+///
+/// Returns a `PathError` describing the first problem found.
+pub fn relative_path(path: &str) -> Result<PathBuf, PathError> {
+    if path.starts_with(['/', '\\']) || path.as_bytes().get(1) == Some(&b':') {
+        return Err(PathError::Absolute(path.to_string()));
+    }
+    let mut relative = PathBuf::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            ".." => return Err(PathError::Traversal(path.to_string())),
+            _ => {
+                check_component(component)?;
+                relative.push(component);
+            }
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        return Err(PathError::Empty);
+    }
+    Ok(relative)
+}
+
+/// Resolves `path` inside `root`, rejecting unsafe names and symlinks that lead outside
+/// `root`. The file itself does not need to exist, but every existing component is
+/// checked: a symlink is only accepted if its target exists and lies inside `root`, so a
+/// dangling link cannot make a later write create a file elsewhere.
+///
+/// # Errors
+///
+/// Returns a `PathError` if the path is unsafe or resolves outside `root`.
+pub fn resolve_in(root: &Path, path: &str) -> Result<PathBuf, PathError> {
+    let relative = relative_path(path)?;
+    let resolved = root.join(&relative);
+
+    if let Ok(canonical_root) = root.canonicalize() {
+        let mut current = root.to_path_buf();
+        for component in relative.components() {
+            current.push(component);
+            // `symlink_metadata` doesn't follow links, so dangling ones are seen too
+            let Ok(metadata) = current.symlink_metadata() else {
+                break;
+            };
+            if metadata.file_type().is_symlink() {
+                let target = current.canonicalize().map_err(|_| PathError::Traversal(path.to_string()))?;
+                if !target.starts_with(&canonical_root) {
+                    return Err(PathError::Traversal(path.to_string()));
+                }
+            }
+        }
+    }
+    Ok(extended_length(&resolved))
+}
+
+/// Turns a model-generated name into a safe single file name, replacing separators and
+/// invalid characters with `_` and renaming reserved or empty names.
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if INVALID_CHARS.contains(&c) || c.is_control() || c == '/' || c == '\\' { '_' } else { c })
+        .collect();
+    sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+    if sanitized.len() > MAX_COMPONENT_LEN {
+        let mut end = MAX_COMPONENT_LEN;
+        while !sanitized.is_char_boundary(end) {
+            end -= 1;
+        }
+        sanitized.truncate(end);
+    }
+    if sanitized.is_empty() || sanitized.chars().all(|c| c == '.') {
+        return "_".to_string();
+    }
+    if is_reserved_name(&sanitized) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Adds the `\\?\` prefix to long absolute paths on Windows; returns other paths unchanged.
+pub fn extended_length(path: &Path) -> PathBuf {
+    if !cfg!(windows) || !path.is_absolute() || path.as_os_str().len() < WINDOWS_MAX_PATH {
+        return path.to_path_buf();
+    }
+    let display = path.to_string_lossy();
+    if display.starts_with(r"\\?\") {
+        return path.to_path_buf();
+    }
+    match path.components().next() {
+        Some(Component::Prefix(prefix)) if matches!(prefix.kind(), std::path::Prefix::UNC(..)) => {
+            PathBuf::from(format!(r"\\?\UNC\{}", display.trim_start_matches('\\')))
+        }
+        _ => PathBuf::from(format!(r"\\?\{}", display)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_unsafe_paths() {
+        assert_eq!(relative_path("../etc/passwd"), Err(PathError::Traversal("../etc/passwd".to_string())));
+        assert_eq!(relative_path(r"reports\..\..\x"), Err(PathError::Traversal(r"reports\..\..\x".to_string())));
+        assert!(matches!(relative_path("/etc/passwd"), Err(PathError::Absolute(_))));
+        assert!(matches!(relative_path(r"C:\Windows\x.txt"), Err(PathError::Absolute(_))));
+        assert!(matches!(relative_path(r"\\server\share\x"), Err(PathError::Absolute(_))));
+        assert!(matches!(relative_path("notes.txt:hidden"), Err(PathError::InvalidCharacter(_, ':'))));
+        assert!(matches!(relative_path("out/CON.txt"), Err(PathError::ReservedName(_))));
+        assert!(matches!(relative_path("out/lpt1"), Err(PathError::ReservedName(_))));
+        assert!(matches!(relative_path("report. "), Err(PathError::TrailingDotOrSpace(_))));
+        assert!(matches!(relative_path(&"a".repeat(256)), Err(PathError::TooLong(_))));
+        assert_eq!(relative_path("./"), Err(PathError::Empty));
+    }
+
+    #[test]
+    fn test_normalizes_separators() {
+        let expected: PathBuf = ["reports", "2024", "summary.md"].iter().collect();
+        assert_eq!(relative_path(r"reports\2024/summary.md").unwrap(), expected);
+        assert_eq!(relative_path("./reports//2024/./summary.md").unwrap(), expected);
+        // Names that merely contain a reserved word are fine
+        assert!(relative_path("console.log").is_ok());
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("Q3: results?.csv"), "Q3_ results_.csv");
+        assert_eq!(sanitize_file_name("../../secret"), ".._.._secret");
+        assert_eq!(sanitize_file_name("nul.txt"), "_nul.txt");
+        assert_eq!(sanitize_file_name("draft..."), "draft");
+        assert_eq!(sanitize_file_name("..."), "_");
+        assert_eq!(sanitize_file_name(&"é".repeat(200)).len(), 254);
+    }
+
+    #[test]
+    fn test_resolve_in_stays_inside_root() {
+        let root = std::env::temp_dir().join(format!("merco-paths-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("data")).unwrap();
+
+        let file = resolve_in(&root, r"data\new\file.txt").unwrap();
+        assert!(file.starts_with(extended_length(&root)));
+        assert!(resolve_in(&root, "../outside.txt").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(std::env::temp_dir(), root.join("escape")).unwrap();
+            assert!(matches!(resolve_in(&root, "escape/x.txt"), Err(PathError::Traversal(_))));
+
+            // A dangling link would be followed by a write that creates its target
+            let outside = std::env::temp_dir().join(format!("merco-paths-outside-{}", std::process::id()));
+            std::os::unix::fs::symlink(&outside, root.join("data/dangling")).unwrap();
+            assert!(matches!(resolve_in(&root, "data/dangling"), Err(PathError::Traversal(_))));
+            assert!(matches!(resolve_in(&root, "data/dangling/x.txt"), Err(PathError::Traversal(_))));
+
+            // Links that stay inside the root are fine
+            std::os::unix::fs::symlink(root.join("data"), root.join("alias")).unwrap();
+            assert!(resolve_in(&root, "alias/x.txt").is_ok());
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,277 @@
+//!
+//! Mock Provider
+//!
+//! A scripted `LlmProvider` for tests. Responses, tool calls and errors are queued up
+//! front and returned in order, optionally after a simulated latency, so agents and
+//! crews can be exercised without network access or an API key. Every request is
+//! recorded for later assertions.
+
+use crate::clock::Clock;
+use crate::embeddings::{EmbeddingProvider, EmbeddingRequest, EmbeddingResponse};
+use crate::tokenizer::{count_message_tokens, HeuristicTokenizer, Tokenizer};
+use crate::traits::{
+    CompletionKind, CompletionRequest, CompletionResponse, CompletionStream, CompletionStreamChunk, LlmProvider,
+    ProviderError, StreamContentDelta, TokenUsage, ToolCallFunction, ToolCallFunctionStreamDelta, ToolCallRequest,
+    ToolCallStreamDelta,
+};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A provider that replays a queue of scripted replies.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    replies: Mutex<VecDeque<Result<CompletionResponse, ProviderError>>>,
+    requests: Mutex<Vec<CompletionRequest>>,
+    latency: Option<Duration>,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl MockProvider {
+    /// Creates a mock with no scripted replies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a text reply (builder style).
+    pub fn with_message(self, content: impl Into<String>) -> Self {
+        self.with_response(CompletionResponse {
+            kind: CompletionKind::Message { content: content.into() },
+            usage: None,
+            finish_reason: Some("stop".to_string()),
+            logprobs: None,
+        })
+    }
+
+    /// Queues a reply requesting a single tool call with JSON `arguments` (builder style).
+    pub fn with_tool_call(self, name: impl Into<String>, arguments: impl Into<String>) -> Self {
+        self.with_tool_calls(vec![(name.into(), arguments.into())])
+    }
+
+    /// Queues a reply requesting several parallel tool calls, given as `(name, arguments)`
+    /// pairs (builder style).
+    pub fn with_tool_calls(self, calls: Vec<(String, String)>) -> Self {
+        let tool_calls = calls
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, arguments))| ToolCallRequest {
+                id: format!("call_mock_{}", i),
+                tool_type: "function".to_string(),
+                function: ToolCallFunction { name, arguments },
+            })
+            .collect();
+        self.with_response(CompletionResponse {
+            kind: CompletionKind::ToolCall { tool_calls },
+            usage: None,
+            finish_reason: Some("tool_calls".to_string()),
+            logprobs: None,
+        })
+    }
+
+    /// Queues a complete response (builder style). If it has no usage, usage is
+    /// estimated when it is returned.
+    pub fn with_response(self, response: CompletionResponse) -> Self {
+        self.push(Ok(response));
+        self
+    }
+
+    /// Queues an error (builder style).
+    pub fn with_error(self, error: ProviderError) -> Self {
+        self.push(Err(error));
+        self
+    }
+
+    /// Delays every reply by `latency` (builder style).
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Simulates the latency on `clock` instead of tokio's clock (builder style).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Queues a reply after construction, e.g. while a test is running.
+    pub fn push(&self, reply: Result<CompletionResponse, ProviderError>) {
+        self.replies.lock().unwrap().push_back(reply);
+    }
+
+    /// Returns every request received so far, in order.
+    pub fn requests(&self) -> Vec<CompletionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the number of requests received so far.
+    pub fn call_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    /// Returns the number of scripted replies not yet consumed.
+    pub fn remaining(&self) -> usize {
+        self.replies.lock().unwrap().len()
+    }
+
+    async fn next_reply(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        if let Some(latency) = self.latency {
+            match &self.clock {
+                Some(clock) => clock.sleep(latency).await,
+                None => tokio::time::sleep(latency).await,
+            }
+        }
+        let prompt_tokens = count_message_tokens(&HeuristicTokenizer::default(), &request.messages) as u32;
+        self.requests.lock().unwrap().push(request);
+
+        let mut response = self.replies.lock().unwrap().pop_front().unwrap_or_else(|| {
+            Err(ProviderError::Unexpected("MockProvider has no scripted replies left".to_string()))
+        })?;
+        if response.usage.is_none() {
+            let completion_tokens = match &response.kind {
+                CompletionKind::Message { content } => HeuristicTokenizer::default().count(content),
+                CompletionKind::ToolCall { tool_calls } => tool_calls
+                    .iter()
+                    .map(|call| HeuristicTokenizer::default().count(&call.function.arguments))
+                    .sum(),
+            } as u32;
+            response.usage = Some(TokenUsage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+            });
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        self.next_reply(request).await
+    }
+
+    /// Streams the next reply: text is split into word-sized chunks and tool calls are
+    /// sent as one delta each. The final chunk carries usage and the finish reason.
+    async fn completion_stream(&self, request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let response = self.next_reply(request).await?;
+        let mut chunks: Vec<StreamContentDelta> = match response.kind {
+            CompletionKind::Message { content } => {
+                content.split_inclusive(' ').map(|word| StreamContentDelta::Text(word.to_string())).collect()
+            }
+            CompletionKind::ToolCall { tool_calls } => tool_calls
+                .into_iter()
+                .enumerate()
+                .map(|(index, call)| {
+                    StreamContentDelta::ToolCallDelta(vec![ToolCallStreamDelta {
+                        index,
+                        id: Some(call.id),
+                        function: Some(ToolCallFunctionStreamDelta {
+                            name: Some(call.function.name),
+                            arguments: Some(call.function.arguments),
+                        }),
+                    }])
+                })
+                .collect(),
+        };
+        if chunks.is_empty() {
+            chunks.push(StreamContentDelta::Text(String::new()));
+        }
+
+        let last = chunks.len() - 1;
+        let chunks: Vec<Result<CompletionStreamChunk, ProviderError>> = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, delta)| {
+                Ok(CompletionStreamChunk {
+                    delta,
+                    usage: if i == last { response.usage } else { None },
+                    finish_reason: if i == last { response.finish_reason.clone() } else { None },
+                })
+            })
+            .collect();
+        Ok(Box::pin(futures::stream::iter(chunks)))
+    }
+}
+
+/// Size of the vectors returned by `MockProvider::embed` unless `dimensions` is set.
+pub const MOCK_EMBEDDING_DIMENSIONS: usize = 64;
+
+/// Deterministic bag-of-words embeddings: each lowercase word is hashed (FNV-1a) into
+/// a bucket, so texts sharing words get similar vectors. Scripted replies are not
+/// consumed and requests are not recorded.
+#[async_trait]
+impl EmbeddingProvider for MockProvider {
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse, ProviderError> {
+        let dimensions = request.dimensions.map_or(MOCK_EMBEDDING_DIMENSIONS, |d| d.max(1) as usize);
+        let embeddings = request
+            .input
+            .iter()
+            .map(|text| {
+                let mut vector = vec![0.0f32; dimensions];
+                for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+                    let hash = word
+                        .to_lowercase()
+                        .bytes()
+                        .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+                    vector[(hash % dimensions as u64) as usize] += 1.0;
+                }
+                vector
+            })
+            .collect();
+        let prompt_tokens: u32 = request.input.iter().map(|text| HeuristicTokenizer::default().count(text) as u32).sum();
+        let usage = TokenUsage { prompt_tokens, completion_tokens: 0, total_tokens: prompt_tokens };
+        Ok(EmbeddingResponse { embeddings, usage: Some(usage) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::ChatMessage;
+    use futures::stream::StreamExt;
+
+    fn request(prompt: &str) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![ChatMessage::user(prompt.to_string())],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replays_script_in_order() {
+        let mock = MockProvider::new()
+            .with_tool_call("add", r#"{"a": 1, "b": 2}"#)
+            .with_error(ProviderError::ApiError { status: 503, message: "busy".to_string() })
+            .with_message("three");
+
+        let first = mock.completion(request("add 1 and 2")).await.unwrap();
+        assert!(matches!(first.kind, CompletionKind::ToolCall { ref tool_calls } if tool_calls[0].function.name == "add"));
+        assert!(mock.completion(request("again")).await.unwrap_err().is_retryable());
+        let third = mock.completion(request("again")).await.unwrap();
+        assert!(matches!(third.kind, CompletionKind::Message { ref content } if content == "three"));
+        assert!(third.usage.is_some());
+
+        assert!(mock.completion(request("exhausted")).await.is_err());
+        assert_eq!(mock.call_count(), 4);
+        assert_eq!(mock.requests()[0].messages[0].content.as_deref(), Some("add 1 and 2"));
+    }
+
+    #[tokio::test]
+    async fn test_streams_scripted_message() {
+        let mock = MockProvider::new().with_message("hello streaming world");
+        let chunks: Vec<CompletionStreamChunk> =
+            mock.completion_stream(request("hi")).await.unwrap().map(|c| c.unwrap()).collect().await;
+
+        let text: String = chunks
+            .iter()
+            .map(|c| match &c.delta {
+                StreamContentDelta::Text(text) => text.as_str(),
+                StreamContentDelta::ToolCallDelta(_) | StreamContentDelta::Reasoning(_) => "",
+            })
+            .collect();
+        assert_eq!(text, "hello streaming world");
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.last().unwrap().finish_reason.as_deref(), Some("stop"));
+    }
+}
@@ -5,10 +5,16 @@
 //! Each provider implements the `LlmProvider` trait defined in `crate::traits`.
 
 // Declare provider implementation modules here
+#[cfg(feature = "openai")]
 pub mod openai;
+#[cfg(feature = "ollama")]
 pub mod ollama;
+pub mod mock;
 // pub mod anthropic; // Example for future provider
 
 // Re-export provider structs for easier access from the library root.
+#[cfg(feature = "openai")]
 pub use openai::OpenAIProvider;
-pub use ollama::OllamaProvider; 
\ No newline at end of file
+#[cfg(feature = "ollama")]
+pub use ollama::{OllamaOptions, OllamaProvider};
+pub use mock::MockProvider; 
\ No newline at end of file
@@ -4,27 +4,39 @@
 //! Provides the `OllamaProvider` struct for interacting with local Ollama instances.
 //! Supports non-streaming chat completions and non-streaming tool calls (via JSON mode).
 //! Streaming tool calls are not supported as they require JSON mode, which Ollama disables for streaming.
+//! `ResponseFormat::JsonSchema` is sent as Ollama's `format`, so structured output is enforced by
+//! the server for both streaming and non-streaming requests without tools.
 
 use crate::config::{LlmConfig, Provider};
+use crate::embeddings::{sum_usage, EmbeddingProvider, EmbeddingRequest, EmbeddingResponse};
+use crate::signing::SigningRequest;
+use crate::transport::{Transport, TransportRequest, TransportResponse};
 use crate::traits::{
-    ChatMessage, ChatMessageRole, CompletionKind, CompletionRequest, CompletionResponse, CompletionStream, CompletionStreamChunk, LlmProvider, ProviderError, StreamContentDelta, TokenUsage, Tool, ToolCallFunction, ToolCallRequest
+    ChatMessage, ChatMessageRole, CompletionKind, CompletionRequest, CompletionResponse, CompletionStream, CompletionStreamChunk, LlmProvider, ProviderError, ResponseFormat, StreamContentDelta, TokenUsage, Tool, ToolCallFunction, ToolCallRequest, ToolChoice
 };
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures::stream::TryStreamExt;
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-use reqwest::Client;
+use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::time::Duration;
+use std::sync::Arc;
 use serde::de::Error as DeError;
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
 
 /// Default base URL for a local Ollama instance.
 const OLLAMA_DEFAULT_BASE_URL: &str = "http://localhost:11434";
-/// Default request timeout in seconds.
-const DEFAULT_TIMEOUT_SECS: u64 = 120;
+/// Default chat endpoint path.
+const CHAT_PATH: &str = "/api/chat";
+/// Generate endpoint path, used to preload models.
+const GENERATE_PATH: &str = "/api/generate";
+/// Embedding endpoint path.
+const EMBED_PATH: &str = "/api/embed";
+/// Default number of texts per embedding call.
+const DEFAULT_EMBEDDING_BATCH_SIZE: usize = 32;
+/// How long a model preloaded by `warmup` stays in memory.
+const WARMUP_KEEP_ALIVE: &str = "30m";
 
 // Internal structs mapping to Ollama's API
 // We can reuse ChatMessage from traits.rs
@@ -35,21 +47,172 @@ struct OllamaChatRequest {
     messages: Vec<ChatMessage>,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    format: Option<String>,
+    format: Option<JsonValue>, // Either "json" or a JSON Schema object
     #[serde(skip_serializing_if = "Option::is_none")]
-    options: Option<OllamaOptions>,
+    options: Option<serde_json::Map<String, JsonValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    think: Option<bool>, // Thinking models return their reasoning separately when enabled
+    #[serde(flatten)]
+    extra: serde_json::Map<String, JsonValue>,
 }
 
-#[derive(Serialize, Debug, Default)] // Default for easier optional creation
-struct OllamaOptions {
+// Options derived from the generic request fields
+#[derive(Serialize, Debug, Default)]
+struct SamplingOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     num_predict: Option<u32>, 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+}
+
+/// Ollama-specific model options, sent under `options` in the request body (except
+/// `keep_alive`, which is a top-level field).
+///
+/// Attach them to a request with `apply` or `CompletionRequestBuilder::ollama_options`.
+/// Values set here take precedence over the generic request fields, e.g. `num_predict`
+/// over `max_tokens`. Fields not covered can be added as `options` entries in
+/// `CompletionRequest::extra`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OllamaOptions {
+    /// Context window size in tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u32>,
+    /// Number of tokens processed per batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_batch: Option<u32>,
+    /// Number of layers offloaded to the GPU; 0 runs on the CPU only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_gpu: Option<i32>,
+    /// GPU used for small tensors when running on several GPUs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub main_gpu: Option<u32>,
+    /// Number of CPU threads.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_thread: Option<u32>,
+    /// Maximum number of tokens to generate; -1 generates until the model stops.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i32>,
+    /// Samples only from the `top_k` most likely tokens.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    /// Minimum probability of a token relative to the most likely one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f32>,
+    /// How far back the model looks to penalize repetition; -1 uses the context size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_last_n: Option<i32>,
+    /// Strength of the repetition penalty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    /// Mirostat sampling: 0 disabled, 1 Mirostat, 2 Mirostat 2.0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat: Option<u8>,
+    /// Mirostat learning rate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat_eta: Option<f32>,
+    /// Mirostat target entropy; lower values give more focused text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirostat_tau: Option<f32>,
+    /// Whether to memory-map the model file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_mmap: Option<bool>,
+    /// How long the model stays loaded after the request, e.g. `"10m"`; `"-1m"` keeps it
+    /// loaded indefinitely and `"0"` unloads it right away.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<String>,
+}
+
+impl OllamaOptions {
+    /// Creates empty options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the context window size (builder style).
+    pub fn with_num_ctx(mut self, num_ctx: u32) -> Self {
+        self.num_ctx = Some(num_ctx);
+        self
+    }
+
+    /// Sets the number of layers offloaded to the GPU (builder style).
+    pub fn with_num_gpu(mut self, num_gpu: i32) -> Self {
+        self.num_gpu = Some(num_gpu);
+        self
+    }
+
+    /// Sets the number of CPU threads (builder style).
+    pub fn with_num_thread(mut self, num_thread: u32) -> Self {
+        self.num_thread = Some(num_thread);
+        self
+    }
+
+    /// Sets the maximum number of tokens to generate (builder style).
+    pub fn with_num_predict(mut self, num_predict: i32) -> Self {
+        self.num_predict = Some(num_predict);
+        self
+    }
+
+    /// Enables Mirostat sampling with the given mode, target entropy and learning rate
+    /// (builder style).
+    pub fn with_mirostat(mut self, mode: u8, tau: f32, eta: f32) -> Self {
+        self.mirostat = Some(mode);
+        self.mirostat_tau = Some(tau);
+        self.mirostat_eta = Some(eta);
+        self
+    }
+
+    /// Sets how long the model stays loaded after the request (builder style).
+    pub fn with_keep_alive(mut self, keep_alive: impl Into<String>) -> Self {
+        self.keep_alive = Some(keep_alive.into());
+        self
+    }
+
+    /// Stores the options in `request.extra`, merging with options already there.
+    pub fn apply(&self, request: &mut CompletionRequest) {
+        let JsonValue::Object(mut options) = serde_json::to_value(self).unwrap_or_default() else {
+            return;
+        };
+        if let Some(keep_alive) = options.remove("keep_alive") {
+            request.extra.insert("keep_alive".to_string(), keep_alive);
+        }
+        match request.extra.get_mut("options") {
+            Some(JsonValue::Object(existing)) => existing.extend(options),
+            _ if options.is_empty() => {}
+            _ => {
+                request.extra.insert("options".to_string(), JsonValue::Object(options));
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct OllamaEmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+    #[serde(default)]
+    prompt_eval_count: Option<u32>,
 }
 
 // Non-streaming response
 #[derive(Deserialize, Debug)]
+#[allow(dead_code)] // Allow unused fields from API response
 struct OllamaChatResponse {
     model: String,
     created_at: String,
@@ -66,6 +229,7 @@ struct OllamaChatResponse {
 
 // Streaming response chunk (newline-delimited JSON)
 #[derive(Deserialize, Debug)]
+#[allow(dead_code)] // Allow unused fields from API response
 struct OllamaChatStreamResponse {
     model: String,
     created_at: String,
@@ -83,13 +247,17 @@ struct OllamaChatStreamResponse {
 }
 
 #[derive(Deserialize, Debug)]
+#[allow(dead_code)] // Allow unused fields from API response
 struct OllamaStreamMessage {
     role: String,
     content: String, // This is the delta content for the stream
+    #[serde(default)]
+    thinking: Option<String>, // Reasoning delta, when `think` is enabled
 }
 
 // Represents the *entire* JSON object returned when format=json
 #[derive(Deserialize, Debug)]
+#[allow(dead_code)] // Allow unused fields from API response
 struct OllamaJsonResponse {
     model: String,
     created_at: String,
@@ -106,6 +274,7 @@ struct OllamaJsonResponse {
 
 // Standard non-streaming, non-json response
 #[derive(Deserialize, Debug)]
+#[allow(dead_code)] // Allow unused fields from API response
 struct OllamaStandardResponse {
     model: String,
     created_at: String,
@@ -146,8 +315,9 @@ struct OllamaToolFunction {
 #[derive(Debug, Clone)]
 pub struct OllamaProvider {
     config: LlmConfig,
-    client: Client,
+    transport: Arc<dyn Transport>,
     base_url: String,
+    embedding_batch_size: usize,
 }
 
 impl OllamaProvider {
@@ -158,43 +328,125 @@ impl OllamaProvider {
             .clone()
             .unwrap_or_else(|| OLLAMA_DEFAULT_BASE_URL.to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
-            .build()
-            .expect("Failed to build Reqwest client");
+        let transport = config.resolve_transport();
 
         // Note: Ollama doesn't typically use an API key, but config validation
         // might check for base_url presence.
-        Self { config, client, base_url }
+        Self { config, transport, base_url, embedding_batch_size: DEFAULT_EMBEDDING_BATCH_SIZE }
     }
 
-    /// Builds standard HTTP headers for Ollama requests.
-    fn build_headers(&self) -> HeaderMap {
+    /// Sets how many texts are sent per `/api/embed` call (builder style).
+    pub fn with_embedding_batch_size(mut self, batch_size: usize) -> Self {
+        self.embedding_batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Builds standard HTTP headers for Ollama requests, plus the custom headers from the config.
+    fn build_headers(&self) -> Result<HeaderMap, ProviderError> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         // No Authorization header needed for default Ollama
-        headers
+        self.config
+            .apply_headers(&mut headers)
+            .map_err(|e| ProviderError::ConfigError(e.to_string()))?;
+        Ok(headers)
     }
 
-    /// Creates the Ollama options structure from the generic request.
-    fn create_ollama_options(request: &CompletionRequest) -> Option<OllamaOptions> {
-        let options = OllamaOptions {
+    /// Sends a chat request to `/api/chat` (or the configured endpoint path).
+    async fn send_request(&self, body: &OllamaChatRequest) -> Result<TransportResponse, ProviderError> {
+        let path = self.config.endpoint_path.as_deref().unwrap_or(CHAT_PATH);
+        self.post(path, body).await
+    }
+
+    /// POSTs `body` as JSON to `path`, signing it if a signer is configured. Non-success
+    /// responses are returned as `ProviderError::ApiError`.
+    async fn post<T: Serialize>(&self, path: &str, body: &T) -> Result<TransportResponse, ProviderError> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut headers = self.build_headers()?;
+        let body_bytes = serde_json::to_vec(body)?;
+        if let Some(signer) = &self.config.request_signer {
+            let signing_request = SigningRequest { method: "POST".to_string(), url: url.clone(), body: body_bytes.clone() };
+            signer.sign_into(signing_request, &mut headers).await?;
+        }
+
+        let res = self
+            .transport
+            .send(TransportRequest { method: "POST".to_string(), url, headers, body: body_bytes })
+            .await?;
+
+        if !res.is_success() {
+            let status = res.status;
+            let error_body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
+            let message = serde_json::from_str::<HashMap<String, String>>(&error_body)
+                .ok()
+                .and_then(|json| json.get("error").cloned())
+                .unwrap_or(error_body);
+            return Err(ProviderError::ApiError { status, message });
+        }
+        Ok(res)
+    }
+
+    /// Builds the `options` object from the generic request fields, overridden by any
+    /// `options` entries in `request.extra`. Returns `None` if no option is set.
+    fn create_ollama_options(request: &CompletionRequest) -> Option<serde_json::Map<String, JsonValue>> {
+        let options = SamplingOptions {
             temperature: request.temperature,
             num_predict: request.max_tokens,
-            // Map other generic options to Ollama options here
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            seed: request.seed,
+            stop: request.stop.clone(),
         };
-        // Only return Some if at least one option is set
-        if options.temperature.is_some() || options.num_predict.is_some() {
-            Some(options)
-        } else {
-            None
+        let JsonValue::Object(mut options) = serde_json::to_value(&options).unwrap_or_default() else {
+            return None;
+        };
+        if let Some(JsonValue::Object(overrides)) = request.extra.get("options") {
+            options.extend(overrides.clone());
+        }
+        (!options.is_empty()).then_some(options)
+    }
+
+    /// Extra body fields, without `options` (merged by `create_ollama_options`).
+    fn extra_fields(request: &CompletionRequest) -> serde_json::Map<String, JsonValue> {
+        let mut extra = request.extra.clone();
+        extra.remove("options");
+        extra
+    }
+
+    /// Maps the generic ResponseFormat to Ollama's `format` field.
+    fn map_response_format(format: Option<&ResponseFormat>) -> Option<JsonValue> {
+        match format? {
+            ResponseFormat::Text => None,
+            ResponseFormat::JsonObject => Some(JsonValue::String("json".to_string())),
+            ResponseFormat::JsonSchema { schema, .. } => Some(schema.clone()),
+        }
+    }
+
+    /// Maps developer messages, which Ollama has no role for, to system messages.
+    fn ollama_role(role: ChatMessageRole) -> ChatMessageRole {
+        match role {
+            ChatMessageRole::Developer => ChatMessageRole::System,
+            other => other,
         }
     }
 
     /// Formats tool definitions into a string suitable for inclusion in a system prompt.
-    fn format_tools_for_prompt(tools: &[Tool]) -> String {
-        let mut tool_desc = String::from("You have access to the following tools. Use them if necessary by outputting ONLY a JSON object with a single key 'tool_calls' containing a list of calls. Each call object in the list should have 'id' (a unique lowercase string), and 'function' containing 'name' (the tool name) and 'arguments' (a JSON object matching the tool's parameters schema). Do not output any other text, explanation, or markdown formatting around the JSON object.\n\nAvailable Tools:\n");
+    /// Ollama has no native tool choice, so `Required`/`Function` are expressed as instructions.
+    fn format_tools_for_prompt(tools: &[Tool], tool_choice: Option<&ToolChoice>) -> String {
+        let mut tool_desc = String::from("You have access to the following tools. Use them if necessary by outputting ONLY a JSON object with a single key 'tool_calls' containing a list of calls. Each call object in the list should have 'id' (a unique lowercase string), and 'function' containing 'name' (the tool name) and 'arguments' (a JSON object matching the tool's parameters schema). Do not output any other text, explanation, or markdown formatting around the JSON object.\n\n");
+        match tool_choice {
+            Some(ToolChoice::Required) => tool_desc.push_str("You MUST call at least one tool.\n\n"),
+            Some(ToolChoice::Function(name)) => tool_desc.push_str(&format!("You MUST call the '{}' tool.\n\n", name)),
+            _ => {}
+        }
+        tool_desc.push_str("Available Tools:\n");
         for tool in tools {
+            if let Some(ToolChoice::Function(name)) = tool_choice {
+                if &tool.name != name {
+                    continue;
+                }
+            }
             tool_desc.push_str(&format!("- Name: {}\n", tool.name));
             tool_desc.push_str(&format!("  Description: {}\n", tool.description));
             match serde_json::to_string_pretty(&tool.parameters) {
@@ -248,14 +500,14 @@ impl LlmProvider for OllamaProvider {
         let mut original_messages = request.messages.clone();
         let mut use_json_format = false;
 
-        // Modify prompt and set format if tools are present
+        // Modify prompt and set format if tools are present (and not disabled by tool_choice)
         if let Some(tools) = &request.tools {
-            if !tools.is_empty() {
+            if !tools.is_empty() && request.tool_choice != Some(ToolChoice::None) {
                 use_json_format = true;
-                let tool_prompt = Self::format_tools_for_prompt(tools);
+                let tool_prompt = Self::format_tools_for_prompt(tools, request.tool_choice.as_ref());
 
                 // Find or create a system prompt in the original messages
-                if let Some(system_message) = original_messages.iter_mut().find(|m| m.role == ChatMessageRole::System) {
+                if let Some(system_message) = original_messages.iter_mut().find(|m| m.role.is_instruction()) {
                     let existing_content = system_message.content.take().unwrap_or_default();
                     system_message.content = Some(format!("{}\n\n{}", existing_content, tool_prompt));
                 } else {
@@ -278,6 +530,7 @@ impl LlmProvider for OllamaProvider {
                 // Ollama API doesn't use tool_calls or tool_call_id in the request messages list.
                 // Keep content and role.
                 msg.tool_calls = None;
+                msg.role = Self::ollama_role(msg.role);
                 // While Ollama doesn't use tool_call_id either, keeping it doesn't seem to cause errors
                 // based on current Ollama API behavior, but we could clear it too if needed.
                 // msg.tool_call_id = None;
@@ -290,30 +543,18 @@ impl LlmProvider for OllamaProvider {
             model: request.model.clone(),
             messages: messages_for_ollama_request, // Use the sanitized messages
             stream: false,
-            format: if use_json_format { Some("json".to_string()) } else { None },
+            // Tool calls rely on plain JSON mode; otherwise honor the requested response format
+            format: if use_json_format {
+                Some(JsonValue::String("json".to_string()))
+            } else {
+                Self::map_response_format(request.response_format.as_ref())
+            },
             options: Self::create_ollama_options(&request),
+            think: request.wants_reasoning().then_some(true),
+            extra: Self::extra_fields(&request),
         };
 
-        let url = format!("{}/api/chat", self.base_url);
-        let headers = self.build_headers();
-
-        let res = self
-            .client
-            .post(&url)
-            .headers(headers)
-            .json(&ollama_request)
-            .send()
-            .await?;
-
-        if !res.status().is_success() {
-            let status = res.status().as_u16();
-            let error_body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
-            let message = serde_json::from_str::<HashMap<String, String>>(&error_body)
-                .ok()
-                .and_then(|json| json.get("error").cloned())
-                .unwrap_or(error_body);
-            return Err(ProviderError::ApiError { status, message });
-        }
+        let res = self.send_request(&ollama_request).await?;
 
         // Handle response based on whether JSON format was requested
         if use_json_format {
@@ -330,6 +571,7 @@ impl LlmProvider for OllamaProvider {
                             kind: CompletionKind::ToolCall { tool_calls: Self::map_ollama_tool_calls(tool_calls) },
                             usage,
                             finish_reason: if ollama_response.done { Some("tool_calls".to_string()) } else { None },
+                            logprobs: None,
                         })
                     } 
                     // If no top-level tool_calls, check if the *message content* contains it
@@ -343,6 +585,7 @@ impl LlmProvider for OllamaProvider {
                                          kind: CompletionKind::ToolCall { tool_calls: Self::map_ollama_tool_calls(tool_payload.tool_calls) },
                                          usage,
                                          finish_reason: if ollama_response.done { Some("tool_calls".to_string()) } else { None },
+                                         logprobs: None,
                                      })
                                  }
                                  Err(_) => {
@@ -351,6 +594,7 @@ impl LlmProvider for OllamaProvider {
                                          kind: CompletionKind::Message { content: content_str.clone() },
                                          usage,
                                          finish_reason: if ollama_response.done { Some("stop".to_string()) } else { None },
+                                         logprobs: None,
                                      })
                                  }
                              }
@@ -360,6 +604,7 @@ impl LlmProvider for OllamaProvider {
                                  kind: CompletionKind::Message { content: "".to_string() },
                                  usage,
                                  finish_reason: if ollama_response.done { Some("stop".to_string()) } else { None },
+                                 logprobs: None,
                              })
                         }
                     } else {
@@ -379,6 +624,7 @@ impl LlmProvider for OllamaProvider {
                                  kind: CompletionKind::ToolCall { tool_calls: Self::map_ollama_tool_calls(tool_payload.tool_calls) },
                                  usage,
                                  finish_reason: Some("tool_calls".to_string()), // Assume tool call finish
+                                 logprobs: None,
                              })
                         }
                         Err(e) => {
@@ -396,6 +642,7 @@ impl LlmProvider for OllamaProvider {
                 kind: CompletionKind::Message { content: ollama_response.message.content.unwrap_or_default() },
                 usage,
                 finish_reason: if ollama_response.done { Some("stop".to_string()) } else { None },
+                logprobs: None,
             })
         }
     }
@@ -420,35 +667,27 @@ impl LlmProvider for OllamaProvider {
 
         let ollama_request = OllamaChatRequest {
             model: request.model.clone(),
-            messages: request.messages.clone(), // Use original messages for streaming
+            messages: request
+                .messages
+                .iter()
+                .cloned()
+                .map(|mut msg| {
+                    msg.role = Self::ollama_role(msg.role);
+                    msg
+                })
+                .collect(),
             stream: true,
-            format: None, // Cannot use JSON format with streaming
+            // Schemas constrain streamed output too, so JSON tasks can be validated as they stream
+            format: Self::map_response_format(request.response_format.as_ref()),
             options: Self::create_ollama_options(&request),
+            think: request.wants_reasoning().then_some(true),
+            extra: Self::extra_fields(&request),
         };
 
-        let url = format!("{}/api/chat", self.base_url);
-        let headers = self.build_headers();
-
-        let res = self
-            .client
-            .post(&url)
-            .headers(headers)
-            .json(&ollama_request)
-            .send()
-            .await?;
-
-        if !res.status().is_success() {
-            let status = res.status().as_u16();
-            let error_body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
-            let message = serde_json::from_str::<HashMap<String, String>>(&error_body)
-                .ok()
-                .and_then(|json| json.get("error").cloned())
-                .unwrap_or(error_body);
-            return Err(ProviderError::ApiError { status, message });
-        }
+        let res = self.send_request(&ollama_request).await?;
 
         // Process the newline-delimited JSON stream
-        let byte_stream = res.bytes_stream().map_err(ProviderError::RequestError);
+        let byte_stream = res.body;
 
         let chunk_stream = byte_stream.try_filter_map(|chunk: Bytes| async move {
             // Need to handle potential partial JSON objects across chunks if lines are split
@@ -463,13 +702,19 @@ impl LlmProvider for OllamaProvider {
                 match serde_json::from_slice::<OllamaChatStreamResponse>(line) {
                     Ok(ollama_chunk) => {
                         let delta_content = ollama_chunk.message.content;
+                        let thinking = ollama_chunk.message.thinking.unwrap_or_default();
                         let usage = Self::calculate_usage(ollama_chunk.prompt_eval_count, ollama_chunk.eval_count);
                         let finish_reason = ollama_chunk.done_reason;
 
                          // Send a chunk if there's content or if it's the final chunk
-                         if !delta_content.is_empty() || ollama_chunk.done {
+                         if !delta_content.is_empty() || !thinking.is_empty() || ollama_chunk.done {
+                             let delta = if delta_content.is_empty() && !thinking.is_empty() {
+                                 StreamContentDelta::Reasoning(thinking)
+                             } else {
+                                 StreamContentDelta::Text(delta_content)
+                             };
                              result_chunk = Some(CompletionStreamChunk {
-                                 delta: StreamContentDelta::Text(delta_content),
+                                 delta,
                                  usage,
                                  finish_reason,
                              });
@@ -492,4 +737,164 @@ impl LlmProvider for OllamaProvider {
 
         Ok(Box::pin(chunk_stream))
     }
-} 
\ No newline at end of file
+
+    /// Loads `model` into memory with an empty generate request and keeps it resident for
+    /// `WARMUP_KEEP_ALIVE`, so the first chat request doesn't pay the model load time.
+    async fn warmup(&self, model: &str) -> Result<(), ProviderError> {
+        let url = format!("{}{}", self.base_url, GENERATE_PATH);
+        let mut headers = self.build_headers()?;
+        let body = serde_json::to_vec(&serde_json::json!({
+            "model": model,
+            "keep_alive": WARMUP_KEEP_ALIVE,
+            "stream": false,
+        }))?;
+        if let Some(signer) = &self.config.request_signer {
+            let signing_request = SigningRequest { method: "POST".to_string(), url: url.clone(), body: body.clone() };
+            signer.sign_into(signing_request, &mut headers).await?;
+        }
+
+        let res = self.transport.send(TransportRequest { method: "POST".to_string(), url, headers, body }).await?;
+        let status = res.status;
+        let success = res.is_success();
+        let body = res.text().await?;
+        if !success {
+            return Err(ProviderError::ApiError { status, message: body });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaProvider {
+    /// Embeds the inputs with `/api/embed`, in batches of `embedding_batch_size`.
+    async fn embed(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse, ProviderError> {
+        let mut response = EmbeddingResponse::default();
+        for batch in request.input.chunks(self.embedding_batch_size) {
+            let body = OllamaEmbedRequest { model: &request.model, input: batch, dimensions: request.dimensions };
+            let res = self.post(EMBED_PATH, &body).await?;
+            let parsed: OllamaEmbedResponse = serde_json::from_str(&res.text().await?)?;
+            if parsed.embeddings.len() != batch.len() {
+                return Err(ProviderError::Unexpected(format!(
+                    "Ollama returned {} embeddings for {} inputs",
+                    parsed.embeddings.len(),
+                    batch.len()
+                )));
+            }
+            let usage = parsed.prompt_eval_count.map(|tokens| TokenUsage {
+                prompt_tokens: tokens,
+                completion_tokens: 0,
+                total_tokens: tokens,
+            });
+            response.usage = sum_usage(response.usage, usage);
+            response.embeddings.extend(parsed.embeddings);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn test_ollama_options_override_generic_fields() {
+        let mut request = CompletionRequest { max_tokens: Some(100), temperature: Some(0.2), ..Default::default() };
+        OllamaOptions::new()
+            .with_num_ctx(8192)
+            .with_num_predict(-1)
+            .with_mirostat(2, 5.0, 0.1)
+            .with_keep_alive("10m")
+            .apply(&mut request);
+
+        let options = OllamaProvider::create_ollama_options(&request).unwrap();
+        assert_eq!(options["num_ctx"], 8192);
+        assert_eq!(options["num_predict"], -1);
+        assert_eq!(options["mirostat"], 2);
+        assert!((options["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+
+        let extra = OllamaProvider::extra_fields(&request);
+        assert_eq!(extra["keep_alive"], "10m");
+        assert!(!extra.contains_key("options"));
+    }
+
+    /// Answers every request with embeddings of the number of inputs it received.
+    #[derive(Debug, Default)]
+    struct EmbedTransport {
+        batches: std::sync::Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl Transport for EmbedTransport {
+        async fn send(&self, request: TransportRequest) -> Result<TransportResponse, ProviderError> {
+            assert!(request.url.ends_with(EMBED_PATH));
+            let body: JsonValue = serde_json::from_slice(&request.body).unwrap();
+            let count = body["input"].as_array().unwrap().len();
+            self.batches.lock().unwrap().push(count);
+            let reply = serde_json::json!({
+                "embeddings": vec![vec![0.5, 0.5]; count],
+                "prompt_eval_count": count * 3,
+            });
+            let bytes = Bytes::from(reply.to_string());
+            Ok(TransportResponse {
+                status: 200,
+                headers: HeaderMap::new(),
+                body: Box::pin(futures::stream::once(async move { Ok(bytes) })),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embed_splits_batches() {
+        let transport = Arc::new(EmbedTransport::default());
+        let provider = OllamaProvider::new(LlmConfig::new(Provider::Ollama).with_transport(transport.clone()))
+            .with_embedding_batch_size(2);
+        let request = EmbeddingRequest::new("nomic-embed-text", ["a", "b", "c", "d", "e"]);
+
+        let response = provider.embed(request).await.unwrap();
+        assert_eq!(response.embeddings.len(), 5);
+        assert_eq!(response.usage.unwrap().prompt_tokens, 15);
+        assert_eq!(*transport.batches.lock().unwrap(), vec![2, 2, 1]);
+    }
+
+    /// Records request bodies and replies with one final NDJSON chat chunk.
+    #[derive(Debug, Default)]
+    struct RecordingTransport {
+        bodies: std::sync::Mutex<Vec<JsonValue>>,
+    }
+
+    #[async_trait]
+    impl Transport for RecordingTransport {
+        async fn send(&self, request: TransportRequest) -> Result<TransportResponse, ProviderError> {
+            self.bodies.lock().unwrap().push(serde_json::from_slice(&request.body).unwrap());
+            let line = r#"{"model":"m","created_at":"2024-01-01T00:00:00Z","message":{"role":"assistant","content":"{\"name\":\"Ada\"}"},"done":true}"#;
+            let bytes = Bytes::from(format!("{}\n", line));
+            Ok(TransportResponse {
+                status: 200,
+                headers: HeaderMap::new(),
+                body: Box::pin(futures::stream::once(async move { Ok(bytes) })),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_schema_is_sent_as_format() {
+        let transport = Arc::new(RecordingTransport::default());
+        let provider = OllamaProvider::new(LlmConfig::new(Provider::Ollama).with_transport(transport.clone()));
+        let schema = serde_json::json!({ "type": "object", "properties": { "name": { "type": "string" } } });
+        let request = CompletionRequest {
+            model: "m".to_string(),
+            messages: vec![ChatMessage::user("Who wrote the first program?")],
+            response_format: Some(ResponseFormat::JsonSchema { name: "person".to_string(), schema: schema.clone(), strict: None }),
+            ..Default::default()
+        };
+
+        provider.completion(request.clone()).await.unwrap();
+        let chunks: Vec<_> = provider.completion_stream(request).await.unwrap().collect().await;
+        assert!(chunks.iter().all(Result::is_ok));
+        let bodies = transport.bodies.lock().unwrap();
+        assert_eq!(bodies[0]["format"], schema);
+        assert_eq!(bodies[1]["format"], schema);
+        assert_eq!(bodies[1]["stream"], true);
+    }
+}
@@ -6,16 +6,19 @@
 //! (including OpenAI itself and proxies like OpenRouter).
 
 use crate::config::{LlmConfig, Provider, APP_SITE_NAME, APP_SITE_URL};
+use crate::key_pool::{rotation_cooldown, ApiKeyPool, KeyUsage};
+use crate::signing::SigningRequest;
+use crate::transport::{Transport, TransportRequest, TransportResponse};
 use crate::traits::{
     ChatMessage, CompletionKind, CompletionRequest, CompletionResponse, CompletionStream,
-    CompletionStreamChunk, JsonSchema, LlmProvider, ProviderError, StreamContentDelta, Tool,
-    ToolCallFunction, ToolCallFunctionStreamDelta, ToolCallRequest, ToolCallStreamDelta, TokenUsage,
+    CompletionStreamChunk, JsonSchema, LlmProvider, ProviderError, ReasoningEffort, ResponseFormat, StreamContentDelta, Tool,
+    ToolCallFunction, ToolCallFunctionStreamDelta, ToolCallRequest, ToolCallStreamDelta, ToolChoice, TokenLogprob,
+    TokenUsage,
 };
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures::stream::TryStreamExt; // Keep TryStreamExt for stream processing
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Client;
+use http::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
 use serde::{Deserialize, Serialize};
 use serde_json::{self, json, Value as JsonValue};
 use std::collections::HashMap;
@@ -25,8 +28,10 @@ use serde::de::Error as DeError;
 
 /// Base URL for the official OpenAI API.
 const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
-/// Default request timeout in seconds.
-const DEFAULT_TIMEOUT_SECS: u64 = 120;
+/// Default chat completions endpoint path.
+const CHAT_COMPLETIONS_PATH: &str = "/chat/completions";
+/// Model listing endpoint path, used to warm up connections.
+const MODELS_PATH: &str = "/models";
 
 // --- OpenAI Specific API Structures ---
 
@@ -52,11 +57,38 @@ struct OpenAIChatRequest {
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAIStreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<OpenAITool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_choice: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<ReasoningEffort>,
+    #[serde(flatten)]
+    extra: serde_json::Map<String, JsonValue>,
+}
+
+#[derive(Serialize, Debug)]
+struct OpenAIStreamOptions {
+    include_usage: bool, // Adds a final chunk with empty `choices` and the request's usage
 }
 
 #[derive(Deserialize, Debug)]
@@ -73,6 +105,12 @@ struct OpenAIChoice {
     // index: u32, // Often unused
     message: OpenAIMessage,
     finish_reason: Option<String>,
+    logprobs: Option<OpenAILogprobs>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAILogprobs {
+    content: Option<Vec<TokenLogprob>>, // Same shape as the generic structure
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -127,6 +165,9 @@ struct OpenAIStreamChoice {
 struct OpenAIStreamDelta {
     // role: Option<String>, // Often unused
     content: Option<String>,
+    // Reasoning text from compatible servers (DeepSeek, vLLM, OpenRouter); OpenAI keeps it hidden
+    #[serde(alias = "reasoning")]
+    reasoning_content: Option<String>,
     tool_calls: Option<Vec<OpenAIStreamToolCallDelta>>,
 }
 
@@ -167,41 +208,42 @@ struct OpenAIErrorDetail {
 #[derive(Debug, Clone)]
 pub struct OpenAIProvider {
     config: LlmConfig,
-    client: Client,
-    api_key: String,
+    transport: Arc<dyn Transport>,
+    keys: Arc<ApiKeyPool>,
     base_url: String,
 }
 
 impl OpenAIProvider {
     /// Creates a new OpenAI provider instance from the given configuration.
-    /// Panics if the configuration is missing the required API key or if the HTTP client fails to build.
+    /// Panics if the configuration is missing the required API key or if the default HTTP client fails to build.
     pub fn new(config: LlmConfig) -> Self {
-        let api_key = config
-            .api_key
-            .clone()
-            .expect("OpenAI provider requires an API key");
+        let keys = ApiKeyPool::new(config.all_api_keys());
+        assert!(!keys.is_empty(), "OpenAI provider requires an API key");
 
         let base_url = config
             .base_url
             .clone()
             .unwrap_or_else(|| OPENAI_BASE_URL.to_string());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
-            .build()
-            .expect("Failed to build Reqwest client");
+        let transport = config.resolve_transport();
 
-        Self { config, client, api_key, base_url }
+        Self { config, transport, keys: Arc::new(keys), base_url }
+    }
+
+    /// Returns usage statistics for each configured API key.
+    pub fn key_usage(&self) -> Vec<KeyUsage> {
+        self.keys.usage()
     }
 
     /// Builds the necessary HTTP headers for OpenAI API calls.
-    /// Adds OpenRouter-specific headers if the base URL contains "openrouter".
-    fn build_headers(&self) -> HeaderMap {
+    /// Adds OpenRouter-specific headers if the base URL contains "openrouter", then the
+    /// custom headers from the config, which take precedence.
+    fn build_headers(&self, api_key: &str) -> Result<HeaderMap, ProviderError> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         headers.insert(
             AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", self.api_key))
+            HeaderValue::from_str(&format!("Bearer {}", api_key))
                 .expect("Failed to create auth header"),
         );
 
@@ -217,7 +259,67 @@ impl OpenAIProvider {
             );
         }
 
-        headers
+        self.config
+            .apply_headers(&mut headers)
+            .map_err(|e| ProviderError::ConfigError(e.to_string()))?;
+        Ok(headers)
+    }
+
+    /// Sends a chat request, rotating to the next API key when a key is rate limited,
+    /// out of quota, or rejected. Other non-success responses are returned as `ApiError`.
+    async fn send_request(&self, body: &OpenAIChatRequest) -> Result<TransportResponse, ProviderError> {
+        let path = self.config.endpoint_path.as_deref().unwrap_or(CHAT_COMPLETIONS_PATH);
+        let url = format!("{}{}", self.base_url, path);
+        let mut last_error = None;
+
+        for _ in 0..self.keys.len() {
+            let Some((key_idx, api_key)) = self.keys.next_key() else {
+                break;
+            };
+
+            let mut headers = self.build_headers(&api_key)?;
+            let body_bytes = serde_json::to_vec(body)?;
+            if let Some(signer) = &self.config.request_signer {
+                let signing_request = SigningRequest { method: "POST".to_string(), url: url.clone(), body: body_bytes.clone() };
+                signer.sign_into(signing_request, &mut headers).await?;
+            }
+
+            let res = self
+                .transport
+                .send(TransportRequest { method: "POST".to_string(), url: url.clone(), headers, body: body_bytes })
+                .await?;
+            if res.is_success() {
+                return Ok(res);
+            }
+
+            let status = res.status;
+            let retry_after = res
+                .headers
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let error_body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
+            let cooldown = rotation_cooldown(status, &error_body, retry_after);
+            // Try to parse OpenAI specific error
+            let message = serde_json::from_str::<OpenAIErrorResponse>(&error_body)
+                .map(|e| e.error.message)
+                .unwrap_or(error_body); // Fallback to full body
+            let error = ProviderError::ApiError { status, message };
+
+            match cooldown {
+                Some(cooldown) => {
+                    self.keys.report_failure(key_idx, cooldown);
+                    last_error = Some(error);
+                }
+                None => return Err(error),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| ProviderError::ApiError {
+            status: 429,
+            message: "All configured API keys are cooling down".to_string(),
+        }))
     }
 
     /// Maps the generic Tool structure to the OpenAI-specific format.
@@ -236,6 +338,34 @@ impl OpenAIProvider {
         })
     }
 
+    /// Maps the generic ToolChoice to OpenAI's `tool_choice` value.
+    /// Defaults to "auto" when tools are present and no choice was specified.
+    fn map_tool_choice(request: &CompletionRequest) -> Option<JsonValue> {
+        request.tools.as_ref()?;
+        Some(match request.tool_choice.as_ref().unwrap_or(&ToolChoice::Auto) {
+            ToolChoice::Auto => json!("auto"),
+            ToolChoice::None => json!("none"),
+            ToolChoice::Required => json!("required"),
+            ToolChoice::Function(name) => json!({ "type": "function", "function": { "name": name } }),
+        })
+    }
+
+    /// Maps the generic ResponseFormat to OpenAI's `response_format` object.
+    fn map_response_format(format: Option<&ResponseFormat>) -> Option<JsonValue> {
+        format.map(|f| match f {
+            ResponseFormat::Text => json!({ "type": "text" }),
+            ResponseFormat::JsonObject => json!({ "type": "json_object" }),
+            ResponseFormat::JsonSchema { name, schema, strict } => json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": name,
+                    "schema": schema,
+                    "strict": strict.unwrap_or(false),
+                }
+            }),
+        })
+    }
+
     /// Maps the OpenAI usage structure to the generic TokenUsage structure.
     fn map_usage(usage: Option<OpenAIUsage>) -> Option<TokenUsage> {
          usage.map(|u| TokenUsage {
@@ -303,35 +433,33 @@ impl LlmProvider for OpenAIProvider {
             messages: request.messages.clone(),
             temperature: request.temperature,
             max_tokens: request.max_tokens,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            seed: request.seed,
+            stop: request.stop.clone(),
+            logprobs: request.logprobs,
+            top_logprobs: request.top_logprobs,
             stream: false,
+            stream_options: None,
             tools: Self::map_tools_to_openai(request.tools.as_ref()),
-            // Default to auto tool choice if tools are present, allows user override later
-            tool_choice: request.tools.as_ref().map(|_| json!("auto")), 
+            tool_choice: Self::map_tool_choice(&request),
+            response_format: Self::map_response_format(request.response_format.as_ref()),
+            reasoning_effort: request.reasoning_effort,
+            extra: request.extra.clone(),
         };
 
-        let url = format!("{}/chat/completions", self.base_url);
-        let headers = self.build_headers();
-
-        let res = self.client.post(&url).headers(headers).json(&openai_request).send().await?;
-
-        if !res.status().is_success() {
-            let status = res.status().as_u16();
-            let error_body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
-            // Try to parse OpenAI specific error
-             let message = serde_json::from_str::<OpenAIErrorResponse>(&error_body)
-                 .map(|e| e.error.message)
-                 .unwrap_or(error_body); // Fallback to full body
-            return Err(ProviderError::ApiError { status, message });
-        }
+        let res = self.send_request(&openai_request).await?;
 
         let openai_response: OpenAIChatResponse = res.json().await?;
 
         let first_choice = openai_response.choices.into_iter().next()
-            .ok_or_else(|| ProviderError::ParseError(serde_json::Error::custom("No choices found in OpenAI response")))?;
+            .ok_or(ProviderError::ParseError(serde_json::Error::custom("No choices found in OpenAI response")))?;
 
         let usage = Self::map_usage(openai_response.usage);
         // Extract finish_reason before moving message into the helper
         let finish_reason = first_choice.finish_reason.clone(); 
+        let logprobs = first_choice.logprobs.and_then(|l| l.content);
 
         // Use the helper function to determine the kind (pass only message)
         let kind = Self::determine_completion_kind(first_choice.message, finish_reason.as_deref()); 
@@ -340,6 +468,7 @@ impl LlmProvider for OpenAIProvider {
             kind,
             usage,
             finish_reason, // Use the extracted finish_reason
+            logprobs,
         })
     }
 
@@ -368,26 +497,26 @@ impl LlmProvider for OpenAIProvider {
             messages: request.messages.clone(),
             temperature: request.temperature,
             max_tokens: request.max_tokens,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            seed: request.seed,
+            stop: request.stop.clone(),
+            logprobs: request.logprobs,
+            top_logprobs: request.top_logprobs,
             stream: true,
+            // Without this, streamed responses carry no token usage
+            stream_options: Some(OpenAIStreamOptions { include_usage: true }),
             tools: None, // Ensure tools are None for stream request
             tool_choice: None, // Ensure tool_choice is None for stream request
+            response_format: Self::map_response_format(request.response_format.as_ref()),
+            reasoning_effort: request.reasoning_effort,
+            extra: request.extra.clone(),
         };
 
-        let url = format!("{}/chat/completions", self.base_url);
-        let headers = self.build_headers();
-
-        let res = self.client.post(&url).headers(headers).json(&openai_request).send().await?;
+        let res = self.send_request(&openai_request).await?;
 
-        if !res.status().is_success() {
-            let status = res.status().as_u16();
-            let error_body = res.text().await.unwrap_or_else(|_| "Failed to read error body".to_string());
-             let message = serde_json::from_str::<OpenAIErrorResponse>(&error_body)
-                 .map(|e| e.error.message)
-                 .unwrap_or(error_body); 
-            return Err(ProviderError::ApiError { status, message });
-        }
-
-        let sse_stream = res.bytes_stream().map_err(ProviderError::RequestError);
+        let sse_stream = res.body;
 
         // State for aggregating tool calls, wrapped for async stream handling
         let tool_call_aggregator = Arc::new(Mutex::new(HashMap::<usize, ToolCallStreamDelta>::new()));
@@ -424,7 +553,13 @@ impl LlmProvider for OpenAIProvider {
                                         ProviderError::Unexpected("Mutex poisoned in stream processing".to_string())
                                     })?;
 
-                                    if let Some(text_delta) = choice.delta.content {
+                                    if let Some(reasoning) = choice.delta.reasoning_content.filter(|r| !r.is_empty()) {
+                                        result_chunk = Some(CompletionStreamChunk {
+                                            delta: StreamContentDelta::Reasoning(reasoning),
+                                            usage: None,
+                                            finish_reason: None,
+                                        });
+                                    } else if let Some(text_delta) = choice.delta.content {
                                         if !text_delta.is_empty() {
                                             result_chunk = Some(CompletionStreamChunk {
                                                 delta: StreamContentDelta::Text(text_delta),
@@ -445,9 +580,9 @@ impl LlmProvider for OpenAIProvider {
                                             // Aggregate parts into the entry in the shared state
                                             if let Some(id) = tool_delta.id { entry.id = Some(id); }
                                             if let Some(func_delta) = tool_delta.function {
-                                                let func_entry = entry.function.get_or_insert_with(|| {
+                                                let func_entry = entry.function.get_or_insert(
                                                     ToolCallFunctionStreamDelta { name: None, arguments: None }
-                                                });
+                                                );
                                                 if let Some(name) = func_delta.name { func_entry.name = Some(name); }
                                                 if let Some(args_chunk) = func_delta.arguments {
                                                      // DEBUG prints removed
@@ -479,8 +614,12 @@ impl LlmProvider for OpenAIProvider {
                     }
                 }
                 
-                // If no data chunk was generated, but we got final usage/reason, create a final chunk
-                if result_chunk.is_none() && (final_reason.is_some() || final_usage.is_some()) {
+                // Attach final usage/reason to the data chunk, or create a final chunk for them.
+                // With `include_usage`, usage comes in its own event with no choices.
+                if let Some(chunk) = result_chunk.as_mut() {
+                    chunk.usage = Self::map_usage(final_usage);
+                    chunk.finish_reason = final_reason;
+                } else if final_reason.is_some() || final_usage.is_some() {
                      result_chunk = Some(CompletionStreamChunk {
                          delta: StreamContentDelta::Text("".to_string()), // Empty delta for final info
                          usage: Self::map_usage(final_usage),
@@ -494,4 +633,165 @@ impl LlmProvider for OpenAIProvider {
 
         Ok(Box::pin(chunk_stream))
     }
-} 
\ No newline at end of file
+
+    /// Opens a pooled connection (TLS handshake included) by listing models. Only auth
+    /// failures are reported; endpoints without `/models` still warm the connection.
+    async fn warmup(&self, _model: &str) -> Result<(), ProviderError> {
+        let Some((_, api_key)) = self.keys.next_key() else {
+            return Err(ProviderError::MissingConfig("API key".to_string()));
+        };
+        let url = format!("{}{}", self.base_url, MODELS_PATH);
+        let mut headers = self.build_headers(&api_key)?;
+        if let Some(signer) = &self.config.request_signer {
+            let signing_request = SigningRequest { method: "GET".to_string(), url: url.clone(), body: Vec::new() };
+            signer.sign_into(signing_request, &mut headers).await?;
+        }
+
+        let res = self
+            .transport
+            .send(TransportRequest { method: "GET".to_string(), url, headers, body: Vec::new() })
+            .await?;
+        let status = res.status;
+        // Read the body so the connection returns to the pool
+        let body = res.text().await?;
+        if status == 401 || status == 403 {
+            let message = serde_json::from_str::<OpenAIErrorResponse>(&body).map(|e| e.error.message).unwrap_or(body);
+            return Err(ProviderError::ApiError { status, message });
+        }
+        Ok(())
+    }
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::StreamExt;
+    use serde_json::json;
+
+    /// Records request bodies and replies with the given network chunks.
+    #[derive(Debug)]
+    struct SseTransport {
+        chunks: Vec<&'static str>,
+        bodies: Mutex<Vec<JsonValue>>,
+    }
+
+    #[async_trait]
+    impl Transport for SseTransport {
+        async fn send(&self, request: TransportRequest) -> Result<TransportResponse, ProviderError> {
+            self.bodies.lock().unwrap().push(serde_json::from_slice(&request.body).unwrap());
+            let chunks: Vec<Result<Bytes, ProviderError>> =
+                self.chunks.iter().map(|chunk| Ok(Bytes::from_static(chunk.as_bytes()))).collect();
+            Ok(TransportResponse {
+                status: 200,
+                headers: HeaderMap::new(),
+                body: Box::pin(futures::stream::iter(chunks)),
+            })
+        }
+    }
+
+    async fn stream_chunks(chunks: Vec<&'static str>) -> (Vec<CompletionStreamChunk>, JsonValue) {
+        let transport = Arc::new(SseTransport { chunks, bodies: Mutex::new(Vec::new()) });
+        let config = LlmConfig::new(Provider::OpenAI)
+            .with_api_key("sk-test".to_string())
+            .with_transport(transport.clone());
+        let request = CompletionRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![ChatMessage::user("Hi")],
+            ..Default::default()
+        };
+        let stream = OpenAIProvider::new(config).completion_stream(request).await.unwrap();
+        let chunks = stream.map(Result::unwrap).collect().await;
+        let body = transport.bodies.lock().unwrap().remove(0);
+        (chunks, body)
+    }
+
+    async fn complete(request: CompletionRequest, reply: &'static str) -> (CompletionResponse, JsonValue) {
+        let transport = Arc::new(SseTransport { chunks: vec![reply], bodies: Mutex::new(Vec::new()) });
+        let config = LlmConfig::new(Provider::OpenAI)
+            .with_api_key("sk-test".to_string())
+            .with_transport(transport.clone());
+        let response = OpenAIProvider::new(config).completion(request).await.unwrap();
+        let body = transport.bodies.lock().unwrap().remove(0);
+        (response, body)
+    }
+
+    const REPLY: &str = "{\"choices\":[{\"message\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}";
+
+    #[tokio::test]
+    async fn test_completion_sends_sampling_parameters() {
+        let request = CompletionRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![ChatMessage::user("Hi")],
+            top_p: Some(0.5),
+            frequency_penalty: Some(0.25),
+            presence_penalty: Some(-0.5),
+            seed: Some(42),
+            stop: Some(vec!["END".to_string()]),
+            ..Default::default()
+        }
+        .with_extra("service_tier", json!("flex"));
+        let (response, body) = complete(request, REPLY).await;
+
+        assert!(matches!(&response.kind, CompletionKind::Message { content } if content == "Hi"));
+        assert_eq!(body["top_p"], 0.5);
+        assert_eq!(body["frequency_penalty"], 0.25);
+        assert_eq!(body["presence_penalty"], -0.5);
+        assert_eq!(body["seed"], 42);
+        assert_eq!(body["stop"], json!(["END"]));
+        assert_eq!(body["service_tier"], "flex");
+        assert!(body.get("extra").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_completion_returns_logprobs() {
+        let request = CompletionRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![ChatMessage::user("Hi")],
+            logprobs: Some(true),
+            top_logprobs: Some(2),
+            ..Default::default()
+        };
+        let reply = "{\"choices\":[{\"message\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\",\
+            \"logprobs\":{\"content\":[{\"token\":\"Hi\",\"logprob\":-0.25,\"bytes\":[72,105],\
+            \"top_logprobs\":[{\"token\":\"Hi\",\"logprob\":-0.25},{\"token\":\"Hey\",\"logprob\":-1.5}]}]}}]}";
+        let (response, body) = complete(request, reply).await;
+
+        assert_eq!(body["logprobs"], true);
+        assert_eq!(body["top_logprobs"], 2);
+        let logprobs = response.logprobs.unwrap();
+        assert_eq!(logprobs.len(), 1);
+        assert_eq!(logprobs[0].token, "Hi");
+        assert_eq!(logprobs[0].logprob, -0.25);
+        assert_eq!(logprobs[0].bytes.as_deref(), Some(&b"Hi"[..]));
+        assert_eq!(logprobs[0].top_logprobs[1].token, "Hey");
+    }
+
+    #[tokio::test]
+    async fn test_stream_requests_and_surfaces_usage() {
+        let (chunks, body) = stream_chunks(vec![
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"},\"finish_reason\":null}],\"usage\":null}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}],\"usage\":null}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":2,\"total_tokens\":9}}\n\ndata: [DONE]\n\n",
+        ])
+        .await;
+
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        assert!(matches!(&chunks[0].delta, StreamContentDelta::Text(t) if t == "Hello"));
+        assert_eq!(chunks[1].finish_reason.as_deref(), Some("stop"));
+        let last = chunks.last().unwrap();
+        assert!(matches!(&last.delta, StreamContentDelta::Text(t) if t.is_empty()));
+        assert_eq!(last.usage.map(|u| u.total_tokens), Some(9));
+    }
+
+    #[tokio::test]
+    async fn test_stream_separates_reasoning() {
+        let (chunks, _) = stream_chunks(vec![
+            "data: {\"choices\":[{\"delta\":{\"reasoning_content\":\"Think\"},\"finish_reason\":null}]}\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Answer\"},\"finish_reason\":null}]}\n",
+        ])
+        .await;
+
+        assert!(matches!(&chunks[0].delta, StreamContentDelta::Reasoning(r) if r == "Think"));
+        assert!(matches!(&chunks[1].delta, StreamContentDelta::Text(t) if t == "Answer"));
+    }
+}
@@ -0,0 +1,235 @@
+//!
+//! Client-Side Rate Limiting
+//!
+//! Provides `RateLimitedProvider`, an `LlmProvider` wrapper that enforces
+//! requests-per-minute and tokens-per-minute budgets using token buckets. Requests
+//! wait until enough capacity is available instead of being sent and rejected with
+//! a 429, so crews with many agents sharing one key don't trigger 429-storms.
+
+use crate::clock::{default_clock, Clock, TokioClock};
+use crate::traits::{
+    ChatMessage, CompletionRequest, CompletionResponse, CompletionStream, LlmProvider, ProviderError,
+};
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Rough number of characters per token used to estimate prompt size before sending.
+const CHARS_PER_TOKEN: usize = 4;
+/// Completion size assumed when a request doesn't set `max_tokens`.
+const DEFAULT_COMPLETION_ESTIMATE: u32 = 256;
+
+/// A token bucket refilled continuously at a fixed rate per minute.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket holding `per_minute` units, refilled at `per_minute` units per minute.
+    pub fn new(per_minute: u32) -> Self {
+        let capacity = f64::from(per_minute.max(1));
+        Self { capacity, available: capacity, refill_per_sec: capacity / 60.0, last_refill: TokioClock.now() }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// Returns how long to wait until `amount` units are available (zero if available now).
+    /// Amounts larger than the capacity are capped so they can eventually proceed.
+    pub fn wait_time(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let amount = amount.min(self.capacity);
+        if self.available >= amount {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount - self.available) / self.refill_per_sec)
+        }
+    }
+
+    /// Removes `amount` units. The balance may go negative to record debt from underestimates.
+    pub fn consume(&mut self, amount: f64) {
+        self.available -= amount.min(self.capacity);
+    }
+
+    /// Adjusts the balance after the actual cost of a request is known.
+    pub fn reconcile(&mut self, estimated: f64, actual: f64) {
+        self.available = (self.available + estimated.min(self.capacity) - actual).min(self.capacity);
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+/// Wraps a provider and enforces requests-per-minute and tokens-per-minute limits.
+///
+/// Token usage is estimated from the prompt length and `max_tokens` before sending, and
+/// corrected with the usage reported by the provider once the response arrives.
+pub struct RateLimitedProvider {
+    inner: Arc<dyn LlmProvider>,
+    buckets: Arc<Mutex<Buckets>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimitedProvider {
+    /// Creates a rate-limited wrapper. `None` disables the corresponding limit.
+    pub fn new(inner: Arc<dyn LlmProvider>, requests_per_minute: Option<u32>, tokens_per_minute: Option<u32>) -> Self {
+        let buckets = Buckets {
+            requests: requests_per_minute.map(TokenBucket::new),
+            tokens: tokens_per_minute.map(TokenBucket::new),
+        };
+        Self { inner, buckets: Arc::new(Mutex::new(buckets)), clock: default_clock() }
+    }
+
+    /// Measures refills and waits on `clock` instead of tokio's clock (builder style).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        if let Ok(mut guard) = self.buckets.lock() {
+            let buckets = &mut *guard;
+            for bucket in [buckets.requests.as_mut(), buckets.tokens.as_mut()].into_iter().flatten() {
+                bucket.last_refill = now;
+            }
+        }
+        self.clock = clock;
+        self
+    }
+
+    /// Estimates the total tokens a request will use.
+    fn estimate_tokens(request: &CompletionRequest) -> u32 {
+        let prompt_chars: usize = request.messages.iter().map(Self::message_chars).sum();
+        let prompt_tokens = (prompt_chars / CHARS_PER_TOKEN) as u32;
+        prompt_tokens + request.max_tokens.unwrap_or(DEFAULT_COMPLETION_ESTIMATE)
+    }
+
+    fn message_chars(message: &ChatMessage) -> usize {
+        let content = message.content.as_ref().map_or(0, |c| c.len());
+        let tool_calls = message
+            .tool_calls
+            .as_ref()
+            .map_or(0, |calls| calls.iter().map(|c| c.function.name.len() + c.function.arguments.len()).sum());
+        content + tool_calls
+    }
+
+    /// Waits until both buckets have capacity, then consumes it.
+    async fn acquire(&self, estimated_tokens: f64) -> Result<(), ProviderError> {
+        loop {
+            let wait = {
+                let mut buckets = self
+                    .buckets
+                    .lock()
+                    .map_err(|_| ProviderError::Unexpected("Rate limiter mutex poisoned".to_string()))?;
+                let now = self.clock.now();
+                let request_wait = buckets.requests.as_mut().map_or(Duration::ZERO, |b| b.wait_time(1.0, now));
+                let token_wait = buckets.tokens.as_mut().map_or(Duration::ZERO, |b| b.wait_time(estimated_tokens, now));
+                let wait = request_wait.max(token_wait);
+
+                if wait.is_zero() {
+                    if let Some(bucket) = buckets.requests.as_mut() {
+                        bucket.consume(1.0);
+                    }
+                    if let Some(bucket) = buckets.tokens.as_mut() {
+                        bucket.consume(estimated_tokens);
+                    }
+                    return Ok(());
+                }
+                wait
+            };
+            self.clock.sleep(wait).await;
+        }
+    }
+
+    fn reconcile(buckets: &Mutex<Buckets>, estimated_tokens: f64, actual_tokens: u32) {
+        if let Ok(mut buckets) = buckets.lock() {
+            if let Some(bucket) = buckets.tokens.as_mut() {
+                bucket.reconcile(estimated_tokens, f64::from(actual_tokens));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for RateLimitedProvider {
+    async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        let estimated = f64::from(Self::estimate_tokens(&request));
+        self.acquire(estimated).await?;
+
+        let response = self.inner.completion(request).await?;
+        if let Some(usage) = &response.usage {
+            Self::reconcile(&self.buckets, estimated, usage.total_tokens);
+        }
+        Ok(response)
+    }
+
+    async fn completion_stream(&self, request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        let estimated = f64::from(Self::estimate_tokens(&request));
+        self.acquire(estimated).await?;
+
+        let stream = self.inner.completion_stream(request).await?;
+        let buckets = Arc::clone(&self.buckets);
+        // Correct the estimate when the final usage chunk arrives
+        let stream = stream.inspect(move |chunk| {
+            if let Ok(chunk) = chunk {
+                if let Some(usage) = &chunk.usage {
+                    Self::reconcile(&buckets, estimated, usage.total_tokens);
+                }
+            }
+        });
+        Ok(Box::pin(stream))
+    }
+    /// Warmup requests generate no tokens, so they are not counted against the limits.
+    async fn warmup(&self, model: &str) -> Result<(), ProviderError> {
+        self.inner.warmup(model).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::providers::MockProvider;
+
+    #[test]
+    fn test_token_bucket_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(60); // one unit per second
+        bucket.last_refill = start;
+
+        assert_eq!(bucket.wait_time(60.0, start), Duration::ZERO);
+        bucket.consume(60.0);
+        assert_eq!(bucket.wait_time(1.0, start), Duration::from_secs(1));
+
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.wait_time(10.0, later), Duration::ZERO);
+        assert_eq!(bucket.wait_time(15.0, later), Duration::from_secs(5));
+
+        // Underestimates become debt that delays later requests
+        bucket.reconcile(10.0, 20.0);
+        assert_eq!(bucket.wait_time(1.0, later), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_waits_on_injected_clock() {
+        let clock = Arc::new(ManualClock::new());
+        let mock = MockProvider::new().with_message("one").with_message("two").with_message("three");
+        let provider = RateLimitedProvider::new(Arc::new(mock), Some(1), None).with_clock(clock.clone());
+        let request = || CompletionRequest::new(vec![ChatMessage::user("hi".to_string())], "m".to_string(), None, None, None);
+
+        for _ in 0..3 {
+            provider.completion(request()).await.unwrap();
+        }
+
+        // The first request uses the initial capacity, the others wait a minute each
+        let waited: Duration = clock.sleeps().iter().sum();
+        assert!(waited.abs_diff(Duration::from_secs(120)) < Duration::from_millis(1), "waited {:?}", waited);
+    }
+}
@@ -0,0 +1,140 @@
+//!
+//! Request Signing
+//!
+//! Provides `RequestSigner`, a pluggable asynchronous hook invoked right before a
+//! request is dispatched. It receives the HTTP method, URL, and the exact body bytes
+//! that will be sent, and returns extra headers (e.g. an HMAC signature and timestamp)
+//! to attach. This lets organizations with signed internal LLM gateways integrate
+//! without writing a custom transport.
+
+use crate::traits::ProviderError;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// The request details passed to a `RequestSigner`.
+#[derive(Debug, Clone)]
+pub struct SigningRequest {
+    /// The HTTP method (e.g. `POST`).
+    pub method: String,
+    /// The full request URL.
+    pub url: String,
+    /// The exact body bytes that will be sent.
+    pub body: Vec<u8>,
+}
+
+/// The future returned by a signing hook: headers to add, or an error message.
+pub type SigningFuture = Pin<Box<dyn Future<Output = Result<Vec<(String, String)>, String>> + Send>>;
+
+/// An asynchronous hook that computes extra headers for each outgoing request.
+///
+/// # Examples
+///
+/// ```
+/// use merco_llmproxy::{LlmConfig, Provider};
+/// use merco_llmproxy::signing::RequestSigner;
+///
+/// let signer = RequestSigner::new(|req| async move {
+///     // Compute a signature over the method, URL, and body here.
+///     let signature = format!("{}:{}:{}", req.method, req.url, req.body.len());
+///     Ok(vec![("X-Signature".to_string(), signature)])
+/// });
+///
+/// let config = LlmConfig::new(Provider::Ollama).with_request_signer(signer);
+/// ```
+#[derive(Clone)]
+pub struct RequestSigner(Arc<dyn Fn(SigningRequest) -> SigningFuture + Send + Sync>);
+
+impl RequestSigner {
+    /// Creates a signer from an async closure.
+    pub fn new<F, Fut>(sign: F) -> Self
+    where
+        F: Fn(SigningRequest) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<(String, String)>, String>> + Send + 'static,
+    {
+        Self(Arc::new(move |request| Box::pin(sign(request))))
+    }
+
+    /// Runs the hook and merges the returned headers into `headers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::SigningError` if the hook fails or returns an invalid header.
+    pub async fn sign_into(&self, request: SigningRequest, headers: &mut HeaderMap) -> Result<(), ProviderError> {
+        let extra = (self.0)(request).await.map_err(ProviderError::SigningError)?;
+        for (name, value) in extra {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| ProviderError::SigningError(format!("Invalid header name '{}': {}", name, e)))?;
+            let value = HeaderValue::from_str(&value)
+                .map_err(|e| ProviderError::SigningError(format!("Invalid header value for '{}': {}", name, e)))?;
+            headers.insert(name, value);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RequestSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RequestSigner(<hook>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::{AUTHORIZATION, CONTENT_TYPE};
+
+    fn request() -> SigningRequest {
+        SigningRequest { method: "POST".to_string(), url: "https://gateway/v1/chat".to_string(), body: b"{}".to_vec() }
+    }
+
+    fn signer(headers: &'static [(&'static str, &'static str)]) -> RequestSigner {
+        RequestSigner::new(move |_| async move {
+            Ok(headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect())
+        })
+    }
+
+    fn signing_error(result: Result<(), ProviderError>) -> String {
+        match result {
+            Err(ProviderError::SigningError(message)) => message,
+            other => panic!("expected a signing error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merges_returned_headers() {
+        let signer = RequestSigner::new(|request| async move {
+            let signature = format!("{} {} {}", request.method, request.url, request.body.len());
+            Ok(vec![("X-Signature".to_string(), signature), ("Authorization".to_string(), "Signed".to_string())])
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer key"));
+
+        signer.sign_into(request(), &mut headers).await.unwrap();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers["x-signature"], "POST https://gateway/v1/chat 2");
+        assert_eq!(headers[AUTHORIZATION], "Signed");
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_headers_are_signing_errors() {
+        let mut headers = HeaderMap::new();
+        let message = signing_error(signer(&[("Bad Name", "v")]).sign_into(request(), &mut headers).await);
+        assert!(message.starts_with("Invalid header name 'Bad Name'"), "{}", message);
+
+        let message = signing_error(signer(&[("X-Signature", "line\nbreak")]).sign_into(request(), &mut headers).await);
+        assert!(message.starts_with("Invalid header value for 'x-signature'"), "{}", message);
+        assert!(headers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hook_errors_are_signing_errors() {
+        let signer = RequestSigner::new(|_| async { Err("key vault unavailable".to_string()) });
+        let message = signing_error(signer.sign_into(request(), &mut HeaderMap::new()).await);
+        assert_eq!(message, "key vault unavailable");
+    }
+}
@@ -0,0 +1,218 @@
+//!
+//! Stream Aggregation
+//!
+//! Folds the chunks of a `CompletionStream` back into the `CompletionResponse` a
+//! non-streaming call would have returned: text deltas are concatenated, tool call deltas
+//! are assembled per index, and the last reported usage and finish reason are kept.
+//! `replay_response` goes the other way, turning a finished response into a stream.
+
+use crate::traits::{
+    CompletionKind, CompletionResponse, CompletionStream, CompletionStreamChunk, ProviderError, StreamContentDelta,
+    TokenUsage, ToolCallFunction, ToolCallFunctionStreamDelta, ToolCallRequest, ToolCallStreamDelta,
+};
+use futures::stream::StreamExt;
+use std::collections::BTreeMap;
+
+/// Incrementally aggregates stream chunks into a `CompletionResponse`.
+///
+/// Useful when chunks are forwarded (e.g. to a UI) as they arrive and the full response
+/// is still needed afterwards; otherwise use `collect_stream`.
+#[derive(Debug, Default, Clone)]
+pub struct StreamCollector {
+    text: String,
+    reasoning: String,
+    tool_calls: BTreeMap<usize, ToolCallRequest>, // Keyed by the delta index, so calls stay in order
+    usage: Option<TokenUsage>,
+    finish_reason: Option<String>,
+}
+
+impl StreamCollector {
+    /// Creates an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one chunk to the aggregate.
+    pub fn push(&mut self, chunk: &CompletionStreamChunk) {
+        match &chunk.delta {
+            StreamContentDelta::Text(text) => self.text.push_str(text),
+            StreamContentDelta::ToolCallDelta(deltas) => deltas.iter().for_each(|delta| self.push_tool_delta(delta)),
+            StreamContentDelta::Reasoning(text) => self.reasoning.push_str(text),
+        }
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+        if chunk.finish_reason.is_some() {
+            self.finish_reason = chunk.finish_reason.clone();
+        }
+    }
+
+    fn push_tool_delta(&mut self, delta: &ToolCallStreamDelta) {
+        let call = self.tool_calls.entry(delta.index).or_insert_with(|| {
+            ToolCallRequest::new_function_call(
+                String::new(),
+                ToolCallFunction { name: String::new(), arguments: String::new() },
+            )
+        });
+        if let Some(id) = &delta.id {
+            call.id.push_str(id);
+        }
+        if let Some(function) = &delta.function {
+            if let Some(name) = &function.name {
+                call.function.name.push_str(name);
+            }
+            if let Some(arguments) = &function.arguments {
+                call.function.arguments.push_str(arguments);
+            }
+        }
+    }
+
+    /// The text received so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The reasoning received so far. It is not part of the final response.
+    pub fn reasoning(&self) -> &str {
+        &self.reasoning
+    }
+
+    /// Builds the final response. Tool calls take precedence over text, matching how
+    /// providers report non-streaming completions; calls without an id get `call_<index>`.
+    pub fn finish(self) -> CompletionResponse {
+        let kind = if self.tool_calls.is_empty() {
+            CompletionKind::Message { content: self.text }
+        } else {
+            let tool_calls = self
+                .tool_calls
+                .into_iter()
+                .map(|(index, mut call)| {
+                    if call.id.is_empty() {
+                        call.id = format!("call_{}", index);
+                    }
+                    call
+                })
+                .collect();
+            CompletionKind::ToolCall { tool_calls }
+        };
+        CompletionResponse { kind, usage: self.usage, finish_reason: self.finish_reason, logprobs: None }
+    }
+}
+
+/// Consumes a stream and returns the aggregated `CompletionResponse`.
+///
+/// # Errors
+///
+/// Returns the first error yielded by the stream.
+pub async fn collect_stream(mut stream: CompletionStream) -> Result<CompletionResponse, ProviderError> {
+    let mut collector = StreamCollector::new();
+    while let Some(chunk) = stream.next().await {
+        collector.push(&chunk?);
+    }
+    Ok(collector.finish())
+}
+
+/// Streams an already complete response, e.g. one served from a cache: the text as a
+/// single chunk, or one delta per tool call. The last chunk carries usage and the finish
+/// reason, so `collect_stream` gives the response back.
+pub fn replay_response(response: CompletionResponse) -> CompletionStream {
+    let mut deltas: Vec<StreamContentDelta> = match response.kind {
+        CompletionKind::Message { content } => vec![StreamContentDelta::Text(content)],
+        CompletionKind::ToolCall { tool_calls } => tool_calls
+            .into_iter()
+            .enumerate()
+            .map(|(index, call)| {
+                StreamContentDelta::ToolCallDelta(vec![ToolCallStreamDelta {
+                    index,
+                    id: Some(call.id),
+                    function: Some(ToolCallFunctionStreamDelta {
+                        name: Some(call.function.name),
+                        arguments: Some(call.function.arguments),
+                    }),
+                }])
+            })
+            .collect(),
+    };
+    if deltas.is_empty() {
+        deltas.push(StreamContentDelta::Text(String::new()));
+    }
+
+    let last = deltas.len() - 1;
+    let chunks: Vec<Result<CompletionStreamChunk, ProviderError>> = deltas
+        .into_iter()
+        .enumerate()
+        .map(|(i, delta)| {
+            Ok(CompletionStreamChunk {
+                delta,
+                usage: if i == last { response.usage } else { None },
+                finish_reason: if i == last { response.finish_reason.clone() } else { None },
+            })
+        })
+        .collect();
+    Box::pin(futures::stream::iter(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    fn tool_delta(index: usize, id: Option<&str>, name: Option<&str>, arguments: &str) -> CompletionStreamChunk {
+        CompletionStreamChunk {
+            delta: StreamContentDelta::ToolCallDelta(vec![ToolCallStreamDelta {
+                index,
+                id: id.map(str::to_string),
+                function: Some(ToolCallFunctionStreamDelta {
+                    name: name.map(str::to_string),
+                    arguments: Some(arguments.to_string()),
+                }),
+            }]),
+            usage: None,
+            finish_reason: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collects_text_and_usage() {
+        let usage = TokenUsage { prompt_tokens: 3, completion_tokens: 2, total_tokens: 5 };
+        let chunks = vec![
+            Ok(CompletionStreamChunk { delta: StreamContentDelta::Text("Hello, ".to_string()), usage: None, finish_reason: None }),
+            Ok(CompletionStreamChunk {
+                delta: StreamContentDelta::Text("world".to_string()),
+                usage: Some(usage),
+                finish_reason: Some("stop".to_string()),
+            }),
+        ];
+        let response = collect_stream(Box::pin(stream::iter(chunks))).await.unwrap();
+
+        assert!(matches!(response.kind, CompletionKind::Message { ref content } if content == "Hello, world"));
+        assert_eq!(response.usage.unwrap().total_tokens, 5);
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_assembles_interleaved_tool_calls() {
+        let chunks = vec![
+            Ok(tool_delta(0, Some("call_a"), Some("search"), "{\"q\":")),
+            Ok(tool_delta(1, None, Some("add"), "{\"a\":1}")),
+            Ok(tool_delta(0, None, None, "\"rust\"}")),
+        ];
+        let response = collect_stream(Box::pin(stream::iter(chunks))).await.unwrap();
+
+        let CompletionKind::ToolCall { tool_calls } = response.kind else {
+            panic!("expected tool calls");
+        };
+        assert_eq!(tool_calls.len(), 2);
+        assert_eq!(tool_calls[0].id, "call_a");
+        assert_eq!(tool_calls[0].function.arguments, "{\"q\":\"rust\"}");
+        assert_eq!(tool_calls[1].id, "call_1");
+        assert_eq!(tool_calls[1].function.name, "add");
+    }
+
+    #[tokio::test]
+    async fn test_returns_stream_error() {
+        let chunks = vec![Err(ProviderError::StreamError("connection reset".to_string()))];
+        let result = collect_stream(Box::pin(stream::iter(chunks))).await;
+        assert!(matches!(result, Err(ProviderError::StreamError(_))));
+    }
+}
@@ -0,0 +1,456 @@
+//!
+//! Anonymous Telemetry
+//!
+//! Opt-in aggregate run statistics for teams operating many Merco services. Nothing is
+//! collected unless a `Telemetry` is created and attached to a config with
+//! `LlmConfig::with_telemetry` (or a provider is wrapped in `TelemetryProvider`), and
+//! reports only go to the endpoint configured by the user.
+//!
+//! Reports contain counters only: requests, streams and failures per provider, failures
+//! by category, and how often features such as tools or JSON mode are used. Prompts,
+//! responses, model names, API keys, URLs and error messages are never included. Each
+//! process is identified by a random id that is not derived from the host or user.
+
+use crate::clock::{default_clock, Clock};
+use crate::traits::{
+    CompletionRequest, CompletionResponse, CompletionStream, LlmProvider, ProviderError, ResponseFormat,
+};
+use crate::transport::{default_transport, Transport, TransportRequest};
+use async_trait::async_trait;
+use futures::stream::StreamExt;
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Identifies the report format, bumped on incompatible changes.
+pub const TELEMETRY_SCHEMA: &str = "merco.telemetry.v1";
+
+/// Counters for one provider.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderStats {
+    /// Completion requests, streaming or not.
+    pub requests: u64,
+    /// How many of the requests were streamed.
+    pub streams: u64,
+    /// Requests that failed (cancellations are not counted).
+    pub failures: u64,
+}
+
+/// One aggregated report, as sent to the telemetry endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryReport {
+    /// Always `TELEMETRY_SCHEMA`.
+    pub schema: String,
+    /// Random id of the reporting process.
+    pub installation_id: String,
+    /// Version of `merco-llmproxy`.
+    pub library_version: String,
+    /// Seconds covered by this report.
+    pub period_seconds: u64,
+    /// Counters per provider (`openai`, `ollama`, ...).
+    pub providers: BTreeMap<String, ProviderStats>,
+    /// Failures per category (`rate_limit`, `server`, `network`, ...).
+    pub errors: BTreeMap<String, u64>,
+    /// Usage count per feature (`tools`, `json_mode`, `streaming`, ...).
+    pub features: BTreeMap<String, u64>,
+}
+
+impl TelemetryReport {
+    /// Returns `true` if nothing was recorded during the period.
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty() && self.errors.is_empty() && self.features.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    providers: BTreeMap<String, ProviderStats>,
+    errors: BTreeMap<String, u64>,
+    features: BTreeMap<String, u64>,
+}
+
+impl Counters {
+    fn merge(&mut self, other: Counters) {
+        for (name, stats) in other.providers {
+            let entry = self.providers.entry(name).or_default();
+            entry.requests += stats.requests;
+            entry.streams += stats.streams;
+            entry.failures += stats.failures;
+        }
+        for (category, count) in other.errors {
+            *self.errors.entry(category).or_default() += count;
+        }
+        for (feature, count) in other.features {
+            *self.features.entry(feature).or_default() += count;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    counters: Counters,
+    period_start: Instant,
+}
+
+/// Collects aggregate statistics and sends them to a user-configured endpoint.
+///
+/// Share one instance (in an `Arc`) between all providers of a service, and either call
+/// `flush` yourself or start `spawn_periodic_flush`.
+#[derive(Debug)]
+pub struct Telemetry {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    transport: Arc<dyn Transport>,
+    clock: Arc<dyn Clock>,
+    installation_id: String,
+    state: Mutex<State>,
+}
+
+/// Random per-process id; `RandomState` is seeded from the OS random source.
+fn random_id() -> String {
+    let state = RandomState::new();
+    let mut first = state.build_hasher();
+    first.write_u32(std::process::id());
+    let mut second = state.build_hasher();
+    second.write_u64(first.finish());
+    format!("{:016x}{:016x}", first.finish(), second.finish())
+}
+
+/// Groups errors into coarse categories, so no message text leaves the process.
+pub fn error_category(error: &ProviderError) -> &'static str {
+    match error {
+        ProviderError::ApiError { status: 401 | 403, .. } => "auth",
+        ProviderError::ApiError { status: 429, .. } => "rate_limit",
+        ProviderError::ApiError { status, .. } if *status >= 500 => "server",
+        ProviderError::ApiError { .. } => "client",
+        #[cfg(feature = "http-transport")]
+        ProviderError::RequestError(e) if e.is_timeout() => "timeout",
+        #[cfg(feature = "http-transport")]
+        ProviderError::RequestError(_) => "network",
+        ProviderError::TransportError(_) => "network",
+        ProviderError::ParseError(_) | ProviderError::StreamError(_) | ProviderError::ToolFormatError(_) => "response",
+        ProviderError::ConfigError(_) | ProviderError::MissingConfig(_) | ProviderError::SigningError(_) => "config",
+        ProviderError::BudgetExceeded(_) => "budget",
+        ProviderError::Cancelled => "cancelled",
+        _ => "other",
+    }
+}
+
+impl Telemetry {
+    /// Creates a collector reporting to `endpoint` over HTTP(S).
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        let clock = default_clock();
+        Self {
+            endpoint: endpoint.into(),
+            headers: Vec::new(),
+            transport: default_transport(),
+            installation_id: random_id(),
+            state: Mutex::new(State { counters: Counters::default(), period_start: clock.now() }),
+            clock,
+        }
+    }
+
+    /// Adds a header sent with every report, e.g. for authenticating to the endpoint
+    /// (builder style).
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sends reports through a custom transport (builder style).
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Uses `clock` to measure report periods (builder style).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.state.get_mut().unwrap().period_start = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// The random id included in every report.
+    pub fn installation_id(&self) -> &str {
+        &self.installation_id
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Counters)) {
+        f(&mut self.state.lock().unwrap().counters);
+    }
+
+    /// Counts one use of an application-level feature (e.g. `crew`, `approval`).
+    pub fn record_feature(&self, feature: &str) {
+        self.update(|counters| *counters.features.entry(feature.to_string()).or_default() += 1);
+    }
+
+    /// Counts a request to `provider` and the features it uses.
+    pub fn record_request(&self, provider: &str, request: &CompletionRequest, streaming: bool) {
+        let mut features = Vec::new();
+        if request.tools.as_ref().is_some_and(|tools| !tools.is_empty()) {
+            features.push("tools");
+        }
+        match request.response_format {
+            Some(ResponseFormat::JsonObject) => features.push("json_mode"),
+            Some(ResponseFormat::JsonSchema { .. }) => features.push("json_schema"),
+            _ => {}
+        }
+        if request.wants_reasoning() {
+            features.push("reasoning");
+        }
+        if streaming {
+            features.push("streaming");
+        }
+
+        self.update(|counters| {
+            let stats = counters.providers.entry(provider.to_string()).or_default();
+            stats.requests += 1;
+            if streaming {
+                stats.streams += 1;
+            }
+            for feature in features {
+                *counters.features.entry(feature.to_string()).or_default() += 1;
+            }
+        });
+    }
+
+    /// Counts a failed request to `provider`. Cancellations are tallied by category but
+    /// do not count as provider failures.
+    pub fn record_error(&self, provider: &str, error: &ProviderError) {
+        let category = error_category(error);
+        self.update(|counters| {
+            *counters.errors.entry(category.to_string()).or_default() += 1;
+            if !matches!(error, ProviderError::Cancelled) {
+                counters.providers.entry(provider.to_string()).or_default().failures += 1;
+            }
+        });
+    }
+
+    fn report(&self, counters: &Counters, period_start: Instant) -> TelemetryReport {
+        TelemetryReport {
+            schema: TELEMETRY_SCHEMA.to_string(),
+            installation_id: self.installation_id.clone(),
+            library_version: env!("CARGO_PKG_VERSION").to_string(),
+            period_seconds: self.clock.now().saturating_duration_since(period_start).as_secs(),
+            providers: counters.providers.clone(),
+            errors: counters.errors.clone(),
+            features: counters.features.clone(),
+        }
+    }
+
+    /// The statistics recorded since the last flush, without resetting them.
+    pub fn snapshot(&self) -> TelemetryReport {
+        let state = self.state.lock().unwrap();
+        self.report(&state.counters, state.period_start)
+    }
+
+    /// Sends the statistics recorded since the last flush and starts a new period.
+    /// Nothing is sent if nothing was recorded. If sending fails the counters are kept
+    /// and included in the next report.
+    ///
+    /// # Errors
+    ///
+    /// Returns the transport error, or `ProviderError::ApiError` if the endpoint rejects
+    /// the report.
+    pub async fn flush(&self) -> Result<(), ProviderError> {
+        let (counters, period_start) = {
+            let mut state = self.state.lock().unwrap();
+            let counters = std::mem::take(&mut state.counters);
+            (counters, std::mem::replace(&mut state.period_start, self.clock.now()))
+        };
+        let report = self.report(&counters, period_start);
+        if report.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.send(&report).await;
+        if result.is_err() {
+            let mut state = self.state.lock().unwrap();
+            state.counters.merge(counters);
+            state.period_start = period_start;
+        }
+        result
+    }
+
+    async fn send(&self, report: &TelemetryReport) -> Result<(), ProviderError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| ProviderError::ConfigError(e.to_string()))?;
+            let value = HeaderValue::from_str(value).map_err(|e| ProviderError::ConfigError(e.to_string()))?;
+            headers.insert(name, value);
+        }
+        let request = TransportRequest {
+            method: "POST".to_string(),
+            url: self.endpoint.clone(),
+            headers,
+            body: serde_json::to_vec(report)?,
+        };
+
+        let response = self.transport.send(request).await?;
+        if response.status >= 400 {
+            return Err(ProviderError::ApiError {
+                status: response.status,
+                message: "Telemetry endpoint rejected the report".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Flushes every `interval` on the tokio runtime until the handle is aborted. Send
+    /// failures are ignored; the counters are retried with the next report.
+    pub fn spawn_periodic_flush(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let telemetry = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                telemetry.clock.sleep(interval).await;
+                let _ = telemetry.flush().await;
+            }
+        })
+    }
+}
+
+/// Wraps a provider and records every call in a `Telemetry`.
+pub struct TelemetryProvider {
+    inner: Arc<dyn LlmProvider>,
+    telemetry: Arc<Telemetry>,
+    provider: String,
+}
+
+impl TelemetryProvider {
+    /// Wraps `inner`; `provider` is the label used in reports (e.g. `openai`).
+    pub fn new(inner: Arc<dyn LlmProvider>, telemetry: Arc<Telemetry>, provider: impl Into<String>) -> Self {
+        Self { inner, telemetry, provider: provider.into() }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for TelemetryProvider {
+    async fn completion(&self, request: CompletionRequest) -> Result<CompletionResponse, ProviderError> {
+        self.telemetry.record_request(&self.provider, &request, false);
+        let result = self.inner.completion(request).await;
+        if let Err(e) = &result {
+            self.telemetry.record_error(&self.provider, e);
+        }
+        result
+    }
+
+    async fn completion_stream(&self, request: CompletionRequest) -> Result<CompletionStream, ProviderError> {
+        self.telemetry.record_request(&self.provider, &request, true);
+        let stream = match self.inner.completion_stream(request).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.telemetry.record_error(&self.provider, &e);
+                return Err(e);
+            }
+        };
+
+        let telemetry = self.telemetry.clone();
+        let provider = self.provider.clone();
+        let stream = stream.inspect(move |chunk| {
+            if let Err(e) = chunk {
+                telemetry.record_error(&provider, e);
+            }
+        });
+        Ok(Box::pin(stream))
+    }
+
+    async fn warmup(&self, model: &str) -> Result<(), ProviderError> {
+        self.inner.warmup(model).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::providers::MockProvider;
+    use crate::traits::ChatMessage;
+    use crate::transport::TransportResponse;
+    use futures::stream;
+
+    /// Records report bodies and answers with a fixed status.
+    #[derive(Debug)]
+    struct RecordingTransport {
+        status: u16,
+        bodies: Mutex<Vec<Vec<u8>>>,
+    }
+
+    #[async_trait]
+    impl Transport for RecordingTransport {
+        async fn send(&self, request: TransportRequest) -> Result<TransportResponse, ProviderError> {
+            self.bodies.lock().unwrap().push(request.body);
+            Ok(TransportResponse { status: self.status, headers: HeaderMap::new(), body: Box::pin(stream::empty()) })
+        }
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "secret-finetune".to_string(),
+            messages: vec![ChatMessage::user("private prompt".to_string())],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_counts_requests_errors_and_features() {
+        let telemetry = Telemetry::new("https://telemetry.internal/v1");
+        let json_request = CompletionRequest { response_format: Some(ResponseFormat::JsonObject), ..request() };
+        telemetry.record_request("openai", &json_request, false);
+        telemetry.record_request("openai", &request(), true);
+        telemetry.record_request("ollama", &request(), false);
+        telemetry.record_error("openai", &ProviderError::ApiError { status: 429, message: "slow down".to_string() });
+        telemetry.record_error("ollama", &ProviderError::Cancelled);
+        telemetry.record_feature("crew");
+
+        let report = telemetry.snapshot();
+        assert_eq!(report.providers["openai"], ProviderStats { requests: 2, streams: 1, failures: 1 });
+        assert_eq!(report.providers["ollama"], ProviderStats { requests: 1, streams: 0, failures: 0 });
+        assert_eq!(report.errors["rate_limit"], 1);
+        assert_eq!(report.errors["cancelled"], 1);
+        assert_eq!(report.features["json_mode"], 1);
+        assert_eq!(report.features["streaming"], 1);
+        assert_eq!(report.features["crew"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_sends_anonymous_report_and_resets() {
+        let transport = Arc::new(RecordingTransport { status: 200, bodies: Mutex::new(Vec::new()) });
+        let clock = Arc::new(ManualClock::new());
+        let telemetry = Arc::new(
+            Telemetry::new("https://telemetry.internal/v1").with_transport(transport.clone()).with_clock(clock.clone()),
+        );
+        let provider = TelemetryProvider::new(Arc::new(MockProvider::new().with_message("ok")), telemetry.clone(), "openai");
+        provider.completion(request()).await.unwrap();
+        clock.advance(Duration::from_secs(60));
+
+        telemetry.flush().await.unwrap();
+        let bodies = transport.bodies.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 1);
+        let body = String::from_utf8(bodies[0].clone()).unwrap();
+        assert!(!body.contains("secret-finetune") && !body.contains("private prompt"));
+        let report: TelemetryReport = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.schema, TELEMETRY_SCHEMA);
+        assert_eq!(report.period_seconds, 60);
+        assert_eq!(report.providers["openai"].requests, 1);
+
+        // Nothing new was recorded, so nothing is sent
+        telemetry.flush().await.unwrap();
+        assert_eq!(transport.bodies.lock().unwrap().len(), 1);
+        assert!(telemetry.snapshot().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_keeps_counters() {
+        let transport = Arc::new(RecordingTransport { status: 503, bodies: Mutex::new(Vec::new()) });
+        let telemetry = Telemetry::new("https://telemetry.internal/v1").with_transport(transport);
+        telemetry.record_feature("approval");
+
+        assert!(matches!(telemetry.flush().await, Err(ProviderError::ApiError { status: 503, .. })));
+        assert_eq!(telemetry.snapshot().features["approval"], 1);
+    }
+}
@@ -0,0 +1,188 @@
+//!
+//! Token Counting
+//!
+//! Provides the `Tokenizer` trait with a character-based `HeuristicTokenizer` and, with
+//! the `tiktoken` feature, an exact BPE `TiktokenTokenizer` for OpenAI-family models.
+//! The `count_tokens` and `fits_in_context` helpers estimate the size of a conversation
+//! before it is sent, so callers can trim history or validate `max_tokens` up front.
+
+use crate::traits::ChatMessage;
+
+/// Tokens added per message for role and formatting markers (OpenAI chat format).
+const TOKENS_PER_MESSAGE: usize = 4;
+/// Tokens used to prime the assistant's reply.
+const REPLY_PRIMING_TOKENS: usize = 3;
+
+/// Counts the tokens in a piece of text.
+pub trait Tokenizer: Send + Sync {
+    /// Returns the number of tokens `text` encodes to.
+    fn count(&self, text: &str) -> usize;
+}
+
+/// Approximates token counts from character length (about 4 characters per token for
+/// English text). Used when no exact tokenizer is available for a model.
+#[derive(Debug, Clone)]
+pub struct HeuristicTokenizer {
+    chars_per_token: f32,
+}
+
+impl HeuristicTokenizer {
+    /// Creates a heuristic tokenizer with the given average characters per token.
+    pub fn new(chars_per_token: f32) -> Self {
+        Self { chars_per_token: chars_per_token.max(0.1) }
+    }
+}
+
+impl Default for HeuristicTokenizer {
+    fn default() -> Self {
+        Self::new(4.0)
+    }
+}
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count(&self, text: &str) -> usize {
+        (text.chars().count() as f32 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// An exact tokenizer using OpenAI's BPE encodings via `tiktoken-rs`.
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenTokenizer {
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenTokenizer {
+    /// Creates a tokenizer with the encoding used by `model` (e.g. `gpt-4o`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::Unsupported` if the model has no known encoding.
+    pub fn for_model(model: &str) -> Result<Self, crate::traits::ProviderError> {
+        let bpe = tiktoken_rs::get_bpe_from_model(strip_vendor_prefix(model))
+            .map_err(|e| crate::traits::ProviderError::Unsupported(format!("No tokenizer for '{}': {}", model, e)))?;
+        Ok(Self { bpe })
+    }
+
+    /// Creates a tokenizer with the `cl100k_base` encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::Unexpected` if the encoding fails to load.
+    pub fn cl100k() -> Result<Self, crate::traits::ProviderError> {
+        let bpe = tiktoken_rs::cl100k_base().map_err(|e| crate::traits::ProviderError::Unexpected(e.to_string()))?;
+        Ok(Self { bpe })
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Tokenizer for TiktokenTokenizer {
+    fn count(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+}
+
+/// Returns the best available tokenizer for `model`: exact when the `tiktoken` feature
+/// is enabled and the model is known, otherwise the heuristic.
+pub fn tokenizer_for_model(model: &str) -> Box<dyn Tokenizer> {
+    #[cfg(feature = "tiktoken")]
+    if let Ok(tokenizer) = TiktokenTokenizer::for_model(model) {
+        return Box::new(tokenizer);
+    }
+    #[cfg(not(feature = "tiktoken"))]
+    let _ = model;
+    Box::new(HeuristicTokenizer::default())
+}
+
+/// Returns the default tokenizer: `cl100k_base` with the `tiktoken` feature, otherwise
+/// the heuristic.
+pub fn default_tokenizer() -> Box<dyn Tokenizer> {
+    #[cfg(feature = "tiktoken")]
+    if let Ok(tokenizer) = TiktokenTokenizer::cl100k() {
+        return Box::new(tokenizer);
+    }
+    Box::new(HeuristicTokenizer::default())
+}
+
+/// Counts the tokens a list of messages will use as a prompt, including per-message
+/// formatting overhead.
+pub fn count_message_tokens(tokenizer: &dyn Tokenizer, messages: &[ChatMessage]) -> usize {
+    let per_message: usize = messages
+        .iter()
+        .map(|message| {
+            let content = message.content.as_deref().map_or(0, |c| tokenizer.count(c));
+            let tool_calls: usize = message.tool_calls.as_ref().map_or(0, |calls| {
+                calls
+                    .iter()
+                    .map(|call| tokenizer.count(&call.function.name) + tokenizer.count(&call.function.arguments))
+                    .sum()
+            });
+            TOKENS_PER_MESSAGE + content + tool_calls
+        })
+        .sum();
+    per_message + REPLY_PRIMING_TOKENS
+}
+
+/// Estimates the prompt tokens of `messages` using the default tokenizer.
+pub fn count_tokens(messages: &[ChatMessage]) -> usize {
+    count_message_tokens(default_tokenizer().as_ref(), messages)
+}
+
+/// Returns the context window size in tokens for well-known models, if known.
+pub fn context_window(model: &str) -> Option<usize> {
+    let model = strip_vendor_prefix(model).to_lowercase();
+    // Most specific prefixes first
+    const WINDOWS: &[(&str, usize)] = &[
+        ("gpt-4.1", 1_047_576),
+        ("gpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo", 16_385),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("o4", 200_000),
+        ("claude", 200_000),
+        ("llama3", 8_192),
+        ("llama-3", 8_192),
+        ("qwen", 32_768),
+        ("mistral", 32_768),
+    ];
+    WINDOWS.iter().find(|(prefix, _)| model.starts_with(prefix)).map(|(_, window)| *window)
+}
+
+/// Returns how many tokens remain in `model`'s context window after `messages`, or
+/// `None` if the model's window is unknown.
+pub fn remaining_context(model: &str, messages: &[ChatMessage]) -> Option<usize> {
+    let window = context_window(model)?;
+    let used = count_message_tokens(tokenizer_for_model(model).as_ref(), messages);
+    Some(window.saturating_sub(used))
+}
+
+/// Returns `true` if `messages` fit in `model`'s context window. Models with an
+/// unknown window are assumed to fit.
+pub fn fits_in_context(model: &str, messages: &[ChatMessage]) -> bool {
+    remaining_context(model, messages).is_none_or(|remaining| remaining > 0)
+}
+
+// Router-style names such as `openai/gpt-4o` carry a vendor prefix
+fn strip_vendor_prefix(model: &str) -> &str {
+    model.rsplit('/').next().unwrap_or(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_and_fit() {
+        let messages = vec![ChatMessage::system("abcd".repeat(10)), ChatMessage::user("hi".to_string())];
+        // 10 + 1 content tokens, plus 4 per message and 3 for reply priming
+        assert_eq!(count_message_tokens(&HeuristicTokenizer::default(), &messages), 22);
+
+        assert_eq!(context_window("openai/gpt-4o-mini"), Some(128_000));
+        assert!(fits_in_context("gpt-4", &messages));
+        assert!(!fits_in_context("gpt-4", &[ChatMessage::user("word ".repeat(10_000))]));
+        assert!(fits_in_context("some-local-model", &[ChatMessage::user("x".repeat(40_000))]));
+    }
+}
@@ -1,14 +1,346 @@
-use crate::traits::{Tool, ToolCallFunction};
+use crate::paths::resolve_in;
+use crate::traits::{JsonSchema, Tool, ToolCallFunction};
 use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+tokio::task_local! {
+    static CURRENT_TOOL_CONTEXT: ToolContext;
+}
+
+/// The pending result of a tool execution
+pub type ToolFuture = Pin<Box<dyn Future<Output = Result<String, String>> + Send>>;
 
 /// Represents a tool function that can be executed with JSON arguments
-pub type ToolExecutor = Arc<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+pub type ToolExecutor = Arc<dyn Fn(String) -> ToolFuture + Send + Sync>;
+
+/// Wrap a synchronous function as a `ToolExecutor`. It runs on the calling task, so
+/// functions that block for long should be async tools instead.
+pub fn sync_executor(f: impl Fn(&str) -> Result<String, String> + Send + Sync + 'static) -> ToolExecutor {
+    let f = Arc::new(f);
+    Arc::new(move |args: String| {
+        let f = f.clone();
+        Box::pin(async move { f(&args) })
+    })
+}
+
+/// Wrap an async function as a `ToolExecutor`
+pub fn async_executor<F, Fut>(f: F) -> ToolExecutor
+where
+    F: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<String, String>> + Send + 'static,
+{
+    Arc::new(move |args: String| Box::pin(f(args)))
+}
+
+/// A structured tool failure, sent to the model as `{"error": ..., "retryable": bool}`
+/// instead of an ad hoc string. Executors return it as the `Err` payload (see
+/// `to_payload`), and tool loops recover it with `parse`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolError {
+    /// What went wrong, in words the model can act on.
+    pub error: String,
+    /// Whether repeating the same call may succeed (e.g. a timeout or rate limit).
+    pub retryable: bool,
+}
+
+impl ToolError {
+    /// A failure that repeating the call will not fix.
+    pub fn new(error: impl Into<String>) -> Self {
+        Self { error: error.into(), retryable: false }
+    }
+
+    /// A transient failure worth retrying.
+    pub fn retryable(error: impl Into<String>) -> Self {
+        Self { error: error.into(), retryable: true }
+    }
+
+    /// Converts any displayable error, keeping a `ToolError` as it is.
+    pub fn from_error<E: fmt::Display + 'static>(error: E) -> Self {
+        match (&error as &dyn std::any::Any).downcast_ref::<ToolError>() {
+            Some(tool_error) => tool_error.clone(),
+            None => Self::new(error.to_string()),
+        }
+    }
+
+    /// The JSON payload returned to the model.
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.error.clone())
+    }
+
+    /// Reads an executor's error: a `to_payload` string, or any other message as a
+    /// non-retryable error.
+    pub fn parse(message: &str) -> Self {
+        serde_json::from_str(message).unwrap_or_else(|_| Self::new(message))
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.error)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// Limits applied to raw tool-call arguments before they reach a tool, protecting tool
+/// implementations from oversized or pathologically nested model output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolArgLimits {
+    /// Maximum size of the raw JSON arguments in bytes.
+    pub max_bytes: usize,
+    /// Maximum nesting depth of objects and arrays.
+    pub max_depth: usize,
+    /// Maximum length in bytes of any single string (keys included).
+    pub max_string_len: usize,
+}
+
+impl Default for ToolArgLimits {
+    fn default() -> Self {
+        Self { max_bytes: 256 * 1024, max_depth: 32, max_string_len: 64 * 1024 }
+    }
+}
+
+impl ToolArgLimits {
+    /// Checks raw JSON arguments against the limits without deserializing them.
+    ///
+    /// # Errors
+    ///
+    /// Returns a corrective message suitable for sending back to the model.
+    pub fn check(&self, args: &str) -> Result<(), String> {
+        let reject = |reason: String| {
+            Err(format!(
+                "Tool arguments rejected: {}. Call the tool again with smaller, flatter arguments.",
+                reason
+            ))
+        };
+        if args.len() > self.max_bytes {
+            return reject(format!("payload is {} bytes, the limit is {}", args.len(), self.max_bytes));
+        }
+
+        let (mut depth, mut in_string, mut escaped, mut string_len) = (0usize, false, false, 0usize);
+        for byte in args.bytes() {
+            if in_string {
+                string_len += 1;
+                if escaped {
+                    escaped = false;
+                } else if byte == b'\\' {
+                    escaped = true;
+                } else if byte == b'"' {
+                    in_string = false;
+                }
+                if string_len > self.max_string_len {
+                    return reject(format!("a string exceeds {} bytes", self.max_string_len));
+                }
+                continue;
+            }
+            match byte {
+                b'"' => {
+                    in_string = true;
+                    string_len = 0;
+                }
+                b'{' | b'[' => {
+                    depth += 1;
+                    if depth > self.max_depth {
+                        return reject(format!("nesting exceeds {} levels", self.max_depth));
+                    }
+                }
+                b'}' | b']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks tool-call arguments against the tool's parameter schema: the arguments must be
+/// a JSON object with every required field, and each value must match its declared
+/// `type` (and `enum`, `items` and nested `properties`, where given). Fields without a
+/// declared type and fields missing from the schema are accepted.
+///
+/// # Errors
+///
+/// Returns a message listing every problem, suitable for sending back to the model so it
+/// can correct the call.
+pub fn validate_tool_args(tool: &Tool, args: &str) -> Result<(), String> {
+    let value = if args.trim().is_empty() {
+        serde_json::Value::Object(serde_json::Map::new())
+    } else {
+        serde_json::from_str(args).map_err(|e| {
+            format!("Invalid arguments for tool '{}': not valid JSON ({}). Call the tool again with a JSON object.", tool.name, e)
+        })?
+    };
+
+    let mut problems = Vec::new();
+    validate_object(&tool.parameters, &value, "", &mut problems);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid arguments for tool '{}': {}. Call the tool again with arguments matching its schema.",
+            tool.name,
+            problems.join("; ")
+        ))
+    }
+}
+
+fn validate_object(schema: &JsonSchema, value: &serde_json::Value, path: &str, problems: &mut Vec<String>) {
+    let Some(object) = value.as_object() else {
+        problems.push(format!("{} must be an object, got {}", describe_path(path), json_type_name(value)));
+        return;
+    };
+    for field in schema.required.iter().flatten() {
+        if !object.contains_key(field) {
+            problems.push(format!("missing required field `{}{}`", path, field));
+        }
+    }
+    let Some(properties) = &schema.properties else { return };
+    for (field, field_value) in object {
+        if let Some(field_schema) = properties.get(field) {
+            validate_value(field_schema, field_value, &format!("{}{}", path, field), problems);
+        }
+    }
+}
+
+fn validate_value(schema: &serde_json::Value, value: &serde_json::Value, path: &str, problems: &mut Vec<String>) {
+    // A field may allow several types, e.g. `["string", "null"]` for optional fields
+    let types: Vec<&str> = match schema.get("type") {
+        Some(serde_json::Value::String(schema_type)) => vec![schema_type.as_str()],
+        Some(serde_json::Value::Array(schema_types)) => schema_types.iter().filter_map(|t| t.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|schema_type| value_has_type(value, schema_type)) {
+        problems.push(format!("`{}` must be {}, got {}", path, types.join(" or "), json_type_name(value)));
+        return;
+    }
+    if let Some(allowed) = schema.get("enum").and_then(|allowed| allowed.as_array()) {
+        if !allowed.contains(value) {
+            problems.push(format!("`{}` must be one of {}", path, serde_json::Value::Array(allowed.clone())));
+        }
+    }
+    match value {
+        serde_json::Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_value(item_schema, item, &format!("{}[{}]", path, index), problems);
+                }
+            }
+        }
+        serde_json::Value::Object(_) if schema.get("properties").is_some() || schema.get("required").is_some() => {
+            let nested = JsonSchema {
+                schema_type: "object".to_string(),
+                properties: schema.get("properties").and_then(|p| p.as_object()).cloned(),
+                required: schema.get("required").and_then(|r| serde_json::from_value(r.clone()).ok()),
+            };
+            validate_object(&nested, value, &format!("{}.", path), problems);
+        }
+        _ => {}
+    }
+}
+
+fn value_has_type(value: &serde_json::Value, schema_type: &str) -> bool {
+    match schema_type {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        // Unknown types are left for the tool to check
+        _ => true,
+    }
+}
+
+fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(number) if number.is_f64() => "number",
+        serde_json::Value::Number(_) => "integer",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+fn describe_path(path: &str) -> String {
+    match path.strip_suffix('.') {
+        Some(field) => format!("`{}`", field),
+        None => "the arguments".to_string(),
+    }
+}
 
-/// A registry for storing and managing tool functions
+/// The namespace of a tool name: everything before the last `.` (`fs` for `fs.read`,
+/// `web.search` for `web.search.news`), or `None` for tools outside any namespace.
+pub fn tool_namespace(name: &str) -> Option<&str> {
+    name.rsplit_once('.').map(|(namespace, _)| namespace)
+}
+
+/// Selects which tools an agent may see and call, by namespace or exact name.
+///
+/// A pattern matches a tool named like it and every tool in it or its sub-namespaces
+/// (`fs` matches `fs.read` and `fs.git.log`); `*` matches every tool. Tools must match
+/// an `allow` pattern, if any are given, and no `deny` pattern: denials win.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolFilter {
+    /// Patterns of tools to expose; empty exposes every tool that isn't denied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+    /// Patterns of tools to hide.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+}
+
+impl ToolFilter {
+    /// Create a filter that permits every tool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expose tools matching `pattern` (builder style)
+    pub fn allow(mut self, pattern: impl Into<String>) -> Self {
+        self.allow.push(pattern.into());
+        self
+    }
+
+    /// Hide tools matching `pattern`, even if they are allowed (builder style)
+    pub fn deny(mut self, pattern: impl Into<String>) -> Self {
+        self.deny.push(pattern.into());
+        self
+    }
+
+    /// Whether the tool named `name` passes the filter
+    pub fn permits(&self, name: &str) -> bool {
+        let matches = |pattern: &String| {
+            pattern == "*"
+                || name == pattern
+                || name.strip_prefix(pattern.as_str()).is_some_and(|rest| rest.starts_with('.'))
+        };
+        (self.allow.is_empty() || self.allow.iter().any(matches)) && !self.deny.iter().any(matches)
+    }
+
+    /// Keep the tools that pass the filter
+    pub fn apply(&self, tools: Vec<Tool>) -> Vec<Tool> {
+        tools.into_iter().filter(|tool| self.permits(&tool.name)).collect()
+    }
+}
+
+/// A registry for storing and managing tool functions.
+///
+/// The `#[merco_tool]` macro fills a global registry, used by the free functions of this
+/// module. Registries created with `new` are independent of it, so each agent or crew can
+/// own its tools (two registries may hold different tools of the same name) and tests
+/// don't share state. Cloning is cheap; executors are shared.
+#[derive(Clone)]
 pub struct ToolRegistry {
     tools: HashMap<String, (Tool, ToolExecutor)>,
+    limits: ToolArgLimits,
 }
 
 impl ToolRegistry {
@@ -16,31 +348,376 @@ impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            limits: ToolArgLimits::default(),
+        }
+    }
+
+    /// Create a registry holding copies of the named tools from the global registry.
+    ///
+    /// # Errors
+    ///
+    /// Returns the names that are not registered globally.
+    pub fn from_global(names: &[&str]) -> Result<Self, Vec<String>> {
+        let global = GLOBAL_REGISTRY.lock().map(|registry| registry.clone()).unwrap_or_default();
+        let mut registry = Self::new();
+        let mut missing = Vec::new();
+        for name in names {
+            match global.tools.get(*name) {
+                Some((tool, executor)) => registry.register(tool.clone(), executor.clone()),
+                None => missing.push(name.to_string()),
+            }
+        }
+        if missing.is_empty() {
+            Ok(registry)
+        } else {
+            Err(missing)
         }
     }
 
+    /// Register a tool (builder style)
+    pub fn with_tool(mut self, tool: Tool, executor: ToolExecutor) -> Self {
+        self.register(tool, executor);
+        self
+    }
+
+    /// Register a synchronous closure as a tool
+    pub fn register_fn(&mut self, tool: Tool, f: impl Fn(&str) -> Result<String, String> + Send + Sync + 'static) {
+        self.register(tool, sync_executor(f));
+    }
+
+    /// Register an async closure as a tool
+    pub fn register_async_fn<F, Fut>(&mut self, tool: Tool, f: F)
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, String>> + Send + 'static,
+    {
+        self.register(tool, async_executor(f));
+    }
+
+    /// Set the limits applied to tool arguments before execution
+    pub fn set_limits(&mut self, limits: ToolArgLimits) {
+        self.limits = limits;
+    }
+
+    /// Get the limits applied to tool arguments before execution
+    pub fn limits(&self) -> ToolArgLimits {
+        self.limits
+    }
+
     /// Register a tool with its tool definition and executor function
     pub fn register(&mut self, tool: Tool, executor: ToolExecutor) {
         self.tools.insert(tool.name.clone(), (tool, executor));
     }
 
+    /// Register a tool under `namespace`, renaming it to `namespace.name`
+    pub fn register_in(&mut self, namespace: &str, mut tool: Tool, executor: ToolExecutor) {
+        tool.name = format!("{}.{}", namespace, tool.name);
+        self.register(tool, executor);
+    }
+
+    /// A copy of the registry holding only the tools that pass `filter`
+    pub fn filtered(&self, filter: &ToolFilter) -> Self {
+        let tools = self.tools.iter().filter(|(name, _)| filter.permits(name)).map(|(name, entry)| (name.clone(), entry.clone()));
+        Self { tools: tools.collect(), limits: self.limits }
+    }
+
+    /// The namespaces of the registered tools, sorted
+    pub fn namespaces(&self) -> Vec<String> {
+        let mut namespaces: Vec<String> = self.tools.keys().filter_map(|name| tool_namespace(name)).map(String::from).collect();
+        namespaces.sort();
+        namespaces.dedup();
+        namespaces
+    }
+
+    /// Remove a tool, returning its definition if it was registered
+    pub fn unregister(&mut self, name: &str) -> Option<Tool> {
+        self.tools.remove(name).map(|(tool, _)| tool)
+    }
+
+    /// Whether a tool with this name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+
     /// Get all registered tool definitions
     pub fn get_tools(&self) -> Vec<Tool> {
         self.tools.values().map(|(tool, _)| tool.clone()).collect()
     }
 
+    /// Get the definitions of the named tools; unknown names are skipped
+    pub fn get_tools_by_names(&self, names: &[&str]) -> Vec<Tool> {
+        names.iter().filter_map(|name| self.tools.get(*name).map(|(tool, _)| tool.clone())).collect()
+    }
+
     /// Execute a tool by name with the provided arguments
-    pub fn execute_tool(&self, name: &str, args: &str) -> Result<String, String> {
-        match self.tools.get(name) {
-            Some((_, executor)) => executor(args),
-            None => Err(format!("Tool '{}' not found in registry", name)),
-        }
+    pub async fn execute_tool(&self, name: &str, args: &str) -> Result<String, String> {
+        let executor = self.checked_executor(name, args)?;
+        executor(args.to_string()).await
+    }
+
+    /// Execute a tool by name, keeping the JSON value of its result alongside the text sent to the model
+    pub async fn execute_tool_output(&self, name: &str, args: &str) -> Result<ToolOutput, String> {
+        self.execute_tool(name, args).await.map(ToolOutput::from_content)
+    }
+
+    /// Look up a tool's executor after checking its arguments against the limits and the
+    /// tool's parameter schema
+    fn checked_executor(&self, name: &str, args: &str) -> Result<ToolExecutor, String> {
+        let (tool, executor) = self.tools.get(name).ok_or_else(|| format!("Tool '{}' not found in registry", name))?;
+        self.limits.check(args)?;
+        validate_tool_args(tool, args)?;
+        Ok(executor.clone())
     }
 
     /// Execute a tool call
-    pub fn execute_tool_call(&self, tool_call: &ToolCallFunction) -> Result<String, String> {
-        self.execute_tool(&tool_call.name, &tool_call.arguments)
+    pub async fn execute_tool_call(&self, tool_call: &ToolCallFunction) -> Result<String, String> {
+        self.execute_tool(&tool_call.name, &tool_call.arguments).await
+    }
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.tools.keys().collect();
+        names.sort();
+        f.debug_struct("ToolRegistry").field("tools", &names).field("limits", &self.limits).finish()
+    }
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serialize a tool result canonically: object keys sorted and no insignificant whitespace,
+/// so identical results always produce identical text for the model.
+pub fn to_canonical_json<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    // `serde_json::Map` is ordered by key, so round-tripping through `Value` sorts nested objects
+    serde_json::to_string(&serde_json::to_value(value)?)
+}
+
+/// The result of a tool call: the text sent to the model and, when the tool returned JSON, the
+/// structured value so callbacks and telemetry don't have to re-parse the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolOutput {
+    /// Text placed in the tool message.
+    pub content: String,
+    /// Structured result, if the tool returned JSON.
+    pub value: Option<serde_json::Value>,
+}
+
+impl ToolOutput {
+    /// A plain-text result.
+    pub fn text(content: impl Into<String>) -> Self {
+        Self { content: content.into(), value: None }
+    }
+
+    /// A structured result, serialized canonically for the model.
+    pub fn json(value: serde_json::Value) -> Self {
+        let content = to_canonical_json(&value).unwrap_or_else(|_| value.to_string());
+        Self { content, value: Some(value) }
+    }
+
+    /// A structured result from any serializable value.
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> Result<Self, serde_json::Error> {
+        Ok(Self::json(serde_json::to_value(value)?))
+    }
+
+    /// Wrap an executor's output, keeping the parsed value when it is a JSON object or array.
+    /// Scalars stay text so a tool returning `"42"` or `"true"` isn't reinterpreted.
+    pub fn from_content(content: String) -> Self {
+        let value = match content.trim_start().as_bytes().first() {
+            Some(b'{') | Some(b'[') => serde_json::from_str(&content).ok(),
+            _ => None,
+        };
+        Self { content, value }
+    }
+
+    /// Deserialize the structured value into `T`.
+    pub fn parse<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
+        self.value.clone().and_then(|value| serde_json::from_value(value).ok())
+    }
+}
+
+/// Receives the result of every successful tool call, with the name of the tool.
+pub type ToolOutputSink = Arc<dyn Fn(&str, &ToolOutput) + Send + Sync>;
+
+/// An incremental progress update reported by a running tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolProgress {
+    /// Name of the tool reporting progress.
+    pub tool: String,
+    /// Completed fraction between 0.0 and 1.0, if known.
+    pub fraction: Option<f32>,
+    /// Human-readable status, if any.
+    pub message: Option<String>,
+}
+
+/// Receives progress updates from running tools.
+pub type ProgressSink = Arc<dyn Fn(&ToolProgress) + Send + Sync>;
+
+/// Reports progress for one tool to the receiver installed when the tool started.
+///
+/// `#[merco_tool]` functions get one by taking a `ProgressReporter` parameter, which is
+/// filled in by the executor rather than by the model. The reporter holds the receiver
+/// itself, so it keeps working inside tasks the tool spawns.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    tool: String,
+    sink: Option<ProgressSink>,
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter")
+            .field("tool", &self.tool)
+            .field("sink", &self.sink.as_ref().map(|_| "<ProgressSink>"))
+            .finish()
+    }
+}
+
+impl ProgressReporter {
+    /// Creates a reporter for `tool` that forwards to `sink`, if any.
+    pub fn new(tool: impl Into<String>, sink: Option<ProgressSink>) -> Self {
+        Self { tool: tool.into(), sink }
+    }
+
+    /// Creates a reporter for `tool` from the context of the current task.
+    pub fn current(tool: impl Into<String>) -> Self {
+        ToolContext::current().progress_reporter(tool)
+    }
+
+    /// Whether anyone is listening; lets tools skip computing expensive status messages.
+    pub fn is_active(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Reports a completed fraction (clamped to 0.0..=1.0) and/or a status message.
+    pub fn report(&self, fraction: Option<f32>, message: Option<String>) {
+        if let Some(sink) = &self.sink {
+            let fraction = fraction.map(|f| f.clamp(0.0, 1.0));
+            sink(&ToolProgress { tool: self.tool.clone(), fraction, message });
+        }
     }
+
+    /// Reports the completed fraction between 0.0 and 1.0.
+    pub fn fraction(&self, fraction: f32) {
+        self.report(Some(fraction), None);
+    }
+
+    /// Reports a status message.
+    pub fn message(&self, message: impl Into<String>) {
+        self.report(None, Some(message.into()));
+    }
+}
+
+/// Per-run information available to tools while they execute.
+///
+/// Runners (such as a crew run) install a context with `scope`; tool executors read it
+/// with `ToolContext::current()`, e.g. to write files into the run's isolated workspace.
+#[derive(Clone, Default)]
+pub struct ToolContext {
+    /// Identifier of the current run, if any.
+    pub run_id: Option<String>,
+    /// Directory reserved for files produced by tools during this run.
+    pub workspace: Option<PathBuf>,
+    /// Maximum total size of the workspace in bytes, if capped.
+    pub max_workspace_bytes: Option<u64>,
+    /// Receiver for progress updates reported by tools, if any.
+    pub progress: Option<ProgressSink>,
+}
+
+impl fmt::Debug for ToolContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolContext")
+            .field("run_id", &self.run_id)
+            .field("workspace", &self.workspace)
+            .field("max_workspace_bytes", &self.max_workspace_bytes)
+            .field("progress", &self.progress.as_ref().map(|_| "<ProgressSink>"))
+            .finish()
+    }
+}
+
+impl ToolContext {
+    /// Sets the receiver for tool progress updates (builder style).
+    pub fn with_progress(mut self, sink: ProgressSink) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    /// Forwards a progress update to the installed receiver, if any.
+    pub fn report_progress(&self, progress: &ToolProgress) {
+        if let Some(sink) = &self.progress {
+            sink(progress);
+        }
+    }
+
+    /// Returns a reporter that sends progress updates for `tool` to this context's receiver.
+    pub fn progress_reporter(&self, tool: impl Into<String>) -> ProgressReporter {
+        ProgressReporter::new(tool, self.progress.clone())
+    }
+
+    /// Resolves a (possibly model-generated) relative path inside the run's workspace.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no workspace or the path is unsafe (see `paths`).
+    pub fn workspace_path(&self, path: &str) -> Result<PathBuf, String> {
+        let root = self.workspace.as_ref().ok_or("No workspace is available for this run")?;
+        resolve_in(root, path).map_err(|e| e.to_string())
+    }
+
+    /// Bytes that may still be written to the workspace before it reaches its size cap,
+    /// or `None` if there is no workspace or it is not capped.
+    pub fn workspace_quota(&self) -> Option<u64> {
+        let (workspace, max) = (self.workspace.as_ref()?, self.max_workspace_bytes?);
+        Some(max.saturating_sub(dir_size(workspace)))
+    }
+
+    /// Checks that the workspace has not grown past its size cap.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message with the workspace's size if it is over the cap.
+    pub fn check_workspace_size(&self) -> Result<(), String> {
+        if let (Some(workspace), Some(max)) = (&self.workspace, self.max_workspace_bytes) {
+            let size = dir_size(workspace);
+            if size > max {
+                return Err(format!("The workspace holds {} bytes, over its size cap of {} bytes", size, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the context installed for the current task, or an empty one.
+    pub fn current() -> Self {
+        CURRENT_TOOL_CONTEXT.try_with(|ctx| ctx.clone()).unwrap_or_default()
+    }
+
+    /// Runs `future` with this context installed for the current task.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT_TOOL_CONTEXT.scope(self, future).await
+    }
+
+    /// Runs the synchronous closure `f` with this context installed.
+    pub fn scope_sync<R>(self, f: impl FnOnce() -> R) -> R {
+        CURRENT_TOOL_CONTEXT.sync_scope(self, f)
+    }
+}
+
+/// Total size of the files under `path`; unreadable entries count as empty.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
 }
 
 // Global registry singleton
@@ -59,10 +736,27 @@ pub fn register_tool(tool: Tool, executor: ToolExecutor) {
 
 /// Helper function for procedural macro to register a tool with tool definition and executor
 #[doc(hidden)]
-pub fn __register_macro_tool(tool_name: &str, tool_definition: Tool, executor_fn: impl Fn(&str) -> Result<String, String> + Send + Sync + 'static) {
+pub fn __register_macro_tool(_tool_name: &str, tool_definition: Tool, executor_fn: impl Fn(String) -> ToolFuture + Send + Sync + 'static) {
     register_tool(tool_definition, Arc::new(executor_fn));
 }
 
+/// Helper for procedural macro to add a parameter's description and example to its schema
+#[doc(hidden)]
+pub fn __describe_param(
+    props: &mut serde_json::Map<String, serde_json::Value>,
+    name: &str,
+    description: Option<&str>,
+    example: Option<serde_json::Value>,
+) {
+    let Some(serde_json::Value::Object(schema)) = props.get_mut(name) else { return };
+    if let Some(description) = description {
+        schema.insert("description".to_string(), description.into());
+    }
+    if let Some(example) = example {
+        schema.insert("examples".to_string(), serde_json::Value::Array(vec![example]));
+    }
+}
+
 /// Get all registered tools from the global registry
 pub fn get_all_tools() -> Vec<Tool> {
     GLOBAL_REGISTRY
@@ -96,12 +790,26 @@ pub fn get_tools_by_names(names: &[&str]) -> Vec<Tool> {
         .collect()
 }
 
+/// Set the limits applied to tool arguments in the global registry
+pub fn set_tool_arg_limits(limits: ToolArgLimits) {
+    if let Ok(mut registry) = GLOBAL_REGISTRY.lock() {
+        registry.set_limits(limits);
+    }
+}
+
 /// Execute a tool by name with JSON arguments
-pub fn execute_tool(name: &str, args: &str) -> Result<String, String> {
-    GLOBAL_REGISTRY
+pub async fn execute_tool(name: &str, args: &str) -> Result<String, String> {
+    // Release the lock before running the tool so tools can use the registry themselves
+    let executor = GLOBAL_REGISTRY
         .lock()
         .map_err(|e| format!("Failed to lock registry: {}", e))?
-        .execute_tool(name, args)
+        .checked_executor(name, args)?;
+    executor(args.to_string()).await
+}
+
+/// Execute a tool by name, keeping the JSON value of its result alongside the text
+pub async fn execute_tool_output(name: &str, args: &str) -> Result<ToolOutput, String> {
+    execute_tool(name, args).await.map(ToolOutput::from_content)
 }
 
 /// Create a public re-export macro for the merco_tool attribute
@@ -113,8 +821,8 @@ mod tests {
     use super::*;
     use crate::traits::JsonSchema;
 
-    #[test]
-    fn test_tool_registry() {
+    #[tokio::test]
+    async fn test_tool_registry() {
         let mut registry = ToolRegistry::new();
         
         // Create a simple addition tool
@@ -134,7 +842,7 @@ mod tests {
         };
         
         // Create executor function
-        let add_executor: ToolExecutor = Arc::new(|args| {
+        let add_executor = sync_executor(|args| {
             let parsed: Result<serde_json::Value, _> = serde_json::from_str(args);
             match parsed {
                 Ok(value) => {
@@ -154,11 +862,217 @@ mod tests {
         assert_eq!(registry.get_tools()[0].name, "add");
         
         // Execute the tool
-        let result = registry.execute_tool("add", r#"{"a": 5, "b": 3}"#);
+        let result = registry.execute_tool("add", r#"{"a": 5, "b": 3}"#).await;
         assert_eq!(result, Ok("8".to_string()));
         
+        // Arguments that don't match the schema never reach the executor
+        let error = registry.execute_tool("add", r#"{"a": "5"}"#).await.unwrap_err();
+        assert!(error.contains("missing required field `b`"), "{}", error);
+        assert!(error.contains("`a` must be integer, got string"), "{}", error);
+
         // Try executing a non-existent tool
-        let error = registry.execute_tool("multiply", r#"{"a": 5, "b": 3}"#);
+        let error = registry.execute_tool("multiply", r#"{"a": 5, "b": 3}"#).await;
         assert!(error.is_err());
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_registries_are_independent() {
+        let tool = |name: &str| Tool {
+            name: name.to_string(),
+            description: "Greets".to_string(),
+            parameters: JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        let mut english = ToolRegistry::new();
+        english.register_fn(tool("greet"), |_| Ok("hello".to_string()));
+        let mut french = ToolRegistry::new().with_tool(tool("greet"), sync_executor(|_| Ok("bonjour".to_string())));
+        french.register_async_fn(tool("wave"), |_| async { Ok("*wave*".to_string()) });
+
+        assert_eq!(english.execute_tool("greet", "{}").await, Ok("hello".to_string()));
+        assert_eq!(french.execute_tool("greet", "{}").await, Ok("bonjour".to_string()));
+        assert!(!english.contains("wave"));
+        assert_eq!(french.get_tools_by_names(&["wave", "missing"]).len(), 1);
+
+        assert_eq!(french.unregister("wave").map(|tool| tool.name), Some("wave".to_string()));
+        assert!(french.execute_tool("wave", "{}").await.is_err());
+        assert_eq!(ToolRegistry::from_global(&["no_such_tool"]).unwrap_err(), vec!["no_such_tool".to_string()]);
+    }
+
+    #[test]
+    fn test_namespaces_and_filters() {
+        let tool = |name: &str| Tool {
+            name: name.to_string(),
+            description: String::new(),
+            parameters: JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        let mut registry = ToolRegistry::new();
+        for name in ["read", "write"] {
+            registry.register_in("fs", tool(name), sync_executor(|_| Ok(String::new())));
+        }
+        registry.register_in("fs.git", tool("log"), sync_executor(|_| Ok(String::new())));
+        registry.register_in("web", tool("search"), sync_executor(|_| Ok(String::new())));
+        registry.register(tool("clock"), sync_executor(|_| Ok(String::new())));
+
+        assert_eq!(registry.namespaces(), vec!["fs", "fs.git", "web"]);
+        assert_eq!(tool_namespace("fs.git.log"), Some("fs.git"));
+        assert_eq!(tool_namespace("clock"), None);
+
+        let names = |filter: &ToolFilter| {
+            let mut names: Vec<String> = registry.filtered(filter).get_tools().into_iter().map(|t| t.name).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&ToolFilter::new()).len(), 5);
+        assert_eq!(names(&ToolFilter::new().allow("fs").deny("fs.write")), vec!["fs.git.log", "fs.read"]);
+        assert_eq!(names(&ToolFilter::new().deny("fs")), vec!["clock", "web.search"]);
+        assert_eq!(names(&ToolFilter::new().allow("*").deny("fs.git")).len(), 4);
+        // A pattern is a whole namespace segment, not a string prefix
+        assert!(!ToolFilter::new().allow("f").permits("fs.read"));
+    }
+
+    #[tokio::test]
+    async fn test_async_tool_sees_context() {
+        let mut registry = ToolRegistry::new();
+        let tool = Tool {
+            name: "whoami".to_string(),
+            description: "Returns the current run id".to_string(),
+            parameters: JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        registry.register(
+            tool,
+            async_executor(|_args| async {
+                tokio::task::yield_now().await;
+                ToolContext::current().run_id.ok_or_else(|| "no run".to_string())
+            }),
+        );
+
+        let context = ToolContext { run_id: Some("run-1".to_string()), ..Default::default() };
+        let result = context.scope(registry.execute_tool("whoami", "{}")).await;
+        assert_eq!(result, Ok("run-1".to_string()));
+    }
+
+    #[test]
+    fn test_tool_error_payload_round_trips() {
+        let payload = ToolError::retryable("upstream timed out").to_payload();
+        assert_eq!(payload, r#"{"error":"upstream timed out","retryable":true}"#);
+        assert_eq!(ToolError::parse(&payload), ToolError::retryable("upstream timed out"));
+        assert_eq!(ToolError::parse("Tool 'x' not found"), ToolError::new("Tool 'x' not found"));
+
+        assert!(ToolError::from_error(ToolError::retryable("busy")).retryable);
+        assert!(!ToolError::from_error("bad input".to_string()).retryable);
+    }
+
+    #[test]
+    fn test_validate_nested_arguments() {
+        let tool = Tool {
+            name: "plan".to_string(),
+            description: "Plans a trip".to_string(),
+            parameters: JsonSchema {
+                schema_type: "object".to_string(),
+                properties: Some(
+                    serde_json::json!({
+                        "mode": { "type": "string", "enum": ["car", "train"] },
+                        "stops": {
+                            "type": "array",
+                            "items": { "type": "object", "properties": { "city": { "type": "string" } }, "required": ["city"] }
+                        },
+                        "note": { "type": ["string", "null"] }
+                    })
+                    .as_object()
+                    .cloned()
+                    .unwrap(),
+                ),
+                required: Some(vec!["mode".to_string()]),
+            },
+        };
+
+        assert!(validate_tool_args(&tool, r#"{"mode": "car", "stops": [{"city": "Oslo"}], "note": null}"#).is_ok());
+        let error = validate_tool_args(&tool, r#"{"mode": "boat", "stops": [{"city": 1}, {}]}"#).unwrap_err();
+        assert!(error.contains(r#"`mode` must be one of ["car","train"]"#), "{}", error);
+        assert!(error.contains("`stops[0].city` must be string, got integer"), "{}", error);
+        assert!(error.contains("missing required field `stops[1].city`"), "{}", error);
+        assert!(validate_tool_args(&tool, "[1, 2]").unwrap_err().contains("the arguments must be an object"));
+        assert!(validate_tool_args(&tool, "{not json").unwrap_err().contains("not valid JSON"));
+    }
+
+    #[test]
+    fn test_describe_param() {
+        let mut props = serde_json::Map::new();
+        props.insert("a".to_string(), serde_json::json!({ "type": "integer" }));
+
+        __describe_param(&mut props, "a", Some("The first addend"), Some(serde_json::json!(3)));
+        __describe_param(&mut props, "missing", Some("ignored"), None);
+        assert_eq!(
+            serde_json::Value::Object(props),
+            serde_json::json!({ "a": { "type": "integer", "description": "The first addend", "examples": [3] } })
+        );
+    }
+
+    #[test]
+    fn test_tool_arg_limits() {
+        let limits = ToolArgLimits { max_bytes: 100, max_depth: 3, max_string_len: 10 };
+
+        assert!(limits.check(r#"{"a": [1, {"b": "short"}]}"#).is_ok());
+        // Brackets and escaped quotes inside strings don't count towards depth
+        assert!(limits.check(r#"{"a": "[[[\"{{"}"#).is_ok());
+        assert!(limits.check(r#"{"a": [[[1]]]}"#).unwrap_err().contains("nesting"));
+        assert!(limits.check(r#"{"a": "much too long"}"#).unwrap_err().contains("string"));
+        assert!(limits.check(&" ".repeat(101)).unwrap_err().contains("bytes"));
+    }
+
+    #[test]
+    fn test_tool_output_keeps_structured_values() {
+        let value = serde_json::json!({"b": 1, "a": {"d": [true], "c": null}});
+        assert_eq!(to_canonical_json(&value).unwrap(), r#"{"a":{"c":null,"d":[true]},"b":1}"#);
+
+        let output = ToolOutput::json(value.clone());
+        assert_eq!(output.content, r#"{"a":{"c":null,"d":[true]},"b":1}"#);
+        assert_eq!(ToolOutput::from_content(output.content.clone()), output);
+
+        // Scalars and plain text stay text
+        assert_eq!(ToolOutput::from_content("42".into()).value, None);
+        assert_eq!(ToolOutput::from_content("[not json".into()).value, None);
+        assert_eq!(ToolOutput::text("done").content, "done");
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Point { y: i32, x: i32 }
+        let output = ToolOutput::from_serialize(&Point { y: 2, x: 1 }).unwrap();
+        assert_eq!(output.content, r#"{"x":1,"y":2}"#);
+        assert_eq!(output.parse::<Point>(), Some(Point { y: 2, x: 1 }));
+    }
+
+    #[tokio::test]
+    async fn test_registry_execute_tool_output() {
+        let tool = Tool {
+            name: "stats".to_string(),
+            description: "Returns statistics".to_string(),
+            parameters: JsonSchema { schema_type: "object".to_string(), properties: None, required: None },
+        };
+        let mut registry = ToolRegistry::new();
+        registry.register_fn(tool, |_| Ok(r#"{"count": 3}"#.to_string()));
+        let output = registry.execute_tool_output("stats", "{}").await.unwrap();
+        assert_eq!(output.value, Some(serde_json::json!({"count": 3})));
+        assert_eq!(output.content, r#"{"count": 3}"#);
+    }
+
+    #[tokio::test]
+    async fn test_progress_reporter_forwards_updates() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let context = ToolContext::default().with_progress(Arc::new(move |p: &ToolProgress| sink.lock().unwrap().push(p.clone())));
+
+        let reporter = context.scope(async { ProgressReporter::current("build") }).await;
+        assert!(reporter.is_active());
+        // The reporter carries the receiver, so it works outside the context's scope too
+        tokio::spawn(async move {
+            reporter.fraction(1.5);
+            reporter.message("linking");
+        })
+        .await
+        .unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0], ToolProgress { tool: "build".to_string(), fraction: Some(1.0), message: None });
+        assert_eq!(seen[1].message.as_deref(), Some("linking"));
+        assert!(!ProgressReporter::current("idle").is_active());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::cancellation::{cancellable, cancellable_stream, CancellationToken};
 use async_trait::async_trait;
 use futures::stream::Stream; // Requires the `futures` crate
 use serde::{Deserialize, Serialize};
@@ -33,7 +34,7 @@ pub struct JsonSchema {
 // --- Request/Response Structures ---
 
 /// Represents a request to an LLM provider for chat completion.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CompletionRequest {
     /// A list of messages comprising the conversation history.
     pub messages: Vec<ChatMessage>,
@@ -45,20 +46,144 @@ pub struct CompletionRequest {
     /// Maximum number of tokens to generate.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    /// Nucleus sampling probability mass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Penalty applied to tokens based on how often they already appeared.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    /// Penalty applied to tokens that already appeared at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    /// Seed for deterministic sampling (where supported).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Sequences at which the model stops generating.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    /// Whether to return log probabilities of the output tokens (where supported).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    /// Number of most likely alternatives to return per token position (requires `logprobs`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    /// Additional provider-specific parameters, merged into the request body as-is.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extra: serde_json::Map<String, JsonValue>,
     /// A list of tools the model may call.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
-    // Consider adding tool_choice option later.
+    /// Controls whether and which tool the model must call. Defaults to `Auto` when tools are present.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    /// The format the model must produce its output in (e.g. JSON mode).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// How much reasoning models (e.g. OpenAI o-series) think before answering.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Token budget for extended thinking (e.g. Anthropic), where supported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<u32>,
 }
 
 impl CompletionRequest {
+    /// Creates a new completion request from its individual parts.
     pub fn new(messages: Vec<ChatMessage>, model: String, temperature: Option<f32>, max_tokens: Option<u32>, tools: Option<Vec<Tool>>) -> Self {
-        Self { messages, model, temperature, max_tokens, tools }
+        Self {
+            messages,
+            model,
+            temperature,
+            max_tokens,
+            tools,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a provider-specific parameter to the request body (builder style).
+    pub fn with_extra(mut self, key: impl Into<String>, value: JsonValue) -> Self {
+        self.extra.insert(key.into(), value);
+        self
+    }
+
+    /// Sets the tool choice strategy for the request (builder style).
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Sets the required output format for the request (builder style).
+    pub fn with_response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = Some(response_format);
+        self
     }
+
+    /// Sets the reasoning effort for reasoning models (builder style).
+    pub fn with_reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+        self.reasoning_effort = Some(effort);
+        self
+    }
+
+    /// Enables extended thinking with the given token budget (builder style).
+    pub fn with_thinking_budget(mut self, budget_tokens: u32) -> Self {
+        self.thinking_budget = Some(budget_tokens);
+        self
+    }
+
+    /// Returns `true` if the request asks for model reasoning in any form.
+    pub fn wants_reasoning(&self) -> bool {
+        self.reasoning_effort.is_some() || self.thinking_budget.is_some()
+    }
+}
+
+/// How much effort a reasoning model spends thinking before it answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    /// Fastest answers, least reasoning.
+    Low,
+    /// The provider's default trade-off.
+    Medium,
+    /// Most thorough reasoning, slowest and most expensive.
+    High,
+}
+
+/// Controls how the model chooses between answering directly and calling tools.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoice {
+    /// The model decides whether to call tools.
+    Auto,
+    /// The model must not call any tools.
+    None,
+    /// The model must call at least one tool.
+    Required,
+    /// The model must call the tool with the given name.
+    Function(String),
+}
+
+/// Specifies the format the model must produce its output in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Free-form text output (the provider default).
+    Text,
+    /// Any syntactically valid JSON object.
+    JsonObject,
+    /// JSON output conforming to the given JSON Schema.
+    JsonSchema {
+        /// A name identifying the schema (required by OpenAI).
+        name: String,
+        /// The JSON Schema the output must conform to.
+        schema: JsonValue,
+        /// Whether the provider should enforce the schema strictly (where supported).
+        #[serde(skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
 }
 
 /// Represents the role of a message sender in a chat conversation.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum ChatMessageRole {
     /// A system message that provides instructions or context for the model.
     #[serde(rename = "system")]
@@ -72,12 +197,41 @@ pub enum ChatMessageRole {
     /// A tool message that contains the result of a tool call.
     #[serde(rename = "tool")]
     Tool,
+    /// Instructions from the application developer, which newer OpenAI models use in
+    /// place of system messages. Providers without a developer role receive it as `system`.
+    #[serde(rename = "developer")]
+    Developer,
+}
+
+impl ChatMessageRole {
+    /// The role name used on the wire (e.g. `"assistant"`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatMessageRole::System => "system",
+            ChatMessageRole::User => "user",
+            ChatMessageRole::Assistant => "assistant",
+            ChatMessageRole::Tool => "tool",
+            ChatMessageRole::Developer => "developer",
+        }
+    }
+
+    /// Returns `true` for system and developer messages, which carry instructions
+    /// rather than conversation turns.
+    pub fn is_instruction(&self) -> bool {
+        matches!(self, ChatMessageRole::System | ChatMessageRole::Developer)
+    }
+}
+
+impl std::fmt::Display for ChatMessageRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Represents a single message in a chat conversation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
-    /// The role of the message sender (e.g., "system", "user", "assistant", "tool").
+    /// The role of the message sender.
     pub role: ChatMessageRole,
     /// The text content of the message. Can be None for assistant messages requesting tool calls
     /// or for tool messages providing results.
@@ -93,28 +247,39 @@ pub struct ChatMessage {
 }
 
 impl ChatMessage {
+    /// Creates a new chat message from its individual parts.
     pub fn new(role: ChatMessageRole, content: Option<String>, tool_calls: Option<Vec<ToolCallRequest>>, tool_call_id: Option<String>) -> Self {
         Self { role, content, tool_calls, tool_call_id }
     }
     
-    // Helper for creating a user message
-    pub fn user(content: String) -> Self {
-        Self { role: ChatMessageRole::User, content: Some(content), tool_calls: None, tool_call_id: None }
+    /// Helper for creating a user message.
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: ChatMessageRole::User, content: Some(content.into()), tool_calls: None, tool_call_id: None }
     }
     
-    // Helper for creating a system message
-    pub fn system(content: String) -> Self {
-        Self { role: ChatMessageRole::System, content: Some(content), tool_calls: None, tool_call_id: None }
+    /// Helper for creating a system message.
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: ChatMessageRole::System, content: Some(content.into()), tool_calls: None, tool_call_id: None }
     }
 
-    // Helper for creating an assistant message
+    /// Helper for creating a developer message.
+    pub fn developer(content: impl Into<String>) -> Self {
+        Self { role: ChatMessageRole::Developer, content: Some(content.into()), tool_calls: None, tool_call_id: None }
+    }
+
+    /// Helper for creating an assistant message.
     pub fn assistant(content: Option<String>, tool_calls: Option<Vec<ToolCallRequest>>) -> Self {
          Self { role: ChatMessageRole::Assistant, content, tool_calls, tool_call_id: None }
     }
 
-    // Helper for creating a tool result message
+    /// Helper for creating a tool result message.
     pub fn tool_result(tool_call_id: String, content: String) -> Self {
-         Self { role: ChatMessageRole::Tool, content: Some(content), tool_calls: None, tool_call_id: Some(tool_call_id) }
+         Self::tool(tool_call_id, content)
+    }
+
+    /// Helper for creating a tool result message answering the call `tool_call_id`.
+    pub fn tool(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+         Self { role: ChatMessageRole::Tool, content: Some(content.into()), tool_calls: None, tool_call_id: Some(tool_call_id.into()) }
     }
 }
 
@@ -131,6 +296,7 @@ pub struct ToolCallRequest {
 }
 
 impl ToolCallRequest {
+    /// Creates a new function-type tool call request.
     pub fn new_function_call(id: String, function: ToolCallFunction) -> Self {
         Self {
             id,
@@ -154,9 +320,15 @@ pub struct ToolCallFunction {
 #[serde(untagged)]
 pub enum CompletionKind {
     /// The LLM generated a text message.
-    Message { content: String },
+    Message {
+        /// The generated text.
+        content: String,
+    },
     /// The LLM requested one or more tool calls.
-    ToolCall { tool_calls: Vec<ToolCallRequest> },
+    ToolCall {
+        /// The tool calls requested by the model.
+        tool_calls: Vec<ToolCallRequest>,
+    },
 }
 
 /// Represents the complete response from a non-streaming LLM completion request.
@@ -171,6 +343,44 @@ pub struct CompletionResponse {
     /// The reason the model stopped generating tokens (if available).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub finish_reason: Option<String>,
+    /// Per-token log probabilities of the output (if requested and supported).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
+}
+
+impl CompletionResponse {
+    /// Returns the mean log probability across all output tokens, a simple confidence score.
+    pub fn mean_logprob(&self) -> Option<f32> {
+        let logprobs = self.logprobs.as_ref().filter(|l| !l.is_empty())?;
+        Some(logprobs.iter().map(|t| t.logprob).sum::<f32>() / logprobs.len() as f32)
+    }
+}
+
+/// The log probability of a single output token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    /// The token text.
+    pub token: String,
+    /// The log probability of this token.
+    pub logprob: f32,
+    /// The UTF-8 bytes of the token, if provided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
+    /// The most likely alternative tokens at this position.
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+/// An alternative token considered at a given position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogprob {
+    /// The token text.
+    pub token: String,
+    /// The log probability of this token.
+    pub logprob: f32,
+    /// The UTF-8 bytes of the token, if provided.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
 }
 
 /// Represents the kind of content delta in a streaming response chunk.
@@ -182,6 +392,9 @@ pub enum StreamContentDelta {
     /// Incremental information about tool calls being generated.
     #[serde(rename = "tool_calls")]
     ToolCallDelta(Vec<ToolCallStreamDelta>),
+    /// A chunk of the model's reasoning ("thinking"), streamed separately from the answer.
+    #[serde(rename = "reasoning")]
+    Reasoning(String),
 }
 
 /// Represents incremental information about a single tool call within a stream.
@@ -218,7 +431,7 @@ pub struct CompletionStreamChunk {
 }
 
 /// Represents token usage statistics for a completion request.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TokenUsage {
     /// Tokens used in the prompt.
     pub prompt_tokens: u32,
@@ -228,15 +441,40 @@ pub struct TokenUsage {
     pub total_tokens: u32,
 }
 
+impl std::ops::Add for TokenUsage {
+    type Output = TokenUsage;
+
+    /// Usage of two requests together, e.g. to total a conversation.
+    fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens.saturating_add(other.prompt_tokens),
+            completion_tokens: self.completion_tokens.saturating_add(other.completion_tokens),
+            total_tokens: self.total_tokens.saturating_add(other.total_tokens),
+        }
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: TokenUsage) {
+        *self = *self + other;
+    }
+}
+
 /// Errors that can occur when interacting with LLM providers.
 #[derive(Error, Debug)]
 pub enum ProviderError {
     /// An error occurred during the underlying HTTP request.
+    #[cfg(feature = "http-transport")]
     #[error("API request failed: {0}")]
     RequestError(#[from] reqwest::Error),
     /// The API returned an error response (e.g., 4xx, 5xx).
     #[error("API response error: {status}: {message}")]
-    ApiError { status: u16, message: String },
+    ApiError {
+        /// The HTTP status code returned by the API.
+        status: u16,
+        /// The error message extracted from the response body.
+        message: String,
+    },
     /// Failed to parse the JSON response from the API.
     #[error("Failed to parse API response: {0}")]
     ParseError(#[from] serde_json::Error),
@@ -252,6 +490,21 @@ pub enum ProviderError {
     /// Error related to the format or processing of tool use/calls.
     #[error("Tool use response format error: {0}")]
     ToolFormatError(String),
+    /// The transport failed to deliver the request or read the response.
+    #[error("Transport error: {0}")]
+    TransportError(String),
+    /// A token or cost budget has been used up.
+    #[error("Budget exceeded: {0}")]
+    BudgetExceeded(String),
+    /// Nested LLM calls (e.g. tools calling the LLM) exceeded the allowed depth.
+    #[error("Recursion limit exceeded: {0}")]
+    RecursionLimitExceeded(String),
+    /// The request was cancelled through its `CancellationToken`.
+    #[error("Request was cancelled")]
+    Cancelled,
+    /// The request signing hook failed.
+    #[error("Request signing failed: {0}")]
+    SigningError(String),
     /// The requested operation is not supported by the provider implementation.
     #[error("Unsupported operation: {0}")]
     Unsupported(String),
@@ -260,6 +513,22 @@ pub enum ProviderError {
     Unexpected(String),
 }
 
+impl ProviderError {
+    /// Returns `true` for transient failures worth retrying on another provider or later:
+    /// rate limits, exhausted quotas, server errors, timeouts and connection failures.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            #[cfg(feature = "http-transport")]
+            ProviderError::RequestError(e) => e.is_timeout() || e.is_connect(),
+            ProviderError::ApiError { status, message } => {
+                matches!(status, 402 | 408 | 429) || *status >= 500 || message.contains("insufficient_quota")
+            }
+            ProviderError::TransportError(_) => true,
+            _ => false,
+        }
+    }
+}
+
 /// Type alias for the stream of completion chunks.
 /// Uses dynamic dispatch (`dyn Stream`) and requires `Send` for async compatibility.
 pub type CompletionStream =
@@ -279,4 +548,32 @@ pub trait LlmProvider: Send + Sync {
     /// Takes a `CompletionRequest` and returns a stream (`CompletionStream`) that yields
     /// `CompletionStreamChunk` results.
     async fn completion_stream(&self, request: CompletionRequest) -> Result<CompletionStream, ProviderError>;
+
+    /// Like `completion`, but aborts the in-flight request when `token` is cancelled.
+    async fn completion_with_cancellation(
+        &self,
+        request: CompletionRequest,
+        token: &CancellationToken,
+    ) -> Result<CompletionResponse, ProviderError> {
+        cancellable(token, self.completion(request)).await
+    }
+
+    /// Like `completion_stream`, but aborts opening the stream, and closes it once open,
+    /// when `token` is cancelled.
+    async fn completion_stream_with_cancellation(
+        &self,
+        request: CompletionRequest,
+        token: &CancellationToken,
+    ) -> Result<CompletionStream, ProviderError> {
+        let stream = cancellable(token, self.completion_stream(request)).await?;
+        Ok(cancellable_stream(stream, token.clone()))
+    }
+
+    /// Prepares the provider for low-latency use, e.g. by opening pooled connections or
+    /// loading `model` into memory, so the first real request isn't slowed down.
+    ///
+    /// The default implementation does nothing.
+    async fn warmup(&self, _model: &str) -> Result<(), ProviderError> {
+        Ok(())
+    }
 } 
\ No newline at end of file
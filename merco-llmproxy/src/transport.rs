@@ -0,0 +1,482 @@
+//!
+//! Transport Layer
+//!
+//! Defines the `Transport` trait used by providers to dispatch HTTP requests, along
+//! with the default reqwest-based `HttpTransport` and a `UnixSocketTransport` for local
+//! inference servers (e.g. llama.cpp or sandboxed deployments) that listen on a
+//! unix domain socket instead of a TCP port. `HttpClientConfig` configures the HTTP
+//! client behind `HttpTransport` (proxies, extra root CAs, pooling, keep-alive).
+//!
+//! `HttpTransport` needs the `http-transport` feature, which the provider, fetch and
+//! OpenAPI features enable. Without it, requests fail unless a transport is set.
+
+#[cfg(feature = "http-transport")]
+use crate::config::ConfigError;
+use crate::traits::ProviderError;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{Stream, TryStreamExt};
+use http::header::HeaderMap;
+#[cfg(feature = "http-transport")]
+use reqwest::Client;
+use std::fmt;
+use std::pin::Pin;
+#[cfg(any(feature = "http-transport", all(unix, feature = "unix-socket")))]
+use std::time::Duration;
+
+/// Default request timeout in seconds.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// A stream of response body bytes.
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, ProviderError>> + Send>>;
+
+/// An outgoing HTTP request as prepared by a provider.
+#[derive(Debug, Clone)]
+pub struct TransportRequest {
+    /// The HTTP method (e.g. `POST`).
+    pub method: String,
+    /// The full request URL.
+    pub url: String,
+    /// Headers to send with the request.
+    pub headers: HeaderMap,
+    /// The request body.
+    pub body: Vec<u8>,
+}
+
+/// A response received from a `Transport`, with a streaming body.
+pub struct TransportResponse {
+    /// The HTTP status code.
+    pub status: u16,
+    /// The response headers.
+    pub headers: HeaderMap,
+    /// The response body as a byte stream.
+    pub body: ByteStream,
+}
+
+impl fmt::Debug for TransportResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransportResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("body", &"<stream>")
+            .finish()
+    }
+}
+
+impl TransportResponse {
+    /// Returns `true` if the status code is in the 2xx range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Collects the entire body into memory.
+    pub async fn bytes(self) -> Result<Bytes, ProviderError> {
+        let chunks: Vec<Bytes> = self.body.try_collect().await?;
+        Ok(Bytes::from(chunks.concat()))
+    }
+
+    /// Collects the entire body as a (lossily decoded) UTF-8 string.
+    pub async fn text(self) -> Result<String, ProviderError> {
+        let bytes = self.bytes().await?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Collects the entire body and deserializes it as JSON.
+    pub async fn json<T: serde::de::DeserializeOwned>(self) -> Result<T, ProviderError> {
+        let bytes = self.bytes().await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+/// Dispatches HTTP requests on behalf of a provider.
+///
+/// Implement this to route provider traffic over a custom channel (unix sockets,
+/// in-process servers, test doubles) without changing the provider logic.
+#[async_trait]
+pub trait Transport: Send + Sync + fmt::Debug {
+    /// Sends the request and returns the response with a streaming body.
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, ProviderError>;
+}
+
+#[cfg(feature = "http-transport")]
+/// Options for the HTTP client used by `HttpTransport`.
+///
+/// Proxy URLs and certificates are parsed when they are set, so a misconfiguration is
+/// reported while building the config rather than on the first request.
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    /// Total timeout for each request, including reading the response body.
+    pub timeout: Duration,
+    /// Timeout for establishing a connection, if different from the request timeout.
+    pub connect_timeout: Option<Duration>,
+    /// Proxies requests are routed through.
+    pub proxies: Vec<reqwest::Proxy>,
+    /// Additional trusted root certificates (e.g. a corporate CA).
+    pub root_certificates: Vec<reqwest::Certificate>,
+    /// Maximum idle connections kept per host.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long idle pooled connections are kept open.
+    pub pool_idle_timeout: Option<Duration>,
+    /// Interval for TCP keep-alive probes.
+    pub tcp_keepalive: Option<Duration>,
+}
+
+#[cfg(feature = "http-transport")]
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            connect_timeout: None,
+            proxies: Vec::new(),
+            root_certificates: Vec::new(),
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            tcp_keepalive: None,
+        }
+    }
+}
+
+#[cfg(feature = "http-transport")]
+impl HttpClientConfig {
+    /// Creates a config with the default timeout and no proxy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the total request timeout (builder style).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the connect timeout (builder style).
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Routes all requests through the proxy at `url` (builder style).
+    ///
+    /// `no_proxy` is a comma-separated list of hosts, domains or CIDR ranges that bypass
+    /// the proxy, in the format of the `NO_PROXY` environment variable.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidHttpOption` if `url` is not a valid proxy URL.
+    pub fn with_proxy(self, url: &str, no_proxy: Option<&str>) -> Result<Self, ConfigError> {
+        self.add_proxy(reqwest::Proxy::all(url), no_proxy)
+    }
+
+    /// Routes plain HTTP requests through the proxy at `url` (builder style).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidHttpOption` if `url` is not a valid proxy URL.
+    pub fn with_http_proxy(self, url: &str, no_proxy: Option<&str>) -> Result<Self, ConfigError> {
+        self.add_proxy(reqwest::Proxy::http(url), no_proxy)
+    }
+
+    /// Routes HTTPS requests through the proxy at `url` (builder style).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidHttpOption` if `url` is not a valid proxy URL.
+    pub fn with_https_proxy(self, url: &str, no_proxy: Option<&str>) -> Result<Self, ConfigError> {
+        self.add_proxy(reqwest::Proxy::https(url), no_proxy)
+    }
+
+    fn add_proxy(mut self, proxy: reqwest::Result<reqwest::Proxy>, no_proxy: Option<&str>) -> Result<Self, ConfigError> {
+        let proxy = proxy.map_err(|e| ConfigError::InvalidHttpOption(format!("invalid proxy: {}", e)))?;
+        self.proxies.push(proxy.no_proxy(no_proxy.and_then(reqwest::NoProxy::from_string)));
+        Ok(self)
+    }
+
+    /// Trusts an additional root certificate in PEM format (builder style).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidHttpOption` if the PEM cannot be parsed.
+    pub fn with_root_certificate_pem(mut self, pem: &[u8]) -> Result<Self, ConfigError> {
+        let certificate = reqwest::Certificate::from_pem(pem)
+            .map_err(|e| ConfigError::InvalidHttpOption(format!("invalid root certificate: {}", e)))?;
+        self.root_certificates.push(certificate);
+        Ok(self)
+    }
+
+    /// Trusts the root certificate(s) in the PEM file at `path` (builder style).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::InvalidHttpOption` if the file cannot be read or parsed.
+    pub fn with_root_certificate_file(self, path: impl AsRef<std::path::Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let pem = std::fs::read(path)
+            .map_err(|e| ConfigError::InvalidHttpOption(format!("failed to read {}: {}", path.display(), e)))?;
+        self.with_root_certificate_pem(&pem)
+    }
+
+    /// Sets the maximum number of idle connections kept per host (builder style).
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Sets how long idle pooled connections are kept open (builder style).
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables TCP keep-alive probes at the given interval (builder style).
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Builds a `reqwest::Client` with these options.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::ConfigError` if the TLS backend fails to initialize.
+    pub fn build_client(&self) -> Result<Client, ProviderError> {
+        let mut builder = Client::builder().timeout(self.timeout).tcp_keepalive(self.tcp_keepalive);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        for proxy in &self.proxies {
+            builder = builder.proxy(proxy.clone());
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        builder
+            .build()
+            .map_err(|e| ProviderError::ConfigError(format!("Failed to build HTTP client: {}", e)))
+    }
+}
+
+#[cfg(feature = "http-transport")]
+/// The default transport, backed by a `reqwest::Client`.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: Client,
+}
+
+#[cfg(feature = "http-transport")]
+impl HttpTransport {
+    /// Creates a transport with the default request timeout.
+    /// Panics if the HTTP client fails to build.
+    pub fn new() -> Self {
+        Self::from_config(&HttpClientConfig::default()).expect("Failed to build Reqwest client")
+    }
+
+    /// Creates a transport whose client is built from `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ProviderError::ConfigError` if the client fails to build.
+    pub fn from_config(config: &HttpClientConfig) -> Result<Self, ProviderError> {
+        Ok(Self { client: config.build_client()? })
+    }
+
+    /// Creates a transport that uses the given pre-built client.
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "http-transport")]
+impl Default for HttpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "http-transport")]
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, ProviderError> {
+        let method = reqwest::Method::from_bytes(request.method.as_bytes())
+            .map_err(|e| ProviderError::TransportError(format!("Invalid HTTP method: {}", e)))?;
+        let res = self
+            .client
+            .request(method, &request.url)
+            .headers(request.headers)
+            .body(request.body)
+            .send()
+            .await?;
+
+        Ok(TransportResponse {
+            status: res.status().as_u16(),
+            headers: res.headers().clone(),
+            body: Box::pin(res.bytes_stream().map_err(ProviderError::RequestError)),
+        })
+    }
+}
+
+/// The transport used when none is configured: `HttpTransport`, or with the
+/// `http-transport` feature disabled, one that fails every request.
+pub(crate) fn default_transport() -> std::sync::Arc<dyn Transport> {
+    #[cfg(feature = "http-transport")]
+    let transport = HttpTransport::new();
+    #[cfg(not(feature = "http-transport"))]
+    let transport = NoTransport;
+    std::sync::Arc::new(transport)
+}
+
+/// Fails every request; see `default_transport`.
+#[cfg(not(feature = "http-transport"))]
+#[derive(Debug)]
+struct NoTransport;
+
+#[cfg(not(feature = "http-transport"))]
+#[async_trait]
+impl Transport for NoTransport {
+    async fn send(&self, _request: TransportRequest) -> Result<TransportResponse, ProviderError> {
+        Err(ProviderError::ConfigError(
+            "No transport configured: enable the `http-transport` feature or set one with `with_transport`".to_string(),
+        ))
+    }
+}
+
+/// A transport that sends HTTP/1.1 requests over a unix domain socket.
+///
+/// The path and query of each request URL are used as the request target; the host
+/// part is only used for the `Host` header (e.g. `http://localhost/v1`).
+#[cfg(all(unix, feature = "unix-socket"))]
+#[derive(Debug, Clone)]
+pub struct UnixSocketTransport {
+    socket_path: std::path::PathBuf,
+    timeout: Duration,
+}
+
+#[cfg(all(unix, feature = "unix-socket"))]
+impl UnixSocketTransport {
+    /// Creates a transport connecting to the socket at `socket_path`.
+    pub fn new(socket_path: impl Into<std::path::PathBuf>) -> Self {
+        Self { socket_path: socket_path.into(), timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS) }
+    }
+
+    /// Sets the timeout for connecting and receiving response headers.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[cfg(all(unix, feature = "unix-socket"))]
+#[async_trait]
+impl Transport for UnixSocketTransport {
+    async fn send(&self, request: TransportRequest) -> Result<TransportResponse, ProviderError> {
+        let transport_err = |e: &dyn fmt::Display| ProviderError::TransportError(e.to_string());
+
+        let uri: hyper::Uri = request.url.parse().map_err(|e| transport_err(&e))?;
+        let target = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/").to_string();
+        let host = uri.authority().map(|a| a.as_str()).unwrap_or("localhost").to_string();
+
+        let mut builder = hyper::Request::builder()
+            .method(request.method.as_str())
+            .uri(target)
+            .header(hyper::header::HOST, host);
+        if let Some(headers) = builder.headers_mut() {
+            headers.extend(request.headers);
+        }
+        let http_request = builder.body(hyper::Body::from(request.body)).map_err(|e| transport_err(&e))?;
+
+        let exchange = async {
+            let stream = tokio::net::UnixStream::connect(&self.socket_path)
+                .await
+                .map_err(|e| transport_err(&format!("Failed to connect to {}: {}", self.socket_path.display(), e)))?;
+            let (mut sender, connection) = hyper::client::conn::handshake(stream).await.map_err(|e| transport_err(&e))?;
+            // Drive the connection in the background until the body is fully consumed
+            tokio::spawn(async move {
+                let _ = connection.await;
+            });
+            sender.send_request(http_request).await.map_err(|e| transport_err(&e))
+        };
+        let res = tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| ProviderError::TransportError("Unix socket request timed out".to_string()))??;
+
+        let status = res.status().as_u16();
+        let headers = res.headers().clone();
+        let body = res.into_body().map_err(|e| ProviderError::TransportError(e.to_string()));
+        Ok(TransportResponse { status, headers, body: Box::pin(body) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "http-transport")]
+    #[test]
+    fn test_http_client_config_validates_options() {
+        let config = HttpClientConfig::new()
+            .with_proxy("http://proxy.internal:3128", Some("localhost,.corp.example"))
+            .unwrap()
+            .with_pool_max_idle_per_host(4)
+            .with_tcp_keepalive(Duration::from_secs(30));
+        assert!(HttpTransport::from_config(&config).is_ok());
+
+        assert!(HttpClientConfig::new().with_root_certificate_pem(b"not a certificate").is_err());
+    }
+
+    #[cfg(not(feature = "http-transport"))]
+    #[tokio::test]
+    async fn test_default_transport_fails_without_http() {
+        let request = TransportRequest {
+            method: "GET".to_string(),
+            url: "http://localhost/".to_string(),
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        };
+        let error = default_transport().send(request).await.unwrap_err();
+        assert!(error.to_string().contains("http-transport"), "{}", error);
+    }
+
+    #[cfg(all(unix, feature = "unix-socket"))]
+    #[tokio::test]
+    async fn test_unix_socket_transport_round_trip() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let socket_path = std::env::temp_dir().join(format!("merco-transport-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !received.ends_with(b"{}") {
+                let n = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await.unwrap();
+            String::from_utf8(received).unwrap()
+        });
+
+        let transport = UnixSocketTransport::new(&socket_path);
+        let response = transport
+            .send(TransportRequest {
+                method: "POST".to_string(),
+                url: "http://localhost/v1/chat/completions".to_string(),
+                headers: HeaderMap::new(),
+                body: b"{}".to_vec(),
+            })
+            .await
+            .unwrap();
+
+        assert!(response.is_success());
+        assert_eq!(response.text().await.unwrap(), "ok");
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions HTTP/1.1\r\n"));
+        let _ = std::fs::remove_file(&socket_path);
+    }
+}
@@ -0,0 +1,272 @@
+//!
+//! Typed Completions
+//!
+//! `completion_typed` asks the model for a value of a Rust type instead of free text.
+//! The JSON Schema derived from the type (via `schemars`) is sent either as the response
+//! format or as the parameters of a tool the model is forced to call; the reply is then
+//! deserialized into the type. If deserialization fails, the error is shown to the model
+//! and the request is retried. Available with the `schema` feature.
+
+use crate::traits::{
+    ChatMessage, CompletionKind, CompletionRequest, JsonSchema, LlmProvider, ProviderError, ResponseFormat, Tool,
+    ToolChoice,
+};
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema as DeriveJsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value as JsonValue};
+
+/// Name of the tool used by `TypedStrategy::ToolCall`.
+const RESULT_TOOL: &str = "return_result";
+
+/// How the schema is conveyed to the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypedStrategy {
+    /// `ResponseFormat::JsonSchema`; best for providers with native structured output.
+    #[default]
+    ResponseFormat,
+    /// A single tool with the schema as parameters, and a tool choice forcing it. Works
+    /// with providers that support tools but not JSON schemas.
+    ToolCall,
+}
+
+/// Options for `completion_typed_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypedOptions {
+    /// How the schema is conveyed to the model.
+    pub strategy: TypedStrategy,
+    /// Additional attempts after a reply that does not deserialize.
+    pub max_retries: u32,
+}
+
+impl Default for TypedOptions {
+    fn default() -> Self {
+        Self { strategy: TypedStrategy::ResponseFormat, max_retries: 2 }
+    }
+}
+
+impl TypedOptions {
+    /// Uses `strategy` to convey the schema (builder style).
+    pub fn with_strategy(mut self, strategy: TypedStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets how many times a malformed reply is retried (builder style).
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+}
+
+/// Returns the JSON Schema of `T` with all definitions inlined, since tool parameters
+/// and several providers do not resolve `$ref`.
+pub fn schema_for<T: DeriveJsonSchema>() -> JsonValue {
+    let settings = SchemaSettings::draft07().with(|s| {
+        s.inline_subschemas = true;
+        s.meta_schema = None;
+    });
+    let schema = settings.into_generator().into_root_schema_for::<T>();
+    serde_json::to_value(schema).unwrap_or_else(|_| json!({}))
+}
+
+/// Tool parameters derived from the argument type `T`, which must be a struct (or map).
+/// Doc comments on its fields become parameter descriptions.
+pub fn parameters_for<T: DeriveJsonSchema>() -> JsonSchema {
+    tool_parameters(&schema_for::<T>()).0
+}
+
+/// A schema name accepted by every provider (`[a-zA-Z0-9_-]`, at most 64 characters).
+fn schema_name<T: DeriveJsonSchema>() -> String {
+    let name: String = T::schema_name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .take(64)
+        .collect();
+    if name.is_empty() { "result".to_string() } else { name }
+}
+
+/// Tool parameters must be an object, so other types are wrapped as `{"value": ...}`.
+fn tool_parameters(schema: &JsonValue) -> (JsonSchema, bool) {
+    if schema.get("type").and_then(JsonValue::as_str) == Some("object") {
+        let properties = schema.get("properties").and_then(JsonValue::as_object).cloned();
+        let required = schema.get("required").and_then(|r| serde_json::from_value(r.clone()).ok());
+        return (JsonSchema { schema_type: "object".to_string(), properties, required }, false);
+    }
+    let mut properties = serde_json::Map::new();
+    properties.insert("value".to_string(), schema.clone());
+    (JsonSchema { schema_type: "object".to_string(), properties: Some(properties), required: Some(vec!["value".to_string()]) }, true)
+}
+
+/// Strips a Markdown code fence some models put around JSON.
+fn strip_code_fence(text: &str) -> &str {
+    let trimmed = text.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let body = rest.split_once('\n').map_or("", |(_, body)| body);
+            body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+        }
+        None => trimmed,
+    }
+}
+
+/// Requests a value of type `T` using the default options.
+///
+/// # Errors
+///
+/// Returns the provider error, or `ProviderError::ParseError` if no reply deserialized
+/// into `T` within the allowed retries.
+pub async fn completion_typed<T>(provider: &dyn LlmProvider, request: CompletionRequest) -> Result<T, ProviderError>
+where
+    T: DeserializeOwned + DeriveJsonSchema,
+{
+    completion_typed_with(provider, request, TypedOptions::default()).await
+}
+
+/// Requests a value of type `T`, retrying with the parse error as feedback.
+///
+/// Any `response_format`, `tools` or `tool_choice` on `request` are replaced.
+///
+/// # Errors
+///
+/// Returns the provider error, or `ProviderError::ParseError` if no reply deserialized
+/// into `T` within the allowed retries.
+pub async fn completion_typed_with<T>(
+    provider: &dyn LlmProvider,
+    mut request: CompletionRequest,
+    options: TypedOptions,
+) -> Result<T, ProviderError>
+where
+    T: DeserializeOwned + DeriveJsonSchema,
+{
+    let schema = schema_for::<T>();
+    let name = schema_name::<T>();
+    let mut wrapped = false;
+    match options.strategy {
+        TypedStrategy::ResponseFormat => {
+            request.response_format = Some(ResponseFormat::JsonSchema { name, schema, strict: None });
+            request.tools = None;
+            request.tool_choice = None;
+        }
+        TypedStrategy::ToolCall => {
+            let (parameters, is_wrapped) = tool_parameters(&schema);
+            wrapped = is_wrapped;
+            request.tools = Some(vec![Tool {
+                name: RESULT_TOOL.to_string(),
+                description: format!("Return the final result as a `{}`.", name),
+                parameters,
+            }]);
+            request.tool_choice = Some(ToolChoice::Function(RESULT_TOOL.to_string()));
+            request.response_format = None;
+        }
+    }
+
+    let mut attempt = 0;
+    loop {
+        let response = provider.completion(request.clone()).await?;
+        let (raw, feedback_message) = match response.kind {
+            CompletionKind::Message { content } => {
+                let assistant = ChatMessage::assistant(Some(content.clone()), None);
+                (strip_code_fence(&content).to_string(), (assistant, None))
+            }
+            CompletionKind::ToolCall { tool_calls } => {
+                let call = tool_calls.iter().find(|c| c.function.name == RESULT_TOOL).or(tool_calls.first());
+                let raw = call.map(|c| c.function.arguments.clone()).unwrap_or_default();
+                let id = call.map(|c| c.id.clone());
+                (raw, (ChatMessage::assistant(None, Some(tool_calls)), id))
+            }
+        };
+
+        let parsed = serde_json::from_str::<JsonValue>(&raw).and_then(|value| {
+            let value = match (wrapped, value) {
+                (true, JsonValue::Object(mut map)) if map.contains_key("value") => map.remove("value").unwrap_or_default(),
+                (_, value) => value,
+            };
+            serde_json::from_value::<T>(value)
+        });
+        let error = match parsed {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt >= options.max_retries => return Err(ProviderError::ParseError(e)),
+            Err(e) => e,
+        };
+
+        // Show the model its reply and why it was rejected, then try again
+        attempt += 1;
+        let feedback = format!("The result does not match the required schema: {}. Reply again with corrected JSON.", error);
+        let (assistant, tool_call_id) = feedback_message;
+        request.messages.push(assistant);
+        request.messages.push(match tool_call_id {
+            Some(id) => ChatMessage::tool_result(id, feedback),
+            None => ChatMessage::user(feedback),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize, DeriveJsonSchema)]
+    struct Weather {
+        city: String,
+        celsius: f32,
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "test-model".to_string(),
+            messages: vec![ChatMessage::user("Weather in Oslo?".to_string())],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sends_schema_and_parses_reply() {
+        let provider = MockProvider::new().with_message("```json\n{\"city\": \"Oslo\", \"celsius\": -3.5}\n```");
+        let weather: Weather = completion_typed(&provider, request()).await.unwrap();
+
+        assert_eq!(weather, Weather { city: "Oslo".to_string(), celsius: -3.5 });
+        let Some(ResponseFormat::JsonSchema { name, schema, .. }) = &provider.requests()[0].response_format else {
+            panic!("expected a JSON schema response format");
+        };
+        assert_eq!(name, "Weather");
+        assert_eq!(schema["required"], json!(["celsius", "city"]));
+    }
+
+    #[tokio::test]
+    async fn test_retries_with_feedback() {
+        let provider = MockProvider::new()
+            .with_message("{\"city\": \"Oslo\"}")
+            .with_message("{\"city\": \"Oslo\", \"celsius\": 2}");
+        let weather: Weather = completion_typed(&provider, request()).await.unwrap();
+
+        assert_eq!(weather.celsius, 2.0);
+        let retry = &provider.requests()[1];
+        assert_eq!(retry.messages.len(), 3);
+        assert!(retry.messages[2].content.as_deref().unwrap().contains("missing field `celsius`"));
+    }
+
+    #[tokio::test]
+    async fn test_forced_tool_call_with_wrapped_value() {
+        let provider = MockProvider::new().with_tool_call(RESULT_TOOL, "{\"value\": [1, 2, 3]}");
+        let options = TypedOptions::default().with_strategy(TypedStrategy::ToolCall);
+        let numbers: Vec<u32> = completion_typed_with(&provider, request(), options).await.unwrap();
+
+        assert_eq!(numbers, vec![1, 2, 3]);
+        let sent = &provider.requests()[0];
+        assert_eq!(sent.tool_choice, Some(ToolChoice::Function(RESULT_TOOL.to_string())));
+        assert_eq!(sent.tools.as_ref().unwrap()[0].parameters.required, Some(vec!["value".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_retries() {
+        let provider = MockProvider::new().with_message("not json").with_message("still not json");
+        let options = TypedOptions::default().with_max_retries(1);
+        let result = completion_typed_with::<Weather>(&provider, request(), options).await;
+
+        assert!(matches!(result, Err(ProviderError::ParseError(_))));
+        assert_eq!(provider.call_count(), 2);
+    }
+}
@@ -0,0 +1,278 @@
+// Python bindings. Agents and crews are kept as plain settings on the Python side and
+// built on every run, so Python objects can be reused and shared freely; calls release
+// the GIL and run on a shared tokio runtime, returning asyncio awaitables.
+
+use merco_agents::agent::agent::{Agent, AgentLLMConfig};
+use merco_agents::agent::sampling::SamplingParams;
+use merco_agents::crew::crew::Crew;
+use merco_agents::definition::definition::from_json;
+use merco_agents::task::task::{JsonFieldType, JsonSchema, Task};
+use merco_llmproxy::{ChatMessage, CompletionKind, CompletionRequest, Provider, get_provider, get_tools_by_names};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3_async_runtimes::tokio::future_into_py;
+
+fn runtime_error(error: impl std::fmt::Display) -> PyErr {
+    PyRuntimeError::new_err(error.to_string())
+}
+
+fn parse_provider(name: &str) -> PyResult<Provider> {
+    match name.to_lowercase().as_str() {
+        "openai" => Ok(Provider::OpenAI),
+        "ollama" => Ok(Provider::Ollama),
+        "anthropic" => Ok(Provider::Anthropic),
+        "custom" => Ok(Provider::Custom),
+        other => Err(PyValueError::new_err(format!("Unknown provider '{}'", other))),
+    }
+}
+
+// "string", "number", "boolean", "object" or "array[<type>]"
+fn parse_field_type(name: &str) -> PyResult<JsonFieldType> {
+    let name = name.trim().to_lowercase();
+    if let Some(inner) = name.strip_prefix("array[").and_then(|rest| rest.strip_suffix(']')) {
+        return Ok(JsonFieldType::Array(Box::new(parse_field_type(inner)?)));
+    }
+    match name.as_str() {
+        "string" => Ok(JsonFieldType::String),
+        "number" => Ok(JsonFieldType::Number),
+        "boolean" => Ok(JsonFieldType::Boolean),
+        "object" => Ok(JsonFieldType::Object(JsonSchema::default())), // Free-form object
+        "array" => Ok(JsonFieldType::Array(Box::new(JsonFieldType::String))),
+        other => Err(PyValueError::new_err(format!("Unknown field type '{}'", other))),
+    }
+}
+
+#[pyclass(name = "LlmConfig", module = "merco")]
+#[derive(Clone)]
+struct PyLlmConfig {
+    inner: merco_llmproxy::LlmConfig,
+}
+
+#[pymethods]
+impl PyLlmConfig {
+    #[new]
+    #[pyo3(signature = (provider, api_key=None, base_url=None, headers=None))]
+    fn new(
+        provider: &str,
+        api_key: Option<String>,
+        base_url: Option<String>,
+        headers: Option<Vec<(String, String)>>,
+    ) -> PyResult<Self> {
+        let mut inner = merco_llmproxy::LlmConfig::new(parse_provider(provider)?);
+        if let Some(api_key) = api_key {
+            inner = inner.with_api_key(api_key);
+        }
+        if let Some(base_url) = base_url {
+            inner = inner.with_base_url(base_url);
+        }
+        for (name, value) in headers.unwrap_or_default() {
+            inner = inner.with_header(name, value);
+        }
+        inner.validate().map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    // Plain completion through the proxy, without an agent
+    #[pyo3(signature = (model, prompt, system=None, temperature=None, max_tokens=None))]
+    fn complete<'py>(
+        &self,
+        py: Python<'py>,
+        model: String,
+        prompt: String,
+        system: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let provider = get_provider(self.inner.clone()).map_err(runtime_error)?;
+        let messages = system.map(ChatMessage::system).into_iter().chain([ChatMessage::user(prompt)]).collect();
+        let request = CompletionRequest { model, messages, temperature, max_tokens, ..Default::default() };
+        future_into_py(py, async move {
+            match provider.completion(request).await.map_err(runtime_error)?.kind {
+                CompletionKind::Message { content } => Ok(content),
+                CompletionKind::ToolCall { .. } => Err(runtime_error("Model replied with a tool call")),
+            }
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("LlmConfig(provider={:?}, base_url={:?})", self.inner.provider, self.inner.base_url)
+    }
+}
+
+#[pyclass(name = "Task", module = "merco")]
+#[derive(Clone)]
+struct PyTask {
+    inner: Task,
+}
+
+#[pymethods]
+impl PyTask {
+    // `json_fields` maps required field names to types and switches the task to JSON output
+    #[new]
+    #[pyo3(signature = (description, expected_output=None, json_fields=None, strict=true))]
+    fn new(
+        description: String,
+        expected_output: Option<String>,
+        json_fields: Option<Vec<(String, String)>>,
+        strict: bool,
+    ) -> PyResult<Self> {
+        let inner = match json_fields {
+            Some(fields) => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(name, field_type)| Ok((name, parse_field_type(&field_type)?)))
+                    .collect::<PyResult<Vec<_>>>()?;
+                Task::new_simple_json(description, expected_output, fields, strict)
+            }
+            None => Task::new(description, expected_output),
+        };
+        Ok(Self { inner })
+    }
+
+    // Load a task definition document (see merco_agents::definition)
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        let inner = from_json::<Task>(json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        merco_agents::definition::definition::to_canonical_json(&self.inner).map_err(runtime_error)
+    }
+
+    #[getter]
+    fn description(&self) -> String {
+        self.inner.description.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Task(description={:?})", self.inner.description)
+    }
+}
+
+#[pyclass(name = "Agent", module = "merco")]
+#[derive(Clone)]
+struct PyAgent {
+    config: merco_llmproxy::LlmConfig,
+    model: String,
+    sampling: SamplingParams,
+    backstory: String,
+    goals: Vec<String>,
+    tools: Vec<String>,
+}
+
+impl PyAgent {
+    fn build(&self) -> PyResult<Agent> {
+        // Agent::new panics on an unusable config, so check it first
+        get_provider(self.config.clone()).map_err(runtime_error)?;
+        let names: Vec<&str> = self.tools.iter().map(String::as_str).collect();
+        let tools = get_tools_by_names(&names);
+        if tools.len() != names.len() {
+            return Err(PyValueError::new_err(format!("Unknown tools among {:?}", self.tools)));
+        }
+        let llm_config = AgentLLMConfig::with_defaults(self.config.clone(), self.model.clone(), self.sampling.clone());
+        Ok(Agent::new(llm_config, self.backstory.clone(), self.goals.clone(), tools))
+    }
+}
+
+#[pymethods]
+impl PyAgent {
+    // `tools` are names of tools registered in Rust with `#[merco_tool]`
+    #[new]
+    #[pyo3(signature = (llm, model, backstory, goals=Vec::new(), tools=Vec::new(), temperature=None, max_tokens=None))]
+    fn new(
+        llm: PyLlmConfig,
+        model: String,
+        backstory: String,
+        goals: Vec<String>,
+        tools: Vec<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Self {
+        let sampling = SamplingParams { temperature, max_tokens, ..Default::default() };
+        Self { config: llm.inner, model, sampling, backstory, goals, tools }
+    }
+
+    // Run one task; returns an awaitable resolving to the output text
+    fn call<'py>(&self, py: Python<'py>, task: PyTask) -> PyResult<Bound<'py, PyAny>> {
+        let agent = self.build()?;
+        future_into_py(py, async move { agent.call(task.inner).await.map(|output| output.text).map_err(runtime_error) })
+    }
+
+    // Blocking variant of `call` for scripts without an event loop
+    fn call_blocking(&self, py: Python<'_>, task: PyTask) -> PyResult<String> {
+        let agent = self.build()?;
+        py.allow_threads(|| {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(agent.call(task.inner)).map(|output| output.text).map_err(runtime_error)
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Agent(model={:?}, tools={:?})", self.model, self.tools)
+    }
+}
+
+#[pyclass(name = "CrewOutput", module = "merco", get_all)]
+struct PyCrewOutput {
+    task_outputs: Vec<String>,
+    final_output: String,
+    total_tokens: u32,
+    cost: Option<f64>,
+}
+
+#[pyclass(name = "Crew", module = "merco")]
+struct PyCrew {
+    agents: Vec<PyAgent>,
+    tasks: Vec<(usize, Task)>,
+}
+
+impl PyCrew {
+    fn build(&self) -> PyResult<Crew> {
+        let agents = self.agents.iter().map(PyAgent::build).collect::<PyResult<Vec<_>>>()?;
+        Ok(self
+            .tasks
+            .iter()
+            .fold(Crew::new(agents), |crew, (agent, task)| crew.with_task(*agent, task.clone())))
+    }
+}
+
+#[pymethods]
+impl PyCrew {
+    #[new]
+    fn new(agents: Vec<PyAgent>) -> Self {
+        Self { agents, tasks: Vec::new() }
+    }
+
+    // Assign `task` to the agent at index `agent`; tasks run in the order they are added
+    fn add_task(&mut self, agent: usize, task: PyTask) -> PyResult<()> {
+        if agent >= self.agents.len() {
+            return Err(PyValueError::new_err(format!("No agent at index {}", agent)));
+        }
+        self.tasks.push((agent, task.inner));
+        Ok(())
+    }
+
+    // Run all tasks; returns an awaitable resolving to a `CrewOutput`
+    fn run<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let crew = self.build()?;
+        future_into_py(py, async move {
+            let output = crew.run().await.map_err(runtime_error)?;
+            Ok(PyCrewOutput {
+                task_outputs: output.task_outputs,
+                final_output: output.final_output,
+                total_tokens: output.usage.total_tokens,
+                cost: output.cost,
+            })
+        })
+    }
+}
+
+#[pymodule]
+fn merco(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyLlmConfig>()?;
+    m.add_class::<PyTask>()?;
+    m.add_class::<PyAgent>()?;
+    m.add_class::<PyCrew>()?;
+    m.add_class::<PyCrewOutput>()?;
+    Ok(())
+}